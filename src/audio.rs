@@ -7,15 +7,17 @@
 //!
 //! The audio file decoding support is an abstraction over various libraries
 //! (currently [caf], [hound], and dr_mp3), usage of which should be confined to
-//! this module.
+//! this module. There is also a small MIDI synthesizer, see [MidiSequence].
 //!
 //! Resources:
 //! - [Apple Core Audio Format Specification 1.0](https://developer.apple.com/library/archive/documentation/MusicAudio/Reference/CAFSpec/CAF_intro/CAF_intro.html)
 
 mod aac;
 mod ima4;
+mod midi;
 
 pub use ima4::decode_ima4;
+pub use midi::{MidiEventKind, MidiSequence};
 use touchHLE_dr_mp3_wrapper as dr_mp3;
 pub use touchHLE_openal_soft_wrapper as openal;
 
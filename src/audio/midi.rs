@@ -0,0 +1,610 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Standard MIDI File parsing and a very small software synthesizer.
+//!
+//! touchHLE can't bundle Apple's DLS sound bank, so instead of sample-based
+//! instruments, each General MIDI instrument family is approximated with a
+//! simple waveform and envelope. This won't sound like the real thing, but it
+//! gets the melody across.
+//!
+//! Resources:
+//! - The MIDI Association's [Standard MIDI Files (SMF) Specification](https://www.midi.org/specifications/file-format-specifications/standard-midi-files)
+//! - [General MIDI Level 1 Sound Set](https://www.midi.org/specifications-old/item/gm-level-1-sound-set)
+
+/// Tempo used if the file doesn't specify one: 120 beats per minute.
+const DEFAULT_MICROSECONDS_PER_QUARTER: u32 = 500_000;
+
+/// The General MIDI percussion channel (channel 10, counting from 1).
+const PERCUSSION_CHANNEL: u8 = 9;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MidiEventKind {
+    NoteOff {
+        channel: u8,
+        key: u8,
+    },
+    NoteOn {
+        channel: u8,
+        key: u8,
+        velocity: u8,
+    },
+    ControlChange {
+        channel: u8,
+        controller: u8,
+        value: u8,
+    },
+    ProgramChange {
+        channel: u8,
+        program: u8,
+    },
+    /// Microseconds per quarter note.
+    Tempo(u32),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MidiEvent {
+    /// Absolute time in ticks.
+    pub tick: u64,
+    pub kind: MidiEventKind,
+}
+
+#[derive(Debug)]
+pub struct MidiSequence {
+    pub ticks_per_quarter: u16,
+    pub tracks: Vec<Vec<MidiEvent>>,
+    /// Tempo changes from all tracks, sorted by time: (tick, microseconds per
+    /// quarter note).
+    tempo_map: Vec<(u64, u32)>,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], ()> {
+        let end = self.pos.checked_add(count).ok_or(())?;
+        let slice = self.bytes.get(self.pos..end).ok_or(())?;
+        self.pos = end;
+        Ok(slice)
+    }
+    fn u8(&mut self) -> Result<u8, ()> {
+        Ok(self.take(1)?[0])
+    }
+    fn u16(&mut self) -> Result<u16, ()> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+    fn u32(&mut self) -> Result<u32, ()> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+    /// Variable-length quantity.
+    fn vlq(&mut self) -> Result<u32, ()> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let byte = self.u8()?;
+            value = (value << 7) | u32::from(byte & 0x7f);
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(())
+    }
+}
+
+impl MidiSequence {
+    /// Parse a Standard MIDI File (format 0 or 1).
+    pub fn parse(bytes: &[u8]) -> Result<Self, ()> {
+        let mut reader = Reader { bytes, pos: 0 };
+
+        if reader.take(4)? != b"MThd" {
+            return Err(());
+        }
+        let header_len = reader.u32()? as usize;
+        let mut header = Reader {
+            bytes: reader.take(header_len)?,
+            pos: 0,
+        };
+        let format = header.u16()?;
+        let track_count = header.u16()?;
+        let division = header.u16()?;
+        if format > 1 {
+            log!("Warning: MIDI file format {} is not supported", format);
+            return Err(());
+        }
+        if division & 0x8000 != 0 || division == 0 {
+            log!("Warning: SMPTE-based MIDI timing is not supported");
+            return Err(());
+        }
+
+        let mut tracks = Vec::with_capacity(track_count.into());
+        while tracks.len() < usize::from(track_count) {
+            let chunk_type = reader.take(4)?;
+            let chunk_len = reader.u32()? as usize;
+            let chunk = reader.take(chunk_len)?;
+            // Unknown chunk types must be ignored.
+            if chunk_type == b"MTrk" {
+                tracks.push(Self::parse_track(chunk)?);
+            }
+        }
+
+        let mut tempo_map: Vec<(u64, u32)> = tracks
+            .iter()
+            .flatten()
+            .filter_map(|event| match event.kind {
+                MidiEventKind::Tempo(tempo) => Some((event.tick, tempo)),
+                _ => None,
+            })
+            .collect();
+        tempo_map.sort_by_key(|&(tick, _)| tick);
+
+        Ok(MidiSequence {
+            ticks_per_quarter: division,
+            tracks,
+            tempo_map,
+        })
+    }
+
+    fn parse_track(bytes: &[u8]) -> Result<Vec<MidiEvent>, ()> {
+        let mut reader = Reader { bytes, pos: 0 };
+        let mut events = Vec::new();
+        let mut tick = 0u64;
+        let mut running_status = None;
+
+        while reader.pos < bytes.len() {
+            tick += u64::from(reader.vlq()?);
+
+            let mut status = reader.u8()?;
+            let first_data_byte = if status & 0x80 == 0 {
+                // Running status: this is actually the first data byte.
+                let data = status;
+                status = running_status.ok_or(())?;
+                Some(data)
+            } else {
+                None
+            };
+
+            match status {
+                0xFF => {
+                    // Meta event. These cancel running status.
+                    running_status = None;
+                    let meta_type = reader.u8()?;
+                    let len = reader.vlq()? as usize;
+                    let data = reader.take(len)?;
+                    match meta_type {
+                        // End of track
+                        0x2F => break,
+                        // Set tempo
+                        0x51 if len == 3 => {
+                            let tempo = u32::from_be_bytes([0, data[0], data[1], data[2]]);
+                            events.push(MidiEvent {
+                                tick,
+                                kind: MidiEventKind::Tempo(tempo),
+                            });
+                        }
+                        _ => (),
+                    }
+                }
+                0xF0 | 0xF7 => {
+                    // System exclusive event, ignored.
+                    running_status = None;
+                    let len = reader.vlq()? as usize;
+                    reader.take(len)?;
+                }
+                0x80..=0xEF => {
+                    running_status = Some(status);
+                    let channel = status & 0x0f;
+                    let data_len = match status & 0xf0 {
+                        0xC0 | 0xD0 => 1,
+                        _ => 2,
+                    };
+                    let data1 = match first_data_byte {
+                        Some(data) => data,
+                        None => reader.u8()?,
+                    };
+                    let data2 = if data_len == 2 { reader.u8()? } else { 0 };
+                    let kind = match status & 0xf0 {
+                        0x80 => MidiEventKind::NoteOff {
+                            channel,
+                            key: data1,
+                        },
+                        // A note-on with velocity 0 is a note-off.
+                        0x90 if data2 == 0 => MidiEventKind::NoteOff {
+                            channel,
+                            key: data1,
+                        },
+                        0x90 => MidiEventKind::NoteOn {
+                            channel,
+                            key: data1,
+                            velocity: data2,
+                        },
+                        0xB0 => MidiEventKind::ControlChange {
+                            channel,
+                            controller: data1,
+                            value: data2,
+                        },
+                        0xC0 => MidiEventKind::ProgramChange {
+                            channel,
+                            program: data1,
+                        },
+                        // Aftertouch and pitch bend are ignored.
+                        _ => continue,
+                    };
+                    events.push(MidiEvent { tick, kind });
+                }
+                _ => return Err(()),
+            }
+        }
+
+        Ok(events)
+    }
+
+    /// Length of the sequence in beats (quarter notes).
+    pub fn length_in_beats(&self) -> f64 {
+        let last_tick = self
+            .tracks
+            .iter()
+            .filter_map(|track| track.last())
+            .map(|event| event.tick)
+            .max()
+            .unwrap_or(0);
+        last_tick as f64 / f64::from(self.ticks_per_quarter)
+    }
+
+    /// Convert a time in beats (quarter notes) to seconds, using the tempo map.
+    pub fn beats_to_seconds(&self, beats: f64) -> f64 {
+        let target_tick = beats * f64::from(self.ticks_per_quarter);
+        let mut seconds = 0.0;
+        let mut last_tick = 0.0;
+        let mut tempo = DEFAULT_MICROSECONDS_PER_QUARTER;
+        for &(tick, new_tempo) in &self.tempo_map {
+            let tick = tick as f64;
+            if tick >= target_tick {
+                break;
+            }
+            seconds += self.ticks_to_seconds(tick - last_tick, tempo);
+            last_tick = tick;
+            tempo = new_tempo;
+        }
+        seconds + self.ticks_to_seconds(target_tick - last_tick, tempo)
+    }
+
+    /// Convert a time in seconds to beats (quarter notes), using the tempo map.
+    pub fn seconds_to_beats(&self, seconds: f64) -> f64 {
+        let mut elapsed = 0.0;
+        let mut last_tick = 0.0;
+        let mut tempo = DEFAULT_MICROSECONDS_PER_QUARTER;
+        for &(tick, new_tempo) in &self.tempo_map {
+            let tick = tick as f64;
+            let segment = self.ticks_to_seconds(tick - last_tick, tempo);
+            if elapsed + segment > seconds {
+                break;
+            }
+            elapsed += segment;
+            last_tick = tick;
+            tempo = new_tempo;
+        }
+        let seconds_per_tick = self.ticks_to_seconds(1.0, tempo);
+        (last_tick + (seconds - elapsed) / seconds_per_tick) / f64::from(self.ticks_per_quarter)
+    }
+
+    fn ticks_to_seconds(&self, ticks: f64, microseconds_per_quarter: u32) -> f64 {
+        ticks * f64::from(microseconds_per_quarter)
+            / 1_000_000.0
+            / f64::from(self.ticks_per_quarter)
+    }
+
+    /// Synthesize the whole sequence to mono 16-bit PCM.
+    pub fn synthesize(&self, sample_rate: u32) -> Vec<i16> {
+        let mut events: Vec<MidiEvent> = self.tracks.iter().flatten().copied().collect();
+        // This is a stable sort, so the order within a track is kept.
+        events.sort_by_key(|event| event.tick);
+
+        // Channel state is shared between tracks.
+        let mut programs = [0u8; 16];
+        let mut volumes = [100u8; 16];
+        let mut held_notes: Vec<(u8, u8, u64, u8)> = Vec::new(); // channel, key, tick, velocity
+
+        let mut notes = Vec::new();
+        for event in &events {
+            match event.kind {
+                MidiEventKind::NoteOn {
+                    channel,
+                    key,
+                    velocity,
+                } => held_notes.push((channel, key, event.tick, velocity)),
+                MidiEventKind::NoteOff { channel, key } => {
+                    let Some(idx) = held_notes
+                        .iter()
+                        .position(|&(c, k, _, _)| c == channel && k == key)
+                    else {
+                        continue;
+                    };
+                    let (_, _, start_tick, velocity) = held_notes.remove(idx);
+                    notes.push(Note {
+                        start: self.tick_to_seconds(start_tick),
+                        end: self.tick_to_seconds(event.tick),
+                        channel,
+                        key,
+                        velocity,
+                        program: programs[usize::from(channel)],
+                        volume: volumes[usize::from(channel)],
+                    });
+                }
+                MidiEventKind::ControlChange {
+                    channel,
+                    controller: 7, // channel volume
+                    value,
+                } => volumes[usize::from(channel)] = value,
+                MidiEventKind::ProgramChange { channel, program } => {
+                    programs[usize::from(channel)] = program
+                }
+                _ => (),
+            }
+        }
+
+        let end_seconds = self.beats_to_seconds(self.length_in_beats());
+        let sample_rate_f = f64::from(sample_rate);
+        let total_samples = (end_seconds * sample_rate_f).ceil() as usize;
+        let mut mix = vec![0f32; total_samples];
+        for note in &notes {
+            note.render(&mut mix, sample_rate_f);
+        }
+
+        // Soft clipping so that loud chords don't wrap around.
+        mix.iter()
+            .map(|&sample| (sample.tanh() * f32::from(i16::MAX)) as i16)
+            .collect()
+    }
+
+    fn tick_to_seconds(&self, tick: u64) -> f64 {
+        self.beats_to_seconds(tick as f64 / f64::from(self.ticks_per_quarter))
+    }
+}
+
+struct Note {
+    start: f64,
+    end: f64,
+    channel: u8,
+    key: u8,
+    velocity: u8,
+    program: u8,
+    volume: u8,
+}
+
+#[derive(Copy, Clone)]
+enum Waveform {
+    Sine,
+    Triangle,
+    Square,
+    Sawtooth,
+    Noise,
+}
+
+/// Parameters of a synthesized instrument. Times are in seconds.
+#[derive(Copy, Clone)]
+struct Instrument {
+    waveform: Waveform,
+    attack: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+    gain: f32,
+}
+
+const fn instrument(
+    waveform: Waveform,
+    attack: f32,
+    decay: f32,
+    sustain: f32,
+    release: f32,
+    gain: f32,
+) -> Instrument {
+    Instrument {
+        waveform,
+        attack,
+        decay,
+        sustain,
+        release,
+        gain,
+    }
+}
+
+/// Instruments for each of the 16 General MIDI program families (piano,
+/// chromatic percussion, organ, guitar, bass, etc).
+#[rustfmt::skip]
+const FAMILY_INSTRUMENTS: [Instrument; 16] = [
+    instrument(Waveform::Triangle, 0.005, 0.8,  0.2, 0.3,  1.0), // Piano
+    instrument(Waveform::Sine,     0.002, 0.5,  0.0, 0.4,  1.0), // Chromatic Percussion
+    instrument(Waveform::Square,   0.01,  0.1,  0.8, 0.05, 0.5), // Organ
+    instrument(Waveform::Triangle, 0.003, 0.6,  0.1, 0.2,  1.0), // Guitar
+    instrument(Waveform::Triangle, 0.005, 0.4,  0.5, 0.1,  1.2), // Bass
+    instrument(Waveform::Sawtooth, 0.08,  0.2,  0.8, 0.2,  0.5), // Strings
+    instrument(Waveform::Sawtooth, 0.1,   0.3,  0.7, 0.3,  0.4), // Ensemble
+    instrument(Waveform::Square,   0.03,  0.2,  0.7, 0.1,  0.5), // Brass
+    instrument(Waveform::Square,   0.04,  0.2,  0.7, 0.1,  0.4), // Reed
+    instrument(Waveform::Sine,     0.04,  0.1,  0.8, 0.1,  0.9), // Pipe
+    instrument(Waveform::Square,   0.01,  0.2,  0.6, 0.1,  0.4), // Synth Lead
+    instrument(Waveform::Sawtooth, 0.2,   0.5,  0.6, 0.5,  0.4), // Synth Pad
+    instrument(Waveform::Triangle, 0.05,  0.5,  0.4, 0.5,  0.7), // Synth Effects
+    instrument(Waveform::Triangle, 0.003, 0.7,  0.1, 0.2,  1.0), // Ethnic
+    instrument(Waveform::Sine,     0.001, 0.2,  0.0, 0.1,  1.0), // Percussive
+    instrument(Waveform::Noise,    0.05,  0.3,  0.5, 0.3,  0.3), // Sound Effects
+];
+
+impl Note {
+    fn render(&self, mix: &mut [f32], sample_rate: f64) {
+        let is_percussion = self.channel == PERCUSSION_CHANNEL;
+        let instrument = if is_percussion {
+            percussion_instrument(self.key)
+        } else {
+            FAMILY_INSTRUMENTS[usize::from(self.program / 8)]
+        };
+        let frequency = if is_percussion {
+            percussion_frequency(self.key)
+        } else {
+            440.0 * 2f64.powf((f64::from(self.key) - 69.0) / 12.0)
+        };
+        // Percussion notes are usually very short, so their length is
+        // determined by the sound instead.
+        let held_for = if is_percussion {
+            None
+        } else {
+            Some((self.end - self.start) as f32)
+        };
+        let amplitude = 0.25
+            * instrument.gain
+            * (f32::from(self.velocity) / 127.0)
+            * (f32::from(self.volume) / 127.0);
+
+        let start_sample = (self.start * sample_rate) as usize;
+        let duration = match held_for {
+            Some(held_for) => held_for + instrument.release,
+            None => instrument.attack + instrument.decay,
+        };
+        let length = (f64::from(duration) * sample_rate) as usize;
+        let mut noise_state = 0x12345678u32 ^ u32::from(self.key);
+        for i in 0..length {
+            let Some(out) = mix.get_mut(start_sample + i) else {
+                break;
+            };
+            let t = (i as f64 / sample_rate) as f32;
+            let phase = ((i as f64 * frequency / sample_rate) % 1.0) as f32;
+            let sample = match instrument.waveform {
+                Waveform::Sine => (phase * std::f32::consts::TAU).sin(),
+                Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+                Waveform::Square => {
+                    if phase < 0.5 {
+                        0.6
+                    } else {
+                        -0.6
+                    }
+                }
+                Waveform::Sawtooth => (2.0 * phase - 1.0) * 0.6,
+                Waveform::Noise => {
+                    // xorshift32
+                    noise_state ^= noise_state << 13;
+                    noise_state ^= noise_state >> 17;
+                    noise_state ^= noise_state << 5;
+                    (noise_state as f32 / u32::MAX as f32) * 2.0 - 1.0
+                }
+            };
+            *out += sample * amplitude * instrument.envelope(t, held_for);
+        }
+    }
+}
+
+impl Instrument {
+    /// Envelope level at time `t`. If `held_for` is [None], the sound decays
+    /// without being released.
+    fn envelope(&self, t: f32, held_for: Option<f32>) -> f32 {
+        let Instrument {
+            attack,
+            decay,
+            sustain,
+            release,
+            ..
+        } = *self;
+        let level_at = |t: f32| {
+            if t < attack {
+                t / attack
+            } else if t < attack + decay {
+                1.0 - (1.0 - sustain) * ((t - attack) / decay)
+            } else {
+                sustain
+            }
+        };
+        let Some(held_for) = held_for else {
+            return if t < attack {
+                t / attack
+            } else {
+                (1.0 - (t - attack) / decay).max(0.0)
+            };
+        };
+        if t < held_for {
+            level_at(t)
+        } else {
+            level_at(held_for) * (1.0 - (t - held_for) / release).max(0.0)
+        }
+    }
+}
+
+/// Percussion sounds for the General MIDI percussion key map.
+fn percussion_instrument(key: u8) -> Instrument {
+    match key {
+        // Bass drums
+        35 | 36 => instrument(Waveform::Sine, 0.001, 0.15, 0.0, 0.05, 3.0),
+        // Toms
+        41 | 43 | 45 | 47 | 48 | 50 => instrument(Waveform::Sine, 0.001, 0.25, 0.0, 0.05, 2.0),
+        // Cymbals and hi-hats
+        42 | 44 => instrument(Waveform::Noise, 0.001, 0.05, 0.0, 0.02, 0.6),
+        46 | 49 | 51 | 52 | 55 | 57 | 59 => instrument(Waveform::Noise, 0.001, 0.6, 0.0, 0.1, 0.5),
+        // Snares, claps and everything else
+        _ => instrument(Waveform::Noise, 0.001, 0.15, 0.0, 0.05, 0.9),
+    }
+}
+
+fn percussion_frequency(key: u8) -> f64 {
+    match key {
+        35 | 36 => 55.0,
+        41 | 43 | 45 | 47 | 48 | 50 => 80.0 + f64::from(key - 41) * 15.0,
+        _ => 0.0, // noise
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_simple_file() {
+        #[rustfmt::skip]
+        let bytes: &[u8] = &[
+            b'M', b'T', b'h', b'd', 0, 0, 0, 6,
+            0, 0, // format 0
+            0, 1, // one track
+            0, 96, // 96 ticks per quarter note
+            b'M', b'T', b'r', b'k', 0, 0, 0, 24,
+            0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20, // tempo: 500000us
+            0x00, 0xC0, 0x05, // program change
+            0x00, 0x90, 60, 100, // note on
+            0x60, 64, 100, // note on, running status
+            0x60, 60, 0, // note off (note on with velocity 0)
+            0x00, 0xFF, 0x2F, 0x00, // end of track
+        ];
+        let sequence = MidiSequence::parse(bytes).unwrap();
+        assert_eq!(sequence.ticks_per_quarter, 96);
+        assert_eq!(sequence.tracks.len(), 1);
+        let kinds: Vec<MidiEventKind> = sequence.tracks[0].iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                MidiEventKind::Tempo(500_000),
+                MidiEventKind::ProgramChange {
+                    channel: 0,
+                    program: 5
+                },
+                MidiEventKind::NoteOn {
+                    channel: 0,
+                    key: 60,
+                    velocity: 100
+                },
+                MidiEventKind::NoteOn {
+                    channel: 0,
+                    key: 64,
+                    velocity: 100
+                },
+                MidiEventKind::NoteOff {
+                    channel: 0,
+                    key: 60
+                },
+            ]
+        );
+        assert_eq!(sequence.tracks[0][4].tick, 192);
+        assert_eq!(sequence.length_in_beats(), 2.0);
+        assert_eq!(sequence.beats_to_seconds(2.0), 1.0);
+        assert_eq!(sequence.seconds_to_beats(1.0), 2.0);
+    }
+}
@@ -50,6 +50,9 @@ pub const ALC_TRUE: ALCboolean = 1;
 
 pub const ALC_DEVICE_SPECIFIER: ALCenum = 0x1005;
 
+pub const ALC_FREQUENCY: ALCenum = 0x1007;
+pub const ALC_REFRESH: ALCenum = 0x1008;
+
 extern "C" {
     pub fn alcOpenDevice(devicename: *const ALCchar) -> *mut ALCdevice;
     pub fn alcCloseDevice(device: *mut ALCdevice) -> ALCboolean;
@@ -93,6 +96,10 @@ use al_types::*;
 
 pub const AL_NO_ERROR: ALenum = 0;
 
+pub const AL_PITCH: ALenum = 0x1003;
+
+pub const AL_BUFFER: ALenum = 0x1009;
+
 pub const AL_MAX_GAIN: ALenum = 0x100E;
 
 pub const AL_SOURCE_STATE: ALenum = 0x1010;
@@ -105,6 +112,9 @@ pub const AL_STOPPED: ALenum = 0x1014;
 pub const AL_BUFFERS_QUEUED: ALenum = 0x1015;
 pub const AL_BUFFERS_PROCESSED: ALenum = 0x1016;
 
+pub const AL_SEC_OFFSET: ALenum = 0x1024;
+pub const AL_SAMPLE_OFFSET: ALenum = 0x1025;
+
 pub const AL_FORMAT_MONO8: ALenum = 0x1100;
 pub const AL_FORMAT_MONO16: ALenum = 0x1101;
 pub const AL_FORMAT_STEREO8: ALenum = 0x1102;
@@ -13,6 +13,7 @@
 
 use crate::fs::{BundleData, Fs, GuestPath, GuestPathBuf};
 use crate::image::Image;
+use crate::window::DeviceOrientation;
 use plist::dictionary::Dictionary;
 use plist::Value;
 use std::io::Cursor;
@@ -86,6 +87,15 @@ impl Bundle {
             .unwrap_or(&EMPTY_VAL)
     }
 
+    /// The language the app was developed in, which is used as the last resort
+    /// when looking for localized resources. This may be a language code
+    /// (e.g. `en`) or a legacy language name (e.g. `English`).
+    pub fn development_region(&self) -> Option<&str> {
+        self.plist
+            .get("CFBundleDevelopmentRegion")
+            .and_then(|v| v.as_string())
+    }
+
     /// Canonical name for the bundle according to Info.plist
     pub fn canonical_bundle_name(&self) -> Option<&str> {
         self.plist
@@ -109,19 +119,59 @@ impl Bundle {
             .map(|v| v.as_string().unwrap())
     }
 
+    /// Whether the app opts out of running in the background, i.e. it should
+    /// be terminated rather than suspended when the Home button is pressed.
+    pub fn exits_on_suspend(&self) -> bool {
+        self.plist
+            .get("UIApplicationExitsOnSuspend")
+            .and_then(|v| v.as_boolean())
+            .unwrap_or(false)
+    }
+
     pub fn executable_path(&self) -> GuestPathBuf {
         // FIXME: Is this key optional? All iPhone apps seem to have it.
         self.path
             .join(self.plist["CFBundleExecutable"].as_string().unwrap())
     }
 
-    pub fn launch_image_path(&self) -> GuestPathBuf {
-        if let Some(base_name) = self.plist.get("UILaunchImageFile") {
-            self.path
-                .join(format!("{}.png", base_name.as_string().unwrap()))
-        } else {
-            self.path.join("Default.png") // not guaranteed to exist!
+    /// Candidate paths for the launch image (`Default.png` or similar), most
+    /// specific first. None of them are guaranteed to exist!
+    pub fn launch_image_paths(
+        &self,
+        orientation: DeviceOrientation,
+        scale_hack: u32,
+    ) -> Vec<GuestPathBuf> {
+        let base_name = self
+            .plist
+            .get("UILaunchImageFile~iphone")
+            .or_else(|| self.plist.get("UILaunchImageFile"))
+            .map(|name| name.as_string().unwrap())
+            .unwrap_or("Default");
+        let base_name = base_name.strip_suffix(".png").unwrap_or(base_name);
+
+        // The names use interface orientations, which are the inverse of
+        // device orientations for landscape. Orientation-specific launch
+        // images are officially iPad-only, but some apps have them anyway.
+        let orientation_suffixes: &[&str] = match orientation {
+            DeviceOrientation::Portrait => &["-Portrait", ""],
+            DeviceOrientation::LandscapeLeft => &["-LandscapeRight", "-Landscape", ""],
+            DeviceOrientation::LandscapeRight => &["-LandscapeLeft", "-Landscape", ""],
+        };
+        // The scale hack is the closest thing we have to a Retina display.
+        let scale_suffixes: &[&str] = if scale_hack > 1 { &["@2x", ""] } else { &[""] };
+
+        let mut paths = Vec::new();
+        for orientation_suffix in orientation_suffixes {
+            for scale_suffix in scale_suffixes {
+                for device_suffix in ["~iphone", ""] {
+                    paths.push(self.path.join(format!(
+                        "{}{}{}{}.png",
+                        base_name, orientation_suffix, scale_suffix, device_suffix
+                    )));
+                }
+            }
         }
+        paths
     }
 
     fn icon_path(&self) -> GuestPathBuf {
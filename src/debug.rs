@@ -7,6 +7,7 @@
 //! debug hacks.
 #![allow(dead_code)]
 
+use crate::image::pixel_format;
 use std::fs::File;
 
 /// Dump RGB8 pixel data to a file in PPM format.
@@ -18,17 +19,6 @@ pub fn write_ppm(path: &str, width: u32, height: u32, pixels: &[u8]) {
     file.write_all(pixels).unwrap();
 }
 
-/// Convert RGBA8 pixel data to RGB8 pixel data by discarding alpha component.
-/// Useful with [write_ppm] for example.
-pub fn rgba8_to_rgb8(pixels: &[u8]) -> Vec<u8> {
-    assert!(pixels.len() % 4 == 0);
-    let mut res = Vec::with_capacity((pixels.len() / 4) * 3);
-    for rgba in pixels.chunks(4) {
-        res.extend_from_slice(&rgba[..3]);
-    }
-    res
-}
-
 /// Dump a region of the current OpenGL ES framebuffer to a file.
 pub fn dump_framebuffer(
     path: &str,
@@ -54,5 +44,9 @@ pub fn dump_framebuffer(
         );
         rgba8_pixels.set_len(rgba8_pixels.capacity());
     }
-    write_ppm(path, width, height, &rgba8_to_rgb8(&rgba8_pixels));
+    // OpenGL's row order is bottom-to-top, but PPM's is top-to-bottom.
+    pixel_format::flip_rows(&mut rgba8_pixels, width as usize * 4);
+    let mut rgb8_pixels = Vec::new();
+    pixel_format::rgba8_to_rgb8(&rgba8_pixels, &mut rgb8_pixels);
+    write_ppm(path, width, height, &rgb8_pixels);
 }
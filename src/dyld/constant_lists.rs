@@ -7,25 +7,36 @@
 //! very long and frequently-updated list.
 
 use crate::frameworks::{
-    core_foundation, core_graphics, foundation, media_player, opengles, uikit,
+    core_foundation, core_graphics, core_telephony, foundation, media_player, opengles, uikit,
 };
 use crate::libc;
 
 /// All the lists of constants that the linker should search through.
 pub const CONSTANT_LISTS: &[super::ConstantExports] = &[
     libc::ctype::CONSTANTS,
+    libc::mach_task::CONSTANTS,
     libc::stdio::CONSTANTS,
     core_foundation::cf_allocator::CONSTANTS,
     core_foundation::cf_run_loop::CONSTANTS,
     core_graphics::cg_affine_transform::CONSTANTS,
     core_graphics::cg_color_space::CONSTANTS,
     core_graphics::cg_geometry::CONSTANTS,
+    core_telephony::CONSTANTS,
+    foundation::ns_calendar::CONSTANTS,
     foundation::ns_error::CONSTANTS,
     foundation::ns_exception::CONSTANTS,
+    foundation::ns_file_handle::CONSTANTS,
+    foundation::ns_file_manager::CONSTANTS,
+    foundation::ns_http_cookie::CONSTANTS,
     foundation::ns_keyed_unarchiver::CONSTANTS,
     foundation::ns_locale::CONSTANTS,
     foundation::ns_run_loop::CONSTANTS,
+    foundation::ns_thread::CONSTANTS,
+    foundation::ns_url_connection::CONSTANTS,
     media_player::movie_player::CONSTANTS,
+    media_player::music_player::CONSTANTS,
     opengles::eagl::CONSTANTS,
+    uikit::ui_application::CONSTANTS,
     uikit::ui_device::CONSTANTS,
+    uikit::ui_pasteboard::CONSTANTS,
 ];
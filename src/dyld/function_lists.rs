@@ -7,7 +7,8 @@
 //! very long and frequently-updated list.
 
 use crate::frameworks::{
-    audio_toolbox, core_foundation, core_graphics, dnssd, foundation, openal, opengles, uikit,
+    accelerate, audio_toolbox, core_foundation, core_graphics, dnssd, foundation, openal, opengles,
+    system_configuration, uikit,
 };
 use crate::libc;
 
@@ -19,13 +20,20 @@ pub const FUNCTION_LISTS: &[super::FunctionExports] = &[
     libc::dirent::FUNCTIONS,
     libc::dlfcn::FUNCTIONS,
     libc::errno::FUNCTIONS,
+    libc::ftw::FUNCTIONS,
+    libc::glob::FUNCTIONS,
     libc::ifaddrs::FUNCTIONS,
     libc::keymgr::FUNCTIONS,
+    libc::mach_port::FUNCTIONS,
+    libc::mach_semaphore::FUNCTIONS,
+    libc::mach_task::FUNCTIONS,
     libc::mach_thread_info::FUNCTIONS,
     libc::mach_time::FUNCTIONS,
     libc::math::FUNCTIONS,
     libc::mmap::FUNCTIONS,
     libc::net::if_::FUNCTIONS,
+    libc::net::inet::FUNCTIONS,
+    libc::os_byte_order::FUNCTIONS,
     libc::posix_io::FUNCTIONS,
     libc::posix_io::stat::FUNCTIONS,
     libc::pthread::key::FUNCTIONS,
@@ -41,6 +49,7 @@ pub const FUNCTION_LISTS: &[super::FunctionExports] = &[
     libc::stdlib::FUNCTIONS,
     libc::stdlib::qsort::FUNCTIONS,
     libc::string::FUNCTIONS,
+    libc::sys::mount::FUNCTIONS,
     libc::sys::timeb::FUNCTIONS,
     libc::sys::utsname::FUNCTIONS,
     libc::sysctl::FUNCTIONS,
@@ -48,13 +57,18 @@ pub const FUNCTION_LISTS: &[super::FunctionExports] = &[
     libc::unistd::FUNCTIONS,
     libc::wchar::FUNCTIONS,
     crate::objc::FUNCTIONS,
+    accelerate::FUNCTIONS,
+    audio_toolbox::au_graph::FUNCTIONS,
     audio_toolbox::audio_components::FUNCTIONS,
     audio_toolbox::audio_file::FUNCTIONS,
     audio_toolbox::audio_queue::FUNCTIONS,
     audio_toolbox::audio_services::FUNCTIONS,
     audio_toolbox::audio_session::FUNCTIONS,
+    audio_toolbox::audio_unit::FUNCTIONS,
+    audio_toolbox::music_player::FUNCTIONS,
     core_foundation::cf_array::FUNCTIONS,
     core_foundation::cf_bundle::FUNCTIONS,
+    core_foundation::cf_byte_order::FUNCTIONS,
     core_foundation::cf_data::FUNCTIONS,
     core_foundation::cf_run_loop::FUNCTIONS,
     core_foundation::cf_run_loop_timer::FUNCTIONS,
@@ -64,11 +78,14 @@ pub const FUNCTION_LISTS: &[super::FunctionExports] = &[
     core_foundation::time::FUNCTIONS,
     core_graphics::cg_affine_transform::FUNCTIONS,
     core_graphics::cg_bitmap_context::FUNCTIONS,
+    core_graphics::cg_color::FUNCTIONS,
     core_graphics::cg_color_space::FUNCTIONS,
     core_graphics::cg_context::FUNCTIONS,
     core_graphics::cg_data_provider::FUNCTIONS,
     core_graphics::cg_geometry::FUNCTIONS,
     core_graphics::cg_image::FUNCTIONS,
+    core_graphics::cg_pdf_document::FUNCTIONS,
+    core_graphics::cg_pdf_page::FUNCTIONS,
     dnssd::FUNCTIONS,
     foundation::FUNCTIONS,
     foundation::ns_file_manager::FUNCTIONS,
@@ -76,6 +93,7 @@ pub const FUNCTION_LISTS: &[super::FunctionExports] = &[
     foundation::ns_objc_runtime::FUNCTIONS,
     openal::FUNCTIONS,
     opengles::FUNCTIONS,
+    system_configuration::FUNCTIONS,
     uikit::ui_application::FUNCTIONS,
     uikit::ui_geometry::FUNCTIONS,
     uikit::ui_graphics::FUNCTIONS,
@@ -14,8 +14,8 @@ use crate::abi::GuestRet;
 use crate::libc::semaphore::sem_t;
 use crate::mem::{MutPtr, MutVoidPtr};
 use crate::{
-    abi, bundle, cpu, dyld, frameworks, fs, gdb, image, libc, mach_o, mem, objc, options, stack,
-    window,
+    abi, bundle, cpu, dyld, frameworks, fs, gdb, image, libc, mach_o, mem, objc, options, random,
+    stack, window,
 };
 use std::net::TcpListener;
 use std::time::{Duration, Instant};
@@ -38,6 +38,12 @@ pub struct Thread {
     in_start_routine: bool,
     /// After a secondary thread finishes, this is set to the returned value.
     return_value: Option<MutVoidPtr>,
+    /// Set by [Environment::exit_current_thread] to the value the thread is
+    /// exiting with. While this is set, the host call stack for this thread is
+    /// being unwound: [Environment::run_call] returns immediately, and each
+    /// host function returns straight to its caller, until the thread exit
+    /// routine is reached.
+    exiting: Option<MutVoidPtr>,
     /// Set to [true] when a thread is currently waiting for a host function
     /// call to return.
     ///
@@ -54,6 +60,10 @@ pub struct Thread {
     /// frame" of the thread is a host function, not whether there are any host
     /// functions at all.
     in_host_function: bool,
+    /// Number of host-to-guest calls ([Environment::run_call]) in progress on
+    /// this thread. If this is non-zero, the host call stack has frames that
+    /// belong to this thread, so it can't simply stop executing.
+    host_to_guest_calls: u32,
     /// Context object containing the CPU state for this thread.
     ///
     /// There should always be `(threads.len() - 1)` contexts in existence.
@@ -64,12 +74,57 @@ pub struct Thread {
     /// Address range of this thread's stack, used to check if addresses are in
     /// range while producing a stack trace.
     stack: Option<std::ops::RangeInclusive<u32>>,
+    /// Name given to this thread by the app (via `pthread_setname_np` or
+    /// `-[NSThread setName:]`), if any. Only used for debugging output.
+    pub name: Option<String>,
+    /// Set by `-[NSThread setThreadPriority:]`, `pthread_setschedparam` or
+    /// `thread_policy_set`.
+    pub priority: ThreadPriority,
+}
+
+/// Scheduling priority of a thread. There's no preemption, but higher-priority
+/// threads get longer time slices (see [ThreadPriority::time_slice]), which
+/// helps e.g. audio threads keep up.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ThreadPriority {
+    /// Priority in the range 0.0 to 1.0, like `NSThread` uses.
+    Normal(f64),
+    /// Real-time thread, set up with `THREAD_TIME_CONSTRAINT_POLICY`.
+    TimeConstraint,
+}
+
+impl ThreadPriority {
+    pub const DEFAULT: ThreadPriority = ThreadPriority::Normal(0.5);
+
+    /// Get the number of CPU ticks a thread with this priority can run for
+    /// before another thread gets a turn.
+    fn time_slice(self) -> u64 {
+        // 100,000 ticks is an arbitrary number. It needs to be reasonably
+        // large so we aren't jumping in and out of dynarmic or trying to
+        // poll for events too often. At the same time, very large values
+        // are bad for responsiveness.
+        const DEFAULT_TIME_SLICE: u64 = 100_000;
+        match self {
+            ThreadPriority::Normal(priority) => {
+                let scaled = (DEFAULT_TIME_SLICE as f64 * priority * 2.0) as u64;
+                scaled.max(DEFAULT_TIME_SLICE / 4)
+            }
+            ThreadPriority::TimeConstraint => DEFAULT_TIME_SLICE * 4,
+        }
+    }
 }
 
 impl Thread {
     fn is_blocked(&self) -> bool {
         !matches!(self.blocked_by, ThreadBlock::NotBlocked)
     }
+
+    /// Size of this thread's stack in bytes, if it has one.
+    pub fn stack_size(&self) -> Option<mem::GuestUSize> {
+        self.stack
+            .as_ref()
+            .map(|stack| stack.end() - stack.start() + 1)
+    }
 }
 
 /// The struct containing the entire emulator state. Methods are provided for
@@ -93,6 +148,8 @@ pub struct Environment {
     pub libc_state: libc::State,
     pub framework_state: frameworks::State,
     pub mutex_state: mutex::MutexState,
+    /// See [random] for what this is used for.
+    pub rng: random::Rng,
     pub options: options::Options,
     gdb_server: Option<gdb::GdbServer>,
 }
@@ -118,10 +175,14 @@ enum ThreadBlock {
     Sleeping(Instant),
     // Thread is waiting for a mutex to unlock.
     Mutex(MutexId),
-    // Thread is waiting on a semaphore.
-    Semaphore(MutPtr<sem_t>),
+    // Thread is waiting on a semaphore. (optionally until Instant, after which
+    // it gives up and the u32 is returned in r0)
+    Semaphore(MutPtr<sem_t>, Option<(Instant, u32)>),
     // Thread is waiting for another thread to finish (joining).
     Joining(ThreadId, MutPtr<MutVoidPtr>),
+    // Thread was waiting for something that no longer exists (e.g. a destroyed
+    // semaphore). It can run again, but the u32 is returned in r0.
+    Interrupted(u32),
     // Deferred guest-to-host return
     DeferredReturn,
 }
@@ -137,7 +198,7 @@ impl Environment {
     /// when allocating a second [mem::Mem] instance.
     pub fn new(
         bundle: bundle::Bundle,
-        fs: fs::Fs,
+        mut fs: fs::Fs,
         options: options::Options,
         env_for_salvage: Option<Environment>,
     ) -> Result<Environment, String> {
@@ -162,8 +223,12 @@ impl Environment {
                 log!("Warning: {}", e);
             }
 
-            let launch_image_path = bundle.launch_image_path();
-            let launch_image = if fs.is_file(&launch_image_path) {
+            let launch_image_path = bundle
+                .launch_image_paths(options.initial_orientation, options.scale_hack.get())
+                .into_iter()
+                .find(|path| fs.is_file(path));
+            let launch_image = if let Some(launch_image_path) = launch_image_path {
+                log_dbg!("Using launch image {:?}", launch_image_path);
                 let res = fs
                     .read(launch_image_path)
                     .map_err(|_| "Could not read launch image file".to_string())
@@ -179,12 +244,22 @@ impl Environment {
                 None
             };
 
-            Some(window::Window::new(
+            let mut window = window::Window::new(
                 &format!("{} (touchHLE {})", bundle.display_name(), super::VERSION),
                 icon.ok(),
                 launch_image,
                 &options,
-            ))
+            );
+            window.set_up_input_recording(
+                &options,
+                window::input_recording::Header {
+                    app_id: bundle.bundle_identifier().to_string(),
+                    app_version: bundle.bundle_version().to_string(),
+                    emulator_version: super::VERSION.to_string(),
+                    device: window::input_recording::device_profile(options.initial_orientation),
+                },
+            )?;
+            Some(window)
         };
 
         let mut mem = if let Some(mem) = mem_for_salvage {
@@ -234,6 +309,9 @@ impl Environment {
         bins.insert(0, executable);
 
         let mut objc = objc::ObjC::new();
+        if options.audit_retain_release {
+            objc.enable_retain_audit();
+        }
 
         let mut dyld = dyld::Dyld::new();
         dyld.do_initial_linking(&bins, &mut mem, &mut objc);
@@ -247,12 +325,25 @@ impl Environment {
             active: true,
             blocked_by: ThreadBlock::NotBlocked,
             return_value: None,
-            in_start_routine: false, // main thread never terminates
+            exiting: None,
+            in_start_routine: false, // main thread has no start routine
             in_host_function: false,
+            host_to_guest_calls: 0,
             context: None,
             stack: Some(mem::Mem::MAIN_THREAD_STACK_LOW_END..=0u32.wrapping_sub(1)),
+            name: None,
+            priority: ThreadPriority::DEFAULT,
         };
 
+        fs.set_free_space(options.disk_space.0);
+
+        let rng = random::Rng::new(options.random_seed);
+        log!(
+            "Random seed: {} (use --random-seed={} to reproduce this run)",
+            rng.seed(),
+            rng.seed()
+        );
+
         let mut env = Environment {
             startup_time,
             bundle,
@@ -268,6 +359,7 @@ impl Environment {
             libc_state: Default::default(),
             mutex_state: Default::default(),
             framework_state: Default::default(),
+            rng,
             options,
             gdb_server: None,
         };
@@ -367,6 +459,9 @@ impl Environment {
         let bins = Vec::new();
 
         let mut objc = objc::ObjC::new();
+        if options.audit_retain_release {
+            objc.enable_retain_audit();
+        }
 
         let mut dyld = dyld::Dyld::new();
         dyld.do_initial_linking_with_no_bins(&mut mem, &mut objc);
@@ -380,12 +475,18 @@ impl Environment {
             active: true,
             blocked_by: ThreadBlock::NotBlocked,
             return_value: None,
-            in_start_routine: false, // main thread never terminates
+            exiting: None,
+            in_start_routine: false, // main thread has no start routine
             in_host_function: false,
+            host_to_guest_calls: 0,
             context: None,
             stack: Some(mem::Mem::MAIN_THREAD_STACK_LOW_END..=0u32.wrapping_sub(1)),
+            name: None,
+            priority: ThreadPriority::DEFAULT,
         };
 
+        let rng = random::Rng::new(options.random_seed);
+
         let mut env = Environment {
             startup_time,
             bundle,
@@ -401,6 +502,7 @@ impl Environment {
             libc_state: Default::default(),
             mutex_state: Default::default(),
             framework_state: Default::default(),
+            rng,
             options,
             gdb_server: None,
         };
@@ -441,19 +543,56 @@ impl Environment {
         )
     }
 
-    fn stack_trace(&self) {
+    /// Describe a guest code address for a stack trace, e.g.
+    /// `0x1234 (_foo + 0x10, in AppName)`. This uses the nearest preceding
+    /// exported symbol, and most functions in an app aren't exported, so take
+    /// the symbol name with a grain of salt!
+    pub fn symbolicate(&self, addr: u32) -> String {
+        let addr_no_thumb = addr & !abi::GuestFunction::THUMB_BIT;
+        let Some(bin) = self.bins.iter().find(|bin| {
+            bin.sections
+                .iter()
+                .any(|s| (s.addr..s.addr.saturating_add(s.size)).contains(&addr_no_thumb))
+        }) else {
+            return format!("{:#x}", addr);
+        };
+        let nearest = bin
+            .exported_symbols
+            .iter()
+            .map(|(name, &sym_addr)| (name, sym_addr & !abi::GuestFunction::THUMB_BIT))
+            .filter(|&(_, sym_addr)| sym_addr <= addr_no_thumb)
+            .max_by_key(|&(_, sym_addr)| sym_addr);
+        if let Some((name, sym_addr)) = nearest {
+            format!(
+                "{:#x} ({} + {:#x}, in {})",
+                addr,
+                name,
+                addr_no_thumb - sym_addr,
+                bin.name
+            )
+        } else {
+            format!("{:#x} (in {})", addr, bin.name)
+        }
+    }
+
+    pub fn stack_trace(&self) {
+        let name = match self.threads[self.current_thread].name {
+            Some(ref name) => format!(" ({:?})", name),
+            None => String::new(),
+        };
         if self.current_thread == 0 {
-            echo!("Attempting to produce stack trace for main thread:");
+            echo!("Attempting to produce stack trace for main thread{}:", name);
         } else {
             echo!(
-                "Attempting to produce stack trace for thread {}:",
-                self.current_thread
+                "Attempting to produce stack trace for thread {}{}:",
+                self.current_thread,
+                name
             );
         }
         let stack_range = self.threads[self.current_thread].stack.clone().unwrap();
         echo!(
-            " 0. {:#x} (PC)",
-            self.cpu.pc_with_thumb_bit().addr_with_thumb_bit()
+            " 0. {} (PC)",
+            self.symbolicate(self.cpu.pc_with_thumb_bit().addr_with_thumb_bit())
         );
         let regs = self.cpu.regs();
         let mut lr = regs[cpu::Cpu::LR];
@@ -465,7 +604,7 @@ impl Environment {
             echo!(" 1. [thread exit] (LR)");
             return;
         } else {
-            echo!(" 1. {:#x} (LR)", lr);
+            echo!(" 1. {} (LR)", self.symbolicate(lr));
         }
         let mut i = 2;
         let mut fp: mem::ConstPtr<u8> = mem::Ptr::from_bits(regs[abi::FRAME_POINTER]);
@@ -482,21 +621,103 @@ impl Environment {
                 echo!("{:2}. [thread exit]", i);
                 return;
             } else {
-                echo!("{:2}. {:#x}", i, lr);
+                echo!("{:2}. {}", i, self.symbolicate(lr));
             }
             i += 1;
         }
     }
 
+    /// Produce a human-readable report of the app's memory usage: the guest
+    /// heap (broken down by allocation site if `--track-allocations` is used),
+    /// live Objective-C objects by class, and OpenGL ES resources. Taking
+    /// reports at different times is a simple way to find leaks, whether
+    /// they're the app's fault or touchHLE's.
+    pub fn memory_usage_report(&self) -> String {
+        use std::fmt::Write;
+
+        /// Only this many allocation sites and classes are listed.
+        const TOP_ENTRIES: usize = 20;
+
+        let mut report = String::new();
+        writeln!(report, "Memory usage report:").unwrap();
+        writeln!(
+            report,
+            "Guest memory in use: {} KiB (including binaries and stacks)",
+            self.mem.used_bytes() / 1024
+        )
+        .unwrap();
+
+        if self.options.track_allocations {
+            let sites = self.mem.heap_usage_by_site();
+            let (count, total) = sites
+                .iter()
+                .fold((0, 0), |(count, total), &(_, c, t)| (count + c, total + t));
+            writeln!(
+                report,
+                "Heap allocations by the app: {} KiB in {} allocations from {} sites",
+                total / 1024,
+                count,
+                sites.len()
+            )
+            .unwrap();
+            for &(site, count, total) in sites.iter().take(TOP_ENTRIES) {
+                writeln!(
+                    report,
+                    "  {} KiB in {} allocations from {}",
+                    total / 1024,
+                    count,
+                    self.symbolicate(site)
+                )
+                .unwrap();
+            }
+        } else {
+            writeln!(
+                report,
+                "Heap allocations by the app: not tracked (use --track-allocations)"
+            )
+            .unwrap();
+        }
+
+        let classes = self.objc.count_live_objects_by_class(&self.mem);
+        writeln!(
+            report,
+            "Live Objective-C objects: {} of {} classes",
+            classes.iter().map(|&(_, count)| count).sum::<usize>(),
+            classes.len()
+        )
+        .unwrap();
+        for (class, count) in classes.iter().take(TOP_ENTRIES) {
+            writeln!(report, "  {} {}", count, class).unwrap();
+        }
+
+        let sharegroups = crate::frameworks::opengles::describe_memory_usage(self);
+        writeln!(
+            report,
+            "OpenGL ES memory (estimated) in {} sharegroups:",
+            sharegroups.len()
+        )
+        .unwrap();
+        for sharegroup in sharegroups {
+            writeln!(report, "  {}", sharegroup).unwrap();
+        }
+
+        if let Some(audit_report) = objc::retain_audit_report(self) {
+            report.push_str(&audit_report);
+        }
+        report
+    }
+
     /// Create a new thread and return its ID. The `start_routine` and
     /// `user_data` arguments have the same meaning as the last two arguments to
-    /// `pthread_create`.
+    /// `pthread_create`. `stack_size` is the size in bytes of the guest stack
+    /// to allocate for it (see [mem::Mem::SECONDARY_THREAD_STACK_SIZE]).
     pub fn new_thread(
         &mut self,
         start_routine: abi::GuestFunction,
         user_data: mem::MutVoidPtr,
+        stack_size: mem::GuestUSize,
     ) -> ThreadId {
-        let stack_size = mem::Mem::SECONDARY_THREAD_STACK_SIZE;
+        assert!(stack_size % 4 == 0);
         let stack_alloc = self.mem.alloc(stack_size);
         let stack_high_addr = stack_alloc.to_bits() + stack_size;
         assert!(stack_high_addr % 4 == 0);
@@ -505,10 +726,14 @@ impl Environment {
             active: true,
             blocked_by: ThreadBlock::NotBlocked,
             return_value: None,
+            exiting: None,
             in_start_routine: true,
             in_host_function: false,
+            host_to_guest_calls: 0,
             context: Some(cpu::CpuContext::new()),
             stack: Some(stack_alloc.to_bits()..=(stack_high_addr - 1)),
+            name: None,
+            priority: ThreadPriority::DEFAULT,
         });
         let new_thread_id = self.threads.len() - 1;
 
@@ -586,6 +811,28 @@ impl Environment {
     /// Also note that like [Self::sleep], this only takes effect after the host
     /// function returns to the main run loop ([Environment::run]).
     pub fn sem_decrement(&mut self, sem: MutPtr<sem_t>, wait_on_lock: bool) -> bool {
+        self.sem_decrement_inner(sem, wait_on_lock, None)
+    }
+
+    /// Like [Self::sem_decrement] with blocking, but if the semaphore isn't
+    /// available before `timeout` has elapsed, the thread stops waiting and
+    /// `timeout_return_value` replaces the host function's return value.
+    pub fn sem_decrement_with_timeout(
+        &mut self,
+        sem: MutPtr<sem_t>,
+        timeout: Duration,
+        timeout_return_value: u32,
+    ) {
+        let until = Instant::now().checked_add(timeout).unwrap();
+        self.sem_decrement_inner(sem, true, Some((until, timeout_return_value)));
+    }
+
+    fn sem_decrement_inner(
+        &mut self,
+        sem: MutPtr<sem_t>,
+        wait_on_lock: bool,
+        timeout: Option<(Instant, u32)>,
+    ) -> bool {
         let host_sem_rc: &mut _ = self
             .libc_state
             .semaphore
@@ -620,7 +867,7 @@ impl Environment {
                 sem
             );
             host_sem.waiting.insert(self.current_thread);
-            self.threads[self.current_thread].blocked_by = ThreadBlock::Semaphore(sem);
+            self.threads[self.current_thread].blocked_by = ThreadBlock::Semaphore(sem, timeout);
         }
 
         true
@@ -649,6 +896,32 @@ impl Environment {
         );
     }
 
+    /// Unblock all threads waiting on a semaphore that is about to be
+    /// destroyed. Instead of the usual result, the function each thread was
+    /// waiting in will return `return_value`.
+    ///
+    /// Like [Self::sem_increment], this only takes effect after the host
+    /// function returns to the main run loop ([Environment::run]).
+    pub fn sem_interrupt_waiters(&mut self, sem: MutPtr<sem_t>, return_value: u32) {
+        let waiting = std::mem::take(
+            &mut self.libc_state.semaphore.open_semaphores[&sem]
+                .borrow_mut()
+                .waiting,
+        );
+        for thread in waiting {
+            log_dbg!(
+                "Thread {} is no longer blocking on semaphore {:?}, it is being destroyed",
+                thread,
+                sem
+            );
+            assert!(matches!(
+                self.threads[thread].blocked_by,
+                ThreadBlock::Semaphore(blocked_on, _) if blocked_on == sem
+            ));
+            self.threads[thread].blocked_by = ThreadBlock::Interrupted(return_value);
+        }
+    }
+
     /// Blocks the current thread until the thread given finishes, writing its
     /// return value to ptr (if non-null).
     ///
@@ -671,6 +944,37 @@ impl Environment {
         self.threads[self.current_thread].blocked_by = ThreadBlock::Joining(joinee_thread, ptr);
     }
 
+    /// Terminates the current thread as if its start routine had returned
+    /// `return_value`, which is made available to threads joining with it.
+    /// This is used to implement `pthread_exit` and `+[NSThread exit]`.
+    ///
+    /// This only takes effect once the host function returns. If there are
+    /// host functions further up the call stack (e.g. the thread was started
+    /// by `NSThread`, or this was called from a callback), any guest code
+    /// they are waiting for is abandoned, and any further host-to-guest calls
+    /// they make return immediately without doing anything (see
+    /// [Self::run_call]), so that they can return in turn.
+    ///
+    /// If this is the main thread, the other threads keep running, and the
+    /// app exits once they have all finished.
+    pub fn exit_current_thread(&mut self, return_value: MutVoidPtr) {
+        let thread = &mut self.threads[self.current_thread];
+        if thread.exiting.is_some() {
+            log!(
+                "Thread {} tried to exit while already exiting, ignoring.",
+                self.current_thread
+            );
+            return;
+        }
+        log_dbg!(
+            "Thread {} is exiting with return value {:?}, {} host-to-guest call(s) to unwind.",
+            self.current_thread,
+            return_value,
+            thread.host_to_guest_calls
+        );
+        thread.exiting = Some(return_value);
+    }
+
     /// Run the emulator. This is the main loop and won't return until app exit.
     /// Only `main.rs` should call this.
     pub fn run(&mut self) {
@@ -678,6 +982,14 @@ impl Environment {
         // the emulator will crash anyway, maybe this is okay.
         let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| self.run_inner(true)));
         if let Err(e) = res {
+            let reason = if let Some(s) = e.downcast_ref::<&str>() {
+                s
+            } else if let Some(s) = e.downcast_ref::<String>() {
+                s
+            } else {
+                "(non-string payload)"
+            };
+            echo!("touchHLE aborted: {}", reason);
             echo!("Register state immediately after panic:");
             self.cpu.dump_regs();
             self.stack_trace();
@@ -690,13 +1002,30 @@ impl Environment {
     ///
     /// Note that this might execute code from other threads while waiting for
     /// the app to return control on the original thread!
+    ///
+    /// If the thread is exiting (see [Self::exit_current_thread]), the call is
+    /// skipped or abandoned, and the return value registers are zeroed so that
+    /// the host function sees `nil`/`0`/`NULL`.
     pub fn run_call(&mut self) {
-        let was_in_host_function = self.threads[self.current_thread].in_host_function;
-        let old_thread = self.current_thread;
-        self.threads[self.current_thread].in_host_function = false;
-        self.run_inner(false);
-        assert!(self.current_thread == old_thread);
-        self.threads[self.current_thread].in_host_function = was_in_host_function;
+        if self.threads[self.current_thread].exiting.is_none() {
+            let was_in_host_function = self.threads[self.current_thread].in_host_function;
+            let old_thread = self.current_thread;
+            self.threads[self.current_thread].in_host_function = false;
+            self.threads[self.current_thread].host_to_guest_calls += 1;
+            self.run_inner(false);
+            assert!(self.current_thread == old_thread);
+            self.threads[self.current_thread].host_to_guest_calls -= 1;
+            self.threads[self.current_thread].in_host_function = was_in_host_function;
+        }
+        if self.threads[self.current_thread].exiting.is_some() {
+            log_dbg!(
+                "Thread {} is exiting, unwinding host-to-guest call.",
+                self.current_thread
+            );
+            let regs = self.cpu.regs_mut();
+            regs[0] = 0;
+            regs[1] = 0;
+        }
     }
 
     fn switch_thread(&mut self, new_thread: ThreadId) {
@@ -783,22 +1112,39 @@ impl Environment {
                 match svc {
                     dyld::Dyld::SVC_THREAD_EXIT => {
                         assert!(svc_pc == self.dyld.thread_exit_routine().addr_without_thumb_bit());
-                        if !self.threads[self.current_thread].in_start_routine {
+                        let exited_early =
+                            self.threads[self.current_thread].exiting.take().is_some();
+                        if !self.threads[self.current_thread].in_start_routine && !exited_early {
                             panic!("Non-exiting thread {} exited!", self.current_thread);
                         } else {
-                            // Secondary thread finished starting.
+                            // Secondary thread finished starting, or some
+                            // thread called exit_current_thread().
                             log_dbg!(
                                 "Thread {} finished start routine and became inactive, {}",
                                 self.current_thread,
                                 initial_thread
                             );
+                            let return_value = GuestRet::from_regs(self.cpu.regs());
+                            frameworks::foundation::ns_thread::handle_thread_exit(self);
+                            frameworks::opengles::handle_thread_exit(self);
+                            libc::pthread::key::run_destructors(self);
+                            frameworks::foundation::ns_autorelease_pool::drain_remaining_pools(
+                                self,
+                            );
                             let curr_thread = &mut self.threads[self.current_thread];
-                            curr_thread.return_value = Some(GuestRet::from_regs(self.cpu.regs()));
+                            curr_thread.return_value = Some(return_value);
                             curr_thread.active = false;
-                            let stack = curr_thread.stack.take().unwrap();
-                            let stack: mem::MutVoidPtr = mem::Ptr::from_bits(*stack.start());
-                            log_dbg!("Freeing thread {} stack {:?}", self.current_thread, stack);
-                            self.mem.free(stack);
+                            // The main thread's stack isn't a heap allocation.
+                            if curr_thread.in_start_routine {
+                                let stack = curr_thread.stack.take().unwrap();
+                                let stack: mem::MutVoidPtr = mem::Ptr::from_bits(*stack.start());
+                                log_dbg!(
+                                    "Freeing thread {} stack {:?}",
+                                    self.current_thread,
+                                    stack
+                                );
+                                self.mem.free(stack);
+                            }
                             ThreadNextAction::Yield
                         }
                     }
@@ -845,6 +1191,10 @@ impl Environment {
                             f.call_from_guest(self);
                             self.threads[self.current_thread].in_host_function =
                                 was_in_host_function;
+                            // Host function might have made the thread exit.
+                            if let Some(return_value) = self.threads[self.current_thread].exiting {
+                                return self.unwind_exiting_thread(return_value, initial_thread);
+                            }
                             // Host function might have put the thread to sleep.
                             if let ThreadBlock::NotBlocked =
                                 self.threads[self.current_thread].blocked_by
@@ -865,23 +1215,47 @@ impl Environment {
         }
     }
 
+    /// Continue unwinding a thread that is exiting (see
+    /// [Self::exit_current_thread]) after one of its host functions returned.
+    fn unwind_exiting_thread(
+        &mut self,
+        return_value: MutVoidPtr,
+        initial_thread: ThreadId,
+    ) -> ThreadNextAction {
+        // Any blocking the host function did on the way out no longer matters.
+        self.threads[self.current_thread].blocked_by = ThreadBlock::NotBlocked;
+        if self.threads[self.current_thread].host_to_guest_calls == 0 {
+            // Nothing left to unwind. The thread exit routine picks up the
+            // return value from r0, just as if the start routine returned
+            // normally.
+            self.cpu.regs_mut()[0] = return_value.to_bits();
+            self.cpu.branch(self.dyld.thread_exit_routine());
+            ThreadNextAction::Continue
+        } else if self.current_thread == initial_thread {
+            // Abandon the guest code and return to the host function that is
+            // waiting for it.
+            ThreadNextAction::ReturnToHost
+        } else {
+            // Same problem as with SVC_RETURN_TO_HOST: another thread is on top
+            // of the host call stack, so this has to wait until it isn't.
+            self.threads[self.current_thread].blocked_by = ThreadBlock::DeferredReturn;
+            ThreadNextAction::Yield
+        }
+    }
+
     fn run_inner(&mut self, root: bool) {
         let initial_thread = self.current_thread;
         assert!(self.threads[initial_thread].active);
         assert!(self.threads[initial_thread].context.is_none());
 
         loop {
-            // 100,000 ticks is an arbitrary number. It needs to be reasonably
-            // large so we aren't jumping in and out of dynarmic or trying to
-            // poll for events too often. At the same time, very large values
-            // are bad for responsiveness.
             let mut ticks = if self.threads[self.current_thread].is_blocked() {
                 // The current thread might be asleep, in which case we want to
                 // immediately switch to another thread. This only happens when
                 // called from Self::sleep().
                 0
             } else {
-                100_000
+                self.threads[self.current_thread].priority.time_slice()
             };
             let mut step_and_debug = false;
             while ticks > 0 {
@@ -927,6 +1301,7 @@ impl Environment {
                 let mut suitable_thread: Option<ThreadId> = None;
                 let mut next_awakening: Option<Instant> = None;
                 let mut mutex_to_relock: Option<MutexId> = None;
+                let mut return_value_override: Option<u32> = None;
                 for i in 0..self.threads.len() {
                     let i = (self.current_thread + 1 + i) % self.threads.len();
                     let candidate = &mut self.threads[i];
@@ -957,14 +1332,14 @@ impl Environment {
                                 break;
                             }
                         }
-                        ThreadBlock::Semaphore(sem) => {
+                        ThreadBlock::Semaphore(sem, timeout) => {
                             let host_sem_rc: &mut _ = self
                                 .libc_state
                                 .semaphore
                                 .open_semaphores
                                 .get_mut(&sem)
                                 .unwrap();
-                            let host_sem = (*host_sem_rc).borrow();
+                            let mut host_sem = (*host_sem_rc).borrow_mut();
 
                             if host_sem.value >= 0 {
                                 log_dbg!(
@@ -973,10 +1348,33 @@ impl Environment {
                                     sem,
                                     host_sem.value
                                 );
+                                host_sem.waiting.remove(&i);
                                 self.threads[i].blocked_by = ThreadBlock::NotBlocked;
                                 suitable_thread = Some(i);
                                 break;
                             }
+                            if let Some((until, timeout_return_value)) = timeout {
+                                if until <= Instant::now() {
+                                    log_dbg!(
+                                        "Thread {} timed out waiting on semaphore {:?}",
+                                        i,
+                                        sem
+                                    );
+                                    // Undo the decrement, since the thread no
+                                    // longer wants the semaphore.
+                                    host_sem.value += 1;
+                                    host_sem.waiting.remove(&i);
+                                    self.threads[i].blocked_by = ThreadBlock::NotBlocked;
+                                    suitable_thread = Some(i);
+                                    return_value_override = Some(timeout_return_value);
+                                    break;
+                                } else {
+                                    next_awakening = match next_awakening {
+                                        None => Some(until),
+                                        Some(other) => Some(other.min(until)),
+                                    };
+                                }
+                            }
                         }
                         ThreadBlock::Joining(joinee_thread, ptr) => {
                             if !self.threads[joinee_thread].active {
@@ -998,6 +1396,13 @@ impl Environment {
                                 break;
                             }
                         }
+                        ThreadBlock::Interrupted(interrupted_return_value) => {
+                            log_dbg!("Thread {} was interrupted while blocked", i);
+                            self.threads[i].blocked_by = ThreadBlock::NotBlocked;
+                            suitable_thread = Some(i);
+                            return_value_override = Some(interrupted_return_value);
+                            break;
+                        }
                         ThreadBlock::DeferredReturn => {
                             if i == initial_thread {
                                 log_dbg!("Thread {} is now able to return, returning", i);
@@ -1017,12 +1422,16 @@ impl Environment {
 
                 // There's a suitable thread we can switch to immediately.
                 if let Some(suitable_thread) = suitable_thread {
+                    crate::libc::mach_port::handle_thread_unblocked(self, suitable_thread);
                     if suitable_thread != self.current_thread {
                         self.switch_thread(suitable_thread);
                     }
                     if let Some(mutex_id) = mutex_to_relock {
                         self.relock_unblocked_mutex(mutex_id);
                     }
+                    if let Some(return_value) = return_value_override {
+                        self.cpu.regs_mut()[0] = return_value;
+                    }
                     break;
                 // All suitable threads are blocked and at least one is asleep.
                 // Sleep until one of them wakes up.
@@ -1033,6 +1442,11 @@ impl Environment {
                     // Try again, there should be some thread awake now (or
                     // there will be soon, since timing is approximate).
                     continue;
+                } else if self.threads.iter().all(|thread| !thread.active) {
+                    // This can happen if the main thread exits before the
+                    // others, like on iOS.
+                    echo!("All threads have exited, exiting.");
+                    std::process::exit(0);
                 } else {
                     // This should hopefully not happen, but if a thread is
                     // blocked on another thread waiting for a deferred return,
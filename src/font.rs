@@ -15,24 +15,50 @@
 
 use crate::paths;
 use rusttype::{Point, Scale};
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::io::Read;
+use std::ops::Range;
 
 pub struct Font {
     font: rusttype::Font<'static>,
+    /// Cache of line layouts produced by [Font::break_lines]. Apps tend to
+    /// measure and draw the same text over and over (`UILabel` does both every
+    /// time it is redrawn), and line breaking is relatively expensive.
+    line_cache: RefCell<HashMap<LineCacheKey, Vec<(f32, Range<usize>)>>>,
 }
 
+/// Font size, text and wrapping parameters. The floats are stored as bits so
+/// they can be hashed.
+type LineCacheKey = (u32, String, Option<(u32, WrapMode)>);
+
+/// Maximum number of entries in [Font::line_cache]. When this is reached, the
+/// cache is emptied. This is crude, but it stops the cache growing forever if
+/// an app draws lots of unique strings (e.g. a timer or score).
+const LINE_CACHE_CAPACITY: usize = 512;
+
 pub enum TextAlignment {
     Left,
     Center,
     Right,
 }
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub enum WrapMode {
     Word,
     Char,
 }
 
+/// Vertical metrics to use instead of the font's own, as fractions of the font
+/// size. This lets a substitute font lay out text like the original would.
+#[derive(Copy, Clone, Debug)]
+pub struct FontMetrics {
+    /// Distance from the baseline to the top of a line (positive).
+    pub ascent: f32,
+    /// Distance from the baseline to the bottom of a line (negative).
+    pub descent: f32,
+}
+
 fn scale(font_size: f32) -> Scale {
     // iPhone OS's interpretation of font size is slightly different, reason
     // unknown. This is not the same as the Windows pt vs Mac pt issue.
@@ -83,7 +109,10 @@ impl Font {
             panic!("Couldn't parse bundled font file {:?}. This probably means the file is corrupt. Try re-downloading it.", path);
         };
 
-        Font { font }
+        Font {
+            font,
+            line_cache: Default::default(),
+        }
     }
 
     pub fn sans_regular() -> Font {
@@ -102,9 +131,29 @@ impl Font {
         Self::from_resource_file("NotoSansJP-Bold.otf")
     }
 
-    fn line_height_and_gap(&self, font_size: f32) -> (f32, f32) {
+    /// Get the height of a line and the gap between lines. iPhone OS always
+    /// uses integral line heights, and some apps position things based on the
+    /// results of measuring text, so these are rounded.
+    fn line_height_and_gap(&self, font_size: f32, metrics: Option<FontMetrics>) -> (f32, f32) {
+        if let Some(FontMetrics { ascent, descent }) = metrics {
+            // iPhone OS rounds the ascent and descent separately.
+            let height = (ascent * font_size).round() + (-descent * font_size).round();
+            return (height, 0.0);
+        }
         let v_metrics = self.font.v_metrics(scale(font_size));
-        (v_metrics.ascent - v_metrics.descent, v_metrics.line_gap)
+        (
+            (v_metrics.ascent - v_metrics.descent).ceil(),
+            v_metrics.line_gap.round(),
+        )
+    }
+
+    /// Get the distance from the top of a line to its baseline. This is rounded
+    /// for the same reason as [Self::line_height_and_gap].
+    fn baseline_offset(&self, font_size: f32, metrics: Option<FontMetrics>) -> f32 {
+        if let Some(FontMetrics { ascent, .. }) = metrics {
+            return (ascent * font_size).round();
+        }
+        self.font.v_metrics(scale(font_size)).ascent.ceil()
     }
 
     /// Calculate the width of a line. This does not handle newlines!
@@ -135,12 +184,53 @@ impl Font {
         line_x_max.ceil() - line_x_min.floor()
     }
 
-    /// Break text into lines with known widths.
+    /// Break text into lines with known widths. The results are cached, see
+    /// [Self::break_lines_uncached] for the actual implementation.
     fn break_lines<'a>(
         &self,
         font_size: f32,
         text: &'a str,
         wrap: Option<(f32, WrapMode)>,
+    ) -> Vec<(f32, &'a str)> {
+        let key = (
+            font_size.to_bits(),
+            text.to_string(),
+            wrap.map(|(wrap_width, wrap_mode)| (wrap_width.to_bits(), wrap_mode)),
+        );
+
+        if let Some(lines) = self.line_cache.borrow().get(&key) {
+            return lines
+                .iter()
+                .map(|(width, range)| (*width, &text[range.clone()]))
+                .collect();
+        }
+
+        let lines = self.break_lines_uncached(font_size, text, wrap);
+
+        // The lines are always slices of the original text, so they can be
+        // stored as byte ranges.
+        let text_start = text.as_ptr() as usize;
+        let cached_lines = lines
+            .iter()
+            .map(|&(width, line)| {
+                let start = line.as_ptr() as usize - text_start;
+                (width, start..(start + line.len()))
+            })
+            .collect();
+        let mut line_cache = self.line_cache.borrow_mut();
+        if line_cache.len() >= LINE_CACHE_CAPACITY {
+            line_cache.clear();
+        }
+        line_cache.insert(key, cached_lines);
+
+        lines
+    }
+
+    fn break_lines_uncached<'a>(
+        &self,
+        font_size: f32,
+        text: &'a str,
+        wrap: Option<(f32, WrapMode)>,
     ) -> Vec<(f32, &'a str)> {
         let mut lines = Vec::new();
 
@@ -219,7 +309,7 @@ impl Font {
                             // Try to break the word.
                             let word_end = wrap_points[next_wrap_point_idx];
                             let word = &line[line_start..word_end];
-                            let broken_words = self.break_lines(
+                            let broken_words = self.break_lines_uncached(
                                 font_size,
                                 word,
                                 Some((wrap_width, WrapMode::Char)),
@@ -258,9 +348,11 @@ impl Font {
     }
 
     /// Calculate the on-screen width and height of text with a given font size.
+    /// If `metrics` is provided, it overrides the font's vertical metrics.
     pub fn calculate_text_size(
         &self,
         font_size: f32,
+        metrics: Option<FontMetrics>,
         text: &str,
         wrap: Option<(f32, WrapMode)>,
     ) -> (f32, f32) {
@@ -269,7 +361,7 @@ impl Font {
         let width = lines
             .iter()
             .fold(0f32, |widest, &(line_width, _line)| widest.max(line_width));
-        let (line_height, line_gap) = self.line_height_and_gap(font_size);
+        let (line_height, line_gap) = self.line_height_and_gap(font_size, metrics);
         let height =
             line_height * (lines.len() as f32) + line_gap * (lines.len().saturating_sub(1) as f32);
 
@@ -278,9 +370,12 @@ impl Font {
 
     /// Draw text. Calls the provided callback for each glyph that is to be
     /// drawn. Assumes y starts at the bottom-left corner and points upwards.
+    /// `metrics` has the same meaning as for [Self::calculate_text_size].
+    #[allow(clippy::too_many_arguments)]
     pub fn draw<F: FnMut(RasterGlyph)>(
         &self,
         font_size: f32,
+        metrics: Option<FontMetrics>,
         text: &str,
         origin: (f32, f32),
         wrap: Option<(f32, WrapMode)>,
@@ -292,8 +387,8 @@ impl Font {
 
         let lines = self.break_lines(font_size, text, wrap);
 
-        let mut line_y = self.font.v_metrics(scale(font_size)).ascent;
-        let (line_height, line_gap) = self.line_height_and_gap(font_size);
+        let mut line_y = self.baseline_offset(font_size, metrics);
+        let (line_height, line_gap) = self.line_height_and_gap(font_size, metrics);
 
         // RustType requires a "draw pixel" callback that will be called for
         // each pixel in the glyph's bounding box, in left-to-right
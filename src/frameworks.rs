@@ -20,6 +20,7 @@
 #![allow(non_upper_case_globals)] // Lots of Apple constants begin with "k"
 #![allow(clippy::too_many_arguments)] // It's not our fault!
 
+pub mod accelerate;
 pub mod audio_toolbox;
 pub mod av_audio;
 pub mod carbon_core;
@@ -27,12 +28,14 @@ pub mod core_animation;
 pub mod core_audio_types;
 pub mod core_foundation;
 pub mod core_graphics;
+pub mod core_telephony;
 pub mod dnssd;
 pub mod foundation;
 pub mod media_player;
 pub mod openal;
 pub mod opengles;
 pub mod store_kit;
+pub mod system_configuration;
 pub mod uikit;
 
 /// Container for state of various child modules
@@ -40,6 +43,7 @@ pub mod uikit;
 pub struct State {
     audio_toolbox: audio_toolbox::State,
     core_animation: core_animation::State,
+    core_graphics: core_graphics::State,
     foundation: foundation::State,
     media_player: media_player::State,
     openal: openal::State,
@@ -0,0 +1,635 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The Accelerate framework. Only a subset of vDSP is implemented.
+//!
+//! vDSP functions take a "stride" for each vector, which is the distance
+//! between consecutive elements, in elements. It can be negative.
+//!
+//! Resources:
+//! - Apple's [vDSP Programming Guide](https://developer.apple.com/library/archive/documentation/Performance/Conceptual/vDSP_Programming_Guide/Introduction/Introduction.html)
+//!   explains the unusual packing used by the real-to-complex FFT functions.
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::mem::{ConstPtr, ConstVoidPtr, Mem, MutPtr, MutVoidPtr, Ptr, SafeRead};
+use crate::Environment;
+use std::f64::consts::PI;
+
+#[allow(non_camel_case_types)]
+type vDSP_Length = u32;
+#[allow(non_camel_case_types)]
+type vDSP_Stride = i32;
+type FFTRadix = i32;
+type FFTDirection = i32;
+/// Opaque pointer. touchHLE stores the maximum `log2n` value in it.
+type FFTSetup = MutPtr<u32>;
+
+const kFFTRadix2: FFTRadix = 0;
+const kFFTDirection_Forward: FFTDirection = 1;
+const kFFTDirection_Inverse: FFTDirection = -1;
+
+#[derive(Copy, Clone)]
+#[repr(C, packed)]
+struct DSPSplitComplex {
+    realp: MutPtr<f32>,
+    imagp: MutPtr<f32>,
+}
+unsafe impl SafeRead for DSPSplitComplex {}
+
+fn element_ptr<const MUT: bool>(
+    ptr: Ptr<f32, MUT>,
+    stride: vDSP_Stride,
+    index: vDSP_Length,
+) -> Ptr<f32, MUT> {
+    let offset = (index as i32).wrapping_mul(stride).wrapping_mul(4);
+    Ptr::from_bits(ptr.to_bits().wrapping_add_signed(offset))
+}
+
+/// Read a vector from guest memory into a host vector.
+fn read_vector<const MUT: bool>(
+    mem: &Mem,
+    ptr: Ptr<f32, MUT>,
+    stride: vDSP_Stride,
+    n: vDSP_Length,
+) -> Vec<f32> {
+    if stride == 1 {
+        // Fast path: read the whole thing at once.
+        let bytes = mem.bytes_at(ptr.cast(), n.checked_mul(4).unwrap());
+        return bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes(b.try_into().unwrap()))
+            .collect();
+    }
+    (0..n)
+        .map(|i| mem.read(element_ptr(ptr, stride, i)))
+        .collect()
+}
+
+/// Write a host vector to guest memory.
+fn write_vector(mem: &mut Mem, ptr: MutPtr<f32>, stride: vDSP_Stride, values: &[f32]) {
+    if stride == 1 {
+        let n: u32 = values.len().try_into().unwrap();
+        let bytes = mem.bytes_at_mut(ptr.cast(), n.checked_mul(4).unwrap());
+        for (b, value) in bytes.chunks_exact_mut(4).zip(values) {
+            b.copy_from_slice(&value.to_le_bytes());
+        }
+        return;
+    }
+    for (i, &value) in values.iter().enumerate() {
+        mem.write(element_ptr(ptr, stride, i as vDSP_Length), value);
+    }
+}
+
+/// Helper for the many functions of the form `C[n] = f(A[n], B[n])`.
+fn binary_op<F: Fn(f32, f32) -> f32>(
+    env: &mut Environment,
+    a: ConstPtr<f32>,
+    ia: vDSP_Stride,
+    b: ConstPtr<f32>,
+    ib: vDSP_Stride,
+    c: MutPtr<f32>,
+    ic: vDSP_Stride,
+    n: vDSP_Length,
+    f: F,
+) {
+    let a = read_vector(&env.mem, a, ia, n);
+    let b = read_vector(&env.mem, b, ib, n);
+    let c_values: Vec<f32> = a.into_iter().zip(b).map(|(a, b)| f(a, b)).collect();
+    write_vector(&mut env.mem, c, ic, &c_values);
+}
+
+/// Helper for functions of the form `C[n] = f(A[n])`.
+fn unary_op<F: Fn(f32) -> f32>(
+    env: &mut Environment,
+    a: ConstPtr<f32>,
+    ia: vDSP_Stride,
+    c: MutPtr<f32>,
+    ic: vDSP_Stride,
+    n: vDSP_Length,
+    f: F,
+) {
+    let a = read_vector(&env.mem, a, ia, n);
+    let c_values: Vec<f32> = a.into_iter().map(f).collect();
+    write_vector(&mut env.mem, c, ic, &c_values);
+}
+
+// Vector-vector arithmetic
+
+fn vDSP_vadd(
+    env: &mut Environment,
+    a: ConstPtr<f32>,
+    ia: vDSP_Stride,
+    b: ConstPtr<f32>,
+    ib: vDSP_Stride,
+    c: MutPtr<f32>,
+    ic: vDSP_Stride,
+    n: vDSP_Length,
+) {
+    binary_op(env, a, ia, b, ib, c, ic, n, |a, b| a + b)
+}
+/// Note the unusual argument order: this computes `C = A - B`.
+fn vDSP_vsub(
+    env: &mut Environment,
+    b: ConstPtr<f32>,
+    ib: vDSP_Stride,
+    a: ConstPtr<f32>,
+    ia: vDSP_Stride,
+    c: MutPtr<f32>,
+    ic: vDSP_Stride,
+    n: vDSP_Length,
+) {
+    binary_op(env, a, ia, b, ib, c, ic, n, |a, b| a - b)
+}
+fn vDSP_vmul(
+    env: &mut Environment,
+    a: ConstPtr<f32>,
+    ia: vDSP_Stride,
+    b: ConstPtr<f32>,
+    ib: vDSP_Stride,
+    c: MutPtr<f32>,
+    ic: vDSP_Stride,
+    n: vDSP_Length,
+) {
+    binary_op(env, a, ia, b, ib, c, ic, n, |a, b| a * b)
+}
+/// Note the unusual argument order: this computes `C = A / B`.
+fn vDSP_vdiv(
+    env: &mut Environment,
+    b: ConstPtr<f32>,
+    ib: vDSP_Stride,
+    a: ConstPtr<f32>,
+    ia: vDSP_Stride,
+    c: MutPtr<f32>,
+    ic: vDSP_Stride,
+    n: vDSP_Length,
+) {
+    binary_op(env, a, ia, b, ib, c, ic, n, |a, b| a / b)
+}
+
+// Vector-scalar arithmetic
+
+fn vDSP_vsadd(
+    env: &mut Environment,
+    a: ConstPtr<f32>,
+    ia: vDSP_Stride,
+    b: ConstPtr<f32>,
+    c: MutPtr<f32>,
+    ic: vDSP_Stride,
+    n: vDSP_Length,
+) {
+    let b = env.mem.read(b);
+    unary_op(env, a, ia, c, ic, n, |a| a + b)
+}
+fn vDSP_vsmul(
+    env: &mut Environment,
+    a: ConstPtr<f32>,
+    ia: vDSP_Stride,
+    b: ConstPtr<f32>,
+    c: MutPtr<f32>,
+    ic: vDSP_Stride,
+    n: vDSP_Length,
+) {
+    let b = env.mem.read(b);
+    unary_op(env, a, ia, c, ic, n, |a| a * b)
+}
+fn vDSP_vsdiv(
+    env: &mut Environment,
+    a: ConstPtr<f32>,
+    ia: vDSP_Stride,
+    b: ConstPtr<f32>,
+    c: MutPtr<f32>,
+    ic: vDSP_Stride,
+    n: vDSP_Length,
+) {
+    let b = env.mem.read(b);
+    unary_op(env, a, ia, c, ic, n, |a| a / b)
+}
+
+// Other element-wise operations
+
+fn vDSP_vneg(
+    env: &mut Environment,
+    a: ConstPtr<f32>,
+    ia: vDSP_Stride,
+    c: MutPtr<f32>,
+    ic: vDSP_Stride,
+    n: vDSP_Length,
+) {
+    unary_op(env, a, ia, c, ic, n, |a| -a)
+}
+fn vDSP_vabs(
+    env: &mut Environment,
+    a: ConstPtr<f32>,
+    ia: vDSP_Stride,
+    c: MutPtr<f32>,
+    ic: vDSP_Stride,
+    n: vDSP_Length,
+) {
+    unary_op(env, a, ia, c, ic, n, |a| a.abs())
+}
+fn vDSP_vclr(env: &mut Environment, c: MutPtr<f32>, ic: vDSP_Stride, n: vDSP_Length) {
+    write_vector(&mut env.mem, c, ic, &vec![0.0; n as usize]);
+}
+fn vDSP_vfill(
+    env: &mut Environment,
+    a: ConstPtr<f32>,
+    c: MutPtr<f32>,
+    ic: vDSP_Stride,
+    n: vDSP_Length,
+) {
+    let a = env.mem.read(a);
+    write_vector(&mut env.mem, c, ic, &vec![a; n as usize]);
+}
+
+// Reductions
+
+fn vDSP_dotpr(
+    env: &mut Environment,
+    a: ConstPtr<f32>,
+    ia: vDSP_Stride,
+    b: ConstPtr<f32>,
+    ib: vDSP_Stride,
+    c: MutPtr<f32>,
+    n: vDSP_Length,
+) {
+    let a = read_vector(&env.mem, a, ia, n);
+    let b = read_vector(&env.mem, b, ib, n);
+    let res = a.into_iter().zip(b).map(|(a, b)| a * b).sum();
+    env.mem.write(c, res);
+}
+fn vDSP_sve(
+    env: &mut Environment,
+    a: ConstPtr<f32>,
+    ia: vDSP_Stride,
+    c: MutPtr<f32>,
+    n: vDSP_Length,
+) {
+    let res = read_vector(&env.mem, a, ia, n).into_iter().sum();
+    env.mem.write(c, res);
+}
+fn vDSP_meanv(
+    env: &mut Environment,
+    a: ConstPtr<f32>,
+    ia: vDSP_Stride,
+    c: MutPtr<f32>,
+    n: vDSP_Length,
+) {
+    let sum: f32 = read_vector(&env.mem, a, ia, n).into_iter().sum();
+    env.mem.write(c, sum / n as f32);
+}
+fn vDSP_maxv(
+    env: &mut Environment,
+    a: ConstPtr<f32>,
+    ia: vDSP_Stride,
+    c: MutPtr<f32>,
+    n: vDSP_Length,
+) {
+    let res = read_vector(&env.mem, a, ia, n)
+        .into_iter()
+        .fold(f32::NEG_INFINITY, f32::max);
+    env.mem.write(c, res);
+}
+fn vDSP_minv(
+    env: &mut Environment,
+    a: ConstPtr<f32>,
+    ia: vDSP_Stride,
+    c: MutPtr<f32>,
+    n: vDSP_Length,
+) {
+    let res = read_vector(&env.mem, a, ia, n)
+        .into_iter()
+        .fold(f32::INFINITY, f32::min);
+    env.mem.write(c, res);
+}
+
+// Matrix operations
+
+/// Multiplies the row-major matrices `A` (`m` by `p`) and `B` (`p` by `n`) to
+/// produce the matrix `C` (`m` by `n`).
+fn vDSP_mmul(
+    env: &mut Environment,
+    a: ConstPtr<f32>,
+    ia: vDSP_Stride,
+    b: ConstPtr<f32>,
+    ib: vDSP_Stride,
+    c: MutPtr<f32>,
+    ic: vDSP_Stride,
+    m: vDSP_Length,
+    n: vDSP_Length,
+    p: vDSP_Length,
+) {
+    let (m, n, p) = (m as usize, n as usize, p as usize);
+    let a = read_vector(&env.mem, a, ia, (m * p).try_into().unwrap());
+    let b = read_vector(&env.mem, b, ib, (p * n).try_into().unwrap());
+    let mut c_values = vec![0.0f32; m * n];
+    for row in 0..m {
+        for k in 0..p {
+            let a_value = a[row * p + k];
+            for col in 0..n {
+                c_values[row * n + col] += a_value * b[k * n + col];
+            }
+        }
+    }
+    write_vector(&mut env.mem, c, ic, &c_values);
+}
+
+// FFT
+
+fn vDSP_create_fftsetup(env: &mut Environment, log2n: vDSP_Length, radix: FFTRadix) -> FFTSetup {
+    if radix != kFFTRadix2 {
+        // TODO: radix 3 and 5
+        log!(
+            "TODO: vDSP_create_fftsetup({}, {}) with unsupported radix, returning NULL",
+            log2n,
+            radix
+        );
+        return Ptr::null();
+    }
+    env.mem.alloc_and_write(log2n)
+}
+fn vDSP_destroy_fftsetup(env: &mut Environment, setup: FFTSetup) {
+    if !setup.is_null() {
+        env.mem.free(setup.cast());
+    }
+}
+
+/// In-place unnormalized radix-2 complex FFT. The length must be a power of
+/// two. The inverse transform uses a positive exponent.
+fn fft_in_place(re: &mut [f32], im: &mut [f32], inverse: bool) {
+    let n = re.len();
+    assert!(n.is_power_of_two() && im.len() == n);
+
+    // Bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    // Butterflies. Twiddle factors are computed in double precision to limit
+    // the accumulation of error.
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let angle = sign * 2.0 * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_im, w_re) = (angle * k as f64).sin_cos();
+                let (w_re, w_im) = (w_re as f32, w_im as f32);
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len *= 2;
+    }
+}
+
+/// In-place unnormalized real FFT, for [vDSP_fft_zrip]. The real and imaginary
+/// parts are in vDSP's packed format, so each slice has half as many elements
+/// as the transform.
+fn fft_zrip(packed_re: &mut [f32], packed_im: &mut [f32], inverse: bool) {
+    let half_n = packed_re.len();
+    let n = half_n * 2;
+
+    let (out_re, out_im): (Vec<f32>, Vec<f32>) = if !inverse {
+        // Even-indexed real inputs are in the real part, odd-indexed ones in
+        // the imaginary part.
+        let mut re = vec![0.0; n];
+        let mut im = vec![0.0; n];
+        for i in 0..half_n {
+            re[i * 2] = packed_re[i];
+            re[i * 2 + 1] = packed_im[i];
+        }
+        fft_in_place(&mut re, &mut im, false);
+        // The DC and Nyquist components are real, so they are packed together
+        // into the first element.
+        let mut out_re: Vec<f32> = re[..half_n].iter().map(|x| x * 2.0).collect();
+        let mut out_im: Vec<f32> = im[..half_n].iter().map(|x| x * 2.0).collect();
+        out_re[0] = re[0] * 2.0;
+        out_im[0] = re[half_n] * 2.0;
+        (out_re, out_im)
+    } else {
+        // Reconstruct the full spectrum using conjugate symmetry.
+        let mut re = vec![0.0; n];
+        let mut im = vec![0.0; n];
+        re[0] = packed_re[0];
+        re[half_n] = packed_im[0];
+        for k in 1..half_n {
+            re[k] = packed_re[k];
+            im[k] = packed_im[k];
+            re[n - k] = packed_re[k];
+            im[n - k] = -packed_im[k];
+        }
+        fft_in_place(&mut re, &mut im, true);
+        // The result is real.
+        let out_re = (0..half_n).map(|i| re[i * 2]).collect();
+        let out_im = (0..half_n).map(|i| re[i * 2 + 1]).collect();
+        (out_re, out_im)
+    };
+    packed_re.copy_from_slice(&out_re);
+    packed_im.copy_from_slice(&out_im);
+}
+
+/// Check the arguments common to the FFT functions. Invalid arguments are
+/// undefined behavior for the real vDSP, so they're just logged and the
+/// function does nothing.
+fn check_fft_args(
+    env: &Environment,
+    func: &str,
+    setup: FFTSetup,
+    log2n: vDSP_Length,
+    direction: FFTDirection,
+) -> bool {
+    if direction != kFFTDirection_Forward && direction != kFFTDirection_Inverse {
+        log!(
+            "Warning: {}() called with invalid direction {}, ignoring",
+            func,
+            direction
+        );
+        return false;
+    }
+    if setup.is_null() {
+        log!("Warning: {}() called with NULL setup, ignoring", func);
+        return false;
+    }
+    let max_log2n = env.mem.read(setup);
+    if log2n > max_log2n {
+        log!(
+            "Warning: {}() called with log2n {} but setup only supports up to {}, ignoring",
+            func,
+            log2n,
+            max_log2n
+        );
+        return false;
+    }
+    true
+}
+
+/// In-place complex FFT.
+fn vDSP_fft_zip(
+    env: &mut Environment,
+    setup: FFTSetup,
+    c: ConstPtr<DSPSplitComplex>,
+    ic: vDSP_Stride,
+    log2n: vDSP_Length,
+    direction: FFTDirection,
+) {
+    if !check_fft_args(env, "vDSP_fft_zip", setup, log2n, direction) {
+        return;
+    }
+    let DSPSplitComplex { realp, imagp } = env.mem.read(c);
+    let n = 1 << log2n;
+    let mut re = read_vector(&env.mem, realp, ic, n);
+    let mut im = read_vector(&env.mem, imagp, ic, n);
+    fft_in_place(&mut re, &mut im, direction == kFFTDirection_Inverse);
+    write_vector(&mut env.mem, realp, ic, &re);
+    write_vector(&mut env.mem, imagp, ic, &im);
+}
+
+/// In-place real FFT. The input and output use vDSP's packed format: see the
+/// vDSP Programming Guide. Like the real thing, the forward transform's
+/// result is scaled by 2 and the inverse transform's result by `n`.
+fn vDSP_fft_zrip(
+    env: &mut Environment,
+    setup: FFTSetup,
+    c: ConstPtr<DSPSplitComplex>,
+    ic: vDSP_Stride,
+    log2n: vDSP_Length,
+    direction: FFTDirection,
+) {
+    if !check_fft_args(env, "vDSP_fft_zrip", setup, log2n, direction) {
+        return;
+    }
+    if log2n == 0 {
+        log!("Warning: vDSP_fft_zrip() called with log2n 0, ignoring");
+        return;
+    }
+    let DSPSplitComplex { realp, imagp } = env.mem.read(c);
+    let half_n = 1 << (log2n - 1);
+    let mut re = read_vector(&env.mem, realp, ic, half_n);
+    let mut im = read_vector(&env.mem, imagp, ic, half_n);
+    fft_zrip(&mut re, &mut im, direction == kFFTDirection_Inverse);
+    write_vector(&mut env.mem, realp, ic, &re);
+    write_vector(&mut env.mem, imagp, ic, &im);
+}
+
+/// Convert interleaved complex data to split complex data. `ic` is in units
+/// of `f32`, not of complex numbers.
+fn vDSP_ctoz(
+    env: &mut Environment,
+    c: ConstVoidPtr, // const DSPComplex *
+    ic: vDSP_Stride,
+    z: ConstPtr<DSPSplitComplex>,
+    iz: vDSP_Stride,
+    n: vDSP_Length,
+) {
+    let c: ConstPtr<f32> = c.cast();
+    let DSPSplitComplex { realp, imagp } = env.mem.read(z);
+    let re = read_vector(&env.mem, c, ic, n);
+    let im = read_vector(&env.mem, c + 1, ic, n);
+    write_vector(&mut env.mem, realp, iz, &re);
+    write_vector(&mut env.mem, imagp, iz, &im);
+}
+/// Convert split complex data to interleaved complex data. `ic` is in units
+/// of `f32`, not of complex numbers.
+fn vDSP_ztoc(
+    env: &mut Environment,
+    z: ConstPtr<DSPSplitComplex>,
+    iz: vDSP_Stride,
+    c: MutVoidPtr, // DSPComplex *
+    ic: vDSP_Stride,
+    n: vDSP_Length,
+) {
+    let c: MutPtr<f32> = c.cast();
+    let DSPSplitComplex { realp, imagp } = env.mem.read(z);
+    let re = read_vector(&env.mem, realp, iz, n);
+    let im = read_vector(&env.mem, imagp, iz, n);
+    write_vector(&mut env.mem, c, ic, &re);
+    write_vector(&mut env.mem, c + 1, ic, &im);
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(vDSP_vadd(_, _, _, _, _, _, _)),
+    export_c_func!(vDSP_vsub(_, _, _, _, _, _, _)),
+    export_c_func!(vDSP_vmul(_, _, _, _, _, _, _)),
+    export_c_func!(vDSP_vdiv(_, _, _, _, _, _, _)),
+    export_c_func!(vDSP_vsadd(_, _, _, _, _, _)),
+    export_c_func!(vDSP_vsmul(_, _, _, _, _, _)),
+    export_c_func!(vDSP_vsdiv(_, _, _, _, _, _)),
+    export_c_func!(vDSP_vneg(_, _, _, _, _)),
+    export_c_func!(vDSP_vabs(_, _, _, _, _)),
+    export_c_func!(vDSP_vclr(_, _, _)),
+    export_c_func!(vDSP_vfill(_, _, _, _)),
+    export_c_func!(vDSP_dotpr(_, _, _, _, _, _)),
+    export_c_func!(vDSP_sve(_, _, _, _)),
+    export_c_func!(vDSP_meanv(_, _, _, _)),
+    export_c_func!(vDSP_maxv(_, _, _, _)),
+    export_c_func!(vDSP_minv(_, _, _, _)),
+    export_c_func!(vDSP_mmul(_, _, _, _, _, _, _, _, _)),
+    export_c_func!(vDSP_create_fftsetup(_, _)),
+    export_c_func!(vDSP_destroy_fftsetup(_)),
+    export_c_func!(vDSP_fft_zip(_, _, _, _, _)),
+    export_c_func!(vDSP_fft_zrip(_, _, _, _, _)),
+    export_c_func!(vDSP_ctoz(_, _, _, _, _)),
+    export_c_func!(vDSP_ztoc(_, _, _, _, _)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: &[f32], expected: &[f32]) {
+        assert_eq!(actual.len(), expected.len());
+        for (&a, &e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-4, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn test_fft_zrip_impulse() {
+        // x = [1, 0, 0, 0, 0, 0, 0, 0], packed as even and odd elements.
+        let mut re = [1.0, 0.0, 0.0, 0.0];
+        let mut im = [0.0, 0.0, 0.0, 0.0];
+        fft_zrip(&mut re, &mut im, false);
+        // Every frequency is 1, times 2. The first element holds the DC and
+        // Nyquist components.
+        assert_close(&re, &[2.0, 2.0, 2.0, 2.0]);
+        assert_close(&im, &[2.0, 0.0, 0.0, 0.0]);
+
+        // The round trip is scaled by 2n.
+        fft_zrip(&mut re, &mut im, true);
+        assert_close(&re, &[16.0, 0.0, 0.0, 0.0]);
+        assert_close(&im, &[0.0, 0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_fft_zrip_sine() {
+        // x[t] = sin(2 * pi * t / 8), packed as even and odd elements.
+        let s = std::f32::consts::FRAC_1_SQRT_2;
+        let mut re = [0.0, 1.0, 0.0, -1.0];
+        let mut im = [s, s, -s, -s];
+        fft_zrip(&mut re, &mut im, false);
+        // X[1] = -4i, times 2.
+        assert_close(&re, &[0.0, 0.0, 0.0, 0.0]);
+        assert_close(&im, &[0.0, -8.0, 0.0, 0.0]);
+
+        fft_zrip(&mut re, &mut im, true);
+        assert_close(&re, &[0.0, 16.0, 0.0, -16.0]);
+        assert_close(&im, &[16.0 * s, 16.0 * s, -16.0 * s, -16.0 * s]);
+    }
+}
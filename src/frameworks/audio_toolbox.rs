@@ -24,30 +24,47 @@ macro_rules! return_if_null {
     };
 }
 
+pub mod au_graph;
 pub mod audio_components;
 pub mod audio_file;
+pub mod audio_mixer;
 pub mod audio_queue;
 pub mod audio_services;
 pub mod audio_session;
+pub mod audio_unit;
+pub mod music_player;
 
 #[derive(Default)]
 pub struct State {
+    au_graph: au_graph::State,
     audio_file: audio_file::State,
+    audio_mixer: audio_mixer::State,
     audio_queue: audio_queue::State,
+    audio_services: audio_services::State,
     audio_components: audio_components::State,
+    audio_session: audio_session::State,
+    music_player: music_player::State,
     al_device_and_context: Option<(*mut ALCdevice, *mut ALCcontext)>,
 }
 impl State {
+    /// Whether the internal OpenAL context has been created yet.
+    pub fn has_al_context(&self) -> bool {
+        self.al_device_and_context.is_some()
+    }
+
     pub fn make_al_context_current(&mut self) -> ContextManager {
         if self.al_device_and_context.is_none() {
-            let device = unsafe { al::alcOpenDevice(std::ptr::null()) };
+            // The internal context shares the app's device, so that its
+            // output is mixed with the app's own OpenAL output.
+            let device = self.audio_mixer.open_device();
             assert!(!device.is_null());
-            let context = unsafe { al::alcCreateContext(device, std::ptr::null()) };
+            let attributes = self.audio_session.al_context_attributes();
+            let context = unsafe { al::alcCreateContext(device, attributes.as_ptr()) };
             assert!(!context.is_null());
             log_dbg!(
-                "New internal OpenAL device ({:?}) and context ({:?})",
-                device,
-                context
+                "New internal OpenAL context ({:?}) on device {:?}",
+                context,
+                device
             );
             self.al_device_and_context = Some((device, context));
         }
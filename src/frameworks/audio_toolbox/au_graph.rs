@@ -0,0 +1,759 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `AUGraph.h` (Audio Unit Processing Graph Services)
+//!
+//! A graph is a thin layer over the [audio units](super::audio_unit) it
+//! contains: opening it instantiates the units, and connections and input
+//! callbacks are applied to them as the equivalent audio unit properties. This
+//! means the same limitations apply, i.e. only a RemoteIO output node, with an
+//! optional multichannel mixer connected to it, is actually rendered.
+
+use super::audio_components::{
+    AURenderCallbackStruct, AudioComponentDescription, AudioComponentFindNext,
+    AudioComponentInstanceDispose, AudioComponentInstanceNew, AudioUnitKind,
+    State as AudioComponentsState,
+};
+use super::audio_unit::{
+    kAudioUnitProperty_MakeConnection, kAudioUnitProperty_SetRenderCallback, kAudioUnitScope_Input,
+    AudioOutputUnitStart, AudioOutputUnitStop, AudioUnit, AudioUnitConnection, AudioUnitElement,
+    AudioUnitInitialize, AudioUnitSetProperty, AudioUnitUninitialize,
+};
+use crate::abi::GuestFunction;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::carbon_core::OSStatus;
+use crate::mem::{guest_size_of, ConstPtr, ConstVoidPtr, MutPtr, SafeRead};
+use crate::objc::nil;
+use crate::Environment;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Default)]
+pub struct State {
+    graphs: HashMap<AUGraph, AUGraphHostObject>,
+}
+impl State {
+    pub fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.audio_toolbox.au_graph
+    }
+}
+
+#[repr(C, packed)]
+pub struct OpaqueAUGraph {
+    _filler: u8,
+}
+unsafe impl SafeRead for OpaqueAUGraph {}
+pub type AUGraph = MutPtr<OpaqueAUGraph>;
+
+type AUNode = i32;
+
+const kAUGraphErr_NodeNotFound: OSStatus = -10860;
+const kAUGraphErr_InvalidConnection: OSStatus = -10861;
+const kAUGraphErr_OutputNodeErr: OSStatus = -10862;
+const kAUGraphErr_CannotDoInCurrentContext: OSStatus = -10863;
+const kAUGraphErr_InvalidAudioUnit: OSStatus = -10864;
+
+struct Node {
+    description: AudioComponentDescription,
+    /// [None] until the graph is opened.
+    unit: Option<AudioUnit>,
+}
+
+/// What feeds an input of a node.
+#[derive(Clone)]
+enum NodeInput {
+    Connection {
+        source_node: AUNode,
+        source_output: u32,
+    },
+    Callback(AURenderCallbackStruct),
+}
+
+#[derive(Default)]
+struct AUGraphHostObject {
+    nodes: BTreeMap<AUNode, Node>,
+    next_node: AUNode,
+    /// Inputs of nodes, keyed by destination node and input number.
+    inputs: BTreeMap<(AUNode, AudioUnitElement), NodeInput>,
+    /// Inputs that have been changed since the graph was last initialized or
+    /// updated, and still need to be applied to the audio units.
+    changed_inputs: Vec<(AUNode, AudioUnitElement)>,
+    is_open: bool,
+    is_initialized: bool,
+    is_running: bool,
+}
+
+fn NewAUGraph(env: &mut Environment, out_graph: MutPtr<AUGraph>) -> OSStatus {
+    return_if_null!(out_graph);
+
+    let graph = env.mem.alloc_and_write(OpaqueAUGraph { _filler: 0 });
+    State::get(&mut env.framework_state)
+        .graphs
+        .insert(graph, Default::default());
+    env.mem.write(out_graph, graph);
+    log_dbg!("NewAUGraph() => {:?}", graph);
+    0 // success
+}
+
+fn DisposeAUGraph(env: &mut Environment, in_graph: AUGraph) -> OSStatus {
+    return_if_null!(in_graph);
+
+    AUGraphStop(env, in_graph);
+    AUGraphClose(env, in_graph);
+    State::get(&mut env.framework_state)
+        .graphs
+        .remove(&in_graph)
+        .unwrap();
+    env.mem.free(in_graph.cast());
+    log_dbg!("DisposeAUGraph({:?})", in_graph);
+    0 // success
+}
+
+fn AUGraphAddNode(
+    env: &mut Environment,
+    in_graph: AUGraph,
+    in_description: ConstPtr<AudioComponentDescription>,
+    out_node: MutPtr<AUNode>,
+) -> OSStatus {
+    return_if_null!(in_graph);
+    return_if_null!(in_description);
+    return_if_null!(out_node);
+
+    let description = env.mem.read(in_description);
+    let host_object = State::get(&mut env.framework_state)
+        .graphs
+        .get_mut(&in_graph)
+        .unwrap();
+    host_object.next_node += 1;
+    let node = host_object.next_node;
+    let is_open = host_object.is_open;
+    host_object.nodes.insert(
+        node,
+        Node {
+            description,
+            unit: None,
+        },
+    );
+    env.mem.write(out_node, node);
+
+    let result = if is_open {
+        open_node(env, in_graph, node)
+    } else {
+        0 // success
+    };
+    log_dbg!(
+        "AUGraphAddNode({:?}, {:?}) => {}, node {}",
+        in_graph,
+        in_description,
+        result,
+        node
+    );
+    result
+}
+
+fn AUGraphRemoveNode(env: &mut Environment, in_graph: AUGraph, in_node: AUNode) -> OSStatus {
+    return_if_null!(in_graph);
+
+    let host_object = State::get(&mut env.framework_state)
+        .graphs
+        .get_mut(&in_graph)
+        .unwrap();
+    let Some(node) = host_object.nodes.remove(&in_node) else {
+        return kAUGraphErr_NodeNotFound;
+    };
+    // Inputs fed by the node are disconnected too.
+    let removed_inputs: Vec<_> = host_object
+        .inputs
+        .iter()
+        .filter(|&(&(dest_node, _), input)| match *input {
+            NodeInput::Connection { source_node, .. } => {
+                dest_node == in_node || source_node == in_node
+            }
+            NodeInput::Callback(_) => dest_node == in_node,
+        })
+        .map(|(&key, _)| key)
+        .collect();
+    for key in removed_inputs {
+        host_object.inputs.remove(&key);
+        if key.0 != in_node {
+            host_object.changed_inputs.push(key);
+        }
+    }
+    if let Some(unit) = node.unit {
+        AudioComponentInstanceDispose(env, unit);
+    }
+    log_dbg!("AUGraphRemoveNode({:?}, {})", in_graph, in_node);
+    0 // success
+}
+
+/// Instantiate the audio unit for a node.
+fn open_node(env: &mut Environment, graph: AUGraph, node: AUNode) -> OSStatus {
+    let description = State::get(&mut env.framework_state).graphs[&graph].nodes[&node].description;
+    let description_ptr = env.mem.alloc_and_write(description);
+    let component = AudioComponentFindNext(env, nil.cast(), description_ptr.cast_const());
+    env.mem.free(description_ptr.cast());
+    if component.is_null() {
+        log!(
+            "Warning: No audio component matches node {} of graph {:?}",
+            node,
+            graph
+        );
+        return kAUGraphErr_InvalidAudioUnit;
+    }
+
+    let unit_ptr: MutPtr<AudioUnit> = env.mem.alloc_and_write(nil.cast());
+    let result = AudioComponentInstanceNew(env, component, unit_ptr);
+    let unit = env.mem.read(unit_ptr);
+    env.mem.free(unit_ptr.cast());
+    if result != 0 {
+        return result;
+    }
+    State::get(&mut env.framework_state)
+        .graphs
+        .get_mut(&graph)
+        .unwrap()
+        .nodes
+        .get_mut(&node)
+        .unwrap()
+        .unit = Some(unit);
+    0 // success
+}
+
+fn AUGraphOpen(env: &mut Environment, in_graph: AUGraph) -> OSStatus {
+    return_if_null!(in_graph);
+
+    let host_object = State::get(&mut env.framework_state)
+        .graphs
+        .get_mut(&in_graph)
+        .unwrap();
+    if host_object.is_open {
+        return 0;
+    }
+    host_object.is_open = true;
+    let nodes: Vec<AUNode> = host_object.nodes.keys().copied().collect();
+    let mut result = 0;
+    for node in nodes {
+        result = open_node(env, in_graph, node);
+        if result != 0 {
+            break;
+        }
+    }
+    log_dbg!("AUGraphOpen({:?}) => {}", in_graph, result);
+    result
+}
+
+fn AUGraphClose(env: &mut Environment, in_graph: AUGraph) -> OSStatus {
+    return_if_null!(in_graph);
+
+    AUGraphUninitialize(env, in_graph);
+    let host_object = State::get(&mut env.framework_state)
+        .graphs
+        .get_mut(&in_graph)
+        .unwrap();
+    host_object.is_open = false;
+    // All inputs will need to be applied again to the new units if the graph
+    // is reopened.
+    host_object.changed_inputs = host_object.inputs.keys().copied().collect();
+    let units: Vec<AudioUnit> = host_object
+        .nodes
+        .values_mut()
+        .filter_map(|node| node.unit.take())
+        .collect();
+    for unit in units {
+        AudioComponentInstanceDispose(env, unit);
+    }
+    log_dbg!("AUGraphClose({:?})", in_graph);
+    0 // success
+}
+
+fn AUGraphNodeInfo(
+    env: &mut Environment,
+    in_graph: AUGraph,
+    in_node: AUNode,
+    out_description: MutPtr<AudioComponentDescription>,
+    out_audio_unit: MutPtr<AudioUnit>,
+) -> OSStatus {
+    return_if_null!(in_graph);
+
+    let Some(node) = State::get(&mut env.framework_state).graphs[&in_graph]
+        .nodes
+        .get(&in_node)
+    else {
+        return kAUGraphErr_NodeNotFound;
+    };
+    let description = node.description;
+    let unit = node.unit.unwrap_or(nil.cast());
+    if !out_description.is_null() {
+        env.mem.write(out_description, description);
+    }
+    if !out_audio_unit.is_null() {
+        env.mem.write(out_audio_unit, unit);
+    }
+    log_dbg!(
+        "AUGraphNodeInfo({:?}, {}, {:?}, {:?}) => unit {:?}",
+        in_graph,
+        in_node,
+        out_description,
+        out_audio_unit,
+        unit
+    );
+    0 // success
+}
+
+fn AUGraphGetNodeCount(
+    env: &mut Environment,
+    in_graph: AUGraph,
+    out_number_of_nodes: MutPtr<u32>,
+) -> OSStatus {
+    return_if_null!(in_graph);
+    return_if_null!(out_number_of_nodes);
+
+    let count = State::get(&mut env.framework_state).graphs[&in_graph]
+        .nodes
+        .len();
+    env.mem
+        .write(out_number_of_nodes, count.try_into().unwrap());
+    0 // success
+}
+
+fn AUGraphGetIndNode(
+    env: &mut Environment,
+    in_graph: AUGraph,
+    in_index: u32,
+    out_node: MutPtr<AUNode>,
+) -> OSStatus {
+    return_if_null!(in_graph);
+    return_if_null!(out_node);
+
+    let Some(&node) = State::get(&mut env.framework_state).graphs[&in_graph]
+        .nodes
+        .keys()
+        .nth(in_index as usize)
+    else {
+        return kAUGraphErr_NodeNotFound;
+    };
+    env.mem.write(out_node, node);
+    0 // success
+}
+
+/// Record a change to a node's input, to be applied when the graph is next
+/// initialized or updated.
+fn set_node_input(
+    env: &mut Environment,
+    graph: AUGraph,
+    dest_node: AUNode,
+    dest_input: AudioUnitElement,
+    input: Option<NodeInput>,
+) -> OSStatus {
+    let host_object = State::get(&mut env.framework_state)
+        .graphs
+        .get_mut(&graph)
+        .unwrap();
+    if !host_object.nodes.contains_key(&dest_node) {
+        return kAUGraphErr_NodeNotFound;
+    }
+    let key = (dest_node, dest_input);
+    if let Some(input) = input {
+        host_object.inputs.insert(key, input);
+    } else if host_object.inputs.remove(&key).is_none() {
+        return 0;
+    }
+    host_object.changed_inputs.push(key);
+    0 // success
+}
+
+fn AUGraphConnectNodeInput(
+    env: &mut Environment,
+    in_graph: AUGraph,
+    in_source_node: AUNode,
+    in_source_output_number: u32,
+    in_dest_node: AUNode,
+    in_dest_input_number: u32,
+) -> OSStatus {
+    return_if_null!(in_graph);
+
+    let host_object = &State::get(&mut env.framework_state).graphs[&in_graph];
+    let result = if !host_object.nodes.contains_key(&in_source_node) {
+        kAUGraphErr_NodeNotFound
+    } else if in_source_node == in_dest_node
+        || host_object.inputs.values().any(|input| {
+            matches!(input, &NodeInput::Connection { source_node, source_output }
+                if source_node == in_source_node && source_output == in_source_output_number)
+        })
+    {
+        // Each output can only feed one input.
+        kAUGraphErr_InvalidConnection
+    } else {
+        set_node_input(
+            env,
+            in_graph,
+            in_dest_node,
+            in_dest_input_number,
+            Some(NodeInput::Connection {
+                source_node: in_source_node,
+                source_output: in_source_output_number,
+            }),
+        )
+    };
+    log_dbg!(
+        "AUGraphConnectNodeInput({:?}, {}, {}, {}, {}) => {}",
+        in_graph,
+        in_source_node,
+        in_source_output_number,
+        in_dest_node,
+        in_dest_input_number,
+        result
+    );
+    result
+}
+
+fn AUGraphDisconnectNodeInput(
+    env: &mut Environment,
+    in_graph: AUGraph,
+    in_dest_node: AUNode,
+    in_dest_input_number: u32,
+) -> OSStatus {
+    return_if_null!(in_graph);
+
+    let result = set_node_input(env, in_graph, in_dest_node, in_dest_input_number, None);
+    log_dbg!(
+        "AUGraphDisconnectNodeInput({:?}, {}, {}) => {}",
+        in_graph,
+        in_dest_node,
+        in_dest_input_number,
+        result
+    );
+    result
+}
+
+fn AUGraphSetNodeInputCallback(
+    env: &mut Environment,
+    in_graph: AUGraph,
+    in_dest_node: AUNode,
+    in_dest_input_number: u32,
+    in_input_callback: ConstPtr<AURenderCallbackStruct>,
+) -> OSStatus {
+    return_if_null!(in_graph);
+    return_if_null!(in_input_callback);
+
+    let callback = env.mem.read(in_input_callback);
+    let result = set_node_input(
+        env,
+        in_graph,
+        in_dest_node,
+        in_dest_input_number,
+        Some(NodeInput::Callback(callback)),
+    );
+    log_dbg!(
+        "AUGraphSetNodeInputCallback({:?}, {}, {}, {:?}) => {}",
+        in_graph,
+        in_dest_node,
+        in_dest_input_number,
+        in_input_callback,
+        result
+    );
+    result
+}
+
+fn AUGraphClearConnections(env: &mut Environment, in_graph: AUGraph) -> OSStatus {
+    return_if_null!(in_graph);
+
+    let host_object = State::get(&mut env.framework_state)
+        .graphs
+        .get_mut(&in_graph)
+        .unwrap();
+    let inputs = std::mem::take(&mut host_object.inputs);
+    host_object.changed_inputs.extend(inputs.into_keys());
+    log_dbg!("AUGraphClearConnections({:?})", in_graph);
+    0 // success
+}
+
+/// [NodeInput] resolved to the audio units of an open graph.
+enum UnitInput {
+    Connection(AudioUnit, u32),
+    Callback(AURenderCallbackStruct),
+}
+
+/// Apply changed inputs to the audio units, using the same properties an app
+/// would use to connect audio units directly.
+fn apply_changed_inputs(env: &mut Environment, graph: AUGraph) -> OSStatus {
+    let host_object = State::get(&mut env.framework_state)
+        .graphs
+        .get_mut(&graph)
+        .unwrap();
+    let mut changed_inputs = std::mem::take(&mut host_object.changed_inputs);
+    changed_inputs.sort();
+    changed_inputs.dedup();
+    let changes: Vec<_> = changed_inputs
+        .into_iter()
+        .filter_map(|(dest_node, dest_input)| {
+            // Nodes removed since the change don't need updating.
+            let dest_unit = host_object.nodes.get(&dest_node)?.unit?;
+            let input = match host_object.inputs.get(&(dest_node, dest_input)) {
+                Some(&NodeInput::Connection {
+                    source_node,
+                    source_output,
+                }) => UnitInput::Connection(host_object.nodes[&source_node].unit?, source_output),
+                Some(NodeInput::Callback(callback)) => UnitInput::Callback(callback.clone()),
+                // A null callback removes both callbacks and connections.
+                None => UnitInput::Callback(AURenderCallbackStruct {
+                    input_proc: GuestFunction::from_addr_with_thumb_bit(0),
+                    input_proc_ref_con: ConstVoidPtr::null(),
+                }),
+            };
+            Some((dest_unit, dest_input, input))
+        })
+        .collect();
+
+    for (dest_unit, dest_input, input) in changes {
+        let result = match input {
+            UnitInput::Connection(source_unit, source_output) => {
+                let connection = AudioUnitConnection {
+                    source_audio_unit: source_unit,
+                    source_output_number: source_output,
+                    dest_input_number: dest_input,
+                };
+                let connection_ptr = env.mem.alloc_and_write(connection);
+                let result = AudioUnitSetProperty(
+                    env,
+                    dest_unit,
+                    kAudioUnitProperty_MakeConnection,
+                    kAudioUnitScope_Input,
+                    dest_input,
+                    connection_ptr.cast_void().cast_const(),
+                    guest_size_of::<AudioUnitConnection>(),
+                );
+                env.mem.free(connection_ptr.cast());
+                result
+            }
+            UnitInput::Callback(callback) => {
+                let callback_ptr = env.mem.alloc_and_write(callback);
+                let result = AudioUnitSetProperty(
+                    env,
+                    dest_unit,
+                    kAudioUnitProperty_SetRenderCallback,
+                    kAudioUnitScope_Input,
+                    dest_input,
+                    callback_ptr.cast_void().cast_const(),
+                    guest_size_of::<AURenderCallbackStruct>(),
+                );
+                env.mem.free(callback_ptr.cast());
+                result
+            }
+        };
+        if result != 0 {
+            log!(
+                "Warning: Couldn't apply graph {:?} input {} of {:?}: {}",
+                graph,
+                dest_input,
+                dest_unit,
+                result
+            );
+            return kAUGraphErr_InvalidConnection;
+        }
+    }
+    0 // success
+}
+
+fn AUGraphInitialize(env: &mut Environment, in_graph: AUGraph) -> OSStatus {
+    return_if_null!(in_graph);
+
+    // Initializing opens the graph if the app hasn't done so already.
+    let result = AUGraphOpen(env, in_graph);
+    if result != 0 {
+        return result;
+    }
+    if State::get(&mut env.framework_state).graphs[&in_graph].is_initialized {
+        return 0;
+    }
+    let result = apply_changed_inputs(env, in_graph);
+    if result != 0 {
+        return result;
+    }
+    let host_object = State::get(&mut env.framework_state)
+        .graphs
+        .get_mut(&in_graph)
+        .unwrap();
+    host_object.is_initialized = true;
+    let units: Vec<AudioUnit> = host_object
+        .nodes
+        .values()
+        .filter_map(|node| node.unit)
+        .collect();
+    for unit in units {
+        AudioUnitInitialize(env, unit);
+    }
+    log_dbg!("AUGraphInitialize({:?}) => 0", in_graph);
+    0 // success
+}
+
+fn AUGraphUninitialize(env: &mut Environment, in_graph: AUGraph) -> OSStatus {
+    return_if_null!(in_graph);
+
+    AUGraphStop(env, in_graph);
+    let host_object = State::get(&mut env.framework_state)
+        .graphs
+        .get_mut(&in_graph)
+        .unwrap();
+    if !std::mem::take(&mut host_object.is_initialized) {
+        return 0;
+    }
+    let units: Vec<AudioUnit> = host_object
+        .nodes
+        .values()
+        .filter_map(|node| node.unit)
+        .collect();
+    for unit in units {
+        AudioUnitUninitialize(env, unit);
+    }
+    log_dbg!("AUGraphUninitialize({:?})", in_graph);
+    0 // success
+}
+
+fn AUGraphUpdate(env: &mut Environment, in_graph: AUGraph, out_is_updated: MutPtr<u8>) -> OSStatus {
+    return_if_null!(in_graph);
+
+    // Changes are applied synchronously, so the update is always complete.
+    let result = if State::get(&mut env.framework_state).graphs[&in_graph].is_initialized {
+        apply_changed_inputs(env, in_graph)
+    } else {
+        0 // success
+    };
+    if !out_is_updated.is_null() {
+        env.mem.write(out_is_updated, (result == 0).into());
+    }
+    log_dbg!(
+        "AUGraphUpdate({:?}, {:?}) => {}",
+        in_graph,
+        out_is_updated,
+        result
+    );
+    result
+}
+
+/// Find the audio unit of the graph's output node, which drives rendering.
+fn output_unit(env: &mut Environment, graph: AUGraph) -> Option<AudioUnit> {
+    let units: Vec<AudioUnit> = State::get(&mut env.framework_state).graphs[&graph]
+        .nodes
+        .values()
+        .filter_map(|node| node.unit)
+        .collect();
+    let instances = &AudioComponentsState::get(&mut env.framework_state).audio_component_instances;
+    units.into_iter().find(|unit| {
+        matches!(
+            instances[unit].kind,
+            AudioUnitKind::RemoteIO | AudioUnitKind::GenericOutput
+        )
+    })
+}
+
+fn AUGraphStart(env: &mut Environment, in_graph: AUGraph) -> OSStatus {
+    return_if_null!(in_graph);
+
+    let host_object = &State::get(&mut env.framework_state).graphs[&in_graph];
+    if !host_object.is_initialized {
+        log!(
+            "Warning: AUGraphStart() on uninitialized graph {:?}",
+            in_graph
+        );
+        return kAUGraphErr_CannotDoInCurrentContext;
+    }
+    if host_object.is_running {
+        return 0;
+    }
+    let Some(unit) = output_unit(env, in_graph) else {
+        log!(
+            "Warning: AUGraphStart() on graph {:?} without an output node",
+            in_graph
+        );
+        return kAUGraphErr_OutputNodeErr;
+    };
+    let result = AudioOutputUnitStart(env, unit);
+    if result == 0 {
+        State::get(&mut env.framework_state)
+            .graphs
+            .get_mut(&in_graph)
+            .unwrap()
+            .is_running = true;
+    }
+    log_dbg!("AUGraphStart({:?}) => {}", in_graph, result);
+    result
+}
+
+fn AUGraphStop(env: &mut Environment, in_graph: AUGraph) -> OSStatus {
+    return_if_null!(in_graph);
+
+    let host_object = State::get(&mut env.framework_state)
+        .graphs
+        .get_mut(&in_graph)
+        .unwrap();
+    if !std::mem::take(&mut host_object.is_running) {
+        return 0;
+    }
+    let result = match output_unit(env, in_graph) {
+        Some(unit) => AudioOutputUnitStop(env, unit),
+        None => 0,
+    };
+    log_dbg!("AUGraphStop({:?}) => {}", in_graph, result);
+    result
+}
+
+fn AUGraphIsOpen(env: &mut Environment, in_graph: AUGraph, out_is_open: MutPtr<u8>) -> OSStatus {
+    return_if_null!(in_graph);
+    return_if_null!(out_is_open);
+
+    let is_open = State::get(&mut env.framework_state).graphs[&in_graph].is_open;
+    env.mem.write(out_is_open, is_open.into());
+    0 // success
+}
+
+fn AUGraphIsInitialized(
+    env: &mut Environment,
+    in_graph: AUGraph,
+    out_is_initialized: MutPtr<u8>,
+) -> OSStatus {
+    return_if_null!(in_graph);
+    return_if_null!(out_is_initialized);
+
+    let is_initialized = State::get(&mut env.framework_state).graphs[&in_graph].is_initialized;
+    env.mem.write(out_is_initialized, is_initialized.into());
+    0 // success
+}
+
+fn AUGraphIsRunning(
+    env: &mut Environment,
+    in_graph: AUGraph,
+    out_is_running: MutPtr<u8>,
+) -> OSStatus {
+    return_if_null!(in_graph);
+    return_if_null!(out_is_running);
+
+    let is_running = State::get(&mut env.framework_state).graphs[&in_graph].is_running;
+    env.mem.write(out_is_running, is_running.into());
+    0 // success
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(NewAUGraph(_)),
+    export_c_func!(DisposeAUGraph(_)),
+    export_c_func!(AUGraphAddNode(_, _, _)),
+    export_c_func!(AUGraphRemoveNode(_, _)),
+    export_c_func!(AUGraphOpen(_)),
+    export_c_func!(AUGraphClose(_)),
+    export_c_func!(AUGraphNodeInfo(_, _, _, _)),
+    export_c_func!(AUGraphGetNodeCount(_, _)),
+    export_c_func!(AUGraphGetIndNode(_, _, _)),
+    export_c_func!(AUGraphConnectNodeInput(_, _, _, _, _)),
+    export_c_func!(AUGraphDisconnectNodeInput(_, _, _)),
+    export_c_func!(AUGraphSetNodeInputCallback(_, _, _, _)),
+    export_c_func!(AUGraphClearConnections(_)),
+    export_c_func!(AUGraphInitialize(_)),
+    export_c_func!(AUGraphUninitialize(_)),
+    export_c_func!(AUGraphUpdate(_, _)),
+    export_c_func!(AUGraphStart(_)),
+    export_c_func!(AUGraphStop(_)),
+    export_c_func!(AUGraphIsOpen(_, _)),
+    export_c_func!(AUGraphIsInitialized(_, _)),
+    export_c_func!(AUGraphIsRunning(_, _)),
+];
@@ -8,22 +8,71 @@
 use std::collections::HashMap;
 
 use crate::abi::GuestFunction;
+use crate::audio::openal::al_types::ALuint;
 use crate::dyld::FunctionExports;
 use crate::environment::Environment;
 use crate::export_c_func;
+use crate::frameworks::audio_toolbox::audio_unit;
 use crate::frameworks::carbon_core::{paramErr, OSStatus};
-use crate::frameworks::core_audio_types::fourcc;
-use crate::mem::{ConstPtr, ConstVoidPtr, MutPtr, SafeRead};
+use crate::frameworks::core_audio_types::{fourcc, AudioStreamBasicDescription};
+use crate::mem::{ConstPtr, ConstVoidPtr, MutPtr, MutVoidPtr, SafeRead};
 use crate::objc::nil;
 
 const kAudioUnitType_Output: u32 = fourcc(b"auou");
+const kAudioUnitType_Mixer: u32 = fourcc(b"aumx");
+const kAudioUnitSubType_GenericOutput: u32 = fourcc(b"genr");
 const kAudioUnitSubType_RemoteIO: u32 = fourcc(b"rioc");
+const kAudioUnitSubType_MultiChannelMixer: u32 = fourcc(b"mcmx");
 const kAudioUnitManufacturer_Apple: u32 = fourcc(b"appl");
 
+/// The kinds of audio unit that can be instantiated. Only RemoteIO produces
+/// sound, and a multichannel mixer is rendered when it's connected to RemoteIO.
+/// Generic output units can be created and configured, so that apps probing
+/// for them work, but nothing renders them yet.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AudioUnitKind {
+    #[default]
+    RemoteIO,
+    GenericOutput,
+    MultiChannelMixer,
+}
+
+struct RegisteredComponent {
+    kind: AudioUnitKind,
+    component_type: u32,
+    component_sub_type: u32,
+    component_manufacturer: u32,
+}
+
+/// All components [AudioComponentFindNext] can return, in enumeration order.
+const REGISTERED_COMPONENTS: &[RegisteredComponent] = &[
+    RegisteredComponent {
+        kind: AudioUnitKind::RemoteIO,
+        component_type: kAudioUnitType_Output,
+        component_sub_type: kAudioUnitSubType_RemoteIO,
+        component_manufacturer: kAudioUnitManufacturer_Apple,
+    },
+    RegisteredComponent {
+        kind: AudioUnitKind::GenericOutput,
+        component_type: kAudioUnitType_Output,
+        component_sub_type: kAudioUnitSubType_GenericOutput,
+        component_manufacturer: kAudioUnitManufacturer_Apple,
+    },
+    RegisteredComponent {
+        kind: AudioUnitKind::MultiChannelMixer,
+        component_type: kAudioUnitType_Mixer,
+        component_sub_type: kAudioUnitSubType_MultiChannelMixer,
+        component_manufacturer: kAudioUnitManufacturer_Apple,
+    },
+];
+
 #[derive(Default)]
 pub struct State {
     pub audio_component_instances:
         HashMap<AudioComponentInstance, AudioComponentInstanceHostObject>,
+    /// Guest objects for [REGISTERED_COMPONENTS], with the same indices.
+    /// Allocated on first use.
+    components: Vec<AudioComponent>,
 }
 impl State {
     pub fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
@@ -31,8 +80,78 @@ impl State {
     }
 }
 
+fn registered_components(env: &mut Environment) -> &[AudioComponent] {
+    let state = State::get(&mut env.framework_state);
+    if state.components.is_empty() {
+        state.components = REGISTERED_COMPONENTS
+            .iter()
+            .map(|_| env.mem.alloc_and_write(OpaqueAudioComponent { _pad: 0 }))
+            .collect();
+    }
+    &state.components
+}
+
+/// Find the index in [REGISTERED_COMPONENTS] of a guest component object.
+fn component_index(env: &mut Environment, component: AudioComponent) -> Option<usize> {
+    registered_components(env)
+        .iter()
+        .position(|&other| other == component)
+}
+
 #[derive(Default, Clone)]
-pub struct AudioComponentInstanceHostObject {}
+pub struct AudioComponentInstanceHostObject {
+    pub kind: AudioUnitKind,
+    pub render_callback: Option<AURenderCallbackStruct>,
+    /// Format of the audio provided by the render callback. [None] means the
+    /// default format.
+    pub stream_format: Option<AudioStreamBasicDescription>,
+    pub max_frames_per_slice: Option<u32>,
+    pub initialized: bool,
+    pub is_running: bool,
+    /// Number of frames rendered so far, used for the render callback's
+    /// timestamps.
+    pub sample_time: f64,
+    pub al_source: Option<ALuint>,
+    pub al_unused_buffers: Vec<ALuint>,
+    /// For RemoteIO: the unit connected to its output element with
+    /// `kAudioUnitProperty_MakeConnection`, which is rendered instead of
+    /// calling the render callback.
+    pub input_connection: Option<AudioComponentInstance>,
+    /// For RemoteIO: the output volume, which is applied by the
+    /// [audio mixer](super::audio_mixer).
+    pub output_volume: f32,
+    /// For the multichannel mixer: the input buses.
+    pub mixer_inputs: Vec<MixerInput>,
+    /// For the multichannel mixer: the volume of the mixed output.
+    pub mixer_output_volume: f32,
+    /// Listeners added with `AudioUnitAddPropertyListener`: the property ID,
+    /// the callback and its user data.
+    pub property_listeners: Vec<(u32, GuestFunction, MutVoidPtr)>,
+}
+
+/// An input bus of a multichannel mixer unit.
+#[derive(Clone)]
+pub struct MixerInput {
+    pub render_callback: Option<AURenderCallbackStruct>,
+    /// [None] means the default format, like for RemoteIO.
+    pub stream_format: Option<AudioStreamBasicDescription>,
+    pub volume: f32,
+    pub enabled: bool,
+}
+impl Default for MixerInput {
+    fn default() -> Self {
+        MixerInput {
+            render_callback: None,
+            stream_format: None,
+            volume: 1.0,
+            enabled: true,
+        }
+    }
+}
+
+/// Number of input buses a new multichannel mixer unit has. This can be
+/// changed with `kAudioUnitProperty_ElementCount`.
+const DEFAULT_MIXER_INPUT_COUNT: usize = 8;
 
 #[derive(Clone)]
 #[repr(C, packed)]
@@ -43,10 +162,12 @@ pub struct AURenderCallbackStruct {
 unsafe impl SafeRead for AURenderCallbackStruct {}
 
 #[repr(C, packed)]
-struct OpaqueAudioComponent {}
+pub(super) struct OpaqueAudioComponent {
+    _pad: u8,
+}
 unsafe impl SafeRead for OpaqueAudioComponent {}
 
-type AudioComponent = MutPtr<OpaqueAudioComponent>;
+pub(super) type AudioComponent = MutPtr<OpaqueAudioComponent>;
 
 pub type AURenderCallback = GuestFunction;
 
@@ -58,8 +179,9 @@ unsafe impl SafeRead for OpaqueAudioComponentInstance {}
 
 pub type AudioComponentInstance = MutPtr<OpaqueAudioComponentInstance>;
 
+#[derive(Clone, Copy)]
 #[repr(C, packed)]
-struct AudioComponentDescription {
+pub(super) struct AudioComponentDescription {
     component_type: u32,
     component_sub_type: u32,
     component_manufacturer: u32,
@@ -67,20 +189,51 @@ struct AudioComponentDescription {
     component_flags_mask: u32,
 }
 unsafe impl SafeRead for AudioComponentDescription {}
+impl AudioComponentDescription {
+    /// Zero fields in a description used for searching are wildcards.
+    fn matches(&self, component: &RegisteredComponent) -> bool {
+        fn field_matches(wanted: u32, actual: u32) -> bool {
+            wanted == 0 || wanted == actual
+        }
+        // None of the registered components have any flags set.
+        field_matches(self.component_type, component.component_type)
+            && field_matches(self.component_sub_type, component.component_sub_type)
+            && field_matches(
+                self.component_manufacturer,
+                component.component_manufacturer,
+            )
+            && self.component_flags & self.component_flags_mask == 0
+    }
+}
 
-fn AudioComponentFindNext(
+pub(super) fn AudioComponentFindNext(
     env: &mut Environment,
     in_component: AudioComponent,
     in_desc: ConstPtr<AudioComponentDescription>,
 ) -> AudioComponent {
-    let audio_comp_descr = env.mem.read(in_desc);
-    assert!(audio_comp_descr.component_type == kAudioUnitType_Output);
-    assert!(audio_comp_descr.component_sub_type == kAudioUnitSubType_RemoteIO);
-    assert!(audio_comp_descr.component_manufacturer == kAudioUnitManufacturer_Apple);
-
-    let out_component = nil.cast();
-    log!(
-        "TODO: AudioComponentFindNext({:?}, {:?}) -> {:?}",
+    let start = if in_component.is_null() {
+        0
+    } else {
+        match component_index(env, in_component) {
+            Some(index) => index + 1,
+            None => REGISTERED_COMPONENTS.len(),
+        }
+    };
+    // A null description matches everything.
+    let audio_comp_descr = (!in_desc.is_null()).then(|| env.mem.read(in_desc));
+
+    let out_component = REGISTERED_COMPONENTS
+        .iter()
+        .enumerate()
+        .skip(start)
+        .find(|(_, component)| {
+            audio_comp_descr
+                .as_ref()
+                .map_or(true, |descr| descr.matches(component))
+        })
+        .map_or(nil.cast(), |(index, _)| registered_components(env)[index]);
+    log_dbg!(
+        "AudioComponentFindNext({:?}, {:?}) -> {:?}",
         in_component,
         in_desc,
         out_component
@@ -88,12 +241,79 @@ fn AudioComponentFindNext(
     out_component
 }
 
-fn AudioComponentInstanceNew(
+fn AudioComponentCount(env: &mut Environment, in_desc: ConstPtr<AudioComponentDescription>) -> u32 {
+    let count = if in_desc.is_null() {
+        REGISTERED_COMPONENTS.len()
+    } else {
+        let audio_comp_descr = env.mem.read(in_desc);
+        REGISTERED_COMPONENTS
+            .iter()
+            .filter(|component| audio_comp_descr.matches(component))
+            .count()
+    };
+    let count = count.try_into().unwrap();
+    log_dbg!("AudioComponentCount({:?}) -> {}", in_desc, count);
+    count
+}
+
+fn AudioComponentGetDescription(
+    env: &mut Environment,
+    in_component: AudioComponent,
+    out_desc: MutPtr<AudioComponentDescription>,
+) -> OSStatus {
+    let result = match component_index(env, in_component) {
+        Some(index) if !out_desc.is_null() => {
+            let component = &REGISTERED_COMPONENTS[index];
+            env.mem.write(
+                out_desc,
+                AudioComponentDescription {
+                    component_type: component.component_type,
+                    component_sub_type: component.component_sub_type,
+                    component_manufacturer: component.component_manufacturer,
+                    component_flags: 0,
+                    component_flags_mask: 0,
+                },
+            );
+            0
+        }
+        _ => paramErr,
+    };
+    log_dbg!(
+        "AudioComponentGetDescription({:?}, {:?}) -> {}",
+        in_component,
+        out_desc,
+        result
+    );
+    result
+}
+
+pub(super) fn AudioComponentInstanceNew(
     env: &mut Environment,
     in_component: AudioComponent,
     out_instance: MutPtr<AudioComponentInstance>,
 ) -> OSStatus {
-    let host_object = AudioComponentInstanceHostObject::default();
+    let Some(index) = component_index(env, in_component) else {
+        log!(
+            "Warning: AudioComponentInstanceNew() with unknown component {:?}",
+            in_component
+        );
+        return paramErr;
+    };
+    let kind = REGISTERED_COMPONENTS[index].kind;
+    let host_object = if kind == AudioUnitKind::MultiChannelMixer {
+        AudioComponentInstanceHostObject {
+            kind,
+            mixer_inputs: vec![MixerInput::default(); DEFAULT_MIXER_INPUT_COUNT],
+            mixer_output_volume: 1.0,
+            ..Default::default()
+        }
+    } else {
+        AudioComponentInstanceHostObject {
+            kind,
+            output_volume: 1.0,
+            ..Default::default()
+        }
+    };
 
     let guest_instance: AudioComponentInstance = env
         .mem
@@ -114,16 +334,18 @@ fn AudioComponentInstanceNew(
     result
 }
 
-fn AudioComponentInstanceDispose(
+pub(super) fn AudioComponentInstanceDispose(
     env: &mut Environment,
     in_instance: AudioComponentInstance,
 ) -> OSStatus {
     let result = if in_instance.is_null() {
         paramErr
     } else {
-        State::get(&mut env.framework_state)
+        let host_object = State::get(&mut env.framework_state)
             .audio_component_instances
-            .remove(&in_instance);
+            .remove(&in_instance)
+            .unwrap();
+        audio_unit::delete_al_objects(env, host_object);
         env.mem.free(in_instance.cast());
         0
     };
@@ -137,6 +359,8 @@ fn AudioComponentInstanceDispose(
 
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(AudioComponentFindNext(_, _)),
+    export_c_func!(AudioComponentCount(_)),
+    export_c_func!(AudioComponentGetDescription(_, _)),
     export_c_func!(AudioComponentInstanceNew(_, _)),
     export_c_func!(AudioComponentInstanceDispose(_)),
 ];
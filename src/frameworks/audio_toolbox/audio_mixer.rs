@@ -0,0 +1,94 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The host audio mixer that all of the app's audio output feeds into.
+//!
+//! There is only ever one host OpenAL device. The app's own OpenAL contexts
+//! (see [crate::frameworks::openal]) and the internal context used for audio
+//! queues and audio units are all created on it, so OpenAL Soft mixes
+//! everything into a single output stream, like the hardware mixer on a real
+//! device. This means e.g. sound effects played with OpenAL and music rendered
+//! by a RemoteIO unit can be heard at the same time, and don't depend on the
+//! host allowing a process to open its audio device more than once.
+//!
+//! Sources on the internal context that aren't otherwise under the app's
+//! control can be registered with the mixer to give them their own volume.
+
+use crate::audio::openal as al;
+use crate::audio::openal::al_types::*;
+use crate::audio::openal::alc_types::ALCdevice;
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct State {
+    device: Option<*mut ALCdevice>,
+    /// Number of users of the device, i.e. open guest devices plus the
+    /// internal context.
+    device_users: usize,
+    /// Volume of each registered source on the internal context.
+    source_volumes: HashMap<ALuint, f32>,
+}
+impl State {
+    pub fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.audio_toolbox.audio_mixer
+    }
+
+    /// Get the shared host device, opening it if necessary. Every successful
+    /// call must be balanced by a call to [Self::close_device]. Returns null
+    /// if the device couldn't be opened.
+    pub fn open_device(&mut self) -> *mut ALCdevice {
+        if self.device.is_none() {
+            let device = unsafe { al::alcOpenDevice(std::ptr::null()) };
+            if device.is_null() {
+                return device;
+            }
+            log_dbg!("Opened shared OpenAL device {:?}", device);
+            self.device = Some(device);
+        }
+        self.device_users += 1;
+        self.device.unwrap()
+    }
+
+    /// Release a reference to the shared host device obtained with
+    /// [Self::open_device]. The device is only really closed once it has no
+    /// more users.
+    pub fn close_device(&mut self, device: *mut ALCdevice) -> bool {
+        assert!(self.device == Some(device));
+        self.device_users -= 1;
+        if self.device_users > 0 {
+            return true;
+        }
+        self.device = None;
+        log_dbg!("Closing shared OpenAL device {:?}", device);
+        unsafe { al::alcCloseDevice(device) != al::ALC_FALSE }
+    }
+
+    /// Register a source on the internal context, which must be current.
+    pub fn add_source(&mut self, al_source: ALuint, volume: f32) {
+        assert!(self.source_volumes.insert(al_source, volume).is_none());
+        apply_volume(al_source, volume);
+    }
+
+    /// Change the volume of a registered source. The internal context must be
+    /// current.
+    pub fn set_source_volume(&mut self, al_source: ALuint, volume: f32) {
+        *self.source_volumes.get_mut(&al_source).unwrap() = volume;
+        apply_volume(al_source, volume);
+    }
+
+    /// Unregister a source before it is deleted.
+    pub fn remove_source(&mut self, al_source: ALuint) {
+        self.source_volumes.remove(&al_source).unwrap();
+    }
+}
+
+fn apply_volume(al_source: ALuint, volume: f32) {
+    // Like for audio queues, the gain itself stays at 1.0 and the maximum gain
+    // is used to scale it.
+    unsafe {
+        al::alSourcef(al_source, al::AL_MAX_GAIN, volume);
+        assert!(al::alGetError() == 0);
+    }
+}
@@ -18,7 +18,7 @@ use crate::frameworks::carbon_core::OSStatus;
 use crate::frameworks::core_audio_types::{
     debug_fourcc, fourcc, kAudioFormatAppleIMA4, kAudioFormatFlagIsBigEndian,
     kAudioFormatFlagIsFloat, kAudioFormatFlagIsPacked, kAudioFormatLinearPCM,
-    AudioStreamBasicDescription,
+    kAudioTimeStampSampleTimeValid, AudioStreamBasicDescription, AudioTimeStamp,
 };
 use crate::frameworks::core_foundation::cf_run_loop::{
     kCFRunLoopCommonModes, CFRunLoopGetMain, CFRunLoopMode, CFRunLoopRef,
@@ -55,10 +55,20 @@ struct AudioQueueHostObject {
     /// queue, though the OpenAL queue may be shorter.
     buffer_queue: VecDeque<AudioQueueBufferRef>,
     is_running: AudioQueueIsRunning,
+    /// Playback rate multiplier (`kAudioQueueParam_PlayRate`).
+    play_rate: f32,
+    /// Pitch shift in cents (`kAudioQueueParam_Pitch`).
+    pitch: f32,
     al_source: Option<ALuint>,
     al_unused_buffers: Vec<ALuint>,
-    aq_is_running_proc: Option<AudioQueuePropertyListenerProc>,
-    aq_is_running_user_data: Option<MutVoidPtr>,
+    /// Number of frames in each OpenAL buffer queued on the source, in the
+    /// same order as the OpenAL queue.
+    al_queued_frames: VecDeque<u32>,
+    /// Number of frames played since the queue was started, not counting the
+    /// OpenAL buffer currently being played. Used for the queue's timeline.
+    frames_played: u64,
+    timeline: Option<AudioQueueTimelineRef>,
+    is_running_listeners: Vec<(AudioQueuePropertyListenerProc, MutVoidPtr)>,
 }
 
 /// Track whether the audio queue is meant to be running, in order to handle
@@ -96,11 +106,21 @@ unsafe impl SafeRead for AudioQueueBuffer {}
 
 pub type AudioQueueBufferRef = MutPtr<AudioQueueBuffer>;
 
+#[repr(C, packed)]
+pub struct OpaqueAudioQueueTimeline {
+    _filler: u8,
+}
+unsafe impl SafeRead for OpaqueAudioQueueTimeline {}
+
+pub type AudioQueueTimelineRef = MutPtr<OpaqueAudioQueueTimeline>;
+
 /// (*void)(void *in_user_data, AudioQueueRef in_aq, AudioQueueBufferRef in_buf)
 pub type AudioQueueOutputCallback = GuestFunction;
 
 type AudioQueueParameterID = u32;
 pub const kAudioQueueParam_Volume: AudioQueueParameterID = 1;
+pub const kAudioQueueParam_PlayRate: AudioQueueParameterID = 2;
+pub const kAudioQueueParam_Pitch: AudioQueueParameterID = 3;
 
 type AudioQueueParameterValue = f32;
 
@@ -113,6 +133,7 @@ type AudioQueuePropertyListenerProc = GuestFunction;
 const kAudioQueueErr_InvalidBuffer: OSStatus = -66687;
 const kAudioQueueErr_InvalidPropertySize: OSStatus = -66683;
 const kAudioQueueErr_BufferInQueue: OSStatus = -66679;
+const kAudioQueueErr_InvalidParameter: OSStatus = -66676;
 
 pub fn AudioQueueNewOutput(
     env: &mut Environment,
@@ -154,10 +175,14 @@ pub fn AudioQueueNewOutput(
         buffers: Vec::new(),
         buffer_queue: VecDeque::new(),
         is_running: AudioQueueIsRunning::Stopped,
+        play_rate: 1.0,
+        pitch: 0.0,
         al_source: None,
         al_unused_buffers: Vec::new(),
-        aq_is_running_proc: None,
-        aq_is_running_user_data: None,
+        al_queued_frames: VecDeque::new(),
+        frames_played: 0,
+        timeline: None,
+        is_running_listeners: Vec::new(),
     };
 
     let aq_ref = env.mem.alloc_and_write(OpaqueAudioQueue { _filler: 0 });
@@ -189,12 +214,24 @@ fn AudioQueueGetParameter(
 ) -> OSStatus {
     return_if_null!(in_aq);
 
-    assert!(in_param_id == kAudioQueueParam_Volume); // others unimplemented
-
     let state = State::get(&mut env.framework_state);
     let host_object = state.audio_queues.get_mut(&in_aq).unwrap();
 
-    env.mem.write(out_value, host_object.volume);
+    let value = match in_param_id {
+        kAudioQueueParam_Volume => host_object.volume,
+        kAudioQueueParam_PlayRate => host_object.play_rate,
+        kAudioQueueParam_Pitch => host_object.pitch,
+        _ => {
+            log!(
+                "TODO: AudioQueueGetParameter({:?}, {}, {:?})",
+                in_aq,
+                in_param_id,
+                out_value
+            );
+            return kAudioQueueErr_InvalidParameter;
+        }
+    };
+    env.mem.write(out_value, value);
 
     0 // success
 }
@@ -207,23 +244,44 @@ pub fn AudioQueueSetParameter(
 ) -> OSStatus {
     return_if_null!(in_aq);
 
-    assert!(in_param_id == kAudioQueueParam_Volume); // others unimplemented
-
     let state = State::get(&mut env.framework_state);
     let host_object = state.audio_queues.get_mut(&in_aq).unwrap();
 
-    host_object.volume = in_value;
+    match in_param_id {
+        kAudioQueueParam_Volume => host_object.volume = in_value,
+        kAudioQueueParam_PlayRate => host_object.play_rate = in_value,
+        kAudioQueueParam_Pitch => host_object.pitch = in_value,
+        _ => {
+            log!(
+                "TODO: AudioQueueSetParameter({:?}, {}, {})",
+                in_aq,
+                in_param_id,
+                in_value
+            );
+            return kAudioQueueErr_InvalidParameter;
+        }
+    }
     if let Some(al_source) = host_object.al_source {
         let _context_manager = env.framework_state.audio_toolbox.make_al_context_current();
-        unsafe {
-            al::alSourcef(al_source, al::AL_MAX_GAIN, in_value);
-            assert!(al::alGetError() == 0);
-        }
+        let host_object = &State::get(&mut env.framework_state).audio_queues[&in_aq];
+        set_al_source_params(al_source, host_object);
     }
 
     0 // success
 }
 
+/// Apply the volume, play rate and pitch parameters to the OpenAL source.
+/// OpenAL can't change the rate without changing the pitch, so these are
+/// combined.
+fn set_al_source_params(al_source: ALuint, host_object: &AudioQueueHostObject) {
+    let al_pitch = host_object.play_rate * (host_object.pitch / 1200.0).exp2();
+    unsafe {
+        al::alSourcef(al_source, al::AL_MAX_GAIN, host_object.volume);
+        al::alSourcef(al_source, al::AL_PITCH, al_pitch);
+        assert!(al::alGetError() == 0);
+    }
+}
+
 pub fn AudioQueueAllocateBuffer(
     env: &mut Environment,
     in_aq: AudioQueueRef,
@@ -296,8 +354,9 @@ fn AudioQueueAddPropertyListener(
             .get_mut(&in_aq)
             .unwrap();
 
-        host_object.aq_is_running_proc = Some(in_proc);
-        host_object.aq_is_running_user_data = Some(in_user_data);
+        host_object
+            .is_running_listeners
+            .push((in_proc, in_user_data));
     } else {
         log!(
             "TODO: AudioQueueAddPropertyListener({:?}, {}, {:?}, {:?})",
@@ -324,8 +383,14 @@ fn AudioQueueRemovePropertyListener(
             .get_mut(&in_aq)
             .unwrap();
 
-        host_object.aq_is_running_proc = None;
-        host_object.aq_is_running_user_data = None;
+        let Some(idx) = host_object
+            .is_running_listeners
+            .iter()
+            .position(|&listener| listener == (in_proc, in_user_data))
+        else {
+            return kAudioQueueErr_InvalidParameter;
+        };
+        host_object.is_running_listeners.remove(idx);
     } else {
         log!(
             "TODO: AudioQueueRemovePropertyListener({:?}, {}, {:?}, {:?})",
@@ -488,12 +553,27 @@ fn decode_buffer(
     }
 }
 
-/// Ensure an audio queue has an OpenAL source and at least one queued OpenAL
-/// buffer.
+/// Number of bytes per frame in PCM data of an OpenAL buffer format.
+fn al_format_bytes_per_frame(al_format: ALenum) -> usize {
+    match al_format {
+        al::AL_FORMAT_MONO8 => 1,
+        al::AL_FORMAT_MONO16 | al::AL_FORMAT_STEREO8 => 2,
+        al::AL_FORMAT_STEREO16 => 4,
+        _ => unreachable!(),
+    }
+}
+
+/// Ensure an audio queue has an OpenAL source and queued OpenAL buffers.
+///
+/// If `frames_to_prepare` is [None], at least two unprocessed buffers are kept
+/// queued, if available. If it is `Some(0)`, all enqueued buffers are
+/// decoded. Otherwise, buffers are decoded until at least that many frames are
+/// ready to play.
 fn prime_audio_queue(
     env: &mut Environment,
     in_aq: AudioQueueRef,
     context_manager: Option<ContextManager>,
+    frames_to_prepare: Option<u32>,
 ) -> ContextManager {
     let context_manager = context_manager
         .unwrap_or_else(|| env.framework_state.audio_toolbox.make_al_context_current());
@@ -509,9 +589,9 @@ fn prime_audio_queue(
         let mut al_source = 0;
         unsafe {
             al::alGenSources(1, &mut al_source);
-            al::alSourcef(al_source, al::AL_MAX_GAIN, host_object.volume);
             assert!(al::alGetError() == 0);
         };
+        set_al_source_params(al_source, host_object);
         host_object.al_source = Some(al_source);
     }
     let al_source = host_object.al_source.unwrap();
@@ -532,9 +612,25 @@ fn prime_audio_queue(
         let al_buffers_processed: usize = al_buffers_processed.try_into().unwrap();
 
         assert!(al_buffers_queued <= host_object.buffer_queue.len());
+        assert!(al_buffers_queued == host_object.al_queued_frames.len());
         let unprocessed_buffers = al_buffers_queued - al_buffers_processed;
 
-        if unprocessed_buffers > 1 || al_buffers_queued == host_object.buffer_queue.len() {
+        if al_buffers_queued == host_object.buffer_queue.len() {
+            break;
+        }
+        let prepared_enough = match frames_to_prepare {
+            None => unprocessed_buffers > 1,
+            Some(0) => false,
+            Some(frames) => {
+                let unprocessed_frames: u32 = host_object
+                    .al_queued_frames
+                    .iter()
+                    .skip(al_buffers_processed)
+                    .sum();
+                unprocessed_frames >= frames
+            }
+        };
+        if prepared_enough {
             break;
         }
 
@@ -568,11 +664,67 @@ fn prime_audio_queue(
         };
         unsafe { al::alSourceQueueBuffers(al_source, 1, &next_al_buffer) };
         assert!(unsafe { al::alGetError() } == 0);
+        let frames = data.len() / al_format_bytes_per_frame(al_format);
+        host_object
+            .al_queued_frames
+            .push_back(frames.try_into().unwrap());
     }
 
     context_manager
 }
 
+/// Get the number of frames that have been decoded but not yet played.
+/// The current OpenAL context must be the internal one.
+fn prepared_frames(host_object: &AudioQueueHostObject) -> u32 {
+    let Some(al_source) = host_object.al_source else {
+        return 0;
+    };
+    let mut al_buffers_processed = 0;
+    let mut al_sample_offset = 0;
+    unsafe {
+        al::alGetSourcei(
+            al_source,
+            al::AL_BUFFERS_PROCESSED,
+            &mut al_buffers_processed,
+        );
+        al::alGetSourcei(al_source, al::AL_SAMPLE_OFFSET, &mut al_sample_offset);
+        assert!(al::alGetError() == 0);
+    }
+    let unprocessed_frames: u32 = host_object
+        .al_queued_frames
+        .iter()
+        .skip(al_buffers_processed.try_into().unwrap())
+        .sum();
+    unprocessed_frames.saturating_sub(al_sample_offset.try_into().unwrap())
+}
+
+/// Get the number of frames played since the queue was started. The current
+/// OpenAL context must be the internal one.
+fn current_sample_time(host_object: &AudioQueueHostObject) -> u64 {
+    let Some(al_source) = host_object.al_source else {
+        return host_object.frames_played;
+    };
+    let mut al_buffers_processed = 0;
+    let mut al_sample_offset = 0;
+    unsafe {
+        al::alGetSourcei(
+            al_source,
+            al::AL_BUFFERS_PROCESSED,
+            &mut al_buffers_processed,
+        );
+        al::alGetSourcei(al_source, al::AL_SAMPLE_OFFSET, &mut al_sample_offset);
+        assert!(al::alGetError() == 0);
+    }
+    // Buffers that have been processed but not yet unqueued.
+    let processed_frames: u64 = host_object
+        .al_queued_frames
+        .iter()
+        .take(al_buffers_processed.try_into().unwrap())
+        .map(|&frames| u64::from(frames))
+        .sum();
+    host_object.frames_played + processed_frames + u64::try_from(al_sample_offset).unwrap()
+}
+
 fn unqueue_buffers<F: FnMut(ALuint)>(al_source: ALuint, mut callback: F) {
     loop {
         let mut al_buffers_processed = 0;
@@ -620,6 +772,8 @@ pub fn handle_audio_queue(env: &mut Environment, in_aq: AudioQueueRef) {
 
     unqueue_buffers(al_source, |al_buffer| {
         host_object.al_unused_buffers.push(al_buffer);
+        let frames = host_object.al_queued_frames.pop_front().unwrap();
+        host_object.frames_played += u64::from(frames);
         let buffer_ref = host_object.buffer_queue.pop_front().unwrap();
         buffers_to_reuse.push(buffer_ref);
     });
@@ -645,7 +799,7 @@ pub fn handle_audio_queue(env: &mut Environment, in_aq: AudioQueueRef) {
 
     // Push new buffers etc.
 
-    let _context_manager = prime_audio_queue(env, in_aq, Some(context_manager));
+    let _context_manager = prime_audio_queue(env, in_aq, Some(context_manager), None);
 
     if is_running != AudioQueueIsRunning::Stopped {
         unsafe {
@@ -686,26 +840,132 @@ pub fn handle_audio_queue(env: &mut Environment, in_aq: AudioQueueRef) {
 fn AudioQueuePrime(
     env: &mut Environment,
     in_aq: AudioQueueRef,
-    _in_number_of_frames_to_prepare: u32,
+    in_number_of_frames_to_prepare: u32,
     out_number_of_frames_prepared: MutPtr<u32>,
 ) -> OSStatus {
     return_if_null!(in_aq);
 
-    assert!(out_number_of_frames_prepared.is_null()); // TODO
-    let _context_manager = prime_audio_queue(env, in_aq, None);
+    let _context_manager =
+        prime_audio_queue(env, in_aq, None, Some(in_number_of_frames_to_prepare));
+    let host_object = State::get(&mut env.framework_state)
+        .audio_queues
+        .get_mut(&in_aq)
+        .unwrap();
+    let frames_prepared = prepared_frames(host_object);
+    log_dbg!(
+        "AudioQueuePrime({:?}, {}) prepared {} frames",
+        in_aq,
+        in_number_of_frames_to_prepare,
+        frames_prepared
+    );
+    if !out_number_of_frames_prepared.is_null() {
+        env.mem
+            .write(out_number_of_frames_prepared, frames_prepared);
+    }
     0 // success
 }
 
-fn notify_aq_is_running(env: &mut Environment, in_aq: AudioQueueRef) {
+fn AudioQueueFlush(env: &mut Environment, in_aq: AudioQueueRef) -> OSStatus {
+    return_if_null!(in_aq);
+
+    // Each buffer is decoded independently, so there is no decoder state that
+    // could hold back audio. Making sure everything enqueued so far is decoded
+    // is enough for it to be played before an asynchronous stop completes.
+    let _context_manager = prime_audio_queue(env, in_aq, None, Some(0));
+    log_dbg!("AudioQueueFlush({:?})", in_aq);
+    0 // success
+}
+
+fn AudioQueueCreateTimeline(
+    env: &mut Environment,
+    in_aq: AudioQueueRef,
+    out_timeline: MutPtr<AudioQueueTimelineRef>,
+) -> OSStatus {
+    return_if_null!(in_aq);
+
     let host_object = State::get(&mut env.framework_state)
         .audio_queues
         .get_mut(&in_aq)
         .unwrap();
+    // There can only be one timeline per queue.
+    let timeline = *host_object.timeline.get_or_insert_with(|| {
+        env.mem
+            .alloc_and_write(OpaqueAudioQueueTimeline { _filler: 0 })
+    });
+    env.mem.write(out_timeline, timeline);
+    0 // success
+}
+
+fn AudioQueueDisposeTimeline(
+    env: &mut Environment,
+    in_aq: AudioQueueRef,
+    in_timeline: AudioQueueTimelineRef,
+) -> OSStatus {
+    return_if_null!(in_aq);
+
+    let host_object = State::get(&mut env.framework_state)
+        .audio_queues
+        .get_mut(&in_aq)
+        .unwrap();
+    if host_object.timeline != Some(in_timeline) {
+        return kAudioQueueErr_InvalidParameter;
+    }
+    host_object.timeline = None;
+    env.mem.free(in_timeline.cast());
+    0 // success
+}
+
+pub fn AudioQueueGetCurrentTime(
+    env: &mut Environment,
+    in_aq: AudioQueueRef,
+    in_timeline: AudioQueueTimelineRef,
+    out_time_stamp: MutPtr<AudioTimeStamp>,
+    out_timeline_discontinuity: MutPtr<bool>,
+) -> OSStatus {
+    return_if_null!(in_aq);
 
-    if let (Some(in_proc), Some(in_user_data)) = (
-        host_object.aq_is_running_proc,
-        host_object.aq_is_running_user_data,
-    ) {
+    let _context_manager = env.framework_state.audio_toolbox.make_al_context_current();
+
+    let host_object = State::get(&mut env.framework_state)
+        .audio_queues
+        .get_mut(&in_aq)
+        .unwrap();
+    if !in_timeline.is_null() && host_object.timeline != Some(in_timeline) {
+        return kAudioQueueErr_InvalidParameter;
+    }
+    let sample_time = current_sample_time(host_object);
+    log_dbg!("AudioQueueGetCurrentTime({:?}) -> {}", in_aq, sample_time);
+
+    if !out_time_stamp.is_null() {
+        env.mem.write(
+            out_time_stamp,
+            AudioTimeStamp {
+                sample_time: sample_time as f64,
+                host_time: 0,
+                rate_scalar: 0.0,
+                word_clock_time: 0,
+                _smpte_time: [0; 24],
+                flags: kAudioTimeStampSampleTimeValid,
+                _reserved: 0,
+            },
+        );
+    }
+    if !out_timeline_discontinuity.is_null() {
+        // The emulated audio hardware never changes its sample rate etc.
+        env.mem.write(out_timeline_discontinuity, false);
+    }
+    0 // success
+}
+
+fn notify_aq_is_running(env: &mut Environment, in_aq: AudioQueueRef) {
+    let listeners = State::get(&mut env.framework_state)
+        .audio_queues
+        .get_mut(&in_aq)
+        .unwrap()
+        .is_running_listeners
+        .clone();
+
+    for (in_proc, in_user_data) in listeners {
         <GuestFunction as CallFromHost<(), (MutVoidPtr, Ptr<OpaqueAudioQueue, true>, u32)>>::
         call_from_host(
             &in_proc, env, (in_user_data, in_aq, kAudioQueueProperty_IsRunning)
@@ -722,7 +982,7 @@ pub fn AudioQueueStart(
 
     assert!(in_device_start_time.is_null()); // TODO
 
-    let _context_manager = prime_audio_queue(env, in_aq, None);
+    let _context_manager = prime_audio_queue(env, in_aq, None, None);
 
     let host_object = State::get(&mut env.framework_state)
         .audio_queues
@@ -770,11 +1030,13 @@ fn finish_stopping_audio_queue(env: &mut Environment, in_aq: AudioQueueRef) {
     // of an asynchronous stop, where the audio queue stopping is triggered by
     // the OpenAL queue stopping.
     AudioQueueReset(env, in_aq);
-    State::get(&mut env.framework_state)
+    let host_object = State::get(&mut env.framework_state)
         .audio_queues
         .get_mut(&in_aq)
-        .unwrap()
-        .is_running = AudioQueueIsRunning::Stopped;
+        .unwrap();
+    host_object.is_running = AudioQueueIsRunning::Stopped;
+    // The timeline restarts from zero the next time the queue is started.
+    host_object.frames_played = 0;
     notify_aq_is_running(env, in_aq);
 }
 
@@ -811,7 +1073,7 @@ pub fn AudioQueueStop(env: &mut Environment, in_aq: AudioQueueRef, in_immediate:
     0 // success
 }
 
-fn AudioQueueReset(env: &mut Environment, in_aq: AudioQueueRef) -> OSStatus {
+pub fn AudioQueueReset(env: &mut Environment, in_aq: AudioQueueRef) -> OSStatus {
     return_if_null!(in_aq);
 
     let _context_manager = env.framework_state.audio_toolbox.make_al_context_current();
@@ -823,6 +1085,9 @@ fn AudioQueueReset(env: &mut Environment, in_aq: AudioQueueRef) -> OSStatus {
     let host_object = state.audio_queues.get_mut(&in_aq).unwrap();
 
     if let Some(al_source) = host_object.al_source {
+        // Whatever has been played so far stays on the timeline.
+        host_object.frames_played = current_sample_time(host_object);
+
         unsafe {
             let mut al_source_state = 0;
             al::alGetSourcei(al_source, al::AL_SOURCE_STATE, &mut al_source_state);
@@ -838,6 +1103,7 @@ fn AudioQueueReset(env: &mut Environment, in_aq: AudioQueueRef) -> OSStatus {
 
         unqueue_buffers(al_source, |al_buffer| {
             host_object.al_unused_buffers.push(al_buffer);
+            host_object.al_queued_frames.pop_front().unwrap();
             host_object.buffer_queue.pop_front().unwrap();
         });
     }
@@ -894,6 +1160,10 @@ pub fn AudioQueueDispose(
 
     env.mem.free(in_aq.cast());
 
+    if let Some(timeline) = host_object.timeline {
+        env.mem.free(timeline.cast());
+    }
+
     for buffer_ptr in host_object.buffers {
         let buffer = env.mem.read(buffer_ptr);
         env.mem.free(buffer.audio_data);
@@ -937,6 +1207,10 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(AudioQueueGetPropertySize(_, _, _)),
     export_c_func!(AudioQueueGetProperty(_, _, _, _)),
     export_c_func!(AudioQueuePrime(_, _, _)),
+    export_c_func!(AudioQueueFlush(_)),
+    export_c_func!(AudioQueueCreateTimeline(_, _)),
+    export_c_func!(AudioQueueDisposeTimeline(_, _)),
+    export_c_func!(AudioQueueGetCurrentTime(_, _, _, _)),
     export_c_func!(AudioQueueStart(_, _)),
     export_c_func!(AudioQueuePause(_)),
     export_c_func!(AudioQueueStop(_, _)),
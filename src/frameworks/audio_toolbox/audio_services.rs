@@ -4,19 +4,55 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `AudioServices.h` (Audio Services)
+//!
+//! System sounds are short sound files that are decoded up-front into a single
+//! OpenAL buffer each, and played on the internal OpenAL context. Vibration is
+//! simulated by rumbling any connected game controllers.
 
+use crate::audio;
+use crate::audio::decode_ima4;
+use crate::audio::openal as al;
+use crate::audio::openal::al_types::*;
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::frameworks::carbon_core::OSStatus;
 use crate::frameworks::core_audio_types::fourcc;
+use crate::frameworks::core_foundation::cf_url::CFURLRef;
+use crate::frameworks::foundation::ns_url::to_rust_path;
 use crate::mem::{MutPtr, MutVoidPtr};
 use crate::Environment;
+use std::collections::HashMap;
+use std::time::Duration;
 
 /// Usually a FourCC.
 type AudioServicesPropertyID = u32;
 type SystemSoundID = u32;
 
 const kAudioServicesUnsupportedPropertyError: OSStatus = fourcc(b"pty?") as _;
+const kAudioServicesSystemSoundUnspecifiedError: OSStatus = -1500;
+
 const kSystemSoundID_Vibrate: SystemSoundID = 0x00000FFF;
+/// IDs of sounds created by the app start after this, like on a real device.
+/// Lower IDs are the built-in system sounds, which we don't have.
+const FIRST_SYSTEM_SOUND_ID: SystemSoundID = 0x1000;
+
+/// How long the vibration motor runs for. This is an approximation.
+const VIBRATION_DURATION: Duration = Duration::from_millis(400);
+
+#[derive(Default)]
+pub struct State {
+    system_sounds: HashMap<SystemSoundID, SystemSound>,
+    next_system_sound_id: Option<SystemSoundID>,
+}
+impl State {
+    fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.audio_toolbox.audio_services
+    }
+}
+
+struct SystemSound {
+    al_source: ALuint,
+    al_buffer: ALuint,
+}
 
 fn AudioServicesGetProperty(
     _env: &mut Environment,
@@ -35,13 +71,235 @@ fn AudioServicesGetProperty(
     }
 }
 
-fn AudioServicesPlaySystemSound(_env: &mut Environment, in_system_sound_id: SystemSoundID) {
-    assert_eq!(in_system_sound_id, kSystemSoundID_Vibrate);
-    log!("TODO: vibration (AudioServicesPlaySystemSound)");
-    // TODO: implement other system sounds
+/// Decode a whole audio file to PCM suitable for an OpenAL buffer.
+fn decode_audio_file(
+    audio_file: &mut audio::AudioFile,
+) -> Result<(ALenum, ALsizei, Vec<u8>), String> {
+    let description = audio_file.audio_description();
+    let channels = description.channels_per_frame;
+    if channels != 1 && channels != 2 {
+        return Err(format!("{} channels", channels));
+    }
+    let mut data = vec![0u8; audio_file.byte_count().try_into().unwrap()];
+    let bytes_read = audio_file
+        .read_bytes(0, &mut data)
+        .map_err(|_| "read error".to_string())?;
+    data.truncate(bytes_read);
+
+    let mono_or_stereo = |mono, stereo| if channels == 1 { mono } else { stereo };
+    let sample_rate = description.sample_rate as ALsizei;
+    match description.format {
+        audio::AudioFormat::AppleIma4 => {
+            // For stereo, the packets alternate between the channels.
+            let mut pcm = Vec::with_capacity((data.len() / 34) * 64 * 2);
+            for packets in data.chunks_exact(34 * channels as usize) {
+                let mut decoded = packets
+                    .chunks_exact(34)
+                    .map(|packet| decode_ima4(packet.try_into().unwrap()));
+                let first = decoded.next().unwrap();
+                if let Some(second) = decoded.next() {
+                    for (left, right) in first.into_iter().zip(second) {
+                        pcm.extend_from_slice(&left.to_le_bytes());
+                        pcm.extend_from_slice(&right.to_le_bytes());
+                    }
+                } else {
+                    for sample in first {
+                        pcm.extend_from_slice(&sample.to_le_bytes());
+                    }
+                }
+            }
+            let format = mono_or_stereo(al::AL_FORMAT_MONO16, al::AL_FORMAT_STEREO16);
+            Ok((format, sample_rate, pcm))
+        }
+        audio::AudioFormat::LinearPcm {
+            is_float,
+            is_little_endian,
+        } => match (is_float, description.bits_per_channel) {
+            (false, 8) => {
+                let format = mono_or_stereo(al::AL_FORMAT_MONO8, al::AL_FORMAT_STEREO8);
+                Ok((format, sample_rate, data))
+            }
+            (false, 16) => {
+                if !is_little_endian {
+                    for sample in data.chunks_exact_mut(2) {
+                        sample.swap(0, 1);
+                    }
+                }
+                let format = mono_or_stereo(al::AL_FORMAT_MONO16, al::AL_FORMAT_STEREO16);
+                Ok((format, sample_rate, data))
+            }
+            (true, 32) => {
+                let pcm = data
+                    .chunks_exact(4)
+                    .flat_map(|sample| {
+                        let sample: [u8; 4] = sample.try_into().unwrap();
+                        let sample = if is_little_endian {
+                            f32::from_le_bytes(sample)
+                        } else {
+                            f32::from_be_bytes(sample)
+                        };
+                        // Saturating float-to-int cast.
+                        ((sample * 32767.0) as i16).to_le_bytes()
+                    })
+                    .collect();
+                let format = mono_or_stereo(al::AL_FORMAT_MONO16, al::AL_FORMAT_STEREO16);
+                Ok((format, sample_rate, pcm))
+            }
+            (_, bits) => Err(format!(
+                "{}-bit {} PCM",
+                bits,
+                if is_float { "float" } else { "integer" }
+            )),
+        },
+    }
+}
+
+fn AudioServicesCreateSystemSoundID(
+    env: &mut Environment,
+    in_file_url: CFURLRef,
+    out_system_sound_id: MutPtr<SystemSoundID>,
+) -> OSStatus {
+    return_if_null!(in_file_url);
+    return_if_null!(out_system_sound_id);
+
+    let path = to_rust_path(env, in_file_url);
+    let Ok(mut audio_file) = audio::AudioFile::open_for_reading(&path, &env.fs) else {
+        log!(
+            "Warning: AudioServicesCreateSystemSoundID() couldn't open {:?}",
+            path
+        );
+        return kAudioServicesSystemSoundUnspecifiedError;
+    };
+    let (al_format, sample_rate, pcm) = match decode_audio_file(&mut audio_file) {
+        Ok(decoded) => decoded,
+        Err(e) => {
+            log!(
+                "Warning: AudioServicesCreateSystemSoundID() can't play {:?}: unsupported format ({})",
+                path,
+                e
+            );
+            return kAudioServicesSystemSoundUnspecifiedError;
+        }
+    };
+
+    let _context_manager = env.framework_state.audio_toolbox.make_al_context_current();
+    let mut al_source = 0;
+    let mut al_buffer = 0;
+    unsafe {
+        al::alGenSources(1, &mut al_source);
+        al::alGenBuffers(1, &mut al_buffer);
+        al::alBufferData(
+            al_buffer,
+            al_format,
+            pcm.as_ptr() as *const ALvoid,
+            pcm.len().try_into().unwrap(),
+            sample_rate,
+        );
+        al::alSourcei(al_source, al::AL_BUFFER, al_buffer as ALint);
+        assert!(al::alGetError() == 0);
+    }
+    drop(_context_manager);
+
+    let state = State::get(&mut env.framework_state);
+    let id = state.next_system_sound_id.unwrap_or(FIRST_SYSTEM_SOUND_ID);
+    state.next_system_sound_id = Some(id + 1);
+    state.system_sounds.insert(
+        id,
+        SystemSound {
+            al_source,
+            al_buffer,
+        },
+    );
+    env.mem.write(out_system_sound_id, id);
+    log_dbg!(
+        "AudioServicesCreateSystemSoundID({:?} ({:?}), {:?}) => {}",
+        in_file_url,
+        path,
+        out_system_sound_id,
+        id
+    );
+    0 // success
+}
+
+fn AudioServicesDisposeSystemSoundID(
+    env: &mut Environment,
+    in_system_sound_id: SystemSoundID,
+) -> OSStatus {
+    let Some(SystemSound {
+        al_source,
+        al_buffer,
+    }) = State::get(&mut env.framework_state)
+        .system_sounds
+        .remove(&in_system_sound_id)
+    else {
+        log!(
+            "Warning: AudioServicesDisposeSystemSoundID() for unknown sound {}",
+            in_system_sound_id
+        );
+        return kAudioServicesSystemSoundUnspecifiedError;
+    };
+
+    let _context_manager = env.framework_state.audio_toolbox.make_al_context_current();
+    unsafe {
+        al::alSourceStop(al_source);
+        al::alDeleteSources(1, &al_source);
+        al::alDeleteBuffers(1, &al_buffer);
+        assert!(al::alGetError() == 0);
+    }
+    log_dbg!("AudioServicesDisposeSystemSoundID({})", in_system_sound_id);
+    0 // success
+}
+
+fn vibrate(env: &mut Environment) {
+    let vibrated = env
+        .window
+        .as_mut()
+        .is_some_and(|window| window.vibrate(VIBRATION_DURATION));
+    if !vibrated {
+        log_dbg!("Vibration requested, but there's no controller to rumble");
+    }
+}
+
+fn AudioServicesPlaySystemSound(env: &mut Environment, in_system_sound_id: SystemSoundID) {
+    if in_system_sound_id == kSystemSoundID_Vibrate {
+        vibrate(env);
+        return;
+    }
+
+    let Some(sound) = State::get(&mut env.framework_state)
+        .system_sounds
+        .get(&in_system_sound_id)
+    else {
+        log!(
+            "TODO: AudioServicesPlaySystemSound() for unknown or built-in sound {}",
+            in_system_sound_id
+        );
+        return;
+    };
+    let al_source = sound.al_source;
+
+    // Playing a sound that is already playing restarts it.
+    let _context_manager = env.framework_state.audio_toolbox.make_al_context_current();
+    unsafe {
+        al::alSourcePlay(al_source);
+        assert!(al::alGetError() == 0);
+    }
+    log_dbg!("AudioServicesPlaySystemSound({})", in_system_sound_id);
+}
+
+/// Like [AudioServicesPlaySystemSound], but it also vibrates, like an iPhone
+/// does for alerts.
+fn AudioServicesPlayAlertSound(env: &mut Environment, in_system_sound_id: SystemSoundID) {
+    if in_system_sound_id != kSystemSoundID_Vibrate {
+        AudioServicesPlaySystemSound(env, in_system_sound_id);
+    }
+    vibrate(env);
 }
 
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(AudioServicesGetProperty(_, _, _, _, _)),
+    export_c_func!(AudioServicesCreateSystemSoundID(_, _)),
+    export_c_func!(AudioServicesDisposeSystemSoundID(_)),
     export_c_func!(AudioServicesPlaySystemSound(_)),
+    export_c_func!(AudioServicesPlayAlertSound(_)),
 ];
@@ -6,13 +6,89 @@
 //! `AudioSession.h` (Audio Session) // TODO: is this the real name?
 
 use crate::abi::GuestFunction;
+use crate::audio::openal as al;
+use crate::audio::openal::alc_types::ALCint;
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::frameworks::carbon_core::OSStatus;
 use crate::frameworks::core_audio_types::{debug_fourcc, fourcc};
 use crate::frameworks::core_foundation::cf_run_loop::{CFRunLoopMode, CFRunLoopRef};
+use crate::frameworks::media_player::music_player;
 use crate::mem::{guest_size_of, ConstVoidPtr, GuestUSize, MutPtr, MutVoidPtr};
 use crate::Environment;
 
+/// Sample rate of the emulated audio hardware. Value taken from an iOS 2
+/// simulator.
+pub const HARDWARE_SAMPLE_RATE: f64 = 44100.0;
+
+/// The hardware only supports power-of-two I/O buffer sizes, within a limited
+/// range. These limits are approximations of what real devices allow.
+const MIN_IO_BUFFER_FRAMES: u32 = 256;
+const MAX_IO_BUFFER_FRAMES: u32 = 4096;
+/// The default I/O buffer size (about 23ms).
+const DEFAULT_IO_BUFFER_FRAMES: u32 = 1024;
+
+pub struct State {
+    category: u32,
+    /// `kAudioSessionProperty_OverrideCategoryMixWithOthers`
+    mix_with_others: bool,
+    /// `kAudioSessionProperty_OtherMixableAudioShouldDuck`
+    duck_others: bool,
+    active: bool,
+    other_audio_ducked: bool,
+    preferred_sample_rate: Option<f64>,
+    preferred_io_buffer_duration: Option<f32>,
+}
+impl Default for State {
+    fn default() -> Self {
+        State {
+            // This is the default value.
+            category: kAudioSessionCategory_SoloAmbientSound,
+            mix_with_others: false,
+            duck_others: false,
+            active: false,
+            other_audio_ducked: false,
+            preferred_sample_rate: None,
+            preferred_io_buffer_duration: None,
+        }
+    }
+}
+impl State {
+    pub fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.audio_toolbox.audio_session
+    }
+
+    /// The size of the hardware I/O buffer in frames, taking the app's
+    /// preferred I/O buffer duration into account.
+    pub fn io_buffer_frames(&self) -> u32 {
+        let Some(duration) = self.preferred_io_buffer_duration else {
+            return DEFAULT_IO_BUFFER_FRAMES;
+        };
+        let frames = (f64::from(duration) * HARDWARE_SAMPLE_RATE).round();
+        // Saturating float-to-int cast, negative values become 0.
+        (frames as u32)
+            .clamp(MIN_IO_BUFFER_FRAMES, MAX_IO_BUFFER_FRAMES)
+            .next_power_of_two()
+    }
+
+    /// The actual duration of the hardware I/O buffer in seconds.
+    pub fn io_buffer_duration(&self) -> f32 {
+        (f64::from(self.io_buffer_frames()) / HARDWARE_SAMPLE_RATE) as f32
+    }
+
+    /// Attribute list for creating an OpenAL context whose mixer matches the
+    /// emulated hardware's sample rate and I/O buffer duration.
+    pub fn al_context_attributes(&self) -> [ALCint; 5] {
+        let refresh = (HARDWARE_SAMPLE_RATE / f64::from(self.io_buffer_frames())).round();
+        [
+            al::ALC_FREQUENCY,
+            HARDWARE_SAMPLE_RATE as ALCint,
+            al::ALC_REFRESH,
+            refresh as ALCint,
+            0,
+        ]
+    }
+}
+
 type AudioSessionInterruptionListener = GuestFunction;
 type AudioSessionPropertyListener = GuestFunction;
 
@@ -25,10 +101,63 @@ const kAudioSessionProperty_AudioCategory: AudioSessionPropertyID = fourcc(b"aca
 const kAudioSessionProperty_CurrentHardwareSampleRate: AudioSessionPropertyID = fourcc(b"chsr");
 const kAudioSessionProperty_CurrentHardwareOutputNumberChannels: AudioSessionPropertyID =
     fourcc(b"choc");
+const kAudioSessionProperty_CurrentHardwareInputNumberChannels: AudioSessionPropertyID =
+    fourcc(b"chic");
+const kAudioSessionProperty_CurrentHardwareIOBufferDuration: AudioSessionPropertyID =
+    fourcc(b"chbd");
+const kAudioSessionProperty_PreferredHardwareSampleRate: AudioSessionPropertyID = fourcc(b"hwsr");
 const kAudioSessionProperty_PreferredHardwareIOBufferDuration: AudioSessionPropertyID =
     fourcc(b"iobd");
+const kAudioSessionProperty_AudioInputAvailable: AudioSessionPropertyID = fourcc(b"aiav");
+const kAudioSessionProperty_OverrideCategoryMixWithOthers: AudioSessionPropertyID = fourcc(b"cmix");
+const kAudioSessionProperty_OtherMixableAudioShouldDuck: AudioSessionPropertyID = fourcc(b"duck");
 
+const kAudioSessionCategory_AmbientSound: u32 = fourcc(b"ambi");
 const kAudioSessionCategory_SoloAmbientSound: u32 = fourcc(b"solo");
+const kAudioSessionCategory_MediaPlayback: u32 = fourcc(b"medi");
+const kAudioSessionCategory_PlayAndRecord: u32 = fourcc(b"plar");
+
+/// Whether audio from outside the app, i.e. the iPod app, is playing. touchHLE
+/// can't play such audio, but the state of the iPod music player is tracked
+/// for the sake of apps that check for it, e.g. to turn off their own music.
+/// See [music_player].
+///
+/// As on a real device, the other audio stops once the audio session is
+/// active with a category that doesn't allow mixing. Apps usually check this
+/// early, before they've chosen a category or started playing anything.
+fn other_audio_is_playing(env: &mut Environment) -> bool {
+    if !music_player::ipod_music_is_playing(env) {
+        return false;
+    }
+    // Playing audio activates the session implicitly, so having an audio
+    // context is treated the same as having called AudioSessionSetActive().
+    let has_audio = env.framework_state.audio_toolbox.has_al_context()
+        || crate::frameworks::openal::has_contexts(env);
+    let state = State::get(&mut env.framework_state);
+    let active = state.active || has_audio;
+    let mixable = match state.category {
+        kAudioSessionCategory_AmbientSound => true,
+        kAudioSessionCategory_MediaPlayback | kAudioSessionCategory_PlayAndRecord => {
+            state.mix_with_others
+        }
+        _ => false,
+    };
+    if active && !mixable {
+        log!(
+            "Audio session is active with category {}, stopping other audio",
+            debug_fourcc(state.category)
+        );
+        state.other_audio_ducked = false;
+        music_player::interrupt_ipod_music(env);
+        return false;
+    }
+    let ducked = active && state.duck_others;
+    if ducked != state.other_audio_ducked {
+        log_dbg!("Other audio ducked: {}", ducked);
+        state.other_audio_ducked = ducked;
+    }
+    true
+}
 
 fn AudioSessionInitialize(
     _env: &mut Environment,
@@ -52,6 +181,13 @@ fn AudioSessionGetProperty(
         kAudioSessionProperty_AudioCategory => guest_size_of::<u32>(),
         kAudioSessionProperty_CurrentHardwareSampleRate => guest_size_of::<f64>(),
         kAudioSessionProperty_CurrentHardwareOutputNumberChannels => guest_size_of::<u32>(),
+        kAudioSessionProperty_CurrentHardwareInputNumberChannels => guest_size_of::<u32>(),
+        kAudioSessionProperty_CurrentHardwareIOBufferDuration => guest_size_of::<f32>(),
+        kAudioSessionProperty_PreferredHardwareSampleRate => guest_size_of::<f64>(),
+        kAudioSessionProperty_PreferredHardwareIOBufferDuration => guest_size_of::<f32>(),
+        kAudioSessionProperty_AudioInputAvailable => guest_size_of::<u32>(),
+        kAudioSessionProperty_OverrideCategoryMixWithOthers => guest_size_of::<u32>(),
+        kAudioSessionProperty_OtherMixableAudioShouldDuck => guest_size_of::<u32>(),
         _ => unimplemented!("Unimplemented property ID: {}", debug_fourcc(in_ID)),
     };
     if env.mem.read(io_data_size) != required_size {
@@ -59,24 +195,59 @@ fn AudioSessionGetProperty(
         return kAudioSessionBadPropertySizeError;
     }
 
+    // Everything the audio mixer mixes belongs to the app itself, including
+    // its OpenAL and RemoteIO output, so other audio can only be simulated.
+    let other_audio_playing = other_audio_is_playing(env);
+    let state = State::get(&mut env.framework_state);
     match in_ID {
         kAudioSessionProperty_OtherAudioIsPlaying => {
-            let value: u32 = 0;
+            let value: u32 = other_audio_playing.into();
             env.mem.write(out_data.cast(), value);
         }
         kAudioSessionProperty_AudioCategory => {
-            // This is the default value. TODO: Actually support changing it?
-            let value: u32 = kAudioSessionCategory_SoloAmbientSound;
+            let value: u32 = state.category;
             env.mem.write(out_data.cast(), value);
         }
         kAudioSessionProperty_CurrentHardwareSampleRate => {
-            let value: f64 = 44100.0; // Value taken from an iOS 2 simulator
+            let value: f64 = HARDWARE_SAMPLE_RATE;
             env.mem.write(out_data.cast(), value);
         }
         kAudioSessionProperty_CurrentHardwareOutputNumberChannels => {
             let value: u32 = 2; // Value taken from an iOS 2 simulator
             env.mem.write(out_data.cast(), value);
         }
+        kAudioSessionProperty_CurrentHardwareInputNumberChannels => {
+            // Audio input is not supported.
+            let value: u32 = 0;
+            env.mem.write(out_data.cast(), value);
+        }
+        kAudioSessionProperty_CurrentHardwareIOBufferDuration => {
+            let value: f32 = state.io_buffer_duration();
+            env.mem.write(out_data.cast(), value);
+        }
+        kAudioSessionProperty_PreferredHardwareSampleRate => {
+            // If the app hasn't set a preference, this is 0.
+            let value: f64 = state.preferred_sample_rate.unwrap_or(0.0);
+            env.mem.write(out_data.cast(), value);
+        }
+        kAudioSessionProperty_PreferredHardwareIOBufferDuration => {
+            // If the app hasn't set a preference, this is 0.
+            let value: f32 = state.preferred_io_buffer_duration.unwrap_or(0.0);
+            env.mem.write(out_data.cast(), value);
+        }
+        kAudioSessionProperty_AudioInputAvailable => {
+            // Audio input is not supported.
+            let value: u32 = 0;
+            env.mem.write(out_data.cast(), value);
+        }
+        kAudioSessionProperty_OverrideCategoryMixWithOthers => {
+            let value: u32 = state.mix_with_others.into();
+            env.mem.write(out_data.cast(), value);
+        }
+        kAudioSessionProperty_OtherMixableAudioShouldDuck => {
+            let value: u32 = state.duck_others.into();
+            env.mem.write(out_data.cast(), value);
+        }
         _ => unreachable!(),
     }
 
@@ -84,27 +255,80 @@ fn AudioSessionGetProperty(
 }
 
 fn AudioSessionSetProperty(
-    _env: &mut Environment,
+    env: &mut Environment,
     in_ID: AudioSessionPropertyID,
     in_data_size: u32,
-    _in_data: ConstVoidPtr,
+    in_data: ConstVoidPtr,
 ) -> OSStatus {
     let required_size: GuestUSize = match in_ID {
         kAudioSessionProperty_AudioCategory => guest_size_of::<u32>(),
+        kAudioSessionProperty_PreferredHardwareSampleRate => guest_size_of::<f64>(),
         kAudioSessionProperty_PreferredHardwareIOBufferDuration => guest_size_of::<f32>(),
+        kAudioSessionProperty_OverrideCategoryMixWithOthers => guest_size_of::<u32>(),
+        kAudioSessionProperty_OtherMixableAudioShouldDuck => guest_size_of::<u32>(),
         _ => unimplemented!("Unimplemented property ID: {}", debug_fourcc(in_ID)),
     };
     if in_data_size != required_size {
-        log!("Warning: AudioSessionGetProperty() failed");
+        log!("Warning: AudioSessionSetProperty() failed");
         return kAudioSessionBadPropertySizeError;
     }
 
-    // TODO: actually implement this
+    match in_ID {
+        kAudioSessionProperty_AudioCategory => {
+            let value: u32 = env.mem.read(in_data.cast());
+            log_dbg!("Audio session category set to {}", debug_fourcc(value));
+            let state = State::get(&mut env.framework_state);
+            state.category = value;
+            // Changing the category resets the mixing override.
+            state.mix_with_others = false;
+        }
+        kAudioSessionProperty_PreferredHardwareSampleRate => {
+            let value: f64 = env.mem.read(in_data.cast());
+            // The emulated hardware only has one sample rate, so this is just
+            // remembered, like how a real device may ignore the preference.
+            log_dbg!("Preferred hardware sample rate set to {}", value);
+            State::get(&mut env.framework_state).preferred_sample_rate = Some(value);
+        }
+        kAudioSessionProperty_PreferredHardwareIOBufferDuration => {
+            let value: f32 = env.mem.read(in_data.cast::<f32>());
+            let state = State::get(&mut env.framework_state);
+            state.preferred_io_buffer_duration = Some(value);
+            log_dbg!(
+                "Preferred hardware I/O buffer duration set to {}, actual duration is {} ({} frames)",
+                value,
+                state.io_buffer_duration(),
+                state.io_buffer_frames(),
+            );
+            if env.framework_state.audio_toolbox.has_al_context()
+                || crate::frameworks::openal::has_contexts(env)
+            {
+                log!(
+                    "Warning: I/O buffer duration changed after audio was initialized, this will only affect new OpenAL contexts"
+                );
+            }
+        }
+        kAudioSessionProperty_OverrideCategoryMixWithOthers => {
+            let value: u32 = env.mem.read(in_data.cast());
+            log_dbg!("Category mixing override set to {}", value);
+            State::get(&mut env.framework_state).mix_with_others = value != 0;
+        }
+        kAudioSessionProperty_OtherMixableAudioShouldDuck => {
+            let value: u32 = env.mem.read(in_data.cast());
+            log_dbg!("Ducking of other audio set to {}", value);
+            State::get(&mut env.framework_state).duck_others = value != 0;
+        }
+        _ => unreachable!(),
+    }
+    // Stop or duck the other audio, if the new settings call for it.
+    other_audio_is_playing(env);
 
     0 // success
 }
 
-fn AudioSessionSetActive(_env: &mut Environment, _active: bool) -> OSStatus {
+fn AudioSessionSetActive(env: &mut Environment, active: bool) -> OSStatus {
+    log_dbg!("AudioSessionSetActive({})", active);
+    State::get(&mut env.framework_state).active = active;
+    other_audio_is_playing(env);
     0 // success
 }
 
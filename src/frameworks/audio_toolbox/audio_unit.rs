@@ -0,0 +1,1168 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `AUComponent.h` and `AudioOutputUnit.h` (Audio Unit framework)
+//!
+//! Only the RemoteIO audio unit's output is supported, optionally fed by a
+//! multichannel mixer unit connected to it. Other kinds of unit (see
+//! [AudioUnitKind]) can be created and configured, but are never rendered.
+//! Like the audio queue implementation, playback is mapped onto OpenAL Soft:
+//! the guest's render callbacks are called from the run loop to fill OpenAL
+//! buffers, which are kept queued on a source. That source is registered with
+//! the [audio mixer](super::audio_mixer), so RemoteIO output plays alongside
+//! the app's own OpenAL output.
+
+use super::audio_components::{
+    AURenderCallbackStruct, AudioComponentInstance, AudioComponentInstanceHostObject,
+    AudioUnitKind, MixerInput, State,
+};
+use super::audio_mixer::State as AudioMixerState;
+use super::audio_session;
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::audio::openal as al;
+use crate::audio::openal::al_types::*;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::carbon_core::OSStatus;
+use crate::frameworks::core_audio_types::{
+    debug_fourcc, kAudioFormatFlagIsBigEndian, kAudioFormatFlagIsFloat,
+    kAudioFormatFlagIsNonInterleaved, kAudioFormatFlagIsPacked, kAudioFormatFlagIsSignedInteger,
+    kAudioFormatLinearPCM, kAudioTimeStampSampleTimeValid, kLinearPCMFormatFlagsSampleFractionMask,
+    kLinearPCMFormatFlagsSampleFractionShift, AudioBuffer, AudioBufferListHeader,
+    AudioStreamBasicDescription, AudioTimeStamp,
+};
+use crate::mem::{guest_size_of, ConstVoidPtr, GuestUSize, MutPtr, MutVoidPtr, SafeRead};
+use crate::Environment;
+
+pub type AudioUnit = AudioComponentInstance;
+
+pub(super) type AudioUnitPropertyID = u32;
+pub(super) const kAudioUnitProperty_MakeConnection: AudioUnitPropertyID = 1;
+const kAudioUnitProperty_StreamFormat: AudioUnitPropertyID = 8;
+const kAudioUnitProperty_ElementCount: AudioUnitPropertyID = 11;
+const kAudioUnitProperty_MaximumFramesPerSlice: AudioUnitPropertyID = 14;
+pub(super) const kAudioUnitProperty_SetRenderCallback: AudioUnitPropertyID = 23;
+const kAudioOutputUnitProperty_IsRunning: AudioUnitPropertyID = 2001;
+const kAudioOutputUnitProperty_EnableIO: AudioUnitPropertyID = 2003;
+
+/// `AudioUnitPropertyListenerProc`
+type AudioUnitPropertyListenerProc = GuestFunction;
+
+type AudioUnitParameterID = u32;
+const kMultiChannelMixerParam_Volume: AudioUnitParameterID = 0;
+const kMultiChannelMixerParam_Enable: AudioUnitParameterID = 1;
+const kHALOutputParam_Volume: AudioUnitParameterID = 14;
+
+type AudioUnitParameterValue = f32;
+
+pub(super) type AudioUnitScope = u32;
+const kAudioUnitScope_Global: AudioUnitScope = 0;
+pub(super) const kAudioUnitScope_Input: AudioUnitScope = 1;
+const kAudioUnitScope_Output: AudioUnitScope = 2;
+
+pub(super) type AudioUnitElement = u32;
+/// On RemoteIO, element 0 is connected to the output hardware and element 1
+/// to the input hardware.
+const OUTPUT_ELEMENT: AudioUnitElement = 0;
+const INPUT_ELEMENT: AudioUnitElement = 1;
+
+type AudioUnitRenderActionFlags = u32;
+const kAudioUnitRenderAction_OutputIsSilence: AudioUnitRenderActionFlags = 1 << 4;
+
+const kAudioUnitErr_InvalidPropertyValue: OSStatus = -10851;
+const kAudioUnitErr_PropertyNotWritable: OSStatus = -10865;
+const kAudioUnitErr_InvalidElement: OSStatus = -10877;
+const kAudioUnitErr_InvalidParameter: OSStatus = -10878;
+const kAudioUnitErr_FormatNotSupported: OSStatus = -10868;
+const kAudioUnitErr_Uninitialized: OSStatus = -10867;
+
+#[repr(C, packed)]
+pub(super) struct AudioUnitConnection {
+    pub(super) source_audio_unit: AudioUnit,
+    pub(super) source_output_number: u32,
+    pub(super) dest_input_number: u32,
+}
+unsafe impl SafeRead for AudioUnitConnection {}
+
+/// The canonical audio unit sample format (8.24 fixed-point, non-interleaved
+/// stereo), which is the format RemoteIO expects if the app doesn't set one.
+const DEFAULT_STREAM_FORMAT: AudioStreamBasicDescription = AudioStreamBasicDescription {
+    sample_rate: audio_session::HARDWARE_SAMPLE_RATE,
+    format_id: kAudioFormatLinearPCM,
+    format_flags: kAudioFormatFlagIsSignedInteger
+        | kAudioFormatFlagIsPacked
+        | kAudioFormatFlagIsNonInterleaved
+        | (24 << kLinearPCMFormatFlagsSampleFractionShift),
+    bytes_per_packet: 4,
+    frames_per_packet: 1,
+    bytes_per_frame: 4,
+    channels_per_frame: 2,
+    bits_per_channel: 32,
+    _reserved: 0,
+};
+
+/// How much audio should be queued ahead of playback. The run loop polls about
+/// 60 times a second, so this needs to cover a few of its iterations.
+const MIN_QUEUED_DURATION: f64 = 0.05;
+
+/// Number of fractional bits for fixed-point formats like 8.24.
+fn sample_fraction_bits(format: &AudioStreamBasicDescription) -> u32 {
+    (format.format_flags & kLinearPCMFormatFlagsSampleFractionMask)
+        >> kLinearPCMFormatFlagsSampleFractionShift
+}
+
+/// Check if a stream format is one we currently support for rendering.
+fn is_supported_stream_format(format: &AudioStreamBasicDescription) -> bool {
+    let &AudioStreamBasicDescription {
+        format_id,
+        format_flags,
+        bytes_per_frame,
+        channels_per_frame,
+        bits_per_channel,
+        ..
+    } = format;
+    let channels_per_buffer = if (format_flags & kAudioFormatFlagIsNonInterleaved) != 0 {
+        1
+    } else {
+        channels_per_frame
+    };
+    let is_float = (format_flags & kAudioFormatFlagIsFloat) != 0;
+    let is_signed_integer = (format_flags & kAudioFormatFlagIsSignedInteger) != 0;
+    format_id == kAudioFormatLinearPCM
+        && (channels_per_frame == 1 || channels_per_frame == 2)
+        && (format_flags & kAudioFormatFlagIsBigEndian) == 0
+        && ((is_float && bits_per_channel == 32)
+            || (is_signed_integer && bits_per_channel == 16)
+            || (is_signed_integer
+                && bits_per_channel == 32
+                && matches!(sample_fraction_bits(format), 0 | 15..=31)))
+        && bytes_per_frame == (bits_per_channel / 8) * channels_per_buffer
+}
+
+/// Convert a sample in a supported stream format to a 16-bit signed integer.
+fn convert_sample(format: &AudioStreamBasicDescription, bytes: &[u8]) -> i16 {
+    if (format.format_flags & kAudioFormatFlagIsFloat) != 0 {
+        let sample = f32::from_le_bytes(bytes.try_into().unwrap());
+        return (sample.clamp(-1.0, 1.0) * 32767.0) as i16;
+    }
+    match format.bits_per_channel {
+        16 => i16::from_le_bytes(bytes.try_into().unwrap()),
+        32 => {
+            let fraction_bits = sample_fraction_bits(format);
+            let sample = i32::from_le_bytes(bytes.try_into().unwrap());
+            if fraction_bits == 0 {
+                (sample >> 16) as i16
+            } else {
+                // Fixed-point with a range of -1.0 to 1.0, e.g. 8.24.
+                (sample >> (fraction_bits - 15)).clamp(i16::MIN.into(), i16::MAX.into()) as i16
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+pub(super) fn AudioUnitInitialize(env: &mut Environment, in_unit: AudioUnit) -> OSStatus {
+    return_if_null!(in_unit);
+
+    State::get(&mut env.framework_state)
+        .audio_component_instances
+        .get_mut(&in_unit)
+        .unwrap()
+        .initialized = true;
+    log_dbg!("AudioUnitInitialize({:?}) -> 0", in_unit);
+    0 // success
+}
+
+pub(super) fn AudioUnitUninitialize(env: &mut Environment, in_unit: AudioUnit) -> OSStatus {
+    return_if_null!(in_unit);
+
+    State::get(&mut env.framework_state)
+        .audio_component_instances
+        .get_mut(&in_unit)
+        .unwrap()
+        .initialized = false;
+    log_dbg!("AudioUnitUninitialize({:?}) -> 0", in_unit);
+    0 // success
+}
+
+/// Call the listeners for a property that has changed.
+fn notify_property_listeners(
+    env: &mut Environment,
+    unit: AudioUnit,
+    id: AudioUnitPropertyID,
+    scope: AudioUnitScope,
+    element: AudioUnitElement,
+) {
+    let listeners: Vec<_> = State::get(&mut env.framework_state).audio_component_instances[&unit]
+        .property_listeners
+        .iter()
+        .filter(|&&(listener_id, _, _)| listener_id == id)
+        .map(|&(_, proc_, ref_con)| (proc_, ref_con))
+        .collect();
+    for (proc_, ref_con) in listeners {
+        log_dbg!(
+            "Calling property listener {:?} with ref con {:?} for property {} of unit {:?}",
+            proc_,
+            ref_con,
+            id,
+            unit
+        );
+        let () = proc_.call_from_host(env, (ref_con, unit, id, scope, element));
+    }
+}
+
+fn AudioUnitAddPropertyListener(
+    env: &mut Environment,
+    in_unit: AudioUnit,
+    in_ID: AudioUnitPropertyID,
+    in_proc: AudioUnitPropertyListenerProc,
+    in_proc_user_data: MutVoidPtr,
+) -> OSStatus {
+    return_if_null!(in_unit);
+
+    State::get(&mut env.framework_state)
+        .audio_component_instances
+        .get_mut(&in_unit)
+        .unwrap()
+        .property_listeners
+        .push((in_ID, in_proc, in_proc_user_data));
+    log_dbg!(
+        "AudioUnitAddPropertyListener({:?}, {}, {:?}, {:?}) -> 0",
+        in_unit,
+        in_ID,
+        in_proc,
+        in_proc_user_data
+    );
+    0 // success
+}
+
+/// Remove the listeners for a property with a particular callback, and if
+/// `user_data` is provided, that user data.
+fn remove_property_listeners(
+    env: &mut Environment,
+    unit: AudioUnit,
+    id: AudioUnitPropertyID,
+    proc_: AudioUnitPropertyListenerProc,
+    user_data: Option<MutVoidPtr>,
+) {
+    State::get(&mut env.framework_state)
+        .audio_component_instances
+        .get_mut(&unit)
+        .unwrap()
+        .property_listeners
+        .retain(|&(listener_id, listener_proc, listener_user_data)| {
+            !(listener_id == id
+                && listener_proc == proc_
+                && user_data.map_or(true, |user_data| user_data == listener_user_data))
+        });
+}
+
+fn AudioUnitRemovePropertyListener(
+    env: &mut Environment,
+    in_unit: AudioUnit,
+    in_ID: AudioUnitPropertyID,
+    in_proc: AudioUnitPropertyListenerProc,
+) -> OSStatus {
+    return_if_null!(in_unit);
+
+    remove_property_listeners(env, in_unit, in_ID, in_proc, None);
+    log_dbg!(
+        "AudioUnitRemovePropertyListener({:?}, {}, {:?}) -> 0",
+        in_unit,
+        in_ID,
+        in_proc
+    );
+    0 // success
+}
+
+fn AudioUnitRemovePropertyListenerWithUserData(
+    env: &mut Environment,
+    in_unit: AudioUnit,
+    in_ID: AudioUnitPropertyID,
+    in_proc: AudioUnitPropertyListenerProc,
+    in_proc_user_data: MutVoidPtr,
+) -> OSStatus {
+    return_if_null!(in_unit);
+
+    remove_property_listeners(env, in_unit, in_ID, in_proc, Some(in_proc_user_data));
+    log_dbg!(
+        "AudioUnitRemovePropertyListenerWithUserData({:?}, {}, {:?}, {:?}) -> 0",
+        in_unit,
+        in_ID,
+        in_proc,
+        in_proc_user_data
+    );
+    0 // success
+}
+
+pub(super) fn AudioUnitSetProperty(
+    env: &mut Environment,
+    in_unit: AudioUnit,
+    in_ID: AudioUnitPropertyID,
+    in_scope: AudioUnitScope,
+    in_element: AudioUnitElement,
+    in_data: ConstVoidPtr,
+    in_data_size: u32,
+) -> OSStatus {
+    return_if_null!(in_unit);
+
+    let required_size: GuestUSize = match in_ID {
+        kAudioUnitProperty_StreamFormat => guest_size_of::<AudioStreamBasicDescription>(),
+        kAudioUnitProperty_MaximumFramesPerSlice => guest_size_of::<u32>(),
+        kAudioUnitProperty_SetRenderCallback => guest_size_of::<AURenderCallbackStruct>(),
+        kAudioOutputUnitProperty_EnableIO => guest_size_of::<u32>(),
+        kAudioUnitProperty_MakeConnection => guest_size_of::<AudioUnitConnection>(),
+        kAudioUnitProperty_ElementCount => guest_size_of::<u32>(),
+        _ => unimplemented!("Unimplemented property ID: {}", debug_fourcc(in_ID)),
+    };
+    if in_data_size != required_size {
+        log!("Warning: AudioUnitSetProperty() failed");
+        return kAudioUnitErr_InvalidPropertyValue;
+    }
+
+    let host_object = State::get(&mut env.framework_state)
+        .audio_component_instances
+        .get_mut(&in_unit)
+        .unwrap();
+
+    let result = match in_ID {
+        kAudioUnitProperty_StreamFormat if host_object.kind == AudioUnitKind::MultiChannelMixer => {
+            let format: AudioStreamBasicDescription = env.mem.read(in_data.cast());
+            if in_scope == kAudioUnitScope_Input {
+                match host_object.mixer_inputs.get_mut(in_element as usize) {
+                    Some(input) if is_supported_stream_format(&format) => {
+                        input.stream_format = Some(format);
+                        0
+                    }
+                    Some(_) => {
+                        log!("Warning: Unsupported stream format {:?}", format);
+                        kAudioUnitErr_FormatNotSupported
+                    }
+                    None => kAudioUnitErr_InvalidElement,
+                }
+            } else {
+                // The mix is converted to RemoteIO's format, so the mixer's
+                // output format is only remembered.
+                host_object.stream_format = Some(format);
+                0
+            }
+        }
+        kAudioUnitProperty_StreamFormat => {
+            let format: AudioStreamBasicDescription = env.mem.read(in_data.cast());
+            if in_scope == kAudioUnitScope_Input && in_element == OUTPUT_ELEMENT {
+                if is_supported_stream_format(&format) {
+                    host_object.stream_format = Some(format);
+                    0
+                } else {
+                    log!("Warning: Unsupported stream format {:?}", format);
+                    kAudioUnitErr_FormatNotSupported
+                }
+            } else {
+                // This is probably the format of the input hardware's output,
+                // which is irrelevant since audio input isn't supported.
+                log!(
+                    "TODO: Ignoring stream format {:?} for scope {}, element {}",
+                    format,
+                    in_scope,
+                    in_element
+                );
+                0
+            }
+        }
+        kAudioUnitProperty_MaximumFramesPerSlice => {
+            let max_frames: u32 = env.mem.read(in_data.cast());
+            host_object.max_frames_per_slice = Some(max_frames);
+            0
+        }
+        kAudioUnitProperty_SetRenderCallback => {
+            let callback: AURenderCallbackStruct = env.mem.read(in_data.cast());
+            // A null callback function removes the existing callback.
+            let callback = (!callback.input_proc.to_ptr().is_null()).then_some(callback);
+            if host_object.kind == AudioUnitKind::MultiChannelMixer {
+                match host_object.mixer_inputs.get_mut(in_element as usize) {
+                    Some(input) => {
+                        input.render_callback = callback;
+                        0
+                    }
+                    None => kAudioUnitErr_InvalidElement,
+                }
+            } else {
+                // A callback replaces any connection, and vice versa.
+                host_object.render_callback = callback;
+                host_object.input_connection = None;
+                0
+            }
+        }
+        kAudioUnitProperty_MakeConnection => {
+            let connection: AudioUnitConnection = env.mem.read(in_data.cast());
+            let source = connection.source_audio_unit;
+            // The mixer only has one output element.
+            if connection.source_output_number != 0 || connection.dest_input_number != in_element {
+                kAudioUnitErr_InvalidElement
+            } else if host_object.kind != AudioUnitKind::RemoteIO || in_element != OUTPUT_ELEMENT {
+                log!(
+                    "TODO: Connection to element {} of {:?} audio unit {:?}",
+                    in_element,
+                    host_object.kind,
+                    in_unit
+                );
+                kAudioUnitErr_InvalidElement
+            } else if source.is_null() {
+                host_object.input_connection = None;
+                0
+            } else {
+                host_object.input_connection = Some(source);
+                host_object.render_callback = None;
+                0
+            }
+        }
+        kAudioUnitProperty_ElementCount => {
+            let count: u32 = env.mem.read(in_data.cast());
+            if host_object.kind == AudioUnitKind::MultiChannelMixer
+                && in_scope == kAudioUnitScope_Input
+            {
+                host_object
+                    .mixer_inputs
+                    .resize_with(count as usize, MixerInput::default);
+                0
+            } else {
+                kAudioUnitErr_PropertyNotWritable
+            }
+        }
+        kAudioOutputUnitProperty_EnableIO => {
+            let enable: u32 = env.mem.read(in_data.cast());
+            if in_scope == kAudioUnitScope_Input && in_element == INPUT_ELEMENT && enable != 0 {
+                log!("TODO: Audio input is not supported, RemoteIO will not record anything");
+            } else if in_scope == kAudioUnitScope_Output
+                && in_element == OUTPUT_ELEMENT
+                && enable == 0
+            {
+                log!("TODO: Disabling RemoteIO output is not supported");
+            }
+            0
+        }
+        _ => unreachable!(),
+    };
+    log_dbg!(
+        "AudioUnitSetProperty({:?}, {}, {}, {}, {:?}, {}) -> {}",
+        in_unit,
+        in_ID,
+        in_scope,
+        in_element,
+        in_data,
+        in_data_size,
+        result
+    );
+    if result == 0 {
+        notify_property_listeners(env, in_unit, in_ID, in_scope, in_element);
+    }
+    result
+}
+
+fn AudioUnitGetProperty(
+    env: &mut Environment,
+    in_unit: AudioUnit,
+    in_ID: AudioUnitPropertyID,
+    in_scope: AudioUnitScope,
+    in_element: AudioUnitElement,
+    out_data: MutVoidPtr,
+    io_data_size: MutPtr<u32>,
+) -> OSStatus {
+    return_if_null!(in_unit);
+
+    let required_size: GuestUSize = match in_ID {
+        kAudioUnitProperty_StreamFormat => guest_size_of::<AudioStreamBasicDescription>(),
+        kAudioUnitProperty_MaximumFramesPerSlice => guest_size_of::<u32>(),
+        kAudioUnitProperty_ElementCount => guest_size_of::<u32>(),
+        kAudioOutputUnitProperty_IsRunning => guest_size_of::<u32>(),
+        _ => unimplemented!("Unimplemented property ID: {}", debug_fourcc(in_ID)),
+    };
+    if env.mem.read(io_data_size) != required_size {
+        log!("Warning: AudioUnitGetProperty() failed");
+        return kAudioUnitErr_InvalidPropertyValue;
+    }
+
+    let io_buffer_frames = audio_session::State::get(&mut env.framework_state).io_buffer_frames();
+    let host_object = State::get(&mut env.framework_state)
+        .audio_component_instances
+        .get_mut(&in_unit)
+        .unwrap();
+
+    let is_mixer_input =
+        host_object.kind == AudioUnitKind::MultiChannelMixer && in_scope == kAudioUnitScope_Input;
+    let result = match in_ID {
+        kAudioUnitProperty_StreamFormat if is_mixer_input => {
+            match host_object.mixer_inputs.get(in_element as usize) {
+                Some(input) => {
+                    let format = input.stream_format.unwrap_or(DEFAULT_STREAM_FORMAT);
+                    env.mem.write(out_data.cast(), format);
+                    0
+                }
+                None => kAudioUnitErr_InvalidElement,
+            }
+        }
+        kAudioUnitProperty_StreamFormat => {
+            // TODO: Track the formats of the other scopes and elements.
+            let format = host_object.stream_format.unwrap_or(DEFAULT_STREAM_FORMAT);
+            env.mem.write(out_data.cast(), format);
+            0
+        }
+        kAudioUnitProperty_MaximumFramesPerSlice => {
+            let max_frames = host_object.max_frames_per_slice.unwrap_or(io_buffer_frames);
+            env.mem.write(out_data.cast(), max_frames);
+            0
+        }
+        kAudioUnitProperty_ElementCount => {
+            let count: u32 = if is_mixer_input {
+                host_object.mixer_inputs.len().try_into().unwrap()
+            } else if host_object.kind == AudioUnitKind::RemoteIO
+                && in_scope != kAudioUnitScope_Global
+            {
+                // Output and input hardware elements.
+                2
+            } else {
+                1
+            };
+            env.mem.write(out_data.cast(), count);
+            0
+        }
+        kAudioOutputUnitProperty_IsRunning => {
+            let is_running: u32 = host_object.is_running.into();
+            env.mem.write(out_data.cast(), is_running);
+            0
+        }
+        _ => unreachable!(),
+    };
+    log_dbg!(
+        "AudioUnitGetProperty({:?}, {}, {}, {}, {:?}, {:?}) -> {}",
+        in_unit,
+        in_ID,
+        in_scope,
+        in_element,
+        out_data,
+        io_data_size,
+        result
+    );
+    result
+}
+
+fn AudioUnitSetParameter(
+    env: &mut Environment,
+    in_unit: AudioUnit,
+    in_ID: AudioUnitParameterID,
+    in_scope: AudioUnitScope,
+    in_element: AudioUnitElement,
+    in_value: AudioUnitParameterValue,
+    in_buffer_offset_in_frames: u32,
+) -> OSStatus {
+    return_if_null!(in_unit);
+
+    let host_object = State::get(&mut env.framework_state)
+        .audio_component_instances
+        .get_mut(&in_unit)
+        .unwrap();
+    // The buffer offset is ignored: changes apply from the next render.
+    let result = match host_object.kind {
+        AudioUnitKind::RemoteIO => match (in_ID, in_scope) {
+            (kHALOutputParam_Volume, kAudioUnitScope_Global) => {
+                host_object.output_volume = in_value;
+                if let Some(al_source) = host_object.al_source {
+                    let _context_manager =
+                        env.framework_state.audio_toolbox.make_al_context_current();
+                    AudioMixerState::get(&mut env.framework_state)
+                        .set_source_volume(al_source, in_value);
+                }
+                0
+            }
+            _ => kAudioUnitErr_InvalidParameter,
+        },
+        AudioUnitKind::MultiChannelMixer => match (in_ID, in_scope) {
+            (kMultiChannelMixerParam_Volume, kAudioUnitScope_Output) if in_element == 0 => {
+                host_object.mixer_output_volume = in_value;
+                0
+            }
+            (kMultiChannelMixerParam_Volume, kAudioUnitScope_Input) => {
+                match host_object.mixer_inputs.get_mut(in_element as usize) {
+                    Some(input) => {
+                        input.volume = in_value;
+                        0
+                    }
+                    None => kAudioUnitErr_InvalidElement,
+                }
+            }
+            (kMultiChannelMixerParam_Enable, kAudioUnitScope_Input) => {
+                match host_object.mixer_inputs.get_mut(in_element as usize) {
+                    Some(input) => {
+                        input.enabled = in_value != 0.0;
+                        0
+                    }
+                    None => kAudioUnitErr_InvalidElement,
+                }
+            }
+            _ => kAudioUnitErr_InvalidParameter,
+        },
+        kind => {
+            log!("TODO: AudioUnitSetParameter() for {:?} audio unit", kind);
+            kAudioUnitErr_InvalidParameter
+        }
+    };
+    log_dbg!(
+        "AudioUnitSetParameter({:?}, {}, {}, {}, {}, {}) -> {}",
+        in_unit,
+        in_ID,
+        in_scope,
+        in_element,
+        in_value,
+        in_buffer_offset_in_frames,
+        result
+    );
+    result
+}
+
+fn AudioUnitGetParameter(
+    env: &mut Environment,
+    in_unit: AudioUnit,
+    in_ID: AudioUnitParameterID,
+    in_scope: AudioUnitScope,
+    in_element: AudioUnitElement,
+    out_value: MutPtr<AudioUnitParameterValue>,
+) -> OSStatus {
+    return_if_null!(in_unit);
+    return_if_null!(out_value);
+
+    let host_object = State::get(&mut env.framework_state)
+        .audio_component_instances
+        .get(&in_unit)
+        .unwrap();
+    let value = match host_object.kind {
+        AudioUnitKind::RemoteIO => match (in_ID, in_scope) {
+            (kHALOutputParam_Volume, kAudioUnitScope_Global) => Ok(host_object.output_volume),
+            _ => Err(kAudioUnitErr_InvalidParameter),
+        },
+        AudioUnitKind::MultiChannelMixer => {
+            let input = host_object.mixer_inputs.get(in_element as usize);
+            match (in_ID, in_scope) {
+                (kMultiChannelMixerParam_Volume, kAudioUnitScope_Output) if in_element == 0 => {
+                    Ok(host_object.mixer_output_volume)
+                }
+                (kMultiChannelMixerParam_Volume, kAudioUnitScope_Input) => input
+                    .map(|input| input.volume)
+                    .ok_or(kAudioUnitErr_InvalidElement),
+                (kMultiChannelMixerParam_Enable, kAudioUnitScope_Input) => input
+                    .map(|input| if input.enabled { 1.0 } else { 0.0 })
+                    .ok_or(kAudioUnitErr_InvalidElement),
+                _ => Err(kAudioUnitErr_InvalidParameter),
+            }
+        }
+        kind => {
+            log!("TODO: AudioUnitGetParameter() for {:?} audio unit", kind);
+            Err(kAudioUnitErr_InvalidParameter)
+        }
+    };
+    let result = match value {
+        Ok(value) => {
+            env.mem.write(out_value, value);
+            0
+        }
+        Err(err) => err,
+    };
+    log_dbg!(
+        "AudioUnitGetParameter({:?}, {}, {}, {}, {:?}) -> {}",
+        in_unit,
+        in_ID,
+        in_scope,
+        in_element,
+        out_value,
+        result
+    );
+    result
+}
+
+pub(super) fn AudioOutputUnitStart(env: &mut Environment, ci: AudioUnit) -> OSStatus {
+    return_if_null!(ci);
+
+    let host_object = State::get(&mut env.framework_state)
+        .audio_component_instances
+        .get_mut(&ci)
+        .unwrap();
+    if !host_object.initialized {
+        log!(
+            "Warning: AudioOutputUnitStart() on uninitialized unit {:?}",
+            ci
+        );
+        return kAudioUnitErr_Uninitialized;
+    }
+    let was_running = std::mem::replace(&mut host_object.is_running, true);
+    let kind = host_object.kind;
+    log_dbg!("AudioOutputUnitStart({:?}) -> 0", ci);
+    if !was_running {
+        notify_property_listeners(
+            env,
+            ci,
+            kAudioOutputUnitProperty_IsRunning,
+            kAudioUnitScope_Global,
+            0,
+        );
+    }
+
+    if kind != AudioUnitKind::RemoteIO {
+        log!(
+            "TODO: {:?} audio unit {:?} started, but it won't be rendered",
+            kind,
+            ci
+        );
+        return 0;
+    }
+
+    // Render the first few buffers right away and start playing.
+    handle_audio_unit(env, ci);
+
+    0 // success
+}
+
+pub(super) fn AudioOutputUnitStop(env: &mut Environment, ci: AudioUnit) -> OSStatus {
+    return_if_null!(ci);
+
+    let _context_manager = env.framework_state.audio_toolbox.make_al_context_current();
+
+    let host_object = State::get(&mut env.framework_state)
+        .audio_component_instances
+        .get_mut(&ci)
+        .unwrap();
+    let was_running = std::mem::replace(&mut host_object.is_running, false);
+    if let Some(al_source) = host_object.al_source {
+        unsafe {
+            al::alSourceStop(al_source);
+            assert!(al::alGetError() == 0);
+        }
+        // Stopping the source marks all buffers as processed.
+        unqueue_buffers(al_source, &mut host_object.al_unused_buffers);
+    }
+    std::mem::drop(_context_manager);
+    log_dbg!("AudioOutputUnitStop({:?}) -> 0", ci);
+    if was_running {
+        notify_property_listeners(
+            env,
+            ci,
+            kAudioOutputUnitProperty_IsRunning,
+            kAudioUnitScope_Global,
+            0,
+        );
+    }
+    0 // success
+}
+
+/// Unqueue all processed buffers from an OpenAL source so they can be reused.
+fn unqueue_buffers(al_source: ALuint, unused_buffers: &mut Vec<ALuint>) {
+    loop {
+        let mut al_buffers_processed = 0;
+        unsafe {
+            al::alGetSourcei(
+                al_source,
+                al::AL_BUFFERS_PROCESSED,
+                &mut al_buffers_processed,
+            );
+            assert!(al::alGetError() == 0);
+        }
+        if al_buffers_processed == 0 {
+            break;
+        }
+
+        let mut al_buffer = 0;
+        unsafe {
+            al::alSourceUnqueueBuffers(al_source, 1, &mut al_buffer);
+            assert!(al::alGetError() == 0);
+        }
+        unused_buffers.push(al_buffer);
+    }
+}
+
+/// For use by `AudioComponentInstanceDispose`.
+pub fn delete_al_objects(env: &mut Environment, mut host_object: AudioComponentInstanceHostObject) {
+    let Some(al_source) = host_object.al_source else {
+        assert!(host_object.al_unused_buffers.is_empty());
+        return;
+    };
+
+    let _context_manager = env.framework_state.audio_toolbox.make_al_context_current();
+    unsafe {
+        al::alSourceStop(al_source);
+        assert!(al::alGetError() == 0);
+    }
+    unqueue_buffers(al_source, &mut host_object.al_unused_buffers);
+    AudioMixerState::get(&mut env.framework_state).remove_source(al_source);
+    unsafe {
+        al::alDeleteSources(1, &al_source);
+        for al_buffer in host_object.al_unused_buffers {
+            al::alDeleteBuffers(1, &al_buffer);
+        }
+        assert!(al::alGetError() == 0);
+    }
+}
+
+/// Call the guest's render callback to get the next `frame_count` frames of
+/// audio, converted to interleaved 16-bit PCM. Returns the PCM data and the
+/// number of channels.
+fn render(env: &mut Environment, unit: AudioUnit, frame_count: u32) -> (Vec<i16>, u32) {
+    let host_object = State::get(&mut env.framework_state)
+        .audio_component_instances
+        .get_mut(&unit)
+        .unwrap();
+    let format = host_object.stream_format.unwrap_or(DEFAULT_STREAM_FORMAT);
+    let channels = format.channels_per_frame;
+    let sample_time = host_object.sample_time;
+    host_object.sample_time += f64::from(frame_count);
+
+    if let Some(source) = host_object.input_connection {
+        return (render_mixer(env, source, frame_count, channels), channels);
+    }
+
+    let Some(callback) = host_object.render_callback.clone() else {
+        return (vec![0; (frame_count * channels) as usize], channels);
+    };
+    let pcm = call_render_callback(
+        env,
+        unit,
+        callback,
+        &format,
+        OUTPUT_ELEMENT,
+        sample_time,
+        frame_count,
+    );
+    (pcm, channels)
+}
+
+/// Render the input buses of a multichannel mixer unit and mix them, with
+/// each converted to `channels` channels.
+fn render_mixer(
+    env: &mut Environment,
+    mixer: AudioUnit,
+    frame_count: u32,
+    channels: u32,
+) -> Vec<i16> {
+    let silence = vec![0; (frame_count * channels) as usize];
+    // The mixer might have been disposed of without disconnecting it.
+    let Some(host_object) = State::get(&mut env.framework_state)
+        .audio_component_instances
+        .get_mut(&mixer)
+    else {
+        return silence;
+    };
+    if host_object.kind != AudioUnitKind::MultiChannelMixer {
+        log!(
+            "TODO: Rendering {:?} audio unit {:?} connected to RemoteIO",
+            host_object.kind,
+            mixer
+        );
+        return silence;
+    }
+    let sample_time = host_object.sample_time;
+    host_object.sample_time += f64::from(frame_count);
+    let output_volume = host_object.mixer_output_volume;
+    let inputs = host_object.mixer_inputs.clone();
+
+    let channels = channels as usize;
+    let mut mix = vec![0f32; frame_count as usize * channels];
+    for (bus, input) in inputs.into_iter().enumerate() {
+        // Disabled buses aren't rendered at all.
+        if !input.enabled {
+            continue;
+        }
+        let Some(callback) = input.render_callback else {
+            continue;
+        };
+        let format = input.stream_format.unwrap_or(DEFAULT_STREAM_FORMAT);
+        let pcm = call_render_callback(
+            env,
+            mixer,
+            callback,
+            &format,
+            bus.try_into().unwrap(),
+            sample_time,
+            frame_count,
+        );
+        let in_channels = format.channels_per_frame as usize;
+        for (in_frame, out_frame) in pcm
+            .chunks_exact(in_channels)
+            .zip(mix.chunks_exact_mut(channels))
+        {
+            for (channel, out_sample) in out_frame.iter_mut().enumerate() {
+                let sample = if in_channels == channels {
+                    f32::from(in_frame[channel])
+                } else if in_channels == 1 {
+                    f32::from(in_frame[0])
+                } else {
+                    // Downmix to mono.
+                    in_frame
+                        .iter()
+                        .map(|&sample| f32::from(sample))
+                        .sum::<f32>()
+                        / in_channels as f32
+                };
+                *out_sample += sample * input.volume;
+            }
+        }
+    }
+
+    mix.into_iter()
+        .map(|sample| {
+            (sample * output_volume).clamp(f32::from(i16::MIN), f32::from(i16::MAX)) as i16
+        })
+        .collect()
+}
+
+/// Call a render callback for `frame_count` frames in `format`, and convert
+/// the result to interleaved 16-bit samples.
+fn call_render_callback(
+    env: &mut Environment,
+    unit: AudioUnit,
+    callback: AURenderCallbackStruct,
+    format: &AudioStreamBasicDescription,
+    bus: AudioUnitElement,
+    sample_time: f64,
+    frame_count: u32,
+) -> Vec<i16> {
+    let AURenderCallbackStruct {
+        input_proc,
+        input_proc_ref_con,
+    } = callback;
+    let channels = format.channels_per_frame;
+
+    let non_interleaved = (format.format_flags & kAudioFormatFlagIsNonInterleaved) != 0;
+    let (buffer_count, channels_per_buffer) = if non_interleaved {
+        (channels, 1)
+    } else {
+        (1, channels)
+    };
+    let bytes_per_sample = format.bits_per_channel / 8;
+    let buffer_size = frame_count * format.bytes_per_frame;
+
+    // Set up the AudioBufferList. The callback may replace the data pointers
+    // with its own, so our allocations have to be remembered separately.
+    let list_size =
+        guest_size_of::<AudioBufferListHeader>() + buffer_count * guest_size_of::<AudioBuffer>();
+    let list_ptr: MutPtr<AudioBufferListHeader> = env.mem.alloc(list_size).cast();
+    env.mem.write(
+        list_ptr,
+        AudioBufferListHeader {
+            number_buffers: buffer_count,
+        },
+    );
+    let buffers_ptr: MutPtr<AudioBuffer> = (list_ptr + 1).cast();
+    let mut data_allocations = Vec::new();
+    for i in 0..buffer_count {
+        let data = env.mem.alloc(buffer_size);
+        data_allocations.push(data);
+        env.mem.write(
+            buffers_ptr + i,
+            AudioBuffer {
+                number_channels: channels_per_buffer,
+                data_byte_size: buffer_size,
+                data,
+            },
+        );
+    }
+    let time_stamp_ptr = env.mem.alloc_and_write(AudioTimeStamp {
+        sample_time,
+        host_time: 0,
+        rate_scalar: 0.0,
+        word_clock_time: 0,
+        _smpte_time: [0; 24],
+        flags: kAudioTimeStampSampleTimeValid,
+        _reserved: 0,
+    });
+    let action_flags_ptr: MutPtr<AudioUnitRenderActionFlags> = env.mem.alloc_and_write(0);
+
+    log_dbg!(
+        "Calling render callback {:?} with ref con {:?} for {} frames for unit {:?} bus {}",
+        input_proc,
+        input_proc_ref_con,
+        frame_count,
+        unit,
+        bus
+    );
+    let status: OSStatus = input_proc.call_from_host(
+        env,
+        (
+            input_proc_ref_con,
+            action_flags_ptr,
+            time_stamp_ptr.cast_const(),
+            bus,
+            frame_count,
+            list_ptr,
+        ),
+    );
+
+    let action_flags = env.mem.read(action_flags_ptr);
+    let mut pcm = vec![0i16; (frame_count * channels) as usize];
+    if status != 0 {
+        log!(
+            "Warning: Render callback for unit {:?} returned {}, outputting silence",
+            unit,
+            status
+        );
+    } else if (action_flags & kAudioUnitRenderAction_OutputIsSilence) == 0 {
+        for buffer_idx in 0..buffer_count {
+            let AudioBuffer {
+                data,
+                data_byte_size,
+                ..
+            } = env.mem.read(buffers_ptr + buffer_idx);
+            let data = env
+                .mem
+                .bytes_at(data.cast(), data_byte_size.min(buffer_size));
+            for (frame_idx, frame) in data
+                .chunks_exact(format.bytes_per_frame as usize)
+                .enumerate()
+            {
+                for (channel_idx, sample) in
+                    frame.chunks_exact(bytes_per_sample as usize).enumerate()
+                {
+                    let out_channel = buffer_idx as usize + channel_idx;
+                    pcm[frame_idx * channels as usize + out_channel] =
+                        convert_sample(format, sample);
+                }
+            }
+        }
+    }
+
+    for data in data_allocations {
+        env.mem.free(data);
+    }
+    env.mem.free(list_ptr.cast());
+    env.mem.free(time_stamp_ptr.cast());
+    env.mem.free(action_flags_ptr.cast());
+
+    pcm
+}
+
+/// For use by `NSRunLoop`: keep all running audio units supplied with audio.
+pub fn handle_audio_units(env: &mut Environment) {
+    let units: Vec<AudioUnit> = State::get(&mut env.framework_state)
+        .audio_component_instances
+        .iter()
+        .filter(|(_, host_object)| {
+            host_object.is_running && host_object.kind == AudioUnitKind::RemoteIO
+        })
+        .map(|(&unit, _)| unit)
+        .collect();
+    for unit in units {
+        handle_audio_unit(env, unit);
+    }
+}
+
+/// Recycle played buffers, render new ones until enough audio is queued, and
+/// make sure the OpenAL source is playing.
+fn handle_audio_unit(env: &mut Environment, unit: AudioUnit) {
+    let _context_manager = env.framework_state.audio_toolbox.make_al_context_current();
+
+    let io_buffer_frames = audio_session::State::get(&mut env.framework_state).io_buffer_frames();
+
+    let host_object = State::get(&mut env.framework_state)
+        .audio_component_instances
+        .get_mut(&unit)
+        .unwrap();
+    let frame_count = io_buffer_frames.min(host_object.max_frames_per_slice.unwrap_or(u32::MAX));
+    let sample_rate = host_object
+        .stream_format
+        .unwrap_or(DEFAULT_STREAM_FORMAT)
+        .sample_rate;
+    let min_queued_buffers = ((MIN_QUEUED_DURATION * sample_rate) / f64::from(frame_count))
+        .ceil()
+        .max(2.0) as ALint;
+
+    let al_source = match host_object.al_source {
+        Some(al_source) => al_source,
+        None => {
+            let mut al_source = 0;
+            unsafe {
+                al::alGenSources(1, &mut al_source);
+                assert!(al::alGetError() == 0);
+            }
+            host_object.al_source = Some(al_source);
+            // The output goes through the mixer, which applies the volume.
+            let volume = host_object.output_volume;
+            AudioMixerState::get(&mut env.framework_state).add_source(al_source, volume);
+            al_source
+        }
+    };
+
+    let host_object = State::get(&mut env.framework_state)
+        .audio_component_instances
+        .get_mut(&unit)
+        .unwrap();
+    unqueue_buffers(al_source, &mut host_object.al_unused_buffers);
+
+    loop {
+        let mut al_buffers_queued = 0;
+        unsafe {
+            al::alGetSourcei(al_source, al::AL_BUFFERS_QUEUED, &mut al_buffers_queued);
+            assert!(al::alGetError() == 0);
+        }
+        if al_buffers_queued >= min_queued_buffers {
+            break;
+        }
+
+        let (pcm, channels) = render(env, unit, frame_count);
+
+        // The render callback might have stopped or disposed of the unit.
+        let Some(host_object) = State::get(&mut env.framework_state)
+            .audio_component_instances
+            .get_mut(&unit)
+        else {
+            return;
+        };
+        if !host_object.is_running {
+            return;
+        }
+
+        let al_buffer = host_object.al_unused_buffers.pop().unwrap_or_else(|| {
+            let mut al_buffer = 0;
+            unsafe { al::alGenBuffers(1, &mut al_buffer) };
+            assert!(unsafe { al::alGetError() } == 0);
+            al_buffer
+        });
+        let al_format = if channels == 1 {
+            al::AL_FORMAT_MONO16
+        } else {
+            al::AL_FORMAT_STEREO16
+        };
+        unsafe {
+            al::alBufferData(
+                al_buffer,
+                al_format,
+                pcm.as_ptr() as *const ALvoid,
+                (pcm.len() * std::mem::size_of::<i16>()).try_into().unwrap(),
+                sample_rate as ALsizei,
+            );
+            al::alSourceQueueBuffers(al_source, 1, &al_buffer);
+            assert!(al::alGetError() == 0);
+        }
+    }
+
+    // The source stops if it runs out of data, e.g. because touchHLE lagged.
+    unsafe {
+        let mut al_source_state = 0;
+        al::alGetSourcei(al_source, al::AL_SOURCE_STATE, &mut al_source_state);
+        assert!(al::alGetError() == 0);
+        if al_source_state != al::AL_PLAYING {
+            al::alSourcePlay(al_source);
+            assert!(al::alGetError() == 0);
+        }
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(AudioUnitInitialize(_)),
+    export_c_func!(AudioUnitUninitialize(_)),
+    export_c_func!(AudioUnitSetProperty(_, _, _, _, _, _)),
+    export_c_func!(AudioUnitGetProperty(_, _, _, _, _, _)),
+    export_c_func!(AudioUnitSetParameter(_, _, _, _, _, _)),
+    export_c_func!(AudioUnitGetParameter(_, _, _, _, _)),
+    export_c_func!(AudioUnitAddPropertyListener(_, _, _, _)),
+    export_c_func!(AudioUnitRemovePropertyListener(_, _, _)),
+    export_c_func!(AudioUnitRemovePropertyListenerWithUserData(_, _, _, _)),
+    export_c_func!(AudioOutputUnitStart(_)),
+    export_c_func!(AudioOutputUnitStop(_)),
+];
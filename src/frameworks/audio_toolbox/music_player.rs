@@ -0,0 +1,558 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `MusicPlayer.h` (Music Player and Music Sequence)
+//!
+//! Only loading Standard MIDI Files and playing them back is supported. The
+//! whole sequence is synthesized up-front by [crate::audio::MidiSequence] and
+//! played as a single OpenAL buffer. Editing sequences and tracks, and routing
+//! them through an AUGraph, is not supported.
+
+use crate::audio;
+use crate::audio::openal as al;
+use crate::audio::openal::al_types::*;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::carbon_core::{fnfErr, OSStatus};
+use crate::frameworks::core_audio_types::{debug_fourcc, fourcc};
+use crate::frameworks::core_foundation::cf_data::CFDataRef;
+use crate::frameworks::core_foundation::cf_url::CFURLRef;
+use crate::frameworks::foundation::ns_url::to_rust_path;
+use crate::frameworks::foundation::NSUInteger;
+use crate::mem::{ConstVoidPtr, MutPtr, SafeRead};
+use crate::objc::msg;
+use crate::Environment;
+use std::collections::HashMap;
+
+/// Sample rate used for synthesis. This is lower than the hardware rate to
+/// keep the up-front synthesis cost down.
+const SAMPLE_RATE: u32 = 22050;
+
+#[derive(Default)]
+pub struct State {
+    sequences: HashMap<MusicSequence, MusicSequenceHostObject>,
+    players: HashMap<MusicPlayer, MusicPlayerHostObject>,
+}
+impl State {
+    pub fn get(framework_state: &mut crate::frameworks::State) -> &mut Self {
+        &mut framework_state.audio_toolbox.music_player
+    }
+}
+
+#[repr(C, packed)]
+pub struct OpaqueMusicSequence {
+    _filler: u8,
+}
+unsafe impl SafeRead for OpaqueMusicSequence {}
+pub type MusicSequence = MutPtr<OpaqueMusicSequence>;
+
+#[repr(C, packed)]
+pub struct OpaqueMusicPlayer {
+    _filler: u8,
+}
+unsafe impl SafeRead for OpaqueMusicPlayer {}
+pub type MusicPlayer = MutPtr<OpaqueMusicPlayer>;
+
+/// Time in beats.
+type MusicTimeStamp = f64;
+
+type MusicSequenceFileTypeID = u32;
+const kMusicSequenceFile_AnyType: MusicSequenceFileTypeID = 0;
+const kMusicSequenceFile_MIDIType: MusicSequenceFileTypeID = fourcc(b"midi");
+
+type MusicSequenceLoadFlags = u32;
+
+const kAudioToolboxErr_InvalidSequenceType: OSStatus = -10846;
+const kAudioToolboxErr_NoSequence: OSStatus = -10854;
+const kAudioToolboxErr_CannotDoInCurrentContext: OSStatus = -10863;
+
+struct MusicSequenceHostObject {
+    /// [None] until a file is loaded.
+    midi: Option<audio::MidiSequence>,
+}
+
+#[derive(Default)]
+struct MusicPlayerHostObject {
+    sequence: Option<MusicSequence>,
+    /// The OpenAL source and the buffer holding the synthesized sequence.
+    /// These are created by prerolling, and deleted if the sequence changes.
+    al_source_and_buffer: Option<(ALuint, ALuint)>,
+    /// Length of the synthesized sequence in seconds.
+    length: f64,
+    is_playing: bool,
+    /// Playback position in seconds, only kept up-to-date while stopped.
+    time: f64,
+}
+
+fn NewMusicSequence(env: &mut Environment, out_sequence: MutPtr<MusicSequence>) -> OSStatus {
+    return_if_null!(out_sequence);
+
+    let sequence = env.mem.alloc_and_write(OpaqueMusicSequence { _filler: 0 });
+    State::get(&mut env.framework_state)
+        .sequences
+        .insert(sequence, MusicSequenceHostObject { midi: None });
+    env.mem.write(out_sequence, sequence);
+    log_dbg!("NewMusicSequence() => {:?}", sequence);
+    0 // success
+}
+
+fn DisposeMusicSequence(env: &mut Environment, in_sequence: MusicSequence) -> OSStatus {
+    return_if_null!(in_sequence);
+
+    let state = State::get(&mut env.framework_state);
+    if state
+        .players
+        .values()
+        .any(|player| player.sequence == Some(in_sequence))
+    {
+        return kAudioToolboxErr_CannotDoInCurrentContext;
+    }
+    state.sequences.remove(&in_sequence).unwrap();
+    env.mem.free(in_sequence.cast());
+    log_dbg!("DisposeMusicSequence({:?})", in_sequence);
+    0 // success
+}
+
+fn load_midi_data(
+    env: &mut Environment,
+    sequence: MusicSequence,
+    file_type: MusicSequenceFileTypeID,
+    bytes: &[u8],
+) -> OSStatus {
+    if file_type != kMusicSequenceFile_AnyType && file_type != kMusicSequenceFile_MIDIType {
+        log!(
+            "Warning: Unsupported music sequence file type {:?}",
+            debug_fourcc(file_type)
+        );
+        return kAudioToolboxErr_InvalidSequenceType;
+    }
+    let Ok(midi) = audio::MidiSequence::parse(bytes) else {
+        log!("Warning: Couldn't parse MIDI data for {:?}", sequence);
+        return kAudioToolboxErr_InvalidSequenceType;
+    };
+    State::get(&mut env.framework_state)
+        .sequences
+        .get_mut(&sequence)
+        .unwrap()
+        .midi = Some(midi);
+    0 // success
+}
+
+fn MusicSequenceFileLoad(
+    env: &mut Environment,
+    in_sequence: MusicSequence,
+    in_file_ref: CFURLRef,
+    in_file_type_hint: MusicSequenceFileTypeID,
+    _in_flags: MusicSequenceLoadFlags,
+) -> OSStatus {
+    return_if_null!(in_sequence);
+    return_if_null!(in_file_ref);
+
+    let path = to_rust_path(env, in_file_ref);
+    let Ok(bytes) = env.fs.read(path.as_ref()) else {
+        log!(
+            "Warning: MusicSequenceFileLoad() for path {:?} failed",
+            path
+        );
+        return fnfErr;
+    };
+    let result = load_midi_data(env, in_sequence, in_file_type_hint, &bytes);
+    log_dbg!(
+        "MusicSequenceFileLoad({:?}, {:?}, {:?}, _) => {}",
+        in_sequence,
+        path,
+        in_file_type_hint,
+        result
+    );
+    result
+}
+
+fn load_cf_data(
+    env: &mut Environment,
+    sequence: MusicSequence,
+    data: CFDataRef,
+    file_type: MusicSequenceFileTypeID,
+) -> OSStatus {
+    let ptr: ConstVoidPtr = msg![env; data bytes];
+    let length: NSUInteger = msg![env; data length];
+    let bytes = env.mem.bytes_at(ptr.cast(), length).to_vec();
+    load_midi_data(env, sequence, file_type, &bytes)
+}
+
+fn MusicSequenceFileLoadData(
+    env: &mut Environment,
+    in_sequence: MusicSequence,
+    in_data: CFDataRef,
+    in_file_type_hint: MusicSequenceFileTypeID,
+    _in_flags: MusicSequenceLoadFlags,
+) -> OSStatus {
+    return_if_null!(in_sequence);
+    return_if_null!(in_data);
+
+    load_cf_data(env, in_sequence, in_data, in_file_type_hint)
+}
+
+/// Older, deprecated equivalent of [MusicSequenceFileLoadData].
+fn MusicSequenceLoadSMFDataWithFlags(
+    env: &mut Environment,
+    in_sequence: MusicSequence,
+    in_data: CFDataRef,
+    _in_flags: MusicSequenceLoadFlags,
+) -> OSStatus {
+    return_if_null!(in_sequence);
+    return_if_null!(in_data);
+
+    load_cf_data(env, in_sequence, in_data, kMusicSequenceFile_MIDIType)
+}
+
+fn MusicSequenceGetTrackCount(
+    env: &mut Environment,
+    in_sequence: MusicSequence,
+    out_number_of_tracks: MutPtr<u32>,
+) -> OSStatus {
+    return_if_null!(in_sequence);
+    return_if_null!(out_number_of_tracks);
+
+    let host_object = &State::get(&mut env.framework_state).sequences[&in_sequence];
+    // Tracks containing only tempo events are merged into the tempo track,
+    // which isn't counted.
+    let count = host_object.midi.as_ref().map_or(0, |midi| {
+        midi.tracks
+            .iter()
+            .filter(|track| {
+                track
+                    .iter()
+                    .any(|event| !matches!(event.kind, audio::MidiEventKind::Tempo(_)))
+            })
+            .count()
+    });
+    env.mem
+        .write(out_number_of_tracks, count.try_into().unwrap());
+    0 // success
+}
+
+fn NewMusicPlayer(env: &mut Environment, out_player: MutPtr<MusicPlayer>) -> OSStatus {
+    return_if_null!(out_player);
+
+    let player = env.mem.alloc_and_write(OpaqueMusicPlayer { _filler: 0 });
+    State::get(&mut env.framework_state)
+        .players
+        .insert(player, Default::default());
+    env.mem.write(out_player, player);
+    log_dbg!("NewMusicPlayer() => {:?}", player);
+    0 // success
+}
+
+fn DisposeMusicPlayer(env: &mut Environment, in_player: MusicPlayer) -> OSStatus {
+    return_if_null!(in_player);
+
+    delete_al_objects(env, in_player);
+    State::get(&mut env.framework_state)
+        .players
+        .remove(&in_player)
+        .unwrap();
+    env.mem.free(in_player.cast());
+    log_dbg!("DisposeMusicPlayer({:?})", in_player);
+    0 // success
+}
+
+fn delete_al_objects(env: &mut Environment, player: MusicPlayer) {
+    let Some((al_source, al_buffer)) = State::get(&mut env.framework_state)
+        .players
+        .get_mut(&player)
+        .unwrap()
+        .al_source_and_buffer
+        .take()
+    else {
+        return;
+    };
+
+    let _context_manager = env.framework_state.audio_toolbox.make_al_context_current();
+    unsafe {
+        al::alSourceStop(al_source);
+        al::alDeleteSources(1, &al_source);
+        al::alDeleteBuffers(1, &al_buffer);
+        assert!(al::alGetError() == 0);
+    }
+}
+
+fn MusicPlayerSetSequence(
+    env: &mut Environment,
+    in_player: MusicPlayer,
+    in_sequence: MusicSequence,
+) -> OSStatus {
+    return_if_null!(in_player);
+
+    delete_al_objects(env, in_player);
+    let host_object = State::get(&mut env.framework_state)
+        .players
+        .get_mut(&in_player)
+        .unwrap();
+    *host_object = MusicPlayerHostObject {
+        sequence: (!in_sequence.is_null()).then_some(in_sequence),
+        ..Default::default()
+    };
+    log_dbg!("MusicPlayerSetSequence({:?}, {:?})", in_player, in_sequence);
+    0 // success
+}
+
+fn MusicPlayerGetSequence(
+    env: &mut Environment,
+    in_player: MusicPlayer,
+    out_sequence: MutPtr<MusicSequence>,
+) -> OSStatus {
+    return_if_null!(in_player);
+    return_if_null!(out_sequence);
+
+    let host_object = &State::get(&mut env.framework_state).players[&in_player];
+    let Some(sequence) = host_object.sequence else {
+        return kAudioToolboxErr_NoSequence;
+    };
+    env.mem.write(out_sequence, sequence);
+    0 // success
+}
+
+/// Synthesizes the player's sequence into an OpenAL buffer, if that hasn't
+/// been done already.
+fn preroll(env: &mut Environment, player: MusicPlayer) -> Result<ALuint, OSStatus> {
+    let state = State::get(&mut env.framework_state);
+    let host_object = &state.players[&player];
+    if let Some((al_source, _)) = host_object.al_source_and_buffer {
+        return Ok(al_source);
+    }
+    let Some(sequence) = host_object.sequence else {
+        return Err(kAudioToolboxErr_NoSequence);
+    };
+    let Some(midi) = &state.sequences[&sequence].midi else {
+        return Err(kAudioToolboxErr_NoSequence);
+    };
+
+    let samples = midi.synthesize(SAMPLE_RATE);
+    log_dbg!(
+        "Synthesized {:?} for {:?}: {} samples",
+        sequence,
+        player,
+        samples.len()
+    );
+
+    let _context_manager = env.framework_state.audio_toolbox.make_al_context_current();
+    let mut al_source = 0;
+    let mut al_buffer = 0;
+    unsafe {
+        al::alGenSources(1, &mut al_source);
+        al::alGenBuffers(1, &mut al_buffer);
+        al::alBufferData(
+            al_buffer,
+            al::AL_FORMAT_MONO16,
+            samples.as_ptr() as *const ALvoid,
+            (samples.len() * 2).try_into().unwrap(),
+            SAMPLE_RATE as ALsizei,
+        );
+        al::alSourcei(al_source, al::AL_BUFFER, al_buffer as ALint);
+        assert!(al::alGetError() == 0);
+    }
+    drop(_context_manager);
+
+    let host_object = State::get(&mut env.framework_state)
+        .players
+        .get_mut(&player)
+        .unwrap();
+    host_object.al_source_and_buffer = Some((al_source, al_buffer));
+    host_object.length = samples.len() as f64 / f64::from(SAMPLE_RATE);
+    Ok(al_source)
+}
+
+fn MusicPlayerPreroll(env: &mut Environment, in_player: MusicPlayer) -> OSStatus {
+    return_if_null!(in_player);
+
+    match preroll(env, in_player) {
+        Ok(_) => 0, // success
+        Err(err) => err,
+    }
+}
+
+fn MusicPlayerStart(env: &mut Environment, in_player: MusicPlayer) -> OSStatus {
+    return_if_null!(in_player);
+
+    let al_source = match preroll(env, in_player) {
+        Ok(al_source) => al_source,
+        Err(err) => return err,
+    };
+    let host_object = State::get(&mut env.framework_state)
+        .players
+        .get_mut(&in_player)
+        .unwrap();
+    if host_object.is_playing {
+        return 0; // success
+    }
+    host_object.is_playing = true;
+    let time = host_object.time;
+    let length = host_object.length;
+
+    let _context_manager = env.framework_state.audio_toolbox.make_al_context_current();
+    unsafe {
+        al::alSourcePlay(al_source);
+        // Seeking past the end is an error in OpenAL.
+        if time < length {
+            al::alSourcef(al_source, al::AL_SEC_OFFSET, time as ALfloat);
+        } else {
+            al::alSourceStop(al_source);
+        }
+        assert!(al::alGetError() == 0);
+    }
+    log_dbg!("MusicPlayerStart({:?}) at {}s", in_player, time);
+    0 // success
+}
+
+fn MusicPlayerStop(env: &mut Environment, in_player: MusicPlayer) -> OSStatus {
+    return_if_null!(in_player);
+
+    let host_object = &State::get(&mut env.framework_state).players[&in_player];
+    if !host_object.is_playing {
+        return 0; // success
+    }
+    let time = current_time_in_seconds(env, in_player);
+    let (al_source, _) = State::get(&mut env.framework_state).players[&in_player]
+        .al_source_and_buffer
+        .unwrap();
+    let _context_manager = env.framework_state.audio_toolbox.make_al_context_current();
+    unsafe {
+        al::alSourceStop(al_source);
+        assert!(al::alGetError() == 0);
+    }
+    drop(_context_manager);
+
+    let host_object = State::get(&mut env.framework_state)
+        .players
+        .get_mut(&in_player)
+        .unwrap();
+    host_object.is_playing = false;
+    host_object.time = time;
+    log_dbg!("MusicPlayerStop({:?}) at {}s", in_player, time);
+    0 // success
+}
+
+fn MusicPlayerIsPlaying(
+    env: &mut Environment,
+    in_player: MusicPlayer,
+    out_is_playing: MutPtr<u8>,
+) -> OSStatus {
+    return_if_null!(in_player);
+    return_if_null!(out_is_playing);
+
+    // Like on a real device, the player keeps "playing" after reaching the
+    // end of the sequence, until it's stopped.
+    let is_playing = State::get(&mut env.framework_state).players[&in_player].is_playing;
+    env.mem.write(out_is_playing, is_playing.into());
+    0 // success
+}
+
+fn current_time_in_seconds(env: &mut Environment, player: MusicPlayer) -> f64 {
+    let host_object = &State::get(&mut env.framework_state).players[&player];
+    if !host_object.is_playing {
+        return host_object.time;
+    }
+    let (al_source, _) = host_object.al_source_and_buffer.unwrap();
+    let length = host_object.length;
+
+    let _context_manager = env.framework_state.audio_toolbox.make_al_context_current();
+    let mut state = 0;
+    let mut offset = 0.0;
+    unsafe {
+        al::alGetSourcei(al_source, al::AL_SOURCE_STATE, &mut state);
+        al::alGetSourcef(al_source, al::AL_SEC_OFFSET, &mut offset);
+        assert!(al::alGetError() == 0);
+    }
+    if state == al::AL_STOPPED {
+        // Reached the end of the sequence.
+        length
+    } else {
+        offset.into()
+    }
+}
+
+fn MusicPlayerGetTime(
+    env: &mut Environment,
+    in_player: MusicPlayer,
+    out_time: MutPtr<MusicTimeStamp>,
+) -> OSStatus {
+    return_if_null!(in_player);
+    return_if_null!(out_time);
+
+    let Some(sequence) = State::get(&mut env.framework_state).players[&in_player].sequence else {
+        return kAudioToolboxErr_NoSequence;
+    };
+    let seconds = current_time_in_seconds(env, in_player);
+    let beats = State::get(&mut env.framework_state).sequences[&sequence]
+        .midi
+        .as_ref()
+        .map_or(0.0, |midi| midi.seconds_to_beats(seconds));
+    env.mem.write(out_time, beats);
+    0 // success
+}
+
+fn MusicPlayerSetTime(
+    env: &mut Environment,
+    in_player: MusicPlayer,
+    in_time: MusicTimeStamp,
+) -> OSStatus {
+    return_if_null!(in_player);
+
+    let state = State::get(&mut env.framework_state);
+    let host_object = &state.players[&in_player];
+    let Some(sequence) = host_object.sequence else {
+        return kAudioToolboxErr_NoSequence;
+    };
+    let seconds = state.sequences[&sequence]
+        .midi
+        .as_ref()
+        .map_or(0.0, |midi| midi.beats_to_seconds(in_time.max(0.0)));
+    log_dbg!(
+        "MusicPlayerSetTime({:?}, {} beats) => {}s",
+        in_player,
+        in_time,
+        seconds
+    );
+
+    if host_object.is_playing {
+        let (al_source, _) = host_object.al_source_and_buffer.unwrap();
+        let length = host_object.length;
+        let _context_manager = env.framework_state.audio_toolbox.make_al_context_current();
+        unsafe {
+            if seconds < length {
+                al::alSourcePlay(al_source);
+                al::alSourcef(al_source, al::AL_SEC_OFFSET, seconds as ALfloat);
+            } else {
+                al::alSourceStop(al_source);
+            }
+            assert!(al::alGetError() == 0);
+        }
+    } else {
+        State::get(&mut env.framework_state)
+            .players
+            .get_mut(&in_player)
+            .unwrap()
+            .time = seconds;
+    }
+    0 // success
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(NewMusicSequence(_)),
+    export_c_func!(DisposeMusicSequence(_)),
+    export_c_func!(MusicSequenceFileLoad(_, _, _, _)),
+    export_c_func!(MusicSequenceFileLoadData(_, _, _, _)),
+    export_c_func!(MusicSequenceLoadSMFDataWithFlags(_, _, _)),
+    export_c_func!(MusicSequenceGetTrackCount(_, _)),
+    export_c_func!(NewMusicPlayer(_)),
+    export_c_func!(DisposeMusicPlayer(_)),
+    export_c_func!(MusicPlayerSetSequence(_, _)),
+    export_c_func!(MusicPlayerGetSequence(_, _)),
+    export_c_func!(MusicPlayerPreroll(_)),
+    export_c_func!(MusicPlayerStart(_)),
+    export_c_func!(MusicPlayerStop(_)),
+    export_c_func!(MusicPlayerIsPlaying(_, _)),
+    export_c_func!(MusicPlayerGetTime(_, _)),
+    export_c_func!(MusicPlayerSetTime(_, _)),
+];
@@ -9,17 +9,20 @@
 
 use crate::dyld::HostFunction;
 use crate::frameworks::audio_toolbox::audio_file::{
-    kAudioFilePropertyDataFormat, kAudioFilePropertyPacketSizeUpperBound, kAudioFileReadPermission,
-    AudioFileClose, AudioFileGetProperty, AudioFileID, AudioFileOpenURL, AudioFileReadPackets,
+    kAudioFilePropertyAudioDataPacketCount, kAudioFilePropertyDataFormat,
+    kAudioFilePropertyPacketSizeUpperBound, kAudioFileReadPermission, AudioFileClose,
+    AudioFileGetProperty, AudioFileID, AudioFileOpenURL, AudioFileReadPackets,
 };
 use crate::frameworks::audio_toolbox::audio_queue::{
     kAudioQueueParam_Volume, AudioQueueAllocateBuffer, AudioQueueBufferRef, AudioQueueDispose,
-    AudioQueueEnqueueBuffer, AudioQueueNewOutput, AudioQueueOutputCallback, AudioQueuePause,
-    AudioQueueRef, AudioQueueSetParameter, AudioQueueStart, AudioQueueStop,
+    AudioQueueEnqueueBuffer, AudioQueueGetCurrentTime, AudioQueueNewOutput,
+    AudioQueueOutputCallback, AudioQueuePause, AudioQueueRef, AudioQueueReset,
+    AudioQueueSetParameter, AudioQueueStart, AudioQueueStop,
 };
 use crate::frameworks::carbon_core::eofErr;
-use crate::frameworks::core_audio_types::AudioStreamBasicDescription;
+use crate::frameworks::core_audio_types::{AudioStreamBasicDescription, AudioTimeStamp};
 use crate::frameworks::core_foundation::cf_run_loop::kCFRunLoopCommonModes;
+use crate::frameworks::foundation::NSTimeInterval;
 use crate::frameworks::foundation::{ns_string, NSInteger};
 use crate::mem::{guest_size_of, GuestUSize, MutPtr, MutVoidPtr, Ptr};
 use crate::msg;
@@ -37,10 +40,17 @@ struct AVAudioPlayerHostObject {
     audio_queue: Option<AudioQueueRef>,
     audio_queue_buffers: Option<MutPtr<AudioQueueBufferRef>>,
     num_packets_to_read: u32,
+    /// Total number of packets in the audio file.
+    packet_count: i64,
     current_packet: i64,
+    /// Offset between the audio queue's timeline and the position in the file,
+    /// in seconds. This changes when seeking.
+    time_offset: NSTimeInterval,
     volume: f32,
     is_playing: bool,
     num_of_loops: NSInteger,
+    /// How many more times playback will loop, -1 meaning indefinitely.
+    loops_remaining: NSInteger,
 }
 impl HostObject for AVAudioPlayerHostObject {}
 
@@ -65,10 +75,13 @@ pub const CLASSES: ClassExports = objc_classes! {
         audio_queue: None,
         audio_queue_buffers: None,
         num_packets_to_read: 0,
+        packet_count: 0,
         current_packet: 0,
+        time_offset: 0.0,
         volume: 1.0,
         is_playing: false,
-        num_of_loops: 0
+        num_of_loops: 0,
+        loops_remaining: 0,
     });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
@@ -148,6 +161,20 @@ pub const CLASSES: ClassExports = objc_classes! {
     assert_eq!(size, env.mem.read(tmp_size_ptr));
     let prop_size = env.mem.read(prop_size_ptr);
 
+    let size = guest_size_of::<u64>();
+    env.mem.write(tmp_size_ptr, size);
+    let packet_count_ptr: MutPtr<u64> = env.mem.alloc(size).cast();
+    let status = AudioFileGetProperty(
+        env, audio_file_id, kAudioFilePropertyAudioDataPacketCount, tmp_size_ptr, packet_count_ptr.cast()
+    );
+    assert_eq!(status, 0);
+    assert_eq!(size, env.mem.read(tmp_size_ptr));
+    let packet_count = env.mem.read(packet_count_ptr).try_into().unwrap();
+    env.mem.free(packet_count_ptr.cast());
+    let host_object = env.objc.borrow_mut::<AVAudioPlayerHostObject>(this);
+    host_object.packet_count = packet_count;
+    host_object.loops_remaining = host_object.num_of_loops;
+
     let (buffer_byte_size, num_packets_to_read) = derive_buffer_size(audio_desc, prop_size, 0.5);
     env.objc.borrow_mut::<AVAudioPlayerHostObject>(this).num_packets_to_read = num_packets_to_read;
 
@@ -166,6 +193,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.mem.free(tmp_size_ptr.cast());
     env.mem.free(aq_ref_ptr.cast());
     env.mem.free(tmp_data_ptr.cast());
+    env.mem.free(prop_size_ptr.cast());
 
     // Reapply volume in case setVolume was called before prepareToPlay
     let volume = env.objc.borrow::<AVAudioPlayerHostObject>(this).volume;
@@ -221,15 +249,90 @@ pub const CLASSES: ClassExports = objc_classes! {
         audio_queue: None,
         audio_queue_buffers: None,
         num_packets_to_read: 0,
+        packet_count: 0,
         current_packet: 0,
+        time_offset: 0.0,
         volume: 1.0,
-        is_playing: false
+        is_playing: false,
+        loops_remaining: num_of_loops,
     };
 }
 
+- (NSInteger)numberOfLoops {
+    env.objc.borrow::<AVAudioPlayerHostObject>(this).num_of_loops
+}
 - (())setNumberOfLoops:(NSInteger)numberOfLoops {
     log_dbg!("[(AVAudioPlayer *) {:?} setNumberOfLoops:{:?}]", this, numberOfLoops);
-    env.objc.borrow_mut::<AVAudioPlayerHostObject>(this).num_of_loops = numberOfLoops;
+    let host_object = env.objc.borrow_mut::<AVAudioPlayerHostObject>(this);
+    host_object.num_of_loops = numberOfLoops;
+    host_object.loops_remaining = numberOfLoops;
+}
+
+- (NSTimeInterval)duration {
+    () = msg![env; this prepareToPlay];
+    let host_object = env.objc.borrow::<AVAudioPlayerHostObject>(this);
+    packets_to_seconds(&host_object.audio_desc.unwrap(), host_object.packet_count)
+}
+
+- (NSTimeInterval)currentTime {
+    let &AVAudioPlayerHostObject {
+        audio_desc,
+        audio_queue,
+        packet_count,
+        time_offset,
+        ..
+    } = env.objc.borrow(this);
+    let (Some(audio_desc), Some(audio_queue)) = (audio_desc, audio_queue) else {
+        return 0.0;
+    };
+    let time = time_offset + queue_time(env, audio_queue, &audio_desc);
+    // When looping, the queue's timeline keeps going past the end of the file.
+    let duration = packets_to_seconds(&audio_desc, packet_count);
+    if duration > 0.0 {
+        time % duration
+    } else {
+        0.0
+    }
+}
+- (())setCurrentTime:(NSTimeInterval)time {
+    log_dbg!("[(AVAudioPlayer *) {:?} setCurrentTime:{}]", this, time);
+    () = msg![env; this prepareToPlay];
+
+    let &AVAudioPlayerHostObject {
+        audio_desc,
+        audio_queue,
+        audio_queue_buffers,
+        packet_count,
+        is_playing,
+        ..
+    } = env.objc.borrow(this);
+    let audio_desc = audio_desc.unwrap();
+    let aq_ref = audio_queue.unwrap();
+    let buffers = audio_queue_buffers.unwrap();
+
+    let frames_per_packet = f64::from(audio_desc.frames_per_packet.max(1));
+    let packet = ((time.max(0.0) * audio_desc.sample_rate) / frames_per_packet) as i64;
+    let packet = packet.min(packet_count);
+
+    // Throw away everything that was already decoded for the old position.
+    // Resetting keeps the queue's timeline, so the offset is relative to it.
+    let status = AudioQueueReset(env, aq_ref);
+    assert_eq!(status, 0);
+    let time_offset = packets_to_seconds(&audio_desc, packet) - queue_time(env, aq_ref, &audio_desc);
+    let host_object = env.objc.borrow_mut::<AVAudioPlayerHostObject>(this);
+    host_object.current_packet = packet;
+    host_object.time_offset = time_offset;
+
+    // Refill all the buffers from the new position.
+    host_object.is_playing = true;
+    for i in 0..kNumberBuffers {
+        let buffer = env.mem.read(buffers + i as u32);
+        _touchHLE_AVAudioPlayerOutputBufferHelper(env, this.cast(), aq_ref, buffer);
+    }
+    let host_object = env.objc.borrow_mut::<AVAudioPlayerHostObject>(this);
+    if host_object.is_playing {
+        host_object.is_playing = is_playing;
+    }
 }
 
 - (())dealloc {
@@ -243,6 +346,26 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
+fn packets_to_seconds(audio_desc: &AudioStreamBasicDescription, packets: i64) -> NSTimeInterval {
+    let frames_per_packet = f64::from(audio_desc.frames_per_packet.max(1));
+    (packets as f64) * frames_per_packet / audio_desc.sample_rate
+}
+
+/// Get the current time on the audio queue's timeline, in seconds.
+fn queue_time(
+    env: &mut Environment,
+    aq_ref: AudioQueueRef,
+    audio_desc: &AudioStreamBasicDescription,
+) -> NSTimeInterval {
+    let time_stamp_ptr: MutPtr<AudioTimeStamp> =
+        env.mem.alloc(guest_size_of::<AudioTimeStamp>()).cast();
+    let status = AudioQueueGetCurrentTime(env, aq_ref, Ptr::null(), time_stamp_ptr, Ptr::null());
+    assert_eq!(status, 0);
+    let sample_time = env.mem.read(time_stamp_ptr).sample_time;
+    env.mem.free(time_stamp_ptr.cast());
+    sample_time / audio_desc.sample_rate
+}
+
 // Listing 3-7 from `Deriving a playback audio queue buffer size`
 // from the Apple's guide
 fn derive_buffer_size(
@@ -310,56 +433,69 @@ fn _touchHLE_AVAudioPlayerOutputBufferHelper(
         return;
     }
 
+    // Fill the buffer. If the end of the file is reached and playback should
+    // loop, the rest of the buffer is filled from the start of the file, so
+    // there's no gap between the end and the start.
     let num_bytes_ptr: MutPtr<u32> = env.mem.alloc(guest_size_of::<u32>()).cast();
     let num_packets_ptr: MutPtr<u32> = env.mem.alloc(guest_size_of::<u32>()).cast();
-    env.mem.write(num_packets_ptr, num_packets_to_read);
     let mut audio_queue_buffer = env.mem.read(in_buf);
-    let status = AudioFileReadPackets(
-        env,
-        audio_file_id.unwrap(),
-        false,
-        num_bytes_ptr,
-        Ptr::null(),
-        current_packet,
-        num_packets_ptr,
-        audio_queue_buffer.audio_data,
-    );
-    let num_packets = env.mem.read(num_packets_ptr);
-    let num_bytes = env.mem.read(num_bytes_ptr);
+    let mut current_packet = current_packet;
+    let mut packets_filled = 0;
+    let mut bytes_filled = 0;
+    while packets_filled < num_packets_to_read {
+        env.mem
+            .write(num_packets_ptr, num_packets_to_read - packets_filled);
+        let status = AudioFileReadPackets(
+            env,
+            audio_file_id.unwrap(),
+            false,
+            num_bytes_ptr,
+            Ptr::null(),
+            current_packet,
+            num_packets_ptr,
+            (audio_queue_buffer.audio_data.cast::<u8>() + bytes_filled).cast(),
+        );
+        let num_packets = env.mem.read(num_packets_ptr);
+        let num_bytes = env.mem.read(num_bytes_ptr);
+        assert!(status == 0 || status == eofErr);
+        packets_filled += num_packets;
+        bytes_filled += num_bytes;
+        current_packet += i64::from(num_packets);
+
+        if packets_filled == num_packets_to_read {
+            break;
+        }
+
+        // Reached the end of the file.
+        let host_object = env
+            .objc
+            .borrow_mut::<AVAudioPlayerHostObject>(av_audio_player);
+        // An empty file can't be looped.
+        if host_object.loops_remaining == 0 || (num_packets == 0 && current_packet == 0) {
+            break;
+        }
+        if host_object.loops_remaining > 0 {
+            host_object.loops_remaining -= 1;
+        }
+        log_dbg!("AVAudioPlayer {:?} looping", av_audio_player);
+        current_packet = 0;
+    }
     env.mem.free(num_packets_ptr.cast());
     env.mem.free(num_bytes_ptr.cast());
+    env.objc
+        .borrow_mut::<AVAudioPlayerHostObject>(av_audio_player)
+        .current_packet = current_packet;
 
-    if num_packets > 0 {
-        assert!(status == 0 || status == eofErr);
-        audio_queue_buffer.audio_data_byte_size = num_bytes;
+    if packets_filled > 0 {
+        audio_queue_buffer.audio_data_byte_size = bytes_filled;
         env.mem.write(in_buf, audio_queue_buffer);
         let status = AudioQueueEnqueueBuffer(env, aq, in_buf, 0, Ptr::null());
         assert_eq!(status, 0);
+    } else {
+        let status = AudioQueueStop(env, aq, false);
+        assert_eq!(status, 0);
         env.objc
             .borrow_mut::<AVAudioPlayerHostObject>(av_audio_player)
-            .current_packet = current_packet + num_packets as i64;
-    } else {
-        assert_eq!(status, eofErr);
-        let number_of_loops = env
-            .objc
-            .borrow::<AVAudioPlayerHostObject>(av_audio_player)
-            .num_of_loops;
-        if number_of_loops == 0 {
-            let status = AudioQueueStop(env, aq, false);
-            assert_eq!(status, 0);
-            env.objc
-                .borrow_mut::<AVAudioPlayerHostObject>(av_audio_player)
-                .is_playing = false;
-        } else {
-            if number_of_loops > 0 {
-                env.objc
-                    .borrow_mut::<AVAudioPlayerHostObject>(av_audio_player)
-                    .num_of_loops -= 1;
-            }
-            env.objc
-                .borrow_mut::<AVAudioPlayerHostObject>(av_audio_player)
-                .current_packet = 0;
-            _touchHLE_AVAudioPlayerOutputBufferHelper(env, in_user_data, in_aq, in_buf);
-        }
+            .is_playing = false;
     }
 }
@@ -16,6 +16,11 @@ pub type OSStatus = i32;
 /// One of many status codes from `MacErrors.h`, which is in Carbon Core.
 pub const eofErr: OSStatus = -39;
 
+/// File not found status code.
+///
+/// One of many status codes from `MacErrors.h`, which is in Carbon Core.
+pub const fnfErr: OSStatus = -43;
+
 /// Status code meaning that a parameter supplied by the user was invalid.
 ///
 /// One of many status codes from `MacErrors.h`, which is in Carbon Core.
@@ -8,6 +8,9 @@
 //! Useful resources:
 //! - Apple's [Core Animation Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/CoreAnimation_guide/Introduction/Introduction.html)
 
+use crate::objc::id;
+
+pub mod ca_display_link;
 pub mod ca_eagl_layer;
 pub mod ca_layer;
 
@@ -17,4 +20,10 @@ pub use composition::recomposite_if_necessary;
 #[derive(Default)]
 pub struct State {
     composition: composition::State,
+    /// Incremented whenever a layer tree changes in a way that affects which
+    /// layers are visible, so that information about it can be cached.
+    layer_tree_generation: u64,
+    /// Cached result of `count_visible_eagl_layers()`: the generation it was
+    /// computed for, the root layer and the count.
+    visible_eagl_layer_count: Option<(u64, id, usize)>,
 }
@@ -0,0 +1,210 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CADisplayLink`.
+//!
+//! touchHLE has no real v-sync signal to synchronize with, so this is a timer
+//! whose interval is derived from the display refresh rate (see the
+//! `--refresh-rate=` option).
+
+use crate::frameworks::core_foundation::time::CFTimeInterval;
+use crate::frameworks::foundation::ns_run_loop::{self, NSRunLoopMode};
+use crate::frameworks::foundation::{ns_string, NSInteger};
+use crate::objc::{
+    autorelease, id, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports,
+    HostObject, SEL,
+};
+use crate::Environment;
+use std::time::{Duration, Instant};
+
+struct CADisplayLinkHostObject {
+    /// Strong reference
+    target: id,
+    selector: SEL,
+    /// Number of display refreshes per firing.
+    frame_interval: NSInteger,
+    paused: bool,
+    /// Time of the most recent firing.
+    timestamp: Option<Instant>,
+    due_by: Option<Instant>,
+    /// Weak reference
+    run_loop: id,
+}
+impl HostObject for CADisplayLinkHostObject {}
+
+/// The time between display refreshes, as configured by `--refresh-rate=`.
+pub fn refresh_period(env: &Environment) -> Duration {
+    Duration::from_secs_f64(1.0 / env.options.refresh_rate)
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CADisplayLink: NSObject
+
++ (id)displayLinkWithTarget:(id)target
+                   selector:(SEL)selector {
+    retain(env, target);
+
+    let host_object = Box::new(CADisplayLinkHostObject {
+        target,
+        selector,
+        frame_interval: 1,
+        paused: false,
+        timestamp: None,
+        due_by: None,
+        run_loop: nil,
+    });
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+
+    log_dbg!(
+        "New display link {:?}, target [{:?} {}]",
+        new,
+        target,
+        selector.as_str(&env.mem),
+    );
+
+    autorelease(env, new)
+}
+
+- (())dealloc {
+    let &CADisplayLinkHostObject { target, .. } = env.objc.borrow(this);
+    release(env, target);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (())addToRunLoop:(id)run_loop // NSRunLoop*
+           forMode:(NSRunLoopMode)mode {
+    log_dbg!(
+        "Adding display link {:?} to run loop {:?} with mode {:?}",
+        this,
+        run_loop,
+        ns_string::to_rust_string(env, mode),
+    );
+
+    let interval = refresh_period(env);
+    let host_object = env.objc.borrow_mut::<CADisplayLinkHostObject>(this);
+    assert!(host_object.run_loop == nil); // TODO: multiple run loops
+    host_object.run_loop = run_loop;
+    host_object.due_by = Some(Instant::now() + interval);
+    // TODO: handle run loop modes
+    ns_run_loop::add_display_link(env, run_loop, this);
+}
+
+- (())removeFromRunLoop:(id)run_loop // NSRunLoop*
+                forMode:(NSRunLoopMode)_mode {
+    if env.objc.borrow::<CADisplayLinkHostObject>(this).run_loop == run_loop {
+        remove_from_run_loop(env, this);
+    }
+}
+
+- (())invalidate {
+    // Display link might already be invalid, don't try to remove it twice.
+    if env.objc.borrow::<CADisplayLinkHostObject>(this).run_loop != nil {
+        remove_from_run_loop(env, this);
+    }
+}
+
+- (CFTimeInterval)duration {
+    refresh_period(env).as_secs_f64()
+}
+
+- (CFTimeInterval)timestamp {
+    // This uses the same clock as mach_absolute_time().
+    env.objc
+        .borrow::<CADisplayLinkHostObject>(this)
+        .timestamp
+        .map_or(0.0, |t| t.duration_since(env.startup_time).as_secs_f64())
+}
+
+- (NSInteger)frameInterval {
+    env.objc.borrow::<CADisplayLinkHostObject>(this).frame_interval
+}
+- (())setFrameInterval:(NSInteger)frame_interval {
+    // Values less than 1 are ignored.
+    if frame_interval >= 1 {
+        env.objc.borrow_mut::<CADisplayLinkHostObject>(this).frame_interval = frame_interval;
+    }
+}
+
+- (bool)isPaused {
+    env.objc.borrow::<CADisplayLinkHostObject>(this).paused
+}
+- (())setPaused:(bool)paused {
+    let interval = refresh_period(env);
+    let host_object = env.objc.borrow_mut::<CADisplayLinkHostObject>(this);
+    if host_object.paused && !paused && host_object.due_by.is_some() {
+        // Don't try to catch up on the frames missed while paused.
+        host_object.due_by = Some(Instant::now() + interval);
+    }
+    host_object.paused = paused;
+}
+
+@end
+
+};
+
+fn remove_from_run_loop(env: &mut Environment, link: id) {
+    let host_object = env.objc.borrow_mut::<CADisplayLinkHostObject>(link);
+    let run_loop = std::mem::replace(&mut host_object.run_loop, nil);
+    host_object.due_by = None;
+    ns_run_loop::remove_display_link(env, run_loop, link);
+}
+
+/// For use by `NSRunLoop`: check if a display link is due to fire and fire it
+/// if necessary.
+///
+/// Returns the next firing time, if any.
+pub fn handle_display_link(env: &mut Environment, link: id) -> Option<Instant> {
+    let &CADisplayLinkHostObject {
+        target,
+        selector,
+        frame_interval,
+        paused,
+        due_by,
+        ..
+    } = env.objc.borrow(link);
+
+    if paused {
+        return None;
+    }
+
+    // Display links not in a run loop should have already been removed from it
+    let due_by = due_by.unwrap();
+
+    let now = Instant::now();
+    if due_by > now {
+        return Some(due_by);
+    }
+
+    // Like a repeating NSTimer, the next firing is aligned to the refresh
+    // interval so there is no drift, but missed refreshes are not caught up on.
+    let interval = refresh_period(env) * frame_interval.try_into().unwrap();
+    let missed = (now.duration_since(due_by).as_secs_f64() / interval.as_secs_f64()).floor();
+    let new_due_by = due_by + interval * (missed as u32 + 1);
+    let host_object = env.objc.borrow_mut::<CADisplayLinkHostObject>(link);
+    host_object.due_by = Some(new_due_by);
+    host_object.timestamp = Some(now);
+
+    log_dbg!(
+        "Display link {:?} fired, sending {:?} message to {:?}",
+        link,
+        selector.as_str(&env.mem),
+        target
+    );
+
+    // The display link might be invalidated by its target.
+    retain(env, link);
+    let pool: id = msg_class![env; NSAutoreleasePool new];
+
+    // Signature should be `- (void)step:(CADisplayLink *)sender`.
+    let _: () = msg_send(env, (target, selector, link));
+
+    release(env, pool);
+    release(env, link);
+
+    Some(new_due_by)
+}
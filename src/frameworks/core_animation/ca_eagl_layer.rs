@@ -7,7 +7,7 @@
 
 use super::ca_layer::CALayerHostObject;
 use crate::frameworks::core_graphics::{CGPoint, CGRect};
-use crate::objc::{id, msg, msg_class, nil, objc_classes, Class, ClassExports};
+use crate::objc::{id, msg, msg_class, nil, objc_classes, Class, ClassExports, ObjC};
 use crate::Environment;
 
 pub const CLASSES: ClassExports = objc_classes! {
@@ -45,7 +45,20 @@ pub const CLASSES: ClassExports = objc_classes! {
 /// there's a single full-screen layer, we skip transferring between contexts
 /// and present it directly from the app's context. This function is used to
 /// determine when that will happen.
+///
+/// If there's more than one visible `CAEAGLLayer` (e.g. a game layer with a
+/// HUD layer on top of it), this always returns [nil], so that all of them are
+/// composited. The same goes for while the splash screen or the console
+/// overlay is visible, since they are drawn on top of the composited frame.
 pub fn find_fullscreen_eagl_layer(env: &mut Environment) -> id {
+    if env
+        .window
+        .as_ref()
+        .is_some_and(|window| window.is_splash_visible() || window.is_console_visible())
+    {
+        return nil;
+    }
+
     // Assumes the last window in the list is the one on top.
     // TODO: this is not correct once we support zPosition.
     let Some(&top_window) = env
@@ -64,7 +77,8 @@ pub fn find_fullscreen_eagl_layer(env: &mut Environment) -> id {
         msg![env; screen bounds]
     };
 
-    let mut layer: id = msg![env; top_window layer];
+    let root_layer: id = msg![env; top_window layer];
+    let mut layer = root_layer;
 
     // Descend through the hierarchy, looking only at the last layer in each
     // list of children, since that should be the one on top.
@@ -85,6 +99,7 @@ pub fn find_fullscreen_eagl_layer(env: &mut Environment) -> id {
                     x: screen_bounds.size.width / 2.0,
                     y: screen_bounds.size.height / 2.0,
                 })
+            || !layer_host_obj.affine_transform.is_identity()
             || layer_host_obj.hidden
             || layer_host_obj.opacity != 1.0
         {
@@ -107,9 +122,48 @@ pub fn find_fullscreen_eagl_layer(env: &mut Environment) -> id {
         return nil;
     }
 
+    if count_visible_eagl_layers(env, root_layer) > 1 {
+        return nil;
+    }
+
     layer
 }
 
+/// Count the `CAEAGLLayer`s in a layer tree that aren't hidden. This is called
+/// on every present, so the result is cached until the layer tree changes.
+fn count_visible_eagl_layers(env: &mut Environment, root_layer: id) -> usize {
+    let state = &env.framework_state.core_animation;
+    let generation = state.layer_tree_generation;
+    if let Some((cached_generation, cached_root_layer, count)) = state.visible_eagl_layer_count {
+        if cached_generation == generation && cached_root_layer == root_layer {
+            return count;
+        }
+    }
+
+    fn traverse(objc: &ObjC, layer: id, visible_layers: &mut Vec<id>) {
+        let host_obj = objc.borrow::<CALayerHostObject>(layer);
+        if host_obj.hidden {
+            return;
+        }
+        visible_layers.push(layer);
+        for &layer in &host_obj.sublayers {
+            traverse(objc, layer, visible_layers);
+        }
+    }
+
+    let mut visible_layers = Vec::new();
+    traverse(&env.objc, root_layer, &mut visible_layers);
+
+    let ca_eagl_layer_class: Class = msg_class![env; CAEAGLLayer class];
+    let count = visible_layers
+        .into_iter()
+        .filter(|&layer| -> bool { msg![env; layer isKindOfClass:ca_eagl_layer_class] })
+        .count();
+    env.framework_state.core_animation.visible_eagl_layer_count =
+        Some((generation, root_layer, count));
+    count
+}
+
 /// For use by `EAGLContext` when presenting to a `CAEAGLLayer`:
 /// [std::mem::take]s the buffer used to hold the pixels. It should be passed
 /// back to [present_pixels] once it has been filled.
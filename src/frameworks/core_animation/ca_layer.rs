@@ -6,6 +6,9 @@
 //! `CALayer`.
 
 use crate::frameworks::core_foundation::{CFRelease, CFRetain};
+use crate::frameworks::core_graphics::cg_affine_transform::{
+    CGAffineTransform, CGAffineTransformIdentity,
+};
 use crate::frameworks::core_graphics::cg_bitmap_context::{
     CGBitmapContextCreate, CGBitmapContextGetHeight, CGBitmapContextGetWidth,
 };
@@ -19,6 +22,7 @@ use crate::frameworks::core_graphics::cg_image::{
 use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
 use crate::mem::{GuestUSize, Ptr};
 use crate::objc::{id, msg, nil, objc_classes, release, retain, ClassExports, HostObject, ObjC};
+use crate::Environment;
 use std::collections::HashMap;
 
 pub(super) struct CALayerHostObject {
@@ -31,6 +35,8 @@ pub(super) struct CALayerHostObject {
     pub(super) bounds: CGRect,
     pub(super) position: CGPoint,
     pub(super) anchor_point: CGPoint,
+    /// Applied around the anchor point.
+    pub(super) affine_transform: CGAffineTransform,
     pub(super) hidden: bool,
     pub(super) opaque: bool,
     pub(super) opacity: f32,
@@ -49,7 +55,37 @@ pub(super) struct CALayerHostObject {
     /// Internal state for compositor
     pub(super) gles_texture_is_up_to_date: bool,
 }
-impl HostObject for CALayerHostObject {}
+impl HostObject for CALayerHostObject {
+    fn strong_references(&self, references: &mut Vec<id>) {
+        references.extend_from_slice(&self.sublayers);
+        references.push(self.background_color);
+        references.push(self.contents);
+        references.push(self.drawable_properties);
+    }
+}
+
+impl CALayerHostObject {
+    /// Get the transform that maps points in this layer's co-ordinate space
+    /// (as defined by its bounds) to its superlayer's co-ordinate space.
+    pub(super) fn transform_to_superlayer(&self) -> CGAffineTransform {
+        let anchor = CGPoint {
+            x: self.bounds.origin.x + self.bounds.size.width * self.anchor_point.x,
+            y: self.bounds.origin.y + self.bounds.size.height * self.anchor_point.y,
+        };
+        CGAffineTransform::make_translation(-anchor.x, -anchor.y)
+            .concat(self.affine_transform)
+            .concat(CGAffineTransform::make_translation(
+                self.position.x,
+                self.position.y,
+            ))
+    }
+}
+
+/// Invalidate cached information about layer trees, see
+/// `layer_tree_generation` in [super::State].
+fn layer_tree_changed(env: &mut Environment) {
+    env.framework_state.core_animation.layer_tree_generation += 1;
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -68,6 +104,7 @@ pub const CLASSES: ClassExports = objc_classes! {
         },
         position: CGPoint { x: 0.0, y: 0.0 },
         anchor_point: CGPoint { x: 0.5, y: 0.5 },
+        affine_transform: CGAffineTransformIdentity,
         hidden: false,
         opaque: false,
         opacity: 1.0,
@@ -117,6 +154,8 @@ pub const CLASSES: ClassExports = objc_classes! {
     }
 
     assert!(superlayer == nil);
+    // The layer's address could be reused for a new root layer.
+    layer_tree_changed(env);
     for sublayer in sublayers {
         env.objc.borrow_mut::<CALayerHostObject>(sublayer).superlayer = nil;
         release(env, sublayer);
@@ -145,6 +184,7 @@ pub const CLASSES: ClassExports = objc_classes! {
         () = msg![env; layer removeFromSuperlayer];
         env.objc.borrow_mut::<CALayerHostObject>(layer).superlayer = this;
         env.objc.borrow_mut::<CALayerHostObject>(this).sublayers.push(layer);
+        layer_tree_changed(env);
     }
 }
 
@@ -159,6 +199,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     let idx = sublayers.iter().position(|&sublayer| sublayer == this).unwrap();
     let sublayer = sublayers.remove(idx);
     assert!(sublayer == this);
+    layer_tree_changed(env);
     release(env, this);
 }
 
@@ -181,13 +222,26 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow_mut::<CALayerHostObject>(this).anchor_point = anchor_point;
 }
 
+- (CGAffineTransform)affineTransform {
+    env.objc.borrow::<CALayerHostObject>(this).affine_transform
+}
+- (())setAffineTransform:(CGAffineTransform)transform {
+    env.objc.borrow_mut::<CALayerHostObject>(this).affine_transform = transform;
+}
+
 - (CGRect)frame {
+    let host_obj = env.objc.borrow::<CALayerHostObject>(this);
     let &CALayerHostObject {
         bounds,
         position,
         anchor_point,
+        affine_transform,
         ..
-    } = env.objc.borrow(this);
+    } = host_obj;
+    // The frame of a transformed layer is the bounding box of its bounds.
+    if !affine_transform.is_identity() {
+        return host_obj.transform_to_superlayer().apply_to_rect(bounds);
+    }
     CGRect {
         origin: CGPoint {
             x: position.x - bounds.size.width * anchor_point.x,
@@ -196,6 +250,8 @@ pub const CLASSES: ClassExports = objc_classes! {
         size: bounds.size,
     }
 }
+// TODO: setting the frame of a transformed layer should also take the
+// transform into account.
 - (())setFrame:(CGRect)frame {
     let CALayerHostObject {
         bounds,
@@ -218,6 +274,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 - (())setHidden:(bool)hidden {
     env.objc.borrow_mut::<CALayerHostObject>(this).hidden = hidden;
+    layer_tree_changed(env);
 }
 
 - (bool)isOpaque {
@@ -393,32 +450,30 @@ pub const CLASSES: ClassExports = objc_classes! {
     // The idea is to walk up each layer's superlayer chain, one at a time,
     // alternating between layers until we find a match.
 
-    // Maps of layer pointers to origins in that layer's co-ordinate space.
-    let mut this_map = HashMap::from([(this, CGPoint { x: 0.0, y: 0.0 })]);
-    let mut other_map = HashMap::from([(other, CGPoint { x: 0.0, y: 0.0 })]);
+    // Maps of layer pointers to the transform from the original layer's
+    // co-ordinate space to that layer's co-ordinate space.
+    let mut this_map = HashMap::from([(this, CGAffineTransformIdentity)]);
+    let mut other_map = HashMap::from([(other, CGAffineTransformIdentity)]);
     // Current iteration state.
     let mut this_superlayer = this;
-    let mut this_origin = CGPoint { x: 0.0, y: 0.0 };
+    let mut this_transform = CGAffineTransformIdentity;
     let mut other_superlayer = other;
-    let mut other_origin = CGPoint { x: 0.0, y: 0.0 };
-    let (common_ancestor, this_origin, other_origin) = loop {
+    let mut other_transform = CGAffineTransformIdentity;
+    let (common_ancestor, this_transform, other_transform) = loop {
         if this_superlayer != nil {
             let next: id = msg![env; this_superlayer superlayer];
             if next == nil {
                 this_superlayer = nil;
             } else {
-                let bounds: CGRect = msg![env; this_superlayer bounds];
-                let frame: CGRect = msg![env; this_superlayer frame];
-                let next_origin = CGPoint {
-                    x: this_origin.x + frame.origin.x - bounds.origin.x,
-                    y: this_origin.y + frame.origin.y - bounds.origin.y,
-                };
-                if let Some(&other_origin) = other_map.get(&next) {
-                    break (next, next_origin, other_origin);
+                let next_transform = this_transform.concat(
+                    env.objc.borrow::<CALayerHostObject>(this_superlayer).transform_to_superlayer()
+                );
+                if let Some(&other_transform) = other_map.get(&next) {
+                    break (next, next_transform, other_transform);
                 }
-                this_map.insert(next, next_origin);
+                this_map.insert(next, next_transform);
                 this_superlayer = next;
-                this_origin = next_origin;
+                this_transform = next_transform;
             }
         }
 
@@ -427,18 +482,15 @@ pub const CLASSES: ClassExports = objc_classes! {
             if next == nil {
                 other_superlayer = nil;
             } else {
-                let bounds: CGRect = msg![env; other_superlayer bounds];
-                let frame: CGRect = msg![env; other_superlayer frame];
-                let next_origin = CGPoint {
-                    x: other_origin.x + frame.origin.x - bounds.origin.x,
-                    y: other_origin.y + frame.origin.y - bounds.origin.y,
-                };
-                if let Some(&this_origin) = this_map.get(&next) {
-                    break (next, this_origin, next_origin);
+                let next_transform = other_transform.concat(
+                    env.objc.borrow::<CALayerHostObject>(other_superlayer).transform_to_superlayer()
+                );
+                if let Some(&this_transform) = this_map.get(&next) {
+                    break (next, this_transform, next_transform);
                 }
-                other_map.insert(next, next_origin);
+                other_map.insert(next, next_transform);
                 other_superlayer = next;
-                other_origin = next_origin;
+                other_transform = next_transform;
             }
         }
 
@@ -449,12 +501,9 @@ pub const CLASSES: ClassExports = objc_classes! {
     };
 
     log_dbg!("{:?} and {:?}'s common ancestor: {:?}", this, other, common_ancestor);
-    log_dbg!("{:?}'s origin in common ancestor: {:?}", this, this_origin);
-    log_dbg!("{:?}'s origin in common ancestor: {:?}", other, other_origin);
-    let res = CGPoint {
-        x: point.x + other_origin.x - this_origin.x,
-        y: point.y + other_origin.y - this_origin.y,
-    };
+    log_dbg!("{:?}'s transform to common ancestor: {:?}", this, this_transform);
+    log_dbg!("{:?}'s transform to common ancestor: {:?}", other, other_transform);
+    let res = this_transform.invert().apply_to_point(other_transform.apply_to_point(point));
     log_dbg!("Converted {:?} from {:?} to {:?}: {:?}", point, other, this, res);
     res
 }
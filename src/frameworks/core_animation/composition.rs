@@ -11,6 +11,9 @@
 
 use super::ca_eagl_layer::find_fullscreen_eagl_layer;
 use super::ca_layer::CALayerHostObject;
+use crate::frameworks::core_graphics::cg_affine_transform::{
+    CGAffineTransform, CGAffineTransformIdentity,
+};
 use crate::frameworks::core_graphics::{
     cg_bitmap_context, cg_image, CGFloat, CGPoint, CGRect, CGSize,
 };
@@ -19,6 +22,7 @@ use crate::gles::gles11_raw as gles11; // constants only
 use crate::gles::gles11_raw::types::*;
 use crate::gles::present::{present_frame, FpsCounter};
 use crate::gles::GLES;
+use crate::image::pixel_format;
 use crate::mem::Mem;
 use crate::objc::{id, msg, msg_class, nil, ObjC};
 use crate::Environment;
@@ -118,15 +122,15 @@ pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
     let present_frame_args = (
         env.window().viewport(),
         env.window().rotation_matrix(),
-        env.window().virtual_cursor_visible_at(),
+        env.window().virtual_cursors_visible_at(),
     );
 
     // TODO: draw status bar if it's not hidden
 
     // Initial state for layer tree traversal (see composite_layer_recursive)
-    let origin = CGPoint { x: 0.0, y: 0.0 };
+    let transform = CGAffineTransformIdentity;
     let clip_to = CGRect {
-        origin,
+        origin: CGPoint { x: 0.0, y: 0.0 },
         size: screen_bounds.size,
     };
     let opacity = 1.0;
@@ -215,11 +219,11 @@ pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
             &mut env.objc,
             &env.mem,
             root_layer,
-            origin,
+            transform,
             clip_to,
             opacity,
             scale_hack,
-            fb_height,
+            (fb_width, fb_height),
         );
     }
 
@@ -241,10 +245,16 @@ pub fn recomposite_if_necessary(env: &mut Environment) -> Option<Instant> {
             gles,
             present_frame_args.0,
             present_frame_args.1,
-            present_frame_args.2,
+            &present_frame_args.2,
         );
     }
-    env.window().swap_window();
+    // Recording happens here too, so that apps which only use UIKit are
+    // captured the same way as apps which present with OpenGL ES.
+    let window = env.window_mut();
+    window.draw_splash_fade();
+    window.record_frame(None);
+    window.draw_console();
+    window.swap_window();
 
     new_recomposite_next
 }
@@ -275,20 +285,21 @@ fn display_layers(env: &mut Environment, root_layer: id) {
     }
 }
 
-/// Traverses the layer tree and draws each layer.
+/// Traverses the layer tree and draws each layer. `transform` maps points in
+/// the superlayer's co-ordinate space to the screen.
 unsafe fn composite_layer_recursive(
     gles: &mut dyn GLES,
     objc: &mut ObjC,
     mem: &Mem,
     layer: id,
-    origin: CGPoint,
+    transform: CGAffineTransform,
     clip_to: CGRect,
     opacity: CGFloat,
     scale_hack: u32,
-    fb_height: u32,
+    fb_size: (u32, u32),
 ) {
-    // TODO: this can't handle zPosition, non-AABB layer transforms, rounded
-    // corners, and many other things, but none of these are supported yet :)
+    // TODO: this can't handle zPosition, 3D layer transforms, rounded corners,
+    // and many other things, but none of these are supported yet :)
     // TODO: back-to-front drawing is not efficient, could we use front-to-back?
 
     let host_obj = objc.borrow::<CALayerHostObject>(layer);
@@ -299,18 +310,15 @@ unsafe fn composite_layer_recursive(
 
     let opacity = opacity * host_obj.opacity;
     let bounds = host_obj.bounds;
-    let absolute_frame = {
-        let position = host_obj.position;
-        let anchor_point = host_obj.anchor_point;
-        CGRect {
-            origin: CGPoint {
-                x: origin.x + position.x - bounds.size.width * anchor_point.x,
-                y: origin.y + position.y - bounds.size.height * anchor_point.y,
-            },
-            size: bounds.size,
-        }
-    };
+    let transform = host_obj.transform_to_superlayer().concat(transform);
+    // Rotated or skewed layers can't be drawn with a scissor rectangle alone,
+    // so they get drawn as an arbitrary quadrilateral.
+    let axis_aligned = transform.b == 0.0 && transform.c == 0.0;
+    // Bounding box of the layer on the screen.
+    let absolute_frame = transform.apply_to_rect(bounds);
     let absolute_frame_clipped = clip_rects(clip_to, absolute_frame);
+    let (fb_width, fb_height) = fb_size;
+    let vertices = quad_vertices(transform, bounds, scale_hack, fb_size);
 
     // Draw background color, if any
     let have_background = if host_obj.background_color == nil {
@@ -320,12 +328,25 @@ unsafe fn composite_layer_recursive(
         // TODO: fully support alpha transparency for backgrounds
         if a == 0.0 || opacity == 0.0 {
             false
-        } else {
+        } else if axis_aligned {
             gles.ClearColor(r * opacity, g * opacity, b * opacity, a * opacity);
             let (x, y, w, h) = gl_rect_from_cg_rect(absolute_frame_clipped, scale_hack, fb_height);
             gles.Scissor(x, y, w, h);
             gles.Clear(gles11::COLOR_BUFFER_BIT);
             true
+        } else {
+            let (x, y, w, h) = gl_rect_from_cg_rect(clip_to, scale_hack, fb_height);
+            gles.Scissor(x, y, w, h);
+            gles.Enable(gles11::BLEND);
+            gles.BlendFunc(gles11::ONE, gles11::ONE_MINUS_SRC_ALPHA);
+            gles.Color4f(r * opacity, g * opacity, b * opacity, a * opacity);
+            gles.Disable(gles11::TEXTURE_2D);
+            gles.DisableClientState(gles11::TEXTURE_COORD_ARRAY);
+            gles.BindBuffer(gles11::ARRAY_BUFFER, 0);
+            gles.EnableClientState(gles11::VERTEX_ARRAY);
+            gles.VertexPointer(2, gles11::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
+            gles.DrawArrays(gles11::TRIANGLES, 0, 6);
+            true
         }
     };
 
@@ -356,11 +377,7 @@ unsafe fn composite_layer_recursive(
             // alpha channel is meant to be ignored. glTexImage2D() has no
             // option to ignore it, so let's manually set them to 255.
             if host_obj.opaque {
-                let mut i = 3;
-                while i < pixels.len() {
-                    pixels[i] = 255;
-                    i += 4;
-                }
+                pixel_format::set_opaque(pixels);
             }
 
             upload_rgba8_pixels(gles, pixels, (width, height));
@@ -407,12 +424,9 @@ unsafe fn composite_layer_recursive(
 
         let (x, y, w, h) = gl_rect_from_cg_rect(absolute_frame_clipped, scale_hack, fb_height);
         gles.Scissor(x, y, w, h);
-        gles.Viewport(x, y, w, h);
+        gles.Viewport(0, 0, fb_width as _, fb_height as _);
 
         gles.BindBuffer(gles11::ARRAY_BUFFER, 0);
-        let vertices: [f32; 12] = [
-            -1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0,
-        ];
         gles.EnableClientState(gles11::VERTEX_ARRAY);
         gles.VertexPointer(2, gles11::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
 
@@ -437,16 +451,12 @@ unsafe fn composite_layer_recursive(
             objc,
             mem,
             child_layer,
-            /* origin: */
-            CGPoint {
-                x: absolute_frame.origin.x - bounds.origin.x,
-                y: absolute_frame.origin.y - bounds.origin.y,
-            },
+            transform,
             // TODO: clipping goes here (when masksToBounds is implemented)
             clip_to,
             opacity,
             scale_hack,
-            fb_height,
+            fb_size,
         )
     }
     objc.borrow_mut::<CALayerHostObject>(layer).sublayers = sublayers;
@@ -476,6 +486,32 @@ unsafe fn upload_rgba8_pixels(gles: &mut dyn GLES, pixels: &[u8], dimensions: (u
     );
 }
 
+/// Get the normalized device co-ordinates of the corners of a layer's bounds,
+/// as two triangles, given the transform from its co-ordinate space to the
+/// screen.
+fn quad_vertices(
+    transform: CGAffineTransform,
+    bounds: CGRect,
+    scale_hack: u32,
+    fb_size: (u32, u32),
+) -> [f32; 12] {
+    let (x1, y1) = (bounds.origin.x, bounds.origin.y);
+    let (x2, y2) = (x1 + bounds.size.width, y1 + bounds.size.height);
+    // The order matches the texture co-ordinates: bottom-left, top-left,
+    // bottom-right, bottom-right, top-left, top-right.
+    let corners = [(x1, y2), (x1, y1), (x2, y2), (x2, y2), (x1, y1), (x2, y1)];
+    let mut vertices = [0.0; 12];
+    for (i, (x, y)) in corners.into_iter().enumerate() {
+        let point = transform.apply_to_point(CGPoint { x, y });
+        let x = point.x * scale_hack as f32 / fb_size.0 as f32;
+        let y = point.y * scale_hack as f32 / fb_size.1 as f32;
+        // y points up in OpenGL ES, but down in UIKit and Core Animation
+        vertices[i * 2] = x * 2.0 - 1.0;
+        vertices[i * 2 + 1] = 1.0 - y * 2.0;
+    }
+    vertices
+}
+
 fn clip_rects(a_clip: CGRect, b_clip: CGRect) -> CGRect {
     let a_x1 = a_clip.origin.x;
     let a_y1 = a_clip.origin.y;
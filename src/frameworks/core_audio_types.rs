@@ -5,7 +5,7 @@
  */
 //! The Core Audio Types framework. (Yes, it's not part of Core Audio?)
 
-use crate::mem::SafeRead;
+use crate::mem::{MutVoidPtr, SafeRead};
 
 // The audio frameworks love FourCC's, and we currently don't need these
 // anywhere else, so this is as good a place to put this as any.
@@ -89,3 +89,39 @@ pub const kAudioFormatFlagIsFloat: AudioFormatFlags = 1 << 0;
 pub const kAudioFormatFlagIsBigEndian: AudioFormatFlags = 1 << 1;
 pub const kAudioFormatFlagIsSignedInteger: AudioFormatFlags = 1 << 2;
 pub const kAudioFormatFlagIsPacked: AudioFormatFlags = 1 << 3;
+pub const kAudioFormatFlagIsNonInterleaved: AudioFormatFlags = 1 << 5;
+pub const kLinearPCMFormatFlagsSampleFractionShift: u32 = 7;
+pub const kLinearPCMFormatFlagsSampleFractionMask: AudioFormatFlags =
+    0x3F << kLinearPCMFormatFlagsSampleFractionShift;
+
+#[repr(C, packed)]
+pub struct AudioBuffer {
+    pub number_channels: u32,
+    pub data_byte_size: u32,
+    pub data: MutVoidPtr,
+}
+unsafe impl SafeRead for AudioBuffer {}
+
+/// `AudioBufferList` is a variable-length structure: a `u32` count followed by
+/// that many [AudioBuffer]s. This is the fixed-size part.
+#[repr(C, packed)]
+pub struct AudioBufferListHeader {
+    pub number_buffers: u32,
+}
+unsafe impl SafeRead for AudioBufferListHeader {}
+
+pub type AudioTimeStampFlags = u32;
+pub const kAudioTimeStampSampleTimeValid: AudioTimeStampFlags = 1 << 0;
+
+#[repr(C, packed)]
+pub struct AudioTimeStamp {
+    pub sample_time: f64,
+    pub host_time: u64,
+    pub rate_scalar: f64,
+    pub word_clock_time: u64,
+    /// Should be a `SMPTETime`, but that's not implemented yet.
+    pub _smpte_time: [u8; 24],
+    pub flags: AudioTimeStampFlags,
+    pub _reserved: u32,
+}
+unsafe impl SafeRead for AudioTimeStamp {}
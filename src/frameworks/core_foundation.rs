@@ -19,6 +19,7 @@
 pub mod cf_allocator;
 pub mod cf_array;
 pub mod cf_bundle;
+pub mod cf_byte_order;
 pub mod cf_data;
 pub mod cf_dictionary;
 pub mod cf_run_loop;
@@ -12,7 +12,9 @@ use super::cf_array::CFArrayRef;
 use super::cf_string::CFStringRef;
 use super::cf_url::CFURLRef;
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::frameworks::foundation::ns_bundle::NSBundleHostObject;
+use crate::frameworks::foundation::ns_bundle::{
+    localization_fallback_chain, normalize_language_code, NSBundleHostObject,
+};
 use crate::frameworks::foundation::{ns_array, ns_string, NSUInteger};
 use crate::objc::{id, msg, msg_class, retain};
 use crate::Environment;
@@ -105,29 +107,33 @@ pub fn CFBundleCopyPreferredLocalizationsFromArray(
 ) -> CFArrayRef {
     let mut result = Vec::new();
 
-    let preferred_languages: id = msg_class![env; NSLocale preferredLanguages];
-
-    // Check if the user's preferred languages are in loc_array
     let loc_count: NSUInteger = msg![env; loc_array count];
-    let pref_loc_count: NSUInteger = msg![env; preferred_languages count];
+    let mut locs = Vec::new();
     for loc_index in 0..loc_count {
         let loc: id = msg![env; loc_array objectAtIndex:loc_index];
-        for pref_loc_index in 0..pref_loc_count {
-            let pref_loc: id = msg![env; preferred_languages objectAtIndex:pref_loc_index];
-            let equal: bool = msg![env; loc isEqualToString:pref_loc];
-            if equal {
-                // If one of them is, add it to the array
-                result.push(loc);
-                retain(env, loc);
-                break;
-            }
-        }
+        let code = normalize_language_code(&ns_string::to_rust_string(env, loc));
+        locs.push((loc, code));
+    }
+
+    // Pick the first localization in loc_array that appears in the fallback
+    // chain (e.g. pt-BR → pt → development region → en)
+    let main_bundle: id = msg_class![env; NSBundle mainBundle];
+    let chain = localization_fallback_chain(env, main_bundle);
+    if let Some(&(loc, _)) = chain
+        .iter()
+        .find_map(|code| locs.iter().find(|(_, loc_code)| loc_code == code))
+    {
+        result.push(loc);
+        retain(env, loc);
     }
 
     // Add the first element as fallback
-    let first_loc: id = msg![env; loc_array objectAtIndex: (0 as NSUInteger)];
-    result.push(first_loc);
-    retain(env, first_loc);
+    if let Some(&(first_loc, _)) = locs.first() {
+        if !result.contains(&first_loc) {
+            result.push(first_loc);
+            retain(env, first_loc);
+        }
+    }
 
     let result = ns_array::from_vec(env, result);
     log_dbg!(
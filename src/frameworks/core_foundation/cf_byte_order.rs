@@ -0,0 +1,87 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CFByteOrder.h`
+//!
+//! These are inline functions in the header, but the function forms are
+//! provided in case an app calls them dynamically. The guest is always
+//! little-endian.
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::Environment;
+
+type CFByteOrder = i32;
+const CFByteOrderLittleEndian: CFByteOrder = 1;
+
+fn CFByteOrderGetCurrent(_env: &mut Environment) -> CFByteOrder {
+    CFByteOrderLittleEndian
+}
+
+fn CFSwapInt16(_env: &mut Environment, arg: u16) -> u16 {
+    arg.swap_bytes()
+}
+fn CFSwapInt32(_env: &mut Environment, arg: u32) -> u32 {
+    arg.swap_bytes()
+}
+fn CFSwapInt64(_env: &mut Environment, arg: u64) -> u64 {
+    arg.swap_bytes()
+}
+
+fn CFSwapInt16BigToHost(_env: &mut Environment, arg: u16) -> u16 {
+    u16::from_be(arg)
+}
+fn CFSwapInt32BigToHost(_env: &mut Environment, arg: u32) -> u32 {
+    u32::from_be(arg)
+}
+fn CFSwapInt64BigToHost(_env: &mut Environment, arg: u64) -> u64 {
+    u64::from_be(arg)
+}
+fn CFSwapInt16HostToBig(_env: &mut Environment, arg: u16) -> u16 {
+    arg.to_be()
+}
+fn CFSwapInt32HostToBig(_env: &mut Environment, arg: u32) -> u32 {
+    arg.to_be()
+}
+fn CFSwapInt64HostToBig(_env: &mut Environment, arg: u64) -> u64 {
+    arg.to_be()
+}
+
+fn CFSwapInt16LittleToHost(_env: &mut Environment, arg: u16) -> u16 {
+    u16::from_le(arg)
+}
+fn CFSwapInt32LittleToHost(_env: &mut Environment, arg: u32) -> u32 {
+    u32::from_le(arg)
+}
+fn CFSwapInt64LittleToHost(_env: &mut Environment, arg: u64) -> u64 {
+    u64::from_le(arg)
+}
+fn CFSwapInt16HostToLittle(_env: &mut Environment, arg: u16) -> u16 {
+    arg.to_le()
+}
+fn CFSwapInt32HostToLittle(_env: &mut Environment, arg: u32) -> u32 {
+    arg.to_le()
+}
+fn CFSwapInt64HostToLittle(_env: &mut Environment, arg: u64) -> u64 {
+    arg.to_le()
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFByteOrderGetCurrent()),
+    export_c_func!(CFSwapInt16(_)),
+    export_c_func!(CFSwapInt32(_)),
+    export_c_func!(CFSwapInt64(_)),
+    export_c_func!(CFSwapInt16BigToHost(_)),
+    export_c_func!(CFSwapInt32BigToHost(_)),
+    export_c_func!(CFSwapInt64BigToHost(_)),
+    export_c_func!(CFSwapInt16HostToBig(_)),
+    export_c_func!(CFSwapInt32HostToBig(_)),
+    export_c_func!(CFSwapInt64HostToBig(_)),
+    export_c_func!(CFSwapInt16LittleToHost(_)),
+    export_c_func!(CFSwapInt32LittleToHost(_)),
+    export_c_func!(CFSwapInt64LittleToHost(_)),
+    export_c_func!(CFSwapInt16HostToLittle(_)),
+    export_c_func!(CFSwapInt32HostToLittle(_)),
+    export_c_func!(CFSwapInt64HostToLittle(_)),
+];
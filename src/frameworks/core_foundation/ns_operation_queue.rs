@@ -1,5 +1,4 @@
 use crate::objc::{id, objc_classes, ClassExports, nil};
-use crate::mem::MutPtr;
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -19,16 +18,4 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 @end
 
-@implementation NSURLConnection: NSObject
-+ (id)sendSynchronousRequest:(id)request
-           returningResponse:(MutPtr<id>)response
-                       error:(MutPtr<id>)error {
-    nil
-}
-@end
-
-@implementation NSHTTPURLResponse: NSObject
-
-@end
-
 };
\ No newline at end of file
@@ -7,10 +7,10 @@
 
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::frameworks::core_foundation::CFTypeRef;
-use crate::frameworks::foundation::NSTimeInterval;
+use crate::frameworks::foundation::{ns_time_zone, NSTimeInterval};
 use crate::libc::time::{time_t, timestamp_to_calendar_date};
 use crate::mem::SafeRead;
-use crate::objc::nil;
+use crate::objc::{msg_class, retain};
 use crate::{impl_GuestRet_for_large_struct, Environment};
 use std::ops::Add;
 use std::time::{Duration, SystemTime};
@@ -47,17 +47,19 @@ fn CFAbsoluteTimeGetCurrent(_env: &mut Environment) -> CFAbsoluteTime {
 
 type CFTimeZoneRef = CFTypeRef;
 
-fn CFTimeZoneCopySystem(_env: &mut Environment) -> CFTimeZoneRef {
-    // TODO: implement (nil seems to correspond to GMT)
-    nil
+/// `CFTimeZone` is toll-free bridged to `NSTimeZone`.
+fn CFTimeZoneCopySystem(env: &mut Environment) -> CFTimeZoneRef {
+    let time_zone: CFTimeZoneRef = msg_class![env; NSTimeZone systemTimeZone];
+    retain(env, time_zone)
 }
 
 pub fn CFAbsoluteTimeGetGregorianDate(
-    _env: &mut Environment,
+    env: &mut Environment,
     at: CFAbsoluteTime,
     tz: CFTimeZoneRef,
 ) -> CFGregorianDate {
-    assert!(tz.is_null());
+    // A null time zone means GMT.
+    let at = at + f64::from(ns_time_zone::seconds_from_gmt(env, tz));
     let time64 = apple_epoch()
         .add(Duration::from_secs_f64(at))
         .duration_since(SystemTime::UNIX_EPOCH)
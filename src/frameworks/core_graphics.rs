@@ -10,12 +10,20 @@
 
 pub mod cg_affine_transform;
 pub mod cg_bitmap_context;
+pub mod cg_color;
 pub mod cg_color_space;
 pub mod cg_context;
 pub mod cg_data_provider;
 pub mod cg_geometry;
 pub mod cg_image;
+pub mod cg_pdf_document;
+pub mod cg_pdf_page;
 
 pub type CGFloat = f32;
 
+#[derive(Default)]
+pub struct State {
+    cg_color_space: cg_color_space::State,
+}
+
 pub use cg_geometry::{CGPoint, CGRect, CGSize};
@@ -147,6 +147,10 @@ impl CGAffineTransform {
         CGSize { width, height }
     }
     pub fn apply_to_rect(self, rect: CGRect) -> CGRect {
+        if rect.is_null() {
+            return rect;
+        }
+
         // Affine transforms applied to a rectangle don't necessarily return a
         // rectangle (just a quadrilateral), so CGRectApplyAffineTransform
         // essentially returns the bounding box of the points.
@@ -299,3 +303,53 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CGSizeApplyAffineTransform(_, _)),
     export_c_func!(CGRectApplyAffineTransform(_, _)),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transforms() {
+        let t = CGAffineTransform::make_translation(10.0, 20.0).scale(2.0, 3.0);
+        let p = t.apply_to_point(CGPoint { x: 1.0, y: 1.0 });
+        assert_eq!(p, CGPoint { x: 12.0, y: 23.0 });
+        let back = t.invert().apply_to_point(p);
+        assert!((back.x - 1.0).abs() < 1e-5 && (back.y - 1.0).abs() < 1e-5);
+
+        // Concatenation applies the first transform first.
+        let scale_then_translate = CGAffineTransform::make_scale(2.0, 2.0)
+            .concat(CGAffineTransform::make_translation(1.0, 0.0));
+        assert_eq!(
+            scale_then_translate.apply_to_point(CGPoint { x: 1.0, y: 1.0 }),
+            CGPoint { x: 3.0, y: 2.0 }
+        );
+
+        let size = t.apply_to_size(CGSize {
+            width: 1.0,
+            height: 1.0,
+        });
+        assert_eq!(
+            size,
+            CGSize {
+                width: 2.0,
+                height: 3.0
+            }
+        );
+
+        let rotated =
+            CGAffineTransform::make_rotation(std::f32::consts::FRAC_PI_2).apply_to_rect(CGRect {
+                origin: CGPoint { x: 0.0, y: 0.0 },
+                size: CGSize {
+                    width: 2.0,
+                    height: 1.0,
+                },
+            });
+        assert!((rotated.origin.x + 1.0).abs() < 1e-5);
+        assert!((rotated.size.width - 1.0).abs() < 1e-5);
+        assert!((rotated.size.height - 2.0).abs() < 1e-5);
+
+        // Singular transforms can't be inverted, so they're returned as-is.
+        let singular = CGAffineTransform::make_scale(0.0, 1.0);
+        assert_eq!(singular.invert(), singular);
+    }
+}
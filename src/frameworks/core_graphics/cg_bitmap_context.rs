@@ -14,13 +14,14 @@ use super::cg_image::{
     self, kCGBitmapAlphaInfoMask, kCGBitmapByteOrderMask, kCGImageAlphaFirst, kCGImageAlphaLast,
     kCGImageAlphaNone, kCGImageAlphaNoneSkipFirst, kCGImageAlphaNoneSkipLast, kCGImageAlphaOnly,
     kCGImageAlphaPremultipliedFirst, kCGImageAlphaPremultipliedLast, kCGImageByteOrder32Big,
-    kCGImageByteOrderDefault, CGBitmapInfo, CGImageAlphaInfo, CGImageRef,
+    kCGImageByteOrder32Little, kCGImageByteOrderDefault, CGBitmapInfo, CGImageAlphaInfo,
+    CGImageRef,
 };
 use super::{CGFloat, CGPoint, CGRect};
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::image::{gamma_decode, gamma_encode, Image};
+use crate::image::{gamma_decode, gamma_encode, pixel_format, Image};
 use crate::mem::{GuestUSize, Mem, MutVoidPtr};
-use crate::objc::ObjC;
+use crate::objc::{nil, ObjC};
 use crate::Environment;
 
 #[derive(Copy, Clone)]
@@ -33,6 +34,10 @@ pub(super) struct CGBitmapContextData {
     bytes_per_row: GuestUSize,
     color_space: &'static str,
     alpha_info: CGImageAlphaInfo,
+    /// Whether 32-bit pixels are little-endian (`kCGBitmapByteOrder32Little`),
+    /// which reverses the order of the components in memory, e.g. BGRA
+    /// instead of ARGB.
+    little_endian: bool,
 }
 
 pub fn CGBitmapContextCreate(
@@ -47,12 +52,30 @@ pub fn CGBitmapContextCreate(
 ) -> CGContextRef {
     assert!(bits_per_component == 8); // TODO: support other bit depths
 
-    let color_space = env.objc.borrow::<CGColorSpaceHostObject>(color_space).name;
+    // A NULL color space is allowed for alpha-only contexts. There's no color
+    // data in that case, so gray is as good as anything.
+    let color_space = if color_space.is_null() && bitmap_info == kCGImageAlphaOnly {
+        kCGColorSpaceGenericGray
+    } else if color_space.is_null() {
+        log!("Warning: CGBitmapContextCreate() with NULL color space and non-alpha-only bitmap info {:#x}, returning NULL", bitmap_info);
+        return nil;
+    } else {
+        env.objc.borrow::<CGColorSpaceHostObject>(color_space).name
+    };
 
     let component_count = match color_space {
-        kCGColorSpaceGenericRGB => components_for_rgb(bitmap_info).unwrap(),
-        kCGColorSpaceGenericGray => components_for_gray(bitmap_info).unwrap(),
-        _ => unimplemented!("support other color spaces"),
+        kCGColorSpaceGenericRGB => components_for_rgb(bitmap_info),
+        kCGColorSpaceGenericGray => components_for_gray(bitmap_info),
+        // Indexed color spaces can't be used for bitmap contexts.
+        _ => Err(()),
+    };
+    let Ok(component_count) = component_count else {
+        log!(
+            "TODO: CGBitmapContextCreate() with color space {} and bitmap info {:#x}, returning NULL",
+            color_space,
+            bitmap_info
+        );
+        return nil;
     };
 
     let (data, data_is_owned, bytes_per_row) = if data.is_null() {
@@ -79,9 +102,11 @@ pub fn CGBitmapContextCreate(
             bytes_per_row,
             color_space,
             alpha_info: bitmap_info & kCGBitmapAlphaInfoMask,
+            little_endian: bitmap_info & kCGBitmapByteOrderMask == kCGImageByteOrder32Little,
         }),
         // TODO: is this the correct default?
         rgb_fill_color: (0.0, 0.0, 0.0, 0.0),
+        fill_pattern: None,
         transform: CGAffineTransformIdentity,
     };
     let isa = env
@@ -111,22 +136,48 @@ pub fn CGBitmapContextGetHeight(env: &mut Environment, context: CGContextRef) ->
 
 pub fn CGBitmapContextCreateImage(env: &mut Environment, context: CGContextRef) -> CGImageRef {
     // TODO: Image::from_pixel_vec() should not exist, and this function should
-    // support any bitmap format.
+    // support grayscale bitmaps.
     let host_obj = env.objc.borrow::<CGContextHostObject>(context);
     let CGContextSubclass::CGBitmapContext(bitmap_data) = host_obj.subclass;
-    assert!(
-        bitmap_data.bits_per_component == 8
-            && bitmap_data.bytes_per_row == bitmap_data.width * 4
-            && bitmap_data.color_space == kCGColorSpaceGenericRGB
-            && bitmap_data.alpha_info == kCGImageAlphaPremultipliedLast
-    );
-    let pixels = env
-        .mem
-        .bytes_at(
-            bitmap_data.data.cast(),
-            bitmap_data.bytes_per_row * bitmap_data.height,
-        )
-        .to_vec();
+    assert!(bitmap_data.color_space == kCGColorSpaceGenericRGB);
+
+    // Image always uses premultiplied RGBA8 with no padding between rows.
+    let pixel_size = bytes_per_pixel(&bitmap_data);
+    let row_size = (bitmap_data.width * pixel_size) as usize;
+    let src = get_pixels(&bitmap_data, &mut env.mem);
+    let mut pixels =
+        Vec::with_capacity((bitmap_data.width as usize) * (bitmap_data.height as usize) * 4);
+    for row in src.chunks(bitmap_data.bytes_per_row as usize) {
+        let row = &row[..row_size];
+        match bitmap_data.alpha_info {
+            kCGImageAlphaNone => pixel_format::rgb8_to_rgba8(row, &mut pixels),
+            kCGImageAlphaOnly => {
+                for &a in row {
+                    pixels.extend_from_slice(&[0, 0, 0, a]);
+                }
+            }
+            _ => pixels.extend_from_slice(row),
+        }
+    }
+    if bitmap_data.little_endian {
+        pixel_format::swap_byte_order_32(&mut pixels);
+    }
+    match bitmap_data.alpha_info {
+        kCGImageAlphaNone | kCGImageAlphaOnly | kCGImageAlphaPremultipliedLast => (),
+        kCGImageAlphaPremultipliedFirst => pixel_format::alpha_first_to_last(&mut pixels),
+        kCGImageAlphaLast => pixel_format::premultiply(&mut pixels),
+        kCGImageAlphaFirst => {
+            pixel_format::alpha_first_to_last(&mut pixels);
+            pixel_format::premultiply(&mut pixels);
+        }
+        kCGImageAlphaNoneSkipLast => pixel_format::set_opaque(&mut pixels),
+        kCGImageAlphaNoneSkipFirst => {
+            pixel_format::alpha_first_to_last(&mut pixels);
+            pixel_format::set_opaque(&mut pixels);
+        }
+        _ => unreachable!(), // checked by bytes_per_pixel
+    }
+
     cg_image::from_image(
         env,
         Image::from_pixel_vec(pixels, (bitmap_data.width, bitmap_data.height)),
@@ -135,7 +186,10 @@ pub fn CGBitmapContextCreateImage(env: &mut Environment, context: CGContextRef)
 
 fn components_for_rgb(bitmap_info: CGBitmapInfo) -> Result<GuestUSize, ()> {
     let byte_order = bitmap_info & kCGBitmapByteOrderMask;
-    if byte_order != kCGImageByteOrderDefault && byte_order != kCGImageByteOrder32Big {
+    if byte_order != kCGImageByteOrderDefault
+        && byte_order != kCGImageByteOrder32Big
+        && byte_order != kCGImageByteOrder32Little
+    {
         return Err(()); // TODO: handle other byte orders
     }
 
@@ -143,6 +197,12 @@ fn components_for_rgb(bitmap_info: CGBitmapInfo) -> Result<GuestUSize, ()> {
     if (alpha_info | byte_order) != bitmap_info {
         return Err(()); // TODO: handle other cases (float)
     }
+    // A 32-bit byte order only makes sense for 32-bit pixels.
+    if byte_order == kCGImageByteOrder32Little
+        && matches!(alpha_info, kCGImageAlphaNone | kCGImageAlphaOnly)
+    {
+        return Err(());
+    }
     match alpha_info & kCGBitmapAlphaInfoMask {
         kCGImageAlphaNone => Ok(3), // RGB
         kCGImageAlphaPremultipliedLast
@@ -235,7 +295,7 @@ fn blend_premultiplied(bg: (f32, f32, f32, f32), fg: (f32, f32, f32, f32)) -> (f
 fn pixel_offsets(data: &CGBitmapContextData) -> (usize, usize, usize, Option<usize>) {
     match data.color_space {
         kCGColorSpaceGenericRGB => {
-            match data.alpha_info {
+            let (r, g, b, a) = match data.alpha_info {
                 kCGImageAlphaNone => (0, 1, 2, None),
                 kCGImageAlphaPremultipliedLast | kCGImageAlphaLast => (0, 1, 2, Some(3)),
                 kCGImageAlphaPremultipliedFirst | kCGImageAlphaFirst => (1, 2, 3, Some(0)),
@@ -243,6 +303,12 @@ fn pixel_offsets(data: &CGBitmapContextData) -> (usize, usize, usize, Option<usi
                 kCGImageAlphaNoneSkipFirst => (1, 2, 3, None),
                 kCGImageAlphaOnly => (0, 0, 0, Some(0)),
                 _ => unreachable!(), // checked by bytes_per_pixel
+            };
+            if data.little_endian {
+                // Only 32-bit pixels can be little-endian.
+                (3 - r, 3 - g, 3 - b, a.map(|a| 3 - a))
+            } else {
+                (r, g, b, a)
             }
         }
         kCGColorSpaceGenericGray => {
@@ -364,6 +430,7 @@ impl CGBitmapContextDrawer<'_> {
             subclass: CGContextSubclass::CGBitmapContext(bitmap_info),
             rgb_fill_color,
             transform,
+            ..
         } = objc.borrow(context);
 
         let pixels = get_pixels(&bitmap_info, mem);
@@ -476,6 +543,7 @@ fn test_iter_transformed_pixels() {
                 bytes_per_row: 3 * width,
                 color_space: "kCGColorSpaceGenericRGB",
                 alpha_info: 0,
+                little_endian: false,
             },
             rgb_fill_color: (0.0, 0.0, 0.0, 0.0),
             transform,
@@ -555,6 +623,12 @@ fn test_iter_transformed_pixels() {
 /// Implementation of `CGContextFillRect` (`clear` == [false]) and
 /// `CGContextClearRect` (`clear` == [true]) for `CGBitmapContext`.
 pub(super) fn fill_rect(env: &mut Environment, context: CGContextRef, rect: CGRect, clear: bool) {
+    let fill_pattern = env.objc.borrow::<CGContextHostObject>(context).fill_pattern;
+    if let (Some(pattern), false) = (fill_pattern, clear) {
+        fill_rect_with_pattern(env, context, rect, pattern);
+        return;
+    }
+
     let mut drawer = CGBitmapContextDrawer::new(&env.objc, &mut env.mem, context);
     let color = if clear {
         (0.0, 0.0, 0.0, 0.0)
@@ -567,6 +641,36 @@ pub(super) fn fill_rect(env: &mut Environment, context: CGContextRef, rect: CGRe
     }
 }
 
+/// Fill a rectangle by tiling an image, for pattern colors. The pattern is
+/// anchored at the origin of the user space.
+fn fill_rect_with_pattern(
+    env: &mut Environment,
+    context: CGContextRef,
+    rect: CGRect,
+    pattern: CGImageRef,
+) {
+    let image = cg_image::borrow_image(&env.objc, pattern);
+
+    let mut drawer = CGBitmapContextDrawer::new(&env.objc, &mut env.mem, context);
+
+    let (image_width, image_height) = image.dimensions();
+    if image_width == 0 || image_height == 0 {
+        return;
+    }
+
+    for ((x, y), (within_x, within_y)) in drawer.iter_transformed_pixels(rect) {
+        let user_x = rect.origin.x + rect.size.width * within_x;
+        let user_y = rect.origin.y + rect.size.height * within_y;
+        let texel_x = (user_x.floor() as i32).rem_euclid(image_width as i32);
+        // Image is in top-to-bottom order, but the bitmap is bottom-to-top
+        let texel_y =
+            image_height as i32 - 1 - (user_y.floor() as i32).rem_euclid(image_height as i32);
+        if let Some(color) = image.get_pixel((texel_x, texel_y)) {
+            drawer.put_pixel((x, y), color, /* blend: */ true)
+        }
+    }
+}
+
 /// Implementation of `CGContextDrawImage` for `CGBitmapContext`.
 pub(super) fn draw_image(
     env: &mut Environment,
@@ -575,8 +679,28 @@ pub(super) fn draw_image(
     image: CGImageRef,
 ) {
     let image = cg_image::borrow_image(&env.objc, image);
+    draw_image_inner(&env.objc, &mut env.mem, context, rect, image);
+}
 
-    let mut drawer = CGBitmapContextDrawer::new(&env.objc, &mut env.mem, context);
+/// Like [draw_image], but for an image that isn't a `CGImage`, e.g. a
+/// rasterized PDF page.
+pub(super) fn draw_host_image(
+    env: &mut Environment,
+    context: CGContextRef,
+    rect: CGRect,
+    image: &Image,
+) {
+    draw_image_inner(&env.objc, &mut env.mem, context, rect, image);
+}
+
+fn draw_image_inner(
+    objc: &ObjC,
+    mem: &mut Mem,
+    context: CGContextRef,
+    rect: CGRect,
+    image: &Image,
+) {
+    let mut drawer = CGBitmapContextDrawer::new(objc, mem, context);
 
     //let _ = std::fs::write(
     //  format!(
@@ -0,0 +1,97 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CGColor.h`
+//!
+//! In this implementation `CGColorRef` is the same type as `UIColor*`, see
+//! [crate::frameworks::uikit::ui_color].
+
+use super::cg_color_space::{self, CGColorSpaceGetNumberOfComponents, CGColorSpaceRef};
+use super::CGFloat;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::frameworks::uikit::ui_color;
+use crate::mem::{ConstPtr, GuestUSize};
+use crate::objc::{msg, nil};
+use crate::Environment;
+
+pub type CGColorRef = CFTypeRef;
+
+fn CGColorCreate(
+    env: &mut Environment,
+    space: CGColorSpaceRef,
+    components: ConstPtr<CGFloat>,
+) -> CGColorRef {
+    if space.is_null() || components.is_null() {
+        return nil;
+    }
+    // The color space's components, plus alpha.
+    let count = CGColorSpaceGetNumberOfComponents(env, space) + 1;
+    let components: Vec<CGFloat> = (0..count).map(|i| env.mem.read(components + i)).collect();
+    let rgba = cg_color_space::components_to_rgba(env, space, &components);
+    ui_color::new_with_rgba(env, rgba)
+}
+
+fn CGColorCreateCopy(env: &mut Environment, color: CGColorRef) -> CGColorRef {
+    // Colors are immutable, so there's no need for an actual copy.
+    CGColorRetain(env, color)
+}
+
+fn CGColorCreateCopyWithAlpha(
+    env: &mut Environment,
+    color: CGColorRef,
+    alpha: CGFloat,
+) -> CGColorRef {
+    let new: CGColorRef = msg![env; color colorWithAlphaComponent:alpha];
+    CFRetain(env, new)
+}
+
+fn CGColorRetain(env: &mut Environment, color: CGColorRef) -> CGColorRef {
+    if !color.is_null() {
+        CFRetain(env, color)
+    } else {
+        color
+    }
+}
+fn CGColorRelease(env: &mut Environment, color: CGColorRef) {
+    if !color.is_null() {
+        CFRelease(env, color);
+    }
+}
+
+fn CGColorEqualToColor(env: &mut Environment, a: CGColorRef, b: CGColorRef) -> bool {
+    if a == b {
+        return true;
+    } else if a.is_null() || b.is_null() {
+        return false;
+    }
+    ui_color::get_rgba(&env.objc, a) == ui_color::get_rgba(&env.objc, b)
+        && ui_color::get_pattern(&env.objc, a) == ui_color::get_pattern(&env.objc, b)
+}
+
+fn CGColorGetAlpha(env: &mut Environment, color: CGColorRef) -> CGFloat {
+    ui_color::get_alpha(&env.objc, color)
+}
+
+fn CGColorGetNumberOfComponents(_env: &mut Environment, _color: CGColorRef) -> GuestUSize {
+    // Colors are always stored as RGBA.
+    4
+}
+
+fn CGColorGetComponents(env: &mut Environment, color: CGColorRef) -> ConstPtr<CGFloat> {
+    ui_color::get_components_ptr(env, color)
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGColorCreate(_, _)),
+    export_c_func!(CGColorCreateCopy(_)),
+    export_c_func!(CGColorCreateCopyWithAlpha(_, _)),
+    export_c_func!(CGColorRetain(_)),
+    export_c_func!(CGColorRelease(_)),
+    export_c_func!(CGColorEqualToColor(_, _)),
+    export_c_func!(CGColorGetAlpha(_)),
+    export_c_func!(CGColorGetNumberOfComponents(_)),
+    export_c_func!(CGColorGetComponents(_)),
+];
@@ -4,12 +4,17 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `CGColorSpace.h`
+//!
+//! There's no real color management: all RGB color spaces are treated as sRGB
+//! and all gray color spaces are treated as the equivalent gray.
 
+use super::CGFloat;
 use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
 use crate::frameworks::core_foundation::cf_string::CFStringRef;
 use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
 use crate::frameworks::foundation::ns_string;
-use crate::objc::{msg, objc_classes, ClassExports, HostObject};
+use crate::mem::{ConstPtr, GuestUSize, MutPtr};
+use crate::objc::{msg, nil, objc_classes, ClassExports, HostObject};
 use crate::Environment;
 
 pub const CLASSES: ClassExports = objc_classes! {
@@ -20,10 +25,25 @@ pub const CLASSES: ClassExports = objc_classes! {
 // those are just Objective-C types, so we need a class for it, but its name is
 // not visible anywhere.
 @implementation _touchHLE_CGColorSpace: NSObject
+
+- (())dealloc {
+    let host_object = env.objc.borrow_mut::<CGColorSpaceHostObject>(this);
+    if let Some(IndexedColorSpace { base, .. }) = host_object.indexed.take() {
+        CGColorSpaceRelease(env, base);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
 @end
 
 };
 
+#[derive(Default)]
+pub struct State {
+    /// Shared color space returned by `CGImageGetColorSpace`.
+    image_color_space: Option<CGColorSpaceRef>,
+}
+
 pub type CGColorSpaceModel = i32;
 #[allow(dead_code)]
 pub const kCGColorSpaceModelUnknown: CGColorSpaceModel = -1;
@@ -35,60 +55,104 @@ pub const kCGColorSpaceModelCMYK: CGColorSpaceModel = 2;
 pub const kCGColorSpaceModelLab: CGColorSpaceModel = 3;
 #[allow(dead_code)]
 pub const kCGColorSpaceModelDeviceN: CGColorSpaceModel = 4;
-#[allow(dead_code)]
 pub const kCGColorSpaceModelIndexed: CGColorSpaceModel = 5;
 #[allow(dead_code)]
 pub const kCGColorSpaceModelPattern: CGColorSpaceModel = 6;
 
 pub(super) struct CGColorSpaceHostObject {
     pub(super) name: &'static str,
+    /// For indexed color spaces only.
+    indexed: Option<IndexedColorSpace>,
 }
 impl HostObject for CGColorSpaceHostObject {}
 
+struct IndexedColorSpace {
+    /// The color space of the table entries (strong reference).
+    base: CGColorSpaceRef,
+    /// The table entries, with one byte per component of `base`.
+    table: Vec<u8>,
+}
+
 pub type CGColorSpaceRef = CFTypeRef;
 
-pub fn CGColorSpaceCreateWithName(env: &mut Environment, name: CFStringRef) -> CGColorSpaceRef {
-    let generic_rgb = ns_string::get_static_str(env, kCGColorSpaceGenericRGB);
-    // TODO: support more color spaces
-    assert!(msg![env; name isEqualToString:generic_rgb]);
+pub const kCGColorSpaceGenericRGB: &str = "kCGColorSpaceGenericRGB";
+pub const kCGColorSpaceGenericGray: &str = "kCGColorSpaceGenericGray";
+pub const kCGColorSpaceSRGB: &str = "kCGColorSpaceSRGB";
+/// Internal name for indexed color spaces, which can't be created by name.
+pub(super) const INDEXED_COLOR_SPACE_NAME: &str = "_touchHLE_indexed";
 
+fn create_color_space(
+    env: &mut Environment,
+    name: &'static str,
+    indexed: Option<IndexedColorSpace>,
+) -> CGColorSpaceRef {
     let isa = env
         .objc
         .get_known_class("_touchHLE_CGColorSpace", &mut env.mem);
     env.objc.alloc_object(
         isa,
-        Box::new(CGColorSpaceHostObject {
-            name: kCGColorSpaceGenericRGB,
-        }),
+        Box::new(CGColorSpaceHostObject { name, indexed }),
         &mut env.mem,
     )
 }
 
+pub fn CGColorSpaceCreateWithName(env: &mut Environment, name: CFStringRef) -> CGColorSpaceRef {
+    // sRGB is passed through as-is, which is what the generic RGB space also
+    // does in this implementation.
+    let known_names = [
+        (kCGColorSpaceGenericRGB, kCGColorSpaceGenericRGB),
+        (kCGColorSpaceSRGB, kCGColorSpaceGenericRGB),
+        (kCGColorSpaceGenericGray, kCGColorSpaceGenericGray),
+    ];
+    for (known_name, model_name) in known_names {
+        let known_name = ns_string::get_static_str(env, known_name);
+        if msg![env; name isEqualToString:known_name] {
+            return create_color_space(env, model_name, None);
+        }
+    }
+    log!(
+        "TODO: CGColorSpaceCreateWithName({:?}) unsupported, returning NULL",
+        ns_string::to_rust_string(env, name)
+    );
+    nil
+}
+
 pub fn CGColorSpaceCreateDeviceRGB(env: &mut Environment) -> CGColorSpaceRef {
     // TODO: figure out what characteristics kCGColorSpaceDeviceRGB actually has
     //       on an iPhone
-    let isa = env
-        .objc
-        .get_known_class("_touchHLE_CGColorSpace", &mut env.mem);
-    env.objc.alloc_object(
-        isa,
-        Box::new(CGColorSpaceHostObject {
-            name: kCGColorSpaceGenericRGB,
-        }),
-        &mut env.mem,
-    )
+    create_color_space(env, kCGColorSpaceGenericRGB, None)
 }
 
 fn CGColorSpaceCreateDeviceGray(env: &mut Environment) -> CGColorSpaceRef {
-    let isa = env
-        .objc
-        .get_known_class("_touchHLE_CGColorSpace", &mut env.mem);
-    env.objc.alloc_object(
-        isa,
-        Box::new(CGColorSpaceHostObject {
-            name: kCGColorSpaceGenericGray,
-        }),
-        &mut env.mem,
+    create_color_space(env, kCGColorSpaceGenericGray, None)
+}
+
+fn CGColorSpaceCreateIndexed(
+    env: &mut Environment,
+    base: CGColorSpaceRef,
+    last_index: GuestUSize,
+    color_table: ConstPtr<u8>,
+) -> CGColorSpaceRef {
+    // Indices are at most 8 bits.
+    if base == nil || last_index > 255 || is_indexed(env, base) {
+        log!(
+            "Warning: CGColorSpaceCreateIndexed({:?}, {}, {:?}) has invalid arguments, returning NULL",
+            base,
+            last_index,
+            color_table
+        );
+        return nil;
+    }
+    let entry_size = CGColorSpaceGetNumberOfComponents(env, base);
+    let table = env
+        .mem
+        .bytes_at(color_table, (last_index + 1) * entry_size)
+        .to_vec();
+    CGColorSpaceRetain(env, base);
+    create_color_space(
+        env,
+        INDEXED_COLOR_SPACE_NAME,
+        Some(IndexedColorSpace { base, table }),
     )
 }
 
@@ -105,17 +169,130 @@ pub fn CGColorSpaceRetain(env: &mut Environment, cs: CGColorSpaceRef) -> CGColor
     }
 }
 
+fn is_indexed(env: &Environment, cs: CGColorSpaceRef) -> bool {
+    env.objc
+        .borrow::<CGColorSpaceHostObject>(cs)
+        .indexed
+        .is_some()
+}
+
 pub fn CGColorSpaceGetModel(env: &mut Environment, cs: CGColorSpaceRef) -> CGColorSpaceModel {
     let host_object = env.objc.borrow::<CGColorSpaceHostObject>(cs);
     match host_object.name {
         kCGColorSpaceGenericGray => kCGColorSpaceModelMonochrome,
         kCGColorSpaceGenericRGB => kCGColorSpaceModelRGB,
+        INDEXED_COLOR_SPACE_NAME => kCGColorSpaceModelIndexed,
         _ => unimplemented!(),
     }
 }
 
-pub const kCGColorSpaceGenericRGB: &str = "kCGColorSpaceGenericRGB";
-pub const kCGColorSpaceGenericGray: &str = "kCGColorSpaceGenericGray";
+pub fn CGColorSpaceGetNumberOfComponents(env: &mut Environment, cs: CGColorSpaceRef) -> GuestUSize {
+    match CGColorSpaceGetModel(env, cs) {
+        kCGColorSpaceModelRGB => 3,
+        // For indexed color spaces, the single component is the index.
+        kCGColorSpaceModelMonochrome | kCGColorSpaceModelIndexed => 1,
+        _ => unreachable!(),
+    }
+}
+
+fn CGColorSpaceGetBaseColorSpace(env: &mut Environment, cs: CGColorSpaceRef) -> CGColorSpaceRef {
+    let host_object = env.objc.borrow::<CGColorSpaceHostObject>(cs);
+    host_object
+        .indexed
+        .as_ref()
+        .map_or(nil, |indexed| indexed.base)
+}
+
+fn CGColorSpaceGetColorTableCount(env: &mut Environment, cs: CGColorSpaceRef) -> GuestUSize {
+    let host_object = env.objc.borrow::<CGColorSpaceHostObject>(cs);
+    let Some(IndexedColorSpace { base, ref table }) = host_object.indexed else {
+        return 0;
+    };
+    let table_len: GuestUSize = table.len().try_into().unwrap();
+    table_len / CGColorSpaceGetNumberOfComponents(env, base)
+}
+
+fn CGColorSpaceGetColorTable(env: &mut Environment, cs: CGColorSpaceRef, table: MutPtr<u8>) {
+    let host_object = env.objc.borrow::<CGColorSpaceHostObject>(cs);
+    let Some(IndexedColorSpace {
+        table: ref src_table,
+        ..
+    }) = host_object.indexed
+    else {
+        return;
+    };
+    let size = src_table.len().try_into().unwrap();
+    env.mem.bytes_at_mut(table, size).copy_from_slice(src_table);
+}
+
+/// For use by `CGColorCreate`: convert a color in a color space, given as
+/// that space's components followed by alpha, to RGBA.
+pub(super) fn components_to_rgba(
+    env: &mut Environment,
+    cs: CGColorSpaceRef,
+    components: &[CGFloat],
+) -> (CGFloat, CGFloat, CGFloat, CGFloat) {
+    match CGColorSpaceGetModel(env, cs) {
+        kCGColorSpaceModelMonochrome => {
+            let [gray, alpha] = components[..2] else {
+                unreachable!()
+            };
+            (gray, gray, gray, alpha)
+        }
+        kCGColorSpaceModelRGB => {
+            let [r, g, b, a] = components[..4] else {
+                unreachable!()
+            };
+            (r, g, b, a)
+        }
+        kCGColorSpaceModelIndexed => {
+            let host_object = env.objc.borrow::<CGColorSpaceHostObject>(cs);
+            let indexed = host_object.indexed.as_ref().unwrap();
+            let (base, table) = (indexed.base, indexed.table.clone());
+            let entry_size = CGColorSpaceGetNumberOfComponents(env, base) as usize;
+            let last_index = table.len() / entry_size - 1;
+            let index = (components[0].round().max(0.0) as usize).min(last_index);
+            let mut base_components: Vec<CGFloat> = table
+                [index * entry_size..(index + 1) * entry_size]
+                .iter()
+                .map(|&component| component as CGFloat / 255.0)
+                .collect();
+            base_components.push(components[1]);
+            components_to_rgba(env, base, &base_components)
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// For use by `CGImageGetColorSpace`: get the color space for images, which
+/// are always sRGB. This is never deallocated, so the caller doesn't need to
+/// retain it.
+pub(super) fn image_color_space(env: &mut Environment) -> CGColorSpaceRef {
+    if let Some(existing) = env
+        .framework_state
+        .core_graphics
+        .cg_color_space
+        .image_color_space
+    {
+        return existing;
+    }
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CGColorSpace", &mut env.mem);
+    let new = env.objc.alloc_static_object(
+        isa,
+        Box::new(CGColorSpaceHostObject {
+            name: kCGColorSpaceGenericRGB,
+            indexed: None,
+        }),
+        &mut env.mem,
+    );
+    env.framework_state
+        .core_graphics
+        .cg_color_space
+        .image_color_space = Some(new);
+    new
+}
 
 pub const CONSTANTS: ConstantExports = &[
     (
@@ -126,13 +303,22 @@ pub const CONSTANTS: ConstantExports = &[
         "_kCGColorSpaceGenericGray",
         HostConstant::NSString(kCGColorSpaceGenericGray),
     ),
+    (
+        "_kCGColorSpaceSRGB",
+        HostConstant::NSString(kCGColorSpaceSRGB),
+    ),
 ];
 
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CGColorSpaceCreateWithName(_)),
     export_c_func!(CGColorSpaceCreateDeviceRGB()),
     export_c_func!(CGColorSpaceCreateDeviceGray()),
+    export_c_func!(CGColorSpaceCreateIndexed(_, _, _)),
     export_c_func!(CGColorSpaceRetain(_)),
     export_c_func!(CGColorSpaceRelease(_)),
     export_c_func!(CGColorSpaceGetModel(_)),
+    export_c_func!(CGColorSpaceGetNumberOfComponents(_)),
+    export_c_func!(CGColorSpaceGetBaseColorSpace(_)),
+    export_c_func!(CGColorSpaceGetColorTableCount(_)),
+    export_c_func!(CGColorSpaceGetColorTable(_, _)),
 ];
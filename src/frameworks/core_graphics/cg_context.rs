@@ -6,10 +6,13 @@
 //! `CGContext.h`
 
 use super::cg_affine_transform::CGAffineTransform;
-use super::cg_image::CGImageRef;
+use super::cg_color::CGColorRef;
+use super::cg_image::{CGImageRef, CGImageRelease, CGImageRetain};
+use super::cg_pdf_page::{self, CGPDFPageRef};
 use super::{cg_bitmap_context, CGFloat, CGRect};
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::frameworks::uikit::ui_color;
 use crate::objc::{objc_classes, ClassExports, HostObject};
 use crate::Environment;
 
@@ -25,9 +28,13 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (())dealloc {
     let host_obj = env.objc.borrow::<CGContextHostObject>(this);
     let CGContextSubclass::CGBitmapContext(bitmap_data) = host_obj.subclass;
+    let fill_pattern = host_obj.fill_pattern;
     if bitmap_data.data_is_owned {
         env.mem.free(bitmap_data.data);
     }
+    if let Some(pattern) = fill_pattern {
+        CGImageRelease(env, pattern);
+    }
 
     env.objc.dealloc_object(this, &mut env.mem)
 }
@@ -39,6 +46,9 @@ pub const CLASSES: ClassExports = objc_classes! {
 pub(super) struct CGContextHostObject {
     pub(super) subclass: CGContextSubclass,
     pub(super) rgb_fill_color: (CGFloat, CGFloat, CGFloat, CGFloat),
+    /// Image tiled when filling, if the fill color is a pattern color (strong
+    /// reference). This takes precedence over `rgb_fill_color`.
+    pub(super) fill_pattern: Option<CGImageRef>,
     /// Current transform.
     pub(super) transform: CGAffineTransform,
 }
@@ -63,6 +73,23 @@ pub fn CGContextRetain(env: &mut Environment, c: CGContextRef) -> CGContextRef {
     }
 }
 
+fn set_fill(
+    env: &mut Environment,
+    context: CGContextRef,
+    color: (CGFloat, CGFloat, CGFloat, CGFloat),
+    pattern: Option<CGImageRef>,
+) {
+    if let Some(pattern) = pattern {
+        CGImageRetain(env, pattern);
+    }
+    let host_obj = env.objc.borrow_mut::<CGContextHostObject>(context);
+    host_obj.rgb_fill_color = color;
+    let old_pattern = std::mem::replace(&mut host_obj.fill_pattern, pattern);
+    if let Some(old_pattern) = old_pattern {
+        CGImageRelease(env, old_pattern);
+    }
+}
+
 pub fn CGContextSetRGBFillColor(
     env: &mut Environment,
     context: CGContextRef,
@@ -71,10 +98,7 @@ pub fn CGContextSetRGBFillColor(
     blue: CGFloat,
     alpha: CGFloat,
 ) {
-    let color = (red, green, blue, alpha);
-    env.objc
-        .borrow_mut::<CGContextHostObject>(context)
-        .rgb_fill_color = color;
+    set_fill(env, context, (red, green, blue, alpha), None);
 }
 
 fn CGContextSetGrayFillColor(
@@ -83,10 +107,17 @@ fn CGContextSetGrayFillColor(
     gray: CGFloat,
     alpha: CGFloat,
 ) {
-    let color = (gray, gray, gray, alpha);
-    env.objc
-        .borrow_mut::<CGContextHostObject>(context)
-        .rgb_fill_color = color;
+    set_fill(env, context, (gray, gray, gray, alpha), None);
+}
+
+pub fn CGContextSetFillColorWithColor(
+    env: &mut Environment,
+    context: CGContextRef,
+    color: CGColorRef,
+) {
+    let pattern = ui_color::get_pattern(&env.objc, color);
+    let rgba = ui_color::get_rgba(&env.objc, color);
+    set_fill(env, context, rgba, pattern);
 }
 
 pub fn CGContextFillRect(env: &mut Environment, context: CGContextRef, rect: CGRect) {
@@ -141,11 +172,16 @@ pub fn CGContextDrawImage(
     cg_bitmap_context::draw_image(env, context, rect, image);
 }
 
+fn CGContextDrawPDFPage(env: &mut Environment, context: CGContextRef, page: CGPDFPageRef) {
+    cg_pdf_page::draw_page(env, context, page);
+}
+
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CGContextRetain(_)),
     export_c_func!(CGContextRelease(_)),
     export_c_func!(CGContextSetRGBFillColor(_, _, _, _, _)),
     export_c_func!(CGContextSetGrayFillColor(_, _, _)),
+    export_c_func!(CGContextSetFillColorWithColor(_, _)),
     export_c_func!(CGContextFillRect(_, _)),
     export_c_func!(CGContextClearRect(_, _)),
     export_c_func!(CGContextConcatCTM(_, _)),
@@ -154,4 +190,5 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CGContextScaleCTM(_, _, _)),
     export_c_func!(CGContextTranslateCTM(_, _, _)),
     export_c_func!(CGContextDrawImage(_, _, _)),
+    export_c_func!(CGContextDrawPDFPage(_, _)),
 ];
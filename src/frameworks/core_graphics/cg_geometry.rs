@@ -10,14 +10,48 @@
 use super::CGFloat;
 use crate::abi::{impl_GuestRet_for_large_struct, GuestArg};
 use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
-use crate::mem::SafeRead;
+use crate::mem::{MutPtr, SafeRead};
 use crate::Environment;
 
+// The string forms are parsed leniently: whitespace is ignored, as it is by
+// `CGPointFromString` and friends on the real system.
+fn strip_whitespace(s: &str) -> String {
+    s.chars().filter(|c| !c.is_whitespace()).collect()
+}
 fn parse_tuple(s: &str) -> Result<(f32, f32), ()> {
-    let (a, b) = s.split_once(", ").ok_or(())?;
+    let (a, b) = s.split_once(',').ok_or(())?;
     Ok((a.parse().map_err(|_| ())?, b.parse().map_err(|_| ())?))
 }
 
+/// Format a number the way `%g` does, which is what Apple use for
+/// `NSStringFromCGPoint` and friends, e.g. `0.1` rather than `0.100000001`.
+fn format_g(value: CGFloat) -> String {
+    let value = value as f64;
+    if value.is_nan() {
+        return "nan".to_string();
+    } else if value.is_infinite() {
+        return if value < 0.0 { "-inf" } else { "inf" }.to_string();
+    }
+    // Six significant digits, rounded, in scientific notation.
+    let scientific = format!("{:.5e}", value);
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    fn trim_zeros(s: &str) -> &str {
+        if s.contains('.') {
+            s.trim_end_matches('0').trim_end_matches('.')
+        } else {
+            s
+        }
+    }
+    if (-4..6).contains(&exponent) {
+        let fixed = format!("{:.*}", (5 - exponent) as usize, value);
+        trim_zeros(&fixed).to_string()
+    } else {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", trim_zeros(mantissa), sign, exponent.abs())
+    }
+}
+
 #[derive(Copy, Clone, Debug, Default, PartialEq)]
 #[repr(C, packed)]
 pub struct CGPoint {
@@ -43,6 +77,7 @@ impl GuestArg for CGPoint {
 impl std::str::FromStr for CGPoint {
     type Err = ();
     fn from_str(s: &str) -> Result<CGPoint, ()> {
+        let s = &strip_whitespace(s);
         let s = s.strip_prefix('{').ok_or(())?.strip_suffix('}').ok_or(())?;
         let (x, y) = parse_tuple(s)?;
         Ok(CGPoint { x, y })
@@ -51,7 +86,7 @@ impl std::str::FromStr for CGPoint {
 impl std::fmt::Display for CGPoint {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         let &CGPoint { x, y } = self;
-        write!(f, "{{{}, {}}}", x, y)
+        write!(f, "{{{}, {}}}", format_g(x), format_g(y))
     }
 }
 // This function is rare because it is usually inlined.
@@ -86,6 +121,7 @@ impl GuestArg for CGSize {
 impl std::str::FromStr for CGSize {
     type Err = ();
     fn from_str(s: &str) -> Result<CGSize, ()> {
+        let s = &strip_whitespace(s);
         let s = s.strip_prefix('{').ok_or(())?.strip_suffix('}').ok_or(())?;
         let (w, h) = parse_tuple(s)?;
         Ok(CGSize {
@@ -97,7 +133,7 @@ impl std::str::FromStr for CGSize {
 impl std::fmt::Display for CGSize {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         let &CGSize { width, height } = self;
-        write!(f, "{{{}, {}}}", width, height)
+        write!(f, "{{{}, {}}}", format_g(width), format_g(height))
     }
 }
 // This function is rare because it is usually inlined.
@@ -135,12 +171,13 @@ impl GuestArg for CGRect {
 impl std::str::FromStr for CGRect {
     type Err = ();
     fn from_str(s: &str) -> Result<CGRect, ()> {
+        let s = &strip_whitespace(s);
         let s = s
             .strip_prefix("{{")
             .ok_or(())?
             .strip_suffix("}}")
             .ok_or(())?;
-        let (a, b) = s.split_once("}, {").ok_or(())?;
+        let (a, b) = s.split_once("},{").ok_or(())?;
         let (x, y) = parse_tuple(a)?;
         let (width, height) = parse_tuple(b)?;
         Ok(CGRect {
@@ -165,18 +202,295 @@ pub const CGRectZero: CGRect = CGRect {
     size: CGSizeZero,
 };
 
+/// The result of operations like intersecting two rectangles that don't
+/// overlap.
+pub const CGRectNull: CGRect = CGRect {
+    origin: CGPoint {
+        x: CGFloat::INFINITY,
+        y: CGFloat::INFINITY,
+    },
+    size: CGSizeZero,
+};
+
+pub type CGRectEdge = u32;
+pub const CGRectMinXEdge: CGRectEdge = 0;
+pub const CGRectMinYEdge: CGRectEdge = 1;
+pub const CGRectMaxXEdge: CGRectEdge = 2;
+pub const CGRectMaxYEdge: CGRectEdge = 3;
+
+// Like for CGAffineTransform, the CGRect* functions are wrappers around these
+// methods, so host code can use them too. Apple's functions standardize their
+// inputs (making the width and height non-negative) before doing anything
+// else, so these do too.
+impl CGRect {
+    pub fn is_null(self) -> bool {
+        self.origin.x.is_infinite() || self.origin.y.is_infinite()
+    }
+    pub fn is_empty(self) -> bool {
+        self.is_null() || self.size.width == 0.0 || self.size.height == 0.0
+    }
+    pub fn standardize(self) -> Self {
+        let CGRect {
+            origin: CGPoint { x, y },
+            size: CGSize { width, height },
+        } = self;
+        CGRect {
+            origin: CGPoint {
+                x: x + width.min(0.0),
+                y: y + height.min(0.0),
+            },
+            size: CGSize {
+                width: width.abs(),
+                height: height.abs(),
+            },
+        }
+    }
+    pub fn min_x(self) -> CGFloat {
+        self.standardize().origin.x
+    }
+    pub fn mid_x(self) -> CGFloat {
+        let rect = self.standardize();
+        rect.origin.x + rect.size.width / 2.0
+    }
+    pub fn max_x(self) -> CGFloat {
+        let rect = self.standardize();
+        rect.origin.x + rect.size.width
+    }
+    pub fn min_y(self) -> CGFloat {
+        self.standardize().origin.y
+    }
+    pub fn mid_y(self) -> CGFloat {
+        let rect = self.standardize();
+        rect.origin.y + rect.size.height / 2.0
+    }
+    pub fn max_y(self) -> CGFloat {
+        let rect = self.standardize();
+        rect.origin.y + rect.size.height
+    }
+    /// Construct a rectangle from its edges.
+    fn from_edges(min_x: CGFloat, min_y: CGFloat, max_x: CGFloat, max_y: CGFloat) -> Self {
+        CGRect {
+            origin: CGPoint { x: min_x, y: min_y },
+            size: CGSize {
+                width: max_x - min_x,
+                height: max_y - min_y,
+            },
+        }
+    }
+
+    pub fn contains_point(self, point: CGPoint) -> bool {
+        !self.is_null()
+            && self.min_x() <= point.x
+            && point.x < self.max_x()
+            && self.min_y() <= point.y
+            && point.y < self.max_y()
+    }
+    pub fn contains_rect(self, other: Self) -> bool {
+        self.union(other) == self.standardize()
+    }
+    pub fn intersects_rect(self, other: Self) -> bool {
+        !self.intersection(other).is_empty()
+    }
+
+    pub fn inset(self, dx: CGFloat, dy: CGFloat) -> Self {
+        if self.is_null() {
+            return self;
+        }
+        let rect = self.standardize();
+        let width = rect.size.width - dx * 2.0;
+        let height = rect.size.height - dy * 2.0;
+        if width < 0.0 || height < 0.0 {
+            return CGRectNull;
+        }
+        CGRect {
+            origin: CGPoint {
+                x: rect.origin.x + dx,
+                y: rect.origin.y + dy,
+            },
+            size: CGSize { width, height },
+        }
+    }
+    pub fn offset(self, dx: CGFloat, dy: CGFloat) -> Self {
+        if self.is_null() {
+            return self;
+        }
+        let rect = self.standardize();
+        CGRect {
+            origin: CGPoint {
+                x: rect.origin.x + dx,
+                y: rect.origin.y + dy,
+            },
+            size: rect.size,
+        }
+    }
+    /// Get the smallest rectangle with integer co-ordinates that contains this
+    /// one.
+    pub fn integral(self) -> Self {
+        if self.is_null() {
+            return self;
+        }
+        Self::from_edges(
+            self.min_x().floor(),
+            self.min_y().floor(),
+            self.max_x().ceil(),
+            self.max_y().ceil(),
+        )
+    }
+    pub fn intersection(self, other: Self) -> Self {
+        if self.is_null() || other.is_null() {
+            return CGRectNull;
+        }
+        let min_x = self.min_x().max(other.min_x());
+        let min_y = self.min_y().max(other.min_y());
+        let max_x = self.max_x().min(other.max_x());
+        let max_y = self.max_y().min(other.max_y());
+        if max_x < min_x || max_y < min_y {
+            return CGRectNull;
+        }
+        Self::from_edges(min_x, min_y, max_x, max_y)
+    }
+    pub fn union(self, other: Self) -> Self {
+        if self.is_null() {
+            return other.standardize();
+        } else if other.is_null() {
+            return self.standardize();
+        }
+        Self::from_edges(
+            self.min_x().min(other.min_x()),
+            self.min_y().min(other.min_y()),
+            self.max_x().max(other.max_x()),
+            self.max_y().max(other.max_y()),
+        )
+    }
+    /// Split the rectangle in two, returning the slice of size `amount` next
+    /// to `edge` and the remainder.
+    pub fn divide(self, amount: CGFloat, edge: CGRectEdge) -> (Self, Self) {
+        if self.is_null() {
+            return (CGRectNull, CGRectNull);
+        }
+        let rect = self.standardize();
+        let (min_x, min_y, max_x, max_y) = (rect.min_x(), rect.min_y(), rect.max_x(), rect.max_y());
+        let amount_x = amount.clamp(0.0, rect.size.width);
+        let amount_y = amount.clamp(0.0, rect.size.height);
+        match edge {
+            CGRectMinXEdge => (
+                Self::from_edges(min_x, min_y, min_x + amount_x, max_y),
+                Self::from_edges(min_x + amount_x, min_y, max_x, max_y),
+            ),
+            CGRectMinYEdge => (
+                Self::from_edges(min_x, min_y, max_x, min_y + amount_y),
+                Self::from_edges(min_x, min_y + amount_y, max_x, max_y),
+            ),
+            CGRectMaxXEdge => (
+                Self::from_edges(max_x - amount_x, min_y, max_x, max_y),
+                Self::from_edges(min_x, min_y, max_x - amount_x, max_y),
+            ),
+            CGRectMaxYEdge => (
+                Self::from_edges(min_x, max_y - amount_y, max_x, max_y),
+                Self::from_edges(min_x, min_y, max_x, max_y - amount_y),
+            ),
+            _ => panic!("Invalid CGRectEdge: {}", edge),
+        }
+    }
+}
+
+fn CGRectGetMinX(_env: &mut Environment, rect: CGRect) -> CGFloat {
+    rect.min_x()
+}
+fn CGRectGetMidX(_env: &mut Environment, rect: CGRect) -> CGFloat {
+    rect.mid_x()
+}
+fn CGRectGetMaxX(_env: &mut Environment, rect: CGRect) -> CGFloat {
+    rect.max_x()
+}
+fn CGRectGetMinY(_env: &mut Environment, rect: CGRect) -> CGFloat {
+    rect.min_y()
+}
+fn CGRectGetMidY(_env: &mut Environment, rect: CGRect) -> CGFloat {
+    rect.mid_y()
+}
+fn CGRectGetMaxY(_env: &mut Environment, rect: CGRect) -> CGFloat {
+    rect.max_y()
+}
+fn CGRectGetWidth(_env: &mut Environment, rect: CGRect) -> CGFloat {
+    rect.size.width.abs()
+}
+fn CGRectGetHeight(_env: &mut Environment, rect: CGRect) -> CGFloat {
+    rect.size.height.abs()
+}
+
+fn CGRectIsNull(_env: &mut Environment, rect: CGRect) -> bool {
+    rect.is_null()
+}
+fn CGRectIsEmpty(_env: &mut Environment, rect: CGRect) -> bool {
+    rect.is_empty()
+}
+
 fn CGRectContainsPoint(_env: &mut Environment, rect: CGRect, point: CGPoint) -> bool {
-    rect.origin.x <= point.x
-        && rect.origin.x + rect.size.width > point.x
-        && rect.origin.y <= point.y
-        && rect.origin.y + rect.size.height > point.y
+    rect.contains_point(point)
+}
+fn CGRectContainsRect(_env: &mut Environment, a: CGRect, b: CGRect) -> bool {
+    a.contains_rect(b)
+}
+fn CGRectIntersectsRect(_env: &mut Environment, a: CGRect, b: CGRect) -> bool {
+    a.intersects_rect(b)
+}
+
+fn CGRectStandardize(_env: &mut Environment, rect: CGRect) -> CGRect {
+    rect.standardize()
+}
+fn CGRectInset(_env: &mut Environment, rect: CGRect, dx: CGFloat, dy: CGFloat) -> CGRect {
+    rect.inset(dx, dy)
+}
+fn CGRectOffset(_env: &mut Environment, rect: CGRect, dx: CGFloat, dy: CGFloat) -> CGRect {
+    rect.offset(dx, dy)
+}
+fn CGRectIntegral(_env: &mut Environment, rect: CGRect) -> CGRect {
+    rect.integral()
+}
+fn CGRectIntersection(_env: &mut Environment, a: CGRect, b: CGRect) -> CGRect {
+    a.intersection(b)
+}
+fn CGRectUnion(_env: &mut Environment, a: CGRect, b: CGRect) -> CGRect {
+    a.union(b)
+}
+fn CGRectDivide(
+    env: &mut Environment,
+    rect: CGRect,
+    slice: MutPtr<CGRect>,
+    remainder: MutPtr<CGRect>,
+    amount: CGFloat,
+    edge: CGRectEdge,
+) {
+    let (new_slice, new_remainder) = rect.divide(amount, edge);
+    env.mem.write(slice, new_slice);
+    env.mem.write(remainder, new_remainder);
 }
 
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CGPointEqualToPoint(_, _)),
     export_c_func!(CGSizeEqualToSize(_, _)),
     export_c_func!(CGRectEqualToRect(_, _)),
+    export_c_func!(CGRectGetMinX(_)),
+    export_c_func!(CGRectGetMidX(_)),
+    export_c_func!(CGRectGetMaxX(_)),
+    export_c_func!(CGRectGetMinY(_)),
+    export_c_func!(CGRectGetMidY(_)),
+    export_c_func!(CGRectGetMaxY(_)),
+    export_c_func!(CGRectGetWidth(_)),
+    export_c_func!(CGRectGetHeight(_)),
+    export_c_func!(CGRectIsNull(_)),
+    export_c_func!(CGRectIsEmpty(_)),
     export_c_func!(CGRectContainsPoint(_, _)),
+    export_c_func!(CGRectContainsRect(_, _)),
+    export_c_func!(CGRectIntersectsRect(_, _)),
+    export_c_func!(CGRectStandardize(_)),
+    export_c_func!(CGRectInset(_, _, _)),
+    export_c_func!(CGRectOffset(_, _, _)),
+    export_c_func!(CGRectIntegral(_)),
+    export_c_func!(CGRectIntersection(_, _)),
+    export_c_func!(CGRectUnion(_, _)),
+    export_c_func!(CGRectDivide(_, _, _, _, _)),
 ];
 
 pub const CONSTANTS: ConstantExports = &[
@@ -192,4 +506,80 @@ pub const CONSTANTS: ConstantExports = &[
         "_CGRectZero",
         HostConstant::Custom(|mem| mem.alloc_and_write(CGRectZero).cast().cast_const()),
     ),
+    (
+        "_CGRectNull",
+        HostConstant::Custom(|mem| mem.alloc_and_write(CGRectNull).cast().cast_const()),
+    ),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: CGFloat, y: CGFloat, width: CGFloat, height: CGFloat) -> CGRect {
+        CGRect {
+            origin: CGPoint { x, y },
+            size: CGSize { width, height },
+        }
+    }
+
+    #[test]
+    fn strings() {
+        assert_eq!(format_g(0.1), "0.1");
+        assert_eq!(format_g(320.0), "320");
+        assert_eq!(format_g(-1.5), "-1.5");
+        assert_eq!(format_g(1.0 / 3.0), "0.333333");
+        assert_eq!(format_g(1234567.0), "1.23457e+06");
+        assert_eq!(format_g(0.00001), "1e-05");
+        assert_eq!(format_g(-0.0), "-0");
+        assert_eq!(
+            rect(0.0, 20.0, 320.0, 460.5).to_string(),
+            "{{0, 20}, {320, 460.5}}"
+        );
+        assert_eq!(
+            "{{0, 20}, {320, 460.5}}".parse::<CGRect>(),
+            Ok(rect(0.0, 20.0, 320.0, 460.5))
+        );
+        assert_eq!(
+            "{{0,20},{320,460.5}}".parse::<CGRect>(),
+            Ok(rect(0.0, 20.0, 320.0, 460.5))
+        );
+        assert_eq!(
+            "{ 1.5 , -2 }".parse::<CGPoint>(),
+            Ok(CGPoint { x: 1.5, y: -2.0 })
+        );
+        assert!("{1.5}".parse::<CGSize>().is_err());
+    }
+
+    #[test]
+    fn rect_functions() {
+        let a = rect(10.0, 10.0, -10.0, 20.0);
+        assert_eq!(a.standardize(), rect(0.0, 10.0, 10.0, 20.0));
+        assert_eq!(a.max_x(), 10.0);
+        assert_eq!(a.mid_y(), 20.0);
+        assert_eq!(a.inset(2.0, 3.0), rect(2.0, 13.0, 6.0, 14.0));
+        assert!(a.inset(6.0, 0.0).is_null());
+        assert_eq!(
+            rect(0.5, 1.2, 2.0, 2.0).integral(),
+            rect(0.0, 1.0, 3.0, 3.0)
+        );
+
+        let b = rect(5.0, 0.0, 10.0, 15.0);
+        assert_eq!(a.intersection(b), rect(5.0, 10.0, 5.0, 5.0));
+        assert_eq!(a.union(b), rect(0.0, 0.0, 15.0, 30.0));
+        assert!(a.intersection(rect(50.0, 50.0, 1.0, 1.0)).is_null());
+        assert_eq!(CGRectNull.union(b), b);
+        assert!(a.union(b).contains_rect(a));
+        assert!(!a.contains_rect(b));
+        assert!(a.intersects_rect(b));
+
+        assert_eq!(
+            rect(0.0, 0.0, 10.0, 20.0).divide(4.0, CGRectMaxYEdge),
+            (rect(0.0, 16.0, 10.0, 4.0), rect(0.0, 0.0, 10.0, 16.0))
+        );
+        assert_eq!(
+            rect(0.0, 0.0, 10.0, 20.0).divide(40.0, CGRectMinXEdge),
+            (rect(0.0, 0.0, 10.0, 20.0), rect(10.0, 0.0, 0.0, 20.0))
+        );
+    }
+}
@@ -5,12 +5,11 @@
  */
 //! `CGImage.h`
 
-use super::cg_color_space::{kCGColorSpaceGenericRGB, CGColorSpaceCreateWithName, CGColorSpaceRef};
+use super::cg_color_space::{self, CGColorSpaceRef};
 use super::cg_data_provider::{self, CGDataProviderRef};
 use super::CGFloat;
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
-use crate::frameworks::foundation::ns_string;
 use crate::image::Image;
 use crate::mem::{ConstPtr, GuestUSize};
 use crate::objc::{autorelease, nil, objc_classes, ClassExports, HostObject, ObjC};
@@ -113,6 +112,17 @@ fn CGImageCreateWithPNGDataProvider(
     from_image(env, image)
 }
 
+fn CGImageCreateWithJPEGDataProvider(
+    env: &mut Environment,
+    source: CGDataProviderRef,
+    decode: ConstPtr<CGFloat>,
+    should_interpolate: bool,
+    intent: i32,
+) -> CGImageRef {
+    // Image::from_bytes() detects the file type itself.
+    CGImageCreateWithPNGDataProvider(env, source, decode, should_interpolate, intent)
+}
+
 fn CGImageGetAlphaInfo(_env: &mut Environment, _image: CGImageRef) -> CGImageAlphaInfo {
     // our Image type always returns premultiplied RGBA
     // (the premultiplied part must match what the real UIImage does, but
@@ -121,11 +131,11 @@ fn CGImageGetAlphaInfo(_env: &mut Environment, _image: CGImageRef) -> CGImageAlp
 }
 
 fn CGImageGetColorSpace(env: &mut Environment, _image: CGImageRef) -> CGColorSpaceRef {
-    // Caller must release
+    // This is a "get" function, so the caller doesn't own the result.
+    // Decoded images are assumed to be sRGB and are passed through without
+    // any conversion.
     // FIXME: what if a loaded image is not sRGB?
-
-    let srgb_name = ns_string::get_static_str(env, kCGColorSpaceGenericRGB);
-    CGColorSpaceCreateWithName(env, srgb_name)
+    cg_color_space::image_color_space(env)
 }
 
 fn CGImageGetWidth(env: &mut Environment, image: CGImageRef) -> GuestUSize {
@@ -168,6 +178,7 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CGImageRelease(_)),
     export_c_func!(CGImageRetain(_)),
     export_c_func!(CGImageCreateWithPNGDataProvider(_, _, _, _)),
+    export_c_func!(CGImageCreateWithJPEGDataProvider(_, _, _, _)),
     export_c_func!(CGImageGetAlphaInfo(_)),
     export_c_func!(CGImageGetColorSpace(_)),
     export_c_func!(CGImageGetWidth(_)),
@@ -0,0 +1,168 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CGPDFDocument.h`
+//!
+//! The actual PDF parsing and rendering is done by [crate::pdf].
+
+use super::cg_data_provider::{self, CGDataProviderRef};
+use super::cg_pdf_page::{self, CGPDFPageRef};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::cf_url::CFURLRef;
+use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::frameworks::foundation::ns_url::to_rust_path;
+use crate::mem::{GuestUSize, MutPtr};
+use crate::objc::{nil, objc_classes, ClassExports, HostObject};
+use crate::pdf::Document;
+use crate::Environment;
+use std::rc::Rc;
+
+pub type CGPDFDocumentRef = CFTypeRef;
+
+pub(super) struct CGPDFDocumentHostObject {
+    pub(super) document: Rc<Document>,
+    /// Page objects, created on demand by `CGPDFDocumentGetPage` (strong
+    /// references).
+    pages: Vec<Option<CGPDFPageRef>>,
+}
+impl HostObject for CGPDFDocumentHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CGPDFDocument is a CFType-based type, but in our implementation those are
+// just Objective-C types, so we need a class for it, but its name is not
+// visible anywhere.
+@implementation _touchHLE_CGPDFDocument: NSObject
+
+- (())dealloc {
+    let pages = std::mem::take(&mut env.objc.borrow_mut::<CGPDFDocumentHostObject>(this).pages);
+    for page in pages.into_iter().flatten() {
+        cg_pdf_page::CGPDFPageRelease(env, page);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+fn create_with_bytes(env: &mut Environment, bytes: &[u8]) -> CGPDFDocumentRef {
+    let document = match Document::parse(bytes) {
+        Ok(document) => document,
+        Err(err) => {
+            log!("Warning: couldn't parse PDF document: {}", err);
+            return nil;
+        }
+    };
+    let pages = vec![None; document.page_count()];
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CGPDFDocument", &mut env.mem);
+    env.objc.alloc_object(
+        isa,
+        Box::new(CGPDFDocumentHostObject {
+            document: Rc::new(document),
+            pages,
+        }),
+        &mut env.mem,
+    )
+}
+
+fn CGPDFDocumentCreateWithURL(env: &mut Environment, url: CFURLRef) -> CGPDFDocumentRef {
+    let path = to_rust_path(env, url);
+    let Ok(bytes) = env.fs.read(&path) else {
+        log!(
+            "Warning: CGPDFDocumentCreateWithURL() couldn't read {:?}, returning NULL",
+            path
+        );
+        return nil;
+    };
+    create_with_bytes(env, &bytes)
+}
+
+fn CGPDFDocumentCreateWithProvider(
+    env: &mut Environment,
+    provider: CGDataProviderRef,
+) -> CGPDFDocumentRef {
+    let bytes = cg_data_provider::borrow_bytes(env, provider).to_vec();
+    create_with_bytes(env, &bytes)
+}
+
+pub fn CGPDFDocumentRelease(env: &mut Environment, document: CGPDFDocumentRef) {
+    if !document.is_null() {
+        CFRelease(env, document);
+    }
+}
+pub fn CGPDFDocumentRetain(env: &mut Environment, document: CGPDFDocumentRef) -> CGPDFDocumentRef {
+    if !document.is_null() {
+        CFRetain(env, document)
+    } else {
+        document
+    }
+}
+
+fn CGPDFDocumentGetNumberOfPages(env: &mut Environment, document: CGPDFDocumentRef) -> GuestUSize {
+    let host_object = env.objc.borrow::<CGPDFDocumentHostObject>(document);
+    host_object.pages.len().try_into().unwrap()
+}
+
+fn CGPDFDocumentGetPage(
+    env: &mut Environment,
+    document: CGPDFDocumentRef,
+    page_number: GuestUSize,
+) -> CGPDFPageRef {
+    let host_object = env.objc.borrow::<CGPDFDocumentHostObject>(document);
+    // Page numbers start at 1.
+    let Some(&existing) = (page_number as usize)
+        .checked_sub(1)
+        .and_then(|index| host_object.pages.get(index))
+    else {
+        return nil;
+    };
+    if let Some(page) = existing {
+        return page;
+    }
+    let page = cg_pdf_page::new(env, document, page_number);
+    env.objc
+        .borrow_mut::<CGPDFDocumentHostObject>(document)
+        .pages[page_number as usize - 1] = Some(page);
+    page
+}
+
+fn CGPDFDocumentGetVersion(
+    env: &mut Environment,
+    document: CGPDFDocumentRef,
+    major_version: MutPtr<i32>,
+    minor_version: MutPtr<i32>,
+) {
+    let host_object = env.objc.borrow::<CGPDFDocumentHostObject>(document);
+    let (major, minor) = host_object.document.version();
+    env.mem.write(major_version, major);
+    env.mem.write(minor_version, minor);
+}
+
+fn CGPDFDocumentIsEncrypted(env: &mut Environment, document: CGPDFDocumentRef) -> bool {
+    let host_object = env.objc.borrow::<CGPDFDocumentHostObject>(document);
+    host_object.document.is_encrypted()
+}
+
+fn CGPDFDocumentIsUnlocked(env: &mut Environment, document: CGPDFDocumentRef) -> bool {
+    // Decryption isn't supported, so encrypted documents can't be unlocked.
+    !CGPDFDocumentIsEncrypted(env, document)
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGPDFDocumentCreateWithURL(_)),
+    export_c_func!(CGPDFDocumentCreateWithProvider(_)),
+    export_c_func!(CGPDFDocumentRetain(_)),
+    export_c_func!(CGPDFDocumentRelease(_)),
+    export_c_func!(CGPDFDocumentGetNumberOfPages(_)),
+    export_c_func!(CGPDFDocumentGetPage(_, _)),
+    export_c_func!(CGPDFDocumentGetVersion(_, _, _)),
+    export_c_func!(CGPDFDocumentIsEncrypted(_)),
+    export_c_func!(CGPDFDocumentIsUnlocked(_)),
+];
@@ -0,0 +1,222 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CGPDFPage.h`
+
+use super::cg_affine_transform::CGAffineTransform;
+use super::cg_bitmap_context;
+use super::cg_context::{CGContextHostObject, CGContextRef};
+use super::cg_geometry::CGRectNull;
+use super::cg_pdf_document::{CGPDFDocumentHostObject, CGPDFDocumentRef};
+use super::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
+use crate::image::Image;
+use crate::mem::GuestUSize;
+use crate::objc::{objc_classes, ClassExports, HostObject};
+use crate::pdf::PageBox;
+use crate::Environment;
+
+pub type CGPDFPageRef = CFTypeRef;
+
+pub type CGPDFBox = i32;
+pub const kCGPDFMediaBox: CGPDFBox = 0;
+pub const kCGPDFCropBox: CGPDFBox = 1;
+pub const kCGPDFBleedBox: CGPDFBox = 2;
+pub const kCGPDFTrimBox: CGPDFBox = 3;
+pub const kCGPDFArtBox: CGPDFBox = 4;
+
+/// Limit on the width and height of the bitmap a page is rendered to, to avoid
+/// excessive memory use with strange transforms.
+const MAX_RENDER_SIZE: u32 = 4096;
+
+struct CGPDFPageHostObject {
+    /// The document this page belongs to (weak reference: the document owns
+    /// its pages).
+    document: CGPDFDocumentRef,
+    /// Page numbers start at 1.
+    page_number: GuestUSize,
+}
+impl HostObject for CGPDFPageHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CGPDFPage is a CFType-based type, but in our implementation those are just
+// Objective-C types, so we need a class for it, but its name is not visible
+// anywhere.
+@implementation _touchHLE_CGPDFPage: NSObject
+@end
+
+};
+
+/// For use by `CGPDFDocumentGetPage`.
+pub(super) fn new(
+    env: &mut Environment,
+    document: CGPDFDocumentRef,
+    page_number: GuestUSize,
+) -> CGPDFPageRef {
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CGPDFPage", &mut env.mem);
+    env.objc.alloc_object(
+        isa,
+        Box::new(CGPDFPageHostObject {
+            document,
+            page_number,
+        }),
+        &mut env.mem,
+    )
+}
+
+pub fn CGPDFPageRelease(env: &mut Environment, page: CGPDFPageRef) {
+    if !page.is_null() {
+        CFRelease(env, page);
+    }
+}
+pub fn CGPDFPageRetain(env: &mut Environment, page: CGPDFPageRef) -> CGPDFPageRef {
+    if !page.is_null() {
+        CFRetain(env, page)
+    } else {
+        page
+    }
+}
+
+fn CGPDFPageGetDocument(env: &mut Environment, page: CGPDFPageRef) -> CGPDFDocumentRef {
+    env.objc.borrow::<CGPDFPageHostObject>(page).document
+}
+
+fn CGPDFPageGetPageNumber(env: &mut Environment, page: CGPDFPageRef) -> GuestUSize {
+    env.objc.borrow::<CGPDFPageHostObject>(page).page_number
+}
+
+fn CGPDFPageGetBoxRect(env: &mut Environment, page: CGPDFPageRef, box_: CGPDFBox) -> CGRect {
+    let page_box = match box_ {
+        kCGPDFMediaBox => PageBox::Media,
+        kCGPDFCropBox => PageBox::Crop,
+        kCGPDFBleedBox => PageBox::Bleed,
+        kCGPDFTrimBox => PageBox::Trim,
+        kCGPDFArtBox => PageBox::Art,
+        _ => return CGRectNull,
+    };
+    let &CGPDFPageHostObject {
+        document,
+        page_number,
+    } = env.objc.borrow(page);
+    let document = &env
+        .objc
+        .borrow::<CGPDFDocumentHostObject>(document)
+        .document;
+    let [x0, y0, x1, y1] = document.page_box(page_number as usize - 1, page_box);
+    CGRect {
+        origin: CGPoint { x: x0, y: y0 },
+        size: CGSize {
+            width: x1 - x0,
+            height: y1 - y0,
+        },
+    }
+}
+
+fn CGPDFPageGetRotationAngle(env: &mut Environment, page: CGPDFPageRef) -> i32 {
+    let &CGPDFPageHostObject {
+        document,
+        page_number,
+    } = env.objc.borrow(page);
+    let document = &env
+        .objc
+        .borrow::<CGPDFDocumentHostObject>(document)
+        .document;
+    document.page_rotation(page_number as usize - 1)
+}
+
+fn CGPDFPageGetDrawingTransform(
+    env: &mut Environment,
+    page: CGPDFPageRef,
+    box_: CGPDFBox,
+    rect: CGRect,
+    rotate: i32,
+    preserve_aspect_ratio: bool,
+) -> CGAffineTransform {
+    let box_rect = CGPDFPageGetBoxRect(env, page, box_);
+    // Both rotations are clockwise and multiples of 90 degrees.
+    let rotate = rotate - rotate % 90 + CGPDFPageGetRotationAngle(env, page);
+    let rotate = rotate.rem_euclid(360);
+
+    let (box_width, box_height) = if rotate % 180 == 90 {
+        (box_rect.size.height, box_rect.size.width)
+    } else {
+        (box_rect.size.width, box_rect.size.height)
+    };
+    let mut scale_x = rect.size.width / box_width;
+    let mut scale_y = rect.size.height / box_height;
+    if preserve_aspect_ratio {
+        scale_x = scale_x.min(scale_y);
+        scale_y = scale_x;
+    }
+    // The box is only ever scaled down, never up.
+    let (scale_x, scale_y) = (scale_x.min(1.0), scale_y.min(1.0));
+
+    // Move the center of the box to the origin, rotate and scale it, then move
+    // it to the center of the rect.
+    CGAffineTransform::make_translation(-box_rect.mid_x(), -box_rect.mid_y())
+        .concat(CGAffineTransform::make_rotation(
+            -(rotate as CGFloat).to_radians(),
+        ))
+        .concat(CGAffineTransform::make_scale(scale_x, scale_y))
+        .concat(CGAffineTransform::make_translation(
+            rect.mid_x(),
+            rect.mid_y(),
+        ))
+}
+
+/// Implementation of `CGContextDrawPDFPage`. The page is rasterized at the
+/// resolution it will be drawn at, then drawn like an image.
+pub(super) fn draw_page(env: &mut Environment, context: CGContextRef, page: CGPDFPageRef) {
+    let &CGPDFPageHostObject {
+        document,
+        page_number,
+    } = env.objc.borrow(page);
+    let document = env
+        .objc
+        .borrow::<CGPDFDocumentHostObject>(document)
+        .document
+        .clone();
+    let page_index = page_number as usize - 1;
+
+    // Pages are drawn in their default user space (without rotation), clipped
+    // to the crop box.
+    let area = document.page_box(page_index, PageBox::Crop);
+    let [x0, y0, x1, y1] = area;
+    let rect = CGRect {
+        origin: CGPoint { x: x0, y: y0 },
+        size: CGSize {
+            width: x1 - x0,
+            height: y1 - y0,
+        },
+    };
+    if rect.is_empty() {
+        return;
+    }
+
+    let transform = env.objc.borrow::<CGContextHostObject>(context).transform;
+    let device_size = transform.apply_to_rect(rect).size;
+    let width = (device_size.width.ceil() as u32).clamp(1, MAX_RENDER_SIZE);
+    let height = (device_size.height.ceil() as u32).clamp(1, MAX_RENDER_SIZE);
+
+    let pixels = document.render_page(page_index, area, width, height);
+    let image = Image::from_pixel_vec(pixels, (width, height));
+    cg_bitmap_context::draw_host_image(env, context, rect, &image);
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGPDFPageRetain(_)),
+    export_c_func!(CGPDFPageRelease(_)),
+    export_c_func!(CGPDFPageGetDocument(_)),
+    export_c_func!(CGPDFPageGetPageNumber(_)),
+    export_c_func!(CGPDFPageGetBoxRect(_, _)),
+    export_c_func!(CGPDFPageGetRotationAngle(_)),
+    export_c_func!(CGPDFPageGetDrawingTransform(_, _, _, _, _)),
+];
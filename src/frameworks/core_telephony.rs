@@ -0,0 +1,104 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The Core Telephony framework.
+//!
+//! There's only a cellular provider when `--connection-type=wwan` is used, so
+//! that it matches what `SCNetworkReachability` reports. Otherwise the device
+//! looks like one without a SIM card.
+
+use super::foundation::ns_string;
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::objc::{autorelease, id, msg_class, nil, objc_classes, ClassExports};
+use crate::options::ConnectionType;
+use crate::Environment;
+
+pub const CTRadioAccessTechnologyGPRS: &str = "CTRadioAccessTechnologyGPRS";
+pub const CTRadioAccessTechnologyEdge: &str = "CTRadioAccessTechnologyEdge";
+pub const CTRadioAccessTechnologyWCDMA: &str = "CTRadioAccessTechnologyWCDMA";
+pub const CTRadioAccessTechnologyHSDPA: &str = "CTRadioAccessTechnologyHSDPA";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_CTRadioAccessTechnologyGPRS",
+        HostConstant::NSString(CTRadioAccessTechnologyGPRS),
+    ),
+    (
+        "_CTRadioAccessTechnologyEdge",
+        HostConstant::NSString(CTRadioAccessTechnologyEdge),
+    ),
+    (
+        "_CTRadioAccessTechnologyWCDMA",
+        HostConstant::NSString(CTRadioAccessTechnologyWCDMA),
+    ),
+    (
+        "_CTRadioAccessTechnologyHSDPA",
+        HostConstant::NSString(CTRadioAccessTechnologyHSDPA),
+    ),
+];
+
+fn has_cellular(env: &Environment) -> bool {
+    env.options.connection_type == ConnectionType::WWAN
+}
+
+/// Get a static string (`NSString*`) if there's a cellular provider, or `nil`.
+fn cellular_str(env: &mut Environment, value: &'static str) -> id {
+    if has_cellular(env) {
+        ns_string::get_static_str(env, value)
+    } else {
+        nil
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation CTTelephonyNetworkInfo: NSObject
+
+- (id)subscriberCellularProvider {
+    let carrier: id = msg_class![env; CTCarrier new];
+    autorelease(env, carrier)
+}
+
+- (id)subscriberCellularProviderDidUpdateNotifier {
+    nil
+}
+- (())setSubscriberCellularProviderDidUpdateNotifier:(id)_notifier { // block
+    // The provider never changes, so there's nothing to notify about.
+}
+
+- (id)currentRadioAccessTechnology {
+    cellular_str(env, CTRadioAccessTechnologyHSDPA)
+}
+
+@end
+
+// The values are those reserved for test networks.
+@implementation CTCarrier: NSObject
+
+- (id)carrierName {
+    cellular_str(env, "touchHLE")
+}
+
+- (id)mobileCountryCode {
+    cellular_str(env, "001")
+}
+
+- (id)mobileNetworkCode {
+    cellular_str(env, "01")
+}
+
+- (id)isoCountryCode {
+    nil
+}
+
+- (bool)allowsVOIP {
+    has_cellular(env)
+}
+
+@end
+
+};
@@ -18,6 +18,7 @@ use crate::Environment;
 pub mod ns_array;
 pub mod ns_autorelease_pool;
 pub mod ns_bundle;
+pub mod ns_calendar;
 pub mod ns_character_set;
 pub mod ns_coder;
 pub mod ns_data;
@@ -29,6 +30,7 @@ pub mod ns_error;
 pub mod ns_exception;
 pub mod ns_file_handle;
 pub mod ns_file_manager;
+pub mod ns_http_cookie;
 pub mod ns_keyed_unarchiver;
 pub mod ns_locale;
 pub mod ns_lock;
@@ -44,8 +46,12 @@ pub mod ns_run_loop;
 pub mod ns_set;
 pub mod ns_string;
 pub mod ns_thread;
+pub mod ns_time_zone;
 pub mod ns_timer;
 pub mod ns_url;
+pub mod ns_url_cache;
+pub mod ns_url_connection;
+pub mod ns_url_request;
 pub mod ns_user_defaults;
 pub mod ns_value;
 
@@ -54,11 +60,16 @@ pub struct State {
     ns_autorelease_pool: ns_autorelease_pool::State,
     ns_bundle: ns_bundle::State,
     ns_file_manager: ns_file_manager::State,
+    ns_http_cookie: ns_http_cookie::State,
     ns_locale: ns_locale::State,
     ns_notification_center: ns_notification_center::State,
     ns_null: ns_null::State,
     ns_run_loop: ns_run_loop::State,
     ns_string: ns_string::State,
+    ns_thread: ns_thread::State,
+    ns_time_zone: ns_time_zone::State,
+    ns_url_cache: ns_url_cache::State,
+    ns_url_connection: ns_url_connection::State,
     ns_user_defaults: ns_user_defaults::State,
 }
 
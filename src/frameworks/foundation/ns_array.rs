@@ -25,7 +25,11 @@ impl HostObject for ObjectEnumeratorHostObject {}
 struct ArrayHostObject {
     array: Vec<id>,
 }
-impl HostObject for ArrayHostObject {}
+impl HostObject for ArrayHostObject {
+    fn strong_references(&self, references: &mut Vec<id>) {
+        references.extend_from_slice(&self.array);
+    }
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -5,7 +5,9 @@
  */
 //! `NSAutoreleasePool`.
 
-use crate::objc::{id, msg, objc_classes, release, ClassExports, HostObject, NSZonePtr};
+use crate::objc::{
+    id, msg, objc_classes, release, release_autoreleased, ClassExports, HostObject, NSZonePtr,
+};
 use crate::{Environment, ThreadId};
 use std::collections::HashMap;
 
@@ -26,6 +28,26 @@ struct NSAutoreleasePoolHostObject {
 }
 impl HostObject for NSAutoreleasePoolHostObject {}
 
+/// Drain any pools that the current thread left behind, most recent first.
+/// This is called when a secondary thread exits, like on Apple platforms, so
+/// that a thread that forgets to drain its pool doesn't leak its contents.
+pub fn drain_remaining_pools(env: &mut Environment) {
+    let current_thread = env.current_thread;
+    while let Some(&pool) = State::get(env)
+        .pool_stacks
+        .get(&current_thread)
+        .and_then(|pool_stack| pool_stack.last())
+    {
+        log_dbg!(
+            "Thread {} is exiting, draining its pool {:?}",
+            current_thread,
+            pool
+        );
+        release(env, pool);
+    }
+    State::get(env).pool_stacks.remove(&current_thread);
+}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -91,6 +113,18 @@ pub const CLASSES: ClassExports = objc_classes! {
     // It's unclear what should happen when draining a pool on the wrong thread,
     // but we prefer to be conservative here
     assert_eq!(host_obj.original_thread, current_thread);
+    // Draining a pool also drains any pools nested inside it that were never
+    // drained, e.g. by code inside a run loop iteration.
+    while let Some(&inner_pool) = State::get(env)
+        .pool_stacks
+        .get(&current_thread)
+        .and_then(|pool_stack| pool_stack.last())
+        .filter(|&&top| top != this)
+    {
+        log_dbg!("Draining nested pool {:?} first", inner_pool);
+        release(env, inner_pool);
+    }
+    let host_obj: &mut NSAutoreleasePoolHostObject = env.objc.borrow_mut(this);
     let pool_stack = &mut env
         .framework_state
         .foundation
@@ -102,7 +136,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     let objects = std::mem::take(&mut host_obj.objects);
     env.objc.dealloc_object(this, &mut env.mem);
     for object in objects {
-        release(env, object);
+        release_autoreleased(env, object);
     }
 }
 
@@ -5,19 +5,22 @@
  */
 //! `NSBundle`.
 
-use super::{ns_string, NSUInteger};
+mod strings_file;
+
+use super::{ns_array, ns_string, NSUInteger};
 use crate::bundle::Bundle;
 use crate::frameworks::core_foundation::cf_bundle::{
     CFBundleCopyBundleLocalizations, CFBundleCopyPreferredLocalizationsFromArray,
 };
 use crate::frameworks::foundation::ns_string::{from_rust_string, get_static_str, to_rust_string};
 use crate::frameworks::uikit::ui_nib::load_nib_file;
-use crate::fs::GuestPathBuf;
+use crate::fs::{GuestPath, GuestPathBuf};
 use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, release, ClassExports, HostObject,
 };
 use crate::Environment;
-use std::collections::HashSet;
+use std::collections::HashMap;
+use strings_file::parse_strings_file;
 
 // Should be ISO 639-1 (or ISO 639-2) compliant
 // TODO: complete this list or use some crate for mapping
@@ -31,6 +34,7 @@ const LANG_ID_TO_LANG_PROJ: &[(&str, &str)] = &[
     ("it", "Italian.lproj"),
     ("ja", "Japanese.lproj"),
     ("no", "Norwegian.lproj"),
+    ("pt", "Portuguese.lproj"),
     ("es", "Spanish.lproj"),
     ("sv", "Swedish.lproj"),
 ];
@@ -50,6 +54,9 @@ pub struct NSBundleHostObject {
     bundle_url: Option<id>,
     /// `NSDictionary*` for the `Info.plist` content. [None] if not created yet.
     info_dictionary: Option<id>,
+    /// Parsed `.strings` tables, by table name. Tables that couldn't be found
+    /// or parsed are cached as empty.
+    strings_tables: HashMap<String, HashMap<String, String>>,
 }
 impl HostObject for NSBundleHostObject {}
 
@@ -70,6 +77,7 @@ pub const CLASSES: ClassExports = objc_classes! {
             bundle_path,
             bundle_url: None,
             info_dictionary: None,
+            strings_tables: HashMap::new(),
         };
         let new = env.objc.alloc_object(
             this,
@@ -92,6 +100,7 @@ pub const CLASSES: ClassExports = objc_classes! {
         bundle_path: _, // FIXME?
         bundle_url,
         info_dictionary,
+        strings_tables: _,
     } = env.objc.borrow(this);
     if let Some(bundle_url) = bundle_url {
         release(env, bundle_url);
@@ -163,32 +172,18 @@ pub const CLASSES: ClassExports = objc_classes! {
         return path
     }
 
-    // Try preferred languages in order of preference
-    let langs: id = msg_class![env; NSLocale preferredLanguages];
-    let lang_count: NSUInteger = msg![env; langs count];
-    let mut unknown_codes = HashSet::new();
-    for i in 0..lang_count {
-        let lang_code: id = msg![env; langs objectAtIndex:i];
-        let lang_code = ns_string::to_rust_string(env, lang_code); // TODO: avoid copy
-        if let Some(&(_, lproj)) = LANG_ID_TO_LANG_PROJ.iter().find(|&&(code, _)| code == lang_code) {
-            let lproj: id = ns_string::get_static_str(env, lproj);
+    // Try the localization fallback chain in order of preference
+    for code in localization_fallback_chain(env, this) {
+        for lproj in lproj_names(&code) {
+            let lproj: id = from_rust_string(env, lproj);
             let localized_path = path_for_resource_helper(env, this, name, lproj, directory, extension);
+            release(env, lproj);
             if localized_path != nil {
                 return localized_path;
             }
-        } else {
-            unknown_codes.insert(lang_code);
         }
     }
-
-    // As a last resort, fallback to English
-    // TODO: fallback to a development language (CFBundleDevelopmentRegion from
-    // Info.plist)
-    if !unknown_codes.is_empty() {
-        log!("TODO: language codes {:?} aren't mapped to a language name, falling back to English", unknown_codes);
-    }
-    let lproj: id = ns_string::get_static_str(env, "English.lproj");
-    path_for_resource_helper(env, this, name, lproj, directory, extension)
+    nil
 }
 - (id)pathForResource:(id)name // NSString*
                ofType:(id)extension { // NSString*
@@ -209,6 +204,122 @@ pub const CLASSES: ClassExports = objc_classes! {
    msg![env; this URLForResource:name withExtension:extension subdirectory:nil]
 }
 
+- (id)pathsForResourcesOfType:(id)extension // NSString*
+                  inDirectory:(id)directory { // NSString*
+    let extension = (extension != nil).then(|| to_rust_string(env, extension));
+    let directory = (directory != nil).then(|| to_rust_string(env, directory));
+    let resource_path: id = msg![env; this resourcePath];
+    let resource_path = GuestPathBuf::from(to_rust_string(env, resource_path).into_owned());
+
+    // Unlocalized resources come first, then ones from the most preferred
+    // localization that has the directory. Like pathForResource:, localized
+    // resources don't replace unlocalized ones with the same name.
+    let mut dirs = vec![resource_path.clone()];
+    for code in localization_fallback_chain(env, this) {
+        let lproj_dir = lproj_names(&code)
+            .into_iter()
+            .map(|lproj| resource_path.join(lproj))
+            .find(|lproj_dir| env.fs.is_dir(lproj_dir));
+        if let Some(lproj_dir) = lproj_dir {
+            dirs.push(lproj_dir);
+            break;
+        }
+    }
+
+    let mut names = Vec::new();
+    let mut paths = Vec::new();
+    for dir in dirs {
+        let dir = match directory {
+            Some(ref directory) => dir.join(directory),
+            None => dir,
+        };
+        let Ok(children) = env.fs.enumerate(&dir) else {
+            continue;
+        };
+        let mut children: Vec<String> = children
+            .filter(|&name| match extension.as_deref() {
+                None | Some("") => true,
+                Some(extension) => name.rsplit_once('.').is_some_and(|(_, ext)| ext == extension),
+            })
+            .filter(|&name| !env.fs.is_dir(&dir.join(name)))
+            .map(ToOwned::to_owned)
+            .collect();
+        children.sort();
+        for name in children {
+            if !names.contains(&name) {
+                paths.push(dir.join(&name));
+                names.push(name);
+            }
+        }
+    }
+    log_dbg!("[{:?} pathsForResourcesOfType:{:?} inDirectory:{:?}] => {:?}", this, extension, directory, paths);
+
+    let paths = paths
+        .into_iter()
+        .map(|path| from_rust_string(env, String::from(path)))
+        .collect();
+    let paths = ns_array::from_vec(env, paths);
+    autorelease(env, paths)
+}
+
+- (id)URLsForResourcesWithExtension:(id)extension // NSString*
+                       subdirectory:(id)subpath { // NSString*
+    let paths: id = msg![env; this pathsForResourcesOfType:extension
+                                               inDirectory:subpath];
+    let count: NSUInteger = msg![env; paths count];
+    let mut urls = Vec::new();
+    for i in 0..count {
+        let path: id = msg![env; paths objectAtIndex:i];
+        let url: id = msg_class![env; NSURL alloc];
+        let url: id = msg![env; url initFileURLWithPath:path];
+        urls.push(url);
+    }
+    let urls = ns_array::from_vec(env, urls);
+    autorelease(env, urls)
+}
+
+- (id)localizedStringForKey:(id)key // NSString*
+                      value:(id)value // NSString*
+                      table:(id)table { // NSString*
+    let table_name = if table == nil {
+        "Localizable".to_string()
+    } else {
+        let table_name = to_rust_string(env, table);
+        if table_name.is_empty() {
+            "Localizable".to_string()
+        } else {
+            table_name.into_owned()
+        }
+    };
+
+    if !env.objc.borrow::<NSBundleHostObject>(this).strings_tables.contains_key(&table_name) {
+        let strings = load_strings_table(env, this, &table_name);
+        env.objc.borrow_mut::<NSBundleHostObject>(this).strings_tables.insert(table_name.clone(), strings);
+    }
+
+    let localized = if key == nil {
+        None
+    } else {
+        let key = to_rust_string(env, key);
+        env.objc.borrow::<NSBundleHostObject>(this).strings_tables[&table_name].get(&*key).cloned()
+    };
+    if let Some(localized) = localized {
+        let localized = from_rust_string(env, localized);
+        return autorelease(env, localized);
+    }
+
+    // If the key isn't found, the value is returned, unless it's nil or
+    // empty, in which case the key is returned.
+    let value_is_empty = value == nil || to_rust_string(env, value).is_empty();
+    if !value_is_empty {
+        value
+    } else if key != nil {
+        key
+    } else {
+        get_static_str(env, "")
+    }
+}
+
 - (id)infoDictionary {
     let &NSBundleHostObject {
         bundle_path,
@@ -238,6 +349,113 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
+/// Normalize a language code or a legacy language name (e.g. `pt-BR` or
+/// `English`) to the form used for comparisons: a code with underscores
+/// between its components (e.g. `pt_BR` or `en`).
+pub fn normalize_language_code(code: &str) -> String {
+    let code = code.strip_suffix(".lproj").unwrap_or(code);
+    if let Some(&(lang_code, _)) = LANG_ID_TO_LANG_PROJ
+        .iter()
+        .find(|&&(_, lproj)| lproj.strip_suffix(".lproj").unwrap() == code)
+    {
+        return lang_code.to_string();
+    }
+    code.replace('-', "_")
+}
+
+/// Expand a list of preferred languages into the list of localizations to
+/// try, in order. Each language is followed by its less specific forms
+/// (`zh-Hans-CN` → `zh_Hans_CN`, `zh_Hans`, `zh`), and the chain ends with
+/// the development region and then English.
+fn expand_language_chain(languages: &[String], development_region: Option<&str>) -> Vec<String> {
+    let mut chain = Vec::new();
+    let mut push = |code: String| {
+        if !chain.contains(&code) {
+            chain.push(code);
+        }
+    };
+    for language in languages {
+        let mut code = normalize_language_code(language);
+        loop {
+            push(code.clone());
+            let Some((less_specific, _)) = code.rsplit_once('_') else {
+                break;
+            };
+            code = less_specific.to_string();
+        }
+    }
+    if let Some(development_region) = development_region {
+        push(normalize_language_code(development_region));
+    }
+    push("en".to_string());
+    chain
+}
+
+/// The localizations (normalized language codes, see
+/// [normalize_language_code]) to look for resources in, in order of
+/// preference, for a bundle. This is based on the user's preferred languages
+/// and the bundle's development region.
+pub fn localization_fallback_chain(env: &mut Environment, bundle: id) -> Vec<String> {
+    let langs: id = msg_class![env; NSLocale preferredLanguages];
+    let lang_count: NSUInteger = msg![env; langs count];
+    let mut languages = Vec::new();
+    for i in 0..lang_count {
+        let lang_code: id = msg![env; langs objectAtIndex:i];
+        languages.push(to_rust_string(env, lang_code).into_owned());
+    }
+    let development_region = env
+        .objc
+        .borrow::<NSBundleHostObject>(bundle)
+        .bundle
+        .as_ref()
+        .unwrap_or(&env.bundle)
+        .development_region();
+    expand_language_chain(&languages, development_region)
+}
+
+/// Possible names of the `.lproj` directory for a normalized language code.
+/// Apps may use either separator in codes, and older apps use language names
+/// (e.g. `English.lproj`) instead.
+fn lproj_names(code: &str) -> Vec<String> {
+    let mut names = vec![format!("{}.lproj", code)];
+    if code.contains('_') {
+        names.push(format!("{}.lproj", code.replace('_', "-")));
+    }
+    if let Some(&(_, lproj)) = LANG_ID_TO_LANG_PROJ.iter().find(|&&(c, _)| c == code) {
+        names.push(lproj.to_string());
+    }
+    names
+}
+
+/// Find and parse a `.strings` table, e.g. `Localizable.strings`, using the
+/// usual localization rules. Returns an empty table on failure.
+fn load_strings_table(
+    env: &mut Environment,
+    bundle: id,
+    table_name: &str,
+) -> HashMap<String, String> {
+    let name = from_rust_string(env, table_name.to_string());
+    let extension = get_static_str(env, "strings");
+    let path: id = msg![env; bundle pathForResource:name ofType:extension];
+    release(env, name);
+    if path == nil {
+        log!("Warning: Couldn't find strings table {:?}", table_name);
+        return HashMap::new();
+    }
+    let path = to_rust_string(env, path);
+    let bytes = env.fs.read(GuestPath::new(&path)).unwrap_or_default();
+    match parse_strings_file(&bytes) {
+        Ok(strings) => {
+            log_dbg!("Loaded {} strings from {:?}", strings.len(), path);
+            strings
+        }
+        Err(e) => {
+            log!("Warning: Couldn't parse strings table {:?}: {}", path, e);
+            HashMap::new()
+        }
+    }
+}
+
 fn path_for_resource_helper(
     env: &mut Environment,
     bundle: id,
@@ -264,3 +482,27 @@ fn path_for_resource_helper(
     }
     nil
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn language_chain() {
+        let languages = ["pt-BR".to_string(), "zh-Hans-CN".to_string()];
+        assert_eq!(
+            expand_language_chain(&languages, Some("French")),
+            ["pt_BR", "pt", "zh_Hans_CN", "zh_Hans", "zh", "fr", "en"]
+        );
+        assert_eq!(
+            expand_language_chain(&["en".to_string()], Some("en")),
+            ["en"]
+        );
+    }
+
+    #[test]
+    fn lproj_name_variants() {
+        assert_eq!(lproj_names("pt_BR"), ["pt_BR.lproj", "pt-BR.lproj"]);
+        assert_eq!(lproj_names("de"), ["de.lproj", "German.lproj"]);
+    }
+}
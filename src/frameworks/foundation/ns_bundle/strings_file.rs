@@ -0,0 +1,65 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Parsing of `.strings` files, used for localized strings.
+//!
+//! In app bundles these are usually compiled to binary property lists, but
+//! they can also be in the original text format, which is usually UTF-16 with
+//! a byte order mark:
+//!
+//! ```text
+//! /* Comment */
+//! "key" = "value";
+//! ```
+
+use crate::frameworks::foundation::ns_property_list_serialization::text_plist::{
+    decode_text, parse_plist, parse_strings,
+};
+use std::collections::HashMap;
+
+/// Parse a `.strings` file in any of the supported formats.
+pub fn parse_strings_file(bytes: &[u8]) -> Result<HashMap<String, String>, String> {
+    let dict = if bytes.starts_with(b"bplist") {
+        parse_plist(bytes)?
+            .into_dictionary()
+            .ok_or_else(|| "Root of property list is not a dictionary".to_string())?
+    } else {
+        let text = decode_text(bytes)?;
+        let trimmed = text.trim_start();
+        if trimmed.starts_with('<') || trimmed.starts_with('{') {
+            // An XML or old-style property list rather than a bare list of
+            // entries.
+            parse_plist(trimmed.as_bytes())?
+                .into_dictionary()
+                .ok_or_else(|| "Root of property list is not a dictionary".to_string())?
+        } else {
+            parse_strings(&text)?
+        }
+    };
+    Ok(dict
+        .into_iter()
+        .filter_map(|(key, value)| Some((key, value.into_string()?)))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf16_with_bom() {
+        let mut bytes = vec![0xFF, 0xFE];
+        for unit in "\"a\" = \"\u{e9}\";".encode_utf16() {
+            bytes.extend_from_slice(&unit.to_le_bytes());
+        }
+        assert_eq!(parse_strings_file(&bytes).unwrap()["a"], "\u{e9}");
+    }
+
+    #[test]
+    fn old_style_dictionary() {
+        let strings = parse_strings_file(b"{ PLAY = Jouer; }").unwrap();
+        assert_eq!(strings["PLAY"], "Jouer");
+    }
+}
@@ -0,0 +1,462 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSCalendar` and `NSDateComponents`.
+//!
+//! Only the Gregorian calendar is supported, and like the rest of touchHLE's
+//! date and time code, the time zone is always UTC.
+
+use super::ns_string::get_static_str;
+use super::{NSInteger, NSTimeInterval, NSUInteger};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::libc::time::{calendar_date_to_days, days_to_calendar_date, CalendarDate};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+
+const NSGregorianCalendar: &str = "gregorian";
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_NSGregorianCalendar",
+    HostConstant::NSString(NSGregorianCalendar),
+)];
+
+type NSCalendarUnit = NSUInteger;
+const NSEraCalendarUnit: NSCalendarUnit = 1 << 1;
+const NSYearCalendarUnit: NSCalendarUnit = 1 << 2;
+const NSMonthCalendarUnit: NSCalendarUnit = 1 << 3;
+const NSDayCalendarUnit: NSCalendarUnit = 1 << 4;
+const NSHourCalendarUnit: NSCalendarUnit = 1 << 5;
+const NSMinuteCalendarUnit: NSCalendarUnit = 1 << 6;
+const NSSecondCalendarUnit: NSCalendarUnit = 1 << 7;
+const NSWeekCalendarUnit: NSCalendarUnit = 1 << 8;
+const NSWeekdayCalendarUnit: NSCalendarUnit = 1 << 9;
+const NSWeekdayOrdinalCalendarUnit: NSCalendarUnit = 1 << 10;
+
+/// Value of a component that hasn't been set. This is `NSIntegerMax`.
+const NSUndefinedDateComponent: NSInteger = 0x7fffffff;
+
+/// Number of seconds between the Unix epoch (1 January 1970) and the absolute
+/// reference date (1 January 2001).
+const REFERENCE_DATE_UNIX_SECONDS: i64 = 978307200;
+const MINUTE_SECONDS: i64 = 60;
+const HOUR_SECONDS: i64 = MINUTE_SECONDS * 60;
+const DAY_SECONDS: i64 = HOUR_SECONDS * 24;
+const WEEK_SECONDS: i64 = DAY_SECONDS * 7;
+
+#[derive(Copy, Clone)]
+struct NSDateComponentsHostObject {
+    era: NSInteger,
+    year: NSInteger,
+    month: NSInteger,
+    day: NSInteger,
+    hour: NSInteger,
+    minute: NSInteger,
+    second: NSInteger,
+    week: NSInteger,
+    weekday: NSInteger,
+    weekday_ordinal: NSInteger,
+}
+impl HostObject for NSDateComponentsHostObject {}
+impl Default for NSDateComponentsHostObject {
+    fn default() -> Self {
+        NSDateComponentsHostObject {
+            era: NSUndefinedDateComponent,
+            year: NSUndefinedDateComponent,
+            month: NSUndefinedDateComponent,
+            day: NSUndefinedDateComponent,
+            hour: NSUndefinedDateComponent,
+            minute: NSUndefinedDateComponent,
+            second: NSUndefinedDateComponent,
+            week: NSUndefinedDateComponent,
+            weekday: NSUndefinedDateComponent,
+            weekday_ordinal: NSUndefinedDateComponent,
+        }
+    }
+}
+
+/// Split a time interval relative to the absolute reference date into a
+/// calendar date and the whole seconds since the start of that day.
+fn split_time_interval(time_interval: NSTimeInterval) -> (CalendarDate, i64) {
+    let seconds = time_interval.floor() as i64 + REFERENCE_DATE_UNIX_SECONDS;
+    let days = seconds.div_euclid(DAY_SECONDS);
+    let second_in_day = seconds.rem_euclid(DAY_SECONDS);
+    (days_to_calendar_date(days as i32), second_in_day)
+}
+
+/// Seconds since the Unix epoch for the start of a day. See
+/// [calendar_date_to_days] for the meaning of the arguments.
+fn day_start_unix_seconds(year: i32, month: i32, day: i32) -> i64 {
+    i64::from(calendar_date_to_days(year, month, day)) * DAY_SECONDS
+}
+
+/// Add a number of months to a time interval relative to the absolute
+/// reference date. Like on a real device, the day of the month is clamped, so
+/// e.g. adding one month to 31 January gives 28 or 29 February.
+fn add_months(time_interval: NSTimeInterval, months: i32) -> NSTimeInterval {
+    let (date, second_in_day) = split_time_interval(time_interval);
+    let month = date.month + months;
+    let days_in_month =
+        calendar_date_to_days(date.year, month + 1, 0) - calendar_date_to_days(date.year, month, 0);
+    let day = date.day.min(days_in_month - 1);
+    let seconds = day_start_unix_seconds(date.year, month, day) + second_in_day;
+    // Keep the fractional part of the second.
+    (seconds - REFERENCE_DATE_UNIX_SECONDS) as NSTimeInterval
+        + (time_interval - time_interval.floor())
+}
+
+fn components_from_time_interval(
+    unit_flags: NSCalendarUnit,
+    time_interval: NSTimeInterval,
+) -> NSDateComponentsHostObject {
+    let (date, second_in_day) = split_time_interval(time_interval);
+    let mut components = NSDateComponentsHostObject::default();
+    let is_set = |unit| unit_flags & unit != 0;
+    if is_set(NSEraCalendarUnit) {
+        // AD = 1, BC = 0
+        components.era = (date.year > 0).into();
+    }
+    if is_set(NSYearCalendarUnit) {
+        // There's no year 0: 1 BC is followed by AD 1.
+        components.year = if date.year > 0 {
+            date.year
+        } else {
+            1 - date.year
+        };
+    }
+    if is_set(NSMonthCalendarUnit) {
+        components.month = date.month + 1;
+    }
+    if is_set(NSDayCalendarUnit) {
+        components.day = date.day + 1;
+    }
+    if is_set(NSHourCalendarUnit) {
+        components.hour = (second_in_day / HOUR_SECONDS) as NSInteger;
+    }
+    if is_set(NSMinuteCalendarUnit) {
+        components.minute = ((second_in_day % HOUR_SECONDS) / MINUTE_SECONDS) as NSInteger;
+    }
+    if is_set(NSSecondCalendarUnit) {
+        components.second = (second_in_day % MINUTE_SECONDS) as NSInteger;
+    }
+    if is_set(NSWeekCalendarUnit) {
+        // Weeks start on Sunday and the first week of the year is the one
+        // containing 1 January.
+        let first_day_weekday = (date.weekday - date.day_of_year).rem_euclid(7);
+        components.week = (date.day_of_year + first_day_weekday) / 7 + 1;
+    }
+    if is_set(NSWeekdayCalendarUnit) {
+        // Sunday = 1
+        components.weekday = date.weekday + 1;
+    }
+    if is_set(NSWeekdayOrdinalCalendarUnit) {
+        components.weekday_ordinal = date.day / 7 + 1;
+    }
+    components
+}
+
+fn time_interval_from_components(components: &NSDateComponentsHostObject) -> NSTimeInterval {
+    let get = |value: NSInteger, default: NSInteger| {
+        if value == NSUndefinedDateComponent {
+            default
+        } else {
+            value
+        }
+    };
+    if components.week != NSUndefinedDateComponent
+        || components.weekday != NSUndefinedDateComponent
+        || components.weekday_ordinal != NSUndefinedDateComponent
+    {
+        log!("TODO: week-based date components are ignored by dateFromComponents:");
+    }
+    let year = get(components.year, 1);
+    let year = if get(components.era, 1) == 0 {
+        1 - year
+    } else {
+        year
+    };
+    let day_start = day_start_unix_seconds(
+        year,
+        get(components.month, 1) - 1,
+        get(components.day, 1) - 1,
+    );
+    let seconds = day_start
+        + i64::from(get(components.hour, 0)) * HOUR_SECONDS
+        + i64::from(get(components.minute, 0)) * MINUTE_SECONDS
+        + i64::from(get(components.second, 0));
+    (seconds - REFERENCE_DATE_UNIX_SECONDS) as NSTimeInterval
+}
+
+/// Implementation of `components:fromDate:toDate:options:`.
+fn components_between(
+    unit_flags: NSCalendarUnit,
+    start: NSTimeInterval,
+    end: NSTimeInterval,
+) -> NSDateComponentsHostObject {
+    let mut components = NSDateComponentsHostObject::default();
+    let is_set = |unit| unit_flags & unit != 0;
+
+    // Years and months vary in length, so they have to be counted on the
+    // calendar. Only whole months are counted.
+    let mut months_used = 0;
+    if is_set(NSYearCalendarUnit) || is_set(NSMonthCalendarUnit) {
+        let (start_date, _) = split_time_interval(start);
+        let (end_date, _) = split_time_interval(end);
+        let mut months =
+            (end_date.year - start_date.year) * 12 + (end_date.month - start_date.month);
+        if months > 0 && add_months(start, months) > end {
+            months -= 1;
+        } else if months < 0 && add_months(start, months) < end {
+            months += 1;
+        }
+        if is_set(NSYearCalendarUnit) {
+            components.year = months / 12;
+            months_used = components.year * 12;
+        }
+        if is_set(NSMonthCalendarUnit) {
+            components.month = months - months_used;
+            months_used = months;
+        }
+    }
+
+    // The rest have fixed lengths.
+    let mut remainder = (end - add_months(start, months_used)).trunc() as i64;
+    for (unit, length, value) in [
+        (NSWeekCalendarUnit, WEEK_SECONDS, &mut components.week),
+        (NSDayCalendarUnit, DAY_SECONDS, &mut components.day),
+        (NSHourCalendarUnit, HOUR_SECONDS, &mut components.hour),
+        (NSMinuteCalendarUnit, MINUTE_SECONDS, &mut components.minute),
+        (NSSecondCalendarUnit, 1, &mut components.second),
+    ] {
+        if is_set(unit) {
+            *value = (remainder / length) as NSInteger;
+            remainder %= length;
+        }
+    }
+    components
+}
+
+fn new_date_components(env: &mut Environment, components: NSDateComponentsHostObject) -> id {
+    let new: id = msg_class![env; NSDateComponents new];
+    *env.objc.borrow_mut::<NSDateComponentsHostObject>(new) = components;
+    autorelease(env, new)
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSCalendar: NSObject
+
++ (id)currentCalendar {
+    let identifier = get_static_str(env, NSGregorianCalendar);
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithCalendarIdentifier:identifier];
+    autorelease(env, new)
+}
+
++ (id)autoupdatingCurrentCalendar {
+    msg![env; this currentCalendar]
+}
+
+- (id)initWithCalendarIdentifier:(id)identifier { // NSString*
+    let gregorian = get_static_str(env, NSGregorianCalendar);
+    if !msg![env; identifier isEqualToString:gregorian] {
+        log!("TODO: Unsupported calendar {:?}, returning nil", identifier);
+        release(env, this);
+        return nil;
+    }
+    this
+}
+
+- (id)calendarIdentifier {
+    get_static_str(env, NSGregorianCalendar)
+}
+
+- (NSUInteger)firstWeekday {
+    1 // Sunday
+}
+
+- (NSUInteger)minimumDaysInFirstWeek {
+    1
+}
+
+- (id)components:(NSCalendarUnit)unit_flags
+        fromDate:(id)date { // NSDate*
+    let time_interval: NSTimeInterval = msg![env; date timeIntervalSinceReferenceDate];
+    let components = components_from_time_interval(unit_flags, time_interval);
+    new_date_components(env, components)
+}
+
+- (id)components:(NSCalendarUnit)unit_flags
+        fromDate:(id)start_date // NSDate*
+          toDate:(id)end_date // NSDate*
+         options:(NSUInteger)_options {
+    let start: NSTimeInterval = msg![env; start_date timeIntervalSinceReferenceDate];
+    let end: NSTimeInterval = msg![env; end_date timeIntervalSinceReferenceDate];
+    let components = components_between(unit_flags, start, end);
+    new_date_components(env, components)
+}
+
+- (id)dateFromComponents:(id)components { // NSDateComponents*
+    let components = *env.objc.borrow::<NSDateComponentsHostObject>(components);
+    let time_interval = time_interval_from_components(&components);
+    msg_class![env; NSDate dateWithTimeIntervalSinceReferenceDate:time_interval]
+}
+
+- (id)dateByAddingComponents:(id)components // NSDateComponents*
+                      toDate:(id)date // NSDate*
+                     options:(NSUInteger)_options {
+    let components = *env.objc.borrow::<NSDateComponentsHostObject>(components);
+    let get = |value: NSInteger| {
+        if value == NSUndefinedDateComponent { 0 } else { value }
+    };
+    let time_interval: NSTimeInterval = msg![env; date timeIntervalSinceReferenceDate];
+    let time_interval = add_months(
+        time_interval,
+        get(components.year) * 12 + get(components.month),
+    );
+    let seconds = i64::from(get(components.week)) * WEEK_SECONDS
+        + i64::from(get(components.day)) * DAY_SECONDS
+        + i64::from(get(components.hour)) * HOUR_SECONDS
+        + i64::from(get(components.minute)) * MINUTE_SECONDS
+        + i64::from(get(components.second));
+    let time_interval = time_interval + seconds as NSTimeInterval;
+    msg_class![env; NSDate dateWithTimeIntervalSinceReferenceDate:time_interval]
+}
+
+@end
+
+@implementation NSDateComponents: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<NSDateComponentsHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (NSInteger)era {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).era
+}
+- (())setEra:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).era = value;
+}
+
+- (NSInteger)year {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).year
+}
+- (())setYear:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).year = value;
+}
+
+- (NSInteger)month {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).month
+}
+- (())setMonth:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).month = value;
+}
+
+- (NSInteger)day {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).day
+}
+- (())setDay:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).day = value;
+}
+
+- (NSInteger)hour {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).hour
+}
+- (())setHour:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).hour = value;
+}
+
+- (NSInteger)minute {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).minute
+}
+- (())setMinute:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).minute = value;
+}
+
+- (NSInteger)second {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).second
+}
+- (())setSecond:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).second = value;
+}
+
+- (NSInteger)week {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).week
+}
+- (())setWeek:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).week = value;
+}
+
+- (NSInteger)weekday {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).weekday
+}
+- (())setWeekday:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).weekday = value;
+}
+
+- (NSInteger)weekdayOrdinal {
+    env.objc.borrow::<NSDateComponentsHostObject>(this).weekday_ordinal
+}
+- (())setWeekdayOrdinal:(NSInteger)value {
+    env.objc.borrow_mut::<NSDateComponentsHostObject>(this).weekday_ordinal = value;
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    let components = *env.objc.borrow::<NSDateComponentsHostObject>(this);
+    let new: id = msg_class![env; NSDateComponents new];
+    *env.objc.borrow_mut::<NSDateComponentsHostObject>(new) = components;
+    new
+}
+
+@end
+
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL_UNITS: NSCalendarUnit = NSEraCalendarUnit
+        | NSYearCalendarUnit
+        | NSMonthCalendarUnit
+        | NSDayCalendarUnit
+        | NSHourCalendarUnit
+        | NSMinuteCalendarUnit
+        | NSSecondCalendarUnit;
+
+    #[test]
+    fn components_round_trip() {
+        // 2010-03-04 05:06:07 UTC
+        let time_interval = 289371967.0;
+        let components = components_from_time_interval(ALL_UNITS, time_interval);
+        assert_eq!(
+            (components.year, components.month, components.day),
+            (2010, 3, 4)
+        );
+        assert_eq!(
+            (components.hour, components.minute, components.second),
+            (5, 6, 7)
+        );
+        assert_eq!(time_interval_from_components(&components), time_interval);
+    }
+
+    #[test]
+    fn month_arithmetic() {
+        // 2011-01-31 00:00:00 UTC + 1 month is 2011-02-28
+        let jan_31 = 318124800.0;
+        let feb_28 = add_months(jan_31, 1);
+        let components = components_from_time_interval(ALL_UNITS, feb_28);
+        assert_eq!((components.month, components.day), (2, 28));
+        // That's less than a whole month in the other direction, though.
+        let between = components_between(NSMonthCalendarUnit | NSDayCalendarUnit, feb_28, jan_31);
+        assert_eq!((between.month, between.day), (0, -28));
+    }
+}
@@ -5,38 +5,148 @@
  */
 //! `NSDate`.
 
-use super::NSTimeInterval;
+use super::{
+    NSComparisonResult, NSOrderedAscending, NSOrderedDescending, NSOrderedSame, NSTimeInterval,
+    NSUInteger,
+};
 use crate::frameworks::core_foundation::time::apple_epoch;
-use crate::objc::{autorelease, id, objc_classes, ClassExports, HostObject};
+use crate::frameworks::foundation::ns_string::get_static_str;
+use crate::objc::{
+    autorelease, id, msg, msg_class, objc_classes, retain, Class, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
 
 use std::time::SystemTime;
 
+/// Number of seconds between the Unix epoch (1 January 1970) and the absolute
+/// reference date (1 January 2001).
+const NSTimeIntervalSince1970: NSTimeInterval = 978307200.0;
+
+/// Values used by Apple for `distantFuture` (the year 4001) and `distantPast`
+/// (the year 1 BC).
+const DISTANT_FUTURE: NSTimeInterval = 63113904000.0;
+const DISTANT_PAST: NSTimeInterval = -63114076800.0;
+
 struct NSDateHostObject {
+    /// Seconds relative to the absolute reference date. This is signed, so
+    /// that dates before 2001 can be represented.
     time_interval: NSTimeInterval,
 }
 impl HostObject for NSDateHostObject {}
 
+/// Convert a host time to an interval relative to the absolute reference date.
+/// Unlike [SystemTime::duration_since], this won't panic if the time is before
+/// the reference date.
+fn time_interval_from_system_time(time: SystemTime) -> NSTimeInterval {
+    match time.duration_since(apple_epoch()) {
+        Ok(duration) => duration.as_secs_f64(),
+        Err(err) => -err.duration().as_secs_f64(),
+    }
+}
+
+/// The current time as an interval relative to the absolute reference date.
+fn now() -> NSTimeInterval {
+    time_interval_from_system_time(SystemTime::now())
+}
+
+/// Create a new `NSDate` (retained) from a host time, e.g. one from a property
+/// list.
+pub fn from_system_time(env: &mut Environment, time: SystemTime) -> id {
+    let time_interval = time_interval_from_system_time(time);
+    let new: id = msg_class![env; NSDate alloc];
+    msg![env; new initWithTimeIntervalSinceReferenceDate:time_interval]
+}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
 
 @implementation NSDate: NSObject
 
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSDateHostObject { time_interval: 0.0 });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
 + (id)date {
     // "Date objects are immutable, representing an invariant time interval
     // relative to an absolute reference date (00:00:00 UTC on 1 January 2001)."
-    let time_interval = SystemTime::now()
-        .duration_since(apple_epoch())
-        .unwrap()
-        .as_secs_f64();
-    let host_object = Box::new(NSDateHostObject {
-        time_interval
-    });
-    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new init];
+    let time_interval = env.objc.borrow::<NSDateHostObject>(new).time_interval;
     log_dbg!("[NSDate date] => {:?} ({:?}s)", new, time_interval);
     autorelease(env, new)
 }
 
++ (id)dateWithTimeIntervalSinceNow:(NSTimeInterval)seconds {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithTimeIntervalSinceNow:seconds];
+    autorelease(env, new)
+}
+
++ (id)dateWithTimeIntervalSince1970:(NSTimeInterval)seconds {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithTimeIntervalSince1970:seconds];
+    autorelease(env, new)
+}
+
++ (id)dateWithTimeIntervalSinceReferenceDate:(NSTimeInterval)seconds {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithTimeIntervalSinceReferenceDate:seconds];
+    autorelease(env, new)
+}
+
++ (id)distantFuture {
+    msg![env; this dateWithTimeIntervalSinceReferenceDate:DISTANT_FUTURE]
+}
+
++ (id)distantPast {
+    msg![env; this dateWithTimeIntervalSinceReferenceDate:DISTANT_PAST]
+}
+
++ (NSTimeInterval)timeIntervalSinceReferenceDate {
+    now()
+}
+
+- (id)init {
+    msg![env; this initWithTimeIntervalSinceReferenceDate:(now())]
+}
+
+- (id)initWithTimeIntervalSinceReferenceDate:(NSTimeInterval)seconds {
+    env.objc.borrow_mut::<NSDateHostObject>(this).time_interval = seconds;
+    this
+}
+
+- (id)initWithTimeIntervalSinceNow:(NSTimeInterval)seconds {
+    msg![env; this initWithTimeIntervalSinceReferenceDate:(now() + seconds)]
+}
+
+- (id)initWithTimeIntervalSince1970:(NSTimeInterval)seconds {
+    let seconds = seconds - NSTimeIntervalSince1970;
+    msg![env; this initWithTimeIntervalSinceReferenceDate:seconds]
+}
+
+- (id)initWithTimeInterval:(NSTimeInterval)seconds
+                 sinceDate:(id)date {
+    let base: NSTimeInterval = msg![env; date timeIntervalSinceReferenceDate];
+    msg![env; this initWithTimeIntervalSinceReferenceDate:(base + seconds)]
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    // Dates are immutable.
+    retain(env, this)
+}
+
+// NSCoding implementation
+- (id)initWithCoder:(id)coder {
+    // NSKeyedArchiver stores the interval since the reference date as-is.
+    let key = get_static_str(env, "NS.time");
+    let seconds: NSTimeInterval = msg![env; coder decodeDoubleForKey:key];
+    msg![env; this initWithTimeIntervalSinceReferenceDate:seconds]
+}
+
 - (NSTimeInterval)timeIntervalSinceDate:(id)anotherDate {
     assert!(!anotherDate.is_null());
     let host_object = env.objc.borrow::<NSDateHostObject>(this);
@@ -50,6 +160,98 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow::<NSDateHostObject>(this).time_interval
 }
 
+- (NSTimeInterval)timeIntervalSinceNow {
+    env.objc.borrow::<NSDateHostObject>(this).time_interval - now()
+}
+
+- (NSTimeInterval)timeIntervalSince1970 {
+    env.objc.borrow::<NSDateHostObject>(this).time_interval + NSTimeIntervalSince1970
+}
+
+- (id)dateByAddingTimeInterval:(NSTimeInterval)seconds {
+    let time_interval = env.objc.borrow::<NSDateHostObject>(this).time_interval;
+    let class: Class = msg![env; this class];
+    msg![env; class dateWithTimeIntervalSinceReferenceDate:(time_interval + seconds)]
+}
+
+// Deprecated predecessor of dateByAddingTimeInterval:
+- (id)addTimeInterval:(NSTimeInterval)seconds {
+    msg![env; this dateByAddingTimeInterval:seconds]
+}
+
+- (NSComparisonResult)compare:(id)other {
+    assert!(!other.is_null());
+    let a = env.objc.borrow::<NSDateHostObject>(this).time_interval;
+    let b = env.objc.borrow::<NSDateHostObject>(other).time_interval;
+    if a < b {
+        NSOrderedAscending
+    } else if a > b {
+        NSOrderedDescending
+    } else {
+        NSOrderedSame
+    }
+}
+
+- (bool)isEqualToDate:(id)other {
+    if other.is_null() {
+        return false;
+    }
+    let result: NSComparisonResult = msg![env; this compare:other];
+    result == NSOrderedSame
+}
+
+- (bool)isEqual:(id)other {
+    if other.is_null() {
+        return false;
+    }
+    let class = env.objc.get_known_class("NSDate", &mut env.mem);
+    let other_class: Class = msg![env; other class];
+    if !env.objc.class_is_subclass_of(other_class, class) {
+        return false;
+    }
+    msg![env; this isEqualToDate:other]
+}
+
+- (NSUInteger)hash {
+    let time_interval = env.objc.borrow::<NSDateHostObject>(this).time_interval;
+    super::hash_helper(&time_interval.to_bits())
+}
+
+- (id)earlierDate:(id)other {
+    let result: NSComparisonResult = msg![env; this compare:other];
+    if result == NSOrderedDescending { other } else { this }
+}
+
+- (id)laterDate:(id)other {
+    let result: NSComparisonResult = msg![env; this compare:other];
+    if result == NSOrderedAscending { other } else { this }
+}
+
 @end
 
 };
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn system_time_round_trip() {
+        for time_interval in [0.0, 0.0005, 123456789.123456, -123456789.987654] {
+            let duration = Duration::from_secs_f64(time_interval.abs());
+            let time = if time_interval >= 0.0 {
+                apple_epoch() + duration
+            } else {
+                apple_epoch() - duration
+            };
+            let round_tripped = time_interval_from_system_time(time);
+            assert!(
+                (round_tripped - time_interval).abs() < 1e-6,
+                "{} != {}",
+                round_tripped,
+                time_interval
+            );
+        }
+    }
+}
@@ -31,7 +31,14 @@ pub(super) struct DictionaryHostObject {
     map: HashMap<Hash, Vec<(id, id)>>,
     pub(super) count: NSUInteger,
 }
-impl HostObject for DictionaryHostObject {}
+impl HostObject for DictionaryHostObject {
+    fn strong_references(&self, references: &mut Vec<id>) {
+        for &(key, value) in self.map.values().flatten() {
+            references.push(key);
+            references.push(value);
+        }
+    }
+}
 impl DictionaryHostObject {
     pub(super) fn lookup(&self, env: &mut Environment, key: id) -> id {
         let hash: Hash = msg![env; key hash];
@@ -64,12 +71,37 @@ impl DictionaryHostObject {
             if candidate_key == key || msg![env; candidate_key isEqualTo:key] {
                 release(env, *existing_value);
                 *existing_value = value;
+                // The existing key is kept.
+                release(env, key);
                 return;
             }
         }
         collisions.push((key, value));
         self.count += 1;
     }
+    pub(super) fn remove(&mut self, env: &mut Environment, key: id) {
+        let hash: Hash = msg![env; key hash];
+        let Some(collisions) = self.map.get_mut(&hash) else {
+            return;
+        };
+        let mut found = None;
+        for (i, &(candidate_key, _value)) in collisions.iter().enumerate() {
+            if candidate_key == key || msg![env; candidate_key isEqualTo:key] {
+                found = Some(i);
+                break;
+            }
+        }
+        let Some(i) = found else {
+            return;
+        };
+        let (existing_key, existing_value) = collisions.remove(i);
+        if collisions.is_empty() {
+            self.map.remove(&hash);
+        }
+        self.count -= 1;
+        release(env, existing_key);
+        release(env, existing_value);
+    }
     pub(super) fn release(&mut self, env: &mut Environment) {
         for collisions in self.map.values() {
             for &(key, value) in collisions {
@@ -190,6 +222,40 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @end
 
+// NSMutableDictionary is an abstract class. A subclass must provide everything
+// NSDictionary provides, plus:
+// - (void)setObject:(id)object forKey:(id)key;
+// - (void)removeObjectForKey:(id)key;
+// Note that it inherits from NSDictionary, so we must ensure we override any
+// default methods that would be inappropriate for mutability.
+@implementation NSMutableDictionary: NSDictionary
+
++ (id)allocWithZone:(NSZonePtr)zone {
+    // NSDictionary might be subclassed by something which needs allocWithZone:
+    // to have the normal behaviour. Unimplemented: call superclass alloc then.
+    assert!(this == env.objc.get_known_class("NSMutableDictionary", &mut env.mem));
+    msg_class![env; _touchHLE_NSMutableDictionary allocWithZone:zone]
+}
+
++ (id)dictionaryWithCapacity:(NSUInteger)_capacity {
+    msg![env; this dictionary]
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    let keys_and_objects: Vec<(id, id)> = env
+        .objc
+        .borrow::<DictionaryHostObject>(this)
+        .map
+        .values()
+        .flatten()
+        .copied()
+        .collect();
+    dict_from_keys_and_objects(env, &keys_and_objects)
+}
+
+@end
+
 // Our private subclass that is the single implementation of NSDictionary for
 // the time being.
 @implementation _touchHLE_NSDictionary: NSDictionary
@@ -228,6 +294,57 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @end
 
+// Our private subclass that is the single implementation of NSMutableDictionary
+// for the time being.
+@implementation _touchHLE_NSMutableDictionary: NSMutableDictionary
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<DictionaryHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (())dealloc {
+    std::mem::take(env.objc.borrow_mut::<DictionaryHostObject>(this)).release(env);
+
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)init {
+    *env.objc.borrow_mut(this) = <DictionaryHostObject as Default>::default();
+    this
+}
+- (id)initWithCapacity:(NSUInteger)_capacity {
+    msg![env; this init]
+}
+
+// TODO: enumeration, more init methods, etc
+
+- (NSUInteger)count {
+    env.objc.borrow::<DictionaryHostObject>(this).count
+}
+- (id)objectForKey:(id)key {
+    let host_obj: DictionaryHostObject = std::mem::take(env.objc.borrow_mut(this));
+    let res = host_obj.lookup(env, key);
+    *env.objc.borrow_mut(this) = host_obj;
+    res
+}
+
+- (())setObject:(id)object
+         forKey:(id)key {
+    assert!(key != nil); // TODO: raise proper exception
+    assert!(object != nil); // TODO: raise proper exception
+    let mut host_obj: DictionaryHostObject = std::mem::take(env.objc.borrow_mut(this));
+    host_obj.insert(env, key, object, /* copy_key: */ true);
+    *env.objc.borrow_mut(this) = host_obj;
+}
+- (())removeObjectForKey:(id)key {
+    let mut host_obj: DictionaryHostObject = std::mem::take(env.objc.borrow_mut(this));
+    host_obj.remove(env, key);
+    *env.objc.borrow_mut(this) = host_obj;
+}
+
+@end
+
 };
 
 /// Direct constructor for use by host code, similar to
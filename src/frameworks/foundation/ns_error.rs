@@ -57,7 +57,20 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
-pub const CONSTANTS: ConstantExports = &[(
-    "_NSLocalizedDescriptionKey",
-    HostConstant::NSString("NSLocalizedDescriptionKey"),
-)];
+pub const NSPOSIXErrorDomain: &str = "NSPOSIXErrorDomain";
+pub const NSUnderlyingErrorKey: &str = "NSUnderlyingError";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_NSLocalizedDescriptionKey",
+        HostConstant::NSString("NSLocalizedDescriptionKey"),
+    ),
+    (
+        "_NSPOSIXErrorDomain",
+        HostConstant::NSString(NSPOSIXErrorDomain),
+    ),
+    (
+        "_NSUnderlyingErrorKey",
+        HostConstant::NSString(NSUnderlyingErrorKey),
+    ),
+];
@@ -3,8 +3,19 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
+//! `NSException`.
+//!
+//! touchHLE can't unwind the guest's stack, so an exception can't be caught:
+//! raising one stops emulation, much like an uncaught exception terminates the
+//! app on a real device.
 
+use super::ns_string;
 use crate::dyld::{ConstantExports, HostConstant};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
 
 // All constants are NSExceptionName
 pub const CONSTANTS: ConstantExports = &[
@@ -273,3 +284,99 @@ pub const CONSTANTS: ConstantExports = &[
         HostConstant::NSString("UIApplicationInvalidInterfaceOrientationException"),
     ),
 ];
+
+struct NSExceptionHostObject {
+    /// `NSString*`
+    name: id,
+    /// `NSString*`
+    reason: id,
+    /// `NSDictionary*`
+    user_info: id,
+}
+impl HostObject for NSExceptionHostObject {}
+
+/// Shortcut for host code: create and raise an exception. `name` should be one
+/// of the constants above, e.g. `"NSFileHandleOperationException"`.
+pub fn raise(env: &mut Environment, name: &'static str, reason: String, user_info: id) -> ! {
+    let name = ns_string::get_static_str(env, name);
+    let reason = ns_string::from_rust_string(env, reason);
+    let reason = autorelease(env, reason);
+    let exception: id = msg_class![env; NSException exceptionWithName:name
+                                                               reason:reason
+                                                             userInfo:user_info];
+    () = msg![env; exception raise];
+    unreachable!()
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSException: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSExceptionHostObject {
+        name: nil,
+        reason: nil,
+        user_info: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)exceptionWithName:(id)name // NSExceptionName
+                 reason:(id)reason // NSString*
+               userInfo:(id)user_info { // NSDictionary*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithName:name reason:reason userInfo:user_info];
+    autorelease(env, new)
+}
+
+- (id)initWithName:(id)name // NSExceptionName
+            reason:(id)reason // NSString*
+          userInfo:(id)user_info { // NSDictionary*
+    let name: id = msg![env; name copy];
+    let reason: id = msg![env; reason copy];
+    retain(env, user_info);
+    let host_object = env.objc.borrow_mut::<NSExceptionHostObject>(this);
+    host_object.name = name;
+    host_object.reason = reason;
+    host_object.user_info = user_info;
+    this
+}
+
+- (())dealloc {
+    let &NSExceptionHostObject { name, reason, user_info } = env.objc.borrow(this);
+    release(env, name);
+    release(env, reason);
+    release(env, user_info);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)name {
+    env.objc.borrow::<NSExceptionHostObject>(this).name
+}
+- (id)reason {
+    env.objc.borrow::<NSExceptionHostObject>(this).reason
+}
+- (id)userInfo {
+    env.objc.borrow::<NSExceptionHostObject>(this).user_info
+}
+
+- (())raise {
+    let &NSExceptionHostObject { name, reason, user_info } = env.objc.borrow(this);
+    let name = ns_string::to_rust_string(env, name);
+    let reason = if reason == nil {
+        "(no reason)".into()
+    } else {
+        ns_string::to_rust_string(env, reason)
+    };
+    // TODO: Unwind the guest stack to the nearest handler, if there is one.
+    panic!(
+        "Uncaught exception {:?} raised by app: {} (userInfo: {:?})",
+        name, reason, user_info
+    );
+}
+
+@end
+
+};
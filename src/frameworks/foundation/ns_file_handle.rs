@@ -6,18 +6,175 @@
 
 //! `NSFileHandle`.
 
-use super::ns_string;
-use super::NSUInteger;
+use super::ns_dictionary::dict_from_keys_and_objects;
+use super::ns_error::{NSPOSIXErrorDomain, NSUnderlyingErrorKey};
+use super::{ns_exception, ns_run_loop};
+use super::{ns_string, NSInteger, NSUInteger};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::libc::errno::{get_errno, EBADF, ENOSPC};
 use crate::libc::posix_io;
-use crate::mem::{ConstPtr, ConstVoidPtr};
-use crate::objc::{autorelease, id, nil, objc_classes, ClassExports, HostObject};
-use crate::{msg, msg_class};
+use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize};
+use crate::objc::{autorelease, id, nil, objc_classes, release, Class, ClassExports, HostObject};
+use crate::{msg, msg_class, Environment};
+
+pub const NSFileHandleReadCompletionNotification: &str = "NSFileHandleReadCompletionNotification";
+pub const NSFileHandleReadToEndOfFileCompletionNotification: &str =
+    "NSFileHandleReadToEndOfFileCompletionNotification";
+pub const NSFileHandleNotificationDataItem: &str = "NSFileHandleNotificationDataItem";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_NSFileHandleReadCompletionNotification",
+        HostConstant::NSString(NSFileHandleReadCompletionNotification),
+    ),
+    (
+        "_NSFileHandleReadToEndOfFileCompletionNotification",
+        HostConstant::NSString(NSFileHandleReadToEndOfFileCompletionNotification),
+    ),
+    (
+        "_NSFileHandleNotificationDataItem",
+        HostConstant::NSString(NSFileHandleNotificationDataItem),
+    ),
+];
 
 struct NSFileHandleHostObject {
-    fd: posix_io::FileDescriptor,
+    /// [None] for the null device.
+    fd: Option<posix_io::FileDescriptor>,
+    /// Set by `closeFile`.
+    is_closed: bool,
+    /// The standard output and error handles don't own their descriptors.
+    close_on_dealloc: bool,
 }
 impl HostObject for NSFileHandleHostObject {}
 
+fn new_file_handle(
+    env: &mut Environment,
+    class: Class,
+    fd: Option<posix_io::FileDescriptor>,
+    close_on_dealloc: bool,
+) -> id {
+    let host_object = Box::new(NSFileHandleHostObject {
+        fd,
+        is_closed: false,
+        close_on_dealloc,
+    });
+    let new = env.objc.alloc_object(class, host_object, &mut env.mem);
+    autorelease(env, new)
+}
+
+/// Raise `NSFileHandleOperationException` for a failed operation. The `errno`
+/// is in the `userInfo`, as an `NSError` for the `NSUnderlyingErrorKey` key.
+fn raise_operation_exception(env: &mut Environment, operation: &str, errno: i32) -> ! {
+    let domain = ns_string::get_static_str(env, NSPOSIXErrorDomain);
+    let error: id = msg_class![env; NSError alloc];
+    let error: id = msg![env; error initWithDomain:domain
+                                              code:(errno as NSInteger)
+                                          userInfo:nil];
+    let key = ns_string::get_static_str(env, NSUnderlyingErrorKey);
+    let user_info = dict_from_keys_and_objects(env, &[(key, error)]);
+    release(env, error);
+    let user_info = autorelease(env, user_info);
+    ns_exception::raise(
+        env,
+        "NSFileHandleOperationException",
+        format!("*** {} failed (errno {})", operation, errno),
+        user_info,
+    )
+}
+
+/// Get the file descriptor, or [None] for the null device.
+fn get_fd(env: &mut Environment, handle: id) -> Option<posix_io::FileDescriptor> {
+    let host_object = env.objc.borrow::<NSFileHandleHostObject>(handle);
+    if host_object.is_closed {
+        raise_operation_exception(env, "Use of NSFileHandle after closeFile", EBADF);
+    }
+    host_object.fd
+}
+
+/// Read up to `length` bytes from the current position into a new `NSData*`.
+/// Reading stops early at the end of the file.
+fn read_data(env: &mut Environment, fd: posix_io::FileDescriptor, length: NSUInteger) -> id {
+    // Avoid allocating more than is needed (`length` could be e.g. the maximum
+    // NSUInteger value, which apps use to mean "read everything").
+    let cur_pos = posix_io::lseek(env, fd, 0, posix_io::SEEK_CUR);
+    let end_pos = posix_io::lseek(env, fd, 0, posix_io::SEEK_END);
+    if cur_pos == -1 || end_pos == -1 || posix_io::lseek(env, fd, cur_pos, posix_io::SEEK_SET) == -1
+    {
+        let errno = get_errno(env);
+        raise_operation_exception(env, "Getting size of file for reading", errno);
+    }
+    let remaining: NSUInteger = (end_pos - cur_pos).max(0).try_into().unwrap();
+    let length = length.min(remaining);
+
+    if length == 0 {
+        return msg_class![env; NSData data];
+    }
+
+    let buffer = env.mem.alloc(length);
+    let mut total_read: NSUInteger = 0;
+    while total_read < length {
+        let bytes_read = posix_io::read(
+            env,
+            fd,
+            (buffer.cast::<u8>() + total_read).cast(),
+            length - total_read,
+        );
+        match bytes_read {
+            -1 => {
+                env.mem.free(buffer);
+                let errno = get_errno(env);
+                raise_operation_exception(env, "Reading from file", errno);
+            }
+            0 => break,
+            bytes_read => total_read += NSUInteger::try_from(bytes_read).unwrap(),
+        }
+    }
+    msg_class![env; NSData dataWithBytesNoCopy:buffer length:total_read]
+}
+
+/// Implementation of the `...InBackgroundAndNotify` methods. Reads can't block
+/// for the kinds of file currently supported, so the read happens immediately,
+/// but the notification is posted from the run loop like on a real device.
+fn read_in_background_and_notify(
+    env: &mut Environment,
+    handle: id,
+    notification_name: &'static str,
+) {
+    let data = match get_fd(env, handle) {
+        Some(fd) => read_data(env, fd, NSUInteger::MAX),
+        None => {
+            let data: id = msg_class![env; NSData new];
+            autorelease(env, data)
+        }
+    };
+    log_dbg!(
+        "Read {:?} from {:?} in background, posting {} later",
+        data,
+        handle,
+        notification_name
+    );
+
+    let key = ns_string::get_static_str(env, NSFileHandleNotificationDataItem);
+    let user_info = dict_from_keys_and_objects(env, &[(key, data)]);
+    let name = ns_string::get_static_str(env, notification_name);
+    let notification: id = msg_class![env; NSNotification notificationWithName:name
+                                                                       object:handle
+                                                                     userInfo:user_info];
+    release(env, user_info);
+
+    if env.current_thread != 0 {
+        // TODO: run loops for other threads
+        log!(
+            "TODO: {} requested on thread {}, but only the main thread has a run loop. Posting on the main thread instead.",
+            notification_name,
+            env.current_thread
+        );
+    }
+    let center: id = msg_class![env; NSNotificationCenter defaultCenter];
+    let selector = env.objc.lookup_selector("postNotification:").unwrap();
+    ns_run_loop::queue_main_thread_perform(env, center, selector, notification);
+}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -29,13 +186,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     let path_str: ConstPtr<u8> = msg![env; path UTF8String];
     match posix_io::open_direct(env, path_str, posix_io::O_RDONLY) {
         -1 => nil,
-        fd => {
-            let host_object = Box::new(NSFileHandleHostObject {
-                fd
-            });
-            let new = env.objc.alloc_object(this, host_object, &mut env.mem);
-            autorelease(env, new)
-        },
+        fd => new_file_handle(env, this, Some(fd), /* close_on_dealloc: */ true),
     }
 }
 
@@ -44,13 +195,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     let path_str: ConstPtr<u8> = msg![env; path UTF8String];
     match posix_io::open_direct(env, path_str, posix_io::O_WRONLY) {
         -1 => nil,
-        fd => {
-            let host_object = Box::new(NSFileHandleHostObject {
-                fd
-            });
-            let new = env.objc.alloc_object(this, host_object, &mut env.mem);
-            autorelease(env, new)
-        },
+        fd => new_file_handle(env, this, Some(fd), /* close_on_dealloc: */ true),
     }
 }
 
@@ -59,71 +204,175 @@ pub const CLASSES: ClassExports = objc_classes! {
     let path_str: ConstPtr<u8> = msg![env; path UTF8String];
     match posix_io::open_direct(env, path_str, posix_io::O_RDWR) {
         -1 => nil,
-        fd => {
-            let host_object = Box::new(NSFileHandleHostObject {
-                fd
-            });
-            let new = env.objc.alloc_object(this, host_object, &mut env.mem);
-            autorelease(env, new)
-        },
+        fd => new_file_handle(env, this, Some(fd), /* close_on_dealloc: */ true),
     }
 }
 
++ (id)fileHandleWithStandardOutput {
+    new_file_handle(env, this, Some(posix_io::STDOUT_FILENO), /* close_on_dealloc: */ false)
+}
+
++ (id)fileHandleWithStandardError {
+    new_file_handle(env, this, Some(posix_io::STDERR_FILENO), /* close_on_dealloc: */ false)
+}
+
++ (id)fileHandleWithNullDevice {
+    new_file_handle(env, this, None, /* close_on_dealloc: */ false)
+}
+
 - (i64)offsetInFile {
-    let fd = env.objc.borrow::<NSFileHandleHostObject>(this).fd;
+    let Some(fd) = get_fd(env, this) else {
+        return 0;
+    };
     match posix_io::lseek(env, fd, 0, posix_io::SEEK_CUR) {
-        -1 => panic!("offsetInFile failed"),
+        -1 => {
+            let errno = get_errno(env);
+            raise_operation_exception(env, "offsetInFile", errno);
+        }
         // TODO: What's the correct behaviour if the position is beyond 2GiB?
         cur_pos => cur_pos,
     }
 }
 
 - (())seekToFileOffset:(i64)offset {
-    let fd = env.objc.borrow::<NSFileHandleHostObject>(this).fd;
-    match posix_io::lseek(env, fd, offset, posix_io::SEEK_SET) {
-        -1 => panic!("seekToFileOffset: failed"),
-        _cur_pos => (),
+    let Some(fd) = get_fd(env, this) else {
+        return;
+    };
+    if posix_io::lseek(env, fd, offset, posix_io::SEEK_SET) == -1 {
+        let errno = get_errno(env);
+        raise_operation_exception(env, "seekToFileOffset:", errno);
     }
 }
 
 - (i64)seekToEndOfFile {
-    let fd = env.objc.borrow::<NSFileHandleHostObject>(this).fd;
+    let Some(fd) = get_fd(env, this) else {
+        return 0;
+    };
     match posix_io::lseek(env, fd, 0, posix_io::SEEK_END) {
-        -1 => panic!("seekToFileOffset: failed"),
+        -1 => {
+            let errno = get_errno(env);
+            raise_operation_exception(env, "seekToEndOfFile", errno);
+        }
         cur_pos => cur_pos,
     }
 }
 
-- (id)readDataOfLength:(NSUInteger)length { // NSData*
-    let fd = env.objc.borrow::<NSFileHandleHostObject>(this).fd;
-    let buffer = env.mem.alloc(length);
-    match posix_io::read(env, fd, buffer, length) {
-        -1 => panic!("readDataOfLength: failed"),
-        bytes_read => {
-            assert_eq!(length, bytes_read.try_into().unwrap());
-            msg_class![env; NSData dataWithBytesNoCopy:buffer length:length]
-        }
+- (())truncateFileAtOffset:(i64)offset {
+    let Some(fd) = get_fd(env, this) else {
+        return;
+    };
+    // The file pointer is moved to the new end of the file.
+    if posix_io::ftruncate(env, fd, offset) == -1
+        || posix_io::lseek(env, fd, offset, posix_io::SEEK_SET) == -1
+    {
+        let errno = get_errno(env);
+        raise_operation_exception(env, "truncateFileAtOffset:", errno);
     }
 }
 
+- (id)readDataOfLength:(NSUInteger)length { // NSData*
+    let Some(fd) = get_fd(env, this) else {
+        return msg_class![env; NSData data];
+    };
+    let data = read_data(env, fd, length);
+    log_dbg!("[{:?} readDataOfLength:{}] => {:?}", this, length, data);
+    data
+}
+
+- (id)readDataToEndOfFile { // NSData*
+    let Some(fd) = get_fd(env, this) else {
+        return msg_class![env; NSData data];
+    };
+    let data = read_data(env, fd, NSUInteger::MAX);
+    log_dbg!("[{:?} readDataToEndOfFile] => {:?}", this, data);
+    data
+}
+
+// For regular files, this is the same as readDataToEndOfFile.
+// TODO: pipes, sockets etc
+- (id)availableData { // NSData*
+    let Some(fd) = get_fd(env, this) else {
+        return msg_class![env; NSData data];
+    };
+    let data = read_data(env, fd, NSUInteger::MAX);
+    log_dbg!("[{:?} availableData] => {:?}", this, data);
+    data
+}
+
+- (())readInBackgroundAndNotify {
+    // For regular files, all the data is available, as with availableData.
+    // TODO: pipes, sockets etc
+    read_in_background_and_notify(env, this, NSFileHandleReadCompletionNotification);
+}
+- (())readInBackgroundAndNotifyForModes:(id)_modes { // NSArray<NSRunLoopMode>*
+    // TODO: run loop modes
+    read_in_background_and_notify(env, this, NSFileHandleReadCompletionNotification);
+}
+
+- (())readToEndOfFileInBackgroundAndNotify {
+    read_in_background_and_notify(env, this, NSFileHandleReadToEndOfFileCompletionNotification);
+}
+- (())readToEndOfFileInBackgroundAndNotifyForModes:(id)_modes { // NSArray<NSRunLoopMode>*
+    // TODO: run loop modes
+    read_in_background_and_notify(env, this, NSFileHandleReadToEndOfFileCompletionNotification);
+}
+
 - (())writeData:(id)data { // NSData *
-    let fd = env.objc.borrow::<NSFileHandleHostObject>(this).fd;
+    let Some(fd) = get_fd(env, this) else {
+        return;
+    };
     let bytes: ConstVoidPtr = msg![env; data bytes];
     let length: NSUInteger = msg![env; data length];
-    if posix_io::write(env, fd, bytes, length) == -1 {
-        panic!("writeData: failed")
+    // Writing past the end of the file grows it. A short write means the disk
+    // is full, and the rest of the data can't be written either.
+    let mut total_written: GuestUSize = 0;
+    while total_written < length {
+        let bytes_written = posix_io::write(
+            env,
+            fd,
+            (bytes.cast::<u8>() + total_written).cast(),
+            length - total_written,
+        );
+        match bytes_written {
+            -1 => {
+                let errno = get_errno(env);
+                raise_operation_exception(env, "writeData:", errno);
+            }
+            0 => raise_operation_exception(env, "writeData:", ENOSPC),
+            bytes_written => total_written += GuestUSize::try_from(bytes_written).unwrap(),
+        }
+    }
+}
+
+- (())synchronizeFile {
+    let Some(fd) = get_fd(env, this) else {
+        return;
+    };
+    log_dbg!("[{:?} synchronizeFile]", this);
+    if posix_io::fsync(env, fd) == -1 {
+        let errno = get_errno(env);
+        raise_operation_exception(env, "synchronizeFile", errno);
     }
 }
 
 - (())closeFile {
-    // file is closed on dealloc
-    // TODO: keep closed state and raise an exception
-    // if handle is used after the closing
+    let host_object = env.objc.borrow_mut::<NSFileHandleHostObject>(this);
+    // Closing twice is harmless.
+    if host_object.is_closed {
+        return;
+    }
+    host_object.is_closed = true;
+    let fd = host_object.fd;
+    if let Some(fd) = fd {
+        posix_io::close(env, fd);
+    }
 }
 
 - (())dealloc {
-    let fd = env.objc.borrow::<NSFileHandleHostObject>(this).fd;
-    posix_io::close(env, fd);
+    let &NSFileHandleHostObject { fd, is_closed, close_on_dealloc } = env.objc.borrow(this);
+    if let (Some(fd), false, true) = (fd, is_closed, close_on_dealloc) {
+        posix_io::close(env, fd);
+    }
     env.objc.dealloc_object(this, &mut env.mem)
 }
 
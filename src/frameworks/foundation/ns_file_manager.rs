@@ -5,9 +5,11 @@
  */
 //! `NSFileManager` etc.
 
+use super::ns_dictionary::dict_from_keys_and_objects;
 use super::{ns_array, ns_string, NSUInteger};
-use crate::dyld::{export_c_func, FunctionExports};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
 use crate::fs::{GuestPath, GuestPathBuf};
+use crate::libc::sys::mount;
 use crate::mem::MutPtr;
 use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, release, ClassExports, HostObject,
@@ -55,6 +57,36 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(NSSearchPathForDirectoriesInDomains(_, _, _)),
 ];
 
+pub const NSFileSystemSize: &str = "NSFileSystemSize";
+pub const NSFileSystemFreeSize: &str = "NSFileSystemFreeSize";
+pub const NSFileSystemNodes: &str = "NSFileSystemNodes";
+pub const NSFileSystemFreeNodes: &str = "NSFileSystemFreeNodes";
+pub const NSFileSystemNumber: &str = "NSFileSystemNumber";
+
+/// `NSString` constants for file system attribute keys.
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_NSFileSystemSize",
+        HostConstant::NSString(NSFileSystemSize),
+    ),
+    (
+        "_NSFileSystemFreeSize",
+        HostConstant::NSString(NSFileSystemFreeSize),
+    ),
+    (
+        "_NSFileSystemNodes",
+        HostConstant::NSString(NSFileSystemNodes),
+    ),
+    (
+        "_NSFileSystemFreeNodes",
+        HostConstant::NSString(NSFileSystemFreeNodes),
+    ),
+    (
+        "_NSFileSystemNumber",
+        HostConstant::NSString(NSFileSystemNumber),
+    ),
+];
+
 #[derive(Default)]
 pub struct State {
     default_manager: Option<id>,
@@ -106,6 +138,40 @@ pub const CLASSES: ClassExports = objc_classes! {
     res_exists
 }
 
+- (id)attributesOfFileSystemForPath:(id)path // NSString*
+                              error:(MutPtr<id>)error { // NSError**
+    let path = ns_string::to_rust_string(env, path); // TODO: avoid copy
+    if !env.fs.exists(GuestPath::new(&path)) {
+        log!("Warning: attributesOfFileSystemForPath:{:?} for non-existent path, returning nil", path);
+        if !error.is_null() {
+            // TODO: create an NSError
+            env.mem.write(error, nil);
+        }
+        return nil;
+    }
+
+    // This matches what statfs() reports.
+    let free = env.fs.free_space();
+    let (_, total) = env.options.disk_space;
+    let nodes = total / u64::from(mount::BLOCK_SIZE);
+    let free_nodes = free / u64::from(mount::BLOCK_SIZE);
+    let mut keys_and_objects = Vec::new();
+    for (key, value) in [
+        (NSFileSystemSize, total),
+        (NSFileSystemFreeSize, free),
+        (NSFileSystemNodes, nodes),
+        (NSFileSystemFreeNodes, free_nodes),
+        (NSFileSystemNumber, 1),
+    ] {
+        let key = ns_string::get_static_str(env, key);
+        let value: id = msg_class![env; NSNumber numberWithUnsignedLongLong:value];
+        keys_and_objects.push((key, value));
+    }
+    let attributes = dict_from_keys_and_objects(env, &keys_and_objects);
+    log_dbg!("attributesOfFileSystemForPath:{:?} => {:?}", path, attributes);
+    autorelease(env, attributes)
+}
+
 - (bool)createFileAtPath:(id)path // NSString*
                 contents:(id)data // NSData*
               attributes:(id)attributes { // NSDictionary*
@@ -0,0 +1,824 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSHTTPCookie` and `NSHTTPCookieStorage`.
+//!
+//! touchHLE doesn't make network requests, so cookies come from the app itself
+//! or from `Set-Cookie` header fields of responses served by the network rules
+//! (see [crate::network_rules]). Cookies that aren't session-only are kept in
+//! `Library/Cookies/Cookies.plist` in the app's sandbox, like on a real device,
+//! so they survive between launches.
+
+use super::ns_dictionary::dict_from_keys_and_objects;
+use super::ns_property_list_serialization::text_plist;
+use super::{ns_array, ns_string, NSUInteger};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::fs::GuestPathBuf;
+use crate::libc::time::calendar_date_to_days;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, Class, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+use plist::{Dictionary, Value};
+
+pub const NSHTTPCookieName: &str = "Name";
+pub const NSHTTPCookieValue: &str = "Value";
+pub const NSHTTPCookieDomain: &str = "Domain";
+pub const NSHTTPCookiePath: &str = "Path";
+pub const NSHTTPCookieExpires: &str = "Expires";
+pub const NSHTTPCookieMaximumAge: &str = "Max-Age";
+pub const NSHTTPCookieSecure: &str = "Secure";
+pub const NSHTTPCookieDiscard: &str = "Discard";
+pub const NSHTTPCookieOriginURL: &str = "OriginURL";
+pub const NSHTTPCookieVersion: &str = "Version";
+
+/// `NSString` constants for cookie property keys.
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_NSHTTPCookieName",
+        HostConstant::NSString(NSHTTPCookieName),
+    ),
+    (
+        "_NSHTTPCookieValue",
+        HostConstant::NSString(NSHTTPCookieValue),
+    ),
+    (
+        "_NSHTTPCookieDomain",
+        HostConstant::NSString(NSHTTPCookieDomain),
+    ),
+    (
+        "_NSHTTPCookiePath",
+        HostConstant::NSString(NSHTTPCookiePath),
+    ),
+    (
+        "_NSHTTPCookieExpires",
+        HostConstant::NSString(NSHTTPCookieExpires),
+    ),
+    (
+        "_NSHTTPCookieMaximumAge",
+        HostConstant::NSString(NSHTTPCookieMaximumAge),
+    ),
+    (
+        "_NSHTTPCookieSecure",
+        HostConstant::NSString(NSHTTPCookieSecure),
+    ),
+    (
+        "_NSHTTPCookieDiscard",
+        HostConstant::NSString(NSHTTPCookieDiscard),
+    ),
+    (
+        "_NSHTTPCookieOriginURL",
+        HostConstant::NSString(NSHTTPCookieOriginURL),
+    ),
+    (
+        "_NSHTTPCookieVersion",
+        HostConstant::NSString(NSHTTPCookieVersion),
+    ),
+];
+
+type NSHTTPCookieAcceptPolicy = NSUInteger;
+const NSHTTPCookieAcceptPolicyAlways: NSHTTPCookieAcceptPolicy = 0;
+const NSHTTPCookieAcceptPolicyNever: NSHTTPCookieAcceptPolicy = 1;
+#[allow(dead_code)]
+const NSHTTPCookieAcceptPolicyOnlyFromMainDocumentDomain: NSHTTPCookieAcceptPolicy = 2;
+
+#[derive(Clone, PartialEq)]
+struct Cookie {
+    name: String,
+    value: String,
+    domain: String,
+    path: String,
+    /// Time interval since the reference date. [None] for session cookies.
+    expires: Option<f64>,
+    secure: bool,
+}
+impl Cookie {
+    fn is_expired(&self, now: f64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    /// Check whether the cookie belongs to `host`, i.e. whether it's for that
+    /// host or, if its domain starts with `.`, for a domain containing it.
+    fn domain_matches(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        let domain = self.domain.to_ascii_lowercase();
+        if let Some(suffix) = domain.strip_prefix('.') {
+            host == suffix || host.ends_with(&domain)
+        } else {
+            host == domain
+        }
+    }
+
+    /// Check whether the cookie should be sent with a request for `url`.
+    fn matches_url(&self, scheme: &str, host: &str, path: &str) -> bool {
+        if self.secure && !scheme.eq_ignore_ascii_case("https") {
+            return false;
+        }
+        self.domain_matches(host) && path.starts_with(&self.path)
+    }
+
+    fn to_plist(&self) -> Value {
+        let mut dict = Dictionary::new();
+        dict.insert(NSHTTPCookieName.to_string(), self.name.clone().into());
+        dict.insert(NSHTTPCookieValue.to_string(), self.value.clone().into());
+        dict.insert(NSHTTPCookieDomain.to_string(), self.domain.clone().into());
+        dict.insert(NSHTTPCookiePath.to_string(), self.path.clone().into());
+        dict.insert(
+            NSHTTPCookieExpires.to_string(),
+            self.expires.unwrap().into(),
+        );
+        dict.insert(NSHTTPCookieSecure.to_string(), self.secure.into());
+        Value::Dictionary(dict)
+    }
+
+    fn from_plist(value: &Value) -> Option<Cookie> {
+        let dict = value.as_dictionary()?;
+        let get_string = |key| Some(dict.get(key)?.as_string()?.to_string());
+        Some(Cookie {
+            name: get_string(NSHTTPCookieName)?,
+            value: get_string(NSHTTPCookieValue)?,
+            domain: get_string(NSHTTPCookieDomain)?,
+            path: get_string(NSHTTPCookiePath)?,
+            expires: Some(dict.get(NSHTTPCookieExpires)?.as_real()?),
+            secure: dict.get(NSHTTPCookieSecure)?.as_boolean()?,
+        })
+    }
+}
+
+/// Split an absolute URL string into its scheme, host and path.
+fn split_url(url: &str) -> Option<(&str, &str, &str)> {
+    let (scheme, rest) = url.split_once("://")?;
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, rest) = rest.split_at(authority_end);
+    let host = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let host = host.split_once(':').map_or(host, |(host, _port)| host);
+    let path = &rest[..rest.find(['?', '#']).unwrap_or(rest.len())];
+    let path = if path.is_empty() { "/" } else { path };
+    Some((scheme, host, path))
+}
+
+/// The default path for a cookie: the directory of the URL's path.
+fn default_path(url_path: &str) -> String {
+    url_path
+        .rsplit_once('/')
+        .map_or("/", |(dir, _)| if dir.is_empty() { "/" } else { dir })
+        .to_string()
+}
+
+/// Parse a date in the format used by HTTP, e.g.
+/// `Sun, 06 Nov 1994 08:49:37 GMT`, as a time interval since the reference
+/// date. The old `Sunday, 06-Nov-94 08:49:37 GMT` format is also accepted.
+fn parse_http_date(date: &str) -> Option<f64> {
+    const MONTHS: [&str; 12] = [
+        "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+    ];
+    // The weekday is redundant.
+    let (_weekday, date) = date.split_once(',')?;
+    let mut parts = date.split([' ', '-']).filter(|part| !part.is_empty());
+    let day: i32 = parts.next()?.parse().ok()?;
+    let month = parts.next()?.to_ascii_lowercase();
+    let month = MONTHS.iter().position(|&name| month.starts_with(name))?;
+    let year: i32 = parts.next()?.parse().ok()?;
+    let year = match year {
+        0..=69 => year + 2000,
+        70..=99 => year + 1900,
+        _ => year,
+    };
+    let mut time = parts
+        .next()?
+        .split(':')
+        .map(|part| part.parse::<i64>().ok());
+    let (Some(Some(hours)), Some(Some(minutes)), Some(Some(seconds)), None) =
+        (time.next(), time.next(), time.next(), time.next())
+    else {
+        return None;
+    };
+    if !(1..=31).contains(&day) || hours > 23 || minutes > 59 || seconds > 60 {
+        return None;
+    }
+    let days = calendar_date_to_days(year, month as i32, day - 1);
+    let unix_seconds = i64::from(days) * 86400 + hours * 3600 + minutes * 60 + seconds;
+    // The reference date is 1 January 2001.
+    Some((unix_seconds - 978307200) as f64)
+}
+
+/// Parse the value of a `Set-Cookie` header field in a response for `url`.
+/// Several cookies can be combined in one field, separated by commas. Cookies
+/// for a domain that `url` isn't part of are rejected.
+fn parse_set_cookie(header: &str, url: &str, now: f64) -> Vec<Cookie> {
+    let Some((_, host, url_path)) = split_url(url) else {
+        return Vec::new();
+    };
+
+    // Commas also appear in expiry dates, so a comma only starts a new cookie
+    // if it's followed by something that looks like a name and a value.
+    let mut cookie_strings = vec![String::new()];
+    for (i, part) in header.split(',').enumerate() {
+        let starts_cookie = part
+            .split_once('=')
+            .is_some_and(|(name, _)| !name.trim().is_empty() && !name.trim().contains([' ', ';']));
+        if i > 0 && starts_cookie {
+            cookie_strings.push(String::new());
+        } else if i > 0 {
+            cookie_strings.last_mut().unwrap().push(',');
+        }
+        cookie_strings.last_mut().unwrap().push_str(part);
+    }
+
+    let mut cookies = Vec::new();
+    for cookie_string in cookie_strings {
+        let mut attributes = cookie_string.split(';');
+        let Some((name, value)) = attributes.next().unwrap().split_once('=') else {
+            continue;
+        };
+        let mut cookie = Cookie {
+            name: name.trim().to_string(),
+            value: value.trim().to_string(),
+            domain: host.to_ascii_lowercase(),
+            path: default_path(url_path),
+            expires: None,
+            secure: false,
+        };
+        let mut max_age = None;
+        for attribute in attributes {
+            let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+            let (key, value) = (key.trim(), value.trim());
+            if key.eq_ignore_ascii_case("Domain") && !value.is_empty() {
+                let domain = value.trim_start_matches('.').to_ascii_lowercase();
+                cookie.domain = format!(".{}", domain);
+            } else if key.eq_ignore_ascii_case("Path") && value.starts_with('/') {
+                cookie.path = value.to_string();
+            } else if key.eq_ignore_ascii_case("Expires") {
+                cookie.expires = parse_http_date(value).or(cookie.expires);
+            } else if key.eq_ignore_ascii_case("Max-Age") {
+                max_age = value.parse::<f64>().ok();
+            } else if key.eq_ignore_ascii_case("Secure") {
+                cookie.secure = true;
+            }
+        }
+        // Max-Age takes precedence over Expires.
+        if let Some(max_age) = max_age {
+            cookie.expires = Some(now + max_age);
+        }
+        if cookie.name.is_empty() || !cookie.domain_matches(host) {
+            log!(
+                "Warning: Rejecting cookie {:?} for domain {:?} set by {:?}",
+                cookie.name,
+                cookie.domain,
+                url
+            );
+            continue;
+        }
+        cookies.push(cookie);
+    }
+    cookies
+}
+
+fn url_to_rust_string(env: &mut Environment, url: id) -> String {
+    let url_string: id = msg![env; url absoluteString];
+    ns_string::to_rust_string(env, url_string).into_owned()
+}
+
+#[derive(Default)]
+pub struct State {
+    shared_storage: Option<id>,
+    /// [None] until the persisted cookies have been loaded.
+    cookies: Option<Vec<Cookie>>,
+    accept_policy: NSHTTPCookieAcceptPolicy,
+}
+
+fn cookies_file_path(env: &Environment) -> GuestPathBuf {
+    env.fs
+        .home_directory()
+        .join("Library")
+        .join("Cookies")
+        .join("Cookies.plist")
+}
+
+/// Get the stored cookies, loading them from the sandbox if necessary.
+fn stored_cookies(env: &mut Environment) -> &mut Vec<Cookie> {
+    if env
+        .framework_state
+        .foundation
+        .ns_http_cookie
+        .cookies
+        .is_none()
+    {
+        let path = cookies_file_path(env);
+        let mut cookies = Vec::new();
+        if let Ok(bytes) = env.fs.read(&path) {
+            match text_plist::parse_plist(&bytes) {
+                Ok(Value::Array(array)) => {
+                    cookies.extend(array.iter().filter_map(Cookie::from_plist))
+                }
+                _ => log!("Warning: Couldn't parse {:?}, ignoring", path),
+            }
+        }
+        log_dbg!("Loaded {} cookies from {:?}", cookies.len(), path);
+        env.framework_state.foundation.ns_http_cookie.cookies = Some(cookies);
+    }
+    env.framework_state
+        .foundation
+        .ns_http_cookie
+        .cookies
+        .as_mut()
+        .unwrap()
+}
+
+/// Write the cookies that aren't session-only to the sandbox.
+fn save_cookies(env: &mut Environment) {
+    let now: f64 = msg_class![env; NSDate timeIntervalSinceReferenceDate];
+    let array = stored_cookies(env)
+        .iter()
+        .filter(|cookie| cookie.expires.is_some() && !cookie.is_expired(now))
+        .map(Cookie::to_plist)
+        .collect();
+    let mut bytes = Vec::new();
+    Value::Array(array).to_writer_xml(&mut bytes).unwrap();
+
+    let path = cookies_file_path(env);
+    let dir = path.parent().unwrap().to_owned();
+    if !env.fs.exists(&dir) {
+        let _ = env.fs.create_dir(&dir);
+    }
+    if env.fs.write(&path, &bytes).is_err() {
+        log!("Warning: Couldn't write cookies to {:?}", path);
+    }
+}
+
+/// Get the stored cookies that should be sent with a request for `url`, with
+/// the most specific paths first, as in a `Cookie` header field.
+fn cookies_for_url(env: &mut Environment, url: &str) -> Vec<Cookie> {
+    let Some((scheme, host, path)) = split_url(url) else {
+        return Vec::new();
+    };
+    let now: f64 = msg_class![env; NSDate timeIntervalSinceReferenceDate];
+    let mut cookies: Vec<Cookie> = stored_cookies(env)
+        .iter()
+        .filter(|cookie| !cookie.is_expired(now) && cookie.matches_url(scheme, host, path))
+        .cloned()
+        .collect();
+    cookies.sort_by(|a, b| b.path.len().cmp(&a.path.len()));
+    cookies
+}
+
+/// Get the value of the `Cookie` header field for a request for `url`, if any
+/// cookies are stored for it.
+pub fn cookie_header_for_url(env: &mut Environment, url: &str) -> Option<String> {
+    let cookies = cookies_for_url(env, url);
+    if cookies.is_empty() {
+        return None;
+    }
+    let pairs: Vec<String> = cookies
+        .iter()
+        .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+        .collect();
+    Some(pairs.join("; "))
+}
+
+/// Store the cookies from a `Set-Cookie` header field in a response for `url`,
+/// if the accept policy allows it.
+pub fn store_cookies_from_header(env: &mut Environment, url: &str, header: &str) {
+    if env.framework_state.foundation.ns_http_cookie.accept_policy == NSHTTPCookieAcceptPolicyNever
+    {
+        return;
+    }
+    let now: f64 = msg_class![env; NSDate timeIntervalSinceReferenceDate];
+    let mut any_persistent = false;
+    for cookie in parse_set_cookie(header, url, now) {
+        log_dbg!(
+            "Storing cookie {:?} for {:?} from response to {:?}",
+            cookie.name,
+            cookie.domain,
+            url
+        );
+        any_persistent |= cookie.expires.is_some();
+        store_cookie(env, cookie);
+    }
+    if any_persistent {
+        save_cookies(env);
+    }
+}
+
+fn store_cookie(env: &mut Environment, cookie: Cookie) {
+    let cookies = stored_cookies(env);
+    cookies.retain(|existing| {
+        existing.name != cookie.name
+            || existing.domain != cookie.domain
+            || existing.path != cookie.path
+    });
+    cookies.push(cookie);
+}
+
+/// Create an `NSArray` (autoreleased) of new `NSHTTPCookie`s.
+fn cookies_to_array(env: &mut Environment, cookies: Vec<Cookie>) -> id {
+    let objects = cookies
+        .into_iter()
+        .map(|cookie| {
+            let new: id = msg_class![env; NSHTTPCookie alloc];
+            env.objc.borrow_mut::<NSHTTPCookieHostObject>(new).cookie = Some(cookie);
+            new
+        })
+        .collect();
+    let array = ns_array::from_vec(env, objects);
+    autorelease(env, array)
+}
+
+struct NSHTTPCookieHostObject {
+    /// [None] only before initialization.
+    cookie: Option<Cookie>,
+}
+impl HostObject for NSHTTPCookieHostObject {}
+
+fn borrow_cookie(env: &mut Environment, this: id) -> &Cookie {
+    env.objc
+        .borrow::<NSHTTPCookieHostObject>(this)
+        .cookie
+        .as_ref()
+        .unwrap()
+}
+
+/// Look up a string-valued cookie property, if present.
+fn get_string_property(env: &mut Environment, properties: id, key: &'static str) -> Option<String> {
+    let key = ns_string::get_static_str(env, key);
+    let value: id = msg![env; properties objectForKey:key];
+    if value == nil {
+        return None;
+    }
+    let string_class = env.objc.get_known_class("NSString", &mut env.mem);
+    if msg![env; value isKindOfClass:string_class] {
+        Some(ns_string::to_rust_string(env, value).into_owned())
+    } else {
+        // URLs are allowed for NSHTTPCookieOriginURL.
+        Some(url_to_rust_string(env, value))
+    }
+}
+
+fn cookie_from_properties(env: &mut Environment, properties: id) -> Option<Cookie> {
+    let name = get_string_property(env, properties, NSHTTPCookieName)?;
+    let value = get_string_property(env, properties, NSHTTPCookieValue)?;
+    let origin_url = get_string_property(env, properties, NSHTTPCookieOriginURL);
+    let origin = origin_url.as_deref().and_then(split_url);
+
+    let domain = match get_string_property(env, properties, NSHTTPCookieDomain) {
+        Some(domain) => domain,
+        None => origin?.1.to_string(),
+    };
+    let path = match get_string_property(env, properties, NSHTTPCookiePath) {
+        Some(path) => path,
+        None => default_path(origin.map_or("/", |(_, _, path)| path)),
+    };
+
+    let now: f64 = msg_class![env; NSDate timeIntervalSinceReferenceDate];
+    let discard = get_string_property(env, properties, NSHTTPCookieDiscard)
+        .is_some_and(|discard| discard.eq_ignore_ascii_case("TRUE"));
+    let expires = if discard {
+        None
+    } else if let Some(max_age) = get_string_property(env, properties, NSHTTPCookieMaximumAge) {
+        max_age
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .map(|max_age| now + max_age)
+    } else {
+        let key = ns_string::get_static_str(env, NSHTTPCookieExpires);
+        let expires: id = msg![env; properties objectForKey:key];
+        let date_class: Class = msg_class![env; NSDate class];
+        if expires == nil {
+            None
+        } else if msg![env; expires isKindOfClass:date_class] {
+            Some(msg![env; expires timeIntervalSinceReferenceDate])
+        } else {
+            let expires = get_string_property(env, properties, NSHTTPCookieExpires).unwrap();
+            let parsed = parse_http_date(&expires);
+            if parsed.is_none() {
+                log!(
+                    "Warning: Couldn't parse cookie expiry date {:?}, treating as session cookie",
+                    expires
+                );
+            }
+            parsed
+        }
+    };
+
+    let secure = get_string_property(env, properties, NSHTTPCookieSecure)
+        .is_some_and(|secure| !secure.eq_ignore_ascii_case("FALSE"));
+
+    Some(Cookie {
+        name,
+        value,
+        domain,
+        path,
+        expires,
+        secure,
+    })
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSHTTPCookie: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSHTTPCookieHostObject { cookie: None });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)cookieWithProperties:(id)properties { // NSDictionary*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithProperties:properties];
+    autorelease(env, new)
+}
+
++ (id)requestHeaderFieldsWithCookies:(id)cookies { // NSArray*
+    let count: NSUInteger = msg![env; cookies count];
+    let mut header = String::new();
+    for i in 0..count {
+        let cookie: id = msg![env; cookies objectAtIndex:i];
+        let Cookie { name, value, .. } = borrow_cookie(env, cookie);
+        if !header.is_empty() {
+            header.push_str("; ");
+        }
+        header.push_str(&format!("{}={}", name, value));
+    }
+    if header.is_empty() {
+        return msg_class![env; NSDictionary dictionary];
+    }
+    let key = ns_string::get_static_str(env, "Cookie");
+    let value = ns_string::from_rust_string(env, header);
+    let dict = dict_from_keys_and_objects(env, &[(key, value)]);
+    release(env, value);
+    autorelease(env, dict)
+}
+
++ (id)cookiesWithResponseHeaderFields:(id)header_fields // NSDictionary*
+                               forURL:(id)url { // NSURL*
+    let url = url_to_rust_string(env, url);
+    let key = ns_string::get_static_str(env, "Set-Cookie");
+    let header: id = msg![env; header_fields objectForKey:key];
+    let cookies = if header == nil {
+        Vec::new()
+    } else {
+        let header = ns_string::to_rust_string(env, header);
+        let now: f64 = msg_class![env; NSDate timeIntervalSinceReferenceDate];
+        parse_set_cookie(&header, &url, now)
+    };
+    cookies_to_array(env, cookies)
+}
+
+- (id)initWithProperties:(id)properties { // NSDictionary*
+    let Some(cookie) = cookie_from_properties(env, properties) else {
+        log!("Warning: [NSHTTPCookie initWithProperties:] missing required properties, returning nil");
+        release(env, this);
+        return nil;
+    };
+    env.objc.borrow_mut::<NSHTTPCookieHostObject>(this).cookie = Some(cookie);
+    this
+}
+
+- (id)name {
+    let name = borrow_cookie(env, this).name.clone();
+    let name = ns_string::from_rust_string(env, name);
+    autorelease(env, name)
+}
+- (id)value {
+    let value = borrow_cookie(env, this).value.clone();
+    let value = ns_string::from_rust_string(env, value);
+    autorelease(env, value)
+}
+- (id)domain {
+    let domain = borrow_cookie(env, this).domain.clone();
+    let domain = ns_string::from_rust_string(env, domain);
+    autorelease(env, domain)
+}
+- (id)path {
+    let path = borrow_cookie(env, this).path.clone();
+    let path = ns_string::from_rust_string(env, path);
+    autorelease(env, path)
+}
+- (id)expiresDate {
+    match borrow_cookie(env, this).expires {
+        Some(expires) => msg_class![env; NSDate dateWithTimeIntervalSinceReferenceDate:expires],
+        None => nil,
+    }
+}
+- (bool)isSecure {
+    borrow_cookie(env, this).secure
+}
+- (bool)isSessionOnly {
+    borrow_cookie(env, this).expires.is_none()
+}
+- (NSUInteger)version {
+    0
+}
+
+- (id)properties {
+    let cookie = borrow_cookie(env, this).clone();
+    let dict: id = msg_class![env; NSMutableDictionary dictionary];
+    for (key, value) in [
+        (NSHTTPCookieName, cookie.name),
+        (NSHTTPCookieValue, cookie.value),
+        (NSHTTPCookieDomain, cookie.domain),
+        (NSHTTPCookiePath, cookie.path),
+    ] {
+        let key = ns_string::get_static_str(env, key);
+        let value = ns_string::from_rust_string(env, value);
+        () = msg![env; dict setObject:value forKey:key];
+        release(env, value);
+    }
+    if let Some(expires) = cookie.expires {
+        let key = ns_string::get_static_str(env, NSHTTPCookieExpires);
+        let date: id = msg_class![env; NSDate dateWithTimeIntervalSinceReferenceDate:expires];
+        () = msg![env; dict setObject:date forKey:key];
+    }
+    if cookie.secure {
+        let key = ns_string::get_static_str(env, NSHTTPCookieSecure);
+        let value = ns_string::get_static_str(env, "TRUE");
+        () = msg![env; dict setObject:value forKey:key];
+    }
+    dict
+}
+
+- (id)description {
+    let Cookie { name, value, domain, path, .. } = borrow_cookie(env, this);
+    let desc = format!(
+        "<NSHTTPCookie {:?}: name:{:?} value:{:?} domain:{:?} path:{:?}>",
+        this, name, value, domain, path
+    );
+    let desc = ns_string::from_rust_string(env, desc);
+    autorelease(env, desc)
+}
+
+@end
+
+@implementation NSHTTPCookieStorage: NSObject
+
++ (id)sharedHTTPCookieStorage {
+    if let Some(existing) = env.framework_state.foundation.ns_http_cookie.shared_storage {
+        existing
+    } else {
+        let new: id = msg![env; this new];
+        env.framework_state.foundation.ns_http_cookie.shared_storage = Some(new);
+        new
+    }
+}
+
+- (NSHTTPCookieAcceptPolicy)cookieAcceptPolicy {
+    env.framework_state.foundation.ns_http_cookie.accept_policy
+}
+- (())setCookieAcceptPolicy:(NSHTTPCookieAcceptPolicy)policy {
+    env.framework_state.foundation.ns_http_cookie.accept_policy = policy;
+}
+
+- (id)cookies {
+    let now: f64 = msg_class![env; NSDate timeIntervalSinceReferenceDate];
+    let cookies = stored_cookies(env)
+        .iter()
+        .filter(|cookie| !cookie.is_expired(now))
+        .cloned()
+        .collect();
+    cookies_to_array(env, cookies)
+}
+
+- (id)cookiesForURL:(id)url { // NSURL*
+    let url = url_to_rust_string(env, url);
+    let cookies = cookies_for_url(env, &url);
+    log_dbg!("[(NSHTTPCookieStorage*){:?} cookiesForURL:{:?}] => {} cookies", this, url, cookies.len());
+    cookies_to_array(env, cookies)
+}
+
+- (())setCookie:(id)cookie { // NSHTTPCookie*
+    if env.framework_state.foundation.ns_http_cookie.accept_policy == NSHTTPCookieAcceptPolicyNever {
+        return;
+    }
+    let cookie = borrow_cookie(env, cookie).clone();
+    log_dbg!("[(NSHTTPCookieStorage*){:?} setCookie:] {:?} for {:?}", this, cookie.name, cookie.domain);
+    let persistent = cookie.expires.is_some();
+    store_cookie(env, cookie);
+    if persistent {
+        save_cookies(env);
+    }
+}
+
+- (())setCookies:(id)cookies // NSArray*
+          forURL:(id)url // NSURL*
+ mainDocumentURL:(id)main_document_url { // NSURL*
+    let policy = env.framework_state.foundation.ns_http_cookie.accept_policy;
+    if policy == NSHTTPCookieAcceptPolicyNever {
+        return;
+    }
+    let url = url_to_rust_string(env, url);
+    let url_host = split_url(&url).map(|(_, host, _)| host.to_ascii_lowercase());
+    if policy != NSHTTPCookieAcceptPolicyAlways && main_document_url != nil {
+        let main_url = url_to_rust_string(env, main_document_url);
+        let main_host = split_url(&main_url).map(|(_, host, _)| host.to_ascii_lowercase());
+        // Only accept cookies if the URL is in the main document's domain.
+        let same_domain = match (&url_host, &main_host) {
+            (Some(url_host), Some(main_host)) => {
+                url_host == main_host || url_host.ends_with(&format!(".{}", main_host))
+            }
+            _ => false,
+        };
+        if !same_domain {
+            return;
+        }
+    }
+
+    let count: NSUInteger = msg![env; cookies count];
+    let mut any_persistent = false;
+    for i in 0..count {
+        let cookie: id = msg![env; cookies objectAtIndex:i];
+        let cookie = borrow_cookie(env, cookie).clone();
+        // A site can't set cookies for another one.
+        if !url_host.as_ref().is_some_and(|host| cookie.domain_matches(host)) {
+            log!("Warning: Rejecting cookie {:?} for domain {:?} set by {:?}", cookie.name, cookie.domain, url);
+            continue;
+        }
+        any_persistent |= cookie.expires.is_some();
+        store_cookie(env, cookie);
+    }
+    if any_persistent {
+        save_cookies(env);
+    }
+}
+
+- (())deleteCookie:(id)cookie { // NSHTTPCookie*
+    let cookie = borrow_cookie(env, cookie).clone();
+    let cookies = stored_cookies(env);
+    let old_len = cookies.len();
+    cookies.retain(|existing| {
+        existing.name != cookie.name
+            || existing.domain != cookie.domain
+            || existing.path != cookie.path
+    });
+    if cookies.len() != old_len && cookie.expires.is_some() {
+        save_cookies(env);
+    }
+}
+
+@end
+
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn http_date() {
+        // 1 January 2001 is the reference date.
+        assert_eq!(parse_http_date("Mon, 01 Jan 2001 00:00:00 GMT"), Some(0.0));
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some((784111777 - 978307200) as f64)
+        );
+        assert_eq!(
+            parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"),
+            Some((784111777 - 978307200) as f64)
+        );
+        assert_eq!(parse_http_date("tomorrow"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49 GMT"), None);
+    }
+
+    #[test]
+    fn set_cookie() {
+        let now = 1000.0;
+        let cookies = parse_set_cookie(
+            "session=abc; Path=/; Secure, \
+             lang=en; Expires=Mon, 01 Jan 2001 00:00:00 GMT; Domain=.example.com, \
+             age=1; Max-Age=60",
+            "https://www.example.com/game/login.php",
+            now,
+        );
+        assert_eq!(cookies.len(), 3);
+        let (session, lang, age) = (&cookies[0], &cookies[1], &cookies[2]);
+
+        assert_eq!((&*session.name, &*session.value), ("session", "abc"));
+        assert_eq!((&*session.domain, &*session.path), ("www.example.com", "/"));
+        assert!(session.secure);
+        assert_eq!(session.expires, None);
+
+        assert_eq!((&*lang.name, &*lang.value), ("lang", "en"));
+        assert_eq!((&*lang.domain, &*lang.path), (".example.com", "/game"));
+        assert_eq!(lang.expires, Some(0.0));
+
+        assert_eq!(age.expires, Some(now + 60.0));
+    }
+
+    #[test]
+    fn set_cookie_for_other_domain() {
+        let cookies = parse_set_cookie(
+            "a=1; Domain=example.org, b=2; Domain=example.com",
+            "http://scores.example.com/",
+            0.0,
+        );
+        assert_eq!(cookies.len(), 1);
+        assert_eq!(cookies[0].name, "b");
+    }
+}
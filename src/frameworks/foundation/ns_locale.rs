@@ -5,19 +5,31 @@
  */
 //! `NSLocale`.
 
-use super::{ns_array, ns_string};
+use super::{ns_array, ns_string, NSUInteger};
 use crate::dyld::{ConstantExports, HostConstant};
-use crate::objc::{id, objc_classes, ClassExports, HostObject};
+use crate::objc::{id, msg, objc_classes, ClassExports, HostObject};
 use crate::options::Options;
 use crate::Environment;
 use std::ffi::CStr;
 
 const NSLocaleCountryCode: &str = "NSLocaleCountryCode";
-
-pub const CONSTANTS: ConstantExports = &[(
-    "_NSLocaleCountryCode",
-    HostConstant::NSString(NSLocaleCountryCode),
-)];
+const NSLocaleLanguageCode: &str = "NSLocaleLanguageCode";
+const NSLocaleIdentifier: &str = "NSLocaleIdentifier";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_NSLocaleCountryCode",
+        HostConstant::NSString(NSLocaleCountryCode),
+    ),
+    (
+        "_NSLocaleLanguageCode",
+        HostConstant::NSString(NSLocaleLanguageCode),
+    ),
+    (
+        "_NSLocaleIdentifier",
+        HostConstant::NSString(NSLocaleIdentifier),
+    ),
+];
 
 #[derive(Default)]
 pub struct State {
@@ -74,7 +86,12 @@ fn get_preferred_languages(options: &Options) -> Vec<String> {
     }
 }
 
-fn get_preferred_countries() -> Vec<String> {
+fn get_preferred_countries(options: &Options) -> Vec<String> {
+    if let Some(ref preferred_region) = options.preferred_region {
+        log!("The app requested your current locale. {:?} will be reported based on your --preferred-region= option.", preferred_region);
+        return vec![preferred_region.clone()];
+    }
+
     // Unfortunately Rust-SDL2 doesn't provide a wrapper for this yet.
     let countries = unsafe {
         let mut countries = Vec::new();
@@ -109,6 +126,9 @@ fn get_preferred_countries() -> Vec<String> {
 
 struct NSLocaleHostObject {
     country_code: id,
+    language_code: id,
+    /// e.g. `pt_BR`
+    locale_identifier: id,
 }
 impl HostObject for NSLocaleHostObject {}
 
@@ -138,10 +158,21 @@ pub const CLASSES: ClassExports = objc_classes! {
     if let Some(locale) = State::get(env).current_locale {
         locale
     } else {
-        let countries = get_preferred_countries();
+        let countries = get_preferred_countries(&env.options);
+        // The language is the first preferred language without any region or
+        // script, since those are reported separately.
+        let langs: id = msg![env; this preferredLanguages];
+        let lang: id = msg![env; langs objectAtIndex:(0 as NSUInteger)];
+        let lang = ns_string::to_rust_string(env, lang);
+        let lang = lang.split(['-', '_']).next().unwrap().to_string();
+        let locale_identifier = format!("{}_{}", lang, countries[0]);
         let country_code = ns_string::from_rust_string(env, countries[0].clone());
+        let language_code = ns_string::from_rust_string(env, lang);
+        let locale_identifier = ns_string::from_rust_string(env, locale_identifier);
         let host_object = NSLocaleHostObject {
-            country_code
+            country_code,
+            language_code,
+            locale_identifier,
         };
         let new_locale = env.objc.alloc_object(
             this,
@@ -155,13 +186,17 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 // TODO: constructors, more accessors
 
+- (id)localeIdentifier {
+    env.objc.borrow::<NSLocaleHostObject>(this).locale_identifier
+}
+
 - (id)objectForKey:(id)key {
     let key_str: &str = &ns_string::to_rust_string(env, key);
+    let host_object = env.objc.borrow::<NSLocaleHostObject>(this);
     match key_str {
-        NSLocaleCountryCode => {
-            let &NSLocaleHostObject { country_code } = env.objc.borrow(this);
-            country_code
-        },
+        NSLocaleCountryCode => host_object.country_code,
+        NSLocaleLanguageCode => host_object.language_code,
+        NSLocaleIdentifier => host_object.locale_identifier,
         _ => unimplemented!()
     }
 }
@@ -29,12 +29,17 @@ fn NSLog(
         args.start(),
     );
     // TODO: Should we include a timestamp, like the real NSLog?
-    echo!(
+    let message = format!(
         "{}[{}] {}",
         env.bundle.executable_path().file_name().unwrap(),
         env.current_thread,
         String::from_utf8_lossy(&res)
     );
+    echo!("{}", message);
+    if let Some(window) = env.window.as_mut() {
+        window.console_write(&message);
+        window.console_write("\n");
+    }
 }
 
 pub const FUNCTIONS: FunctionExports = &[export_c_func!(NSLog(_, _))];
@@ -14,13 +14,19 @@
 //!
 //! See also: [crate::objc], especially the `objects` module.
 
+use super::ns_run_loop::queue_main_thread_perform;
 use super::ns_string::to_rust_string;
 use super::NSUInteger;
 use crate::mem::MutVoidPtr;
 use crate::objc::{
-    id, msg, msg_class, msg_send, objc_classes, Class, ClassExports, NSZonePtr, ObjC,
-    TrivialHostObject, SEL,
+    id, msg, msg_class, msg_send, objc_classes, record_retain_release, Class, ClassExports,
+    NSZonePtr, ObjC, RetainReleaseOperation, TrivialHostObject, SEL,
 };
+use std::time::Duration;
+
+/// How often a thread waiting for `performSelectorOnMainThread:` to finish
+/// checks whether it has.
+const MAIN_THREAD_PERFORM_POLL_INTERVAL: Duration = Duration::from_millis(1);
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -67,16 +73,19 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 - (id)retain {
     log_dbg!("[{:?} retain]", this);
+    record_retain_release(env, this, RetainReleaseOperation::Retain);
     env.objc.increment_refcount(this);
     this
 }
 - (())release {
     log_dbg!("[{:?} release]", this);
+    record_retain_release(env, this, RetainReleaseOperation::Release);
     if env.objc.decrement_refcount(this) {
         () = msg![env; this dealloc];
     }
 }
 - (id)autorelease {
+    record_retain_release(env, this, RetainReleaseOperation::Autorelease);
     () = msg_class![env; NSAutoreleasePool addObject:this];
     this
 }
@@ -175,6 +184,40 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg_send(env, (this, sel, o1, o2))
 }
 
+// These are declared in NSThread.h.
+
+- (())performSelectorInBackground:(SEL)sel
+                       withObject:(id)arg {
+    assert!(!sel.is_null());
+    msg_class![env; NSThread detachNewThreadSelector:sel toTarget:this withObject:arg]
+}
+
+- (())performSelectorOnMainThread:(SEL)sel
+                       withObject:(id)arg
+                    waitUntilDone:(bool)wait {
+    assert!(!sel.is_null());
+    // Waiting for the main thread from the main thread would deadlock, so the
+    // message is sent immediately instead, like on the real system.
+    if wait && env.current_thread == 0 {
+        () = msg_send(env, (this, sel, arg));
+        return;
+    }
+    let done = queue_main_thread_perform(env, this, sel, arg);
+    if wait {
+        while !done.get() {
+            env.sleep(MAIN_THREAD_PERFORM_POLL_INTERVAL, /* tail_call: */ false);
+        }
+    }
+}
+
+- (())performSelectorOnMainThread:(SEL)sel
+                       withObject:(id)arg
+                    waitUntilDone:(bool)wait
+                            modes:(id)_modes { // NSArray* of NSString*
+    // TODO: handle run loop modes
+    msg![env; this performSelectorOnMainThread:sel withObject:arg waitUntilDone:wait]
+}
+
 @end
 
 };
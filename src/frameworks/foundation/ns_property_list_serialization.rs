@@ -1,12 +1,13 @@
 //! `NSPropertyListSerialization`.
 
-use super::{ns_array, ns_dictionary, ns_string, NSUInteger};
+use super::{ns_array, ns_date, ns_dictionary, ns_string, NSUInteger};
 use crate::fs::GuestPath;
 use crate::mem::MutPtr;
 use crate::objc::{id, msg, msg_class, nil, release};
 use crate::Environment;
 use plist::Value;
-use std::io::Cursor;
+
+pub mod text_plist;
 
 // TODO: Implement reading of property lists other than Info.plist.
 // [NSDictionary contentsOfFile:] and [NSArray contentsOfFile:] in particular.
@@ -24,7 +25,7 @@ pub(super) fn deserialize_plist_from_file(
         return nil;
     };
 
-    let Ok(root) = Value::from_reader(Cursor::new(bytes)) else {
+    let Ok(root) = text_plist::parse_plist(&bytes) else {
         log_dbg!("Couldn't parse plist, returning nil.");
         return nil;
     };
@@ -82,9 +83,7 @@ fn deserialize_plist(env: &mut Environment, value: &Value) -> id {
             let data: id = msg_class![env; NSData alloc];
             msg![env; data initWithBytesNoCopy:alloc length:length]
         }
-        Value::Date(_) => {
-            todo!("deserialize plist value: {:?}", value); // TODO
-        }
+        Value::Date(date) => ns_date::from_system_time(env, (*date).into()),
         Value::Integer(int) => {
             let number: id = msg_class![env; NSNumber alloc];
             // TODO: is this the correct order of preference? does it matter?
@@ -0,0 +1,353 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Property list formats not handled by the `plist` crate: the old-style
+//! (NeXTSTEP) ASCII format, and XML or ASCII property lists in UTF-16.
+//!
+//! The old-style format looks like this:
+//!
+//! ```text
+//! {
+//!     Name = "Player 1"; // Comment
+//!     Levels = (one, two, "three");
+//!     Checksum = <0fbd 7788>;
+//! }
+//! ```
+//!
+//! There are no numbers or booleans, only strings, so values that look like
+//! them remain strings. `.strings` files use the same syntax, but the
+//! dictionary has no braces (see [parse_strings]).
+
+use plist::{Dictionary, Value};
+use std::io::Cursor;
+
+/// Parse a property list in any format, detecting the text encoding from the
+/// byte order mark if there is one.
+pub fn parse_plist(bytes: &[u8]) -> Result<Value, String> {
+    if bytes.starts_with(b"bplist") {
+        return Value::from_reader(Cursor::new(bytes)).map_err(|e| e.to_string());
+    }
+    let text = decode_text(bytes)?;
+    let trimmed = text.trim_start();
+    if trimmed.starts_with("<?xml")
+        || trimmed.starts_with("<!DOCTYPE")
+        || trimmed.starts_with("<plist")
+    {
+        // The text is now UTF-8, regardless of what the XML declaration says.
+        return Value::from_reader(Cursor::new(trimmed.as_bytes())).map_err(|e| e.to_string());
+    }
+    let mut parser = Parser::new(&text);
+    let value = parser.parse_value()?;
+    parser.skip_whitespace_and_comments()?;
+    if let Some(c) = parser.peek() {
+        return Err(format!("Unexpected {:?} after end of property list", c));
+    }
+    Ok(value)
+}
+
+/// Parse the text of a `.strings` file: dictionary entries with no braces
+/// around them. Unlike in dictionaries, an entry can be a key on its own,
+/// which is then also the value.
+pub fn parse_strings(text: &str) -> Result<Dictionary, String> {
+    let mut parser = Parser::new(text);
+    let dict = parser.parse_dictionary_entries(None)?;
+    Ok(dict)
+}
+
+/// Decode text using its byte order mark, defaulting to UTF-8.
+pub fn decode_text(bytes: &[u8]) -> Result<String, String> {
+    fn decode_utf16(bytes: &[u8], from_bytes: fn([u8; 2]) -> u16) -> Result<String, String> {
+        if bytes.len() % 2 != 0 {
+            return Err("Odd number of bytes in UTF-16 text".to_string());
+        }
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|chunk| from_bytes(chunk.try_into().unwrap()))
+            .collect();
+        String::from_utf16(&units).map_err(|e| e.to_string())
+    }
+
+    if let Some(rest) = bytes.strip_prefix(&[0xFF, 0xFE]) {
+        decode_utf16(rest, u16::from_le_bytes)
+    } else if let Some(rest) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        decode_utf16(rest, u16::from_be_bytes)
+    } else {
+        let rest = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes);
+        String::from_utf8(rest.to_vec()).map_err(|e| e.to_string())
+    }
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Self {
+        Parser {
+            chars: text.chars().peekable(),
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace_and_comments()?;
+        match self.chars.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("Expected {:?} but found {:?}", expected, c)),
+            None => Err(format!("Expected {:?} but found end of text", expected)),
+        }
+    }
+
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), String> {
+        loop {
+            match self.peek() {
+                Some(c) if c.is_whitespace() => {
+                    self.chars.next();
+                }
+                Some('/') => {
+                    self.chars.next();
+                    match self.chars.next() {
+                        Some('/') => {
+                            for c in self.chars.by_ref() {
+                                if c == '\n' {
+                                    break;
+                                }
+                            }
+                        }
+                        Some('*') => {
+                            let mut last = None;
+                            loop {
+                                match self.chars.next() {
+                                    Some('/') if last == Some('*') => break,
+                                    Some(c) => last = Some(c),
+                                    None => return Err("Unterminated comment".to_string()),
+                                }
+                            }
+                        }
+                        _ => return Err("Unexpected '/'".to_string()),
+                    }
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace_and_comments()?;
+        match self.peek() {
+            Some('{') => {
+                self.chars.next();
+                let dict = self.parse_dictionary_entries(Some('}'))?;
+                Ok(Value::Dictionary(dict))
+            }
+            Some('(') => {
+                self.chars.next();
+                self.parse_array().map(Value::Array)
+            }
+            Some('<') => {
+                self.chars.next();
+                self.parse_data().map(Value::Data)
+            }
+            _ => self.parse_string().map(Value::String),
+        }
+    }
+
+    /// Parse `key = value;` entries until `terminator` (or the end of the
+    /// text, if there is no terminator).
+    fn parse_dictionary_entries(&mut self, terminator: Option<char>) -> Result<Dictionary, String> {
+        let mut dict = Dictionary::new();
+        loop {
+            self.skip_whitespace_and_comments()?;
+            match (self.peek(), terminator) {
+                (None, None) => return Ok(dict),
+                (None, Some(_)) => return Err("Unterminated dictionary".to_string()),
+                (Some(c), Some(terminator)) if c == terminator => {
+                    self.chars.next();
+                    return Ok(dict);
+                }
+                _ => (),
+            }
+            let key = self.parse_string()?;
+            self.skip_whitespace_and_comments()?;
+            let value = if self.peek() == Some('=') {
+                self.chars.next();
+                self.parse_value()?
+            } else if terminator.is_none() {
+                Value::String(key.clone())
+            } else {
+                return Err(format!("Expected '=' after key {:?}", key));
+            };
+            self.expect(';')?;
+            dict.insert(key, value);
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Vec<Value>, String> {
+        let mut array = Vec::new();
+        loop {
+            self.skip_whitespace_and_comments()?;
+            if self.peek() == Some(')') {
+                self.chars.next();
+                return Ok(array);
+            }
+            array.push(self.parse_value()?);
+            self.skip_whitespace_and_comments()?;
+            match self.chars.next() {
+                Some(',') => (),
+                Some(')') => return Ok(array),
+                Some(c) => return Err(format!("Expected ',' or ')' but found {:?}", c)),
+                None => return Err("Unterminated array".to_string()),
+            }
+        }
+    }
+
+    /// Parse hexadecimal data after the opening `<`. Whitespace is allowed
+    /// between digits.
+    fn parse_data(&mut self) -> Result<Vec<u8>, String> {
+        let mut data = Vec::new();
+        let mut high_nibble = None;
+        loop {
+            match self.chars.next() {
+                Some('>') if high_nibble.is_none() => return Ok(data),
+                Some('>') => return Err("Odd number of hex digits in data".to_string()),
+                Some(c) if c.is_whitespace() => (),
+                Some(c) => {
+                    let nibble = c
+                        .to_digit(16)
+                        .ok_or_else(|| format!("Invalid character {:?} in data", c))?
+                        as u8;
+                    match high_nibble.take() {
+                        None => high_nibble = Some(nibble),
+                        Some(high) => data.push((high << 4) | nibble),
+                    }
+                }
+                None => return Err("Unterminated data".to_string()),
+            }
+        }
+    }
+
+    /// Parse a quoted string, or an unquoted one made of the characters
+    /// allowed in unquoted strings.
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.skip_whitespace_and_comments()?;
+        let mut string = String::new();
+        if self.peek() != Some('"') {
+            while let Some(c) = self.peek() {
+                if !(c.is_ascii_alphanumeric() || "_$+/:.-".contains(c)) {
+                    break;
+                }
+                string.push(c);
+                self.chars.next();
+            }
+            if string.is_empty() {
+                return Err(match self.peek() {
+                    Some(c) => format!("Unexpected {:?}", c),
+                    None => "Unexpected end of text".to_string(),
+                });
+            }
+            return Ok(string);
+        }
+
+        self.chars.next();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(string),
+                Some('\\') => string.push(self.parse_escape()?),
+                Some(c) => string.push(c),
+                None => return Err("Unterminated string".to_string()),
+            }
+        }
+    }
+
+    /// Parse an escape sequence in a quoted string, after the backslash.
+    fn parse_escape(&mut self) -> Result<char, String> {
+        let c = match self.chars.next() {
+            Some('n') => '\n',
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('a') => '\u{7}',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('v') => '\u{b}',
+            Some('U') | Some('u') => {
+                let mut code = 0;
+                for _ in 0..4 {
+                    let Some(digit) = self.peek().and_then(|c| c.to_digit(16)) else {
+                        break;
+                    };
+                    self.chars.next();
+                    code = code * 16 + digit;
+                }
+                char::from_u32(code).ok_or_else(|| format!("Invalid escape \\U{:04x}", code))?
+            }
+            Some(c @ '0'..='7') => {
+                // Up to three octal digits. Like Apple's parser, values above
+                // 0x7F are taken to be in the NeXTSTEP encoding, but only the
+                // ASCII range is the same as Unicode, so others are rejected.
+                let mut code = c.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    let Some(digit) = self.peek().and_then(|c| c.to_digit(8)) else {
+                        break;
+                    };
+                    self.chars.next();
+                    code = code * 8 + digit;
+                }
+                if code > 0x7F {
+                    return Err(format!("Unsupported octal escape \\{:o}", code));
+                }
+                char::from_u32(code).unwrap()
+            }
+            Some(c) => c,
+            None => return Err("Unterminated string".to_string()),
+        };
+        Ok(c)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn old_style_plist() {
+        let text = b"// Config\n{\n  Name = \"Player\\t1\\101\";\n  Levels = (one, \"two\", );\n  Blob = <0fbd 7788>;\n  Nested = { a = b; };\n}\n";
+        let value = parse_plist(text).unwrap();
+        let dict = value.as_dictionary().unwrap();
+        assert_eq!(dict["Name"].as_string(), Some("Player\t1A"));
+        assert_eq!(
+            dict["Levels"].as_array().unwrap(),
+            &[Value::from("one"), Value::from("two")]
+        );
+        assert_eq!(dict["Blob"].as_data(), Some(&[0x0f, 0xbd, 0x77, 0x88][..]));
+        assert_eq!(
+            dict["Nested"].as_dictionary().unwrap()["a"].as_string(),
+            Some("b")
+        );
+    }
+
+    #[test]
+    fn utf16_with_bom() {
+        let mut bytes = vec![0xFE, 0xFF];
+        for unit in "(\"\u{e9}\")".encode_utf16() {
+            bytes.extend_from_slice(&unit.to_be_bytes());
+        }
+        assert_eq!(
+            parse_plist(&bytes).unwrap(),
+            Value::Array(vec![Value::from("\u{e9}")])
+        );
+    }
+
+    #[test]
+    fn strings() {
+        let text =
+            "/* Menu */\n\"PLAY\" = \"Jouer\";\nQUIT = \"Quitter\\n\\\"ok\\\"\";\n\"ALONE\";\n";
+        let strings = parse_strings(text).unwrap();
+        assert_eq!(strings["PLAY"].as_string(), Some("Jouer"));
+        assert_eq!(strings["QUIT"].as_string(), Some("Quitter\n\"ok\""));
+        assert_eq!(strings["ALONE"].as_string(), Some("ALONE"));
+    }
+}
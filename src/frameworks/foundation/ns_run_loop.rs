@@ -8,15 +8,21 @@
 //! Resources:
 //! - Apple's [Threading Programming Guide](https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/Multithreading/Introduction/Introduction.html)
 
-use super::{ns_string, ns_timer};
+use super::{ns_string, ns_timer, ns_url_connection};
 use crate::dyld::{ConstantExports, HostConstant};
 use crate::frameworks::audio_toolbox::audio_queue::{handle_audio_queue, AudioQueueRef};
+use crate::frameworks::audio_toolbox::audio_unit::handle_audio_units;
+use crate::frameworks::core_animation::{self, ca_display_link};
 use crate::frameworks::core_foundation::cf_run_loop::{
     kCFRunLoopCommonModes, kCFRunLoopDefaultMode, CFRunLoopRef,
 };
-use crate::frameworks::{core_animation, media_player, uikit};
-use crate::objc::{id, msg, objc_classes, release, retain, ClassExports, HostObject};
+use crate::frameworks::{media_player, uikit};
+use crate::objc::{
+    id, msg, msg_class, msg_send, objc_classes, release, retain, ClassExports, HostObject, SEL,
+};
 use crate::Environment;
+use std::cell::Cell;
+use std::rc::Rc;
 use std::time::{Duration, Instant};
 
 /// `NSString*`
@@ -39,6 +45,19 @@ pub const CONSTANTS: ConstantExports = &[
 #[derive(Default)]
 pub struct State {
     main_thread_run_loop: Option<id>,
+    /// Messages queued by `performSelectorOnMainThread:withObject:`, in the
+    /// order they should be sent.
+    main_thread_performs: Vec<MainThreadPerform>,
+}
+
+struct MainThreadPerform {
+    /// Strong reference.
+    target: id,
+    selector: SEL,
+    /// Strong reference.
+    object: id,
+    /// Set once the message has been sent.
+    done: Rc<Cell<bool>>,
 }
 
 struct NSRunLoopHostObject {
@@ -48,6 +67,9 @@ struct NSRunLoopHostObject {
     /// Strong references to `NSTimer*` in no particular order. Timers are owned
     /// by the run loop. The timer must remove itself when invalidated.
     timers: Vec<id>,
+    /// Strong references to `CADisplayLink*` in no particular order. The
+    /// display link must remove itself when invalidated.
+    display_links: Vec<id>,
 }
 impl HostObject for NSRunLoopHostObject {}
 
@@ -64,6 +86,7 @@ pub const CLASSES: ClassExports = objc_classes! {
         let host_object = Box::new(NSRunLoopHostObject {
             audio_queues: Vec::new(),
             timers: Vec::new(),
+            display_links: Vec::new(),
         });
         let new = env.objc.alloc_static_object(this, host_object, &mut env.mem);
         env.framework_state.foundation.ns_run_loop.main_thread_run_loop = Some(new);
@@ -159,6 +182,76 @@ pub(super) fn remove_timer(env: &mut Environment, run_loop: id, timer: id) {
     }
 }
 
+/// For use by `CADisplayLink`.
+/// TODO: Handle run loop modes. Currently assumes the common modes.
+pub fn add_display_link(env: &mut Environment, run_loop: id, link: id) {
+    retain(env, link);
+    env.objc
+        .borrow_mut::<NSRunLoopHostObject>(run_loop)
+        .display_links
+        .push(link);
+}
+
+/// For use by `CADisplayLink` so it can remove itself once it's invalidated.
+pub fn remove_display_link(env: &mut Environment, run_loop: id, link: id) {
+    let links = &mut env
+        .objc
+        .borrow_mut::<NSRunLoopHostObject>(run_loop)
+        .display_links;
+    let link_idx = links.iter().position(|&item| item == link).unwrap();
+    links.swap_remove(link_idx);
+    release(env, link);
+}
+
+/// For use by `NSObject` and `NSFileHandle`: queue a message to be sent on the
+/// main thread the next time its run loop runs. The returned flag is set once
+/// it has been sent.
+pub(super) fn queue_main_thread_perform(
+    env: &mut Environment,
+    target: id,
+    selector: SEL,
+    object: id,
+) -> Rc<Cell<bool>> {
+    retain(env, target);
+    retain(env, object);
+    let done = Rc::new(Cell::new(false));
+    env.framework_state
+        .foundation
+        .ns_run_loop
+        .main_thread_performs
+        .push(MainThreadPerform {
+            target,
+            selector,
+            object,
+            done: done.clone(),
+        });
+    done
+}
+
+/// Send the messages queued by [queue_main_thread_perform]. Messages queued
+/// while doing this are left for the next iteration.
+fn handle_main_thread_performs(env: &mut Environment) {
+    let performs = std::mem::take(
+        &mut env
+            .framework_state
+            .foundation
+            .ns_run_loop
+            .main_thread_performs,
+    );
+    for MainThreadPerform {
+        target,
+        selector,
+        object,
+        done,
+    } in performs
+    {
+        () = msg_send(env, (target, selector, object));
+        release(env, target);
+        release(env, object);
+        done.set(true);
+    }
+}
+
 /// Run the run loop for just a single iteration. This is a special mode just
 /// for the app picker, since we don't have `runMode:beforeDate:` or
 /// `runUntilDate:` yet. (TODO: implement those to replace this.)
@@ -176,6 +269,7 @@ fn run_run_loop(env: &mut Environment, run_loop: id, single_iteration: bool) {
     // Temporary vectors used to track things without needing a reference to the
     // environment or to lock the object. Re-used each iteration for efficiency.
     let mut timers_tmp = Vec::new();
+    let mut display_links_tmp = Vec::new();
     let mut audio_queues_tmp = Vec::new();
 
     fn limit_sleep_time(current: &mut Option<Instant>, new: Option<Instant>) {
@@ -187,6 +281,11 @@ fn run_run_loop(env: &mut Environment, run_loop: id, single_iteration: bool) {
     loop {
         let mut sleep_until = None;
 
+        // Like on Apple platforms, each iteration gets its own autorelease
+        // pool, so objects autoreleased by timers, display links and host code
+        // outside of event handling don't build up.
+        let pool: id = msg_class![env; NSAutoreleasePool new];
+
         env.window
             .as_mut()
             .expect("NSRunLoop not supported in headless mode")
@@ -198,6 +297,10 @@ fn run_run_loop(env: &mut Environment, run_loop: id, single_iteration: bool) {
         let next_due = core_animation::recomposite_if_necessary(env);
         limit_sleep_time(&mut sleep_until, next_due);
 
+        handle_main_thread_performs(env);
+
+        ns_url_connection::handle_transfers(env);
+
         assert!(timers_tmp.is_empty());
         timers_tmp.extend_from_slice(&env.objc.borrow::<NSRunLoopHostObject>(run_loop).timers);
 
@@ -206,6 +309,18 @@ fn run_run_loop(env: &mut Environment, run_loop: id, single_iteration: bool) {
             limit_sleep_time(&mut sleep_until, next_due);
         }
 
+        assert!(display_links_tmp.is_empty());
+        display_links_tmp.extend_from_slice(
+            &env.objc
+                .borrow::<NSRunLoopHostObject>(run_loop)
+                .display_links,
+        );
+
+        for link in display_links_tmp.drain(..) {
+            let next_due = ca_display_link::handle_display_link(env, link);
+            limit_sleep_time(&mut sleep_until, next_due);
+        }
+
         assert!(audio_queues_tmp.is_empty());
         audio_queues_tmp.extend_from_slice(
             &env.objc
@@ -217,8 +332,12 @@ fn run_run_loop(env: &mut Environment, run_loop: id, single_iteration: bool) {
             handle_audio_queue(env, audio_queue);
         }
 
+        handle_audio_units(env);
+
         media_player::handle_players(env);
 
+        release(env, pool);
+
         // Unfortunately, touchHLE has to poll for certain things repeatedly;
         // it can't just wait until the next event appears.
         //
@@ -19,7 +19,11 @@ use crate::objc::{
 struct SetHostObject {
     dict: DictionaryHostObject,
 }
-impl HostObject for SetHostObject {}
+impl HostObject for SetHostObject {
+    fn strong_references(&self, references: &mut Vec<id>) {
+        self.dict.strong_references(references);
+    }
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
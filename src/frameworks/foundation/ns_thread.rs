@@ -5,29 +5,83 @@
  */
 //! `NSThread`.
 
-use super::NSTimeInterval;
-use crate::dyld::HostFunction;
+use super::{ns_string, NSTimeInterval, NSUInteger};
+use crate::dyld::{ConstantExports, HostConstant, HostFunction};
 use crate::frameworks::core_foundation::CFTypeRef;
 use crate::libc::pthread::thread::{
-    pthread_attr_init, pthread_attr_setdetachstate, pthread_attr_t, pthread_create, pthread_t,
-    PTHREAD_CREATE_DETACHED,
+    pthread_attr_init, pthread_attr_setdetachstate, pthread_attr_setstacksize, pthread_attr_t,
+    pthread_create, pthread_t, PAGE_SIZE, PTHREAD_CREATE_DETACHED, PTHREAD_STACK_MIN,
 };
-use crate::mem::{guest_size_of, MutPtr};
-use crate::msg;
+use crate::mem::{guest_size_of, GuestUSize, Mem, MutPtr, Ptr};
 use crate::objc::{
     id, msg_send, nil, objc_classes, release, retain, Class, ClassExports, HostObject, NSZonePtr,
     SEL,
 };
-use crate::Environment;
+use crate::{msg, msg_class};
+use crate::{Environment, ThreadId, ThreadPriority};
+use std::collections::HashMap;
 use std::time::Duration;
 
+pub const NSWillBecomeMultiThreadedNotification: &str = "NSWillBecomeMultiThreadedNotification";
+pub const NSDidBecomeSingleThreadedNotification: &str = "NSDidBecomeSingleThreadedNotification";
+pub const NSThreadWillExitNotification: &str = "NSThreadWillExitNotification";
+
+/// `NSNotificationName` values.
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_NSWillBecomeMultiThreadedNotification",
+        HostConstant::NSString(NSWillBecomeMultiThreadedNotification),
+    ),
+    (
+        "_NSDidBecomeSingleThreadedNotification",
+        HostConstant::NSString(NSDidBecomeSingleThreadedNotification),
+    ),
+    (
+        "_NSThreadWillExitNotification",
+        HostConstant::NSString(NSThreadWillExitNotification),
+    ),
+];
+
+#[derive(Default)]
+pub struct State {
+    /// Set once the first `NSThread` has been started.
+    is_multi_threaded: bool,
+    /// The `NSThread*` for each running thread (strong references). Threads not
+    /// started by `NSThread` get theirs on demand, see [thread_object].
+    threads: HashMap<ThreadId, id>,
+}
+impl State {
+    fn get(env: &mut Environment) -> &mut Self {
+        &mut env.framework_state.foundation.ns_thread
+    }
+}
+
 struct NSThreadHostObject {
     target: id,
     selector: Option<SEL>,
     object: id,
+    /// `NSString*`
+    name: id,
+    /// Size of the thread's stack in bytes. This can be changed until the
+    /// thread is started.
+    stack_size: GuestUSize,
+    /// Set once the thread has started running.
+    thread_id: Option<ThreadId>,
+    /// Set once the thread's selector has returned.
+    finished: bool,
+    /// Set by `-cancel`. It's up to the thread to check this.
+    cancelled: bool,
+    /// Priority to use once the thread starts. After that, the scheduler's
+    /// priority for the thread is used instead (see [ThreadPriority]).
+    priority: f64,
+    /// `NSMutableDictionary*`, created on demand.
+    thread_dictionary: id,
 }
 impl HostObject for NSThreadHostObject {}
 
+/// Priority of a new thread, in the range 0.0 to 1.0.
+const DEFAULT_PRIORITY: f64 = 0.5;
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -39,25 +93,50 @@ pub const CLASSES: ClassExports = objc_classes! {
         target: nil,
         selector: None,
         object: nil,
+        name: nil,
+        stack_size: Mem::SECONDARY_THREAD_STACK_SIZE,
+        thread_id: None,
+        finished: false,
+        cancelled: false,
+        priority: DEFAULT_PRIORITY,
+        thread_dictionary: nil,
     });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
 
++ (bool)isMainThread {
+    env.current_thread == 0
+}
+
++ (id)mainThread {
+    thread_object(env, 0)
+}
+
++ (bool)isMultiThreaded {
+    State::get(env).is_multi_threaded
+}
+
++ (())exit {
+    log_dbg!("[NSThread exit] on thread {}", env.current_thread);
+    // The NSThread object and thread-specific data are cleaned up in the same
+    // way as when the thread returns normally, see [handle_thread_exit].
+    env.exit_current_thread(Ptr::null());
+}
+
 + (f64)threadPriority {
-    log!("TODO: [NSThread threadPriority] (not implemented yet)");
-    1.0
+    let current: id = msg![env; this currentThread];
+    msg![env; current threadPriority]
 }
 
 + (bool)setThreadPriority:(f64)priority {
-    log!("TODO: [NSThread setThreadPriority:{:?}] (ignored)", priority);
+    let current: id = msg![env; this currentThread];
+    () = msg![env; current setThreadPriority:priority];
     true
 }
 
 + (id)currentThread {
-    // Simple hack to make the `setThreadPriority:` work as an instance method
-    // (it's both a class and an instance method). Must be replaced if we ever
-    // need to support other methods.
-    this
+    let current_thread = env.current_thread;
+    thread_object(env, current_thread)
 }
 
 + (())sleepForTimeInterval:(NSTimeInterval)ti {
@@ -68,16 +147,153 @@ pub const CLASSES: ClassExports = objc_classes! {
 + (())detachNewThreadSelector:(SEL)selector
                        toTarget:(id)target
                      withObject:(id)object {
-    let host_object = Box::new(NSThreadHostObject {
-        target,
-        selector: Some(selector),
-        object,
-    });
-    let this = env.objc.alloc_object(this, host_object, &mut env.mem);
-    retain(env, this);
+    // This goes through -start, so the thread gets the same default stack
+    // size as one created with -initWithTarget:selector:object:.
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithTarget:target selector:selector object:object];
+    () = msg![env; new start];
+    release(env, new);
+}
 
+- (id)initWithTarget:(id)target
+            selector:(SEL)selector
+              object:(id)object {
     retain(env, target);
     retain(env, object);
+    let host_object = env.objc.borrow_mut::<NSThreadHostObject>(this);
+    host_object.target = target;
+    host_object.selector = Some(selector);
+    host_object.object = object;
+    this
+}
+
+- (())dealloc {
+    let &NSThreadHostObject {
+        target,
+        object,
+        name,
+        thread_dictionary,
+        ..
+    } = env.objc.borrow(this);
+    release(env, target);
+    release(env, object);
+    release(env, name);
+    release(env, thread_dictionary);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (bool)isMainThread {
+    env.objc.borrow::<NSThreadHostObject>(this).thread_id == Some(0)
+}
+
+- (f64)threadPriority {
+    let host_object = env.objc.borrow::<NSThreadHostObject>(this);
+    match host_object.thread_id {
+        Some(thread_id) if !host_object.finished => match env.threads[thread_id].priority {
+            ThreadPriority::Normal(priority) => priority,
+            ThreadPriority::TimeConstraint => 1.0,
+        },
+        _ => host_object.priority,
+    }
+}
+
+- (())setThreadPriority:(f64)priority {
+    log_dbg!("[{:?} setThreadPriority:{:?}]", this, priority);
+    let priority = priority.clamp(0.0, 1.0);
+    let host_object = env.objc.borrow_mut::<NSThreadHostObject>(this);
+    host_object.priority = priority;
+    if let Some(thread_id) = host_object.thread_id {
+        if !host_object.finished {
+            env.threads[thread_id].priority = ThreadPriority::Normal(priority);
+        }
+    }
+}
+
+- (())cancel {
+    env.objc.borrow_mut::<NSThreadHostObject>(this).cancelled = true;
+}
+
+- (bool)isCancelled {
+    env.objc.borrow::<NSThreadHostObject>(this).cancelled
+}
+
+- (bool)isExecuting {
+    let host_object = env.objc.borrow::<NSThreadHostObject>(this);
+    host_object.thread_id.is_some() && !host_object.finished
+}
+
+- (bool)isFinished {
+    env.objc.borrow::<NSThreadHostObject>(this).finished
+}
+
+- (id)threadDictionary {
+    let existing = env.objc.borrow::<NSThreadHostObject>(this).thread_dictionary;
+    if existing != nil {
+        return existing;
+    }
+    let new: id = msg_class![env; NSMutableDictionary new];
+    env.objc.borrow_mut::<NSThreadHostObject>(this).thread_dictionary = new;
+    new
+}
+
+- (id)name {
+    env.objc.borrow::<NSThreadHostObject>(this).name
+}
+- (())setName:(id)name { // NSString*
+    let name: id = msg![env; name copy];
+    let host_object = env.objc.borrow_mut::<NSThreadHostObject>(this);
+    let old_name = std::mem::replace(&mut host_object.name, name);
+    let thread_id = host_object.thread_id;
+    release(env, old_name);
+    if let Some(thread_id) = thread_id {
+        set_host_thread_name(env, thread_id, name);
+    }
+}
+
+- (NSUInteger)stackSize {
+    env.objc.borrow::<NSThreadHostObject>(this).stack_size
+}
+- (())setStackSize:(NSUInteger)stack_size {
+    let host_object = env.objc.borrow_mut::<NSThreadHostObject>(this);
+    if host_object.thread_id.is_some() {
+        log!(
+            "Warning: [{:?} setStackSize:{:#x}] ignored, the thread has already started",
+            this,
+            stack_size
+        );
+        return;
+    }
+    // Apple's documentation says the size must be a multiple of 4KiB, but
+    // middleware that wants a big stack for deep recursion isn't always that
+    // careful, and silently getting the (much smaller) default is worse than
+    // getting slightly more than asked for. pthreads would reject the size, so
+    // round it up here, which also means -stackSize gives the real size.
+    let rounded = stack_size
+        .max(PTHREAD_STACK_MIN)
+        .div_ceil(PAGE_SIZE)
+        .saturating_mul(PAGE_SIZE);
+    log_dbg!("[{:?} setStackSize:{:#x}] => {:#x}", this, stack_size, rounded);
+    host_object.stack_size = rounded;
+}
+
+- (())start {
+    let &NSThreadHostObject {
+        selector,
+        stack_size,
+        thread_id,
+        ..
+    } = env.objc.borrow(this);
+    assert!(selector.is_some()); // TODO: subclasses overriding -main
+    assert!(thread_id.is_none()); // should raise NSInvalidArgumentException
+
+    if !State::get(env).is_multi_threaded {
+        State::get(env).is_multi_threaded = true;
+        // Sent only once, before the first thread is started.
+        post_notification(env, NSWillBecomeMultiThreadedNotification, nil);
+    }
+
+    // Released by the thread when it finishes.
+    retain(env, this);
 
     let symb = "__touchHLE_NSThreadInvocationHelper";
     let hf: HostFunction = &(_touchHLE_NSThreadInvocationHelper as fn(&mut Environment, _) -> _);
@@ -89,15 +305,16 @@ pub const CLASSES: ClassExports = objc_classes! {
     pthread_attr_init(env, attr);
 
     pthread_attr_setdetachstate(env, attr, PTHREAD_CREATE_DETACHED);
+    // If the size is invalid, this logs a warning and the default is kept.
+    pthread_attr_setstacksize(env, attr, stack_size);
     let thread_ptr: MutPtr<pthread_t> = env.mem.alloc(guest_size_of::<pthread_t>()).cast();
 
     pthread_create(env, thread_ptr, attr.cast_const(), gf, this.cast());
 
-    // TODO: post NSWillBecomeMultiThreadedNotification
+    env.mem.free(thread_ptr.cast());
+    env.mem.free(attr.cast());
 }
 
-// TODO: construction etc
-
 @end
 
 };
@@ -112,17 +329,94 @@ pub fn _touchHLE_NSThreadInvocationHelper(env: &mut Environment, ns_thread_obj:
     );
     assert_eq!(class, env.objc.get_known_class("NSThread", &mut env.mem));
 
-    let &NSThreadHostObject {
+    let current_thread = env.current_thread;
+    let host_object = env.objc.borrow_mut::<NSThreadHostObject>(ns_thread_obj);
+    host_object.thread_id = Some(current_thread);
+    // This takes over the reference that -start made. It is released when the
+    // thread exits, see [handle_thread_exit].
+    State::get(env)
+        .threads
+        .insert(current_thread, ns_thread_obj);
+    let &mut NSThreadHostObject {
         target,
         selector,
         object,
-    } = env.objc.borrow(ns_thread_obj);
+        name,
+        priority,
+        ..
+    } = host_object;
+    env.threads[current_thread].priority = ThreadPriority::Normal(priority);
+    if name != nil {
+        set_host_thread_name(env, current_thread, name);
+    }
+
+    // The thread exits once this returns (or earlier, if the method calls
+    // +[NSThread exit]), see [handle_thread_exit].
     () = msg_send(env, (target, selector.unwrap(), object));
+}
 
-    release(env, object);
-    release(env, target);
+/// Called when a thread is about to exit, whether or not it was started by
+/// `NSThread`, and however it exits. Posts `NSThreadWillExitNotification`,
+/// marks its `NSThread*` as finished, and releases it and its thread
+/// dictionary. This may run guest code (e.g. observers and `dealloc`
+/// methods), so the thread must still exist.
+pub fn handle_thread_exit(env: &mut Environment) {
+    let current_thread = env.current_thread;
+    let Some(&ns_thread_obj) = State::get(env).threads.get(&current_thread) else {
+        return;
+    };
+
+    // Observers receive this on the exiting thread, before it exits.
+    post_notification(env, NSThreadWillExitNotification, ns_thread_obj);
 
+    State::get(env).threads.remove(&current_thread);
+    env.objc
+        .borrow_mut::<NSThreadHostObject>(ns_thread_obj)
+        .finished = true;
+    let thread_dictionary = std::mem::replace(
+        &mut env
+            .objc
+            .borrow_mut::<NSThreadHostObject>(ns_thread_obj)
+            .thread_dictionary,
+        nil,
+    );
+    release(env, thread_dictionary);
     release(env, ns_thread_obj);
+}
+
+/// Get the `NSThread*` for a thread, creating it if necessary (for the main
+/// thread or threads not started by `NSThread`).
+fn thread_object(env: &mut Environment, thread_id: ThreadId) -> id {
+    if let Some(&existing) = State::get(env).threads.get(&thread_id) {
+        return existing;
+    }
+
+    let class = env.objc.get_known_class("NSThread", &mut env.mem);
+    let new: id = msg![env; class alloc];
+    let host_object = env.objc.borrow_mut::<NSThreadHostObject>(new);
+    host_object.thread_id = Some(thread_id);
+    // The main thread in particular has a bigger stack than the default.
+    if let Some(stack_size) = env.threads[thread_id].stack_size() {
+        host_object.stack_size = stack_size;
+    }
+    log_dbg!("Created NSThread object {:?} for thread {}", new, thread_id);
+    State::get(env).threads.insert(thread_id, new);
+    new
+}
+
+fn post_notification(env: &mut Environment, name: &'static str, object: id) {
+    let name = ns_string::get_static_str(env, name);
+    let center: id = msg_class![env; NSNotificationCenter defaultCenter];
+    () = msg![env; center postNotificationName:name object:object];
+}
 
-    // TODO: NSThread exit
+/// Make the thread's name visible in touchHLE's debugging output.
+fn set_host_thread_name(env: &mut Environment, thread_id: ThreadId, name: id) {
+    let name = if name == nil {
+        None
+    } else {
+        Some(ns_string::to_rust_string(env, name).into_owned())
+    };
+    log_dbg!("Thread {} name set to {:?}", thread_id, name);
+    env.threads[thread_id].name = name;
 }
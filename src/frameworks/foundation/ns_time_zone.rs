@@ -0,0 +1,239 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSTimeZone`.
+//!
+//! Like the rest of touchHLE's date and time code (e.g. `localtime()`), the
+//! system time zone is GMT. Apps can still create time zones with a fixed
+//! offset from GMT, but there's no time zone database, so named time zones
+//! other than GMT/UTC have no offset and no daylight saving time.
+
+use super::{ns_string, NSInteger, NSTimeInterval};
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+
+#[derive(Default)]
+pub struct State {
+    system_time_zone: Option<id>,
+    default_time_zone: Option<id>,
+}
+impl State {
+    fn get(env: &mut Environment) -> &mut State {
+        &mut env.framework_state.foundation.ns_time_zone
+    }
+}
+
+struct NSTimeZoneHostObject {
+    name: String,
+    seconds_from_gmt: NSInteger,
+}
+impl HostObject for NSTimeZoneHostObject {}
+
+/// Parse the name or abbreviation of a time zone with a fixed offset from GMT,
+/// e.g. `GMT`, `UTC`, `GMT+1`, `GMT-0530` or `UTC+05:30`.
+fn parse_gmt_offset(name: &str) -> Option<NSInteger> {
+    let offset = name
+        .strip_prefix("GMT")
+        .or_else(|| name.strip_prefix("UTC"))?;
+    if offset.is_empty() {
+        return Some(0);
+    }
+    let (sign, offset) = match offset.as_bytes()[0] {
+        b'+' => (1, &offset[1..]),
+        b'-' => (-1, &offset[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = match offset.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if offset.len() > 2 => offset.split_at(offset.len() - 2),
+        None => (offset, "0"),
+    };
+    let hours: NSInteger = hours.parse().ok()?;
+    let minutes: NSInteger = minutes.parse().ok()?;
+    if !(0..=18).contains(&hours) || !(0..60).contains(&minutes) {
+        return None;
+    }
+    Some(sign * (hours * 60 + minutes) * 60)
+}
+
+/// The name Apple uses for a time zone with a fixed offset, e.g. `GMT+0530`.
+fn gmt_offset_name(seconds_from_gmt: NSInteger) -> String {
+    if seconds_from_gmt == 0 {
+        return "GMT".to_string();
+    }
+    let sign = if seconds_from_gmt < 0 { '-' } else { '+' };
+    let minutes = seconds_from_gmt.abs() / 60;
+    format!("GMT{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+}
+
+/// Create a new `NSTimeZone*` (retained).
+fn new_time_zone(env: &mut Environment, name: String, seconds_from_gmt: NSInteger) -> id {
+    let new: id = msg_class![env; NSTimeZone alloc];
+    let host_object = env.objc.borrow_mut::<NSTimeZoneHostObject>(new);
+    host_object.name = name;
+    host_object.seconds_from_gmt = seconds_from_gmt;
+    new
+}
+
+/// The offset from GMT in seconds for a time zone (`NSTimeZone*` or
+/// `CFTimeZoneRef`). `nil` means GMT.
+pub fn seconds_from_gmt(env: &mut Environment, time_zone: id) -> NSInteger {
+    if time_zone == nil {
+        return 0;
+    }
+    env.objc
+        .borrow::<NSTimeZoneHostObject>(time_zone)
+        .seconds_from_gmt
+}
+
+/// Shortcut for host code: get the default time zone (`NSTimeZone*`).
+pub fn default_time_zone(env: &mut Environment) -> id {
+    msg_class![env; NSTimeZone defaultTimeZone]
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSTimeZone: NSObject
+
++ (id)systemTimeZone {
+    if let Some(existing) = State::get(env).system_time_zone {
+        return existing;
+    }
+    let new = new_time_zone(env, "GMT".to_string(), 0);
+    State::get(env).system_time_zone = Some(new);
+    new
+}
++ (())resetSystemTimeZone {
+    // The system time zone never changes.
+}
+
++ (id)defaultTimeZone {
+    if let Some(existing) = State::get(env).default_time_zone {
+        return existing;
+    }
+    msg![env; this systemTimeZone]
+}
++ (())setDefaultTimeZone:(id)time_zone { // NSTimeZone*
+    let time_zone: id = msg![env; time_zone copy];
+    if let Some(old) = State::get(env).default_time_zone.replace(time_zone) {
+        release(env, old);
+    }
+}
+
+// TODO: This should update automatically when the default time zone changes.
++ (id)localTimeZone {
+    msg![env; this defaultTimeZone]
+}
+
++ (id)timeZoneWithName:(id)name { // NSString*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithName:name];
+    autorelease(env, new)
+}
+
++ (id)timeZoneWithAbbreviation:(id)abbreviation { // NSString*
+    let abbreviation = ns_string::to_rust_string(env, abbreviation);
+    let Some(seconds_from_gmt) = parse_gmt_offset(&abbreviation) else {
+        log!("TODO: Unknown time zone abbreviation {:?}, returning nil", abbreviation);
+        return nil;
+    };
+    msg![env; this timeZoneForSecondsFromGMT:seconds_from_gmt]
+}
+
++ (id)timeZoneForSecondsFromGMT:(NSInteger)seconds {
+    let new = new_time_zone(env, gmt_offset_name(seconds), seconds);
+    autorelease(env, new)
+}
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSTimeZoneHostObject {
+        name: String::new(),
+        seconds_from_gmt: 0,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithName:(id)name { // NSString*
+    let name = ns_string::to_rust_string(env, name).into_owned();
+    let seconds_from_gmt = match parse_gmt_offset(&name) {
+        Some(seconds_from_gmt) => seconds_from_gmt,
+        None => {
+            log!("TODO: No time zone database, treating {:?} as GMT", name);
+            0
+        }
+    };
+    let host_object = env.objc.borrow_mut::<NSTimeZoneHostObject>(this);
+    host_object.name = name;
+    host_object.seconds_from_gmt = seconds_from_gmt;
+    this
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    retain(env, this)
+}
+
+- (id)name {
+    let name = env.objc.borrow::<NSTimeZoneHostObject>(this).name.clone();
+    let name = ns_string::from_rust_string(env, name);
+    autorelease(env, name)
+}
+
+- (id)abbreviation {
+    let seconds_from_gmt = seconds_from_gmt(env, this);
+    let abbreviation = ns_string::from_rust_string(env, gmt_offset_name(seconds_from_gmt));
+    autorelease(env, abbreviation)
+}
+- (id)abbreviationForDate:(id)_date { // NSDate*
+    msg![env; this abbreviation]
+}
+
+- (NSInteger)secondsFromGMT {
+    seconds_from_gmt(env, this)
+}
+- (NSInteger)secondsFromGMTForDate:(id)_date { // NSDate*
+    seconds_from_gmt(env, this)
+}
+
+- (bool)isDaylightSavingTime {
+    false
+}
+- (bool)isDaylightSavingTimeForDate:(id)_date { // NSDate*
+    false
+}
+- (NSTimeInterval)daylightSavingTimeOffset {
+    0.0
+}
+
+- (bool)isEqualToTimeZone:(id)other { // NSTimeZone*
+    if other == nil {
+        return false;
+    }
+    let this_host_object = env.objc.borrow::<NSTimeZoneHostObject>(this);
+    let other_host_object = env.objc.borrow::<NSTimeZoneHostObject>(other);
+    this_host_object.name == other_host_object.name
+        && this_host_object.seconds_from_gmt == other_host_object.seconds_from_gmt
+}
+
+- (id)description {
+    let host_object = env.objc.borrow::<NSTimeZoneHostObject>(this);
+    let description = format!(
+        "{} ({}) offset {}",
+        host_object.name,
+        gmt_offset_name(host_object.seconds_from_gmt),
+        host_object.seconds_from_gmt
+    );
+    let description = ns_string::from_rust_string(env, description);
+    autorelease(env, description)
+}
+
+@end
+
+};
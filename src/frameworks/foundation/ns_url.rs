@@ -97,7 +97,11 @@ pub const CLASSES: ClassExports = objc_classes! {
     }
 
     // FIXME: this should parse the URL
-    assert!(!to_rust_string(env, url).starts_with("file:")); // TODO
+    if let Some(path) = file_url_to_path(&to_rust_string(env, url)) {
+        let path = from_rust_string(env, path);
+        *env.objc.borrow_mut(this) = NSURLHostObject::FileURL { ns_string: path, working_directory: env.fs.working_directory().into() };
+        return this;
+    }
     let url: id = msg![env; url copy];
     *env.objc.borrow_mut(this) = NSURLHostObject::OtherURL { ns_string: url };
     this
@@ -117,6 +121,10 @@ pub const CLASSES: ClassExports = objc_classes! {
     }
 }
 
+- (bool)isFileURL {
+    matches!(env.objc.borrow(this), NSURLHostObject::FileURL { .. })
+}
+
 - (id)path {
     match *env.objc.borrow(this) {
         NSURLHostObject::FileURL { ns_string, .. } => ns_string,
@@ -166,6 +174,51 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
+/// Get the path from a `file:` URL string, e.g. `file:///foo/bar%20baz`
+/// becomes `/foo/bar baz`. Returns [None] if the string isn't a `file:` URL
+/// with an absolute path.
+pub fn file_url_to_path(url: &str) -> Option<String> {
+    let (scheme, rest) = url.split_once(':')?;
+    if !scheme.eq_ignore_ascii_case("file") {
+        return None;
+    }
+    // The host is usually empty, but "localhost" is also allowed.
+    let rest = rest.strip_prefix("//").map_or(rest, |authority_and_path| {
+        authority_and_path
+            .strip_prefix("localhost")
+            .unwrap_or(authority_and_path)
+    });
+    if !rest.starts_with('/') {
+        return None;
+    }
+    let path = rest.split(['?', '#']).next().unwrap();
+    Some(percent_decode(path))
+}
+
+/// Replace `%XX` escapes in a URL component with the bytes they represent.
+/// Malformed escapes are left as they are.
+pub fn percent_decode(component: &str) -> String {
+    let bytes = component.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let Some(byte) = escaped {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
 /// Shortcut for host code, provides a view of a URL as a path.
 /// TODO: Try to avoid allocating a new GuestPathBuf in more cases.
 pub fn to_rust_path(env: &mut Environment, url: id) -> Cow<'static, GuestPath> {
@@ -0,0 +1,323 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSURLCache` and `NSCachedURLResponse`.
+//!
+//! touchHLE doesn't fetch anything over the network, so the cache only ever
+//! contains what the app stores in it explicitly. Apps mostly use this class to
+//! change the capacities of the shared cache.
+//!
+//! The cache is only kept in memory, and the least recently stored responses
+//! are evicted to stay within the memory capacity. Writing it to the disk path
+//! and validating responses with `Cache-Control` and `ETag` are deferred until
+//! touchHLE can make real HTTP requests, because until then nothing would ever
+//! be revalidated or reloaded.
+
+use super::{ns_string, NSUInteger};
+use crate::objc::{
+    autorelease, id, msg, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
+};
+use crate::Environment;
+
+/// The capacities of the shared cache on a real device.
+const DEFAULT_MEMORY_CAPACITY: NSUInteger = 512 * 1024;
+const DEFAULT_DISK_CAPACITY: NSUInteger = 10 * 1024 * 1024;
+
+type NSURLCacheStoragePolicy = NSUInteger;
+const NSURLCacheStorageAllowed: NSURLCacheStoragePolicy = 0;
+#[allow(dead_code)]
+const NSURLCacheStorageAllowedInMemoryOnly: NSURLCacheStoragePolicy = 1;
+const NSURLCacheStorageNotAllowed: NSURLCacheStoragePolicy = 2;
+
+#[derive(Default)]
+pub struct State {
+    shared_cache: Option<id>,
+}
+
+struct CacheEntry {
+    /// The absolute URL string of the request.
+    key: String,
+    /// `NSCachedURLResponse*`
+    response: id,
+    /// Size of the response's data, in bytes.
+    size: NSUInteger,
+}
+
+struct NSURLCacheHostObject {
+    memory_capacity: NSUInteger,
+    disk_capacity: NSUInteger,
+    /// `NSString*`
+    disk_path: id,
+    /// Least recently stored first.
+    entries: Vec<CacheEntry>,
+}
+impl HostObject for NSURLCacheHostObject {}
+impl NSURLCacheHostObject {
+    fn memory_usage(&self) -> NSUInteger {
+        self.entries.iter().map(|entry| entry.size).sum()
+    }
+}
+
+struct NSCachedURLResponseHostObject {
+    /// `NSURLResponse*`
+    response: id,
+    /// `NSData*`
+    data: id,
+    /// `NSDictionary*`
+    user_info: id,
+    storage_policy: NSURLCacheStoragePolicy,
+}
+impl HostObject for NSCachedURLResponseHostObject {}
+
+/// The key for a request's cached response.
+fn cache_key(env: &mut Environment, request: id) -> String {
+    let url: id = msg![env; request URL];
+    let url_string: id = msg![env; url absoluteString];
+    ns_string::to_rust_string(env, url_string).into_owned()
+}
+
+/// Evict the least recently stored responses until the memory usage is within
+/// the capacity.
+fn evict_to_capacity(env: &mut Environment, cache: id) {
+    let host_object = env.objc.borrow_mut::<NSURLCacheHostObject>(cache);
+    let mut usage = host_object.memory_usage();
+    let mut evict_count = 0;
+    for entry in &host_object.entries {
+        if usage <= host_object.memory_capacity {
+            break;
+        }
+        usage -= entry.size;
+        evict_count += 1;
+    }
+    let evicted: Vec<CacheEntry> = host_object.entries.drain(..evict_count).collect();
+    for entry in evicted {
+        log_dbg!(
+            "NSURLCache {:?} evicting response for {:?}",
+            cache,
+            entry.key
+        );
+        release(env, entry.response);
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSCachedURLResponse: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSCachedURLResponseHostObject {
+        response: nil,
+        data: nil,
+        user_info: nil,
+        storage_policy: NSURLCacheStorageAllowed,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithResponse:(id)response // NSURLResponse*
+                  data:(id)data { // NSData*
+    msg![env; this initWithResponse:response
+                               data:data
+                           userInfo:nil
+                      storagePolicy:NSURLCacheStorageAllowed]
+}
+
+- (id)initWithResponse:(id)response // NSURLResponse*
+                  data:(id)data // NSData*
+              userInfo:(id)user_info // NSDictionary*
+         storagePolicy:(NSURLCacheStoragePolicy)storage_policy {
+    retain(env, response);
+    retain(env, data);
+    retain(env, user_info);
+    let host_object = env.objc.borrow_mut::<NSCachedURLResponseHostObject>(this);
+    host_object.response = response;
+    host_object.data = data;
+    host_object.user_info = user_info;
+    host_object.storage_policy = storage_policy;
+    this
+}
+
+- (())dealloc {
+    let &NSCachedURLResponseHostObject {
+        response,
+        data,
+        user_info,
+        ..
+    } = env.objc.borrow(this);
+    release(env, response);
+    release(env, data);
+    release(env, user_info);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    // This class is immutable.
+    retain(env, this)
+}
+
+- (id)response {
+    env.objc.borrow::<NSCachedURLResponseHostObject>(this).response
+}
+- (id)data {
+    env.objc.borrow::<NSCachedURLResponseHostObject>(this).data
+}
+- (id)userInfo {
+    env.objc.borrow::<NSCachedURLResponseHostObject>(this).user_info
+}
+- (NSURLCacheStoragePolicy)storagePolicy {
+    env.objc.borrow::<NSCachedURLResponseHostObject>(this).storage_policy
+}
+
+@end
+
+@implementation NSURLCache: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSURLCacheHostObject {
+        memory_capacity: 0,
+        disk_capacity: 0,
+        disk_path: nil,
+        entries: Vec::new(),
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)sharedURLCache {
+    if let Some(existing) = env.framework_state.foundation.ns_url_cache.shared_cache {
+        return existing;
+    }
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithMemoryCapacity:DEFAULT_MEMORY_CAPACITY
+                                          diskCapacity:DEFAULT_DISK_CAPACITY
+                                              diskPath:nil];
+    env.framework_state.foundation.ns_url_cache.shared_cache = Some(new);
+    new
+}
+
++ (())setSharedURLCache:(id)cache { // NSURLCache*
+    retain(env, cache);
+    let old = env.framework_state.foundation.ns_url_cache.shared_cache.replace(cache);
+    if let Some(old) = old {
+        release(env, old);
+    }
+}
+
+- (id)initWithMemoryCapacity:(NSUInteger)memory_capacity
+                diskCapacity:(NSUInteger)disk_capacity
+                    diskPath:(id)disk_path { // NSString*
+    log_dbg!(
+        "[(NSURLCache*){:?} initWithMemoryCapacity:{} diskCapacity:{} diskPath:{:?}]",
+        this,
+        memory_capacity,
+        disk_capacity,
+        disk_path
+    );
+    let disk_path: id = msg![env; disk_path copy];
+    let host_object = env.objc.borrow_mut::<NSURLCacheHostObject>(this);
+    host_object.memory_capacity = memory_capacity;
+    host_object.disk_capacity = disk_capacity;
+    host_object.disk_path = disk_path;
+    this
+}
+
+- (())dealloc {
+    let host_object = env.objc.borrow_mut::<NSURLCacheHostObject>(this);
+    let disk_path = host_object.disk_path;
+    let entries = std::mem::take(&mut host_object.entries);
+    release(env, disk_path);
+    for entry in entries {
+        release(env, entry.response);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (NSUInteger)memoryCapacity {
+    env.objc.borrow::<NSURLCacheHostObject>(this).memory_capacity
+}
+- (())setMemoryCapacity:(NSUInteger)capacity {
+    env.objc.borrow_mut::<NSURLCacheHostObject>(this).memory_capacity = capacity;
+    evict_to_capacity(env, this);
+}
+
+- (NSUInteger)diskCapacity {
+    env.objc.borrow::<NSURLCacheHostObject>(this).disk_capacity
+}
+- (())setDiskCapacity:(NSUInteger)capacity {
+    env.objc.borrow_mut::<NSURLCacheHostObject>(this).disk_capacity = capacity;
+}
+
+- (NSUInteger)currentMemoryUsage {
+    env.objc.borrow::<NSURLCacheHostObject>(this).memory_usage()
+}
+- (NSUInteger)currentDiskUsage {
+    // Nothing is written to disk yet, see the module docs.
+    0
+}
+
+- (id)cachedResponseForRequest:(id)request { // NSURLRequest*
+    let key = cache_key(env, request);
+    let response = env
+        .objc
+        .borrow::<NSURLCacheHostObject>(this)
+        .entries
+        .iter()
+        .find(|entry| entry.key == key)
+        .map_or(nil, |entry| entry.response);
+    log_dbg!("[(NSURLCache*){:?} cachedResponseForRequest:{:?}] ({:?}) => {:?}", this, request, key, response);
+    // The response must survive being removed from the cache while the caller
+    // is still using it.
+    retain(env, response);
+    autorelease(env, response)
+}
+
+- (())storeCachedResponse:(id)response // NSCachedURLResponse*
+               forRequest:(id)request { // NSURLRequest*
+    let key = cache_key(env, request);
+    log_dbg!("[(NSURLCache*){:?} storeCachedResponse:{:?} forRequest:{:?}] ({:?})", this, response, request, key);
+    let storage_policy: NSURLCacheStoragePolicy = msg![env; response storagePolicy];
+    if storage_policy == NSURLCacheStorageNotAllowed {
+        return;
+    }
+    () = msg![env; this removeCachedResponseForRequest:request];
+    let data: id = msg![env; response data];
+    let size: NSUInteger = msg![env; data length];
+    // Like on a real device, a response bigger than 5% of the cache isn't
+    // stored at all.
+    let memory_capacity = env.objc.borrow::<NSURLCacheHostObject>(this).memory_capacity;
+    if size > memory_capacity / 20 {
+        log_dbg!("Not caching {} byte response, capacity is {} bytes", size, memory_capacity);
+        return;
+    }
+    retain(env, response);
+    env.objc
+        .borrow_mut::<NSURLCacheHostObject>(this)
+        .entries
+        .push(CacheEntry { key, response, size });
+    evict_to_capacity(env, this);
+}
+
+- (())removeCachedResponseForRequest:(id)request { // NSURLRequest*
+    let key = cache_key(env, request);
+    let host_object = env.objc.borrow_mut::<NSURLCacheHostObject>(this);
+    let Some(index) = host_object.entries.iter().position(|entry| entry.key == key) else {
+        return;
+    };
+    let old = host_object.entries.remove(index);
+    release(env, old.response);
+}
+
+- (())removeAllCachedResponses {
+    let entries = std::mem::take(&mut env.objc.borrow_mut::<NSURLCacheHostObject>(this).entries);
+    for entry in entries {
+        release(env, entry.response);
+    }
+}
+
+@end
+
+};
@@ -0,0 +1,639 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSURLConnection` and `NSURLResponse`.
+//!
+//! touchHLE doesn't access the network, so the only requests that succeed are
+//! those answered by the user's `--network-rules=` file (see
+//! [crate::network_rules]). Every other request fails as if the device were
+//! offline.
+//!
+//! Asynchronous connections pass the data to their delegate in chunks, one per
+//! run loop iteration, like a real download would. This gives apps a chance to
+//! show their progress, and touchHLE shows the overall progress in the window
+//! title (see [download_progress]).
+
+use super::ns_dictionary::dict_from_keys_and_objects;
+use super::{ns_http_cookie, ns_string, NSInteger};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::uikit::ui_application;
+use crate::mem::{GuestUSize, MutPtr};
+use crate::network_rules::Resolution;
+use crate::objc::{
+    autorelease, id, msg, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports,
+    HostObject, NSZonePtr, SEL,
+};
+use crate::Environment;
+use std::path::Path;
+
+pub const NSURLErrorDomain: &str = "NSURLErrorDomain";
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_NSURLErrorDomain",
+    HostConstant::NSString(NSURLErrorDomain),
+)];
+
+const NSURLErrorNotConnectedToInternet: NSInteger = -1009;
+
+/// How much of a response body is passed to the delegate per run loop
+/// iteration. At 60 iterations per second, this is a few megabytes per second,
+/// which is a plausible speed for Wi-Fi.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Default)]
+pub struct State {
+    /// Asynchronous connections that have started and haven't finished yet, in
+    /// the order they were started.
+    transfers: Vec<Transfer>,
+}
+impl State {
+    fn get(env: &mut Environment) -> &mut Self {
+        &mut env.framework_state.foundation.ns_url_connection
+    }
+}
+
+struct Transfer {
+    /// `NSURLConnection*`. Strong reference, so that the connection stays alive
+    /// until it finishes, like on iOS.
+    connection: id,
+    url: String,
+    /// [None] if the request can't be made.
+    response: Option<ServedResponse>,
+    response_sent: bool,
+    /// How much of the body has been passed to the delegate so far.
+    bytes_sent: usize,
+}
+
+struct NSURLConnectionHostObject {
+    /// `NSURLRequest*`
+    request: id,
+    /// Strong reference, released once the connection finishes.
+    delegate: id,
+}
+impl HostObject for NSURLConnectionHostObject {}
+
+struct NSURLResponseHostObject {
+    /// `NSURL*`
+    url: id,
+    /// `NSString*`
+    mime_type: id,
+    expected_content_length: i64,
+    /// `NSString*`
+    text_encoding_name: id,
+    status_code: NSInteger,
+    /// `NSDictionary*` of `NSString*` to `NSString*`
+    header_fields: id,
+}
+impl HostObject for NSURLResponseHostObject {}
+
+/// Create a new `NSData*` (autoreleased) with a copy of some bytes.
+fn data_from_bytes(env: &mut Environment, bytes: &[u8]) -> id {
+    if bytes.is_empty() {
+        return msg_class![env; NSData data];
+    }
+    let length: GuestUSize = bytes.len().try_into().unwrap();
+    let buffer = env.mem.alloc(length);
+    env.mem
+        .bytes_at_mut(buffer.cast(), length)
+        .copy_from_slice(bytes);
+    msg_class![env; NSData dataWithBytesNoCopy:buffer length:length]
+}
+
+/// A response to a request, from a file served by the network rules.
+pub struct ServedResponse {
+    pub body: Vec<u8>,
+    pub status_code: NSInteger,
+    /// Header fields in the order they were given, including `Content-Length`.
+    pub header_fields: Vec<(String, String)>,
+}
+
+/// Apply the `--network-rules=` to a request for an absolute URL. Returns the
+/// final URL, and the response if a rule served a file. Otherwise, the request
+/// can't be made.
+///
+/// Cookies are handled like for a real request: the ones stored for the URL
+/// are (notionally) sent, and any the response sets are stored.
+pub fn fetch_with_rules(env: &mut Environment, url: &str) -> (String, Option<ServedResponse>) {
+    let (url, resolution) = env.options.network_rules.resolve(url);
+    let served = match resolution {
+        Resolution::Serve(path) => {
+            log!("Serving {:?} for request to {:?}", path, url);
+            if let Some(cookie) = ns_http_cookie::cookie_header_for_url(env, &url) {
+                // There's no server that could read the header, but logging
+                // it helps with writing a rules file for a login session.
+                log_dbg!("Request to {:?} has Cookie: {}", url, cookie);
+            }
+            let (body, status_code) = match std::fs::read(&path) {
+                Ok(bytes) => (bytes, 200),
+                Err(e) => {
+                    log!(
+                        "Warning: Couldn't read {:?}: {}, responding with 404",
+                        path,
+                        e
+                    );
+                    (Vec::new(), 404)
+                }
+            };
+            let mut header_fields = read_header_fields(&path);
+            if !header_fields
+                .iter()
+                .any(|(name, _)| name.eq_ignore_ascii_case("Content-Length"))
+            {
+                header_fields.push(("Content-Length".to_string(), body.len().to_string()));
+            }
+            for (name, value) in &header_fields {
+                if name.eq_ignore_ascii_case("Set-Cookie") {
+                    ns_http_cookie::store_cookies_from_header(env, &url, value);
+                }
+            }
+            Some(ServedResponse {
+                body,
+                status_code,
+                header_fields,
+            })
+        }
+        Resolution::Block => {
+            log!("Blocked request to {:?}", url);
+            None
+        }
+        Resolution::Unhandled => {
+            log!(
+                "TODO: Network request to {:?}, failing it as if offline",
+                url
+            );
+            None
+        }
+    };
+    (url, served)
+}
+
+/// Read the extra header fields for a served file, if it has a `.headers` file
+/// next to it (see [crate::network_rules]).
+fn read_header_fields(path: &Path) -> Vec<(String, String)> {
+    let mut headers_path = path.as_os_str().to_owned();
+    headers_path.push(".headers");
+    let Ok(text) = std::fs::read_to_string(&headers_path) else {
+        return Vec::new();
+    };
+    text.lines()
+        .filter_map(|line| {
+            let Some((name, value)) = line.split_once(':') else {
+                if !line.trim().is_empty() {
+                    log!("Warning: Ignoring {:?} in {:?}", line, headers_path);
+                }
+                return None;
+            };
+            Some((name.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Create a new `NSDictionary*` (retained) of header fields. Fields given more
+/// than once are combined with commas, like in a real response.
+fn header_fields_to_dict(env: &mut Environment, header_fields: &[(String, String)]) -> id {
+    let mut combined: Vec<(&str, String)> = Vec::new();
+    for (name, value) in header_fields {
+        match combined
+            .iter_mut()
+            .find(|(existing, _)| existing.eq_ignore_ascii_case(name))
+        {
+            Some((_, existing_value)) => {
+                existing_value.push_str(", ");
+                existing_value.push_str(value);
+            }
+            None => combined.push((name, value.clone())),
+        }
+    }
+    let keys_and_objects: Vec<(id, id)> = combined
+        .into_iter()
+        .map(|(name, value)| {
+            let name = ns_string::from_rust_string(env, name.to_string());
+            let value = ns_string::from_rust_string(env, value);
+            (name, value)
+        })
+        .collect();
+    let dict = dict_from_keys_and_objects(env, &keys_and_objects);
+    for (name, value) in keys_and_objects {
+        release(env, name);
+        release(env, value);
+    }
+    dict
+}
+
+/// Create a new `NSHTTPURLResponse*` (autoreleased) for a served file.
+fn new_response(
+    env: &mut Environment,
+    url: &str,
+    status_code: NSInteger,
+    header_fields: &[(String, String)],
+) -> id {
+    let url = ns_string::from_rust_string(env, url.to_string());
+    let ns_url: id = msg_class![env; NSURL URLWithString:url];
+    release(env, url);
+    let header_fields = header_fields_to_dict(env, header_fields);
+    let version = ns_string::get_static_str(env, "HTTP/1.1");
+    let response: id = msg_class![env; NSHTTPURLResponse alloc];
+    let response: id = msg![env; response initWithURL:ns_url
+                                           statusCode:status_code
+                                          HTTPVersion:version
+                                         headerFields:header_fields];
+    release(env, header_fields);
+    autorelease(env, response)
+}
+
+/// Create a new `NSError*` (autoreleased) for a request that can't be made.
+fn offline_error(env: &mut Environment, url: &str) -> id {
+    let description = format!("The Internet connection appears to be offline. ({})", url);
+    let description = ns_string::from_rust_string(env, description);
+    let key = ns_string::get_static_str(env, "NSLocalizedDescription");
+    let user_info = dict_from_keys_and_objects(env, &[(key, description)]);
+    release(env, description);
+    let domain = ns_string::get_static_str(env, NSURLErrorDomain);
+    let error: id = msg_class![env; NSError alloc];
+    let error: id = msg![env; error initWithDomain:domain
+                                              code:NSURLErrorNotConnectedToInternet
+                                          userInfo:user_info];
+    release(env, user_info);
+    autorelease(env, error)
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSURLConnection: NSObject
+
++ (id)sendSynchronousRequest:(id)request // NSURLRequest*
+           returningResponse:(MutPtr<id>)response // NSURLResponse**
+                       error:(MutPtr<id>)error { // NSError**
+    let url: id = msg![env; request URL];
+    let url: id = msg![env; url absoluteString];
+    let url = ns_string::to_rust_string(env, url).into_owned();
+
+    let (url, served) = fetch_with_rules(env, &url);
+    let (data, new_response, new_error) = match served {
+        Some(ServedResponse { body, status_code, header_fields }) => {
+            let data = data_from_bytes(env, &body);
+            let new_response = new_response(env, &url, status_code, &header_fields);
+            (data, new_response, nil)
+        }
+        None => (nil, nil, offline_error(env, &url)),
+    };
+
+    if !response.is_null() {
+        env.mem.write(response, new_response);
+    }
+    if !error.is_null() {
+        env.mem.write(error, new_error);
+    }
+    data
+}
+
++ (id)connectionWithRequest:(id)request // NSURLRequest*
+                   delegate:(id)delegate {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithRequest:request delegate:delegate];
+    autorelease(env, new)
+}
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSURLConnectionHostObject {
+        request: nil,
+        delegate: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithRequest:(id)request // NSURLRequest*
+             delegate:(id)delegate {
+    msg![env; this initWithRequest:request delegate:delegate startImmediately:true]
+}
+
+- (id)initWithRequest:(id)request // NSURLRequest*
+             delegate:(id)delegate
+     startImmediately:(bool)start_immediately {
+    let request: id = msg![env; request copy];
+    retain(env, delegate);
+    let host_object = env.objc.borrow_mut::<NSURLConnectionHostObject>(this);
+    host_object.request = request;
+    host_object.delegate = delegate;
+    if start_immediately {
+        () = msg![env; this start];
+    }
+    this
+}
+
+- (())dealloc {
+    let &NSURLConnectionHostObject { request, delegate } = env.objc.borrow(this);
+    release(env, request);
+    release(env, delegate);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// The delegate is always called on the main thread's run loop.
+- (())scheduleInRunLoop:(id)_run_loop // NSRunLoop*
+                forMode:(id)_mode {} // NSString*
+- (())unscheduleFromRunLoop:(id)_run_loop // NSRunLoop*
+                    forMode:(id)_mode {} // NSString*
+
+- (())start {
+    if State::get(env).transfers.iter().any(|transfer| transfer.connection == this) {
+        return;
+    }
+    let request = env.objc.borrow::<NSURLConnectionHostObject>(this).request;
+    let url: id = msg![env; request URL];
+    let url: id = msg![env; url absoluteString];
+    let url = ns_string::to_rust_string(env, url).into_owned();
+    log_dbg!("[(NSURLConnection*){:?} start] for {:?}", this, url);
+
+    let (url, response) = fetch_with_rules(env, &url);
+    retain(env, this);
+    State::get(env).transfers.push(Transfer {
+        connection: this,
+        url,
+        response,
+        response_sent: false,
+        bytes_sent: 0,
+    });
+    ui_application::update_window_title(env);
+}
+
+- (())cancel {
+    log_dbg!("[(NSURLConnection*){:?} cancel]", this);
+    end_transfer(env, this);
+    ui_application::update_window_title(env);
+}
+
+@end
+
+@implementation NSURLResponse: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSURLResponseHostObject {
+        url: nil,
+        mime_type: nil,
+        expected_content_length: -1,
+        text_encoding_name: nil,
+        status_code: 200,
+        header_fields: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithURL:(id)url // NSURL*
+         MIMEType:(id)mime_type // NSString*
+expectedContentLength:(NSInteger)length
+ textEncodingName:(id)text_encoding_name { // NSString*
+    let url: id = msg![env; url copy];
+    let mime_type: id = msg![env; mime_type copy];
+    let text_encoding_name: id = msg![env; text_encoding_name copy];
+    let host_object = env.objc.borrow_mut::<NSURLResponseHostObject>(this);
+    host_object.url = url;
+    host_object.mime_type = mime_type;
+    host_object.expected_content_length = length.into();
+    host_object.text_encoding_name = text_encoding_name;
+    this
+}
+
+- (())dealloc {
+    let &NSURLResponseHostObject {
+        url,
+        mime_type,
+        text_encoding_name,
+        header_fields,
+        ..
+    } = env.objc.borrow(this);
+    release(env, url);
+    release(env, mime_type);
+    release(env, text_encoding_name);
+    release(env, header_fields);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+- (id)URL {
+    env.objc.borrow::<NSURLResponseHostObject>(this).url
+}
+
+- (i64)expectedContentLength {
+    env.objc.borrow::<NSURLResponseHostObject>(this).expected_content_length
+}
+
+// TODO: MIME types and text encodings for served files
+- (id)MIMEType {
+    env.objc.borrow::<NSURLResponseHostObject>(this).mime_type
+}
+- (id)textEncodingName {
+    env.objc.borrow::<NSURLResponseHostObject>(this).text_encoding_name
+}
+
+@end
+
+@implementation NSHTTPURLResponse: NSURLResponse
+
+- (id)initWithURL:(id)url // NSURL*
+       statusCode:(NSInteger)status_code
+      HTTPVersion:(id)_http_version // NSString*
+     headerFields:(id)header_fields { // NSDictionary*
+    let this: id = msg![env; this initWithURL:url
+                                     MIMEType:nil
+                        expectedContentLength:(-1 as NSInteger)
+                             textEncodingName:nil];
+    let length = if header_fields == nil {
+        nil
+    } else {
+        let key = ns_string::get_static_str(env, "Content-Length");
+        msg![env; header_fields objectForKey:key]
+    };
+    let length = if length == nil {
+        -1
+    } else {
+        ns_string::to_rust_string(env, length).trim().parse().unwrap_or(-1)
+    };
+    let header_fields: id = msg![env; header_fields copy];
+    let host_object = env.objc.borrow_mut::<NSURLResponseHostObject>(this);
+    host_object.status_code = status_code;
+    host_object.header_fields = header_fields;
+    host_object.expected_content_length = length;
+    this
+}
+
+- (NSInteger)statusCode {
+    env.objc.borrow::<NSURLResponseHostObject>(this).status_code
+}
+
+- (id)allHeaderFields {
+    let header_fields = env.objc.borrow::<NSURLResponseHostObject>(this).header_fields;
+    if header_fields == nil {
+        msg_class![env; NSDictionary dictionary]
+    } else {
+        header_fields
+    }
+}
+
+@end
+
+};
+
+/// For use by `NSRunLoop`: move each asynchronous connection on by one step,
+/// calling its delegate.
+pub(super) fn handle_transfers(env: &mut Environment) {
+    let connections: Vec<id> = State::get(env)
+        .transfers
+        .iter()
+        .map(|transfer| transfer.connection)
+        .collect();
+    if connections.is_empty() {
+        return;
+    }
+    for connection in connections {
+        step_transfer(env, connection);
+    }
+    ui_application::update_window_title(env);
+}
+
+fn step_transfer(env: &mut Environment, connection: id) {
+    // The delegate might have cancelled this connection while handling another
+    // one.
+    let Some(transfer) = State::get(env)
+        .transfers
+        .iter_mut()
+        .find(|transfer| transfer.connection == connection)
+    else {
+        return;
+    };
+
+    let Some(response) = &transfer.response else {
+        let url = transfer.url.clone();
+        let error = offline_error(env, &url);
+        // The delegate is released when the transfer ends, so keep it alive
+        // for the callback.
+        let delegate = delegate_for(env, connection);
+        retain(env, delegate);
+        end_transfer(env, connection);
+        call_delegate(
+            env,
+            delegate,
+            "connection:didFailWithError:",
+            connection,
+            Some(error),
+        );
+        release(env, delegate);
+        return;
+    };
+
+    if !transfer.response_sent {
+        transfer.response_sent = true;
+        let url = transfer.url.clone();
+        let status_code = response.status_code;
+        let header_fields = response.header_fields.clone();
+        let response = new_response(env, &url, status_code, &header_fields);
+        let delegate = delegate_for(env, connection);
+        call_delegate(
+            env,
+            delegate,
+            "connection:didReceiveResponse:",
+            connection,
+            Some(response),
+        );
+    } else if transfer.bytes_sent < response.body.len() {
+        let start = transfer.bytes_sent;
+        let end = response.body.len().min(start + CHUNK_SIZE);
+        let chunk = response.body[start..end].to_vec();
+        transfer.bytes_sent = end;
+        let data = data_from_bytes(env, &chunk);
+        let delegate = delegate_for(env, connection);
+        call_delegate(
+            env,
+            delegate,
+            "connection:didReceiveData:",
+            connection,
+            Some(data),
+        );
+    } else {
+        let delegate = delegate_for(env, connection);
+        retain(env, delegate);
+        end_transfer(env, connection);
+        call_delegate(
+            env,
+            delegate,
+            "connectionDidFinishLoading:",
+            connection,
+            None,
+        );
+        release(env, delegate);
+    }
+}
+
+fn delegate_for(env: &Environment, connection: id) -> id {
+    env.objc
+        .borrow::<NSURLConnectionHostObject>(connection)
+        .delegate
+}
+
+/// Send a message to a connection's delegate, if it implements it. The
+/// arguments are the connection and, if given, one object.
+fn call_delegate(
+    env: &mut Environment,
+    delegate: id,
+    selector: &str,
+    connection: id,
+    object: Option<id>,
+) {
+    if delegate == nil {
+        return;
+    }
+    let sel: SEL = env
+        .objc
+        .register_host_selector(selector.to_string(), &mut env.mem);
+    let responds: bool = msg![env; delegate respondsToSelector:sel];
+    if !responds {
+        return;
+    }
+    match object {
+        Some(object) => msg_send(env, (delegate, sel, connection, object)),
+        None => msg_send(env, (delegate, sel, connection)),
+    }
+}
+
+/// Stop an asynchronous connection, if it's still running, and release what it
+/// was keeping alive.
+fn end_transfer(env: &mut Environment, connection: id) {
+    let transfers = &mut State::get(env).transfers;
+    let Some(index) = transfers
+        .iter()
+        .position(|transfer| transfer.connection == connection)
+    else {
+        return;
+    };
+    transfers.remove(index);
+    let delegate = std::mem::replace(
+        &mut env
+            .objc
+            .borrow_mut::<NSURLConnectionHostObject>(connection)
+            .delegate,
+        nil,
+    );
+    release(env, delegate);
+    release(env, connection);
+}
+
+/// Get the overall progress of the asynchronous connections that are running,
+/// as bytes received so far and bytes expected in total, if there are any.
+pub fn download_progress(env: &mut Environment) -> Option<(u64, u64)> {
+    let transfers = &State::get(env).transfers;
+    if transfers.is_empty() {
+        return None;
+    }
+    let (received, expected) = transfers
+        .iter()
+        .filter_map(|transfer| {
+            let response = transfer.response.as_ref()?;
+            Some((transfer.bytes_sent, response.body.len()))
+        })
+        .fold((0, 0), |(received, expected), (bytes_sent, len)| {
+            (received + bytes_sent as u64, expected + len as u64)
+        });
+    Some((received, expected))
+}
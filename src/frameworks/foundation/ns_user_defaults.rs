@@ -10,7 +10,7 @@
 
 use super::ns_dictionary::dict_from_keys_and_objects;
 use super::ns_string;
-use crate::objc::{id, msg_class, objc_classes, ClassExports};
+use crate::objc::{id, msg, msg_class, objc_classes, ClassExports};
 use crate::Environment;
 
 #[derive(Default)]
@@ -37,7 +37,13 @@ pub const CLASSES: ClassExports = objc_classes! {
         // TODO: Are there other default keys we need to set?
         let langs_value: id = msg_class![env; NSLocale preferredLanguages];
         let langs_key: id = ns_string::get_static_str(env, "AppleLanguages");
-        let new = dict_from_keys_and_objects(env, &[(langs_key, langs_value)]);
+        let locale: id = msg_class![env; NSLocale currentLocale];
+        let locale_value: id = msg![env; locale localeIdentifier];
+        let locale_key: id = ns_string::get_static_str(env, "AppleLocale");
+        let new = dict_from_keys_and_objects(
+            env,
+            &[(langs_key, langs_value), (locale_key, locale_value)],
+        );
         State::get(env).standard_defaults = Some(new);
         new
     }
@@ -12,6 +12,7 @@ pub mod music_player;
 #[derive(Default)]
 pub struct State {
     movie_player: movie_player::State,
+    music_player: music_player::State,
 }
 
 /// For use by `NSRunLoop`: check media players' status, send notifications if
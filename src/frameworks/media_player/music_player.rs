@@ -4,8 +4,144 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `MPMusicPlayerController` etc.
+//!
+//! touchHLE has no music library, so neither player can actually play
+//! anything. The iPod music player's state is still tracked, because it
+//! represents audio from outside the app, which the audio session reports with
+//! `kAudioSessionProperty_OtherAudioIsPlaying`. Whether it is playing when the
+//! app starts is controlled by `--other-audio-playing`.
 
-use crate::objc::{id, nil, objc_classes, ClassExports};
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::foundation::{ns_string, NSInteger};
+use crate::objc::{id, msg, msg_class, nil, objc_classes, ClassExports, HostObject};
+use crate::Environment;
+
+#[derive(Default)]
+pub struct State {
+    ipod_music_player: Option<id>,
+    application_music_player: Option<id>,
+    /// State of the iPod music player, if it has been changed since the app
+    /// started.
+    ipod_playback_state: Option<MPMusicPlaybackState>,
+}
+impl State {
+    fn get(env: &mut Environment) -> &mut Self {
+        &mut env.framework_state.media_player.music_player
+    }
+}
+
+type MPMusicPlaybackState = NSInteger;
+const MPMusicPlaybackStateStopped: MPMusicPlaybackState = 0;
+const MPMusicPlaybackStatePlaying: MPMusicPlaybackState = 1;
+const MPMusicPlaybackStatePaused: MPMusicPlaybackState = 2;
+const MPMusicPlaybackStateInterrupted: MPMusicPlaybackState = 3;
+
+pub const MPMusicPlayerControllerPlaybackStateDidChangeNotification: &str =
+    "MPMusicPlayerControllerPlaybackStateDidChangeNotification";
+
+/// `NSNotificationName` values.
+pub const CONSTANTS: ConstantExports = &[(
+    "_MPMusicPlayerControllerPlaybackStateDidChangeNotification",
+    HostConstant::NSString(MPMusicPlayerControllerPlaybackStateDidChangeNotification),
+)];
+
+struct MPMusicPlayerControllerHostObject {
+    is_ipod: bool,
+    /// Only used for the application music player, see [ipod_playback_state]
+    /// for the iPod music player.
+    playback_state: MPMusicPlaybackState,
+    volume: f32,
+    generating_notifications: bool,
+}
+impl HostObject for MPMusicPlayerControllerHostObject {}
+
+fn ipod_playback_state(env: &mut Environment) -> MPMusicPlaybackState {
+    // TODO: Detect whether audio is actually playing on the host? There's no
+    // portable way to do that, so for now this is up to the user.
+    let initial = if env.options.other_audio_playing {
+        MPMusicPlaybackStatePlaying
+    } else {
+        MPMusicPlaybackStateStopped
+    };
+    *State::get(env).ipod_playback_state.get_or_insert(initial)
+}
+
+/// Whether music is playing in the iPod app, i.e. outside this app. For use by
+/// the audio session.
+pub fn ipod_music_is_playing(env: &mut Environment) -> bool {
+    ipod_playback_state(env) == MPMusicPlaybackStatePlaying
+}
+
+/// Pause the iPod app's music, because the app's audio session doesn't allow
+/// mixing with it. For use by the audio session.
+pub fn interrupt_ipod_music(env: &mut Environment) {
+    // A real device reports this as paused, not interrupted, since the music
+    // doesn't resume afterwards.
+    set_playback_state(env, None, MPMusicPlaybackStatePaused);
+}
+
+/// Change the state of a player. If `player` is [None], this is the iPod music
+/// player, which may not have been created yet.
+fn set_playback_state(env: &mut Environment, player: Option<id>, new_state: MPMusicPlaybackState) {
+    let player = player.or(State::get(env).ipod_music_player);
+    let is_ipod = match player {
+        Some(player) => {
+            env.objc
+                .borrow::<MPMusicPlayerControllerHostObject>(player)
+                .is_ipod
+        }
+        None => true,
+    };
+    let old_state = if is_ipod {
+        let old_state = ipod_playback_state(env);
+        State::get(env).ipod_playback_state = Some(new_state);
+        old_state
+    } else {
+        let host_object = env
+            .objc
+            .borrow_mut::<MPMusicPlayerControllerHostObject>(player.unwrap());
+        std::mem::replace(&mut host_object.playback_state, new_state)
+    };
+    if old_state == new_state {
+        return;
+    }
+    log_dbg!(
+        "{} music player state changed from {} to {}",
+        if is_ipod { "iPod" } else { "Application" },
+        old_state,
+        new_state
+    );
+
+    let Some(player) = player else {
+        return;
+    };
+    if env
+        .objc
+        .borrow::<MPMusicPlayerControllerHostObject>(player)
+        .generating_notifications
+    {
+        let name = ns_string::get_static_str(
+            env,
+            MPMusicPlayerControllerPlaybackStateDidChangeNotification,
+        );
+        let center: id = msg_class![env; NSNotificationCenter defaultCenter];
+        let _: () = msg![env; center postNotificationName:name object:player];
+    }
+}
+
+fn new_player(env: &mut Environment, is_ipod: bool) -> id {
+    let host_object = Box::new(MPMusicPlayerControllerHostObject {
+        is_ipod,
+        playback_state: MPMusicPlaybackStateStopped,
+        volume: 1.0,
+        generating_notifications: false,
+    });
+    let class = env
+        .objc
+        .get_known_class("MPMusicPlayerController", &mut env.mem);
+    env.objc
+        .alloc_static_object(class, host_object, &mut env.mem)
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -14,21 +150,77 @@ pub const CLASSES: ClassExports = objc_classes! {
 @implementation MPMusicPlayerController: NSObject
 
 + (id)iPodMusicPlayer {
-    log_dbg!(
-        "TODO: [(MPMusicPlayerController*){:?} iPodMusicPlayer]",
-        this
-    );
-    nil
+    if let Some(existing) = State::get(env).ipod_music_player {
+        return existing;
+    }
+    let new = new_player(env, /* is_ipod: */ true);
+    State::get(env).ipod_music_player = Some(new);
+    new
 }
 
 + (id)applicationMusicPlayer {
-    log_dbg!(
-        "TODO: [(MPMusicPlayerController*){:?} applicationMusicPlayer]",
-        this
-    );
+    if let Some(existing) = State::get(env).application_music_player {
+        return existing;
+    }
+    let new = new_player(env, /* is_ipod: */ false);
+    State::get(env).application_music_player = Some(new);
+    new
+}
+
+- (id)retain { this }
+- (())release {}
+- (id)autorelease { this }
+
+- (MPMusicPlaybackState)playbackState {
+    let &MPMusicPlayerControllerHostObject { is_ipod, playback_state, .. } = env.objc.borrow(this);
+    if is_ipod {
+        ipod_playback_state(env)
+    } else {
+        playback_state
+    }
+}
+
+- (())play {
+    let is_ipod = env.objc.borrow::<MPMusicPlayerControllerHostObject>(this).is_ipod;
+    if !is_ipod {
+        // There's no music library to set a queue from, so there's nothing
+        // the app could have asked to play.
+        log!("TODO: [(MPMusicPlayerController*){:?} play] (application music player)", this);
+    }
+    set_playback_state(env, Some(this), MPMusicPlaybackStatePlaying);
+}
+- (())pause {
+    let old_state: MPMusicPlaybackState = msg![env; this playbackState];
+    if old_state == MPMusicPlaybackStatePlaying || old_state == MPMusicPlaybackStateInterrupted {
+        set_playback_state(env, Some(this), MPMusicPlaybackStatePaused);
+    }
+}
+- (())stop {
+    set_playback_state(env, Some(this), MPMusicPlaybackStateStopped);
+}
+
+- (f32)volume {
+    env.objc.borrow::<MPMusicPlayerControllerHostObject>(this).volume
+}
+- (())setVolume:(f32)volume {
+    env.objc.borrow_mut::<MPMusicPlayerControllerHostObject>(this).volume = volume.clamp(0.0, 1.0);
+}
+
+- (id)nowPlayingItem {
     nil
 }
 
+- (())setQueueWithQuery:(id)query { // MPMediaQuery*
+    log!("TODO: [(MPMusicPlayerController*){:?} setQueueWithQuery:{:?}], ignoring", this, query);
+}
+
+- (())beginGeneratingPlaybackNotifications {
+    env.objc.borrow_mut::<MPMusicPlayerControllerHostObject>(this).generating_notifications = true;
+}
+- (())endGeneratingPlaybackNotifications {
+    env.objc.borrow_mut::<MPMusicPlayerControllerHostObject>(this).generating_notifications = false;
+}
+
 @end
 
 };
@@ -15,6 +15,8 @@ use crate::audio::openal as al;
 use crate::audio::openal::al_types::*;
 use crate::audio::openal::alc_types::*;
 use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::audio_toolbox::audio_mixer::State as AudioMixerState;
+use crate::frameworks::audio_toolbox::audio_session::State as AudioSessionState;
 use crate::libc::string::strcmp;
 use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, MutPtr, MutVoidPtr, Ptr, SafeWrite};
 use crate::Environment;
@@ -24,8 +26,13 @@ use touchHLE_openal_soft_wrapper::ALC_DEVICE_SPECIFIER;
 
 #[derive(Default)]
 pub struct State {
+    /// All of these map to the audio mixer's shared host device, see
+    /// [crate::frameworks::audio_toolbox::audio_mixer].
     devices: HashMap<MutPtr<GuestALCdevice>, *mut ALCdevice>,
     contexts: HashMap<MutPtr<GuestALCcontext>, *mut ALCcontext>,
+    /// The guest device each context was created on. This can't be derived
+    /// from the host context, because the host device is shared.
+    context_devices: HashMap<MutPtr<GuestALCcontext>, MutPtr<GuestALCdevice>>,
 }
 impl State {
     fn get(env: &mut Environment) -> &mut Self {
@@ -33,6 +40,11 @@ impl State {
     }
 }
 
+/// Whether the app has created any OpenAL contexts.
+pub fn has_contexts(env: &mut Environment) -> bool {
+    !State::get(env).contexts.is_empty()
+}
+
 /// Opaque type in guest memory standing in for [ALCdevice] in host memory.
 struct GuestALCdevice {
     _filler: u8,
@@ -56,7 +68,7 @@ fn alcOpenDevice(env: &mut Environment, devicename: ConstPtr<u8>) -> MutPtr<Gues
         env.mem.free(d_name.cast_mut().cast());
     }
 
-    let res = unsafe { al::alcOpenDevice(std::ptr::null()) };
+    let res = AudioMixerState::get(&mut env.framework_state).open_device();
     if res.is_null() {
         log_dbg!("alcOpenDevice(NULL) returned NULL");
         return Ptr::null();
@@ -68,11 +80,24 @@ fn alcOpenDevice(env: &mut Environment, devicename: ConstPtr<u8>) -> MutPtr<Gues
     guest_res
 }
 fn alcCloseDevice(env: &mut Environment, device: MutPtr<GuestALCdevice>) -> bool {
+    // A device with contexts can't be closed. OpenAL Soft would check this
+    // itself, but it can't tell which contexts belong to which guest device.
+    if State::get(env)
+        .context_devices
+        .values()
+        .any(|&context_device| context_device == device)
+    {
+        log!(
+            "Warning: alcCloseDevice({:?}) called for device with contexts, returning false",
+            device
+        );
+        return false;
+    }
     let host_device = State::get(env).devices.remove(&device).unwrap();
     env.mem.free(device.cast());
-    let res = unsafe { al::alcCloseDevice(host_device) };
+    let res = AudioMixerState::get(&mut env.framework_state).close_device(host_device);
     log_dbg!("alcCloseDevice({:?}) => {:?}", device, res,);
-    res != al::ALC_FALSE
+    res
 }
 
 fn alcGetError(env: &mut Environment, device: MutPtr<GuestALCdevice>) -> i32 {
@@ -106,7 +131,10 @@ fn alcCreateContext(
 
     let &host_device = State::get(env).devices.get(&device).unwrap();
 
-    let res = unsafe { al::alcCreateContext(host_device, std::ptr::null()) };
+    // The mixer should behave like the emulated audio hardware, e.g. in order
+    // to honor the I/O buffer duration requested through the audio session.
+    let attributes = AudioSessionState::get(&mut env.framework_state).al_context_attributes();
+    let res = unsafe { al::alcCreateContext(host_device, attributes.as_ptr()) };
     if res.is_null() {
         log_dbg!("alcCreateContext({:?}, NULL) returned NULL", device);
         return Ptr::null();
@@ -114,6 +142,7 @@ fn alcCreateContext(
 
     let guest_res = env.mem.alloc_and_write(GuestALCcontext { _filler: 0 });
     State::get(env).contexts.insert(guest_res, res);
+    State::get(env).context_devices.insert(guest_res, device);
     log_dbg!(
         "alcCreateContext({:?}, NULL) => {:?} (host: {:?})",
         device,
@@ -124,6 +153,7 @@ fn alcCreateContext(
 }
 fn alcDestroyContext(env: &mut Environment, context: MutPtr<GuestALCcontext>) {
     let host_context = State::get(env).contexts.remove(&context).unwrap();
+    State::get(env).context_devices.remove(&context);
     env.mem.free(context.cast());
     unsafe { al::alcDestroyContext(host_context) };
     log_dbg!("alcDestroyContext({:?})", context);
@@ -167,14 +197,11 @@ fn alcGetContextsDevice(
     env: &mut Environment,
     context: MutPtr<GuestALCcontext>,
 ) -> MutPtr<GuestALCdevice> {
-    let host_context = State::get(env).contexts.get(&context).copied().unwrap();
-    let host_device = unsafe { al::alcGetContextsDevice(host_context) };
-    *State::get(env)
-        .devices
-        .iter()
-        .find(|(&_guest, &host)| host == host_device)
+    State::get(env)
+        .context_devices
+        .get(&context)
+        .copied()
         .unwrap()
-        .0
 }
 
 fn alcGetProcAddress(
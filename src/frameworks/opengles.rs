@@ -11,6 +11,7 @@
 
 pub mod eagl;
 mod gles_guest;
+mod memory_usage;
 
 use crate::mem::ConstPtr;
 pub use gles_guest::FUNCTIONS;
@@ -18,11 +19,18 @@ use touchHLE_gl_bindings::gles11::types::GLenum;
 
 #[derive(Default)]
 pub struct State {
-    /// Current EAGLContext for each thread
+    /// Current EAGLContext for each thread (strong references). Each thread
+    /// can have a different one, and they can be used at the same time.
     current_ctxs: std::collections::HashMap<crate::ThreadId, Option<crate::objc::id>>,
-    /// Which thread's EAGLContext is currently active
-    current_ctx_thread: Option<crate::ThreadId>,
-    strings_cache: std::collections::HashMap<GLenum, ConstPtr<u8>>,
+    /// The EAGLContext whose host context is actually current on the host
+    /// thread, or [None] if this is unknown. Since all guest threads run on one
+    /// host thread, [sync_context] switches host contexts when a guest thread
+    /// with a different context makes an OpenGL ES call.
+    host_current_ctx: Option<crate::objc::id>,
+    /// Strings returned by `glGetString`, which differ between API versions.
+    strings_cache: std::collections::HashMap<(eagl::EAGLRenderingAPI, GLenum), ConstPtr<u8>>,
+    /// Every live `EAGLSharegroup*`, for memory usage reports.
+    sharegroups: Vec<crate::objc::id>,
 }
 impl State {
     fn current_ctx_for_thread(&mut self, thread: crate::ThreadId) -> &mut Option<crate::objc::id> {
@@ -31,24 +39,66 @@ impl State {
     }
 }
 
+/// Describe the estimated memory usage of the app's OpenGL ES objects in each
+/// sharegroup, one line per sharegroup. See [memory_usage].
+pub fn describe_memory_usage(env: &crate::Environment) -> Vec<String> {
+    env.framework_state
+        .opengles
+        .sharegroups
+        .iter()
+        .map(|&sharegroup| {
+            let usage = &env
+                .objc
+                .borrow::<eagl::EAGLSharegroupHostObject>(sharegroup)
+                .memory_usage;
+            format!("EAGLSharegroup {:?}: {}", sharegroup, usage.summary())
+        })
+        .collect()
+}
+
 fn sync_context<'a>(
     state: &mut State,
     objc: &'a mut crate::objc::ObjC,
     window: &mut crate::window::Window,
     current_thread: crate::ThreadId,
 ) -> &'a mut dyn crate::gles::GLES {
-    let current_ctx = state.current_ctx_for_thread(current_thread);
-    let host_obj = objc.borrow_mut::<eagl::EAGLContextHostObject>(current_ctx.unwrap());
+    let current_ctx = state.current_ctx_for_thread(current_thread).unwrap();
+    let host_obj = objc.borrow_mut::<eagl::EAGLContextHostObject>(current_ctx);
     let gles_ctx = host_obj.gles_ctx.as_deref_mut().unwrap();
 
-    if window.is_app_gl_ctx_no_longer_current() || state.current_ctx_thread != Some(current_thread)
-    {
+    // Threads that use the same context, or a thread that keeps making calls,
+    // don't need a switch. This matters for apps that upload textures on a
+    // background thread with a context in the same sharegroup as the main
+    // thread's, since both threads' calls are interleaved.
+    if window.is_app_gl_ctx_no_longer_current() || state.host_current_ctx != Some(current_ctx) {
         log_dbg!(
-            "Restoring guest app OpenGL context for thread {}.",
+            "Switching to guest app OpenGL context {:?} for thread {}.",
+            current_ctx,
             current_thread
         );
         gles_ctx.make_current(window);
+        state.host_current_ctx = Some(current_ctx);
     }
 
     gles_ctx
 }
+
+/// Called when a secondary thread is about to exit. Releases its current
+/// context, like a real device does.
+pub fn handle_thread_exit(env: &mut crate::Environment) {
+    let current_thread = env.current_thread;
+    let Some(Some(ctx)) = env
+        .framework_state
+        .opengles
+        .current_ctxs
+        .remove(&current_thread)
+    else {
+        return;
+    };
+    log_dbg!(
+        "Thread {} exited with current EAGLContext {:?}, releasing it.",
+        current_thread,
+        ctx
+    );
+    crate::objc::release(env, ctx);
+}
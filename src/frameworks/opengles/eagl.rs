@@ -5,17 +5,28 @@
  */
 //! EAGL.
 
+use super::memory_usage::{renderbuffer_bytes_per_pixel, MemoryUsage};
 use crate::dyld::{ConstantExports, HostConstant};
 use crate::frameworks::core_animation::ca_eagl_layer::{
     find_fullscreen_eagl_layer, get_pixels_vec_for_presenting, present_pixels,
 };
+use crate::frameworks::core_graphics::CGRect;
 use crate::frameworks::foundation::ns_string::get_static_str;
 use crate::frameworks::foundation::NSUInteger;
 use crate::gles::gles11_raw as gles11; // constants only
 use crate::gles::gles11_raw::types::*;
-use crate::gles::present::{present_frame, FpsCounter};
-use crate::gles::{create_gles1_ctx, gles1_on_gl2, GLES};
-use crate::objc::{id, msg, nil, objc_classes, release, retain, ClassExports, HostObject};
+use crate::gles::gles2_raw as gles2; // constants only
+use crate::gles::present::{
+    create_present_program_gles2, present_frame, present_frame_gles2, FpsCounter,
+    PRESENT_ATTRIBS_GLES2,
+};
+use crate::gles::{
+    create_gles1_ctx, create_gles2_ctx, gles1_on_gl2, gles2_on_gl2, GLESImplementation, GLES,
+};
+use crate::image::pixel_format::{rgb565_to_rgba8, rgba8_to_rgb565};
+use crate::objc::{
+    id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+};
 use crate::options::Options;
 use crate::window::Window;
 use std::collections::HashMap;
@@ -48,33 +59,168 @@ pub const CONSTANTS: ConstantExports = &[
     ),
 ];
 
-type EAGLRenderingAPI = u32;
+pub(super) type EAGLRenderingAPI = u32;
 const kEAGLRenderingAPIOpenGLES1: EAGLRenderingAPI = 1;
-#[allow(dead_code)]
-const kEAGLRenderingAPIOpenGLES2: EAGLRenderingAPI = 2;
+pub(super) const kEAGLRenderingAPIOpenGLES2: EAGLRenderingAPI = 2;
 #[allow(dead_code)]
 const kEAGLRenderingAPIOpenGLES3: EAGLRenderingAPI = 3;
 
-pub(super) struct EAGLContextHostObject {
-    pub(super) gles_ctx: Option<Box<dyn GLES>>,
+/// The color format of a renderbuffer bound to an `EAGLDrawable`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum DrawableColorFormat {
+    /// `kEAGLColorFormatRGBA8`.
+    RGBA8,
+    /// `kEAGLColorFormatRGB565`.
+    RGB565,
+    /// `kEAGLColorFormatRGB565`, but the host can't render to RGB565, so the
+    /// renderbuffer has RGBA8 storage and its pixels are reduced to RGB565
+    /// precision when read back. See [renderbuffer_storage_rgb565].
+    RGB565OnRGBA8,
+}
+
+pub(super) struct EAGLSharegroupHostObject {
+    /// The contexts in this sharegroup. These are weak references: a context
+    /// removes itself from this list when it is deallocated.
+    contexts: Vec<id>,
+    /// The implementation used by the contexts in this sharegroup. Host
+    /// contexts can only share objects if they use the same implementation.
+    gles_implementation: Option<GLESImplementation>,
     /// Mapping of OpenGL ES renderbuffer names to `EAGLDrawable` instances
     /// (always `CAEAGLLayer*`). Retains the instance so it won't dangle.
+    /// Renderbuffer names are shared by all contexts in a sharegroup, so this
+    /// can't be per-context.
     renderbuffer_drawable_bindings: HashMap<GLuint, id>,
+    /// The color format of each renderbuffer in
+    /// `renderbuffer_drawable_bindings`.
+    renderbuffer_color_formats: HashMap<GLuint, DrawableColorFormat>,
+    /// Scale hack factors for renderbuffers that don't use the usual one,
+    /// because of `--layer-scale-hack=`.
+    pub(super) renderbuffer_scale_hacks: HashMap<GLuint, u32>,
+    /// Estimated memory usage of the app's OpenGL ES objects in this
+    /// sharegroup.
+    pub(super) memory_usage: MemoryUsage,
+}
+impl HostObject for EAGLSharegroupHostObject {}
+
+pub(super) struct EAGLContextHostObject {
+    pub(super) gles_ctx: Option<Box<dyn GLES>>,
+    /// The API version this context was created with.
+    pub(super) api: EAGLRenderingAPI,
+    /// Program used to present frames, for OpenGL ES 2.0 contexts only. See
+    /// [present_frame_gles2].
+    present_program: Option<GLuint>,
+    /// Objects used by [present_renderbuffer], kept between frames.
+    present_resources: Option<PresentResources>,
+    /// State of [read_renderbuffer] for each renderbuffer it's used with.
+    async_readbacks: HashMap<GLuint, AsyncReadback>,
+    /// The `EAGLSharegroup*` this context belongs to (strong reference).
+    pub(super) sharegroup: id,
+    /// Error to be returned by the app's next call to `glGetError`, when
+    /// `--gles-error-checking` is in use. This is zero if there is no error.
+    pub(super) pending_error: GLenum,
     fps_counter: Option<FpsCounter>,
     next_frame_due: Option<Instant>,
 }
 impl HostObject for EAGLContextHostObject {}
 
+/// The framebuffer and texture [present_renderbuffer] copies the renderbuffer
+/// with. Creating these every frame is expensive with some drivers.
+struct PresentResources {
+    framebuffer: GLuint,
+    texture: GLuint,
+    /// The size of the texture's storage. If the renderbuffer's size changes,
+    /// the storage is reallocated.
+    texture_size: (GLsizei, GLsizei),
+}
+
+/// Pixel buffer objects [read_renderbuffer] uses to read a renderbuffer without
+/// waiting for rendering to finish, where the implementation supports it. Two
+/// are used: one being read into for the current frame, and one being read
+/// from for the previous frame.
+#[derive(Default)]
+struct AsyncReadback {
+    /// Buffer object being read into, and the size of the pixel data.
+    in_flight: Option<(GLuint, GLsizei, GLsizei)>,
+    /// Buffer object that was read from and can be reused.
+    spare: Option<GLuint>,
+}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
 
+@implementation EAGLSharegroup: NSObject
+
++ (id)alloc {
+    let host_object = Box::new(EAGLSharegroupHostObject {
+        contexts: Vec::new(),
+        gles_implementation: None,
+        renderbuffer_drawable_bindings: HashMap::new(),
+        renderbuffer_color_formats: HashMap::new(),
+        renderbuffer_scale_hacks: HashMap::new(),
+        memory_usage: Default::default(),
+    });
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    env.framework_state.opengles.sharegroups.push(new);
+    new
+}
+
+- (())dealloc {
+    env.framework_state
+        .opengles
+        .sharegroups
+        .retain(|&sharegroup| sharegroup != this);
+    let host_obj = env.objc.borrow_mut::<EAGLSharegroupHostObject>(this);
+    let contexts = std::mem::take(&mut host_obj.contexts);
+    if !contexts.is_empty() {
+        // Each context retains its sharegroup, so this means the app
+        // over-released it. Move the contexts and the shared state to a new
+        // sharegroup so they can keep working.
+        log!(
+            "Warning: {:?} was deallocated while still used by contexts {:?}, detaching them",
+            this,
+            contexts,
+        );
+        let new: id = msg_class![env; EAGLSharegroup alloc];
+        let new: id = msg![env; new init];
+        for _ in 1..contexts.len() {
+            retain(env, new);
+        }
+        for &context in &contexts {
+            env.objc.borrow_mut::<EAGLContextHostObject>(context).sharegroup = new;
+        }
+        let host_obj = env.objc.borrow_mut::<EAGLSharegroupHostObject>(this);
+        let state = EAGLSharegroupHostObject {
+            contexts,
+            gles_implementation: host_obj.gles_implementation.take(),
+            renderbuffer_drawable_bindings: std::mem::take(&mut host_obj.renderbuffer_drawable_bindings),
+            renderbuffer_color_formats: std::mem::take(&mut host_obj.renderbuffer_color_formats),
+            renderbuffer_scale_hacks: std::mem::take(&mut host_obj.renderbuffer_scale_hacks),
+            memory_usage: std::mem::take(&mut host_obj.memory_usage),
+        };
+        *env.objc.borrow_mut::<EAGLSharegroupHostObject>(new) = state;
+    }
+    let host_obj = env.objc.borrow_mut::<EAGLSharegroupHostObject>(this);
+    let bindings = std::mem::take(&mut host_obj.renderbuffer_drawable_bindings);
+    for (_renderbuffer, drawable) in bindings {
+        release(env, drawable);
+    }
+    env.objc.dealloc_object(this, &mut env.mem);
+}
+
+@end
+
 @implementation EAGLContext: NSObject
 
 + (id)alloc {
     let host_object = Box::new(EAGLContextHostObject {
         gles_ctx: None,
-        renderbuffer_drawable_bindings: HashMap::new(),
+        api: 0,
+        present_program: None,
+        present_resources: None,
+        async_readbacks: HashMap::new(),
+        sharegroup: nil,
+        pending_error: 0,
         fps_counter: None,
         next_frame_due: None,
     });
@@ -85,56 +231,174 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.framework_state.opengles.current_ctx_for_thread(env.current_thread).unwrap_or(nil)
 }
 + (bool)setCurrentContext:(id)context { // EAGLContext*
-    retain(env, context);
-
     // Clear flag value, we're changing context anyway.
     let _ = env.window_mut().is_app_gl_ctx_no_longer_current();
 
-    let current_ctx = env.framework_state.opengles.current_ctx_for_thread(env.current_thread);
-
-    if let Some(old_ctx) = std::mem::take(current_ctx) {
-        release(env, old_ctx);
-        env.framework_state.opengles.current_ctx_thread = None;
+    if context == nil {
+        env.window().make_no_gl_context_current();
+    } else {
+        let host_obj = env.objc.borrow::<EAGLContextHostObject>(context);
+        let result = host_obj.gles_ctx.as_ref().unwrap().try_make_current(env.window());
+        if let Err(e) = result {
+            log!("Warning: [EAGLContext setCurrentContext:{:?}] failed: {}", context, e);
+            // Whatever the host context is now, it's not one the app expects.
+            env.framework_state.opengles.host_current_ctx = None;
+            return false;
+        }
     }
+    env.framework_state.opengles.host_current_ctx = (context != nil).then_some(context);
 
-    // reborrow
+    // Retain before releasing, in case this is the same context.
+    retain(env, context);
     let current_ctx = env.framework_state.opengles.current_ctx_for_thread(env.current_thread);
-
-    if context != nil {
-        let host_obj = env.objc.borrow_mut::<EAGLContextHostObject>(context);
-        host_obj.gles_ctx.as_mut().unwrap().make_current(env.window.as_ref().unwrap());
-        *current_ctx = Some(context);
-        env.framework_state.opengles.current_ctx_thread = Some(env.current_thread);
+    let old_ctx = std::mem::replace(current_ctx, (context != nil).then_some(context));
+    if let Some(old_ctx) = old_ctx {
+        release(env, old_ctx);
     }
 
     true
 }
 
 - (id)initWithAPI:(EAGLRenderingAPI)api {
-    assert!(api == kEAGLRenderingAPIOpenGLES1);
+    msg![env; this initWithAPI:api sharegroup:nil]
+}
+
+- (id)initWithAPI:(EAGLRenderingAPI)api
+       sharegroup:(id)sharegroup { // EAGLSharegroup*
+    if api != kEAGLRenderingAPIOpenGLES1 && api != kEAGLRenderingAPIOpenGLES2 {
+        // Apps check for nil to find out whether an API version is supported.
+        log!("[EAGLContext initWithAPI:{} sharegroup:{:?}] Unsupported API, returning nil", api, sharegroup);
+        release(env, this);
+        return nil;
+    }
+
+    if sharegroup != nil {
+        // All contexts in a sharegroup must use the same API.
+        let contexts = &env.objc.borrow::<EAGLSharegroupHostObject>(sharegroup).contexts;
+        if let Some(&other_ctx) = contexts.first() {
+            let other_api = env.objc.borrow::<EAGLContextHostObject>(other_ctx).api;
+            if other_api != api {
+                log!("[EAGLContext initWithAPI:{} sharegroup:{:?}] Sharegroup has API {}, returning nil", api, sharegroup, other_api);
+                release(env, this);
+                return nil;
+            }
+        }
+    }
+
+    let sharegroup = if sharegroup == nil {
+        let new: id = msg_class![env; EAGLSharegroup alloc];
+        msg![env; new init]
+    } else {
+        retain(env, sharegroup)
+    };
 
     let window = env.window.as_mut().expect("OpenGL ES is not supported in headless mode");
-    let gles1_ctx = create_gles1_ctx(window, &env.options);
+
+    // If there's already a context in the sharegroup, the new host context
+    // needs to share objects with it.
+    let sharegroup_host_obj = env.objc.borrow::<EAGLSharegroupHostObject>(sharegroup);
+    let share_with = sharegroup_host_obj.contexts.first().map(|&other_ctx| {
+        let other_host_obj = env.objc.borrow::<EAGLContextHostObject>(other_ctx);
+        (
+            sharegroup_host_obj.gles_implementation.unwrap(),
+            other_host_obj.gles_ctx.as_deref().unwrap(),
+        )
+    });
+    let (mut gles_ctx, gles_implementation) = if api == kEAGLRenderingAPIOpenGLES2 {
+        let Some(res) = create_gles2_ctx(window, &env.options, share_with) else {
+            // Apps that support both APIs will fall back to OpenGL ES 1.1.
+            release(env, sharegroup);
+            release(env, this);
+            return nil;
+        };
+        res
+    } else {
+        create_gles1_ctx(window, &env.options, share_with)
+    };
 
     // Make the context current so we can get driver info from it.
     // initWithAPI: is not supposed to make the new context current (the app
     // must call setCurrentContext: for that), so we need to hide this from the
-    // app. Setting current_ctx_thread to None should cause sync_context to
+    // app. Setting host_current_ctx to None should cause sync_context to
     // switch back to the right context if the app makes an OpenGL ES call.
-    gles1_ctx.make_current(window);
-    env.framework_state.opengles.current_ctx_thread = None;
-    log!("Driver info: {}", unsafe { gles1_ctx.driver_description() });
+    gles_ctx.make_current(window);
+    env.framework_state.opengles.host_current_ctx = None;
+    log!("Driver info: {}", unsafe { gles_ctx.driver_description() });
+
+    let present_program = (api == kEAGLRenderingAPIOpenGLES2).then(|| unsafe {
+        create_present_program_gles2(&mut *gles_ctx)
+    });
+
+    let host_obj = env.objc.borrow_mut::<EAGLContextHostObject>(this);
+    host_obj.gles_ctx = Some(gles_ctx);
+    host_obj.api = api;
+    host_obj.present_program = present_program;
+    host_obj.sharegroup = sharegroup;
 
-    env.objc.borrow_mut::<EAGLContextHostObject>(this).gles_ctx = Some(gles1_ctx);
+    let sharegroup_host_obj = env.objc.borrow_mut::<EAGLSharegroupHostObject>(sharegroup);
+    sharegroup_host_obj.contexts.push(this);
+    sharegroup_host_obj.gles_implementation = Some(gles_implementation);
 
     this
 }
 
+- (id)sharegroup {
+    env.objc.borrow::<EAGLContextHostObject>(this).sharegroup
+}
+
+- (EAGLRenderingAPI)API {
+    env.objc.borrow::<EAGLContextHostObject>(this).api
+}
+
 - (())dealloc {
+    // A context that's current is retained, so this shouldn't normally find
+    // anything, but a stale pointer here would be a use-after-free.
+    let state = &mut env.framework_state.opengles;
+    for (&thread, current_ctx) in state.current_ctxs.iter_mut() {
+        if *current_ctx == Some(this) {
+            log!("Warning: {:?} deallocated while current on thread {}", this, thread);
+            *current_ctx = None;
+        }
+    }
+
+    // Destroy the host context. Its objects need to be deleted while it's
+    // current, and afterwards no context is current, so the next OpenGL ES
+    // call (from any thread) has to restore one.
     let host_obj = env.objc.borrow_mut::<EAGLContextHostObject>(this);
-    let bindings = std::mem::take(&mut host_obj.renderbuffer_drawable_bindings);
-    for (_renderbuffer, drawable) in bindings {
-        release(env, drawable);
+    if let Some(mut gles_ctx) = host_obj.gles_ctx.take() {
+        let present_program = host_obj.present_program.take();
+        let present_resources = host_obj.present_resources.take();
+        let async_readbacks = std::mem::take(&mut host_obj.async_readbacks);
+        if present_program.is_some() || present_resources.is_some() || !async_readbacks.is_empty() {
+            gles_ctx.make_current(env.window.as_ref().unwrap());
+        }
+        if let Some(program) = present_program {
+            unsafe { gles_ctx.DeleteProgram(program) };
+        }
+        if let Some(resources) = present_resources {
+            unsafe {
+                gles_ctx.DeleteFramebuffersOES(1, &resources.framebuffer);
+                gles_ctx.DeleteTextures(1, &resources.texture);
+            }
+        }
+        for readback in async_readbacks.into_values() {
+            let buffers = readback.in_flight.map(|(buffer, _, _)| buffer).into_iter().chain(readback.spare);
+            for buffer in buffers {
+                unsafe { gles_ctx.DeleteBuffers(1, &buffer) };
+            }
+        }
+        log_dbg!("Destroying host OpenGL context for {:?}", this);
+        std::mem::drop(gles_ctx);
+        env.framework_state.opengles.host_current_ctx = None;
+    }
+
+    let sharegroup = env.objc.borrow::<EAGLContextHostObject>(this).sharegroup;
+    if sharegroup != nil {
+        env.objc
+            .borrow_mut::<EAGLSharegroupHostObject>(sharegroup)
+            .contexts
+            .retain(|&ctx| ctx != this);
+        release(env, sharegroup);
     }
     env.objc.dealloc_object(this, &mut env.mem);
 }
@@ -152,36 +416,67 @@ pub const CLASSES: ClassExports = objc_classes! {
     let format_rgb565 = get_static_str(env, kEAGLColorFormatRGB565);
 
     let format: id = msg![env; props objectForKey:format_key];
-    // Theoretically this should map formats like:
-    // - kColorFormatRGBA8 => RGBA8_OES
-    // - kColorFormatRGB565 => RGB565_OES
-    // However, the specification of EXT_framebuffer_object allows the
-    // implementation to arbitrarily restrict which formats can be rendered to,
-    // and it seems like RGB565 isn't supported, at least on a machine with
-    // Intel HD Graphics 615 running macOS Monterey. I don't think RGBA8 is
-    // guaranteed either, but it at least seems to work.
-    if !msg![env; format isEqualTo:format_rgba8] && !msg![env; format isEqualTo:format_rgb565] {
-        log!("[renderbufferStorage:{:?} fromDrawable:{:?}] Warning: unhandled format {:?}, using RGBA8", target, drawable, format);
-    }
-    let internalformat = gles11::RGBA8_OES;
+    // The default format is RGBA8.
+    let rgb565_requested = msg![env; format isEqualTo:format_rgb565];
+    if format != nil && !rgb565_requested && !msg![env; format isEqualTo:format_rgba8] {
+        log_rate_limited!("[renderbufferStorage:{:?} fromDrawable:{:?}] Warning: unhandled format {:?}, using RGBA8", target, drawable, format);
+    }
+
+    let sharegroup = env.objc.borrow::<EAGLContextHostObject>(this).sharegroup;
+    let gles_implementation = env
+        .objc
+        .borrow::<EAGLSharegroupHostObject>(sharegroup)
+        .gles_implementation
+        .unwrap();
+
+    let bounds: CGRect = msg![env; drawable bounds];
+    let layer_size = (bounds.size.width as u32, bounds.size.height as u32);
+    let layer_scale_hack = env.options.layer_scale_hack(layer_size);
 
     let window = env.window.as_mut().expect("OpenGL ES is not supported in headless mode");
 
-    // FIXME: get width and height from the layer!
-    let (width, height) = window.size_unrotated_scalehacked();
+    let (width, height) = if let Some(factor) = layer_scale_hack {
+        (layer_size.0 * factor.get(), layer_size.1 * factor.get())
+    } else {
+        // FIXME: get width and height from the layer!
+        window.size_unrotated_scalehacked()
+    };
 
     // Unclear from documentation if this method requires an appropriate context
     // to already be active, but that seems to be the case in practice?
     let gles = super::sync_context(&mut env.framework_state.opengles, &mut env.objc, window, env.current_thread);
-    let renderbuffer: GLuint = unsafe {
-        gles.RenderbufferStorageOES(target, internalformat, width.try_into().unwrap(), height.try_into().unwrap());
+    let (renderbuffer, color_format): (GLuint, _) = unsafe {
+        let (width, height) = (width.try_into().unwrap(), height.try_into().unwrap());
+        let color_format = if rgb565_requested {
+            renderbuffer_storage_rgb565(gles, gles_implementation, width, height)
+        } else {
+            gles.RenderbufferStorageOES(target, gles11::RGBA8_OES, width, height);
+            DrawableColorFormat::RGBA8
+        };
         let mut renderbuffer = 0;
         gles.GetIntegerv(gles11::RENDERBUFFER_BINDING_OES, &mut renderbuffer);
-        renderbuffer as _
+        (renderbuffer as _, color_format)
+    };
+
+    // The memory usage is what the app would be using on a real device, so
+    // emulated RGB565 counts as RGB565.
+    let internalformat = match color_format {
+        DrawableColorFormat::RGBA8 => gles11::RGBA8_OES,
+        DrawableColorFormat::RGB565 | DrawableColorFormat::RGB565OnRGBA8 => gles11::RGB565_OES,
     };
 
     retain(env, drawable);
-    let host_obj = env.objc.borrow_mut::<EAGLContextHostObject>(this);
+    let host_obj = env.objc.borrow_mut::<EAGLSharegroupHostObject>(sharegroup);
+    host_obj.memory_usage.set_renderbuffer(
+        renderbuffer,
+        (width as usize) * (height as usize) * renderbuffer_bytes_per_pixel(internalformat),
+    );
+    host_obj.renderbuffer_color_formats.insert(renderbuffer, color_format);
+    if let Some(factor) = layer_scale_hack {
+        host_obj.renderbuffer_scale_hacks.insert(renderbuffer, factor.get());
+    } else {
+        host_obj.renderbuffer_scale_hacks.remove(&renderbuffer);
+    }
     if let Some(old_drawable) = host_obj.renderbuffer_drawable_bindings.insert(
         renderbuffer,
         drawable
@@ -199,17 +494,26 @@ pub const CLASSES: ClassExports = objc_classes! {
     // delayed, so this needs to be checked before returning.
     let sleep_for = limit_framerate(&mut env.objc.borrow_mut::<EAGLContextHostObject>(this).next_frame_due, &env.options);
 
-    if env.options.print_fps {
-        env
+    let sharegroup = env.objc.borrow::<EAGLContextHostObject>(this).sharegroup;
+
+    if env.options.print_fps
+        && env
             .objc
             .borrow_mut::<EAGLContextHostObject>(this)
             .fps_counter
             .get_or_insert_with(FpsCounter::start)
-            .count_frame(format_args!("EAGLContext {:?}", this));
+            .count_frame(format_args!("EAGLContext {:?}", this))
+    {
+        env.objc
+            .borrow::<EAGLSharegroupHostObject>(sharegroup)
+            .memory_usage
+            .report(format_args!("EAGLSharegroup {:?}", sharegroup));
     }
 
     let fullscreen_layer = find_fullscreen_eagl_layer(env);
 
+    let present_program = env.objc.borrow::<EAGLContextHostObject>(this).present_program;
+
     // Unclear from documentation if this method requires the context to be
     // current, but it would be weird if it didn't?
     let window = env.window.as_mut().expect("OpenGL ES is not supported in headless mode");
@@ -221,12 +525,12 @@ pub const CLASSES: ClassExports = objc_classes! {
         renderbuffer as _
     };
 
-    let &drawable = env
-        .objc
-        .borrow::<EAGLContextHostObject>(this)
+    let sharegroup_host_obj = env.objc.borrow::<EAGLSharegroupHostObject>(sharegroup);
+    let &drawable = sharegroup_host_obj
         .renderbuffer_drawable_bindings
         .get(&renderbuffer)
         .expect("Can't present a renderbuffer not bound to a drawable!");
+    let color_format = sharegroup_host_obj.renderbuffer_color_formats[&renderbuffer];
 
     // We're presenting to the opaque CAEAGLLayer that covers the screen.
     // We can use the fast path where we skip composition and present directly.
@@ -236,45 +540,44 @@ pub const CLASSES: ClassExports = objc_classes! {
             drawable,
             renderbuffer,
         );
+        // This is taken out of the host object while presenting, since the
+        // host context is borrowed from it too.
+        let mut present_resources = env.objc.borrow_mut::<EAGLContextHostObject>(this).present_resources.take();
         // re-borrow
         let gles = super::sync_context(&mut env.framework_state.opengles, &mut env.objc, env.window.as_mut().unwrap(), env.current_thread);
         unsafe {
-            present_renderbuffer(gles, env.window.as_mut().unwrap());
+            present_renderbuffer(gles, env.window.as_mut().unwrap(), present_program, &mut present_resources);
         }
+        env.objc.borrow_mut::<EAGLContextHostObject>(this).present_resources = present_resources;
     } else {
-        if fullscreen_layer != nil {
-            // If there's a single layer that covers the screen, and this isn't
-            // it, there's no point in presenting the output because it won't be
-            // seen. Using a noisy log because it's a weird scenario and might
-            // indicate a bug.
-            log!(
-                "Layer {:?} is not the fullscreen layer {:?}, skipping presentation of renderbuffer {:?}!",
-                drawable,
-                fullscreen_layer,
-                renderbuffer,
-            );
-            if let Some(sleep_for) = sleep_for {
-                env.sleep(sleep_for, /* tail_call: */ false);
-            }
-            return true;
-        }
+        // If there's a fullscreen layer and this isn't it, this layer isn't
+        // currently visible (find_fullscreen_eagl_layer() only returns a layer
+        // if it's the only visible one), but its contents are still kept up
+        // to date in case it becomes visible later.
 
         // The very slow and inefficient path: not only does glReadPixels()
         // block the thread until rendering finishes, but the result has to be
         // copied back to system RAM, and then will have to be copied to VRAM
-        // again during composition. find_fullscreen_eagl_layer() exists to
-        // avoid this.
+        // again during composition. This is unavoidable when several
+        // CAEAGLLayers are visible, since they have to be composited together,
+        // but when there's a single fullscreen one,
+        // find_fullscreen_eagl_layer() lets the fast path above be used.
         log_dbg!(
-            "There is no fullscreen layer, presenting renderbuffer {:?} to layer {:?} by copying to RAM (slow path).",
-            renderbuffer,
+            "Layer {:?} is not the fullscreen layer {:?}, presenting renderbuffer {:?} to it by copying to RAM (slow path).",
             drawable,
+            fullscreen_layer,
+            renderbuffer,
         );
         let pixels_vec = get_pixels_vec_for_presenting(env, drawable);
+        // This is taken out of the host object while reading, since the host
+        // context is borrowed from it too.
+        let mut async_readback = env.objc.borrow_mut::<EAGLContextHostObject>(this).async_readbacks.remove(&renderbuffer).unwrap_or_default();
         // re-borrow
         let gles = super::sync_context(&mut env.framework_state.opengles, &mut env.objc, env.window.as_mut().unwrap(), env.current_thread);
         let (pixels_vec, width, height) = unsafe {
-            read_renderbuffer(gles, pixels_vec)
+            read_renderbuffer(gles, pixels_vec, color_format, &mut async_readback)
         };
+        env.objc.borrow_mut::<EAGLContextHostObject>(this).async_readbacks.insert(renderbuffer, async_readback);
         present_pixels(env, drawable, pixels_vec, width, height);
     }
 
@@ -292,9 +595,9 @@ pub const CLASSES: ClassExports = objc_classes! {
 /// Implement framerate limiting.
 ///
 /// The real iPhone OS seems to force 60Hz v-sync in `presentRenderbuffer:`.
-/// touchHLE does not force v-sync, and its users might not have 60Hz monitors
-/// in any case, so to avoid excessive FPS or games running too fast, we need
-/// to simulate it.
+/// touchHLE only uses v-sync if `--vsync` is set, and its users might not have
+/// 60Hz monitors in any case, so to avoid excessive FPS or games running too
+/// fast, we need to simulate it.
 ///
 /// V-sync is essentially a limiter with no "slop", or allowance for frames
 /// arriving late: if the frame misses a 60Hz interval, it must wait until the
@@ -403,39 +706,137 @@ unsafe fn get_renderbuffer_size(gles: &mut dyn GLES) -> (GLsizei, GLsizei) {
     (width, height)
 }
 
-/// Copies the pixels in a renderbuffer bound to `GL_RENDERBUFFER_BINDING_OES`
-/// (which should be provided by the app) to a provided [Vec], trying to avoid
-/// noticeably modifying OpenGL ES state while doing so.
-///
-/// This uses `glReadPixels()`, with all the associated performance risks. Any
-/// existing content in the [Vec] will bereplaced. The format is RGBA8.
-/// The returned values are the [Vec], the width and height.
+/// Allocates storage for the renderbuffer bound to
+/// `GL_RENDERBUFFER_BINDING_OES`, for a drawable with the
+/// `kEAGLColorFormatRGB565` format. Returns the format actually used.
 ///
-/// The provided context must be current.
-unsafe fn read_renderbuffer(gles: &mut dyn GLES, mut pixel_buffer: Vec<u8>) -> (Vec<u8>, u32, u32) {
-    let renderbuffer: GLuint = get_int(gles, gles11::RENDERBUFFER_BINDING_OES) as _;
-    let (width, height) = get_renderbuffer_size(gles);
-    let width_u32: u32 = width.try_into().unwrap();
-    let height_u32: u32 = height.try_into().unwrap();
+/// The specification of EXT_framebuffer_object allows the implementation to
+/// arbitrarily restrict which formats can be rendered to, and desktop OpenGL
+/// 2.1 doesn't accept RGB565 renderbuffers at all (it seems to be unsupported
+/// on a machine with Intel HD Graphics 615 running macOS Monterey, for
+/// example). In that case RGBA8 is used instead, which has more precision than
+/// the app expects; [read_renderbuffer] compensates for this, but the fast path
+/// in [present_renderbuffer] does not, since it would require a round trip
+/// through system RAM for every frame.
+unsafe fn renderbuffer_storage_rgb565(
+    gles: &mut dyn GLES,
+    gles_implementation: GLESImplementation,
+    width: GLsizei,
+    height: GLsizei,
+) -> DrawableColorFormat {
+    match gles_implementation {
+        GLESImplementation::GLES1Native | GLESImplementation::GLES2Native => (),
+        // Trying anyway would leave a GL error for the app to find.
+        GLESImplementation::GLES1OnGL2 | GLESImplementation::GLES2OnGL2 => {
+            gles.RenderbufferStorageOES(gles11::RENDERBUFFER_OES, gles11::RGBA8_OES, width, height);
+            return DrawableColorFormat::RGB565OnRGBA8;
+        }
+    }
 
-    // To avoid confusing the guest app, we need to be able to undo any
-    // state changes we make.
-    let old_framebuffer: GLuint = get_int(gles, gles11::FRAMEBUFFER_BINDING_OES) as _;
+    gles.RenderbufferStorageOES(gles11::RENDERBUFFER_OES, gles11::RGB565_OES, width, height);
 
-    // Create a framebuffer we can use to read from the renderbuffer
-    let mut src_framebuffer = 0;
-    gles.GenFramebuffersOES(1, &mut src_framebuffer);
-    gles.BindFramebufferOES(gles11::FRAMEBUFFER_OES, src_framebuffer);
+    // Check whether the host can actually render to it.
+    let renderbuffer: GLuint = get_int(gles, gles11::RENDERBUFFER_BINDING_OES) as _;
+    let old_framebuffer: GLuint = get_int(gles, gles11::FRAMEBUFFER_BINDING_OES) as _;
+    let mut framebuffer = 0;
+    gles.GenFramebuffersOES(1, &mut framebuffer);
+    gles.BindFramebufferOES(gles11::FRAMEBUFFER_OES, framebuffer);
     gles.FramebufferRenderbufferOES(
         gles11::FRAMEBUFFER_OES,
         gles11::COLOR_ATTACHMENT0_OES,
         gles11::RENDERBUFFER_OES,
         renderbuffer,
     );
+    let status = gles.CheckFramebufferStatusOES(gles11::FRAMEBUFFER_OES);
+    gles.DeleteFramebuffersOES(1, &framebuffer);
+    gles.BindFramebufferOES(gles11::FRAMEBUFFER_OES, old_framebuffer);
 
-    // Read the pixels
-    let size = (width_u32 as usize)
-        .checked_mul(height_u32 as usize)
+    if status == gles11::FRAMEBUFFER_COMPLETE_OES {
+        DrawableColorFormat::RGB565
+    } else {
+        log!(
+            "Warning: host can't render to RGB565 renderbuffer (status {:#x}), using RGBA8",
+            status
+        );
+        gles.RenderbufferStorageOES(gles11::RENDERBUFFER_OES, gles11::RGBA8_OES, width, height);
+        DrawableColorFormat::RGB565OnRGBA8
+    }
+}
+
+/// If `renderbuffer`, which must be bound to `GL_RENDERBUFFER_BINDING_OES`, is
+/// multisampled (see `APPLE_framebuffer_multisample`), its pixels can't be
+/// copied or read directly. In that case, it is resolved to a new
+/// single-sampled renderbuffer, which is returned and must be deleted by the
+/// caller once it's done with it.
+///
+/// This changes the framebuffer bindings, so the caller must restore them.
+unsafe fn resolve_renderbuffer_if_multisampled(
+    gles: &mut dyn GLES,
+    renderbuffer: GLuint,
+    width: GLsizei,
+    height: GLsizei,
+) -> Option<GLuint> {
+    let mut samples = 0;
+    gles.GetRenderbufferParameterivOES(
+        gles11::RENDERBUFFER_OES,
+        gles11::RENDERBUFFER_SAMPLES_APPLE,
+        &mut samples,
+    );
+    if samples == 0 {
+        return None;
+    }
+    log_dbg!(
+        "Renderbuffer {} has {} samples, resolving it before reading",
+        renderbuffer,
+        samples
+    );
+
+    let mut resolved_renderbuffer = 0;
+    gles.GenRenderbuffersOES(1, &mut resolved_renderbuffer);
+    gles.BindRenderbufferOES(gles11::RENDERBUFFER_OES, resolved_renderbuffer);
+    gles.RenderbufferStorageOES(gles11::RENDERBUFFER_OES, gles11::RGBA8_OES, width, height);
+    gles.BindRenderbufferOES(gles11::RENDERBUFFER_OES, renderbuffer);
+
+    let mut framebuffers = [0; 2];
+    gles.GenFramebuffersOES(2, framebuffers.as_mut_ptr());
+    gles.BindFramebufferOES(gles11::READ_FRAMEBUFFER_APPLE, framebuffers[0]);
+    gles.FramebufferRenderbufferOES(
+        gles11::READ_FRAMEBUFFER_APPLE,
+        gles11::COLOR_ATTACHMENT0_OES,
+        gles11::RENDERBUFFER_OES,
+        renderbuffer,
+    );
+    gles.BindFramebufferOES(gles11::DRAW_FRAMEBUFFER_APPLE, framebuffers[1]);
+    gles.FramebufferRenderbufferOES(
+        gles11::DRAW_FRAMEBUFFER_APPLE,
+        gles11::COLOR_ATTACHMENT0_OES,
+        gles11::RENDERBUFFER_OES,
+        resolved_renderbuffer,
+    );
+
+    // The resolve is affected by the scissor test.
+    let scissor_was_enabled = gles.IsEnabled(gles11::SCISSOR_TEST) != gles11::FALSE;
+    gles.Disable(gles11::SCISSOR_TEST);
+    gles.ResolveMultisampleFramebufferAPPLE();
+    if scissor_was_enabled {
+        gles.Enable(gles11::SCISSOR_TEST);
+    }
+
+    gles.DeleteFramebuffersOES(2, framebuffers.as_ptr());
+
+    Some(resolved_renderbuffer)
+}
+
+/// Synchronous `glReadPixels()` of the current framebuffer, for
+/// [read_renderbuffer]. Any existing content in the [Vec] will be replaced.
+unsafe fn read_pixels(
+    gles: &mut dyn GLES,
+    width: GLsizei,
+    height: GLsizei,
+    pixel_buffer: &mut Vec<u8>,
+) {
+    let size = (width as usize)
+        .checked_mul(height as usize)
         .unwrap()
         .checked_mul(4)
         .unwrap();
@@ -458,9 +859,96 @@ unsafe fn read_renderbuffer(gles: &mut dyn GLES, mut pixel_buffer: Vec<u8>) -> (
         Instant::now().saturating_duration_since(before)
     );
     pixel_buffer.set_len(size);
+}
+
+/// Copies the pixels in a renderbuffer bound to `GL_RENDERBUFFER_BINDING_OES`
+/// (which should be provided by the app) to a provided [Vec], trying to avoid
+/// noticeably modifying OpenGL ES state while doing so.
+///
+/// This uses `glReadPixels()`, with all the associated performance risks. Any
+/// existing content in the [Vec] will be replaced. The format is RGBA8, but
+/// if the renderbuffer's `color_format` is RGB565, the alpha channel is opaque
+/// and the pixels have RGB565 precision.
+/// The returned values are the [Vec], the width and height.
+///
+/// If the implementation supports [GLES::start_read_pixels_async], the pixels
+/// returned are those read by the previous call with the same
+/// `async_readback`, i.e. they're one frame behind. This means the read
+/// doesn't have to wait for rendering of the current frame to finish.
+///
+/// The provided context must be current.
+unsafe fn read_renderbuffer(
+    gles: &mut dyn GLES,
+    mut pixel_buffer: Vec<u8>,
+    color_format: DrawableColorFormat,
+    async_readback: &mut AsyncReadback,
+) -> (Vec<u8>, u32, u32) {
+    let renderbuffer: GLuint = get_int(gles, gles11::RENDERBUFFER_BINDING_OES) as _;
+    let (width, height) = get_renderbuffer_size(gles);
+
+    // To avoid confusing the guest app, we need to be able to undo any
+    // state changes we make.
+    let old_framebuffer: GLuint = get_int(gles, gles11::FRAMEBUFFER_BINDING_OES) as _;
+
+    let resolved_renderbuffer =
+        resolve_renderbuffer_if_multisampled(gles, renderbuffer, width, height);
+
+    // Create a framebuffer we can use to read from the renderbuffer
+    let mut src_framebuffer = 0;
+    gles.GenFramebuffersOES(1, &mut src_framebuffer);
+    gles.BindFramebufferOES(gles11::FRAMEBUFFER_OES, src_framebuffer);
+    gles.FramebufferRenderbufferOES(
+        gles11::FRAMEBUFFER_OES,
+        gles11::COLOR_ATTACHMENT0_OES,
+        gles11::RENDERBUFFER_OES,
+        resolved_renderbuffer.unwrap_or(renderbuffer),
+    );
+
+    // Read the pixels
+    let spare = async_readback.spare.take().unwrap_or(0);
+    let (width, height) = match gles.start_read_pixels_async(spare, width, height) {
+        Some(buffer) => match async_readback.in_flight.replace((buffer, width, height)) {
+            Some((old_buffer, old_width, old_height)) => {
+                let before = Instant::now();
+                gles.finish_read_pixels_async(old_buffer, old_width, old_height, &mut pixel_buffer);
+                log_dbg!(
+                    "Reading previous frame from pixel buffer object {} took {:?}",
+                    old_buffer,
+                    Instant::now().saturating_duration_since(before)
+                );
+                async_readback.spare = Some(old_buffer);
+                (old_width, old_height)
+            }
+            // There's no previous frame the first time, so this frame must be
+            // read synchronously. It will be presented twice.
+            None => {
+                read_pixels(gles, width, height, &mut pixel_buffer);
+                (width, height)
+            }
+        },
+        None => {
+            read_pixels(gles, width, height, &mut pixel_buffer);
+            (width, height)
+        }
+    };
+    let width_u32: u32 = width.try_into().unwrap();
+    let height_u32: u32 = height.try_into().unwrap();
+
+    // A real RGB565 renderbuffer already reads back with opaque alpha, but an
+    // emulated one has to be converted so the app sees what it would have on
+    // a real device.
+    if color_format == DrawableColorFormat::RGB565OnRGBA8 {
+        let mut rgb565_pixels = Vec::new();
+        rgba8_to_rgb565(&pixel_buffer, &mut rgb565_pixels);
+        pixel_buffer.clear();
+        rgb565_to_rgba8(&rgb565_pixels, &mut pixel_buffer);
+    }
 
     // Clean up the framebuffer object since we no longer need it.
     gles.DeleteFramebuffersOES(1, &src_framebuffer);
+    if let Some(resolved_renderbuffer) = resolved_renderbuffer {
+        gles.DeleteRenderbuffersOES(1, &resolved_renderbuffer);
+    }
 
     // Restore the framebuffer binding
     gles.BindFramebufferOES(gles11::FRAMEBUFFER_OES, old_framebuffer);
@@ -473,8 +961,19 @@ unsafe fn read_renderbuffer(gles: &mut dyn GLES, mut pixel_buffer: Vec<u8>) -> (
 /// [present_frame], trying to avoid noticeably modifying OpenGL ES state while
 /// doing so. The front and back buffers are then swapped.
 ///
+/// For OpenGL ES 2.0 contexts, `present_program` must be provided, and
+/// [present_frame_gles2] is used instead.
+///
+/// The framebuffer and texture used for this are created on the first call and
+/// stored in `resources`, so they can be reused by later calls.
+///
 /// The provided context must be current.
-unsafe fn present_renderbuffer(gles: &mut dyn GLES, window: &mut Window) {
+unsafe fn present_renderbuffer(
+    gles: &mut dyn GLES,
+    window: &mut Window,
+    present_program: Option<GLuint>,
+    resources: &mut Option<PresentResources>,
+) {
     // We can't directly copy the content of the renderbuffer to the default
     // framebuffer (the window), but if we attach it to a framebuffer object, we
     // can use glCopyTexImage2D() to copy it to a texture, which we can then
@@ -487,46 +986,109 @@ unsafe fn present_renderbuffer(gles: &mut dyn GLES, window: &mut Window) {
     // To avoid confusing the guest app, we need to be able to undo any
     // state changes we make.
     let old_framebuffer: GLuint = get_int(gles, gles11::FRAMEBUFFER_BINDING_OES) as _;
+    // The program used with OpenGL ES 2.0 samples from the first texture unit.
+    let old_active_texture: Option<GLenum> = present_program.map(|_| {
+        let old_active_texture = get_int(gles, gles11::ACTIVE_TEXTURE) as _;
+        gles.ActiveTexture(gles11::TEXTURE0);
+        old_active_texture
+    });
     let old_texture_2d: GLuint = get_int(gles, gles11::TEXTURE_BINDING_2D) as _;
 
-    // Create a framebuffer we can use to read from the renderbuffer
-    let mut src_framebuffer = 0;
-    gles.GenFramebuffersOES(1, &mut src_framebuffer);
-    gles.BindFramebufferOES(gles11::FRAMEBUFFER_OES, src_framebuffer);
+    let resolved_renderbuffer =
+        resolve_renderbuffer_if_multisampled(gles, renderbuffer, width, height);
+
+    let resources = resources.get_or_insert_with(|| {
+        let mut framebuffer = 0;
+        gles.GenFramebuffersOES(1, &mut framebuffer);
+        let mut texture = 0;
+        gles.GenTextures(1, &mut texture);
+        gles.BindTexture(gles11::TEXTURE_2D, texture);
+        // The texture will not have any mip levels so we must ensure the
+        // filter does not use them, else rendering will fail.
+        gles.TexParameteri(
+            gles11::TEXTURE_2D,
+            gles11::TEXTURE_MIN_FILTER,
+            gles11::LINEAR as _,
+        );
+        PresentResources {
+            framebuffer,
+            texture,
+            texture_size: (0, 0),
+        }
+    });
+
+    // Attach the renderbuffer to the framebuffer so we can read from it
+    gles.BindFramebufferOES(gles11::FRAMEBUFFER_OES, resources.framebuffer);
     gles.FramebufferRenderbufferOES(
         gles11::FRAMEBUFFER_OES,
         gles11::COLOR_ATTACHMENT0_OES,
         gles11::RENDERBUFFER_OES,
-        renderbuffer,
+        resolved_renderbuffer.unwrap_or(renderbuffer),
     );
 
-    // Create a texture with a copy of the pixels in the framebuffer
-    let mut texture: GLuint = 0;
-    gles.GenTextures(1, &mut texture);
-    gles.BindTexture(gles11::TEXTURE_2D, texture);
-    gles.CopyTexImage2D(
-        gles11::TEXTURE_2D,
-        0,
-        gles11::RGB as _,
-        0,
-        0,
-        width,
-        height,
+    // Copy the pixels in the framebuffer to the texture, reallocating its
+    // storage only if the size changed.
+    gles.BindTexture(gles11::TEXTURE_2D, resources.texture);
+    if resources.texture_size == (width, height) {
+        gles.CopyTexSubImage2D(gles11::TEXTURE_2D, 0, 0, 0, 0, 0, width, height);
+    } else {
+        log_dbg!(
+            "Allocating {}x{} texture {} for presenting",
+            width,
+            height,
+            resources.texture
+        );
+        gles.CopyTexImage2D(
+            gles11::TEXTURE_2D,
+            0,
+            gles11::RGB as _,
+            0,
+            0,
+            width,
+            height,
+            0,
+        );
+        resources.texture_size = (width, height);
+    }
+
+    // Detach the renderbuffer, so the framebuffer doesn't keep it alive if
+    // the app deletes it, and set the framebuffer binding back to zero, so
+    // rendering will go to the default framebuffer (the window).
+    gles.FramebufferRenderbufferOES(
+        gles11::FRAMEBUFFER_OES,
+        gles11::COLOR_ATTACHMENT0_OES,
+        gles11::RENDERBUFFER_OES,
         0,
     );
-    // The texture will not have any mip levels so we must ensure the filter
-    // does not use them, else rendering will fail.
-    gles.TexParameteri(
-        gles11::TEXTURE_2D,
-        gles11::TEXTURE_MIN_FILTER,
-        gles11::LINEAR as _,
-    );
+    gles.BindFramebufferOES(gles11::FRAMEBUFFER_OES, 0);
+    if let Some(resolved_renderbuffer) = resolved_renderbuffer {
+        gles.DeleteRenderbuffersOES(1, &resolved_renderbuffer);
+    }
 
-    // Clean up the framebuffer object since we no longer need it.
-    // This also sets the framebuffer bindings back to zero, so rendering
-    // will go to the default framebuffer (the window).
-    gles.DeleteFramebuffersOES(1, &src_framebuffer);
+    if let Some(program) = present_program {
+        draw_frame_gles2(gles, window, program);
+    } else {
+        draw_frame_gles1(gles, window);
+    }
+
+    // SDL2's documentation warns 0 should be bound to the draw framebuffer
+    // when swapping the window, so this is the perfect moment.
+    window.record_frame(Some(&mut *gles));
+    window.swap_window();
+
+    // Restore the other bindings
+    gles.BindTexture(gles11::TEXTURE_2D, old_texture_2d);
+    if let Some(old_active_texture) = old_active_texture {
+        gles.ActiveTexture(old_active_texture);
+    }
+    gles.BindFramebufferOES(gles11::FRAMEBUFFER_OES, old_framebuffer);
+
+    //{ let err = gl21::GetError(); if err != 0 { panic!("{:#x}", err); } }
+}
 
+/// Draws a frame with [present_frame], for [present_renderbuffer]. This resets
+/// and restores the OpenGL ES 1.1 state that could affect the drawing.
+unsafe fn draw_frame_gles1(gles: &mut dyn GLES, window: &mut Window) {
     // Reset various things that could affect the quad or virtual cursor we're
     // going to draw. Back up the old state while doing so, so it can be
     // restored later. The app's subsequent drawing will be messed up if we
@@ -596,12 +1158,9 @@ unsafe fn present_renderbuffer(gles: &mut dyn GLES, window: &mut Window) {
         gles,
         window.viewport(),
         window.rotation_matrix(),
-        window.virtual_cursor_visible_at(),
+        &window.virtual_cursors_visible_at(),
     );
 
-    // Clean up the texture
-    gles.DeleteTextures(1, &texture);
-
     // Restore all the state saved before rendering
     for (&is_enabled, info) in old_arrays.iter().zip(gles1_on_gl2::ARRAYS.iter()) {
         match is_enabled {
@@ -663,14 +1222,107 @@ unsafe fn present_renderbuffer(gles: &mut dyn GLES, window: &mut Window) {
         gles11::TEXTURE_ENV_MODE,
         old_tex_env_mode_arr.as_ptr().cast(),
     );
+}
 
-    // SDL2's documentation warns 0 should be bound to the draw framebuffer
-    // when swapping the window, so this is the perfect moment.
-    window.swap_window();
+/// Like [draw_frame_gles1], but for OpenGL ES 2.0 contexts, using
+/// [present_frame_gles2].
+unsafe fn draw_frame_gles2(gles: &mut dyn GLES, window: &mut Window, program: GLuint) {
+    // Reset various things that could affect the quad or virtual cursor we're
+    // going to draw. Back up the old state while doing so, so it can be
+    // restored later. Most state is part of the program, which makes this much
+    // simpler than for OpenGL ES 1.1.
+    let old_capabilities = {
+        let mut old_capabilities = [gles11::FALSE; gles2_on_gl2::CAPABILITIES.len()];
+        for (is_enabled, &name) in old_capabilities
+            .iter_mut()
+            .zip(gles2_on_gl2::CAPABILITIES.iter())
+        {
+            gles.GetBooleanv(name, is_enabled);
+            gles.Disable(name);
+        }
+        old_capabilities
+    };
+    let old_attribs = PRESENT_ATTRIBS_GLES2.map(|index| {
+        let get_attrib_int = |gles: &mut dyn GLES, pname| {
+            let mut res = 0;
+            gles.GetVertexAttribiv(index, pname, &mut res);
+            res
+        };
+        let enabled = get_attrib_int(gles, gles2::VERTEX_ATTRIB_ARRAY_ENABLED);
+        let binding = get_attrib_int(gles, gles2::VERTEX_ATTRIB_ARRAY_BUFFER_BINDING);
+        let size = get_attrib_int(gles, gles2::VERTEX_ATTRIB_ARRAY_SIZE);
+        let type_ = get_attrib_int(gles, gles2::VERTEX_ATTRIB_ARRAY_TYPE);
+        let normalized = get_attrib_int(gles, gles2::VERTEX_ATTRIB_ARRAY_NORMALIZED);
+        let stride = get_attrib_int(gles, gles2::VERTEX_ATTRIB_ARRAY_STRIDE);
+        let mut pointer = std::ptr::null();
+        gles.GetVertexAttribPointerv(index, gles2::VERTEX_ATTRIB_ARRAY_POINTER, &mut pointer);
+        (enabled, binding, size, type_, normalized, stride, pointer)
+    });
 
-    // Restore the other bindings
-    gles.BindTexture(gles11::TEXTURE_2D, old_texture_2d);
-    gles.BindFramebufferOES(gles11::FRAMEBUFFER_OES, old_framebuffer);
+    // Back up other things that will be modified while drawing.
+    let old_program: GLuint = get_int(gles, gles2::CURRENT_PROGRAM) as _;
+    let old_viewport: (GLint, GLint, GLsizei, GLsizei) = {
+        let [x, y, width, height] = get_ints(gles, gles11::VIEWPORT);
+        (x, y, width as _, height as _)
+    };
+    let old_clear_color: [GLfloat; 4] = get_floats(gles, gles11::COLOR_CLEAR_VALUE);
+    let old_array_buffer: GLuint = get_int(gles, gles11::ARRAY_BUFFER_BINDING) as _;
+    let old_blend_src_rgb: GLenum = get_int(gles, gles2::BLEND_SRC_RGB) as _;
+    let old_blend_dst_rgb: GLenum = get_int(gles, gles2::BLEND_DST_RGB) as _;
+    let old_blend_src_alpha: GLenum = get_int(gles, gles2::BLEND_SRC_ALPHA) as _;
+    let old_blend_dst_alpha: GLenum = get_int(gles, gles2::BLEND_DST_ALPHA) as _;
 
-    //{ let err = gl21::GetError(); if err != 0 { panic!("{:#x}", err); } }
+    // Draw the quad
+    present_frame_gles2(
+        gles,
+        program,
+        window.viewport(),
+        window.rotation_matrix(),
+        &window.virtual_cursors_visible_at(),
+    );
+
+    // Restore all the state saved before rendering
+    for (&is_enabled, &name) in old_capabilities
+        .iter()
+        .zip(gles2_on_gl2::CAPABILITIES.iter())
+    {
+        match is_enabled {
+            gles11::TRUE => gles.Enable(name),
+            gles11::FALSE => gles.Disable(name),
+            _ => unreachable!(),
+        }
+    }
+    for (index, (enabled, binding, size, type_, normalized, stride, pointer)) in
+        PRESENT_ATTRIBS_GLES2.into_iter().zip(old_attribs)
+    {
+        // GL_ARRAY_BUFFER is implicitly used by glVertexAttribPointer but is
+        // also an independent binding.
+        gles.BindBuffer(gles11::ARRAY_BUFFER, binding as _);
+        gles.VertexAttribPointer(index, size, type_ as _, normalized as _, stride, pointer);
+        if enabled != 0 {
+            gles.EnableVertexAttribArray(index);
+        } else {
+            gles.DisableVertexAttribArray(index);
+        }
+    }
+    gles.BindBuffer(gles11::ARRAY_BUFFER, old_array_buffer);
+    gles.UseProgram(old_program);
+    gles.Viewport(
+        old_viewport.0,
+        old_viewport.1,
+        old_viewport.2,
+        old_viewport.3,
+    );
+    gles.ClearColor(
+        old_clear_color[0],
+        old_clear_color[1],
+        old_clear_color[2],
+        old_clear_color[3],
+    );
+    gles.BlendFuncSeparate(
+        old_blend_src_rgb,
+        old_blend_dst_rgb,
+        old_blend_src_alpha,
+        old_blend_dst_alpha,
+    );
 }
@@ -12,16 +12,21 @@
 //! depending on the value of `pname`, using the upper bound (4 in this case)
 //! every time is never going to cause a problem in practice.
 
+use super::eagl::{
+    kEAGLRenderingAPIOpenGLES2, EAGLContextHostObject, EAGLRenderingAPI, EAGLSharegroupHostObject,
+};
+use super::memory_usage::{renderbuffer_bytes_per_pixel, MemoryUsage};
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::gles::gles11_raw as gles11; // constants only
+use crate::gles::gles2_raw as gles2; // constants only
 use crate::gles::GLES;
 use crate::mem::{ConstPtr, ConstVoidPtr, GuestISize, GuestUSize, Mem, MutPtr, Ptr};
 use crate::Environment;
 
 // These types are the same size in guest code (32-bit) and host code (64-bit).
 use crate::gles::gles11_raw::types::{
-    GLbitfield, GLboolean, GLclampf, GLclampx, GLenum, GLfixed, GLfloat, GLint, GLsizei, GLubyte,
-    GLuint, GLvoid,
+    GLbitfield, GLboolean, GLchar, GLclampf, GLclampx, GLenum, GLfixed, GLfloat, GLint, GLsizei,
+    GLubyte, GLuint, GLvoid,
 };
 // These types have different sizes, so some care is needed.
 use crate::gles::gles11_raw::types::{GLintptr as HostGLintptr, GLsizeiptr as HostGLsizeiptr};
@@ -41,13 +46,260 @@ where
         env.current_thread,
     );
 
-    //panic_on_gl_errors(&mut **gles);
+    if !env.options.gles_error_checking {
+        //panic_on_gl_errors(&mut **gles);
+        let res = f(gles, &mut env.mem);
+        //panic_on_gl_errors(&mut **gles);
+        return res;
+    }
+
+    // Any errors that are already pending must have been caused by touchHLE's
+    // own use of the context (e.g. in presentRenderbuffer:), and the app
+    // shouldn't see those.
+    loop {
+        let err = unsafe { gles.GetError() };
+        if err == 0 {
+            break;
+        }
+        log!(
+            "Warning: Host OpenGL error {:#x} was caused by touchHLE itself, not by the app.",
+            err
+        );
+    }
+
     let res = f(gles, &mut env.mem);
-    //panic_on_gl_errors(&mut **gles);
-    #[allow(clippy::let_and_return)]
+
+    let mut guest_err = 0;
+    loop {
+        let err = unsafe { gles.GetError() };
+        if err == 0 {
+            break;
+        }
+        let mapped_err = map_host_error(err);
+        log!(
+            "Warning: {}() called from {:#x} caused host OpenGL error {:#x} (reported to app as {:#x})",
+            guest_func_name::<T>(),
+            env.cpu.regs()[crate::cpu::Cpu::LR],
+            err,
+            mapped_err
+        );
+        if guest_err == 0 {
+            guest_err = mapped_err;
+        }
+    }
+    if guest_err != 0 {
+        set_pending_error(env, guest_err);
+    }
+
     res
 }
 
+/// Like [with_ctx_and_mem], but for functions that only exist in OpenGL ES
+/// 1.1. If the current context uses OpenGL ES 2.0, the app gets
+/// `GL_INVALID_OPERATION` and a default value instead.
+fn with_ctx_and_mem_es1<T, U>(env: &mut Environment, f: T) -> U
+where
+    T: FnOnce(&mut dyn GLES, &mut Mem) -> U,
+    U: Default,
+{
+    if current_api(env) == kEAGLRenderingAPIOpenGLES2 {
+        report_wrong_api::<T>(env, "OpenGL ES 1.1");
+        return U::default();
+    }
+    with_ctx_and_mem(env, f)
+}
+
+/// Like [with_ctx_and_mem_es1], but for functions that only exist in OpenGL
+/// ES 2.0.
+fn with_ctx_and_mem_es2<T, U>(env: &mut Environment, f: T) -> U
+where
+    T: FnOnce(&mut dyn GLES, &mut Mem) -> U,
+    U: Default,
+{
+    if current_api(env) != kEAGLRenderingAPIOpenGLES2 {
+        report_wrong_api::<T>(env, "OpenGL ES 2.0");
+        return U::default();
+    }
+    with_ctx_and_mem(env, f)
+}
+
+fn report_wrong_api<T>(env: &mut Environment, version: &str) {
+    log!(
+        "Warning: {}() called from {:#x} is only available in {}, ignoring (GL_INVALID_OPERATION)",
+        guest_func_name::<T>(),
+        env.cpu.regs()[crate::cpu::Cpu::LR],
+        version
+    );
+    set_pending_error(env, gles11::INVALID_OPERATION);
+}
+
+/// Record an error for the app to retrieve with `glGetError()`. This is for
+/// errors detected by touchHLE itself, before anything is passed to the host.
+fn set_pending_error(env: &mut Environment, err: GLenum) {
+    let current_ctx = env
+        .framework_state
+        .opengles
+        .current_ctx_for_thread(env.current_thread)
+        .unwrap();
+    let host_obj = env.objc.borrow_mut::<EAGLContextHostObject>(current_ctx);
+    // Like a real OpenGL ES implementation, only the first error is recorded
+    // until the app calls glGetError().
+    if host_obj.pending_error == 0 {
+        host_obj.pending_error = err;
+    }
+}
+
+/// Map an error code from the host OpenGL (ES) implementation to the closest
+/// error that OpenGL ES 1.1 could produce.
+fn map_host_error(err: GLenum) -> GLenum {
+    match err {
+        gles11::INVALID_ENUM
+        | gles11::INVALID_VALUE
+        | gles11::INVALID_OPERATION
+        | gles11::STACK_OVERFLOW
+        | gles11::STACK_UNDERFLOW
+        | gles11::OUT_OF_MEMORY
+        | gles11::INVALID_FRAMEBUFFER_OPERATION_OES => err,
+        // Errors specific to desktop OpenGL, e.g. GL_TABLE_TOO_LARGE. These
+        // shouldn't be reachable with valid OpenGL ES 1.1 usage, so the app
+        // must have done something invalid.
+        _ => gles11::INVALID_OPERATION,
+    }
+}
+
+/// Get the name of the guest-facing function that [with_ctx_and_mem] was
+/// called from, based on the type name of its closure.
+fn guest_func_name<T>() -> &'static str {
+    let name = std::any::type_name::<T>();
+    let name = name.strip_suffix("::{{closure}}").unwrap_or(name);
+    name.rsplit("::").next().unwrap()
+}
+
+/// Get the host object of the current context's sharegroup.
+fn current_sharegroup(env: &mut Environment) -> &mut EAGLSharegroupHostObject {
+    let current_ctx = env
+        .framework_state
+        .opengles
+        .current_ctx_for_thread(env.current_thread)
+        .unwrap();
+    let sharegroup = env
+        .objc
+        .borrow::<EAGLContextHostObject>(current_ctx)
+        .sharegroup;
+    env.objc.borrow_mut::<EAGLSharegroupHostObject>(sharegroup)
+}
+
+/// Record a change in the estimated memory usage of the objects in the current
+/// context's sharegroup. See [super::memory_usage].
+fn update_memory_usage<T>(env: &mut Environment, f: T)
+where
+    T: FnOnce(&mut MemoryUsage),
+{
+    f(&mut current_sharegroup(env).memory_usage)
+}
+
+/// Get the scale hack factor for a renderbuffer. This is the `--scale-hack=`
+/// factor, unless the renderbuffer has a different one because of
+/// `--layer-scale-hack=`.
+fn renderbuffer_scale_hack(env: &mut Environment, renderbuffer: GLuint) -> GLsizei {
+    let default = env.options.scale_hack.get() as GLsizei;
+    if env.options.layer_scale_hacks.is_empty() {
+        return default;
+    }
+    current_sharegroup(env)
+        .renderbuffer_scale_hacks
+        .get(&renderbuffer)
+        .map_or(default, |&factor| factor as GLsizei)
+}
+
+/// Get the scale hack factor for the bound framebuffer, which is that of the
+/// renderbuffer attached as its color buffer, if any.
+fn framebuffer_scale_hack(env: &mut Environment) -> GLsizei {
+    if env.options.layer_scale_hacks.is_empty() {
+        return env.options.scale_hack.get() as GLsizei;
+    }
+    let renderbuffer = with_ctx_and_mem(env, |gles, _mem| unsafe {
+        let mut framebuffer = 0;
+        gles.GetIntegerv(gles11::FRAMEBUFFER_BINDING_OES, &mut framebuffer);
+        if framebuffer == 0 {
+            return 0;
+        }
+        let mut object_type = 0;
+        gles.GetFramebufferAttachmentParameterivOES(
+            gles11::FRAMEBUFFER_OES,
+            gles11::COLOR_ATTACHMENT0_OES,
+            gles11::FRAMEBUFFER_ATTACHMENT_OBJECT_TYPE_OES,
+            &mut object_type,
+        );
+        if object_type as GLenum != gles11::RENDERBUFFER_OES {
+            return 0;
+        }
+        let mut renderbuffer = 0;
+        gles.GetFramebufferAttachmentParameterivOES(
+            gles11::FRAMEBUFFER_OES,
+            gles11::COLOR_ATTACHMENT0_OES,
+            gles11::FRAMEBUFFER_ATTACHMENT_OBJECT_NAME_OES,
+            &mut renderbuffer,
+        );
+        renderbuffer as GLuint
+    });
+    renderbuffer_scale_hack(env, renderbuffer)
+}
+
+/// Record the scale hack factor used for a renderbuffer's storage, so it can
+/// be undone when the app asks for its size.
+fn set_renderbuffer_scale_hack(env: &mut Environment, renderbuffer: GLuint, factor: GLsizei) {
+    if env.options.layer_scale_hacks.is_empty() {
+        return;
+    }
+    let default = env.options.scale_hack.get() as GLsizei;
+    let scale_hacks = &mut current_sharegroup(env).renderbuffer_scale_hacks;
+    if factor == default {
+        scale_hacks.remove(&renderbuffer);
+    } else {
+        scale_hacks.insert(renderbuffer, factor as u32);
+    }
+}
+
+/// Get the API version of the current context.
+fn current_api(env: &mut Environment) -> EAGLRenderingAPI {
+    let current_ctx = env
+        .framework_state
+        .opengles
+        .current_ctx_for_thread(env.current_thread)
+        .unwrap();
+    env.objc.borrow::<EAGLContextHostObject>(current_ctx).api
+}
+
+/// Read an array of object names (e.g. the parameter of `glDeleteTextures`).
+fn read_names(mem: &Mem, names: ConstPtr<GLuint>, n: GLsizei) -> Vec<GLuint> {
+    let n: GuestUSize = n.try_into().unwrap();
+    (0..n).map(|i| mem.read(names + i)).collect()
+}
+
+/// Get the name of the buffer bound to a target, or 0 if the target isn't
+/// recognized.
+unsafe fn get_buffer_binding(gles: &mut dyn GLES, target: GLenum) -> GLuint {
+    let pname = match target {
+        gles11::ARRAY_BUFFER => gles11::ARRAY_BUFFER_BINDING,
+        gles11::ELEMENT_ARRAY_BUFFER => gles11::ELEMENT_ARRAY_BUFFER_BINDING,
+        _ => return 0,
+    };
+    let mut buffer = 0;
+    gles.GetIntegerv(pname, &mut buffer);
+    buffer as _
+}
+/// Get the name of the texture bound to a target, or 0 if the target isn't
+/// recognized.
+unsafe fn get_texture_binding(gles: &mut dyn GLES, target: GLenum) -> GLuint {
+    if target != gles11::TEXTURE_2D {
+        return 0;
+    }
+    let mut texture = 0;
+    gles.GetIntegerv(gles11::TEXTURE_BINDING_2D, &mut texture);
+    texture as _
+}
+
 /// Useful for debugging
 #[allow(dead_code)]
 fn panic_on_gl_errors(gles: &mut dyn GLES) {
@@ -67,6 +319,23 @@ fn panic_on_gl_errors(gles: &mut dyn GLES) {
 
 // Generic state manipulation
 fn glGetError(env: &mut Environment) -> GLenum {
+    if env.options.gles_error_checking {
+        // Collect any host errors first.
+        with_ctx_and_mem(env, |_gles, _mem| ());
+    }
+    // Errors detected by touchHLE itself are recorded even without error
+    // checking, and take precedence because they happened first.
+    let current_ctx = env
+        .framework_state
+        .opengles
+        .current_ctx_for_thread(env.current_thread)
+        .unwrap();
+    let host_obj = env.objc.borrow_mut::<EAGLContextHostObject>(current_ctx);
+    let pending_error = std::mem::take(&mut host_obj.pending_error);
+    if pending_error != 0 || env.options.gles_error_checking {
+        return pending_error;
+    }
+
     with_ctx_and_mem(env, |gles, _mem| {
         let err = unsafe { gles.GetError() };
         if err != 0 {
@@ -96,12 +365,12 @@ fn glClientActiveTexture(env: &mut Environment, texture: GLenum) {
     })
 }
 fn glEnableClientState(env: &mut Environment, array: GLenum) {
-    with_ctx_and_mem(env, |gles, _mem| {
+    with_ctx_and_mem_es1(env, |gles, _mem| {
         unsafe { gles.EnableClientState(array) };
     });
 }
 fn glDisableClientState(env: &mut Environment, array: GLenum) {
-    with_ctx_and_mem(env, |gles, _mem| {
+    with_ctx_and_mem_es1(env, |gles, _mem| {
         unsafe { gles.DisableClientState(array) };
     });
 }
@@ -127,7 +396,7 @@ fn glGetPointerv(env: &mut Environment, pname: GLenum, params: MutPtr<ConstVoidP
     use crate::gles::gles1_on_gl2::{ArrayInfo, ARRAYS};
     let &ArrayInfo { buffer_binding, .. } =
         ARRAYS.iter().find(|info| info.pointer == pname).unwrap();
-    with_ctx_and_mem(env, |gles, mem| {
+    with_ctx_and_mem_es1(env, |gles, mem| {
         // params always points to just one pointer for this function
         let mut host_pointer_or_offset = std::ptr::null();
         let guest_pointer_or_offset = unsafe {
@@ -138,7 +407,7 @@ fn glGetPointerv(env: &mut Environment, pname: GLenum, params: MutPtr<ConstVoidP
     });
 }
 fn glGetTexEnviv(env: &mut Environment, target: GLenum, pname: GLenum, params: MutPtr<GLint>) {
-    with_ctx_and_mem(env, |gles, mem| {
+    with_ctx_and_mem_es1(env, |gles, mem| {
         let params = mem.ptr_at_mut(params, 16 /* upper bound */);
         unsafe { gles.GetTexEnviv(target, pname, params) };
     });
@@ -150,32 +419,57 @@ fn glFlush(env: &mut Environment) {
     with_ctx_and_mem(env, |gles, _mem| unsafe { gles.Flush() })
 }
 fn glGetString(env: &mut Environment, name: GLenum) -> ConstPtr<GLubyte> {
-    let res = if let Some(&str) = env.framework_state.opengles.strings_cache.get(&name) {
+    let api = current_api(env);
+    let res = if let Some(&str) = env.framework_state.opengles.strings_cache.get(&(api, name)) {
         str
     } else {
         let new_str = with_ctx_and_mem(env, |_gles, mem| {
-            // Those values are extracted from the iPod touch 2nd gen, iOS 4.2.1
-            let s: &[u8] = match name {
-                gles11::VENDOR => {
-                    b"Imagination Technologies"
-                }
-                gles11::RENDERER => {
-                    b"PowerVR MBXLite with VGPLite"
-                }
-                gles11::VERSION => {
-                    b"OpenGL ES-CM 1.1 (76)"
+            let s: &[u8] = if api == kEAGLRenderingAPIOpenGLES2 {
+                // Those values are based on the iPhone 3GS, but the extensions
+                // are limited to those touchHLE supports.
+                match name {
+                    gles2::VENDOR => {
+                        b"Imagination Technologies"
+                    }
+                    gles2::RENDERER => {
+                        b"PowerVR SGX 535"
+                    }
+                    gles2::VERSION => {
+                        b"OpenGL ES 2.0 IMGSGX535-63.24"
+                    }
+                    gles2::SHADING_LANGUAGE_VERSION => {
+                        b"OpenGL ES GLSL ES 1.00"
+                    }
+                    gles2::EXTENSIONS => {
+                        b"GL_APPLE_framebuffer_multisample GL_APPLE_texture_format_BGRA8888 GL_EXT_texture_filter_anisotropic GL_IMG_texture_compression_pvrtc GL_OES_depth24 GL_OES_rgb8_rgba8 "
+                    }
+                    _ => unreachable!(),
                 }
-                gles11::EXTENSIONS => {
-                    b"GL_APPLE_framebuffer_multisample GL_APPLE_texture_max_level GL_EXT_discard_framebuffer GL_EXT_texture_filter_anisotropic GL_EXT_texture_lod_bias GL_IMG_read_format GL_IMG_texture_compression_pvrtc GL_IMG_texture_format_BGRA8888 GL_OES_blend_subtract GL_OES_compressed_paletted_texture GL_OES_depth24 GL_OES_draw_texture GL_OES_framebuffer_object GL_OES_mapbuffer GL_OES_matrix_palette GL_OES_point_size_array GL_OES_point_sprite GL_OES_read_format GL_OES_rgb8_rgba8 GL_OES_texture_mirrored_repeat GL_OES_vertex_array_object "
+            } else {
+                // Those values are extracted from the iPod touch 2nd gen, iOS
+                // 4.2.1
+                match name {
+                    gles11::VENDOR => {
+                        b"Imagination Technologies"
+                    }
+                    gles11::RENDERER => {
+                        b"PowerVR MBXLite with VGPLite"
+                    }
+                    gles11::VERSION => {
+                        b"OpenGL ES-CM 1.1 (76)"
+                    }
+                    gles11::EXTENSIONS => {
+                        b"GL_APPLE_framebuffer_multisample GL_APPLE_texture_max_level GL_EXT_discard_framebuffer GL_EXT_texture_filter_anisotropic GL_EXT_texture_lod_bias GL_IMG_read_format GL_IMG_texture_compression_pvrtc GL_IMG_texture_format_BGRA8888 GL_OES_blend_subtract GL_OES_compressed_paletted_texture GL_OES_depth24 GL_OES_draw_texture GL_OES_framebuffer_object GL_OES_mapbuffer GL_OES_matrix_palette GL_OES_point_size_array GL_OES_point_sprite GL_OES_read_format GL_OES_rgb8_rgba8 GL_OES_texture_mirrored_repeat GL_OES_vertex_array_object "
+                    }
+                    _ => unreachable!(),
                 }
-                _ => unreachable!(),
             };
             mem.alloc_and_write_cstr(s).cast_const()
         });
         env.framework_state
             .opengles
             .strings_cache
-            .insert(name, new_str);
+            .insert((api, name), new_str);
         new_str
     };
     log_dbg!("glGetString({}) => {:?}", name, res);
@@ -184,10 +478,10 @@ fn glGetString(env: &mut Environment, name: GLenum) -> ConstPtr<GLubyte> {
 
 // Other state manipulation
 fn glAlphaFunc(env: &mut Environment, func: GLenum, ref_: GLclampf) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.AlphaFunc(func, ref_) })
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe { gles.AlphaFunc(func, ref_) })
 }
 fn glAlphaFuncx(env: &mut Environment, func: GLenum, ref_: GLclampx) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.AlphaFuncx(func, ref_) })
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe { gles.AlphaFuncx(func, ref_) })
 }
 fn glBlendFunc(env: &mut Environment, sfactor: GLenum, dfactor: GLenum) {
     with_ctx_and_mem(env, |gles, _mem| unsafe {
@@ -218,7 +512,7 @@ fn glDepthRangef(env: &mut Environment, near: GLclampf, far: GLclampf) {
     with_ctx_and_mem(env, |gles, _mem| unsafe { gles.DepthRangef(near, far) })
 }
 fn glDepthRangex(env: &mut Environment, near: GLclampx, far: GLclampx) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.DepthRangex(near, far) })
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe { gles.DepthRangex(near, far) })
 }
 fn glFrontFace(env: &mut Environment, mode: GLenum) {
     with_ctx_and_mem(env, |gles, _mem| unsafe { gles.FrontFace(mode) })
@@ -229,17 +523,17 @@ fn glPolygonOffset(env: &mut Environment, factor: GLfloat, units: GLfloat) {
     })
 }
 fn glPolygonOffsetx(env: &mut Environment, factor: GLfixed, units: GLfixed) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe {
         gles.PolygonOffsetx(factor, units)
     })
 }
 fn glShadeModel(env: &mut Environment, mode: GLenum) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.ShadeModel(mode) })
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe { gles.ShadeModel(mode) })
 }
 fn glScissor(env: &mut Environment, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
     // apply scale hack: assume framebuffer's size is larger than the app thinks
     // and scale scissor appropriately
-    let factor = env.options.scale_hack.get() as GLsizei;
+    let factor = framebuffer_scale_hack(env);
     let (x, y) = (x * factor, y * factor);
     let (width, height) = (width * factor, height * factor);
     with_ctx_and_mem(env, |gles, _mem| unsafe {
@@ -249,7 +543,7 @@ fn glScissor(env: &mut Environment, x: GLint, y: GLint, width: GLsizei, height:
 fn glViewport(env: &mut Environment, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
     // apply scale hack: assume framebuffer's size is larger than the app thinks
     // and scale viewport appropriately
-    let factor = env.options.scale_hack.get() as GLsizei;
+    let factor = framebuffer_scale_hack(env);
     let (x, y) = (x * factor, y * factor);
     let (width, height) = (width * factor, height * factor);
     with_ctx_and_mem(env, |gles, _mem| unsafe {
@@ -260,33 +554,33 @@ fn glLineWidth(env: &mut Environment, val: GLfloat) {
     with_ctx_and_mem(env, |gles, _mem| unsafe { gles.LineWidth(val) })
 }
 fn glLineWidthx(env: &mut Environment, val: GLfixed) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.LineWidthx(val) })
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe { gles.LineWidthx(val) })
 }
 // Points
 fn glPointSize(env: &mut Environment, size: GLfloat) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.PointSize(size) })
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe { gles.PointSize(size) })
 }
 fn glPointSizex(env: &mut Environment, size: GLfixed) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.PointSizex(size) })
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe { gles.PointSizex(size) })
 }
 fn glPointParameterf(env: &mut Environment, pname: GLenum, param: GLfloat) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe {
         gles.PointParameterf(pname, param)
     })
 }
 fn glPointParameterx(env: &mut Environment, pname: GLenum, param: GLfixed) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe {
         gles.PointParameterx(pname, param)
     })
 }
 fn glPointParameterfv(env: &mut Environment, pname: GLenum, params: ConstPtr<GLfloat>) {
-    with_ctx_and_mem(env, |gles, mem| {
+    with_ctx_and_mem_es1(env, |gles, mem| {
         let params = mem.ptr_at(params, 4 /* upper bound */);
         unsafe { gles.PointParameterfv(pname, params) }
     })
 }
 fn glPointParameterxv(env: &mut Environment, pname: GLenum, params: ConstPtr<GLfixed>) {
-    with_ctx_and_mem(env, |gles, mem| {
+    with_ctx_and_mem_es1(env, |gles, mem| {
         let params = mem.ptr_at(params, 4 /* upper bound */);
         unsafe { gles.PointParameterxv(pname, params) }
     })
@@ -294,72 +588,72 @@ fn glPointParameterxv(env: &mut Environment, pname: GLenum, params: ConstPtr<GLf
 
 // Lighting and materials
 fn glFogf(env: &mut Environment, pname: GLenum, param: GLfloat) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.Fogf(pname, param) })
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe { gles.Fogf(pname, param) })
 }
 fn glFogx(env: &mut Environment, pname: GLenum, param: GLfixed) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.Fogx(pname, param) })
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe { gles.Fogx(pname, param) })
 }
 fn glFogfv(env: &mut Environment, pname: GLenum, params: ConstPtr<GLfloat>) {
-    with_ctx_and_mem(env, |gles, mem| {
+    with_ctx_and_mem_es1(env, |gles, mem| {
         let params = mem.ptr_at(params, 4 /* upper bound */);
         unsafe { gles.Fogfv(pname, params) }
     })
 }
 fn glFogxv(env: &mut Environment, pname: GLenum, params: ConstPtr<GLfixed>) {
-    with_ctx_and_mem(env, |gles, mem| {
+    with_ctx_and_mem_es1(env, |gles, mem| {
         let params = mem.ptr_at(params, 4 /* upper bound */);
         unsafe { gles.Fogxv(pname, params) }
     })
 }
 fn glLightf(env: &mut Environment, light: GLenum, pname: GLenum, param: GLfloat) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe {
         gles.Lightf(light, pname, param)
     })
 }
 fn glLightx(env: &mut Environment, light: GLenum, pname: GLenum, param: GLfixed) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe {
         gles.Lightx(light, pname, param)
     })
 }
 fn glLightfv(env: &mut Environment, light: GLenum, pname: GLenum, params: ConstPtr<GLfloat>) {
-    with_ctx_and_mem(env, |gles, mem| {
+    with_ctx_and_mem_es1(env, |gles, mem| {
         let params = mem.ptr_at(params, 4 /* upper bound */);
         unsafe { gles.Lightfv(light, pname, params) }
     })
 }
 fn glLightxv(env: &mut Environment, light: GLenum, pname: GLenum, params: ConstPtr<GLfixed>) {
-    with_ctx_and_mem(env, |gles, mem| {
+    with_ctx_and_mem_es1(env, |gles, mem| {
         let params = mem.ptr_at(params, 4 /* upper bound */);
         unsafe { gles.Lightxv(light, pname, params) }
     })
 }
 fn glLightModelf(env: &mut Environment, pname: GLenum, param: GLfloat) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.LightModelf(pname, param) })
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe { gles.LightModelf(pname, param) })
 }
 fn glLightModelfv(env: &mut Environment, pname: GLenum, params: ConstPtr<GLfloat>) {
-    with_ctx_and_mem(env, |gles, mem| {
+    with_ctx_and_mem_es1(env, |gles, mem| {
         let params = mem.ptr_at(params, 4 /* upper bound */);
         unsafe { gles.LightModelfv(pname, params) }
     })
 }
 fn glMaterialf(env: &mut Environment, face: GLenum, pname: GLenum, param: GLfloat) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe {
         gles.Materialf(face, pname, param)
     })
 }
 fn glMaterialx(env: &mut Environment, face: GLenum, pname: GLenum, param: GLfixed) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe {
         gles.Materialx(face, pname, param)
     })
 }
 fn glMaterialfv(env: &mut Environment, face: GLenum, pname: GLenum, params: ConstPtr<GLfloat>) {
-    with_ctx_and_mem(env, |gles, mem| {
+    with_ctx_and_mem_es1(env, |gles, mem| {
         let params = mem.ptr_at(params, 4 /* upper bound */);
         unsafe { gles.Materialfv(face, pname, params) }
     })
 }
 fn glMaterialxv(env: &mut Environment, face: GLenum, pname: GLenum, params: ConstPtr<GLfixed>) {
-    with_ctx_and_mem(env, |gles, mem| {
+    with_ctx_and_mem_es1(env, |gles, mem| {
         let params = mem.ptr_at(params, 4 /* upper bound */);
         unsafe { gles.Materialxv(face, pname, params) }
     })
@@ -374,11 +668,13 @@ fn glGenBuffers(env: &mut Environment, n: GLsizei, buffers: MutPtr<GLuint>) {
     })
 }
 fn glDeleteBuffers(env: &mut Environment, n: GLsizei, buffers: ConstPtr<GLuint>) {
+    let names = read_names(&env.mem, buffers, n);
     with_ctx_and_mem(env, |gles, mem| {
         let n_usize: GuestUSize = n.try_into().unwrap();
         let buffers = mem.ptr_at(buffers, n_usize);
         unsafe { gles.DeleteBuffers(n, buffers) }
-    })
+    });
+    update_memory_usage(env, |usage| usage.delete_buffers(&names));
 }
 fn glBindBuffer(env: &mut Environment, target: GLenum, buffer: GLuint) {
     with_ctx_and_mem(env, |gles, _mem| unsafe { gles.BindBuffer(target, buffer) })
@@ -390,15 +686,18 @@ fn glBufferData(
     data: ConstPtr<GLvoid>,
     usage: GLenum,
 ) {
-    with_ctx_and_mem(env, |gles, mem| unsafe {
+    let buffer = with_ctx_and_mem(env, |gles, mem| unsafe {
         let data = if data.is_null() {
             std::ptr::null()
         } else {
             mem.ptr_at(data.cast::<u8>(), size.try_into().unwrap())
                 .cast()
         };
-        gles.BufferData(target, size as HostGLsizeiptr, data, usage)
-    })
+        gles.BufferData(target, size as HostGLsizeiptr, data, usage);
+        get_buffer_binding(gles, target)
+    });
+    let size: usize = size.try_into().unwrap_or(0);
+    update_memory_usage(env, |usage| usage.set_buffer(buffer, size));
 }
 
 fn glBufferSubData(
@@ -421,25 +720,25 @@ fn glBufferSubData(
 
 // Non-pointers
 fn glColor4f(env: &mut Environment, red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe {
         gles.Color4f(red, green, blue, alpha)
     })
 }
 fn glColor4x(env: &mut Environment, red: GLfixed, green: GLfixed, blue: GLfixed, alpha: GLfixed) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe {
         gles.Color4x(red, green, blue, alpha)
     })
 }
 fn glColor4ub(env: &mut Environment, red: GLubyte, green: GLubyte, blue: GLubyte, alpha: GLubyte) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe {
         gles.Color4ub(red, green, blue, alpha)
     })
 }
 fn glNormal3f(env: &mut Environment, nx: GLfloat, ny: GLfloat, nz: GLfloat) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.Normal3f(nx, ny, nz) })
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe { gles.Normal3f(nx, ny, nz) })
 }
 fn glNormal3x(env: &mut Environment, nx: GLfixed, ny: GLfixed, nz: GLfixed) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.Normal3x(nx, ny, nz) })
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe { gles.Normal3x(nx, ny, nz) })
 }
 
 // Pointers
@@ -505,14 +804,14 @@ fn glColorPointer(
     stride: GLsizei,
     pointer: ConstVoidPtr,
 ) {
-    with_ctx_and_mem(env, |gles, mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, mem| unsafe {
         let pointer =
             translate_pointer_or_offset_to_host(gles, mem, pointer, gles11::ARRAY_BUFFER_BINDING);
         gles.ColorPointer(size, type_, stride, pointer)
     })
 }
 fn glNormalPointer(env: &mut Environment, type_: GLenum, stride: GLsizei, pointer: ConstVoidPtr) {
-    with_ctx_and_mem(env, |gles, mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, mem| unsafe {
         let pointer =
             translate_pointer_or_offset_to_host(gles, mem, pointer, gles11::ARRAY_BUFFER_BINDING);
         gles.NormalPointer(type_, stride, pointer)
@@ -525,7 +824,7 @@ fn glTexCoordPointer(
     stride: GLsizei,
     pointer: ConstVoidPtr,
 ) {
-    with_ctx_and_mem(env, |gles, mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, mem| unsafe {
         let pointer =
             translate_pointer_or_offset_to_host(gles, mem, pointer, gles11::ARRAY_BUFFER_BINDING);
         gles.TexCoordPointer(size, type_, stride, pointer)
@@ -538,7 +837,7 @@ fn glVertexPointer(
     stride: GLsizei,
     pointer: ConstVoidPtr,
 ) {
-    with_ctx_and_mem(env, |gles, mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, mem| unsafe {
         let pointer =
             translate_pointer_or_offset_to_host(gles, mem, pointer, gles11::ARRAY_BUFFER_BINDING);
         gles.VertexPointer(size, type_, stride, pointer)
@@ -547,8 +846,13 @@ fn glVertexPointer(
 
 // Drawing
 fn glDrawArrays(env: &mut Environment, mode: GLenum, first: GLint, count: GLsizei) {
+    let is_gles2 = current_api(env) == kEAGLRenderingAPIOpenGLES2;
     with_ctx_and_mem(env, |gles, _mem| unsafe {
-        let fog_state_backup = clamp_fog_state_values(gles);
+        let fog_state_backup = if is_gles2 {
+            None
+        } else {
+            clamp_fog_state_values(gles)
+        };
         gles.DrawArrays(mode, first, count);
         restore_fog_state_values(gles, fog_state_backup);
     })
@@ -560,8 +864,13 @@ fn glDrawElements(
     type_: GLenum,
     indices: ConstVoidPtr,
 ) {
+    let is_gles2 = current_api(env) == kEAGLRenderingAPIOpenGLES2;
     with_ctx_and_mem(env, |gles, mem| unsafe {
-        let fog_state_backup = clamp_fog_state_values(gles);
+        let fog_state_backup = if is_gles2 {
+            None
+        } else {
+            clamp_fog_state_values(gles)
+        };
         let indices = translate_pointer_or_offset_to_host(
             gles,
             mem,
@@ -595,7 +904,7 @@ fn glClearColorx(
     blue: GLclampx,
     alpha: GLclampx,
 ) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe {
         gles.ClearColorx(red, green, blue, alpha)
     });
 }
@@ -603,7 +912,7 @@ fn glClearDepthf(env: &mut Environment, depth: GLclampf) {
     with_ctx_and_mem(env, |gles, _mem| unsafe { gles.ClearDepthf(depth) });
 }
 fn glClearDepthx(env: &mut Environment, depth: GLclampx) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.ClearDepthx(depth) });
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe { gles.ClearDepthx(depth) });
 }
 fn glClearStencil(env: &mut Environment, s: GLint) {
     with_ctx_and_mem(env, |gles, _mem| unsafe { gles.ClearStencil(s) });
@@ -611,46 +920,46 @@ fn glClearStencil(env: &mut Environment, s: GLint) {
 
 // Matrix stack operations
 fn glMatrixMode(env: &mut Environment, mode: GLenum) {
-    with_ctx_and_mem(env, |gles, _mem| {
+    with_ctx_and_mem_es1(env, |gles, _mem| {
         unsafe { gles.MatrixMode(mode) };
     });
 }
 fn glLoadIdentity(env: &mut Environment) {
-    with_ctx_and_mem(env, |gles, _mem| {
+    with_ctx_and_mem_es1(env, |gles, _mem| {
         unsafe { gles.LoadIdentity() };
     });
 }
 fn glLoadMatrixf(env: &mut Environment, m: ConstPtr<GLfloat>) {
-    with_ctx_and_mem(env, |gles, mem| {
+    with_ctx_and_mem_es1(env, |gles, mem| {
         let m = mem.ptr_at(m, 16);
         unsafe { gles.LoadMatrixf(m) };
     });
 }
 fn glLoadMatrixx(env: &mut Environment, m: ConstPtr<GLfixed>) {
-    with_ctx_and_mem(env, |gles, mem| {
+    with_ctx_and_mem_es1(env, |gles, mem| {
         let m = mem.ptr_at(m, 16);
         unsafe { gles.LoadMatrixx(m) };
     });
 }
 fn glMultMatrixf(env: &mut Environment, m: ConstPtr<GLfloat>) {
-    with_ctx_and_mem(env, |gles, mem| {
+    with_ctx_and_mem_es1(env, |gles, mem| {
         let m = mem.ptr_at(m, 16);
         unsafe { gles.MultMatrixf(m) };
     });
 }
 fn glMultMatrixx(env: &mut Environment, m: ConstPtr<GLfixed>) {
-    with_ctx_and_mem(env, |gles, mem| {
+    with_ctx_and_mem_es1(env, |gles, mem| {
         let m = mem.ptr_at(m, 16);
         unsafe { gles.MultMatrixx(m) };
     });
 }
 fn glPushMatrix(env: &mut Environment) {
-    with_ctx_and_mem(env, |gles, _mem| {
+    with_ctx_and_mem_es1(env, |gles, _mem| {
         unsafe { gles.PushMatrix() };
     });
 }
 fn glPopMatrix(env: &mut Environment) {
-    with_ctx_and_mem(env, |gles, _mem| {
+    with_ctx_and_mem_es1(env, |gles, _mem| {
         unsafe { gles.PopMatrix() };
     });
 }
@@ -663,7 +972,7 @@ fn glOrthof(
     near: GLfloat,
     far: GLfloat,
 ) {
-    with_ctx_and_mem(env, |gles, _mem| {
+    with_ctx_and_mem_es1(env, |gles, _mem| {
         unsafe { gles.Orthof(left, right, bottom, top, near, far) };
     });
 }
@@ -676,7 +985,7 @@ fn glOrthox(
     near: GLfixed,
     far: GLfixed,
 ) {
-    with_ctx_and_mem(env, |gles, _mem| {
+    with_ctx_and_mem_es1(env, |gles, _mem| {
         unsafe { gles.Orthox(left, right, bottom, top, near, far) };
     });
 }
@@ -689,7 +998,7 @@ fn glFrustumf(
     near: GLfloat,
     far: GLfloat,
 ) {
-    with_ctx_and_mem(env, |gles, _mem| {
+    with_ctx_and_mem_es1(env, |gles, _mem| {
         unsafe { gles.Frustumf(left, right, bottom, top, near, far) };
     });
 }
@@ -702,37 +1011,37 @@ fn glFrustumx(
     near: GLfixed,
     far: GLfixed,
 ) {
-    with_ctx_and_mem(env, |gles, _mem| {
+    with_ctx_and_mem_es1(env, |gles, _mem| {
         unsafe { gles.Frustumx(left, right, bottom, top, near, far) };
     });
 }
 fn glRotatef(env: &mut Environment, angle: GLfloat, x: GLfloat, y: GLfloat, z: GLfloat) {
-    with_ctx_and_mem(env, |gles, _mem| {
+    with_ctx_and_mem_es1(env, |gles, _mem| {
         unsafe { gles.Rotatef(angle, x, y, z) };
     });
 }
 fn glRotatex(env: &mut Environment, angle: GLfixed, x: GLfixed, y: GLfixed, z: GLfixed) {
-    with_ctx_and_mem(env, |gles, _mem| {
+    with_ctx_and_mem_es1(env, |gles, _mem| {
         unsafe { gles.Rotatex(angle, x, y, z) };
     });
 }
 fn glScalef(env: &mut Environment, x: GLfloat, y: GLfloat, z: GLfloat) {
-    with_ctx_and_mem(env, |gles, _mem| {
+    with_ctx_and_mem_es1(env, |gles, _mem| {
         unsafe { gles.Scalef(x, y, z) };
     });
 }
 fn glScalex(env: &mut Environment, x: GLfixed, y: GLfixed, z: GLfixed) {
-    with_ctx_and_mem(env, |gles, _mem| {
+    with_ctx_and_mem_es1(env, |gles, _mem| {
         unsafe { gles.Scalex(x, y, z) };
     });
 }
 fn glTranslatef(env: &mut Environment, x: GLfloat, y: GLfloat, z: GLfloat) {
-    with_ctx_and_mem(env, |gles, _mem| {
+    with_ctx_and_mem_es1(env, |gles, _mem| {
         unsafe { gles.Translatef(x, y, z) };
     });
 }
 fn glTranslatex(env: &mut Environment, x: GLfixed, y: GLfixed, z: GLfixed) {
-    with_ctx_and_mem(env, |gles, _mem| {
+    with_ctx_and_mem_es1(env, |gles, _mem| {
         unsafe { gles.Translatex(x, y, z) };
     });
 }
@@ -749,11 +1058,13 @@ fn glGenTextures(env: &mut Environment, n: GLsizei, textures: MutPtr<GLuint>) {
     })
 }
 fn glDeleteTextures(env: &mut Environment, n: GLsizei, textures: ConstPtr<GLuint>) {
+    let names = read_names(&env.mem, textures, n);
     with_ctx_and_mem(env, |gles, mem| {
         let n_usize: GuestUSize = n.try_into().unwrap();
         let textures = mem.ptr_at(textures, n_usize);
         unsafe { gles.DeleteTextures(n, textures) }
-    })
+    });
+    update_memory_usage(env, |usage| usage.delete_textures(&names));
 }
 fn glActiveTexture(env: &mut Environment, texture: GLenum) {
     with_ctx_and_mem(env, |gles, _mem| unsafe { gles.ActiveTexture(texture) })
@@ -787,7 +1098,7 @@ fn glTexParameterx(env: &mut Environment, target: GLenum, pname: GLenum, param:
     if pname == gles11::TEXTURE_CROP_RECT_OES {
         return;
     }
-    with_ctx_and_mem(env, |gles, _mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe {
         gles.TexParameterx(target, pname, param)
     })
 }
@@ -826,28 +1137,78 @@ fn glTexParameterxv(
     if pname == gles11::TEXTURE_CROP_RECT_OES {
         return;
     }
-    with_ctx_and_mem(env, |gles, mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, mem| unsafe {
         let params = mem.ptr_at(params, 1 /* upper bound */);
         gles.TexParameterxv(target, pname, params)
     })
 }
-fn image_size_estimate(pixel_count: GuestUSize, format: GLenum, type_: GLenum) -> GuestUSize {
+/// Estimate the size of image data for `glTexImage2D` and similar functions,
+/// or [None] if the format and type aren't known or the size overflows.
+fn image_size_estimate(
+    width: GLsizei,
+    height: GLsizei,
+    format: GLenum,
+    type_: GLenum,
+) -> Option<GuestUSize> {
     let bytes_per_pixel: GuestUSize = match type_ {
-        gles11::UNSIGNED_BYTE => match format {
-            gles11::ALPHA | gles11::LUMINANCE => 1,
-            gles11::LUMINANCE_ALPHA => 2,
-            gles11::RGB => 3,
-            gles11::RGBA => 4,
-            gles11::BGRA_EXT => 4,
-            _ => panic!("Unexpected format {:#x}", format),
-        },
         gles11::UNSIGNED_SHORT_5_6_5
         | gles11::UNSIGNED_SHORT_4_4_4_4
         | gles11::UNSIGNED_SHORT_5_5_5_1 => 2,
-        _ => panic!("Unexpected type {:#x}", type_),
+        _ => {
+            let components = match format {
+                gles11::ALPHA | gles11::LUMINANCE | gles2::DEPTH_COMPONENT => 1,
+                gles11::LUMINANCE_ALPHA => 2,
+                gles11::RGB => 3,
+                gles11::RGBA | gles11::BGRA_EXT => 4,
+                _ => return None,
+            };
+            let bytes_per_component = match type_ {
+                gles11::UNSIGNED_BYTE => 1,
+                gles11::UNSIGNED_SHORT => 2,
+                gles2::UNSIGNED_INT | gles11::FLOAT => 4,
+                _ => return None,
+            };
+            components * bytes_per_component
+        }
     };
     // This is approximate, it doesn't account for alignment.
-    pixel_count.checked_mul(bytes_per_pixel).unwrap()
+    let width: GuestUSize = width.try_into().ok()?;
+    let height: GuestUSize = height.try_into().ok()?;
+    width.checked_mul(height)?.checked_mul(bytes_per_pixel)
+}
+/// Like [image_size_estimate], but for the internal format of a texture, for
+/// functions like `glCopyTexImage2D` that don't specify a format and type.
+fn internal_format_size_estimate(
+    width: GLsizei,
+    height: GLsizei,
+    internalformat: GLenum,
+) -> Option<GuestUSize> {
+    let bytes_per_pixel: GuestUSize = match internalformat {
+        gles11::ALPHA | gles11::LUMINANCE => 1,
+        gles11::LUMINANCE_ALPHA => 2,
+        gles11::RGB => 3,
+        gles11::RGBA => 4,
+        // Sized formats are the same as for renderbuffers.
+        _ => renderbuffer_bytes_per_pixel(internalformat) as GuestUSize,
+    };
+    let width: GuestUSize = width.try_into().ok()?;
+    let height: GuestUSize = height.try_into().ok()?;
+    width.checked_mul(height)?.checked_mul(bytes_per_pixel)
+}
+/// Check the size passed to `glTexImage2D` and similar functions, recording
+/// `GL_INVALID_VALUE` for the app if it's negative.
+fn check_image_size(env: &mut Environment, func: &str, width: GLsizei, height: GLsizei) -> bool {
+    if width < 0 || height < 0 {
+        log!(
+            "Warning: {}() called with negative size {}x{}, ignoring (GL_INVALID_VALUE)",
+            func,
+            width,
+            height
+        );
+        set_pending_error(env, gles11::INVALID_VALUE);
+        return false;
+    }
+    true
 }
 fn glTexImage2D(
     env: &mut Environment,
@@ -861,13 +1222,25 @@ fn glTexImage2D(
     type_: GLenum,
     pixels: ConstVoidPtr,
 ) {
-    with_ctx_and_mem(env, |gles, mem| unsafe {
+    if !check_image_size(env, "glTexImage2D", width, height) {
+        return;
+    }
+    let size = image_size_estimate(width, height, format, type_);
+    if size.is_none() && !pixels.is_null() {
+        log!(
+            "Warning: glTexImage2D() called with unsupported format {:#x} and type {:#x}, ignoring (GL_INVALID_ENUM)",
+            format,
+            type_
+        );
+        set_pending_error(env, gles11::INVALID_ENUM);
+        return;
+    }
+    let texture = with_ctx_and_mem(env, |gles, mem| unsafe {
         let pixels = if pixels.is_null() {
             std::ptr::null()
         } else {
-            let pixel_count: GuestUSize = width.checked_mul(height).unwrap().try_into().unwrap();
-            let size = image_size_estimate(pixel_count, format, type_);
-            mem.ptr_at(pixels.cast::<u8>(), size).cast::<GLvoid>()
+            mem.ptr_at(pixels.cast::<u8>(), size.unwrap())
+                .cast::<GLvoid>()
         };
         gles.TexImage2D(
             target,
@@ -879,8 +1252,13 @@ fn glTexImage2D(
             format,
             type_,
             pixels,
-        )
-    })
+        );
+        get_texture_binding(gles, target)
+    });
+    // Unknown formats can only be used without data (e.g. for depth
+    // textures), so they aren't counted.
+    let size = size.unwrap_or(0) as usize;
+    update_memory_usage(env, |usage| usage.set_texture_level(texture, level, size));
 }
 fn glTexSubImage2D(
     env: &mut Environment,
@@ -894,9 +1272,19 @@ fn glTexSubImage2D(
     type_: GLenum,
     pixels: ConstVoidPtr,
 ) {
+    if !check_image_size(env, "glTexSubImage2D", width, height) {
+        return;
+    }
+    let Some(size) = image_size_estimate(width, height, format, type_) else {
+        log!(
+            "Warning: glTexSubImage2D() called with unsupported format {:#x} and type {:#x}, ignoring (GL_INVALID_ENUM)",
+            format,
+            type_
+        );
+        set_pending_error(env, gles11::INVALID_ENUM);
+        return;
+    };
     with_ctx_and_mem(env, |gles, mem| unsafe {
-        let pixel_count: GuestUSize = width.checked_mul(height).unwrap().try_into().unwrap();
-        let size = image_size_estimate(pixel_count, format, type_);
         let pixels = mem.ptr_at(pixels.cast::<u8>(), size).cast::<GLvoid>();
         gles.TexSubImage2D(
             target, level, xoffset, yoffset, width, height, format, type_, pixels,
@@ -914,7 +1302,7 @@ fn glCompressedTexImage2D(
     image_size: GLsizei,
     data: ConstVoidPtr,
 ) {
-    with_ctx_and_mem(env, |gles, mem| unsafe {
+    let texture = with_ctx_and_mem(env, |gles, mem| unsafe {
         let data = mem
             .ptr_at(data.cast::<u8>(), image_size.try_into().unwrap())
             .cast();
@@ -927,8 +1315,13 @@ fn glCompressedTexImage2D(
             border,
             image_size,
             data,
-        )
-    })
+        );
+        get_texture_binding(gles, target)
+    });
+    // For paletted textures, the size includes the palette and all the levels
+    // of detail, but that's fine for an estimate.
+    let size: usize = image_size.try_into().unwrap_or(0);
+    update_memory_usage(env, |usage| usage.set_texture_level(texture, level, size));
 }
 fn glCopyTexImage2D(
     env: &mut Environment,
@@ -941,9 +1334,17 @@ fn glCopyTexImage2D(
     height: GLsizei,
     border: GLint,
 ) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe {
-        gles.CopyTexImage2D(target, level, internalformat, x, y, width, height, border)
-    })
+    if !check_image_size(env, "glCopyTexImage2D", width, height) {
+        return;
+    }
+    let texture = with_ctx_and_mem(env, |gles, _mem| unsafe {
+        gles.CopyTexImage2D(target, level, internalformat, x, y, width, height, border);
+        get_texture_binding(gles, target)
+    });
+    let size = internal_format_size_estimate(width, height, internalformat).unwrap_or(0);
+    update_memory_usage(env, |usage| {
+        usage.set_texture_level(texture, level, size as usize)
+    });
 }
 fn glCopyTexSubImage2D(
     env: &mut Environment,
@@ -961,24 +1362,24 @@ fn glCopyTexSubImage2D(
     })
 }
 fn glTexEnvf(env: &mut Environment, target: GLenum, pname: GLenum, param: GLfloat) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe {
         gles.TexEnvf(target, pname, param)
     })
 }
 fn glTexEnvx(env: &mut Environment, target: GLenum, pname: GLenum, param: GLfixed) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe {
         gles.TexEnvx(target, pname, param)
     })
 }
 fn glTexEnvi(env: &mut Environment, target: GLenum, pname: GLenum, param: GLint) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe {
+    with_ctx_and_mem_es1(env, |gles, _mem| unsafe {
         gles.TexEnvi(target, pname, param)
     })
 }
 fn glTexEnvfv(env: &mut Environment, target: GLenum, pname: GLenum, params: ConstPtr<GLfloat>) {
     // TODO: GL_POINT_SPRITE_OES
     assert!(target == gles11::TEXTURE_ENV);
-    with_ctx_and_mem(env, |gles, mem| {
+    with_ctx_and_mem_es1(env, |gles, mem| {
         let params = mem.ptr_at(params, 4 /* upper bound */);
         unsafe { gles.TexEnvfv(target, pname, params) }
     })
@@ -986,7 +1387,7 @@ fn glTexEnvfv(env: &mut Environment, target: GLenum, pname: GLenum, params: Cons
 fn glTexEnvxv(env: &mut Environment, target: GLenum, pname: GLenum, params: ConstPtr<GLfixed>) {
     // TODO: GL_POINT_SPRITE_OES
     assert!(target == gles11::TEXTURE_ENV);
-    with_ctx_and_mem(env, |gles, mem| {
+    with_ctx_and_mem_es1(env, |gles, mem| {
         let params = mem.ptr_at(params, 4 /* upper bound */);
         unsafe { gles.TexEnvxv(target, pname, params) }
     })
@@ -994,7 +1395,7 @@ fn glTexEnvxv(env: &mut Environment, target: GLenum, pname: GLenum, params: Cons
 fn glTexEnviv(env: &mut Environment, target: GLenum, pname: GLenum, params: ConstPtr<GLint>) {
     // TODO: GL_POINT_SPRITE_OES
     assert!(target == gles11::TEXTURE_ENV);
-    with_ctx_and_mem(env, |gles, mem| {
+    with_ctx_and_mem_es1(env, |gles, mem| {
         let params = mem.ptr_at(params, 4 /* upper bound */);
         unsafe { gles.TexEnviv(target, pname, params) }
     })
@@ -1032,12 +1433,22 @@ fn glRenderbufferStorageOES(
     width: GLsizei,
     height: GLsizei,
 ) {
-    // apply scale hack: give the app a larger framebuffer than it asked for
-    let factor = env.options.scale_hack.get() as GLsizei;
+    // apply scale hack: give the app a larger framebuffer than it asked for.
+    // This is usually a depth buffer for a framebuffer whose color buffer
+    // already has storage, so it should be scaled the same way.
+    let factor = framebuffer_scale_hack(env);
     let (width, height) = (width * factor, height * factor);
-    with_ctx_and_mem(env, |gles, _mem| unsafe {
-        gles.RenderbufferStorageOES(target, internalformat, width, height)
-    })
+    let renderbuffer = with_ctx_and_mem(env, |gles, _mem| unsafe {
+        gles.RenderbufferStorageOES(target, internalformat, width, height);
+        let mut renderbuffer = 0;
+        gles.GetIntegerv(gles11::RENDERBUFFER_BINDING_OES, &mut renderbuffer);
+        renderbuffer as GLuint
+    });
+    set_renderbuffer_scale_hack(env, renderbuffer, factor);
+    let size = (width.max(0) as usize)
+        * (height.max(0) as usize)
+        * renderbuffer_bytes_per_pixel(internalformat);
+    update_memory_usage(env, |usage| usage.set_renderbuffer(renderbuffer, size));
 }
 fn glFramebufferRenderbufferOES(
     env: &mut Environment,
@@ -1068,7 +1479,16 @@ fn glGetRenderbufferParameterivOES(
     pname: GLenum,
     params: MutPtr<GLint>,
 ) {
-    let factor = env.options.scale_hack.get() as GLint;
+    let factor = if env.options.layer_scale_hacks.is_empty() {
+        env.options.scale_hack.get() as GLint
+    } else {
+        let renderbuffer = with_ctx_and_mem(env, |gles, _mem| unsafe {
+            let mut renderbuffer = 0;
+            gles.GetIntegerv(gles11::RENDERBUFFER_BINDING_OES, &mut renderbuffer);
+            renderbuffer as GLuint
+        });
+        renderbuffer_scale_hack(env, renderbuffer)
+    };
     with_ctx_and_mem(env, |gles, mem| {
         let params = mem.ptr_at_mut(params, 1);
         unsafe { gles.GetRenderbufferParameterivOES(target, pname, params) };
@@ -1092,14 +1512,614 @@ fn glDeleteFramebuffersOES(env: &mut Environment, n: GLsizei, framebuffers: Cons
     })
 }
 fn glDeleteRenderbuffersOES(env: &mut Environment, n: GLsizei, renderbuffers: ConstPtr<GLuint>) {
+    let names = read_names(&env.mem, renderbuffers, n);
     with_ctx_and_mem(env, |gles, mem| {
         let n_usize: GuestUSize = n.try_into().unwrap();
         let renderbuffers = mem.ptr_at(renderbuffers, n_usize);
         unsafe { gles.DeleteRenderbuffersOES(n, renderbuffers) }
-    })
+    });
+    if !env.options.layer_scale_hacks.is_empty() {
+        let scale_hacks = &mut current_sharegroup(env).renderbuffer_scale_hacks;
+        for name in &names {
+            scale_hacks.remove(name);
+        }
+    }
+    update_memory_usage(env, |usage| usage.delete_renderbuffers(&names));
 }
 fn glGenerateMipmapOES(env: &mut Environment, target: GLenum) {
-    with_ctx_and_mem(env, |gles, _mem| unsafe { gles.GenerateMipmapOES(target) })
+    let texture = with_ctx_and_mem(env, |gles, _mem| unsafe {
+        gles.GenerateMipmapOES(target);
+        get_texture_binding(gles, target)
+    });
+    update_memory_usage(env, |usage| usage.generate_mipmaps(texture));
+}
+
+// APPLE_framebuffer_multisample
+fn glRenderbufferStorageMultisampleAPPLE(
+    env: &mut Environment,
+    target: GLenum,
+    samples: GLsizei,
+    internalformat: GLenum,
+    width: GLsizei,
+    height: GLsizei,
+) {
+    // apply scale hack (see glRenderbufferStorageOES)
+    let factor = framebuffer_scale_hack(env);
+    let (width, height) = (width * factor, height * factor);
+    let renderbuffer = with_ctx_and_mem(env, |gles, _mem| unsafe {
+        gles.RenderbufferStorageMultisampleAPPLE(target, samples, internalformat, width, height);
+        let mut renderbuffer = 0;
+        gles.GetIntegerv(gles11::RENDERBUFFER_BINDING_OES, &mut renderbuffer);
+        renderbuffer as GLuint
+    });
+    set_renderbuffer_scale_hack(env, renderbuffer, factor);
+    let size = (width.max(0) as usize)
+        * (height.max(0) as usize)
+        * renderbuffer_bytes_per_pixel(internalformat)
+        * (samples.max(1) as usize);
+    update_memory_usage(env, |usage| usage.set_renderbuffer(renderbuffer, size));
+}
+fn glResolveMultisampleFramebufferAPPLE(env: &mut Environment) {
+    with_ctx_and_mem(env, |gles, _mem| unsafe {
+        gles.ResolveMultisampleFramebufferAPPLE()
+    })
+}
+
+// Core framebuffer object functions in OpenGL ES 2.0. These are identical to
+// the OES_framebuffer_object ones.
+fn glGenFramebuffers(env: &mut Environment, n: GLsizei, framebuffers: MutPtr<GLuint>) {
+    glGenFramebuffersOES(env, n, framebuffers)
+}
+fn glGenRenderbuffers(env: &mut Environment, n: GLsizei, renderbuffers: MutPtr<GLuint>) {
+    glGenRenderbuffersOES(env, n, renderbuffers)
+}
+fn glBindFramebuffer(env: &mut Environment, target: GLenum, framebuffer: GLuint) {
+    glBindFramebufferOES(env, target, framebuffer)
+}
+fn glBindRenderbuffer(env: &mut Environment, target: GLenum, renderbuffer: GLuint) {
+    glBindRenderbufferOES(env, target, renderbuffer)
+}
+fn glRenderbufferStorage(
+    env: &mut Environment,
+    target: GLenum,
+    internalformat: GLenum,
+    width: GLsizei,
+    height: GLsizei,
+) {
+    glRenderbufferStorageOES(env, target, internalformat, width, height)
+}
+fn glFramebufferRenderbuffer(
+    env: &mut Environment,
+    target: GLenum,
+    attachment: GLenum,
+    renderbuffertarget: GLenum,
+    renderbuffer: GLuint,
+) {
+    glFramebufferRenderbufferOES(env, target, attachment, renderbuffertarget, renderbuffer)
+}
+fn glFramebufferTexture2D(
+    env: &mut Environment,
+    target: GLenum,
+    attachment: GLenum,
+    textarget: GLenum,
+    texture: GLuint,
+    level: i32,
+) {
+    glFramebufferTexture2DOES(env, target, attachment, textarget, texture, level)
+}
+fn glGetRenderbufferParameteriv(
+    env: &mut Environment,
+    target: GLenum,
+    pname: GLenum,
+    params: MutPtr<GLint>,
+) {
+    glGetRenderbufferParameterivOES(env, target, pname, params)
+}
+fn glCheckFramebufferStatus(env: &mut Environment, target: GLenum) -> GLenum {
+    glCheckFramebufferStatusOES(env, target)
+}
+fn glDeleteFramebuffers(env: &mut Environment, n: GLsizei, framebuffers: ConstPtr<GLuint>) {
+    glDeleteFramebuffersOES(env, n, framebuffers)
+}
+fn glDeleteRenderbuffers(env: &mut Environment, n: GLsizei, renderbuffers: ConstPtr<GLuint>) {
+    glDeleteRenderbuffersOES(env, n, renderbuffers)
+}
+fn glGenerateMipmap(env: &mut Environment, target: GLenum) {
+    glGenerateMipmapOES(env, target)
+}
+
+// OpenGL ES 2.0: blending
+fn glBlendColor(
+    env: &mut Environment,
+    red: GLclampf,
+    green: GLclampf,
+    blue: GLclampf,
+    alpha: GLclampf,
+) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe {
+        gles.BlendColor(red, green, blue, alpha)
+    })
+}
+fn glBlendEquation(env: &mut Environment, mode: GLenum) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe { gles.BlendEquation(mode) })
+}
+fn glBlendEquationSeparate(env: &mut Environment, mode_rgb: GLenum, mode_alpha: GLenum) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe {
+        gles.BlendEquationSeparate(mode_rgb, mode_alpha)
+    })
+}
+fn glBlendFuncSeparate(
+    env: &mut Environment,
+    sfactor_rgb: GLenum,
+    dfactor_rgb: GLenum,
+    sfactor_alpha: GLenum,
+    dfactor_alpha: GLenum,
+) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe {
+        gles.BlendFuncSeparate(sfactor_rgb, dfactor_rgb, sfactor_alpha, dfactor_alpha)
+    })
+}
+
+// OpenGL ES 2.0: shaders
+fn glCreateShader(env: &mut Environment, type_: GLenum) -> GLuint {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe { gles.CreateShader(type_) })
+}
+fn glShaderSource(
+    env: &mut Environment,
+    shader: GLuint,
+    count: GLsizei,
+    string: ConstPtr<ConstPtr<u8>>,
+    length: ConstPtr<GLint>,
+) {
+    let Ok(count_usize) = GuestUSize::try_from(count) else {
+        log!(
+            "Warning: glShaderSource() called with negative count {}, ignoring (GL_INVALID_VALUE)",
+            count
+        );
+        set_pending_error(env, gles11::INVALID_VALUE);
+        return;
+    };
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let strings: Vec<*const GLchar> = (0..count_usize)
+            .map(|i| {
+                let string = mem.read(string + i);
+                let length = if length.is_null() {
+                    -1
+                } else {
+                    mem.read(length + i)
+                };
+                // A negative length means the string is null-terminated.
+                let size = if length < 0 {
+                    mem.cstr_at(string).len() as GuestUSize + 1
+                } else {
+                    length as GuestUSize
+                };
+                mem.ptr_at(string, size).cast()
+            })
+            .collect();
+        let length = if length.is_null() {
+            std::ptr::null()
+        } else {
+            mem.ptr_at(length, count_usize)
+        };
+        unsafe { gles.ShaderSource(shader, count, strings.as_ptr(), length) }
+    })
+}
+fn glCompileShader(env: &mut Environment, shader: GLuint) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe { gles.CompileShader(shader) })
+}
+fn glGetShaderiv(env: &mut Environment, shader: GLuint, pname: GLenum, params: MutPtr<GLint>) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let params = mem.ptr_at_mut(params, 1);
+        unsafe { gles.GetShaderiv(shader, pname, params) };
+    });
+}
+fn glGetShaderInfoLog(
+    env: &mut Environment,
+    shader: GLuint,
+    buf_size: GLsizei,
+    length: MutPtr<GLsizei>,
+    info_log: MutPtr<u8>,
+) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let length = if length.is_null() {
+            std::ptr::null_mut()
+        } else {
+            mem.ptr_at_mut(length, 1)
+        };
+        let info_log = mem.ptr_at_mut(info_log, buf_size.try_into().unwrap());
+        unsafe { gles.GetShaderInfoLog(shader, buf_size, length, info_log.cast()) };
+    });
+}
+fn glIsShader(env: &mut Environment, shader: GLuint) -> GLboolean {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe { gles.IsShader(shader) })
+}
+fn glDeleteShader(env: &mut Environment, shader: GLuint) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe { gles.DeleteShader(shader) })
+}
+fn glReleaseShaderCompiler(_env: &mut Environment) {
+    // This is only a hint that the app won't compile shaders for a while.
+}
+
+// OpenGL ES 2.0: programs
+fn glCreateProgram(env: &mut Environment) -> GLuint {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe { gles.CreateProgram() })
+}
+fn glAttachShader(env: &mut Environment, program: GLuint, shader: GLuint) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe {
+        gles.AttachShader(program, shader)
+    })
+}
+fn glDetachShader(env: &mut Environment, program: GLuint, shader: GLuint) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe {
+        gles.DetachShader(program, shader)
+    })
+}
+fn glBindAttribLocation(env: &mut Environment, program: GLuint, index: GLuint, name: ConstPtr<u8>) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let name = mem.cstr_at(name).as_ptr();
+        unsafe { gles.BindAttribLocation(program, index, name.cast()) }
+    })
+}
+fn glLinkProgram(env: &mut Environment, program: GLuint) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe { gles.LinkProgram(program) })
+}
+fn glValidateProgram(env: &mut Environment, program: GLuint) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe { gles.ValidateProgram(program) })
+}
+fn glUseProgram(env: &mut Environment, program: GLuint) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe { gles.UseProgram(program) })
+}
+fn glGetProgramiv(env: &mut Environment, program: GLuint, pname: GLenum, params: MutPtr<GLint>) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let params = mem.ptr_at_mut(params, 1);
+        unsafe { gles.GetProgramiv(program, pname, params) };
+    });
+}
+fn glGetProgramInfoLog(
+    env: &mut Environment,
+    program: GLuint,
+    buf_size: GLsizei,
+    length: MutPtr<GLsizei>,
+    info_log: MutPtr<u8>,
+) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let length = if length.is_null() {
+            std::ptr::null_mut()
+        } else {
+            mem.ptr_at_mut(length, 1)
+        };
+        let info_log = mem.ptr_at_mut(info_log, buf_size.try_into().unwrap());
+        unsafe { gles.GetProgramInfoLog(program, buf_size, length, info_log.cast()) };
+    });
+}
+fn glIsProgram(env: &mut Environment, program: GLuint) -> GLboolean {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe { gles.IsProgram(program) })
+}
+fn glDeleteProgram(env: &mut Environment, program: GLuint) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe { gles.DeleteProgram(program) })
+}
+fn glGetAttribLocation(env: &mut Environment, program: GLuint, name: ConstPtr<u8>) -> GLint {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let name = mem.cstr_at(name).as_ptr();
+        unsafe { gles.GetAttribLocation(program, name.cast()) }
+    })
+}
+fn glGetUniformLocation(env: &mut Environment, program: GLuint, name: ConstPtr<u8>) -> GLint {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let name = mem.cstr_at(name).as_ptr();
+        unsafe { gles.GetUniformLocation(program, name.cast()) }
+    })
+}
+fn glGetActiveAttrib(
+    env: &mut Environment,
+    program: GLuint,
+    index: GLuint,
+    buf_size: GLsizei,
+    length: MutPtr<GLsizei>,
+    size: MutPtr<GLint>,
+    type_: MutPtr<GLenum>,
+    name: MutPtr<u8>,
+) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let length = if length.is_null() {
+            std::ptr::null_mut()
+        } else {
+            mem.ptr_at_mut(length, 1)
+        };
+        let size = mem.ptr_at_mut(size, 1);
+        let type_ = mem.ptr_at_mut(type_, 1);
+        let name = mem.ptr_at_mut(name, buf_size.try_into().unwrap());
+        unsafe { gles.GetActiveAttrib(program, index, buf_size, length, size, type_, name.cast()) };
+    });
+}
+fn glGetActiveUniform(
+    env: &mut Environment,
+    program: GLuint,
+    index: GLuint,
+    buf_size: GLsizei,
+    length: MutPtr<GLsizei>,
+    size: MutPtr<GLint>,
+    type_: MutPtr<GLenum>,
+    name: MutPtr<u8>,
+) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let length = if length.is_null() {
+            std::ptr::null_mut()
+        } else {
+            mem.ptr_at_mut(length, 1)
+        };
+        let size = mem.ptr_at_mut(size, 1);
+        let type_ = mem.ptr_at_mut(type_, 1);
+        let name = mem.ptr_at_mut(name, buf_size.try_into().unwrap());
+        unsafe {
+            gles.GetActiveUniform(program, index, buf_size, length, size, type_, name.cast())
+        };
+    });
+}
+
+// OpenGL ES 2.0: uniforms
+fn glUniform1f(env: &mut Environment, location: GLint, v0: GLfloat) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe { gles.Uniform1f(location, v0) })
+}
+fn glUniform2f(env: &mut Environment, location: GLint, v0: GLfloat, v1: GLfloat) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe {
+        gles.Uniform2f(location, v0, v1)
+    })
+}
+fn glUniform3f(env: &mut Environment, location: GLint, v0: GLfloat, v1: GLfloat, v2: GLfloat) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe {
+        gles.Uniform3f(location, v0, v1, v2)
+    })
+}
+fn glUniform4f(
+    env: &mut Environment,
+    location: GLint,
+    v0: GLfloat,
+    v1: GLfloat,
+    v2: GLfloat,
+    v3: GLfloat,
+) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe {
+        gles.Uniform4f(location, v0, v1, v2, v3)
+    })
+}
+fn glUniform1i(env: &mut Environment, location: GLint, v0: GLint) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe { gles.Uniform1i(location, v0) })
+}
+fn glUniform2i(env: &mut Environment, location: GLint, v0: GLint, v1: GLint) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe {
+        gles.Uniform2i(location, v0, v1)
+    })
+}
+fn glUniform3i(env: &mut Environment, location: GLint, v0: GLint, v1: GLint, v2: GLint) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe {
+        gles.Uniform3i(location, v0, v1, v2)
+    })
+}
+fn glUniform4i(env: &mut Environment, location: GLint, v0: GLint, v1: GLint, v2: GLint, v3: GLint) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe {
+        gles.Uniform4i(location, v0, v1, v2, v3)
+    })
+}
+/// Helper for implementing `glUniform*v` and `glUniformMatrix*fv`: get a host
+/// pointer to `count` uniform values of `components` components each.
+fn uniform_values_ptr<T>(
+    mem: &Mem,
+    value: ConstPtr<T>,
+    count: GLsizei,
+    components: GuestUSize,
+) -> *const T {
+    let count: GuestUSize = count.try_into().unwrap();
+    mem.ptr_at(value, count.checked_mul(components).unwrap())
+}
+fn glUniform1fv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLfloat>) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 1);
+        unsafe { gles.Uniform1fv(location, count, value) }
+    })
+}
+fn glUniform2fv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLfloat>) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 2);
+        unsafe { gles.Uniform2fv(location, count, value) }
+    })
+}
+fn glUniform3fv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLfloat>) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 3);
+        unsafe { gles.Uniform3fv(location, count, value) }
+    })
+}
+fn glUniform4fv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLfloat>) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 4);
+        unsafe { gles.Uniform4fv(location, count, value) }
+    })
+}
+fn glUniform1iv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLint>) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 1);
+        unsafe { gles.Uniform1iv(location, count, value) }
+    })
+}
+fn glUniform2iv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLint>) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 2);
+        unsafe { gles.Uniform2iv(location, count, value) }
+    })
+}
+fn glUniform3iv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLint>) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 3);
+        unsafe { gles.Uniform3iv(location, count, value) }
+    })
+}
+fn glUniform4iv(env: &mut Environment, location: GLint, count: GLsizei, value: ConstPtr<GLint>) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 4);
+        unsafe { gles.Uniform4iv(location, count, value) }
+    })
+}
+fn glUniformMatrix2fv(
+    env: &mut Environment,
+    location: GLint,
+    count: GLsizei,
+    transpose: GLboolean,
+    value: ConstPtr<GLfloat>,
+) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 2 * 2);
+        unsafe { gles.UniformMatrix2fv(location, count, transpose, value) }
+    })
+}
+fn glUniformMatrix3fv(
+    env: &mut Environment,
+    location: GLint,
+    count: GLsizei,
+    transpose: GLboolean,
+    value: ConstPtr<GLfloat>,
+) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 3 * 3);
+        unsafe { gles.UniformMatrix3fv(location, count, transpose, value) }
+    })
+}
+fn glUniformMatrix4fv(
+    env: &mut Environment,
+    location: GLint,
+    count: GLsizei,
+    transpose: GLboolean,
+    value: ConstPtr<GLfloat>,
+) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let value = uniform_values_ptr(mem, value, count, 4 * 4);
+        unsafe { gles.UniformMatrix4fv(location, count, transpose, value) }
+    })
+}
+
+// OpenGL ES 2.0: vertex attributes
+fn glEnableVertexAttribArray(env: &mut Environment, index: GLuint) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe {
+        gles.EnableVertexAttribArray(index)
+    })
+}
+fn glDisableVertexAttribArray(env: &mut Environment, index: GLuint) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe {
+        gles.DisableVertexAttribArray(index)
+    })
+}
+fn glVertexAttribPointer(
+    env: &mut Environment,
+    index: GLuint,
+    size: GLint,
+    type_: GLenum,
+    normalized: GLboolean,
+    stride: GLsizei,
+    pointer: ConstVoidPtr,
+) {
+    with_ctx_and_mem_es2(env, |gles, mem| unsafe {
+        let pointer =
+            translate_pointer_or_offset_to_host(gles, mem, pointer, gles11::ARRAY_BUFFER_BINDING);
+        gles.VertexAttribPointer(index, size, type_, normalized, stride, pointer)
+    })
+}
+fn glVertexAttrib1f(env: &mut Environment, index: GLuint, x: GLfloat) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe { gles.VertexAttrib1f(index, x) })
+}
+fn glVertexAttrib2f(env: &mut Environment, index: GLuint, x: GLfloat, y: GLfloat) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe {
+        gles.VertexAttrib2f(index, x, y)
+    })
+}
+fn glVertexAttrib3f(env: &mut Environment, index: GLuint, x: GLfloat, y: GLfloat, z: GLfloat) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe {
+        gles.VertexAttrib3f(index, x, y, z)
+    })
+}
+fn glVertexAttrib4f(
+    env: &mut Environment,
+    index: GLuint,
+    x: GLfloat,
+    y: GLfloat,
+    z: GLfloat,
+    w: GLfloat,
+) {
+    with_ctx_and_mem_es2(env, |gles, _mem| unsafe {
+        gles.VertexAttrib4f(index, x, y, z, w)
+    })
+}
+fn glVertexAttrib1fv(env: &mut Environment, index: GLuint, v: ConstPtr<GLfloat>) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let v = mem.ptr_at(v, 1);
+        unsafe { gles.VertexAttrib1fv(index, v) }
+    })
+}
+fn glVertexAttrib2fv(env: &mut Environment, index: GLuint, v: ConstPtr<GLfloat>) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let v = mem.ptr_at(v, 2);
+        unsafe { gles.VertexAttrib2fv(index, v) }
+    })
+}
+fn glVertexAttrib3fv(env: &mut Environment, index: GLuint, v: ConstPtr<GLfloat>) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let v = mem.ptr_at(v, 3);
+        unsafe { gles.VertexAttrib3fv(index, v) }
+    })
+}
+fn glVertexAttrib4fv(env: &mut Environment, index: GLuint, v: ConstPtr<GLfloat>) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let v = mem.ptr_at(v, 4);
+        unsafe { gles.VertexAttrib4fv(index, v) }
+    })
+}
+fn glGetVertexAttribiv(env: &mut Environment, index: GLuint, pname: GLenum, params: MutPtr<GLint>) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let params = mem.ptr_at_mut(params, 4 /* upper bound */);
+        unsafe { gles.GetVertexAttribiv(index, pname, params) };
+    });
+}
+fn glGetVertexAttribfv(
+    env: &mut Environment,
+    index: GLuint,
+    pname: GLenum,
+    params: MutPtr<GLfloat>,
+) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let params = mem.ptr_at_mut(params, 4 /* upper bound */);
+        unsafe { gles.GetVertexAttribfv(index, pname, params) };
+    });
+}
+fn glGetVertexAttribPointerv(
+    env: &mut Environment,
+    index: GLuint,
+    pname: GLenum,
+    pointer: MutPtr<ConstVoidPtr>,
+) {
+    with_ctx_and_mem_es2(env, |gles, mem| {
+        let mut host_pointer_or_offset = std::ptr::null();
+        let guest_pointer_or_offset = unsafe {
+            gles.GetVertexAttribPointerv(index, pname, &mut host_pointer_or_offset);
+            // Unlike for OpenGL ES 1.1's arrays, the binding can't be queried
+            // with glGetIntegerv, so translate_pointer_or_offset_to_guest()
+            // can't be used.
+            let mut buffer_binding = 0;
+            gles.GetVertexAttribiv(
+                index,
+                gles2::VERTEX_ATTRIB_ARRAY_BUFFER_BINDING,
+                &mut buffer_binding,
+            );
+            if buffer_binding != 0 {
+                let offset = host_pointer_or_offset as usize;
+                Ptr::from_bits(u32::try_from(offset).unwrap())
+            } else if host_pointer_or_offset.is_null() {
+                Ptr::null()
+            } else {
+                mem.host_ptr_to_guest_ptr(host_pointer_or_offset)
+            }
+        };
+        mem.write(pointer, guest_pointer_or_offset);
+    });
 }
 
 /// If fog is enabled, check if the values for start and end distances
@@ -1270,4 +2290,84 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(glDeleteFramebuffersOES(_, _)),
     export_c_func!(glDeleteRenderbuffersOES(_, _)),
     export_c_func!(glGenerateMipmapOES(_)),
+    export_c_func!(glRenderbufferStorageMultisampleAPPLE(_, _, _, _, _)),
+    export_c_func!(glResolveMultisampleFramebufferAPPLE()),
+    // Core framebuffer object functions in OpenGL ES 2.0
+    export_c_func!(glGenFramebuffers(_, _)),
+    export_c_func!(glGenRenderbuffers(_, _)),
+    export_c_func!(glBindFramebuffer(_, _)),
+    export_c_func!(glBindRenderbuffer(_, _)),
+    export_c_func!(glRenderbufferStorage(_, _, _, _)),
+    export_c_func!(glFramebufferRenderbuffer(_, _, _, _)),
+    export_c_func!(glFramebufferTexture2D(_, _, _, _, _)),
+    export_c_func!(glGetRenderbufferParameteriv(_, _, _)),
+    export_c_func!(glCheckFramebufferStatus(_)),
+    export_c_func!(glDeleteFramebuffers(_, _)),
+    export_c_func!(glDeleteRenderbuffers(_, _)),
+    export_c_func!(glGenerateMipmap(_)),
+    // OpenGL ES 2.0: blending
+    export_c_func!(glBlendColor(_, _, _, _)),
+    export_c_func!(glBlendEquation(_)),
+    export_c_func!(glBlendEquationSeparate(_, _)),
+    export_c_func!(glBlendFuncSeparate(_, _, _, _)),
+    // OpenGL ES 2.0: shaders
+    export_c_func!(glCreateShader(_)),
+    export_c_func!(glShaderSource(_, _, _, _)),
+    export_c_func!(glCompileShader(_)),
+    export_c_func!(glGetShaderiv(_, _, _)),
+    export_c_func!(glGetShaderInfoLog(_, _, _, _)),
+    export_c_func!(glIsShader(_)),
+    export_c_func!(glDeleteShader(_)),
+    export_c_func!(glReleaseShaderCompiler()),
+    // OpenGL ES 2.0: programs
+    export_c_func!(glCreateProgram()),
+    export_c_func!(glAttachShader(_, _)),
+    export_c_func!(glDetachShader(_, _)),
+    export_c_func!(glBindAttribLocation(_, _, _)),
+    export_c_func!(glLinkProgram(_)),
+    export_c_func!(glValidateProgram(_)),
+    export_c_func!(glUseProgram(_)),
+    export_c_func!(glGetProgramiv(_, _, _)),
+    export_c_func!(glGetProgramInfoLog(_, _, _, _)),
+    export_c_func!(glIsProgram(_)),
+    export_c_func!(glDeleteProgram(_)),
+    export_c_func!(glGetAttribLocation(_, _)),
+    export_c_func!(glGetUniformLocation(_, _)),
+    export_c_func!(glGetActiveAttrib(_, _, _, _, _, _, _)),
+    export_c_func!(glGetActiveUniform(_, _, _, _, _, _, _)),
+    // OpenGL ES 2.0: uniforms
+    export_c_func!(glUniform1f(_, _)),
+    export_c_func!(glUniform2f(_, _, _)),
+    export_c_func!(glUniform3f(_, _, _, _)),
+    export_c_func!(glUniform4f(_, _, _, _, _)),
+    export_c_func!(glUniform1i(_, _)),
+    export_c_func!(glUniform2i(_, _, _)),
+    export_c_func!(glUniform3i(_, _, _, _)),
+    export_c_func!(glUniform4i(_, _, _, _, _)),
+    export_c_func!(glUniform1fv(_, _, _)),
+    export_c_func!(glUniform2fv(_, _, _)),
+    export_c_func!(glUniform3fv(_, _, _)),
+    export_c_func!(glUniform4fv(_, _, _)),
+    export_c_func!(glUniform1iv(_, _, _)),
+    export_c_func!(glUniform2iv(_, _, _)),
+    export_c_func!(glUniform3iv(_, _, _)),
+    export_c_func!(glUniform4iv(_, _, _)),
+    export_c_func!(glUniformMatrix2fv(_, _, _, _)),
+    export_c_func!(glUniformMatrix3fv(_, _, _, _)),
+    export_c_func!(glUniformMatrix4fv(_, _, _, _)),
+    // OpenGL ES 2.0: vertex attributes
+    export_c_func!(glEnableVertexAttribArray(_)),
+    export_c_func!(glDisableVertexAttribArray(_)),
+    export_c_func!(glVertexAttribPointer(_, _, _, _, _, _)),
+    export_c_func!(glVertexAttrib1f(_, _)),
+    export_c_func!(glVertexAttrib2f(_, _, _)),
+    export_c_func!(glVertexAttrib3f(_, _, _, _)),
+    export_c_func!(glVertexAttrib4f(_, _, _, _, _)),
+    export_c_func!(glVertexAttrib1fv(_, _)),
+    export_c_func!(glVertexAttrib2fv(_, _)),
+    export_c_func!(glVertexAttrib3fv(_, _)),
+    export_c_func!(glVertexAttrib4fv(_, _)),
+    export_c_func!(glGetVertexAttribiv(_, _, _)),
+    export_c_func!(glGetVertexAttribfv(_, _, _)),
+    export_c_func!(glGetVertexAttribPointerv(_, _, _)),
 ];
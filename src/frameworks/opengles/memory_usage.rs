@@ -0,0 +1,131 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Accounting of the memory used by the app's OpenGL ES objects.
+//!
+//! There's no portable way to ask the host driver how much memory an object
+//! uses, and it wouldn't tell us how much the app _thinks_ it is using anyway,
+//! so the sizes here are estimates based on the parameters the app passes when
+//! allocating storage. This is mainly useful for spotting apps that leak
+//! textures or buffers, which is easy to do by accident when the app was only
+//! ever tested with a single context on a real device.
+
+use crate::gles::gles11_raw as gles11; // constants only
+use crate::gles::gles11_raw::types::*;
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct MemoryUsage {
+    /// Estimated size in bytes of each level of detail of each texture.
+    textures: HashMap<(GLuint, GLint), usize>,
+    /// Estimated size in bytes of each renderbuffer's storage.
+    renderbuffers: HashMap<GLuint, usize>,
+    /// Size in bytes of each buffer object's data store.
+    buffers: HashMap<GLuint, usize>,
+}
+
+impl MemoryUsage {
+    pub fn set_texture_level(&mut self, texture: GLuint, level: GLint, size: usize) {
+        // Texture 0 is the default texture, which can have storage too, but
+        // can't be deleted, so it's not interesting to track.
+        if texture == 0 {
+            return;
+        }
+        self.textures.insert((texture, level), size);
+    }
+    /// Estimate the size of a mipmap chain generated from level 0 of a texture.
+    pub fn generate_mipmaps(&mut self, texture: GLuint) {
+        let Some(&base_size) = self.textures.get(&(texture, 0)) else {
+            return;
+        };
+        let mut level = 1;
+        let mut size = base_size / 4;
+        while size > 0 {
+            self.textures.insert((texture, level), size);
+            level += 1;
+            size /= 4;
+        }
+    }
+    pub fn delete_textures(&mut self, textures: &[GLuint]) {
+        self.textures
+            .retain(|&(texture, _), _| !textures.contains(&texture));
+    }
+    pub fn set_renderbuffer(&mut self, renderbuffer: GLuint, size: usize) {
+        if renderbuffer == 0 {
+            return;
+        }
+        self.renderbuffers.insert(renderbuffer, size);
+    }
+    pub fn delete_renderbuffers(&mut self, renderbuffers: &[GLuint]) {
+        self.renderbuffers
+            .retain(|renderbuffer, _| !renderbuffers.contains(renderbuffer));
+    }
+    pub fn set_buffer(&mut self, buffer: GLuint, size: usize) {
+        if buffer == 0 {
+            return;
+        }
+        self.buffers.insert(buffer, size);
+    }
+    pub fn delete_buffers(&mut self, buffers: &[GLuint]) {
+        self.buffers.retain(|buffer, _| !buffers.contains(buffer));
+    }
+
+    /// Total estimated size in bytes of all textures, renderbuffers and
+    /// buffers respectively.
+    pub fn totals(&self) -> (usize, usize, usize) {
+        (
+            self.textures.values().sum(),
+            self.renderbuffers.values().sum(),
+            self.buffers.values().sum(),
+        )
+    }
+
+    /// Describe the totals and the number of objects of each kind.
+    pub fn summary(&self) -> String {
+        fn mib(bytes: usize) -> f64 {
+            bytes as f64 / (1024.0 * 1024.0)
+        }
+        let (textures, renderbuffers, buffers) = self.totals();
+        let texture_count = {
+            let mut names: Vec<GLuint> = self.textures.keys().map(|&(name, _)| name).collect();
+            names.sort();
+            names.dedup();
+            names.len()
+        };
+        format!(
+            "{:.2} MiB total, {:.2} MiB in {} textures, {:.2} MiB in {} renderbuffers, {:.2} MiB in {} buffers",
+            mib(textures + renderbuffers + buffers),
+            mib(textures),
+            texture_count,
+            mib(renderbuffers),
+            self.renderbuffers.len(),
+            mib(buffers),
+            self.buffers.len(),
+        )
+    }
+
+    /// Print a summary to the console.
+    pub fn report(&self, label: std::fmt::Arguments<'_>) {
+        echo!(
+            "touchHLE: {} OpenGL ES memory (estimated): {}",
+            label,
+            self.summary()
+        );
+    }
+}
+
+/// Estimate the number of bytes per pixel used by a renderbuffer with a
+/// particular internal format.
+pub fn renderbuffer_bytes_per_pixel(internalformat: GLenum) -> usize {
+    match internalformat {
+        gles11::RGBA8_OES | gles11::RGB8_OES => 4,
+        gles11::RGB565_OES | gles11::RGBA4_OES | gles11::RGB5_A1_OES => 2,
+        gles11::DEPTH_COMPONENT16_OES => 2,
+        // Other depth and stencil formats come from extensions that don't have
+        // constants in our bindings. Most are 24-bit depth, often packed with
+        // 8-bit stencil, so this is a reasonable guess.
+        _ => 4,
+    }
+}
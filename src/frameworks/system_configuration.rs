@@ -0,0 +1,219 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! The System Configuration framework, which only has
+//! `SCNetworkReachability.h` so far.
+//!
+//! Reachability just reports the connection type from the `--connection-type=`
+//! option, which never changes while the app is running, so every target is
+//! equally reachable.
+
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::core_foundation::cf_allocator::CFAllocatorRef;
+use crate::frameworks::core_foundation::cf_run_loop::{CFRunLoopMode, CFRunLoopRef};
+use crate::frameworks::core_foundation::{CFIndex, CFTypeRef};
+use crate::mem::{ConstPtr, ConstVoidPtr, MutPtr, MutVoidPtr, SafeRead};
+use crate::objc::{id, msg, msg_class, nil, objc_classes, ClassExports, HostObject};
+use crate::options::ConnectionType;
+use crate::Environment;
+
+type SCNetworkReachabilityRef = CFTypeRef;
+
+type SCNetworkReachabilityFlags = u32;
+const kSCNetworkReachabilityFlagsTransientConnection: SCNetworkReachabilityFlags = 1 << 0;
+const kSCNetworkReachabilityFlagsReachable: SCNetworkReachabilityFlags = 1 << 1;
+const kSCNetworkReachabilityFlagsIsDirect: SCNetworkReachabilityFlags = 1 << 17;
+const kSCNetworkReachabilityFlagsIsWWAN: SCNetworkReachabilityFlags = 1 << 18;
+
+// void (*)(SCNetworkReachabilityRef target, SCNetworkReachabilityFlags flags,
+//          void *info)
+type SCNetworkReachabilityCallBack = GuestFunction;
+
+#[repr(C, packed)]
+struct SCNetworkReachabilityContext {
+    version: CFIndex,
+    info: MutVoidPtr,
+    retain: GuestFunction,
+    release: GuestFunction,
+    copy_description: GuestFunction,
+}
+unsafe impl SafeRead for SCNetworkReachabilityContext {}
+
+struct SCNetworkReachabilityHostObject {
+    /// Whether the target is a link-local address (169.254.0.0/16), which is
+    /// what apps use to check for Wi-Fi specifically.
+    is_link_local: bool,
+    callout: Option<(SCNetworkReachabilityCallBack, MutVoidPtr)>,
+    scheduled: bool,
+}
+impl HostObject for SCNetworkReachabilityHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// SCNetworkReachability is a CFType-based type, but in our implementation
+// those are just Objective-C types, so we need a class for it, but its name is
+// not visible anywhere.
+@implementation _touchHLE_SCNetworkReachability: NSObject
+
+// Fired by a timer once the target has been scheduled on a run loop.
+- (())notifyCallout:(id)_timer { // NSTimer*
+    let host_object = env.objc.borrow::<SCNetworkReachabilityHostObject>(this);
+    let (true, Some((callout, info))) = (host_object.scheduled, host_object.callout) else {
+        return;
+    };
+    let flags = flags_for(env, this);
+    log_dbg!("Calling reachability callout for {:?} with flags {:#x}", this, flags);
+    () = callout.call_from_host(env, (this, flags, info));
+}
+
+@end
+
+};
+
+fn create_reachability(env: &mut Environment, is_link_local: bool) -> SCNetworkReachabilityRef {
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_SCNetworkReachability", &mut env.mem);
+    env.objc.alloc_object(
+        isa,
+        Box::new(SCNetworkReachabilityHostObject {
+            is_link_local,
+            callout: None,
+            scheduled: false,
+        }),
+        &mut env.mem,
+    )
+}
+
+fn flags_for(
+    env: &mut Environment,
+    target: SCNetworkReachabilityRef,
+) -> SCNetworkReachabilityFlags {
+    let is_link_local = env
+        .objc
+        .borrow::<SCNetworkReachabilityHostObject>(target)
+        .is_link_local;
+    match env.options.connection_type {
+        ConnectionType::None => 0,
+        ConnectionType::WiFi if is_link_local => {
+            kSCNetworkReachabilityFlagsReachable | kSCNetworkReachabilityFlagsIsDirect
+        }
+        ConnectionType::WiFi => kSCNetworkReachabilityFlagsReachable,
+        // Link-local addresses aren't reachable over cellular.
+        ConnectionType::WWAN if is_link_local => 0,
+        ConnectionType::WWAN => {
+            kSCNetworkReachabilityFlagsReachable
+                | kSCNetworkReachabilityFlagsIsWWAN
+                | kSCNetworkReachabilityFlagsTransientConnection
+        }
+    }
+}
+
+fn SCNetworkReachabilityCreateWithName(
+    env: &mut Environment,
+    _allocator: CFAllocatorRef,
+    nodename: ConstPtr<u8>,
+) -> SCNetworkReachabilityRef {
+    log_dbg!(
+        "SCNetworkReachabilityCreateWithName(_, {:?})",
+        env.mem.cstr_at_utf8(nodename)
+    );
+    create_reachability(env, false)
+}
+
+fn SCNetworkReachabilityCreateWithAddress(
+    env: &mut Environment,
+    _allocator: CFAllocatorRef,
+    address: ConstVoidPtr, // const struct sockaddr *
+) -> SCNetworkReachabilityRef {
+    // struct sockaddr_in has a length byte, a family byte, a 16-bit port and
+    // then the IPv4 address.
+    const AF_INET: u8 = 2;
+    let address: ConstPtr<u8> = address.cast();
+    let is_link_local = env.mem.read(address + 1) == AF_INET
+        && env.mem.read(address + 4) == 169
+        && env.mem.read(address + 5) == 254;
+    create_reachability(env, is_link_local)
+}
+
+fn SCNetworkReachabilityGetFlags(
+    env: &mut Environment,
+    target: SCNetworkReachabilityRef,
+    flags: MutPtr<SCNetworkReachabilityFlags>,
+) -> bool {
+    let value = flags_for(env, target);
+    env.mem.write(flags, value);
+    true
+}
+
+fn SCNetworkReachabilitySetCallback(
+    env: &mut Environment,
+    target: SCNetworkReachabilityRef,
+    callout: SCNetworkReachabilityCallBack,
+    context: MutPtr<SCNetworkReachabilityContext>,
+) -> bool {
+    let callout = if callout.to_ptr().is_null() {
+        None
+    } else {
+        let context = env.mem.read(context);
+        // TODO: handle non-NULL callbacks
+        let retain = context.retain;
+        let release = context.release;
+        if !retain.to_ptr().is_null() || !release.to_ptr().is_null() {
+            log!("TODO: SCNetworkReachabilityContext retain/release callbacks are ignored");
+        }
+        Some((callout, context.info))
+    };
+    env.objc
+        .borrow_mut::<SCNetworkReachabilityHostObject>(target)
+        .callout = callout;
+    true
+}
+
+fn SCNetworkReachabilityScheduleWithRunLoop(
+    env: &mut Environment,
+    target: SCNetworkReachabilityRef,
+    run_loop: CFRunLoopRef,
+    mode: CFRunLoopMode,
+) -> bool {
+    env.objc
+        .borrow_mut::<SCNetworkReachabilityHostObject>(target)
+        .scheduled = true;
+
+    // The reachability never changes, but apps expect to be told about it
+    // once after scheduling, like when the system finishes resolving a name.
+    let selector = env.objc.lookup_selector("notifyCallout:").unwrap();
+    let timer: id = msg_class![env; NSTimer timerWithTimeInterval:0.0
+                                                           target:target
+                                                         selector:selector
+                                                         userInfo:nil
+                                                          repeats:false];
+    () = msg![env; run_loop addTimer:timer forMode:mode];
+    true
+}
+
+fn SCNetworkReachabilityUnscheduleFromRunLoop(
+    env: &mut Environment,
+    target: SCNetworkReachabilityRef,
+    _run_loop: CFRunLoopRef,
+    _mode: CFRunLoopMode,
+) -> bool {
+    env.objc
+        .borrow_mut::<SCNetworkReachabilityHostObject>(target)
+        .scheduled = false;
+    true
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(SCNetworkReachabilityCreateWithName(_, _)),
+    export_c_func!(SCNetworkReachabilityCreateWithAddress(_, _)),
+    export_c_func!(SCNetworkReachabilityGetFlags(_, _)),
+    export_c_func!(SCNetworkReachabilitySetCallback(_, _, _)),
+    export_c_func!(SCNetworkReachabilityScheduleWithRunLoop(_, _, _)),
+    export_c_func!(SCNetworkReachabilityUnscheduleFromRunLoop(_, _, _)),
+];
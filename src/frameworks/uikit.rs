@@ -24,6 +24,7 @@ pub mod ui_graphics;
 pub mod ui_image;
 pub mod ui_image_picker_controller;
 pub mod ui_nib;
+pub mod ui_pasteboard;
 pub mod ui_responder;
 pub mod ui_screen;
 pub mod ui_touch;
@@ -38,6 +39,7 @@ pub struct State {
     ui_device: ui_device::State,
     ui_font: ui_font::State,
     ui_graphics: ui_graphics::State,
+    ui_pasteboard: ui_pasteboard::State,
     ui_screen: ui_screen::State,
     ui_touch: ui_touch::State,
     pub ui_view: ui_view::State,
@@ -89,6 +91,8 @@ pub fn handle_events(env: &mut Environment) -> Option<Instant> {
                 log!("Handling app-will-terminate event.");
                 ui_application::exit(env);
             }
+            Event::HomeButton => ui_application::handle_home_button(env),
+            Event::LockButton => ui_application::handle_lock_button(env),
             Event::EnterDebugger => {
                 if env.is_debugging_enabled() {
                     log!("Handling EnterDebugger event: entering debugger.");
@@ -98,6 +102,26 @@ pub fn handle_events(env: &mut Environment) -> Option<Instant> {
                     log!("Ignoring EnterDebugger event: no debugger connected.");
                 }
             }
+            Event::MemoryReport => {
+                let report = env.memory_usage_report();
+                echo!("{}", report.trim_end());
+                let window = env.window_mut();
+                window.console_write(&report);
+                window.show_console();
+            }
+            Event::HeapSnapshot => {
+                let snapshot = env.objc.heap_snapshot(&env.mem);
+                let dir =
+                    crate::paths::user_data_base_path().join(crate::paths::HEAP_SNAPSHOTS_DIR);
+                match snapshot.write_files(&dir) {
+                    Ok(path) => echo!(
+                        "Saved heap snapshot of {} objects to {}",
+                        snapshot.object_count(),
+                        path.display()
+                    ),
+                    Err(e) => log!("Warning: couldn't save heap snapshot: {}", e),
+                }
+            }
             Event::TextInput(text_event) => {
                 let responder = env.framework_state.uikit.ui_responder.first_responder;
                 let class = msg![env; responder class];
@@ -120,5 +144,7 @@ pub fn handle_events(env: &mut Environment) -> Option<Instant> {
         }
     }
 
+    ui_device::handle_battery(env);
+
     ui_accelerometer::handle_accelerometer(env)
 }
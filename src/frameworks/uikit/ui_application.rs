@@ -6,22 +6,28 @@
 //! `UIApplication` and `UIApplicationMain`.
 
 use super::ui_device::*;
-use crate::dyld::{export_c_func, FunctionExports};
-use crate::frameworks::foundation::{ns_array, ns_string};
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::frameworks::foundation::{ns_array, ns_string, ns_url_connection, NSInteger};
 use crate::frameworks::uikit::ui_nib::load_main_nib_file;
 use crate::mem::MutPtr;
 use crate::objc::{
-    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
-    NSZonePtr,
+    autorelease, id, msg, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports,
+    HostObject, NSZonePtr,
 };
-use crate::window::DeviceOrientation;
+use crate::window::{DeviceOrientation, Event};
 use crate::Environment;
+use std::time::Duration;
 
 #[derive(Default)]
 pub struct State {
     /// [UIApplication sharedApplication]
     shared_application: Option<id>,
     pub(super) status_bar_hidden: bool,
+    /// Whether `applicationDidBecomeActive:` has been sent more recently than
+    /// `applicationWillResignActive:`.
+    is_active: bool,
+    icon_badge_number: NSInteger,
+    network_activity_indicator_visible: bool,
 }
 
 struct UIApplicationHostObject {
@@ -32,6 +38,69 @@ impl HostObject for UIApplicationHostObject {}
 
 type UIInterfaceOrientation = UIDeviceOrientation;
 
+pub const UIApplicationDidBecomeActiveNotification: &str =
+    "UIApplicationDidBecomeActiveNotification";
+pub const UIApplicationWillResignActiveNotification: &str =
+    "UIApplicationWillResignActiveNotification";
+pub const UIApplicationDidEnterBackgroundNotification: &str =
+    "UIApplicationDidEnterBackgroundNotification";
+pub const UIApplicationWillEnterForegroundNotification: &str =
+    "UIApplicationWillEnterForegroundNotification";
+pub const UIApplicationWillTerminateNotification: &str = "UIApplicationWillTerminateNotification";
+
+/// `NSNotificationName` values.
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_UIApplicationDidBecomeActiveNotification",
+        HostConstant::NSString(UIApplicationDidBecomeActiveNotification),
+    ),
+    (
+        "_UIApplicationWillResignActiveNotification",
+        HostConstant::NSString(UIApplicationWillResignActiveNotification),
+    ),
+    (
+        "_UIApplicationDidEnterBackgroundNotification",
+        HostConstant::NSString(UIApplicationDidEnterBackgroundNotification),
+    ),
+    (
+        "_UIApplicationWillEnterForegroundNotification",
+        HostConstant::NSString(UIApplicationWillEnterForegroundNotification),
+    ),
+    (
+        "_UIApplicationWillTerminateNotification",
+        HostConstant::NSString(UIApplicationWillTerminateNotification),
+    ),
+];
+
+/// There's no home screen or status bar to show the icon badge and network
+/// activity indicator in, so they're shown in the window title instead, along
+/// with the progress of any downloads.
+pub fn update_window_title(env: &mut Environment) {
+    let &State {
+        icon_badge_number,
+        network_activity_indicator_visible,
+        ..
+    } = &env.framework_state.uikit.ui_application;
+    let mut status = Vec::new();
+    if icon_badge_number != 0 {
+        status.push(format!("[{}]", icon_badge_number));
+    }
+    if network_activity_indicator_visible {
+        status.push("(network activity)".to_string());
+    }
+    if let Some((received, expected)) = ns_url_connection::download_progress(env) {
+        const MEGABYTE: f64 = (1 << 20) as f64;
+        status.push(format!(
+            "(downloading {:.1} of {:.1} MB)",
+            received as f64 / MEGABYTE,
+            expected as f64 / MEGABYTE
+        ));
+    }
+    if let Some(window) = env.window.as_mut() {
+        window.set_title_status(&status.join(" "));
+    }
+}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -114,6 +183,23 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.window_mut().set_screen_saver_enabled(!disabled);
 }
 
+- (NSInteger)applicationIconBadgeNumber {
+    env.framework_state.uikit.ui_application.icon_badge_number
+}
+- (())setApplicationIconBadgeNumber:(NSInteger)number {
+    log_dbg!("[(UIApplication*){:?} setApplicationIconBadgeNumber:{}]", this, number);
+    env.framework_state.uikit.ui_application.icon_badge_number = number;
+    update_window_title(env);
+}
+
+- (bool)isNetworkActivityIndicatorVisible {
+    env.framework_state.uikit.ui_application.network_activity_indicator_visible
+}
+- (())setNetworkActivityIndicatorVisible:(bool)visible {
+    env.framework_state.uikit.ui_application.network_activity_indicator_visible = visible;
+    update_window_title(env);
+}
+
 - (bool)openURL:(id)url { // NSURL
     let ns_string = msg![env; url absoluteString];
     let url_string = ns_string::to_rust_string(env, ns_string);
@@ -246,17 +332,7 @@ pub(super) fn UIApplicationMain(
 
     // Send applicationDidBecomeActive now that the application is ready to
     // become active.
-    {
-        let pool: id = msg_class![env; NSAutoreleasePool new];
-        let delegate: id = msg![env; ui_application delegate];
-        if env
-            .objc
-            .object_has_method_named(&env.mem, delegate, "applicationDidBecomeActive:")
-        {
-            () = msg![env; delegate applicationDidBecomeActive:ui_application];
-        }
-        let _: () = msg![env; pool drain];
-    }
+    become_active(env);
 
     // FIXME: There are more messages we should send.
     // TODO: Send UIApplicationDidFinishLaunchingNotification?
@@ -270,37 +346,133 @@ pub(super) fn UIApplicationMain(
     let _: () = msg![env; run_loop run];
 }
 
+/// Send a lifecycle message (e.g. `applicationWillResignActive:`) to the app
+/// delegate, if it implements it, and then post the matching notification.
+fn send_lifecycle_event(env: &mut Environment, selector: &str, notification: &'static str) {
+    let pool: id = msg_class![env; NSAutoreleasePool new];
+    let ui_application: id = msg_class![env; UIApplication sharedApplication];
+    let delegate: id = msg![env; ui_application delegate];
+    if env
+        .objc
+        .object_has_method_named(&env.mem, delegate, selector)
+    {
+        let sel = env.objc.lookup_selector(selector).unwrap();
+        let _: () = msg_send(env, (delegate, sel, ui_application));
+    }
+
+    let name = ns_string::get_static_str(env, notification);
+    let center: id = msg_class![env; NSNotificationCenter defaultCenter];
+    let _: () = msg![env; center postNotificationName:name object:ui_application];
+    let _: () = msg![env; pool drain];
+}
+
+fn become_active(env: &mut Environment) {
+    send_lifecycle_event(
+        env,
+        "applicationDidBecomeActive:",
+        UIApplicationDidBecomeActiveNotification,
+    );
+    env.framework_state.uikit.ui_application.is_active = true;
+}
+
+fn resign_active(env: &mut Environment) {
+    if !env.framework_state.uikit.ui_application.is_active {
+        return;
+    }
+    env.framework_state.uikit.ui_application.is_active = false;
+    send_lifecycle_event(
+        env,
+        "applicationWillResignActive:",
+        UIApplicationWillResignActiveNotification,
+    );
+}
+
 /// Tell the app it's about to quit and then exit.
 pub(super) fn exit(env: &mut Environment) {
+    resign_active(env);
+    send_lifecycle_event(
+        env,
+        "applicationWillTerminate:",
+        UIApplicationWillTerminateNotification,
+    );
+
+    if let Some(report) = crate::objc::retain_audit_report(env) {
+        echo!("{}", report.trim_end());
+    }
+    std::process::exit(0);
+}
+
+/// Block until the user presses the key for the button matching `button`,
+/// while the app is inactive. The app can't run during this time, which is
+/// similar to it being suspended.
+fn wait_for_button(env: &mut Environment, button: fn(&Event) -> bool) {
+    loop {
+        env.window.as_mut().unwrap().poll_for_events(&env.options);
+        while let Some(event) = env.window.as_mut().unwrap().pop_event() {
+            match event {
+                Event::Quit | Event::AppWillResignActive | Event::AppWillTerminate => {
+                    echo!("User requested quit while app was inactive, exiting.");
+                    exit(env);
+                }
+                _ if button(&event) => return,
+                // Touches etc don't reach an inactive app.
+                _ => (),
+            }
+        }
+        std::thread::sleep(Duration::from_millis(1000 / 60));
+    }
+}
+
+/// Simulate the Home button being pressed: the app is sent to the background,
+/// or terminated if it doesn't support that, and then returned to the
+/// foreground once the button is pressed again.
+pub(super) fn handle_home_button(env: &mut Environment) {
+    resign_active(env);
+
+    // Apps from before iPhone OS 4 don't know about backgrounding, and on
+    // those old OS versions pressing the Home button always terminated the
+    // app. There's no perfect way to tell these apps apart, but an app
+    // that handles entering the background surely supports it.
     let ui_application: id = msg_class![env; UIApplication sharedApplication];
+    let delegate: id = msg![env; ui_application delegate];
+    let supports_background =
+        env.objc
+            .object_has_method_named(&env.mem, delegate, "applicationDidEnterBackground:")
+            && !env.bundle.exits_on_suspend();
+    if !supports_background || env.options.home_button_exits {
+        echo!("Home button pressed, exiting.");
+        exit(env);
+    }
 
-    // TODO: send notifications also
+    send_lifecycle_event(
+        env,
+        "applicationDidEnterBackground:",
+        UIApplicationDidEnterBackgroundNotification,
+    );
+    env.window.as_mut().unwrap().present_blank_frame();
+    echo!("Home button pressed, app is in the background. Press F10 to return to the app.");
+    wait_for_button(env, |event| matches!(event, Event::HomeButton));
+
+    send_lifecycle_event(
+        env,
+        "applicationWillEnterForeground:",
+        UIApplicationWillEnterForegroundNotification,
+    );
+    become_active(env);
+    log!("App returned to the foreground.");
+}
 
-    {
-        let pool: id = msg_class![env; NSAutoreleasePool new];
-        let delegate: id = msg![env; ui_application delegate];
-        if env
-            .objc
-            .object_has_method_named(&env.mem, delegate, "applicationWillResignActive:")
-        {
-            () = msg![env; delegate applicationWillResignActive:ui_application];
-        }
-        let _: () = msg![env; pool drain];
-    };
+/// Simulate the Sleep/Wake button being pressed: the app becomes inactive and
+/// the screen is turned off until the button is pressed again.
+pub(super) fn handle_lock_button(env: &mut Environment) {
+    resign_active(env);
 
-    {
-        let pool: id = msg_class![env; NSAutoreleasePool new];
-        let delegate: id = msg![env; ui_application delegate];
-        if env
-            .objc
-            .object_has_method_named(&env.mem, delegate, "applicationWillTerminate:")
-        {
-            () = msg![env; delegate applicationWillTerminate:ui_application];
-        }
-        let _: () = msg![env; pool drain];
-    };
+    env.window.as_mut().unwrap().present_blank_frame();
+    echo!("Lock button pressed, device is locked. Press F11 to unlock it.");
+    wait_for_button(env, |event| matches!(event, Event::LockButton));
 
-    std::process::exit(0);
+    become_active(env);
+    log!("Device unlocked.");
 }
 
 pub const FUNCTIONS: FunctionExports = &[export_c_func!(UIApplicationMain(_, _, _, _))];
@@ -4,11 +4,19 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `UIColor`.
+//!
+//! In this implementation, `CGColorRef` is the same type as `UIColor*`, so the
+//! `CGColor*` functions in [crate::frameworks::core_graphics::cg_color] are
+//! built on top of the helpers here.
 
+use super::ui_graphics::UIGraphicsGetCurrentContext;
+use crate::frameworks::core_graphics::cg_context::CGContextSetFillColorWithColor;
+use crate::frameworks::core_graphics::cg_image::CGImageRef;
 use crate::frameworks::core_graphics::CGFloat;
-use crate::mem::MutPtr;
+use crate::mem::{guest_size_of, ConstPtr, MutPtr};
 use crate::objc::{
-    autorelease, id, msg, msg_class, objc_classes, ClassExports, HostObject, NSZonePtr, ObjC, SEL,
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr, ObjC, SEL,
 };
 use crate::Environment;
 use std::collections::HashMap;
@@ -42,9 +50,56 @@ fn get_standard_color(
 
 struct UIColorHostObject {
     rgba: (CGFloat, CGFloat, CGFloat, CGFloat),
+    /// For pattern colors, the image to tile (strong reference).
+    pattern: Option<CGImageRef>,
+    /// Guest copy of the components, allocated on demand for
+    /// `CGColorGetComponents`.
+    components: Option<MutPtr<CGFloat>>,
 }
 impl HostObject for UIColorHostObject {}
 
+/// Convert a color in the HSB (aka HSV) model to RGB. All values are in the
+/// range 0 to 1.
+fn hsb_to_rgb(
+    hue: CGFloat,
+    saturation: CGFloat,
+    brightness: CGFloat,
+) -> (CGFloat, CGFloat, CGFloat) {
+    let hue = hue.rem_euclid(1.0) * 6.0;
+    let sector = hue.floor();
+    let f = hue - sector;
+    let v = brightness;
+    let p = v * (1.0 - saturation);
+    let q = v * (1.0 - saturation * f);
+    let t = v * (1.0 - saturation * (1.0 - f));
+    match sector as u32 {
+        0 => (v, t, p),
+        1 => (q, v, p),
+        2 => (p, v, t),
+        3 => (p, q, v),
+        4 => (t, p, v),
+        _ => (v, p, q),
+    }
+}
+
+/// Inverse of [hsb_to_rgb].
+fn rgb_to_hsb(r: CGFloat, g: CGFloat, b: CGFloat) -> (CGFloat, CGFloat, CGFloat) {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let saturation = if max == 0.0 { 0.0 } else { delta / max };
+    let hue = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        (g - b) / delta
+    } else if max == g {
+        (b - r) / delta + 2.0
+    } else {
+        (r - g) / delta + 4.0
+    };
+    ((hue / 6.0).rem_euclid(1.0), saturation, max)
+}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -54,6 +109,8 @@ pub const CLASSES: ClassExports = objc_classes! {
 + (id)allocWithZone:(NSZonePtr)_zone {
     let host_object = Box::new(UIColorHostObject {
         rgba: (0.0, 0.0, 0.0, 0.0),
+        pattern: None,
+        components: None,
     });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
@@ -73,6 +130,27 @@ pub const CLASSES: ClassExports = objc_classes! {
     autorelease(env, new)
 }
 
++ (id)colorWithHue:(CGFloat)h
+        saturation:(CGFloat)s
+        brightness:(CGFloat)b
+             alpha:(CGFloat)a {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithHue:h saturation:s brightness:b alpha:a];
+    autorelease(env, new)
+}
+
++ (id)colorWithPatternImage:(id)image { // UIImage*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithPatternImage:image];
+    autorelease(env, new)
+}
+
++ (id)colorWithCGColor:(id)cg_color { // CGColorRef
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithCGColor:cg_color];
+    autorelease(env, new)
+}
+
 + (id)clearColor    { get_standard_color(env, _cmd, 0.0, 0.0, 0.0, 0.0) }
 + (id)blackColor    { get_standard_color(env, _cmd, 0.0, 0.0, 0.0, 1.0) }
 + (id)whiteColor    { get_standard_color(env, _cmd, 1.0, 1.0, 1.0, 1.0) }
@@ -91,11 +169,33 @@ pub const CLASSES: ClassExports = objc_classes! {
 + (id)greenColor    { get_standard_color(env, _cmd, 0.0, 1.0, 0.0, 1.0) }
 + (id)magentaColor  { get_standard_color(env, _cmd, 1.0, 0.0, 1.0, 1.0) }
 + (id)orangeColor   { get_standard_color(env, _cmd, 1.0, 0.5, 0.0, 1.0) }
-+ (id)purpleColor   { get_standard_color(env, _cmd, 0.5, 0.0, 1.5, 1.0) }
++ (id)purpleColor   { get_standard_color(env, _cmd, 0.5, 0.0, 0.5, 1.0) }
 + (id)redColor      { get_standard_color(env, _cmd, 1.0, 0.0, 0.0, 1.0) }
 + (id)yellowColor   { get_standard_color(env, _cmd, 1.0, 1.0, 0.0, 1.0) }
++ (id)lightTextColor { get_standard_color(env, _cmd, 1.0, 1.0, 1.0, 0.6) }
++ (id)darkTextColor { get_standard_color(env, _cmd, 0.0, 0.0, 0.0, 1.0) }
+// These are patterns on the real system. They're approximated here with the
+// average color of each pattern.
++ (id)groupTableViewBackgroundColor {
+    get_standard_color(env, _cmd, 0.773, 0.8, 0.831, 1.0)
+}
++ (id)viewFlipsideBackgroundColor {
+    get_standard_color(env, _cmd, 0.122, 0.129, 0.141, 1.0)
+}
++ (id)scrollViewTexturedBackgroundColor {
+    get_standard_color(env, _cmd, 0.435, 0.443, 0.475, 1.0)
+}
 
-// TODO: more initializers, set methods, more accessors
+- (())dealloc {
+    let &UIColorHostObject { pattern, components, .. } = env.objc.borrow(this);
+    if let Some(pattern) = pattern {
+        release(env, pattern);
+    }
+    if let Some(components) = components {
+        env.mem.free(components.cast());
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
 
 - (id)initWithWhite:(CGFloat)w alpha:(CGFloat)a {
     let w = w.clamp(0.0, 1.0);
@@ -114,24 +214,194 @@ pub const CLASSES: ClassExports = objc_classes! {
     this
 }
 
+- (id)initWithHue:(CGFloat)h
+       saturation:(CGFloat)s
+       brightness:(CGFloat)b
+            alpha:(CGFloat)a {
+    let (r, g, b) = hsb_to_rgb(h, s.clamp(0.0, 1.0), b.clamp(0.0, 1.0));
+    env.objc.borrow_mut::<UIColorHostObject>(this).rgba = (r, g, b, a.clamp(0.0, 1.0));
+    this
+}
+
+- (id)initWithPatternImage:(id)image { // UIImage*
+    let cg_image: CGImageRef = if image == nil {
+        nil
+    } else {
+        msg![env; image CGImage]
+    };
+    if cg_image == nil {
+        log!("Warning: [{:?} initWithPatternImage:{:?}] has no image", this, image);
+    } else {
+        retain(env, cg_image);
+    }
+    let host_obj = env.objc.borrow_mut::<UIColorHostObject>(this);
+    host_obj.rgba = (0.0, 0.0, 0.0, 1.0);
+    host_obj.pattern = (cg_image != nil).then_some(cg_image);
+    this
+}
+
+- (id)initWithCGColor:(id)cg_color { // CGColorRef
+    let &UIColorHostObject { rgba, pattern, .. } = env.objc.borrow(cg_color);
+    if let Some(pattern) = pattern {
+        retain(env, pattern);
+    }
+    let host_obj = env.objc.borrow_mut::<UIColorHostObject>(this);
+    host_obj.rgba = rgba;
+    host_obj.pattern = pattern;
+    this
+}
+
+- (id)CGColor { // CGColorRef
+    this
+}
+
+- (id)colorWithAlphaComponent:(CGFloat)a {
+    let &UIColorHostObject { rgba, pattern, .. } = env.objc.borrow(this);
+    let new: id = msg_class![env; UIColor alloc];
+    if let Some(pattern) = pattern {
+        retain(env, pattern);
+    }
+    let host_obj = env.objc.borrow_mut::<UIColorHostObject>(new);
+    host_obj.rgba = (rgba.0, rgba.1, rgba.2, a.clamp(0.0, 1.0));
+    host_obj.pattern = pattern;
+    autorelease(env, new)
+}
+
+// The getters return false for pattern colors, which have no components.
+
 - (bool)getRed:(MutPtr<CGFloat>)r
          green:(MutPtr<CGFloat>)g
           blue:(MutPtr<CGFloat>)b
          alpha:(MutPtr<CGFloat>)a {
-    let (r_, g_, b_, a_) = env.objc.borrow::<UIColorHostObject>(this).rgba;
-    env.mem.write(r, r_);
-    env.mem.write(g, g_);
-    env.mem.write(b, b_);
-    env.mem.write(a, a_);
+    let &UIColorHostObject { rgba: (r_, g_, b_, a_), pattern, .. } = env.objc.borrow(this);
+    if pattern.is_some() {
+        return false;
+    }
+    write_if_not_null(env, r, r_);
+    write_if_not_null(env, g, g_);
+    write_if_not_null(env, b, b_);
+    write_if_not_null(env, a, a_);
+    true
+}
+
+- (bool)getHue:(MutPtr<CGFloat>)h
+    saturation:(MutPtr<CGFloat>)s
+    brightness:(MutPtr<CGFloat>)b
+         alpha:(MutPtr<CGFloat>)a {
+    let &UIColorHostObject { rgba: (r_, g_, b_, a_), pattern, .. } = env.objc.borrow(this);
+    if pattern.is_some() {
+        return false;
+    }
+    let (h_, s_, br_) = rgb_to_hsb(r_, g_, b_);
+    write_if_not_null(env, h, h_);
+    write_if_not_null(env, s, s_);
+    write_if_not_null(env, b, br_);
+    write_if_not_null(env, a, a_);
+    true
+}
+
+- (bool)getWhite:(MutPtr<CGFloat>)w
+           alpha:(MutPtr<CGFloat>)a {
+    let &UIColorHostObject { rgba: (r_, g_, b_, a_), pattern, .. } = env.objc.borrow(this);
+    // Only grayscale colors can be converted.
+    if pattern.is_some() || r_ != g_ || g_ != b_ {
+        return false;
+    }
+    write_if_not_null(env, w, r_);
+    write_if_not_null(env, a, a_);
     true
 }
 
+- (())set {
+    () = msg![env; this setFill];
+    () = msg![env; this setStroke];
+}
+- (())setFill {
+    let context = UIGraphicsGetCurrentContext(env);
+    if context.is_null() {
+        return;
+    }
+    CGContextSetFillColorWithColor(env, context, this);
+}
+- (())setStroke {
+    // TODO: Core Graphics has no stroking support yet
+    log_dbg!("TODO: [{:?} setStroke]", this);
+}
+
 @end
 
 };
 
+fn write_if_not_null(env: &mut Environment, ptr: MutPtr<CGFloat>, value: CGFloat) {
+    if !ptr.is_null() {
+        env.mem.write(ptr, value);
+    }
+}
+
 /// Shortcut for use in Core Animation's compositor: get the RGBA triple for a
 /// `UIColor*`.
 pub fn get_rgba(objc: &ObjC, ui_color: id) -> (CGFloat, CGFloat, CGFloat, CGFloat) {
-    objc.borrow::<UIColorHostObject>(ui_color).rgba
+    let host_obj = objc.borrow::<UIColorHostObject>(ui_color);
+    if host_obj.pattern.is_some() {
+        // TODO: draw pattern colors in the compositor. For now, they're
+        // transparent rather than an arbitrary solid color.
+        return (0.0, 0.0, 0.0, 0.0);
+    }
+    host_obj.rgba
+}
+
+/// For use by `CGColorGetAlpha`. Unlike [get_rgba], this is meaningful for
+/// pattern colors.
+pub fn get_alpha(objc: &ObjC, ui_color: id) -> CGFloat {
+    objc.borrow::<UIColorHostObject>(ui_color).rgba.3
+}
+
+/// For use by Core Graphics: get the image a pattern color tiles, if it is
+/// one.
+pub fn get_pattern(objc: &ObjC, ui_color: id) -> Option<CGImageRef> {
+    objc.borrow::<UIColorHostObject>(ui_color).pattern
+}
+
+/// For use by Core Graphics: create a new (retained) color.
+pub fn new_with_rgba(env: &mut Environment, rgba: (CGFloat, CGFloat, CGFloat, CGFloat)) -> id {
+    let (r, g, b, a) = rgba;
+    let new: id = msg_class![env; UIColor alloc];
+    msg![env; new initWithRed:r green:g blue:b alpha:a]
+}
+
+/// For use by `CGColorGetComponents`: get a pointer to the RGBA components
+/// in guest memory, which lives as long as the color.
+pub fn get_components_ptr(env: &mut Environment, ui_color: id) -> ConstPtr<CGFloat> {
+    let &UIColorHostObject {
+        rgba: (r, g, b, a),
+        components,
+        ..
+    } = env.objc.borrow(ui_color);
+    let components = components.unwrap_or_else(|| {
+        let ptr: MutPtr<CGFloat> = env.mem.alloc(guest_size_of::<CGFloat>() * 4).cast();
+        env.objc
+            .borrow_mut::<UIColorHostObject>(ui_color)
+            .components = Some(ptr);
+        ptr
+    });
+    for (i, value) in [r, g, b, a].into_iter().enumerate() {
+        env.mem.write(components + i as u32, value);
+    }
+    components.cast_const()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hsb() {
+        assert_eq!(hsb_to_rgb(0.0, 1.0, 1.0), (1.0, 0.0, 0.0));
+        assert_eq!(hsb_to_rgb(0.5, 1.0, 1.0), (0.0, 1.0, 1.0));
+        assert_eq!(hsb_to_rgb(0.25, 0.0, 0.5), (0.5, 0.5, 0.5));
+        assert_eq!(rgb_to_hsb(0.0, 0.0, 1.0), (4.0 / 6.0, 1.0, 1.0));
+        let (h, s, b) = rgb_to_hsb(0.2, 0.6, 0.4);
+        let (r, g, b) = hsb_to_rgb(h, s, b);
+        assert!((r - 0.2).abs() < 1e-5 && (g - 0.6).abs() < 1e-5 && (b - 0.4).abs() < 1e-5);
+    }
 }
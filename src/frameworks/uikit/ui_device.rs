@@ -4,16 +4,25 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `UIDevice`.
+//!
+//! The battery is simulated according to the `--battery=` and
+//! `--battery-drain=` options.
 
 use crate::dyld::ConstantExports;
 use crate::dyld::HostConstant;
 use crate::frameworks::foundation::ns_string;
 use crate::frameworks::foundation::NSInteger;
-use crate::objc::{id, objc_classes, ClassExports, TrivialHostObject};
+use crate::objc::{id, msg, msg_class, objc_classes, ClassExports, TrivialHostObject};
 use crate::window::DeviceOrientation;
+use crate::Environment;
+use std::time::Instant;
 
 pub const UIDeviceOrientationDidChangeNotification: &str =
     "UIDeviceOrientationDidChangeNotification";
+pub const UIDeviceBatteryLevelDidChangeNotification: &str =
+    "UIDeviceBatteryLevelDidChangeNotification";
+pub const UIDeviceBatteryStateDidChangeNotification: &str =
+    "UIDeviceBatteryStateDidChangeNotification";
 
 pub type UIDeviceOrientation = NSInteger;
 #[allow(dead_code)]
@@ -28,15 +37,131 @@ pub const UIDeviceOrientationFaceUp: UIDeviceOrientation = 5;
 #[allow(dead_code)]
 pub const UIDeviceOrientationFaceDown: UIDeviceOrientation = 6;
 
+type UIDeviceBatteryState = NSInteger;
+const UIDeviceBatteryStateUnknown: UIDeviceBatteryState = 0;
+const UIDeviceBatteryStateUnplugged: UIDeviceBatteryState = 1;
+const UIDeviceBatteryStateCharging: UIDeviceBatteryState = 2;
+const UIDeviceBatteryStateFull: UIDeviceBatteryState = 3;
+
+/// Real devices report the battery level in steps of 5%.
+const BATTERY_LEVEL_STEP: f32 = 0.05;
+
 #[derive(Default)]
 pub struct State {
     current_device: Option<id>,
+    battery_monitoring_enabled: bool,
+    /// Created on first use, since it depends on the options.
+    battery: Option<Battery>,
 }
 
-pub const CONSTANTS: ConstantExports = &[(
-    "_UIDeviceOrientationDidChangeNotification",
-    HostConstant::NSString(UIDeviceOrientationDidChangeNotification),
-)];
+struct Battery {
+    /// Exact level, which changes continuously if `--battery-drain=` is used.
+    level: f32,
+    state: UIDeviceBatteryState,
+    last_update: Instant,
+    /// The level and state last reported to the app, used to decide when to
+    /// send notifications.
+    reported: (f32, UIDeviceBatteryState),
+}
+impl Battery {
+    fn reported_level(&self) -> f32 {
+        (self.level / BATTERY_LEVEL_STEP).round() * BATTERY_LEVEL_STEP
+    }
+}
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_UIDeviceOrientationDidChangeNotification",
+        HostConstant::NSString(UIDeviceOrientationDidChangeNotification),
+    ),
+    (
+        "_UIDeviceBatteryLevelDidChangeNotification",
+        HostConstant::NSString(UIDeviceBatteryLevelDidChangeNotification),
+    ),
+    (
+        "_UIDeviceBatteryStateDidChangeNotification",
+        HostConstant::NSString(UIDeviceBatteryStateDidChangeNotification),
+    ),
+];
+
+/// Bring the simulated battery up to date and get it.
+fn battery(env: &mut Environment) -> &mut Battery {
+    let now = Instant::now();
+    let (initial_level, charging) = env.options.battery.unwrap_or((1.0, true));
+    let drain = env.options.battery_drain;
+    let battery = env
+        .framework_state
+        .uikit
+        .ui_device
+        .battery
+        .get_or_insert_with(|| {
+            let state = match (charging, initial_level >= 1.0) {
+                (true, true) => UIDeviceBatteryStateFull,
+                (true, false) => UIDeviceBatteryStateCharging,
+                (false, _) => UIDeviceBatteryStateUnplugged,
+            };
+            let mut battery = Battery {
+                level: initial_level,
+                state,
+                last_update: now,
+                reported: (0.0, state),
+            };
+            battery.reported.0 = battery.reported_level();
+            battery
+        });
+
+    let minutes = now.duration_since(battery.last_update).as_secs_f32() / 60.0;
+    battery.last_update = now;
+    match battery.state {
+        UIDeviceBatteryStateUnplugged => {
+            battery.level = (battery.level - drain * minutes).max(0.0);
+        }
+        UIDeviceBatteryStateCharging => {
+            battery.level = (battery.level + drain * minutes).min(1.0);
+            if battery.level >= 1.0 {
+                battery.state = UIDeviceBatteryStateFull;
+            }
+        }
+        _ => (),
+    }
+    battery
+}
+
+/// For use by `NSRunLoop` via [super::handle_events]: send notifications if
+/// the simulated battery's level or state has changed.
+pub fn handle_battery(env: &mut Environment) {
+    if !env
+        .framework_state
+        .uikit
+        .ui_device
+        .battery_monitoring_enabled
+    {
+        return;
+    }
+    let battery = battery(env);
+    let level = battery.reported_level();
+    let state = battery.state;
+    let (old_level, old_state) = std::mem::replace(&mut battery.reported, (level, state));
+
+    let mut notifications = Vec::new();
+    if state != old_state {
+        log_dbg!("Battery state changed from {} to {}", old_state, state);
+        notifications.push(UIDeviceBatteryStateDidChangeNotification);
+    }
+    if level != old_level {
+        log_dbg!("Battery level changed from {} to {}", old_level, level);
+        notifications.push(UIDeviceBatteryLevelDidChangeNotification);
+    }
+    if notifications.is_empty() {
+        return;
+    }
+    let device: id = msg_class![env; UIDevice currentDevice];
+    let center: id = msg_class![env; NSNotificationCenter defaultCenter];
+    for notification in notifications {
+        let name = ns_string::get_static_str(env, notification);
+        let _: () = msg![env; center postNotificationName:name object:device];
+    }
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -84,6 +209,32 @@ pub const CLASSES: ClassExports = objc_classes! {
     false
 }
 
+- (bool)isBatteryMonitoringEnabled {
+    env.framework_state.uikit.ui_device.battery_monitoring_enabled
+}
+- (())setBatteryMonitoringEnabled:(bool)enabled {
+    env.framework_state.uikit.ui_device.battery_monitoring_enabled = enabled;
+    if enabled {
+        // Changes from before monitoring was enabled aren't notified.
+        let battery = battery(env);
+        battery.reported = (battery.reported_level(), battery.state);
+    }
+}
+
+- (f32)batteryLevel {
+    if !env.framework_state.uikit.ui_device.battery_monitoring_enabled {
+        return -1.0;
+    }
+    battery(env).reported_level()
+}
+
+- (UIDeviceBatteryState)batteryState {
+    if !env.framework_state.uikit.ui_device.battery_monitoring_enabled {
+        return UIDeviceBatteryStateUnknown;
+    }
+    battery(env).state
+}
+
 - (UIDeviceOrientation)orientation {
     match env.window().current_rotation() {
         DeviceOrientation::Portrait => UIDeviceOrientationPortrait,
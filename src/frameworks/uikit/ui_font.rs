@@ -6,11 +6,11 @@
 //! `UIFont`.
 
 use super::ui_graphics::UIGraphicsGetCurrentContext;
-use crate::font::{Font, TextAlignment, WrapMode};
+use crate::font::{Font, FontMetrics, TextAlignment, WrapMode};
 use crate::frameworks::core_graphics::cg_bitmap_context::CGBitmapContextDrawer;
 use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
-use crate::frameworks::foundation::NSInteger;
-use crate::objc::{autorelease, id, objc_classes, ClassExports, HostObject};
+use crate::frameworks::foundation::{ns_string, NSInteger};
+use crate::objc::{autorelease, id, msg, objc_classes, Class, ClassExports, HostObject};
 use crate::Environment;
 use std::ops::Range;
 
@@ -30,12 +30,102 @@ enum FontKind {
     Italic,
 }
 
+/// Information about a font that was available on iPhone OS.
+struct FontInfo {
+    /// PostScript name, as used by `fontWithName:size:`.
+    name: &'static str,
+    family_name: &'static str,
+    /// Which of touchHLE's bundled fonts to substitute it with.
+    kind: FontKind,
+    /// The original font's metrics, so that text is laid out like it would be
+    /// on iPhone OS even though the glyphs are different. All of these are
+    /// fractions of the point size.
+    metrics: FontMetrics,
+    cap_height: CGFloat,
+    x_height: CGFloat,
+}
+
+const fn font_info(
+    name: &'static str,
+    family_name: &'static str,
+    kind: FontKind,
+    (ascent, descent, cap_height, x_height): (f32, f32, f32, f32),
+) -> FontInfo {
+    FontInfo {
+        name,
+        family_name,
+        kind,
+        metrics: FontMetrics { ascent, descent },
+        cap_height,
+        x_height,
+    }
+}
+
+/// Metrics of the original fonts: ascender, descender, cap height, x-height.
+const HELVETICA_METRICS: (f32, f32, f32, f32) = (0.9625, -0.2875, 0.717, 0.523);
+const ARIAL_METRICS: (f32, f32, f32, f32) = (0.905, -0.212, 0.716, 0.519);
+const COURIER_NEW_METRICS: (f32, f32, f32, f32) = (0.833, -0.300, 0.571, 0.423);
+const GEORGIA_METRICS: (f32, f32, f32, f32) = (0.917, -0.219, 0.692, 0.481);
+// TODO: These are rough estimates, not verified against a real device.
+const MARKER_FELT_METRICS: (f32, f32, f32, f32) = (0.870, -0.225, 0.680, 0.460);
+const TIMES_METRICS: (f32, f32, f32, f32) = (0.891, -0.216, 0.662, 0.448);
+const TREBUCHET_MS_METRICS: (f32, f32, f32, f32) = (0.939, -0.222, 0.715, 0.523);
+const VERDANA_METRICS: (f32, f32, f32, f32) = (1.005, -0.210, 0.727, 0.545);
+
+/// Fonts known to `fontWithName:size:`. The first entry is the system font.
+/// There are no serif or monospace fonts bundled with touchHLE, so the
+/// substitutes are not always very close, but at least the metrics match.
+#[rustfmt::skip]
+const FONTS: &[FontInfo] = &[
+    font_info("Helvetica", "Helvetica", FontKind::Regular, HELVETICA_METRICS),
+    font_info("Helvetica-Bold", "Helvetica", FontKind::Bold, HELVETICA_METRICS),
+    font_info("Helvetica-Oblique", "Helvetica", FontKind::Italic, HELVETICA_METRICS),
+    font_info("Helvetica-BoldOblique", "Helvetica", FontKind::Bold, HELVETICA_METRICS),
+    font_info("ArialMT", "Arial", FontKind::Regular, ARIAL_METRICS),
+    font_info("Arial-BoldMT", "Arial", FontKind::Bold, ARIAL_METRICS),
+    font_info("Arial-ItalicMT", "Arial", FontKind::Italic, ARIAL_METRICS),
+    font_info("Arial-BoldItalicMT", "Arial", FontKind::Bold, ARIAL_METRICS),
+    font_info("CourierNewPSMT", "Courier New", FontKind::Regular, COURIER_NEW_METRICS),
+    font_info("CourierNewPS-BoldMT", "Courier New", FontKind::Bold, COURIER_NEW_METRICS),
+    font_info("Georgia", "Georgia", FontKind::Regular, GEORGIA_METRICS),
+    font_info("Georgia-Bold", "Georgia", FontKind::Bold, GEORGIA_METRICS),
+    font_info("MarkerFelt-Thin", "Marker Felt", FontKind::Regular, MARKER_FELT_METRICS),
+    font_info("MarkerFelt-Wide", "Marker Felt", FontKind::Bold, MARKER_FELT_METRICS),
+    font_info("TimesNewRomanPSMT", "Times New Roman", FontKind::Regular, TIMES_METRICS),
+    font_info("TimesNewRomanPS-BoldMT", "Times New Roman", FontKind::Bold, TIMES_METRICS),
+    font_info("TimesNewRomanPS-ItalicMT", "Times New Roman", FontKind::Italic, TIMES_METRICS),
+    font_info("TrebuchetMS", "Trebuchet MS", FontKind::Regular, TREBUCHET_MS_METRICS),
+    font_info("TrebuchetMS-Bold", "Trebuchet MS", FontKind::Bold, TREBUCHET_MS_METRICS),
+    font_info("Verdana", "Verdana", FontKind::Regular, VERDANA_METRICS),
+    font_info("Verdana-Bold", "Verdana", FontKind::Bold, VERDANA_METRICS),
+];
+
+fn find_font_info(name: &str) -> Option<&'static FontInfo> {
+    FONTS.iter().find(|info| info.name == name)
+}
+
 struct UIFontHostObject {
     size: CGFloat,
-    kind: FontKind,
+    info: &'static FontInfo,
 }
 impl HostObject for UIFontHostObject {}
 
+/// Shared implementation of the `UIFont` constructors.
+fn new_font(env: &mut Environment, class: Class, info: &'static FontInfo, size: CGFloat) -> id {
+    // Cache for later use
+    let state = &mut env.framework_state.uikit.ui_font;
+    match info.kind {
+        FontKind::Regular => state.regular.get_or_insert_with(Font::sans_regular),
+        FontKind::Bold => state.bold.get_or_insert_with(Font::sans_bold),
+        FontKind::Italic => state.italic.get_or_insert_with(Font::sans_italic),
+    };
+    let host_object = UIFontHostObject { size, info };
+    let new = env
+        .objc
+        .alloc_object(class, Box::new(host_object), &mut env.mem);
+    autorelease(env, new)
+}
+
 /// Line break mode.
 ///
 /// This is put here for convenience since it's font-related.
@@ -67,40 +157,61 @@ pub const CLASSES: ClassExports = objc_classes! {
 @implementation UIFont: NSObject
 
 + (id)systemFontOfSize:(CGFloat)size {
-    // Cache for later use
-    if env.framework_state.uikit.ui_font.regular.is_none() {
-        env.framework_state.uikit.ui_font.regular = Some(Font::sans_regular());
-    }
-    let host_object = UIFontHostObject {
-        size,
-        kind: FontKind::Regular,
-    };
-    let new = env.objc.alloc_object(this, Box::new(host_object), &mut env.mem);
-    autorelease(env, new)
+    new_font(env, this, find_font_info("Helvetica").unwrap(), size)
 }
 + (id)boldSystemFontOfSize:(CGFloat)size {
-    // Cache for later use
-    if env.framework_state.uikit.ui_font.bold.is_none() {
-        env.framework_state.uikit.ui_font.bold = Some(Font::sans_bold());
-    }
-    let host_object = UIFontHostObject {
-        size,
-        kind: FontKind::Bold,
-    };
-    let new = env.objc.alloc_object(this, Box::new(host_object), &mut env.mem);
-    autorelease(env, new)
+    new_font(env, this, find_font_info("Helvetica-Bold").unwrap(), size)
 }
 + (id)italicSystemFontOfSize:(CGFloat)size {
-    // Cache for later use
-    if env.framework_state.uikit.ui_font.italic.is_none() {
-        env.framework_state.uikit.ui_font.italic = Some(Font::sans_italic());
-    }
-    let host_object = UIFontHostObject {
-        size,
-        kind: FontKind::Italic,
-    };
-    let new = env.objc.alloc_object(this, Box::new(host_object), &mut env.mem);
-    autorelease(env, new)
+    new_font(env, this, find_font_info("Helvetica-Oblique").unwrap(), size)
+}
++ (id)fontWithName:(id)name // NSString*
+              size:(CGFloat)size {
+    let name = ns_string::to_rust_string(env, name);
+    let info = find_font_info(&name).unwrap_or_else(|| {
+        log!("TODO: font {:?} is unknown, using the system font instead", name);
+        &FONTS[0]
+    });
+    new_font(env, this, info, size)
+}
+
+- (id)fontWithSize:(CGFloat)size {
+    let info = env.objc.borrow::<UIFontHostObject>(this).info;
+    let class: Class = msg![env; this class];
+    new_font(env, class, info, size)
+}
+
+- (id)fontName {
+    let name = env.objc.borrow::<UIFontHostObject>(this).info.name;
+    ns_string::get_static_str(env, name)
+}
+- (id)familyName {
+    let family_name = env.objc.borrow::<UIFontHostObject>(this).info.family_name;
+    ns_string::get_static_str(env, family_name)
+}
+- (CGFloat)pointSize {
+    env.objc.borrow::<UIFontHostObject>(this).size
+}
+- (CGFloat)ascender {
+    let &UIFontHostObject { size, info } = env.objc.borrow(this);
+    (info.metrics.ascent * size).round()
+}
+- (CGFloat)descender {
+    let &UIFontHostObject { size, info } = env.objc.borrow(this);
+    (info.metrics.descent * size).round()
+}
+- (CGFloat)leading {
+    // On iPhone OS, this is the line height, not the gap between lines.
+    let &UIFontHostObject { size, info } = env.objc.borrow(this);
+    (info.metrics.ascent * size).round() + (-info.metrics.descent * size).round()
+}
+- (CGFloat)capHeight {
+    let &UIFontHostObject { size, info } = env.objc.borrow(this);
+    info.cap_height * size
+}
+- (CGFloat)xHeight {
+    let &UIFontHostObject { size, info } = env.objc.borrow(this);
+    info.x_height * size
 }
 
 @end
@@ -168,13 +279,14 @@ pub fn size_with_font(
 
     let font = get_font(
         &mut env.framework_state.uikit.ui_font,
-        host_object.kind,
+        host_object.info.kind,
         text,
     );
 
     let wrap = constrained.map(|(size, ui_mode)| (size.width, convert_line_break_mode(ui_mode)));
 
-    let (width, height) = font.calculate_text_size(host_object.size, text, wrap);
+    let (width, height) =
+        font.calculate_text_size(host_object.size, Some(host_object.info.metrics), text, wrap);
 
     CGSize { width, height }
 }
@@ -247,21 +359,26 @@ pub fn draw_at_point(
 
     let font = get_font(
         &mut env.framework_state.uikit.ui_font,
-        host_object.kind,
+        host_object.info.kind,
         text,
     );
 
     let width_and_line_break_mode =
         width_and_line_break_mode.map(|(width, ui_mode)| (width, convert_line_break_mode(ui_mode)));
     let clip_x = width_and_line_break_mode.map(|(width, _)| point.x..(point.x + width));
-    let (width, height) =
-        font.calculate_text_size(host_object.size, text, width_and_line_break_mode);
+    let (width, height) = font.calculate_text_size(
+        host_object.size,
+        Some(host_object.info.metrics),
+        text,
+        width_and_line_break_mode,
+    );
 
     let mut drawer = CGBitmapContextDrawer::new(&env.objc, &mut env.mem, context);
     let fill_color = drawer.rgb_fill_color();
 
     font.draw(
         host_object.size,
+        Some(host_object.info.metrics),
         text,
         (point.x, point.y),
         width_and_line_break_mode,
@@ -297,7 +414,7 @@ pub fn draw_in_rect(
 
     let font = get_font(
         &mut env.framework_state.uikit.ui_font,
-        host_object.kind,
+        host_object.info.kind,
         text,
     );
 
@@ -313,6 +430,7 @@ pub fn draw_in_rect(
 
     font.draw(
         host_object.size,
+        Some(host_object.info.metrics),
         text,
         (rect.origin.x + origin_x_offset, rect.origin.y),
         Some((rect.size.width, convert_line_break_mode(line_break_mode))),
@@ -11,17 +11,39 @@ use crate::frameworks::core_graphics::{CGRect, CGSize};
 use crate::frameworks::foundation::{ns_data, ns_string, NSInteger};
 use crate::frameworks::uikit::ui_graphics::UIGraphicsGetCurrentContext;
 use crate::fs::GuestPath;
+use crate::image::exif::{self, ExifOrientation};
 use crate::image::Image;
 use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, release, ClassExports, HostObject,
     NSZonePtr,
 };
+use crate::Environment;
 
 struct UIImageHostObject {
     cg_image: CGImageRef,
+    /// Orientation from the image file's EXIF metadata, if any. The CGImage
+    /// is not rotated, like on a real device.
+    orientation: ExifOrientation,
 }
 impl HostObject for UIImageHostObject {}
 
+type UIImageOrientation = NSInteger;
+const UIImageOrientationUp: UIImageOrientation = 0;
+const UIImageOrientationDown: UIImageOrientation = 1;
+const UIImageOrientationLeft: UIImageOrientation = 2;
+const UIImageOrientationRight: UIImageOrientation = 3;
+const UIImageOrientationUpMirrored: UIImageOrientation = 4;
+const UIImageOrientationDownMirrored: UIImageOrientation = 5;
+const UIImageOrientationLeftMirrored: UIImageOrientation = 6;
+const UIImageOrientationRightMirrored: UIImageOrientation = 7;
+
+fn init_with_image(env: &mut Environment, this: id, image: Image, orientation: ExifOrientation) {
+    let cg_image = cg_image::from_image(env, image);
+    let host_obj = env.objc.borrow_mut::<UIImageHostObject>(this);
+    host_obj.cg_image = cg_image;
+    host_obj.orientation = orientation;
+}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -29,7 +51,10 @@ pub const CLASSES: ClassExports = objc_classes! {
 @implementation UIImage: NSObject
 
 + (id)allocWithZone:(NSZonePtr)_zone {
-    let host_object = Box::new(UIImageHostObject { cg_image: nil });
+    let host_object = Box::new(UIImageHostObject {
+        cg_image: nil,
+        orientation: ExifOrientation::Normal,
+    });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
 
@@ -86,17 +111,16 @@ pub const CLASSES: ClassExports = objc_classes! {
     //       by a functionality gap in touchHLE, not the app actually trying to
     //       load a broken file, so panicking is most useful.
     let image = Image::from_bytes(&bytes).unwrap();
-    let cg_image = cg_image::from_image(env, image);
-    env.objc.borrow_mut::<UIImageHostObject>(this).cg_image = cg_image;
+    let orientation = exif::jpeg_orientation(&bytes).unwrap_or(ExifOrientation::Normal);
+    init_with_image(env, this, image, orientation);
     this
 }
 
 - (id)initWithData:(id)data { // NSData*
     let slice = ns_data::to_rust_slice(env, data);
-    // TODO: refactor common parts
     let image = Image::from_bytes(slice).unwrap();
-    let cg_image = cg_image::from_image(env, image);
-    env.objc.borrow_mut::<UIImageHostObject>(this).cg_image = cg_image;
+    let orientation = exif::jpeg_orientation(slice).unwrap_or(ExifOrientation::Normal);
+    init_with_image(env, this, image, orientation);
     this
 }
 
@@ -107,15 +131,28 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.objc.borrow::<UIImageHostObject>(this).cg_image
 }
 
-// TODO: should have UIImageOrientation type
-- (NSInteger)imageOrientation {
-    // FIXME: load image orientation info from file?
-    0 // UIImageOrientationUp
+- (UIImageOrientation)imageOrientation {
+    match env.objc.borrow::<UIImageHostObject>(this).orientation {
+        ExifOrientation::Normal => UIImageOrientationUp,
+        ExifOrientation::FlipHorizontal => UIImageOrientationUpMirrored,
+        ExifOrientation::Rotate180 => UIImageOrientationDown,
+        ExifOrientation::FlipVertical => UIImageOrientationDownMirrored,
+        ExifOrientation::Transpose => UIImageOrientationLeftMirrored,
+        ExifOrientation::Rotate90Clockwise => UIImageOrientationRight,
+        ExifOrientation::Transverse => UIImageOrientationRightMirrored,
+        ExifOrientation::Rotate90CounterClockwise => UIImageOrientationLeft,
+    }
 }
 
 - (CGSize)size {
-    let image = env.objc.borrow::<UIImageHostObject>(this).cg_image;
-    let (width, height) = cg_image::borrow_image(&env.objc, image).dimensions();
+    let &UIImageHostObject { cg_image, orientation } = env.objc.borrow(this);
+    let (width, height) = cg_image::borrow_image(&env.objc, cg_image).dimensions();
+    // The size takes the orientation into account, unlike the CGImage.
+    let (width, height) = if orientation.swaps_dimensions() {
+        (height, width)
+    } else {
+        (width, height)
+    };
     CGSize {
         width: width as _,
         height: height as _,
@@ -124,8 +161,19 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 - (())drawInRect:(CGRect)rect {
     let context = UIGraphicsGetCurrentContext(env);
-    let image = env.objc.borrow::<UIImageHostObject>(this).cg_image;
-    CGContextDrawImage(env, context, rect, image);
+    let &UIImageHostObject { cg_image, orientation } = env.objc.borrow(this);
+    if orientation == ExifOrientation::Normal {
+        CGContextDrawImage(env, context, rect, cg_image);
+    } else {
+        // Drawing takes the orientation into account, unlike the CGImage.
+        // This makes a rotated copy every time, but hopefully apps don't draw
+        // such images often.
+        let rotated = cg_image::borrow_image(&env.objc, cg_image)
+            .apply_exif_orientation(orientation);
+        let rotated = cg_image::from_image(env, rotated);
+        CGContextDrawImage(env, context, rect, rotated);
+        CGImageRelease(env, rotated);
+    }
 }
 
 @end
@@ -4,11 +4,45 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `UIImagePickerController`
+//!
+//! No sources can actually be used, but the camera is reported as available
+//! according to the `--camera=` option, so that apps which check for it take
+//! the same path as they would on a device with that hardware.
 
-use crate::frameworks::foundation::NSInteger;
-use crate::objc::{objc_classes, ClassExports};
+use crate::frameworks::foundation::{ns_array, ns_string, NSInteger};
+use crate::objc::{autorelease, id, msg, msg_class, nil, objc_classes, ClassExports};
+use crate::options::CameraFeatures;
+use crate::Environment;
 
 type UIImagePickerControllerSourceType = NSInteger;
+const UIImagePickerControllerSourceTypeCamera: UIImagePickerControllerSourceType = 1;
+
+type UIImagePickerControllerCameraDevice = NSInteger;
+const UIImagePickerControllerCameraDeviceRear: UIImagePickerControllerCameraDevice = 0;
+const UIImagePickerControllerCameraDeviceFront: UIImagePickerControllerCameraDevice = 1;
+
+type UIImagePickerControllerCameraCaptureMode = NSInteger;
+const UIImagePickerControllerCameraCaptureModePhoto: UIImagePickerControllerCameraCaptureMode = 0;
+const UIImagePickerControllerCameraCaptureModeVideo: UIImagePickerControllerCameraCaptureMode = 1;
+
+/// `kUTTypeImage`
+const IMAGE_MEDIA_TYPE: &str = "public.image";
+/// `kUTTypeMovie`
+const MOVIE_MEDIA_TYPE: &str = "public.movie";
+
+fn camera_available(camera: CameraFeatures, device: UIImagePickerControllerCameraDevice) -> bool {
+    match device {
+        UIImagePickerControllerCameraDeviceRear => camera.rear,
+        UIImagePickerControllerCameraDeviceFront => camera.front,
+        _ => false,
+    }
+}
+
+fn source_type_available(env: &Environment, type_: UIImagePickerControllerSourceType) -> bool {
+    // TODO: support the photo library sources.
+    type_ == UIImagePickerControllerSourceTypeCamera
+        && (env.options.camera.rear || env.options.camera.front)
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -18,10 +52,50 @@ pub const CLASSES: ClassExports = objc_classes! {
 //       UIViewController.
 @implementation UIImagePickerController: UIViewController
 
-+ (bool)isSourceTypeAvailable:(UIImagePickerControllerSourceType)_type {
-    // For now, simply claim no sources are available.
-    // TODO: support some sources.
-    false
++ (bool)isSourceTypeAvailable:(UIImagePickerControllerSourceType)type_ {
+    source_type_available(env, type_)
+}
+
++ (id)availableMediaTypesForSourceType:(UIImagePickerControllerSourceType)type_ {
+    if !source_type_available(env, type_) {
+        return nil;
+    }
+    let mut types = vec![ns_string::get_static_str(env, IMAGE_MEDIA_TYPE)];
+    if env.options.camera.video {
+        types.push(ns_string::get_static_str(env, MOVIE_MEDIA_TYPE));
+    }
+    let array = ns_array::from_vec(env, types);
+    autorelease(env, array)
+}
+
++ (bool)isCameraDeviceAvailable:(UIImagePickerControllerCameraDevice)device {
+    camera_available(env.options.camera, device)
+}
+
++ (bool)isFlashAvailableForCameraDevice:(UIImagePickerControllerCameraDevice)device {
+    let camera = env.options.camera;
+    device == UIImagePickerControllerCameraDeviceRear && camera.rear && camera.flash
+}
+
++ (id)availableCaptureModesForCameraDevice:(UIImagePickerControllerCameraDevice)device {
+    let camera = env.options.camera;
+    if !camera_available(camera, device) {
+        return nil;
+    }
+    let mut modes = vec![UIImagePickerControllerCameraCaptureModePhoto];
+    if camera.video {
+        modes.push(UIImagePickerControllerCameraCaptureModeVideo);
+    }
+    let modes = modes
+        .into_iter()
+        .map(|mode| {
+            let number: id = msg_class![env; NSNumber alloc];
+            let number: id = msg![env; number initWithLongLong:(mode as i64)];
+            number
+        })
+        .collect();
+    let array = ns_array::from_vec(env, modes);
+    autorelease(env, array)
 }
 
 @end
@@ -0,0 +1,266 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIPasteboard`.
+//!
+//! Only text is supported. The general pasteboard is bridged to the host's
+//! clipboard, so text copied in the app can be pasted on the host and vice
+//! versa. Other pasteboards only exist within touchHLE and aren't persisted.
+
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::frameworks::foundation::{ns_array, ns_string, NSInteger, NSUInteger};
+use crate::objc::{autorelease, id, msg, nil, objc_classes, ClassExports, HostObject};
+use crate::Environment;
+use std::collections::HashMap;
+
+const UIPasteboardNameGeneral: &str = "com.apple.UIKit.pboard.general";
+const UIPasteboardNameFind: &str = "com.apple.UIKit.pboard.find";
+
+/// The only pasteboard type that is supported.
+const PLAIN_TEXT_TYPE: &str = "public.utf8-plain-text";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_UIPasteboardNameGeneral",
+        HostConstant::NSString(UIPasteboardNameGeneral),
+    ),
+    (
+        "_UIPasteboardNameFind",
+        HostConstant::NSString(UIPasteboardNameFind),
+    ),
+];
+
+#[derive(Default)]
+pub struct State {
+    pasteboards: HashMap<String, id>,
+    /// Counter for `pasteboardWithUniqueName`.
+    next_unique_name: u32,
+}
+impl State {
+    fn get(env: &mut Environment) -> &mut Self {
+        &mut env.framework_state.uikit.ui_pasteboard
+    }
+}
+
+struct UIPasteboardHostObject {
+    name: String,
+    string: Option<String>,
+    change_count: NSInteger,
+    persistent: bool,
+}
+impl HostObject for UIPasteboardHostObject {}
+
+/// Get the pasteboard with some name, optionally creating it.
+fn pasteboard_with_name(env: &mut Environment, name: &str, create: bool) -> id {
+    if let Some(&existing) = State::get(env).pasteboards.get(name) {
+        return existing;
+    }
+    if !create {
+        return nil;
+    }
+    let host_object = Box::new(UIPasteboardHostObject {
+        name: name.to_string(),
+        string: None,
+        change_count: 0,
+        persistent: false,
+    });
+    let class = env.objc.get_known_class("UIPasteboard", &mut env.mem);
+    // Pasteboards live until they're removed, so they're never deallocated.
+    let new = env
+        .objc
+        .alloc_static_object(class, host_object, &mut env.mem);
+    State::get(env).pasteboards.insert(name.to_string(), new);
+    new
+}
+
+fn uses_host_clipboard(env: &mut Environment, pasteboard: id) -> bool {
+    env.window.is_some()
+        && env.objc.borrow::<UIPasteboardHostObject>(pasteboard).name == UIPasteboardNameGeneral
+}
+
+/// Bring a pasteboard's contents up to date, in case it's the general
+/// pasteboard and the host's clipboard changed.
+fn sync_from_host(env: &mut Environment, pasteboard: id) {
+    if !uses_host_clipboard(env, pasteboard) {
+        return;
+    }
+    let host_text = env.window().clipboard_text();
+    let host_object = env.objc.borrow_mut::<UIPasteboardHostObject>(pasteboard);
+    if host_object.string != host_text {
+        log_dbg!("Host clipboard changed, updating {:?}", pasteboard);
+        host_object.string = host_text;
+        host_object.change_count += 1;
+    }
+}
+
+fn get_string(env: &mut Environment, pasteboard: id) -> Option<String> {
+    sync_from_host(env, pasteboard);
+    env.objc
+        .borrow::<UIPasteboardHostObject>(pasteboard)
+        .string
+        .clone()
+}
+
+fn set_string(env: &mut Environment, pasteboard: id, string: Option<String>) {
+    log_dbg!("Setting {:?} string to {:?}", pasteboard, string);
+    if uses_host_clipboard(env, pasteboard) {
+        env.window()
+            .set_clipboard_text(string.as_deref().unwrap_or(""));
+    }
+    let host_object = env.objc.borrow_mut::<UIPasteboardHostObject>(pasteboard);
+    host_object.string = string;
+    host_object.change_count += 1;
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIPasteboard: NSObject
+
++ (id)generalPasteboard {
+    pasteboard_with_name(env, UIPasteboardNameGeneral, /* create: */ true)
+}
+
++ (id)pasteboardWithName:(id)name // NSString*
+                  create:(bool)create {
+    let name = ns_string::to_rust_string(env, name);
+    pasteboard_with_name(env, &name, create)
+}
+
++ (id)pasteboardWithUniqueName {
+    let state = State::get(env);
+    let name = format!("touchHLE.pboard.{}", state.next_unique_name);
+    state.next_unique_name += 1;
+    pasteboard_with_name(env, &name, /* create: */ true)
+}
+
++ (())removePasteboardWithName:(id)name { // NSString*
+    let name = ns_string::to_rust_string(env, name);
+    // The object itself is leaked, since it's static, but it can no longer be
+    // found by name.
+    State::get(env).pasteboards.remove(&*name);
+}
+
+- (id)retain { this }
+- (())release {}
+- (id)autorelease { this }
+
+- (id)name {
+    let name = env.objc.borrow::<UIPasteboardHostObject>(this).name.clone();
+    let name = ns_string::from_rust_string(env, name);
+    autorelease(env, name)
+}
+
+- (bool)isPersistent {
+    env.objc.borrow::<UIPasteboardHostObject>(this).persistent
+}
+- (())setPersistent:(bool)persistent {
+    // Nothing is saved between launches regardless, but apps can't tell.
+    env.objc.borrow_mut::<UIPasteboardHostObject>(this).persistent = persistent;
+}
+
+- (NSInteger)changeCount {
+    sync_from_host(env, this);
+    env.objc.borrow::<UIPasteboardHostObject>(this).change_count
+}
+
+- (id)string {
+    match get_string(env, this) {
+        Some(string) => {
+            let string = ns_string::from_rust_string(env, string);
+            autorelease(env, string)
+        }
+        None => nil,
+    }
+}
+- (())setString:(id)string { // NSString*
+    let string = (string != nil).then(|| ns_string::to_rust_string(env, string).into_owned());
+    set_string(env, this, string);
+}
+
+- (id)strings {
+    match get_string(env, this) {
+        Some(string) => {
+            let string = ns_string::from_rust_string(env, string);
+            let array = ns_array::from_vec(env, vec![string]);
+            autorelease(env, array)
+        }
+        None => nil,
+    }
+}
+- (())setStrings:(id)strings { // NSArray<NSString*>*
+    // Only one item is supported, so the rest are dropped.
+    let count: NSUInteger = msg![env; strings count];
+    if count > 1 {
+        log!("TODO: [(UIPasteboard*){:?} setStrings:] with {} strings, keeping only the first", this, count);
+    }
+    let first: id = if count > 0 {
+        msg![env; strings objectAtIndex:0u32]
+    } else {
+        nil
+    };
+    () = msg![env; this setString:first];
+}
+
+- (id)pasteboardTypes {
+    let types = if get_string(env, this).is_some() {
+        let plain_text = ns_string::get_static_str(env, PLAIN_TEXT_TYPE);
+        vec![plain_text]
+    } else {
+        Vec::new()
+    };
+    let array = ns_array::from_vec(env, types);
+    autorelease(env, array)
+}
+- (bool)containsPasteboardTypes:(id)types { // NSArray<NSString*>*
+    if get_string(env, this).is_none() {
+        return false;
+    }
+    let count: NSUInteger = msg![env; types count];
+    (0..count).any(|i| {
+        let type_: id = msg![env; types objectAtIndex:i];
+        ns_string::to_rust_string(env, type_) == PLAIN_TEXT_TYPE
+    })
+}
+
+- (id)valueForPasteboardType:(id)type_ { // NSString*
+    let type_ = ns_string::to_rust_string(env, type_);
+    if type_ != PLAIN_TEXT_TYPE {
+        log!("TODO: [(UIPasteboard*){:?} valueForPasteboardType:{:?}]", this, type_);
+        return nil;
+    }
+    msg![env; this string]
+}
+- (())setValue:(id)value
+forPasteboardType:(id)type_ { // NSString*
+    let type_ = ns_string::to_rust_string(env, type_);
+    if type_ != PLAIN_TEXT_TYPE {
+        log!("TODO: [(UIPasteboard*){:?} setValue:{:?} forPasteboardType:{:?}], ignoring", this, value, type_);
+        return;
+    }
+    () = msg![env; this setString:value];
+}
+
+// TODO: URLs and images. These are stubs so apps that offer copying them don't
+// crash.
+- (id)URL {
+    log!("TODO: [(UIPasteboard*){:?} URL]", this);
+    nil
+}
+- (())setURL:(id)url { // NSURL*
+    log!("TODO: [(UIPasteboard*){:?} setURL:{:?}], ignoring", this, url);
+}
+- (id)image {
+    log!("TODO: [(UIPasteboard*){:?} image]", this);
+    nil
+}
+- (())setImage:(id)image { // UIImage*
+    log!("TODO: [(UIPasteboard*){:?} setImage:{:?}], ignoring", this, image);
+}
+
+@end
+
+};
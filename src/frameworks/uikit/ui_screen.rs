@@ -6,6 +6,7 @@
 //! `UIScreen`.
 
 use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::NSInteger;
 use crate::objc::{id, msg, objc_classes, ClassExports, TrivialHostObject};
 
 #[derive(Default)]
@@ -58,6 +59,10 @@ pub const CLASSES: ClassExports = objc_classes! {
     bounds
 }
 
+- (NSInteger)maximumFramesPerSecond {
+    env.options.refresh_rate.round() as NSInteger
+}
+
 @end
 
 };
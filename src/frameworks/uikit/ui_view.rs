@@ -12,6 +12,7 @@ pub mod ui_alert_view;
 pub mod ui_control;
 pub mod ui_image_view;
 pub mod ui_label;
+pub mod ui_web_view;
 pub mod ui_window;
 
 use super::ui_graphics::{UIGraphicsPopContext, UIGraphicsPushContext};
@@ -44,7 +45,12 @@ pub(super) struct UIViewHostObject {
     user_interaction_enabled: bool,
     multiple_touch_enabled: bool,
 }
-impl HostObject for UIViewHostObject {}
+impl HostObject for UIViewHostObject {
+    fn strong_references(&self, references: &mut Vec<id>) {
+        references.push(self.layer);
+        references.extend_from_slice(&self.subviews);
+    }
+}
 impl Default for UIViewHostObject {
     fn default() -> UIViewHostObject {
         // The Default trait is implemented so subclasses will get the same
@@ -366,8 +372,15 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; layer setFrame:frame]
 }
 
+// The layer's transform is applied around its anchor point, which for a
+// view's layer is always the center, so these can be forwarded directly.
+- (CGAffineTransform)transform {
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    msg![env; layer affineTransform]
+}
 - (())setTransform:(CGAffineTransform)transform {
-    log!("TODO: [{:?} setTransform:{:?}]", this, transform);
+    let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
+    msg![env; layer setAffineTransform:transform]
 }
 
 - (())setContentMode:(NSInteger)content_mode { // should be UIViewContentMode
@@ -421,12 +434,8 @@ pub const CLASSES: ClassExports = objc_classes! {
         if hidden || alpha < 0.01 || !interactible {
            continue;
         }
-        let frame: CGRect = msg![env; subview frame];
-        let bounds: CGRect = msg![env; subview bounds];
-        let point = CGPoint {
-            x: point.x - frame.origin.x + bounds.origin.x,
-            y: point.y - frame.origin.y + bounds.origin.y,
-        };
+        // This takes the subview's transform into account.
+        let point: CGPoint = msg![env; subview convertPoint:point fromView:this];
         let subview: id = msg![env; subview hitTest:point withEvent:event];
         if subview != nil {
             return subview;
@@ -230,10 +230,12 @@ pub const CLASSES: ClassExports = objc_classes! {
     // UILabel always vertically centers text
     // (TODO: check whether this is actually a UILabel thing, or a property of
     // UIStringDrawing?)
+    // The text is aligned to whole points, like on iPhone OS, so that it
+    // doesn't move by a fraction of a pixel relative to the label's frame.
     let rect = CGRect {
         origin: CGPoint {
             x: bounds.origin.x,
-            y: bounds.origin.y + (bounds.size.height - calculated_size.height) / 2.0,
+            y: bounds.origin.y + ((bounds.size.height - calculated_size.height) / 2.0).round(),
         },
         size: CGSize {
             width: bounds.size.width,
@@ -251,7 +253,7 @@ pub const CLASSES: ClassExports = objc_classes! {
             _ => unimplemented!(),
         };
         let point = CGPoint {
-            x: rect.origin.x + x_offset * (bounds.size.width - calculated_size.width),
+            x: rect.origin.x + (x_offset * (bounds.size.width - calculated_size.width)).round(),
             y: rect.origin.y
         };
         msg![env; text drawAtPoint:point
@@ -0,0 +1,926 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIWebView`.
+//!
+//! There's no web engine here. Loaded HTML is reduced to a list of text
+//! blocks and images, which is enough for the help and credits screens that
+//! games typically show with this class.
+//!
+//! Pages and images usually come from `file:` URLs, which are read from the
+//! guest filesystem, so they can be in the app bundle or in its sandbox (e.g.
+//! the `Documents` directory). Relative links and image paths are resolved
+//! against the base URL, or the directory of the page itself. Stylesheets are
+//! ignored, since there's no styling.
+//!
+//! Requests for other URLs can only load a page if the user's
+//! `--network-rules=` file serves one (see [crate::network_rules]). Images on
+//! such pages aren't loaded.
+
+mod javascript;
+
+use crate::frameworks::core_graphics::cg_context::CGContextSetRGBFillColor;
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::ns_string::{from_rust_string, to_rust_string};
+use crate::frameworks::foundation::ns_url_connection::fetch_with_rules;
+use crate::frameworks::foundation::{ns_url, NSInteger, NSTimeInterval};
+use crate::frameworks::uikit::ui_font::{
+    UILineBreakModeWordWrap, UITextAlignment, UITextAlignmentCenter, UITextAlignmentLeft,
+};
+use crate::frameworks::uikit::ui_graphics::UIGraphicsGetCurrentContext;
+use crate::fs::{GuestPath, GuestPathBuf};
+use crate::objc::{
+    autorelease, id, impl_HostObject_with_superclass, msg, msg_class, msg_super, nil, objc_classes,
+    release, retain, ClassExports, NSZonePtr, SEL,
+};
+use crate::Environment;
+
+pub type UIWebViewNavigationType = NSInteger;
+pub const UIWebViewNavigationTypeLinkClicked: UIWebViewNavigationType = 0;
+#[allow(dead_code)]
+pub const UIWebViewNavigationTypeFormSubmitted: UIWebViewNavigationType = 1;
+#[allow(dead_code)]
+pub const UIWebViewNavigationTypeBackForward: UIWebViewNavigationType = 2;
+pub const UIWebViewNavigationTypeReload: UIWebViewNavigationType = 3;
+#[allow(dead_code)]
+pub const UIWebViewNavigationTypeFormResubmitted: UIWebViewNavigationType = 4;
+pub const UIWebViewNavigationTypeOther: UIWebViewNavigationType = 5;
+
+/// Margin around the content, in points.
+const MARGIN: CGFloat = 8.0;
+
+/// A piece of content, laid out vertically one after another.
+#[derive(Debug, PartialEq)]
+enum HtmlBlock {
+    Text {
+        text: String,
+        /// 1 to 6 for `<h1>` to `<h6>`, 0 for normal text.
+        heading_level: u8,
+        centered: bool,
+        /// `href` of the enclosing `<a>`, if any.
+        link: Option<String>,
+    },
+    Image {
+        src: String,
+        centered: bool,
+    },
+}
+
+/// Content waiting for the load to happen, see `loadHTMLString:baseURL:`.
+enum PendingLoad {
+    Html {
+        html: String,
+        base_dir: Option<GuestPathBuf>,
+    },
+    /// `NSURLRequest*`
+    Request(id, UIWebViewNavigationType),
+}
+
+/// [HtmlBlock] with images loaded.
+enum LoadedBlock {
+    Text {
+        text: String,
+        heading_level: u8,
+        centered: bool,
+        link: Option<String>,
+    },
+    Image {
+        /// `UIImage*`
+        image: id,
+        centered: bool,
+    },
+}
+
+#[derive(Default)]
+pub struct UIWebViewHostObject {
+    superclass: super::UIViewHostObject,
+    /// Weak reference (delegates aren't retained)
+    delegate: id,
+    /// `NSURLRequest*`, only set for `loadRequest:`.
+    request: id,
+    pending_load: Option<PendingLoad>,
+    loading: bool,
+    content: Vec<LoadedBlock>,
+    /// Content of the page's `<title>` element.
+    title: String,
+    /// Directory of the current page, used to resolve relative links.
+    base_dir: Option<GuestPathBuf>,
+    /// Where each link was drawn, so taps can be handled.
+    link_rects: Vec<(CGRect, String)>,
+    scales_page_to_fit: bool,
+}
+impl_HostObject_with_superclass!(UIWebViewHostObject);
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UIWebView: UIView
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<UIWebViewHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithFrame:(CGRect)frame {
+    let this: id = msg_super![env; this initWithFrame:frame];
+    let white: id = msg_class![env; UIColor whiteColor];
+    () = msg![env; this setBackgroundColor:white];
+    this
+}
+
+- (id)initWithCoder:(id)coder {
+    let this: id = msg_super![env; this initWithCoder:coder];
+    // TODO: Decode the actual property values from the coder
+    let white: id = msg_class![env; UIColor whiteColor];
+    () = msg![env; this setBackgroundColor:white];
+    this
+}
+
+- (())dealloc {
+    let host_object = env.objc.borrow_mut::<UIWebViewHostObject>(this);
+    let request = host_object.request;
+    let pending_load = host_object.pending_load.take();
+    let content = std::mem::take(&mut host_object.content);
+    release(env, request);
+    if let Some(PendingLoad::Request(pending_request, _)) = pending_load {
+        release(env, pending_request);
+    }
+    release_content(env, content);
+    msg_super![env; this dealloc]
+}
+
+- (id)delegate {
+    env.objc.borrow::<UIWebViewHostObject>(this).delegate
+}
+- (())setDelegate:(id)delegate {
+    env.objc.borrow_mut::<UIWebViewHostObject>(this).delegate = delegate;
+}
+
+- (bool)scalesPageToFit {
+    env.objc.borrow::<UIWebViewHostObject>(this).scales_page_to_fit
+}
+- (())setScalesPageToFit:(bool)scales {
+    // TODO: Content is always laid out to fit the width of the view.
+    env.objc.borrow_mut::<UIWebViewHostObject>(this).scales_page_to_fit = scales;
+}
+
+- (id)request {
+    env.objc.borrow::<UIWebViewHostObject>(this).request
+}
+
+- (bool)isLoading {
+    env.objc.borrow::<UIWebViewHostObject>(this).loading
+}
+
+- (bool)canGoBack {
+    false
+}
+- (bool)canGoForward {
+    false
+}
+- (())goBack {
+    log!("TODO: [(UIWebView*){:?} goBack] (ignored)", this);
+}
+- (())goForward {
+    log!("TODO: [(UIWebView*){:?} goForward] (ignored)", this);
+}
+
+- (())loadHTMLString:(id)string // NSString*
+             baseURL:(id)base_url { // NSURL*
+    let html = if string == nil {
+        String::new()
+    } else {
+        to_rust_string(env, string).into_owned()
+    };
+    let base_dir = if base_url != nil && msg![env; base_url isFileURL] {
+        let base_path = ns_url::to_rust_path(env, base_url).into_owned();
+        // The base URL can be either a directory, e.g. the bundle's URL, or
+        // a file in that directory, e.g. the page the HTML came from.
+        if base_path.as_str().ends_with('/') || env.fs.is_dir(&base_path) {
+            Some(base_path)
+        } else {
+            base_path.parent().map(GuestPathBuf::from)
+        }
+    } else {
+        None
+    };
+    schedule_load(env, this, PendingLoad::Html { html, base_dir });
+}
+
+- (())loadRequest:(id)request { // NSURLRequest*
+    retain(env, request);
+    schedule_load(env, this, PendingLoad::Request(request, UIWebViewNavigationTypeOther));
+}
+
+- (id)stringByEvaluatingJavaScriptFromString:(id)script { // NSString*
+    if script == nil {
+        return nil;
+    }
+    let script = to_rust_string(env, script);
+    let host_object = env.objc.borrow::<UIWebViewHostObject>(this);
+    let document = javascript::Document { title: &host_object.title };
+    let result = match javascript::evaluate(&script, &document) {
+        Ok(result) => result,
+        Err(e) => {
+            // Errors also give an empty string on a real device.
+            log!("Warning: UIWebView {:?} couldn't evaluate {:?}: {}", this, script, e);
+            String::new()
+        }
+    };
+    let result = from_rust_string(env, result);
+    autorelease(env, result)
+}
+
+- (())reload {
+    let request = env.objc.borrow::<UIWebViewHostObject>(this).request;
+    if request != nil {
+        retain(env, request);
+        schedule_load(env, this, PendingLoad::Request(request, UIWebViewNavigationTypeReload));
+    }
+}
+
+- (())stopLoading {
+    let host_object = env.objc.borrow_mut::<UIWebViewHostObject>(this);
+    host_object.loading = false;
+    if let Some(PendingLoad::Request(request, _)) = host_object.pending_load.take() {
+        release(env, request);
+    }
+}
+
+// Called by the timer set up by schedule_load(), so that the delegate methods
+// are called asynchronously like on a real device.
+- (())_touchHLE_performLoad:(id)_timer { // NSTimer*
+    let Some(pending_load) = env.objc.borrow_mut::<UIWebViewHostObject>(this).pending_load.take() else {
+        // The load was stopped or replaced.
+        return;
+    };
+
+    let delegate = env.objc.borrow::<UIWebViewHostObject>(this).delegate;
+
+    if let PendingLoad::Request(request, navigation_type) = pending_load {
+        if !should_start_load(env, delegate, this, request, navigation_type) {
+            log_dbg!("UIWebView {:?} load was cancelled by the delegate", this);
+            env.objc.borrow_mut::<UIWebViewHostObject>(this).loading = false;
+            release(env, request);
+            return;
+        }
+    }
+
+    call_delegate(env, delegate, "webViewDidStartLoad:", this);
+
+    let loaded = match pending_load {
+        PendingLoad::Html { html, base_dir } => Ok((html, base_dir)),
+        PendingLoad::Request(request, _) => {
+            let old_request = std::mem::replace(&mut env.objc.borrow_mut::<UIWebViewHostObject>(this).request, request);
+            release(env, old_request);
+            read_request(env, request)
+        },
+    };
+
+    let (html, base_dir) = match loaded {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            log!("Warning: UIWebView {:?} couldn't load request: {}", this, e);
+            env.objc.borrow_mut::<UIWebViewHostObject>(this).loading = false;
+            let sel: SEL = env.objc.register_host_selector("webView:didFailLoadWithError:".to_string(), &mut env.mem);
+            if delegate != nil && msg![env; delegate respondsToSelector:sel] {
+                // TODO: Pass an NSError.
+                () = msg![env; delegate webView:this didFailLoadWithError:nil];
+            }
+            return;
+        }
+    };
+
+    let title = find_title(&html);
+    let content = parse_html(&html)
+        .into_iter()
+        .filter_map(|block| match block {
+            HtmlBlock::Text { text, heading_level, centered, link } => {
+                Some(LoadedBlock::Text { text, heading_level, centered, link })
+            }
+            HtmlBlock::Image { src, centered } => {
+                let image = load_image(env, base_dir.as_deref(), &src);
+                (image != nil).then_some(LoadedBlock::Image { image, centered })
+            }
+        })
+        .collect();
+    let host_object = env.objc.borrow_mut::<UIWebViewHostObject>(this);
+    let old_content = std::mem::replace(&mut host_object.content, content);
+    host_object.title = title;
+    host_object.base_dir = base_dir;
+    host_object.loading = false;
+    release_content(env, old_content);
+    () = msg![env; this setNeedsDisplay];
+
+    call_delegate(env, delegate, "webViewDidFinishLoad:", this);
+}
+
+- (())drawRect:(CGRect)_rect {
+    let bounds: CGRect = msg![env; this bounds];
+    let context = UIGraphicsGetCurrentContext(env);
+    CGContextSetRGBFillColor(env, context, 0.0, 0.0, 0.0, 1.0);
+
+    let width = (bounds.size.width - MARGIN * 2.0).max(0.0);
+    let mut y = bounds.origin.y + MARGIN;
+    let mut link_rects = Vec::new();
+    let block_count = env.objc.borrow::<UIWebViewHostObject>(this).content.len();
+    for i in 0..block_count {
+        if y >= bounds.origin.y + bounds.size.height {
+            // TODO: scrolling
+            break;
+        }
+        match env.objc.borrow::<UIWebViewHostObject>(this).content[i] {
+            LoadedBlock::Text { ref text, heading_level, centered, ref link } => {
+                let text = text.clone();
+                let link = link.clone();
+                let text = from_rust_string(env, text);
+                let font = font_for_heading_level(env, heading_level);
+                let alignment: UITextAlignment = if centered { UITextAlignmentCenter } else { UITextAlignmentLeft };
+                let size: CGSize = msg![env; text sizeWithFont:font
+                                            constrainedToSize:(CGSize { width, height: CGFloat::MAX })
+                                                lineBreakMode:UILineBreakModeWordWrap];
+                let rect = CGRect {
+                    origin: CGPoint { x: bounds.origin.x + MARGIN, y },
+                    size: CGSize { width, height: size.height },
+                };
+                if link.is_some() {
+                    CGContextSetRGBFillColor(env, context, 0.0, 0.0, 0.8, 1.0);
+                }
+                let _: CGSize = msg![env; text drawInRect:rect
+                                                 withFont:font
+                                            lineBreakMode:UILineBreakModeWordWrap
+                                                alignment:alignment];
+                if let Some(link) = link {
+                    CGContextSetRGBFillColor(env, context, 0.0, 0.0, 0.0, 1.0);
+                    link_rects.push((rect, link));
+                }
+                release(env, text);
+                y += size.height + MARGIN;
+            }
+            LoadedBlock::Image { image, centered } => {
+                let mut size: CGSize = msg![env; image size];
+                // Images that are too wide are scaled down to fit.
+                if size.width > width && size.width > 0.0 {
+                    size.height *= width / size.width;
+                    size.width = width;
+                }
+                let x_offset = if centered { ((width - size.width) / 2.0).round() } else { 0.0 };
+                let rect = CGRect {
+                    origin: CGPoint { x: bounds.origin.x + MARGIN + x_offset, y },
+                    size,
+                };
+                () = msg![env; image drawInRect:rect];
+                y += size.height + MARGIN;
+            }
+        }
+    }
+    env.objc.borrow_mut::<UIWebViewHostObject>(this).link_rects = link_rects;
+}
+
+- (())touchesEnded:(id)touches // NSSet* of UITouch*
+         withEvent:(id)_event { // UIEvent*
+    let touch: id = msg![env; touches anyObject];
+    let CGPoint { x, y }: CGPoint = msg![env; touch locationInView:this];
+    let host_object = env.objc.borrow::<UIWebViewHostObject>(this);
+    let Some(link) = host_object.link_rects.iter().find_map(|(rect, link)| {
+        let CGRect { origin, size } = *rect;
+        let inside_x = x >= origin.x && x < origin.x + size.width;
+        let inside_y = y >= origin.y && y < origin.y + size.height;
+        (inside_x && inside_y).then(|| link.clone())
+    }) else {
+        return;
+    };
+    let base_dir = host_object.base_dir.clone();
+    let Some(url) = url_for_link(env, base_dir.as_deref(), &link) else {
+        return;
+    };
+    let request: id = msg_class![env; NSURLRequest requestWithURL:url];
+    retain(env, request);
+    schedule_load(env, this, PendingLoad::Request(request, UIWebViewNavigationTypeLinkClicked));
+}
+
+@end
+
+};
+
+/// Start a load on the next run loop iteration.
+fn schedule_load(env: &mut Environment, web_view: id, load: PendingLoad) {
+    let host_object = env.objc.borrow_mut::<UIWebViewHostObject>(web_view);
+    let old_load = host_object.pending_load.replace(load);
+    host_object.loading = true;
+    if let Some(PendingLoad::Request(old_request, _)) = old_load {
+        release(env, old_request);
+    }
+
+    let sel: SEL = env.objc.lookup_selector("_touchHLE_performLoad:").unwrap();
+    let interval: NSTimeInterval = 0.0;
+    let _: id = msg_class![env; NSTimer scheduledTimerWithTimeInterval:interval
+                                                                 target:web_view
+                                                               selector:sel
+                                                               userInfo:nil
+                                                                repeats:false];
+}
+
+fn call_delegate(env: &mut Environment, delegate: id, selector: &str, web_view: id) {
+    if delegate == nil {
+        return;
+    }
+    let sel: SEL = env
+        .objc
+        .register_host_selector(selector.to_string(), &mut env.mem);
+    let responds: bool = msg![env; delegate respondsToSelector:sel];
+    if responds {
+        let _: () = crate::objc::msg_send(env, (delegate, sel, web_view));
+    }
+}
+
+/// Ask the delegate whether a request should be loaded. Apps use this to
+/// intercept links with custom URL schemes.
+fn should_start_load(
+    env: &mut Environment,
+    delegate: id,
+    web_view: id,
+    request: id,
+    navigation_type: UIWebViewNavigationType,
+) -> bool {
+    if delegate == nil {
+        return true;
+    }
+    let sel: SEL = env.objc.register_host_selector(
+        "webView:shouldStartLoadWithRequest:navigationType:".to_string(),
+        &mut env.mem,
+    );
+    let responds: bool = msg![env; delegate respondsToSelector:sel];
+    if !responds {
+        return true;
+    }
+    msg![env; delegate webView:web_view
+    shouldStartLoadWithRequest:request
+                navigationType:navigation_type]
+}
+
+/// Get the scheme of a URL, if it has one, e.g. "http" or an app's custom
+/// "myapp". Relative URLs don't have one.
+fn url_scheme(url: &str) -> Option<&str> {
+    let (scheme, _) = url.split_once(':')?;
+    let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+    valid.then_some(scheme)
+}
+
+/// Resolve a URL referenced by a page, e.g. the `src` of an image or the `href`
+/// of a link, to a path in the guest filesystem. Relative URLs are resolved
+/// against `base_dir`. Any query or fragment is dropped.
+fn resolve_file_url(base_dir: Option<&GuestPath>, url: &str) -> Result<GuestPathBuf, String> {
+    if let Some(scheme) = url_scheme(url) {
+        return if scheme.eq_ignore_ascii_case("file") {
+            ns_url::file_url_to_path(url)
+                .map(GuestPathBuf::from)
+                .ok_or_else(|| format!("{:?} is not a valid file URL", url))
+        } else {
+            Err(format!(
+                "{:?} is not a file URL, network access is not supported",
+                url
+            ))
+        };
+    }
+    let path = ns_url::percent_decode(url.split(['#', '?']).next().unwrap());
+    if path.starts_with('/') {
+        Ok(GuestPathBuf::from(path))
+    } else if let Some(base_dir) = base_dir {
+        Ok(base_dir.join(path))
+    } else {
+        Err(format!("can't resolve {:?} without a base URL", url))
+    }
+}
+
+/// Make an (autoreleased) `NSURL*` for the `href` of a tapped link.
+fn url_for_link(env: &mut Environment, base_dir: Option<&GuestPath>, href: &str) -> Option<id> {
+    if href.starts_with('#') {
+        // TODO: scroll to the anchor
+        return None;
+    }
+    // URLs with other schemes, e.g. "http:" or an app's custom "myapp:", are
+    // passed on as-is, so that the delegate can intercept them.
+    if url_scheme(href).is_some_and(|scheme| !scheme.eq_ignore_ascii_case("file")) {
+        let href = from_rust_string(env, href.to_string());
+        let url: id = msg_class![env; NSURL URLWithString:href];
+        release(env, href);
+        return (url != nil).then_some(url);
+    }
+    let path = match resolve_file_url(base_dir, href) {
+        Ok(path) => path,
+        Err(e) => {
+            log!("Warning: UIWebView can't follow link: {}", e);
+            return None;
+        }
+    };
+    let path = from_rust_string(env, String::from(path));
+    let url: id = msg_class![env; NSURL fileURLWithPath:path];
+    release(env, path);
+    Some(url)
+}
+
+/// Read the HTML for a request. The directory of the file is used to resolve
+/// relative image paths. Requests for non-file URLs go through the network
+/// rules.
+fn read_request(
+    env: &mut Environment,
+    request: id,
+) -> Result<(String, Option<GuestPathBuf>), String> {
+    let url: id = msg![env; request URL];
+    if url == nil {
+        return Err("request has no URL".to_string());
+    }
+    if !msg![env; url isFileURL] {
+        let url_string: id = msg![env; url absoluteString];
+        let url_string = to_rust_string(env, url_string).into_owned();
+        return match fetch_with_rules(env, &url_string) {
+            (_, Some(response)) => Ok((String::from_utf8_lossy(&response.body).into_owned(), None)),
+            (url, None) => Err(format!(
+                "{:?} is not a file URL and wasn't served by the network rules",
+                url
+            )),
+        };
+    }
+    let path = ns_url::to_rust_path(env, url).into_owned();
+    let bytes = env
+        .fs
+        .read(&path)
+        .map_err(|_| format!("couldn't read {:?}", path))?;
+    let html = String::from_utf8_lossy(&bytes).into_owned();
+    Ok((html, path.parent().map(GuestPathBuf::from)))
+}
+
+fn load_image(env: &mut Environment, base_dir: Option<&GuestPath>, src: &str) -> id {
+    let path = match resolve_file_url(base_dir, src) {
+        Ok(path) => path,
+        Err(e) => {
+            log!("Warning: UIWebView can't load image: {}", e);
+            return nil;
+        }
+    };
+    let path = from_rust_string(env, String::from(path));
+    let image: id = msg_class![env; UIImage alloc];
+    let image: id = msg![env; image initWithContentsOfFile:path];
+    if image == nil {
+        log!(
+            "Warning: UIWebView couldn't load image {:?}",
+            to_rust_string(env, path)
+        );
+    }
+    release(env, path);
+    image
+}
+
+fn release_content(env: &mut Environment, content: Vec<LoadedBlock>) {
+    for block in content {
+        if let LoadedBlock::Image { image, .. } = block {
+            release(env, image);
+        }
+    }
+}
+
+fn font_for_heading_level(env: &mut Environment, heading_level: u8) -> id {
+    let size: CGFloat = match heading_level {
+        0 => 14.0,
+        1 => 24.0,
+        2 => 20.0,
+        3 => 17.0,
+        _ => 15.0,
+    };
+    if heading_level == 0 {
+        msg_class![env; UIFont systemFontOfSize:size]
+    } else {
+        msg_class![env; UIFont boldSystemFontOfSize:size]
+    }
+}
+
+/// Reduce HTML to [HtmlBlock]s. This is very forgiving: unknown tags are
+/// ignored, and unclosed tags don't matter.
+fn parse_html(html: &str) -> Vec<HtmlBlock> {
+    let mut blocks = Vec::new();
+    let mut text = String::new();
+    let mut heading_level = 0;
+    let mut center_depth = 0u32;
+    let mut link: Option<String> = None;
+    // Content of these elements is not displayed.
+    let mut hidden_element: Option<String> = None;
+
+    fn flush(
+        blocks: &mut Vec<HtmlBlock>,
+        text: &mut String,
+        heading_level: u8,
+        centered: bool,
+        link: &Option<String>,
+    ) {
+        let trimmed = text
+            .split('\n')
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let trimmed = trimmed.trim_matches('\n');
+        if !trimmed.is_empty() {
+            blocks.push(HtmlBlock::Text {
+                text: trimmed.to_string(),
+                heading_level,
+                centered,
+                link: link.clone(),
+            });
+        }
+        text.clear();
+    }
+
+    let mut rest = html;
+    while !rest.is_empty() {
+        // Skip to the end of hidden content. Scripts may contain '<', so this
+        // can't be handled like other tags.
+        if let Some(hidden) = hidden_element.take() {
+            let closing_tag = format!("</{}", hidden);
+            let end = rest.to_ascii_lowercase().find(&closing_tag);
+            rest = end.map_or("", |end| &rest[end..]);
+            continue;
+        }
+
+        let Some(tag_start) = rest.find('<') else {
+            push_text(&mut text, rest);
+            break;
+        };
+        push_text(&mut text, &rest[..tag_start]);
+        rest = &rest[tag_start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(tag_end) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[1..tag_end];
+        rest = &rest[tag_end + 1..];
+
+        let closing = tag.starts_with('/');
+        let tag = tag.trim_start_matches('/').trim_end_matches('/');
+        let (name, attributes) = tag
+            .split_once(|c: char| c.is_ascii_whitespace())
+            .unwrap_or((tag, ""));
+        let name = name.to_ascii_lowercase();
+
+        let centered = center_depth > 0;
+        match name.as_str() {
+            "head" | "script" | "style" | "title" if !closing => {
+                hidden_element = Some(name);
+            }
+            "br" => text.push('\n'),
+            "a" => {
+                // Link text becomes its own block so that it can be tapped.
+                let href = (!closing)
+                    .then(|| get_attribute(attributes, "href"))
+                    .flatten();
+                if closing || href.is_some() {
+                    flush(&mut blocks, &mut text, heading_level, centered, &link);
+                    link = href;
+                }
+            }
+            "center" => {
+                flush(&mut blocks, &mut text, heading_level, centered, &link);
+                if closing {
+                    center_depth = center_depth.saturating_sub(1);
+                } else {
+                    center_depth += 1;
+                }
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                flush(&mut blocks, &mut text, heading_level, centered, &link);
+                heading_level = if closing {
+                    0
+                } else {
+                    name.as_bytes()[1] - b'0'
+                };
+            }
+            "p" | "div" | "tr" | "table" | "ul" | "ol" | "dl" | "dt" | "dd" | "hr"
+            | "blockquote" | "pre" | "body" | "html" => {
+                flush(&mut blocks, &mut text, heading_level, centered, &link);
+            }
+            "li" => {
+                flush(&mut blocks, &mut text, heading_level, centered, &link);
+                if !closing {
+                    text.push_str("\u{2022} ");
+                }
+            }
+            "img" if !closing => {
+                flush(&mut blocks, &mut text, heading_level, centered, &link);
+                if let Some(src) = get_attribute(attributes, "src") {
+                    blocks.push(HtmlBlock::Image { src, centered });
+                }
+            }
+            _ => (),
+        }
+    }
+    flush(
+        &mut blocks,
+        &mut text,
+        heading_level,
+        center_depth > 0,
+        &link,
+    );
+    blocks
+}
+
+/// Get the text of the `<title>` element, or an empty string if there is none.
+fn find_title(html: &str) -> String {
+    // ASCII lowercasing doesn't change byte offsets.
+    let lowercase = html.to_ascii_lowercase();
+    let Some(start) = lowercase.find("<title") else {
+        return String::new();
+    };
+    let Some(content_start) = lowercase[start..].find('>').map(|end| start + end + 1) else {
+        return String::new();
+    };
+    let content_end = lowercase[content_start..]
+        .find("</title")
+        .map_or(html.len(), |end| content_start + end);
+    let mut title = String::new();
+    push_text(&mut title, &html[content_start..content_end]);
+    title.trim().to_string()
+}
+
+/// Append text content, collapsing whitespace and decoding entities.
+fn push_text(text: &mut String, raw: &str) {
+    let decoded = decode_entities(raw);
+    for c in decoded.chars() {
+        if c.is_whitespace() && c != '\u{a0}' {
+            if !text.is_empty() && !text.ends_with([' ', '\n']) {
+                text.push(' ');
+            }
+        } else if c == '\u{a0}' {
+            text.push(' ');
+        } else {
+            text.push(c);
+        }
+    }
+}
+
+fn decode_entities(raw: &str) -> String {
+    let mut decoded = String::new();
+    let mut rest = raw;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 8)
+            .map(|end| &rest[1..end + 1]);
+        let c = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            "copy" => Some('\u{a9}'),
+            _ => {
+                let number = entity.strip_prefix('#')?;
+                let number = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => number.parse().ok()?,
+                };
+                char::from_u32(number)
+            }
+        });
+        if let (Some(c), Some(entity)) = (c, entity) {
+            decoded.push(c);
+            rest = &rest[entity.len() + 2..];
+        } else {
+            decoded.push('&');
+            rest = &rest[1..];
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn get_attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    loop {
+        rest = rest.trim_start();
+        let name_end = rest.find(|c: char| c == '=' || c.is_ascii_whitespace())?;
+        let attribute_name = &rest[..name_end];
+        rest = rest[name_end..].trim_start();
+        let Some(value_part) = rest.strip_prefix('=') else {
+            continue;
+        };
+        let value_part = value_part.trim_start();
+        let (value, after) = match value_part.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let value_part = &value_part[1..];
+                let end = value_part.find(quote).unwrap_or(value_part.len());
+                (&value_part[..end], value_part.get(end + 1..).unwrap_or(""))
+            }
+            _ => {
+                let end = value_part
+                    .find(|c: char| c.is_ascii_whitespace())
+                    .unwrap_or(value_part.len());
+                (&value_part[..end], &value_part[end..])
+            }
+        };
+        if attribute_name.eq_ignore_ascii_case(name) {
+            return Some(decode_entities(value));
+        }
+        rest = after;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn title() {
+        assert_eq!(
+            find_title("<HEAD><Title> Tips &amp;\n Tricks </Title></HEAD>"),
+            "Tips & Tricks"
+        );
+        assert_eq!(find_title("<p>No title</p>"), "");
+    }
+
+    #[test]
+    fn file_urls() {
+        let base_dir = GuestPath::new("/app/Game.app/help");
+        let resolve = |url| resolve_file_url(Some(base_dir), url).map(String::from);
+        assert_eq!(
+            resolve("images/my%20logo.png?v=2"),
+            Ok("/app/Game.app/help/images/my logo.png".to_string())
+        );
+        assert_eq!(
+            resolve("/app/Documents/page.html#top"),
+            Ok("/app/Documents/page.html".to_string())
+        );
+        assert_eq!(
+            resolve("file://localhost/app/Game.app/a.png"),
+            Ok("/app/Game.app/a.png".to_string())
+        );
+        assert!(resolve("http://example.com/a.png").is_err());
+        assert!(resolve_file_url(None, "a.png").is_err());
+    }
+
+    #[test]
+    fn simple_page() {
+        let html = "<html><head><title>Help</title></head><body>\
+            <center><h1>My&nbsp;Game</h1><img src=\"logo.png\" width=100></center>\
+            <p>Tap   the\nscreen<br>to jump &amp; run.</p>\
+            <ul><li>One</li><li>Two</li></ul>\
+            <a name=top>See</a> <a href=\"more.html\">more</a>\
+            <script>var x = 1 < 2;</script><!-- <p>hidden</p> --></body></html>";
+        assert_eq!(
+            parse_html(html),
+            [
+                HtmlBlock::Text {
+                    text: "My Game".to_string(),
+                    heading_level: 1,
+                    centered: true,
+                    link: None,
+                },
+                HtmlBlock::Image {
+                    src: "logo.png".to_string(),
+                    centered: true,
+                },
+                HtmlBlock::Text {
+                    text: "Tap the screen\nto jump & run.".to_string(),
+                    heading_level: 0,
+                    centered: false,
+                    link: None,
+                },
+                HtmlBlock::Text {
+                    text: "\u{2022} One".to_string(),
+                    heading_level: 0,
+                    centered: false,
+                    link: None,
+                },
+                HtmlBlock::Text {
+                    text: "\u{2022} Two".to_string(),
+                    heading_level: 0,
+                    centered: false,
+                    link: None,
+                },
+                HtmlBlock::Text {
+                    text: "See".to_string(),
+                    heading_level: 0,
+                    centered: false,
+                    link: None,
+                },
+                HtmlBlock::Text {
+                    text: "more".to_string(),
+                    heading_level: 0,
+                    centered: false,
+                    link: Some("more.html".to_string()),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,194 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! A tiny evaluator for the JavaScript passed to
+//! `stringByEvaluatingJavaScriptFromString:`.
+//!
+//! Apps mostly use this to read something back from the page, so only
+//! expressions are supported: string and number literals, `document.title`,
+//! parentheses and `+`. Statements can be separated by `;`, and the value of
+//! the last one is the result.
+
+/// What scripts can see of the page.
+pub struct Document<'a> {
+    pub title: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum JsValue {
+    String(String),
+    Number(f64),
+}
+impl JsValue {
+    fn to_js_string(&self) -> String {
+        match *self {
+            JsValue::String(ref string) => string.clone(),
+            JsValue::Number(number) if number.is_nan() => "NaN".to_string(),
+            JsValue::Number(number) if number.is_infinite() => if number > 0.0 {
+                "Infinity"
+            } else {
+                "-Infinity"
+            }
+            .to_string(),
+            // Integers are printed without a fractional part, like in JS.
+            // Rust's formatting of other numbers is close enough.
+            JsValue::Number(number) => format!("{}", number),
+        }
+    }
+}
+
+/// Evaluate a script and convert its result to a string, like
+/// `stringByEvaluatingJavaScriptFromString:` does.
+pub fn evaluate(script: &str, document: &Document) -> Result<String, String> {
+    let mut evaluator = Evaluator {
+        chars: script.chars().peekable(),
+        document,
+    };
+    let mut result = None;
+    loop {
+        evaluator.skip_whitespace();
+        match evaluator.chars.peek() {
+            None => break,
+            Some(';') => {
+                evaluator.chars.next();
+            }
+            Some(_) => {
+                result = Some(evaluator.parse_expression()?);
+                evaluator.skip_whitespace();
+                match evaluator.chars.next() {
+                    None | Some(';') => (),
+                    Some(c) => return Err(format!("Unexpected {:?}", c)),
+                }
+            }
+        }
+    }
+    // An undefined result becomes an empty string.
+    Ok(result.map_or_else(String::new, |value| value.to_js_string()))
+}
+
+struct Evaluator<'a, 'b> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    document: &'b Document<'b>,
+}
+impl Evaluator<'_, '_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    /// `term (+ term)*`
+    fn parse_expression(&mut self) -> Result<JsValue, String> {
+        let mut value = self.parse_term()?;
+        loop {
+            self.skip_whitespace();
+            if self.chars.next_if_eq(&'+').is_none() {
+                return Ok(value);
+            }
+            let rhs = self.parse_term()?;
+            value = match (value, rhs) {
+                (JsValue::Number(a), JsValue::Number(b)) => JsValue::Number(a + b),
+                (a, b) => JsValue::String(a.to_js_string() + &b.to_js_string()),
+            };
+        }
+    }
+
+    fn parse_term(&mut self) -> Result<JsValue, String> {
+        self.skip_whitespace();
+        match self.chars.peek().copied() {
+            Some('(') => {
+                self.chars.next();
+                let value = self.parse_expression()?;
+                self.skip_whitespace();
+                match self.chars.next() {
+                    Some(')') => Ok(value),
+                    _ => Err("Expected ')'".to_string()),
+                }
+            }
+            Some(quote @ ('"' | '\'')) => {
+                self.chars.next();
+                self.parse_string(quote).map(JsValue::String)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.parse_number(),
+            Some(c) if c.is_alphabetic() || c == '_' || c == '$' => {
+                let path = self.parse_path();
+                match path.as_str() {
+                    "document.title" => Ok(JsValue::String(self.document.title.to_string())),
+                    _ => Err(format!("Unsupported expression {:?}", path)),
+                }
+            }
+            Some(c) => Err(format!("Unexpected {:?}", c)),
+            None => Err("Unexpected end of script".to_string()),
+        }
+    }
+
+    /// Parse the rest of a string literal, after the opening quote.
+    fn parse_string(&mut self, quote: char) -> Result<String, String> {
+        let mut string = String::new();
+        loop {
+            match self.chars.next() {
+                Some(c) if c == quote => return Ok(string),
+                Some('\\') => match self.chars.next() {
+                    Some('n') => string.push('\n'),
+                    Some('t') => string.push('\t'),
+                    Some('r') => string.push('\r'),
+                    Some('u') => {
+                        let hex: String = (0..4).filter_map(|_| self.chars.next()).collect();
+                        let c = u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("Invalid escape \\u{}", hex))?;
+                        string.push(c);
+                    }
+                    Some(c) => string.push(c),
+                    None => return Err("Unterminated string".to_string()),
+                },
+                Some(c) => string.push(c),
+                None => return Err("Unterminated string".to_string()),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<JsValue, String> {
+        let mut number = String::new();
+        while let Some(c) = self.chars.next_if(|&c| c.is_ascii_digit() || c == '.') {
+            number.push(c);
+        }
+        number
+            .parse()
+            .map(JsValue::Number)
+            .map_err(|_| format!("Invalid number {:?}", number))
+    }
+
+    /// Parse an identifier with property accesses, e.g. `document.title`.
+    fn parse_path(&mut self) -> String {
+        let mut path = String::new();
+        while let Some(c) = self
+            .chars
+            .next_if(|&c| c.is_alphanumeric() || c == '_' || c == '$' || c == '.')
+        {
+            path.push(c);
+        }
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expressions() {
+        let document = Document { title: "Help" };
+        let eval = |script| evaluate(script, &document);
+        assert_eq!(eval("document.title").unwrap(), "Help");
+        assert_eq!(
+            eval("'Page: ' + document.title + \"\\n\";").unwrap(),
+            "Page: Help\n"
+        );
+        assert_eq!(eval("1 + 2 + 'x' + (1 + 2)").unwrap(), "3x3");
+        assert_eq!(eval("0.5; 'last'").unwrap(), "last");
+        assert_eq!(eval("").unwrap(), "");
+        assert!(eval("window.alert('hi')").is_err());
+    }
+}
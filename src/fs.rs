@@ -22,18 +22,26 @@
 //! Directories only need a corresponding directory in the host filesystem if
 //! they are writeable (i.e. if new files can be created in them).
 //!
+//! How much the app can write is limited by a [DiskQuota], so that it sees the
+//! free space set with `--disk-space=` run out like on a real device.
+//!
 //! See also [crate::paths], which has paths for host files used by touchHLE.
 
 mod bundle;
+mod pipe;
 
 pub use bundle::BundleData;
 
 use crate::fs::bundle::{IpaFile, IpaFileRef};
+use crate::fs::pipe::PipeEnd;
 use crate::paths;
+use crate::random::Rng;
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{Read, Seek, Write};
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 /// The actual location of a file outside the virtual filesystem, e.g. a host
 /// file path.
@@ -341,17 +349,63 @@ fn handle_open_err<T, E: std::fmt::Display, P: std::fmt::Debug>(
     }
 }
 
+/// The free space left for the app to write to, shared between [Fs] and the
+/// host files it opens. Files growing uses it up, and files shrinking or being
+/// deleted gives it back, like on a device. Files that were already there when
+/// touchHLE started aren't counted against it.
+#[derive(Debug, Clone)]
+pub struct DiskQuota(Rc<Cell<u64>>);
+impl DiskQuota {
+    fn new(free_space: u64) -> DiskQuota {
+        DiskQuota(Rc::new(Cell::new(free_space)))
+    }
+
+    /// Take some free space, or fail like a full disk if there isn't enough.
+    fn take(&self, bytes: u64) -> std::io::Result<()> {
+        let free_space = self.0.get();
+        if bytes > free_space {
+            log!(
+                "Warning: App tried to use {} bytes but only {} are free, failing with ENOSPC",
+                bytes,
+                free_space
+            );
+            return Err(std::io::Error::from(std::io::ErrorKind::StorageFull));
+        }
+        self.0.set(free_space - bytes);
+        Ok(())
+    }
+
+    fn give_back(&self, bytes: u64) {
+        self.0.set(self.0.get().saturating_add(bytes));
+    }
+
+    /// Take or give back free space for a file changing size.
+    fn resize(&self, old_len: u64, new_len: u64) -> std::io::Result<()> {
+        if new_len > old_len {
+            self.take(new_len - old_len)
+        } else {
+            self.give_back(old_len - new_len);
+            Ok(())
+        }
+    }
+}
+
 /// Like [File] but for the guest filesystem.
 #[derive(Debug)]
 pub enum GuestFile {
-    File(File),
+    /// Host file, with the quota that writes to it are limited by.
+    File(File, DiskQuota),
     IpaBundleFile(IpaFile),
     ResourceFile(paths::ResourceFile),
+    /// `/dev/random` or `/dev/urandom`, see [crate::random].
+    RandomDevice(Rng),
+    /// One end of a pipe created with `pipe()`, see [pipe].
+    Pipe(PipeEnd),
 }
 
 impl GuestFile {
-    fn from_host_file(file: File) -> GuestFile {
-        GuestFile::File(file)
+    fn from_host_file(file: File, disk_quota: &DiskQuota) -> GuestFile {
+        GuestFile::File(file, disk_quota.clone())
     }
 
     fn from_ipa_file(file: &IpaFileRef) -> GuestFile {
@@ -362,21 +416,64 @@ impl GuestFile {
         GuestFile::ResourceFile(file)
     }
 
+    pub fn random_device(rng: Rng) -> GuestFile {
+        GuestFile::RandomDevice(rng)
+    }
+
+    /// Create a pipe, returning the read end and the write end.
+    pub fn pipe() -> (GuestFile, GuestFile) {
+        let (read_end, write_end) = pipe::new_pipe();
+        (GuestFile::Pipe(read_end), GuestFile::Pipe(write_end))
+    }
+
+    /// Make another handle for the same open file, for `dup()`. Like with
+    /// [File::try_clone], the handles share a file position.
+    pub fn try_clone(&self) -> std::io::Result<GuestFile> {
+        match self {
+            GuestFile::File(file, disk_quota) => file
+                .try_clone()
+                .map(|file| GuestFile::from_host_file(file, disk_quota)),
+            GuestFile::RandomDevice(rng) => Ok(GuestFile::RandomDevice(rng.fork())),
+            GuestFile::Pipe(pipe_end) => Ok(GuestFile::Pipe(pipe_end.clone())),
+            // TODO: These would need a shared position.
+            GuestFile::IpaBundleFile(_) | GuestFile::ResourceFile(_) => {
+                Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+            }
+        }
+    }
+
     pub fn sync_all(&self) -> std::io::Result<()> {
         match self {
-            GuestFile::File(file) => file.sync_all(),
-            GuestFile::IpaBundleFile(_) | GuestFile::ResourceFile(_) => Ok(()),
+            GuestFile::File(file, _) => file.sync_all(),
+            GuestFile::IpaBundleFile(_)
+            | GuestFile::ResourceFile(_)
+            | GuestFile::RandomDevice(_)
+            | GuestFile::Pipe(_) => Ok(()),
+        }
+    }
+    pub fn sync_data(&self) -> std::io::Result<()> {
+        match self {
+            GuestFile::File(file, _) => file.sync_data(),
+            GuestFile::IpaBundleFile(_)
+            | GuestFile::ResourceFile(_)
+            | GuestFile::RandomDevice(_)
+            | GuestFile::Pipe(_) => Ok(()),
         }
     }
     pub fn set_len(&self, len: u64) -> std::io::Result<()> {
         match self {
-            GuestFile::File(file) => file.set_len(len),
+            GuestFile::File(file, disk_quota) => {
+                disk_quota.resize(file.metadata()?.len(), len)?;
+                file.set_len(len)
+            }
             GuestFile::IpaBundleFile(file) => {
                 panic!("Attempt to resize a read-only file: {:?}", file)
             }
             GuestFile::ResourceFile(file) => {
                 panic!("Attempt to resize a read-only file: {:?}", file)
             }
+            GuestFile::RandomDevice(_) => panic!("Attempt to resize a random device"),
+            GuestFile::Pipe(_) => Err(std::io::Error::from(std::io::ErrorKind::InvalidInput)),
         }
     }
 }
@@ -384,9 +481,14 @@ impl GuestFile {
 impl Read for GuestFile {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         match self {
-            GuestFile::File(file) => file.read(buf),
+            GuestFile::File(file, _) => file.read(buf),
             GuestFile::IpaBundleFile(file) => file.read(buf),
             GuestFile::ResourceFile(file) => file.get().read(buf),
+            GuestFile::RandomDevice(rng) => {
+                rng.fill_bytes(buf);
+                Ok(buf.len())
+            }
+            GuestFile::Pipe(pipe_end) => pipe_end.read(buf),
         }
     }
 }
@@ -394,25 +496,41 @@ impl Read for GuestFile {
 impl Write for GuestFile {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         match self {
-            GuestFile::File(file) => file.write(buf),
+            GuestFile::File(file, disk_quota) => {
+                // Writes to a file opened for appending happen at the end
+                // whatever the position is, but the callers seek there first.
+                let old_len = file.metadata()?.len();
+                let end = file.stream_position()? + u64::try_from(buf.len()).unwrap();
+                disk_quota.resize(old_len, end.max(old_len))?;
+                let result = file.write(buf);
+                // Give back whatever wasn't written after all.
+                let new_len = file.metadata()?.len();
+                disk_quota.give_back(end.max(old_len).saturating_sub(new_len.max(old_len)));
+                result
+            }
             GuestFile::IpaBundleFile(file) => {
                 panic!("Attempt to write to a read-only file: {:?}", file)
             }
             GuestFile::ResourceFile(file) => {
                 panic!("Attempt to write to a read-only file: {:?}", file)
             }
+            // Writing adds entropy on a real device, but the output has to
+            // stay reproducible here, so it's ignored.
+            GuestFile::RandomDevice(_) => Ok(buf.len()),
+            GuestFile::Pipe(pipe_end) => pipe_end.write(buf),
         }
     }
 
     fn flush(&mut self) -> std::io::Result<()> {
         match self {
-            GuestFile::File(file) => file.flush(),
+            GuestFile::File(file, _) => file.flush(),
             GuestFile::IpaBundleFile(file) => {
                 panic!("Attempt to flush a read-only file: {:?}", file)
             }
             GuestFile::ResourceFile(file) => {
                 panic!("Attempt to flush a read-only file: {:?}", file)
             }
+            GuestFile::RandomDevice(_) | GuestFile::Pipe(_) => Ok(()),
         }
     }
 }
@@ -420,9 +538,12 @@ impl Write for GuestFile {
 impl Seek for GuestFile {
     fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
         match self {
-            GuestFile::File(file) => file.seek(pos),
+            GuestFile::File(file, _) => file.seek(pos),
             GuestFile::IpaBundleFile(file) => file.seek(pos),
             GuestFile::ResourceFile(file) => file.get().seek(pos),
+            GuestFile::RandomDevice(_) => Ok(0),
+            // Pipes can't seek.
+            GuestFile::Pipe(_) => Err(std::io::Error::from(std::io::ErrorKind::Unsupported)),
         }
     }
 }
@@ -433,6 +554,7 @@ pub struct Fs {
     root: FsNode,
     working_directory: GuestPathBuf,
     home_directory: GuestPathBuf,
+    disk_quota: DiskQuota,
 }
 impl Fs {
     /// Construct a filesystem containing a home directory for the app, its
@@ -467,20 +589,26 @@ impl Fs {
 
         let bundle_guest_path = home_directory.join(&bundle_dir_name);
 
-        let documents_host_path = if !read_only_mode {
-            let path = paths::user_data_base_path()
-                .join(paths::SANDBOX_DIR)
-                .join(bundle_id)
-                .join("Documents");
-            if let Err(e) = std::fs::create_dir_all(&path) {
-                panic!(
-                    "Could not create documents directory for app at {:?}: {:?}",
-                    path, e
-                );
-            }
-            Some(path)
+        // The writeable directories of the app's sandbox.
+        let sandbox_host_paths = if !read_only_mode {
+            ["Documents", "Library"]
+                .into_iter()
+                .map(|dir_name| {
+                    let path = paths::user_data_base_path()
+                        .join(paths::SANDBOX_DIR)
+                        .join(bundle_id)
+                        .join(dir_name);
+                    if let Err(e) = std::fs::create_dir_all(&path) {
+                        panic!(
+                            "Could not create {} directory for app at {:?}: {:?}",
+                            dir_name, path, e
+                        );
+                    }
+                    (dir_name, path)
+                })
+                .collect()
         } else {
-            None
+            Vec::new()
         };
 
         // Some Free Software libraries are bundled with touchHLE.
@@ -502,10 +630,10 @@ impl Fs {
 
         let mut app_dir_children = HashMap::new();
         app_dir_children.insert(bundle_dir_name, app_bundle.into_fs_node());
-        if let Some(documents_host_path) = documents_host_path {
+        for (dir_name, host_path) in sandbox_host_paths {
             app_dir_children.insert(
-                "Documents".to_string(),
-                FsNode::from_host_dir(&documents_host_path, /* writeable: */ true),
+                dir_name.to_string(),
+                FsNode::from_host_dir(&host_path, /* writeable: */ true),
             );
         }
 
@@ -534,6 +662,8 @@ impl Fs {
             root,
             working_directory,
             home_directory,
+            // See Self::set_free_space().
+            disk_quota: DiskQuota::new(u64::MAX),
         };
         assert!(fs.lookup_node(&bundle_guest_path).is_some());
         (fs, bundle_guest_path)
@@ -545,9 +675,20 @@ impl Fs {
             root: FsNode::dir(),
             working_directory: GuestPathBuf::from(String::new()),
             home_directory: GuestPathBuf::from(String::new()),
+            disk_quota: DiskQuota::new(u64::MAX),
         }
     }
 
+    /// Set how much more the app can write (see [DiskQuota]).
+    pub fn set_free_space(&mut self, free_space: u64) {
+        self.disk_quota.0.set(free_space);
+    }
+
+    /// Get how much more the app can write (see [DiskQuota]).
+    pub fn free_space(&self) -> u64 {
+        self.disk_quota.0.get()
+    }
+
     /// Get the absolute path of the guest app's (sandboxed) home directory.
     pub fn home_directory(&self) -> &GuestPath {
         &self.home_directory
@@ -733,7 +874,7 @@ impl Fs {
             FsNode::File { location, .. } => match location {
                 FileLocation::Path(host_path) => {
                     let host_file = handle_open_err(File::open(host_path), host_path);
-                    Ok(GuestFile::from_host_file(host_file))
+                    Ok(GuestFile::from_host_file(host_file, &self.disk_quota))
                 }
                 FileLocation::IpaFileRef(file) => Ok(GuestFile::from_ipa_file(file)),
                 FileLocation::ResourceFilePath(name) => {
@@ -762,6 +903,7 @@ impl Fs {
 
         let path = path.as_ref();
 
+        let disk_quota = self.disk_quota.clone();
         let (parent_node, new_filename) = self.lookup_parent_node(path).ok_or(())?;
         let FsNode::Directory {
             children,
@@ -785,6 +927,12 @@ impl Fs {
                     }
                     match location {
                         FileLocation::Path(host_path) => {
+                            if truncate {
+                                let old_len = std::fs::metadata(host_path)
+                                    .map(|metadata| metadata.len())
+                                    .unwrap_or(0);
+                                disk_quota.give_back(old_len);
+                            }
                             let file = handle_open_err(
                                 File::options()
                                     .read(read)
@@ -795,7 +943,7 @@ impl Fs {
                                     .open(host_path),
                                 host_path,
                             );
-                            return Ok(GuestFile::File(file));
+                            return Ok(GuestFile::from_host_file(file, &disk_quota));
                         }
                         FileLocation::IpaFileRef(file) => {
                             assert!(!(writeable || append || write));
@@ -859,7 +1007,7 @@ impl Fs {
                 writeable: true,
             },
         );
-        Ok(GuestFile::File(file))
+        Ok(GuestFile::from_host_file(file, &disk_quota))
     }
 
     /// Removes a file or a directory. If the node is a directory, it must be
@@ -867,6 +1015,7 @@ impl Fs {
     pub fn remove<P: AsRef<GuestPath>>(&mut self, path: P) -> Result<(), ()> {
         let path = path.as_ref();
 
+        let disk_quota = self.disk_quota.clone();
         let (parent_node, node_name) = self.lookup_parent_node(path).ok_or(())?;
 
         // Parent directory is not a directory
@@ -904,7 +1053,11 @@ impl Fs {
                     FileLocation::IpaFileRef(_) | FileLocation::ResourceFilePath(_) => panic!(),
                 };
 
+                let len = std::fs::metadata(host_path)
+                    .map(|metadata| metadata.len())
+                    .unwrap_or(0);
                 handle_open_err(std::fs::remove_file(host_path), host_path);
+                disk_quota.give_back(len);
                 log_dbg!(
                     "Deleted file at path {:?} (host path: {:?})",
                     path,
@@ -0,0 +1,107 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Anonymous pipes, as created by `pipe()`.
+//!
+//! Pipes only exist within the guest, so the buffer is just host memory shared
+//! between the two ends. It has no size limit, so writing never blocks.
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind};
+use std::rc::{Rc, Weak};
+
+/// One end of a pipe. Each file descriptor for it holds a clone, so that the
+/// other end can tell when the last one has been closed.
+#[derive(Debug, Clone)]
+pub struct PipeEnd {
+    buffer: Rc<RefCell<VecDeque<u8>>>,
+    this_end: Rc<()>,
+    other_end: Weak<()>,
+}
+
+/// Create a pipe, returning the read end and the write end.
+pub fn new_pipe() -> (PipeEnd, PipeEnd) {
+    let buffer = Rc::new(RefCell::new(VecDeque::new()));
+    let read_end = Rc::new(());
+    let write_end = Rc::new(());
+    (
+        PipeEnd {
+            buffer: buffer.clone(),
+            other_end: Rc::downgrade(&write_end),
+            this_end: read_end.clone(),
+        },
+        PipeEnd {
+            buffer,
+            other_end: Rc::downgrade(&read_end),
+            this_end: write_end,
+        },
+    )
+}
+
+impl PipeEnd {
+    fn other_end_open(&self) -> bool {
+        self.other_end.strong_count() > 0
+    }
+
+    /// Read from the read end. Returns [ErrorKind::WouldBlock] if the pipe is
+    /// empty but could still be written to, and 0 bytes (end of file) if the
+    /// pipe is empty and the write end has been closed.
+    pub fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut buffer = self.buffer.borrow_mut();
+        if buffer.is_empty() && !buf.is_empty() {
+            return if self.other_end_open() {
+                Err(Error::from(ErrorKind::WouldBlock))
+            } else {
+                Ok(0)
+            };
+        }
+        let count = buf.len().min(buffer.len());
+        for (dest, src) in buf.iter_mut().zip(buffer.drain(..count)) {
+            *dest = src;
+        }
+        Ok(count)
+    }
+
+    /// Write to the write end. Fails with [ErrorKind::BrokenPipe] if the read
+    /// end has been closed.
+    pub fn write(&self, buf: &[u8]) -> std::io::Result<usize> {
+        if !self.other_end_open() {
+            return Err(Error::from(ErrorKind::BrokenPipe));
+        }
+        self.buffer.borrow_mut().extend(buf);
+        Ok(buf.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pipe() {
+        let (read_end, write_end) = new_pipe();
+        let mut buf = [0u8; 4];
+        assert_eq!(
+            read_end.read(&mut buf).unwrap_err().kind(),
+            ErrorKind::WouldBlock
+        );
+        assert_eq!(write_end.write(b"hello").unwrap(), 5);
+        let write_end_dup = write_end.clone();
+        drop(write_end);
+        assert_eq!(read_end.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf, b"hell");
+        drop(write_end_dup);
+        assert_eq!(read_end.read(&mut buf).unwrap(), 1);
+        assert_eq!(read_end.read(&mut buf).unwrap(), 0);
+
+        let (read_end, write_end) = new_pipe();
+        drop(read_end);
+        assert_eq!(
+            write_end.write(b"x").unwrap_err().kind(),
+            ErrorKind::BrokenPipe
+        );
+    }
+}
@@ -349,6 +349,21 @@ impl GdbServer {
                             // Unsupported annex or invalid offset
                             self.send_packet("E00");
                         }
+                    // Command sent with GDB's `monitor` command
+                    } else if let Some(command) = p.strip_prefix("qRcmd,") {
+                        let command: Vec<u8> = (0..command.len() / 2)
+                            .map(|i| u8::from_str_radix(&command[i * 2..][..2], 16).unwrap())
+                            .collect();
+                        let output = handle_monitor_command(&String::from_utf8_lossy(&command));
+                        // Console output is sent as hex-encoded "O" packets
+                        // before the final reply.
+                        let mut packet = String::with_capacity(1 + output.len() * 2);
+                        packet.push('O');
+                        for byte in output.bytes() {
+                            write!(packet, "{:02x}", byte).unwrap();
+                        }
+                        self.send_packet(&packet);
+                        self.send_packet("OK");
                     } else {
                         log_dbg!("Unhandled packet.");
                         // Tell GDB we don't understand this packet.
@@ -369,3 +384,21 @@ impl GdbServer {
         do_step
     }
 }
+
+/// Handle a command sent with GDB's `monitor` command, returning the text to
+/// show to the user.
+fn handle_monitor_command(command: &str) -> String {
+    let mut words = command.split_ascii_whitespace();
+    match (words.next(), words.next(), words.next()) {
+        (Some("log"), None, None) => {
+            format!("Log levels: {}\n", crate::log::describe_levels())
+        }
+        (Some("log"), Some(spec), None) => match crate::log::apply_level_spec(spec) {
+            Ok(()) => format!("Log levels: {}\n", crate::log::describe_levels()),
+            Err(e) => format!("{}\n", e),
+        },
+        _ => "Supported commands: log, log <levels> (see --log-level= in \
+              OPTIONS_HELP.txt)\n"
+            .to_string(),
+    }
+}
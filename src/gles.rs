@@ -24,10 +24,13 @@
 //!   - [gles1_native] passes through native OpenGL ES 1.1.
 //!   - [gles1_on_gl2] provides an implementation of OpenGL ES 1.1 using OpenGL
 //!     2.1 compatibility profile.
-//!   - There might be more in future.
+//!   - [gles2_native] passes through native OpenGL ES 2.0.
+//!   - [gles2_on_gl2] provides an implementation of OpenGL ES 2.0 using OpenGL
+//!     2.1 compatibility profile.
 //! - [gles11_raw] provides raw bindings for OpenGL ES 1.1 generated from the
 //!   Khronos API headers. **The function bindings are only for use within this
 //!   module.** The constants and types can be used outside it, however.
+//!   - [gles2_raw] is the same thing, but for OpenGL ES 2.0.
 //!   - [gl21compat_raw] is the same thing, but for OpenGL 2.1 compatibility
 //!     profile, which can't be used outside this module at all.
 //! - [present] provides utilities for presenting frames to the window using an
@@ -47,9 +50,15 @@
 //!   - [OES_matrix_palette](https://registry.khronos.org/OpenGL/extensions/OES/OES_matrix_palette.txt)
 //!   - [EXT_texture_format_BGRA8888](https://registry.khronos.org/OpenGL/extensions/EXT/EXT_texture_format_BGRA8888.txt)
 //!
+//! Useful resources for OpenGL ES 2.0:
+//! - [Reference pages](https://registry.khronos.org/OpenGL-Refpages/es2.0/xhtml/)
+//! - [Specification](https://registry.khronos.org/OpenGL/specs/es/2.0/es_full_spec_2.0.pdf)
+//! - [GLSL ES 1.00 specification](https://registry.khronos.org/OpenGL/specs/es/2.0/GLSL_ES_Specification_1.00.pdf)
+//!
 //! Useful resources for OpenGL 2.1:
 //! - [Reference pages](https://registry.khronos.org/OpenGL-Refpages/gl2.1/)
 //! - [Specification](https://registry.khronos.org/OpenGL/specs/gl/glspec21.pdf)
+//! - [GLSL 1.20 specification](https://registry.khronos.org/OpenGL/specs/gl/GLSLangSpec.1.20.pdf)
 //! - Extensions:
 //!   - [EXT_framebuffer_object](https://registry.khronos.org/OpenGL/extensions/EXT/EXT_framebuffer_object.txt)
 //!   - [ARB_matrix_palette](https://registry.khronos.org/OpenGL/extensions/ARB/ARB_matrix_palette.txt)
@@ -62,34 +71,47 @@
 
 pub mod gles1_native;
 pub mod gles1_on_gl2;
+pub mod gles2_native;
+pub mod gles2_on_gl2;
 mod gles_generic;
 pub mod present;
 mod util;
 
 use touchHLE_gl_bindings::gl21compat as gl21compat_raw;
 pub use touchHLE_gl_bindings::gles11 as gles11_raw;
+pub use touchHLE_gl_bindings::gles2 as gles2_raw;
 
 use gles1_native::GLES1Native;
 use gles1_on_gl2::GLES1OnGL2;
+use gles2_native::GLES2Native;
+use gles2_on_gl2::GLES2OnGL2;
 pub use gles_generic::GLES;
 
 /// Labels for [GLES] implementations and an abstraction for constructing them.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub enum GLESImplementation {
     /// [GLES1Native].
     GLES1Native,
     /// [GLES1OnGL2].
     GLES1OnGL2,
+    /// [GLES2Native].
+    GLES2Native,
+    /// [GLES2OnGL2].
+    GLES2OnGL2,
 }
 impl GLESImplementation {
     /// List of OpenGL ES 1.1 implementations in order of preference.
     pub const GLES1_IMPLEMENTATIONS: &'static [Self] = &[Self::GLES1Native, Self::GLES1OnGL2];
+    /// List of OpenGL ES 2.0 implementations in order of preference.
+    pub const GLES2_IMPLEMENTATIONS: &'static [Self] = &[Self::GLES2Native, Self::GLES2OnGL2];
     /// Convert from short name used for command-line arguments. Returns [Err]
     /// if name is not recognized..
     pub fn from_short_name(name: &str) -> Result<Self, ()> {
         match name {
             "gles1_on_gl2" => Ok(Self::GLES1OnGL2),
             "gles1_native" => Ok(Self::GLES1Native),
+            "gles2_on_gl2" => Ok(Self::GLES2OnGL2),
+            "gles2_native" => Ok(Self::GLES2Native),
             _ => Err(()),
         }
     }
@@ -98,45 +120,96 @@ impl GLESImplementation {
         match self {
             Self::GLES1Native => GLES1Native::description(),
             Self::GLES1OnGL2 => GLES1OnGL2::description(),
+            Self::GLES2Native => GLES2Native::description(),
+            Self::GLES2OnGL2 => GLES2OnGL2::description(),
         }
     }
+    /// Whether this is an OpenGL ES 2.0 implementation rather than an OpenGL
+    /// ES 1.1 implementation.
+    pub fn is_gles2(self) -> bool {
+        Self::GLES2_IMPLEMENTATIONS.contains(&self)
+    }
     /// See [GLES::new].
-    pub fn construct(self, window: &mut crate::window::Window) -> Result<Box<dyn GLES>, String> {
+    pub fn construct(
+        self,
+        window: &mut crate::window::Window,
+        share_with_current: bool,
+    ) -> Result<Box<dyn GLES>, String> {
         fn boxer<T: GLES + 'static>(ctx: T) -> Box<dyn GLES> {
             Box::new(ctx)
         }
         match self {
-            Self::GLES1Native => GLES1Native::new(window).map(boxer),
-            Self::GLES1OnGL2 => GLES1OnGL2::new(window).map(boxer),
+            Self::GLES1Native => GLES1Native::new(window, share_with_current).map(boxer),
+            Self::GLES1OnGL2 => GLES1OnGL2::new(window, share_with_current).map(boxer),
+            Self::GLES2Native => GLES2Native::new(window, share_with_current).map(boxer),
+            Self::GLES2OnGL2 => GLES2OnGL2::new(window, share_with_current).map(boxer),
         }
     }
 }
 
 /// Try to create an OpenGL ES 1.1 context using the configured strategies,
-/// panicking on failure.
+/// panicking on failure. Returns the context and the implementation used.
+///
+/// If `share_with` is provided, the new context will share objects with that
+/// context, and the implementation used for that context will be used.
 pub fn create_gles1_ctx(
     window: &mut crate::window::Window,
     options: &crate::options::Options,
-) -> Box<dyn GLES> {
+    share_with: Option<(GLESImplementation, &dyn GLES)>,
+) -> (Box<dyn GLES>, GLESImplementation) {
     log!("Creating an OpenGL ES 1.1 context:");
     let list = if let Some(ref preference) = options.gles1_implementation {
         std::slice::from_ref(preference)
     } else {
         GLESImplementation::GLES1_IMPLEMENTATIONS
     };
-    let mut gles1_ctx = None;
-    for implementation in list {
+    create_ctx(window, list, share_with).expect("Couldn't create OpenGL ES 1.1 context!")
+}
+
+/// Like [create_gles1_ctx], but for OpenGL ES 2.0. Returns [None] on failure
+/// rather than panicking, because an app can fall back to OpenGL ES 1.1.
+pub fn create_gles2_ctx(
+    window: &mut crate::window::Window,
+    options: &crate::options::Options,
+    share_with: Option<(GLESImplementation, &dyn GLES)>,
+) -> Option<(Box<dyn GLES>, GLESImplementation)> {
+    log!("Creating an OpenGL ES 2.0 context:");
+    let list = if let Some(ref preference) = options.gles2_implementation {
+        std::slice::from_ref(preference)
+    } else {
+        GLESImplementation::GLES2_IMPLEMENTATIONS
+    };
+    let res = create_ctx(window, list, share_with);
+    if res.is_none() {
+        log!("Couldn't create OpenGL ES 2.0 context!");
+    }
+    res
+}
+
+fn create_ctx(
+    window: &mut crate::window::Window,
+    list: &[GLESImplementation],
+    share_with: Option<(GLESImplementation, &dyn GLES)>,
+) -> Option<(Box<dyn GLES>, GLESImplementation)> {
+    let list = if let Some((ref implementation, _)) = share_with {
+        std::slice::from_ref(implementation)
+    } else {
+        list
+    };
+    for &implementation in list {
         log!("Trying: {}", implementation.description());
-        match implementation.construct(window) {
+        if let Some((_, share_with)) = share_with {
+            share_with.make_current(window);
+        }
+        match implementation.construct(window, share_with.is_some()) {
             Ok(ctx) => {
                 log!("=> Success!");
-                gles1_ctx = Some(ctx);
-                break;
+                return Some((ctx, implementation));
             }
             Err(err) => {
                 log!("=> Failed: {}.", err);
             }
         }
     }
-    gles1_ctx.expect("Couldn't create OpenGL ES 1.1 context!")
+    None
 }
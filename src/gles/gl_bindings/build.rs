@@ -18,6 +18,8 @@ fn main() {
         Fallbacks::None,
         [
             "GL_EXT_framebuffer_object",
+            "GL_EXT_framebuffer_multisample",
+            "GL_EXT_framebuffer_blit",
             "GL_EXT_texture_filter_anisotropic",
             "GL_EXT_texture_lod_bias",
             "GL_ARB_matrix_palette",
@@ -36,6 +38,7 @@ fn main() {
         [
             "GL_OES_framebuffer_object",
             "GL_OES_rgb8_rgba8",
+            "GL_APPLE_framebuffer_multisample",
             "GL_EXT_texture_filter_anisotropic",
             "GL_IMG_texture_compression_pvrtc",
             "GL_EXT_texture_lod_bias",
@@ -48,4 +51,21 @@ fn main() {
     )
     .write_bindings(GlobalGenerator, &mut file)
     .unwrap();
+
+    let mut file = File::create(out_dir.join("gles2.rs")).unwrap();
+    Registry::new(
+        Api::Gles2,
+        (2, 0),
+        Profile::Core,
+        Fallbacks::None,
+        [
+            "GL_OES_rgb8_rgba8",
+            "GL_APPLE_framebuffer_multisample",
+            "GL_EXT_texture_filter_anisotropic",
+            "GL_IMG_texture_compression_pvrtc",
+            "GL_EXT_texture_format_BGRA8888",
+        ],
+    )
+    .write_bindings(GlobalGenerator, &mut file)
+    .unwrap();
 }
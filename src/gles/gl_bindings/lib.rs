@@ -18,3 +18,7 @@ pub mod gl21compat {
 pub mod gles11 {
     include!(concat!(env!("OUT_DIR"), "/gles11.rs"));
 }
+#[allow(warnings)]
+pub mod gles2 {
+    include!(concat!(env!("OUT_DIR"), "/gles2.rs"));
+}
@@ -27,15 +27,16 @@ impl GLES for GLES1Native {
         "Native OpenGL ES 1.1"
     }
 
-    fn new(window: &mut Window) -> Result<Self, String> {
+    fn new(window: &mut Window, share_with_current: bool) -> Result<Self, String> {
         Ok(Self {
-            gl_ctx: window.create_gl_context(GLVersion::GLES11)?,
+            gl_ctx: window.create_gl_context(GLVersion::GLES11, share_with_current)?,
         })
     }
 
-    fn make_current(&self, window: &Window) {
-        unsafe { window.make_gl_context_current(&self.gl_ctx) };
-        gles11::load_with(|s| window.gl_get_proc_address(s))
+    fn try_make_current(&self, window: &Window) -> Result<(), String> {
+        unsafe { window.make_gl_context_current(&self.gl_ctx)? };
+        gles11::load_with(|s| window.gl_get_proc_address(s));
+        Ok(())
     }
 
     unsafe fn driver_description(&self) -> String {
@@ -677,6 +678,15 @@ impl GLES for GLES1Native {
     ) {
         gles11::GetRenderbufferParameterivOES(target, pname, params)
     }
+    unsafe fn GetFramebufferAttachmentParameterivOES(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    ) {
+        gles11::GetFramebufferAttachmentParameterivOES(target, attachment, pname, params)
+    }
     unsafe fn CheckFramebufferStatusOES(&mut self, target: GLenum) -> GLenum {
         gles11::CheckFramebufferStatusOES(target)
     }
@@ -689,4 +699,19 @@ impl GLES for GLES1Native {
     unsafe fn GenerateMipmapOES(&mut self, target: GLenum) {
         gles11::GenerateMipmapOES(target)
     }
+
+    // APPLE_framebuffer_multisample
+    unsafe fn RenderbufferStorageMultisampleAPPLE(
+        &mut self,
+        target: GLenum,
+        samples: GLsizei,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    ) {
+        gles11::RenderbufferStorageMultisampleAPPLE(target, samples, internalformat, width, height)
+    }
+    unsafe fn ResolveMultisampleFramebufferAPPLE(&mut self) {
+        gles11::ResolveMultisampleFramebufferAPPLE()
+    }
 }
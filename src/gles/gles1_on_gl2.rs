@@ -22,8 +22,8 @@ use super::gl21compat_raw as gl21;
 use super::gl21compat_raw::types::*;
 use super::gles11_raw as gles11; // constants only
 use super::util::{
-    fixed_to_float, matrix_fixed_to_float, try_decode_pvrtc, PalettedTextureFormat, ParamTable,
-    ParamType,
+    finish_read_pixels_async_gl21, fixed_to_float, matrix_fixed_to_float,
+    start_read_pixels_async_gl21, try_decode_pvrtc, PalettedTextureFormat, ParamTable, ParamType,
 };
 use super::GLES;
 use crate::window::{GLContext, GLVersion, Window};
@@ -271,6 +271,10 @@ const GET_PARAMS: ParamTable = ParamTable(&[
     // OES_framebuffer_object -> EXT_framebuffer_object
     (gl21::FRAMEBUFFER_BINDING_EXT, ParamType::Int, 1),
     (gl21::RENDERBUFFER_BINDING_EXT, ParamType::Int, 1),
+    // APPLE_framebuffer_multisample -> EXT_framebuffer_multisample and
+    // EXT_framebuffer_blit
+    (gl21::MAX_SAMPLES_EXT, ParamType::Int, 1),
+    (gl21::READ_FRAMEBUFFER_BINDING_EXT, ParamType::Int, 1),
     // EXT_texture_lod_bias
     (gl21::MAX_TEXTURE_LOD_BIAS_EXT, ParamType::Float, 1),
     // OES_matrix_palette -> ARB_matrix_palette
@@ -547,18 +551,19 @@ impl GLES for GLES1OnGL2 {
         "OpenGL ES 1.1 via touchHLE GLES1-on-GL2 layer"
     }
 
-    fn new(window: &mut Window) -> Result<Self, String> {
+    fn new(window: &mut Window, share_with_current: bool) -> Result<Self, String> {
         Ok(Self {
-            gl_ctx: window.create_gl_context(GLVersion::GL21Compat)?,
+            gl_ctx: window.create_gl_context(GLVersion::GL21Compat, share_with_current)?,
             pointer_is_fixed_point: [false; ARRAYS.len()],
             fixed_point_texture_units: HashSet::new(),
             fixed_point_translation_buffers: [Vec::new(), Vec::new(), Vec::new(), Vec::new()],
         })
     }
 
-    fn make_current(&self, window: &Window) {
-        unsafe { window.make_gl_context_current(&self.gl_ctx) };
-        gl21::load_with(|s| window.gl_get_proc_address(s))
+    fn try_make_current(&self, window: &Window) -> Result<(), String> {
+        unsafe { window.make_gl_context_current(&self.gl_ctx)? };
+        gl21::load_with(|s| window.gl_get_proc_address(s));
+        Ok(())
     }
 
     unsafe fn driver_description(&self) -> String {
@@ -574,6 +579,24 @@ impl GLES for GLES1OnGL2 {
         )
     }
 
+    unsafe fn start_read_pixels_async(
+        &mut self,
+        buffer: GLuint,
+        width: GLsizei,
+        height: GLsizei,
+    ) -> Option<GLuint> {
+        Some(start_read_pixels_async_gl21(buffer, width, height))
+    }
+    unsafe fn finish_read_pixels_async(
+        &mut self,
+        buffer: GLuint,
+        width: GLsizei,
+        height: GLsizei,
+        pixels: &mut Vec<u8>,
+    ) {
+        finish_read_pixels_async_gl21(buffer, width, height, pixels)
+    }
+
     // Generic state manipulation
     unsafe fn GetError(&mut self) -> GLenum {
         gl21::GetError()
@@ -1737,6 +1760,15 @@ impl GLES for GLES1OnGL2 {
     ) {
         gl21::GetRenderbufferParameterivEXT(target, pname, params)
     }
+    unsafe fn GetFramebufferAttachmentParameterivOES(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    ) {
+        gl21::GetFramebufferAttachmentParameterivEXT(target, attachment, pname, params)
+    }
     unsafe fn CheckFramebufferStatusOES(&mut self, target: GLenum) -> GLenum {
         gl21::CheckFramebufferStatusEXT(target)
     }
@@ -1749,4 +1781,20 @@ impl GLES for GLES1OnGL2 {
     unsafe fn GenerateMipmapOES(&mut self, target: GLenum) {
         gl21::GenerateMipmapEXT(target)
     }
+
+    // APPLE_framebuffer_multisample -> EXT_framebuffer_multisample and
+    // EXT_framebuffer_blit
+    unsafe fn RenderbufferStorageMultisampleAPPLE(
+        &mut self,
+        target: GLenum,
+        samples: GLsizei,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    ) {
+        gl21::RenderbufferStorageMultisampleEXT(target, samples, internalformat, width, height)
+    }
+    unsafe fn ResolveMultisampleFramebufferAPPLE(&mut self) {
+        resolve_multisample_framebuffer_gl21()
+    }
 }
@@ -0,0 +1,694 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Passthrough for a native OpenGL ES 2.0 driver.
+//!
+//! Like [super::gles1_native], there's almost no validation of arguments here.
+//! Shaders are passed to the driver unmodified, since they are already written
+//! in GLSL ES.
+
+use super::gles2_raw as gles2;
+use super::gles2_raw::types::*;
+use super::util::try_decode_pvrtc;
+use super::GLES;
+use crate::window::{GLContext, GLVersion, Window};
+use std::ffi::CStr;
+
+pub struct GLES2Native {
+    gl_ctx: GLContext,
+}
+impl GLES for GLES2Native {
+    fn description() -> &'static str {
+        "Native OpenGL ES 2.0"
+    }
+
+    fn new(window: &mut Window, share_with_current: bool) -> Result<Self, String> {
+        Ok(Self {
+            gl_ctx: window.create_gl_context(GLVersion::GLES20, share_with_current)?,
+        })
+    }
+
+    fn try_make_current(&self, window: &Window) -> Result<(), String> {
+        unsafe { window.make_gl_context_current(&self.gl_ctx)? };
+        gles2::load_with(|s| window.gl_get_proc_address(s));
+        Ok(())
+    }
+
+    unsafe fn driver_description(&self) -> String {
+        let version = CStr::from_ptr(gles2::GetString(gles2::VERSION) as *const _);
+        let vendor = CStr::from_ptr(gles2::GetString(gles2::VENDOR) as *const _);
+        let renderer = CStr::from_ptr(gles2::GetString(gles2::RENDERER) as *const _);
+        // OpenGL ES requires the version to be prefixed "OpenGL ES", so we
+        // don't need to contextualize it.
+        format!(
+            "{} / {} / {}",
+            version.to_string_lossy(),
+            vendor.to_string_lossy(),
+            renderer.to_string_lossy()
+        )
+    }
+
+    // Generic state manipulation
+    unsafe fn GetError(&mut self) -> GLenum {
+        gles2::GetError()
+    }
+    unsafe fn Enable(&mut self, cap: GLenum) {
+        gles2::Enable(cap)
+    }
+    unsafe fn IsEnabled(&mut self, cap: GLenum) -> GLboolean {
+        gles2::IsEnabled(cap)
+    }
+    unsafe fn Disable(&mut self, cap: GLenum) {
+        gles2::Disable(cap)
+    }
+    unsafe fn GetBooleanv(&mut self, pname: GLenum, params: *mut GLboolean) {
+        gles2::GetBooleanv(pname, params)
+    }
+    unsafe fn GetFloatv(&mut self, pname: GLenum, params: *mut GLfloat) {
+        gles2::GetFloatv(pname, params)
+    }
+    unsafe fn GetIntegerv(&mut self, pname: GLenum, params: *mut GLint) {
+        gles2::GetIntegerv(pname, params)
+    }
+    unsafe fn Hint(&mut self, target: GLenum, mode: GLenum) {
+        gles2::Hint(target, mode)
+    }
+    unsafe fn Flush(&mut self) {
+        gles2::Flush()
+    }
+    unsafe fn GetString(&mut self, name: GLenum) -> *const GLubyte {
+        gles2::GetString(name)
+    }
+
+    // Other state manipulation
+    unsafe fn BlendFunc(&mut self, sfactor: GLenum, dfactor: GLenum) {
+        gles2::BlendFunc(sfactor, dfactor)
+    }
+    unsafe fn ColorMask(
+        &mut self,
+        red: GLboolean,
+        green: GLboolean,
+        blue: GLboolean,
+        alpha: GLboolean,
+    ) {
+        gles2::ColorMask(red, green, blue, alpha)
+    }
+    unsafe fn CullFace(&mut self, mode: GLenum) {
+        gles2::CullFace(mode)
+    }
+    unsafe fn DepthFunc(&mut self, func: GLenum) {
+        gles2::DepthFunc(func)
+    }
+    unsafe fn DepthMask(&mut self, flag: GLboolean) {
+        gles2::DepthMask(flag)
+    }
+    unsafe fn DepthRangef(&mut self, near: GLclampf, far: GLclampf) {
+        gles2::DepthRangef(near, far)
+    }
+    unsafe fn FrontFace(&mut self, mode: GLenum) {
+        gles2::FrontFace(mode)
+    }
+    unsafe fn PolygonOffset(&mut self, factor: GLfloat, units: GLfloat) {
+        gles2::PolygonOffset(factor, units)
+    }
+    unsafe fn Scissor(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        gles2::Scissor(x, y, width, height)
+    }
+    unsafe fn Viewport(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        gles2::Viewport(x, y, width, height)
+    }
+    unsafe fn LineWidth(&mut self, val: GLfloat) {
+        gles2::LineWidth(val)
+    }
+
+    // Buffers
+    unsafe fn GenBuffers(&mut self, n: GLsizei, buffers: *mut GLuint) {
+        gles2::GenBuffers(n, buffers)
+    }
+    unsafe fn DeleteBuffers(&mut self, n: GLsizei, buffers: *const GLuint) {
+        gles2::DeleteBuffers(n, buffers)
+    }
+    unsafe fn BindBuffer(&mut self, target: GLenum, buffer: GLuint) {
+        gles2::BindBuffer(target, buffer)
+    }
+    unsafe fn BufferData(
+        &mut self,
+        target: GLenum,
+        size: GLsizeiptr,
+        data: *const GLvoid,
+        usage: GLenum,
+    ) {
+        gles2::BufferData(target, size, data, usage)
+    }
+    unsafe fn BufferSubData(
+        &mut self,
+        target: GLenum,
+        offset: GLintptr,
+        size: GLsizeiptr,
+        data: *const GLvoid,
+    ) {
+        gles2::BufferSubData(target, offset, size, data)
+    }
+
+    // Drawing
+    unsafe fn DrawArrays(&mut self, mode: GLenum, first: GLint, count: GLsizei) {
+        gles2::DrawArrays(mode, first, count)
+    }
+    unsafe fn DrawElements(
+        &mut self,
+        mode: GLenum,
+        count: GLsizei,
+        type_: GLenum,
+        indices: *const GLvoid,
+    ) {
+        gles2::DrawElements(mode, count, type_, indices)
+    }
+
+    // Clearing
+    unsafe fn Clear(&mut self, mask: GLbitfield) {
+        gles2::Clear(mask)
+    }
+    unsafe fn ClearColor(
+        &mut self,
+        red: GLclampf,
+        green: GLclampf,
+        blue: GLclampf,
+        alpha: GLclampf,
+    ) {
+        gles2::ClearColor(red, green, blue, alpha)
+    }
+    unsafe fn ClearDepthf(&mut self, depth: GLclampf) {
+        gles2::ClearDepthf(depth)
+    }
+    unsafe fn ClearStencil(&mut self, s: GLint) {
+        gles2::ClearStencil(s)
+    }
+
+    // Textures
+    unsafe fn PixelStorei(&mut self, pname: GLenum, param: GLint) {
+        gles2::PixelStorei(pname, param)
+    }
+    unsafe fn ReadPixels(
+        &mut self,
+        x: GLint,
+        y: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        format: GLenum,
+        type_: GLenum,
+        pixels: *mut GLvoid,
+    ) {
+        gles2::ReadPixels(x, y, width, height, format, type_, pixels)
+    }
+    unsafe fn GenTextures(&mut self, n: GLsizei, textures: *mut GLuint) {
+        gles2::GenTextures(n, textures)
+    }
+    unsafe fn DeleteTextures(&mut self, n: GLsizei, textures: *const GLuint) {
+        gles2::DeleteTextures(n, textures)
+    }
+    unsafe fn ActiveTexture(&mut self, texture: GLenum) {
+        gles2::ActiveTexture(texture)
+    }
+    unsafe fn BindTexture(&mut self, target: GLenum, texture: GLuint) {
+        gles2::BindTexture(target, texture)
+    }
+    unsafe fn TexParameteri(&mut self, target: GLenum, pname: GLenum, param: GLint) {
+        gles2::TexParameteri(target, pname, param)
+    }
+    unsafe fn TexParameterf(&mut self, target: GLenum, pname: GLenum, param: GLfloat) {
+        gles2::TexParameterf(target, pname, param)
+    }
+    unsafe fn TexParameteriv(&mut self, target: GLenum, pname: GLenum, params: *const GLint) {
+        gles2::TexParameteriv(target, pname, params)
+    }
+    unsafe fn TexParameterfv(&mut self, target: GLenum, pname: GLenum, params: *const GLfloat) {
+        gles2::TexParameterfv(target, pname, params)
+    }
+    unsafe fn TexImage2D(
+        &mut self,
+        target: GLenum,
+        level: GLint,
+        internalformat: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        border: GLint,
+        format: GLenum,
+        type_: GLenum,
+        pixels: *const GLvoid,
+    ) {
+        gles2::TexImage2D(
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            border,
+            format,
+            type_,
+            pixels,
+        )
+    }
+    unsafe fn TexSubImage2D(
+        &mut self,
+        target: GLenum,
+        level: GLint,
+        xoffset: GLint,
+        yoffset: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        format: GLenum,
+        type_: GLenum,
+        pixels: *const GLvoid,
+    ) {
+        gles2::TexSubImage2D(
+            target, level, xoffset, yoffset, width, height, format, type_, pixels,
+        )
+    }
+    unsafe fn CompressedTexImage2D(
+        &mut self,
+        target: GLenum,
+        level: GLint,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+        border: GLint,
+        image_size: GLsizei,
+        data: *const GLvoid,
+    ) {
+        let data = unsafe { std::slice::from_raw_parts(data.cast::<u8>(), image_size as usize) };
+        // IMG_texture_compression_pvrtc (only on Imagination/Apple GPUs)
+        if try_decode_pvrtc(
+            self,
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            border,
+            data,
+        ) {
+            log_dbg!("Decoded PVRTC");
+            return;
+        }
+
+        // OpenGL ES 2.0 on iPhone OS has no other compressed texture formats.
+        unimplemented!("CompressedTexImage2D internalformat: {:#x}", internalformat);
+    }
+    unsafe fn CopyTexImage2D(
+        &mut self,
+        target: GLenum,
+        level: GLint,
+        internalformat: GLenum,
+        x: GLint,
+        y: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        border: GLint,
+    ) {
+        gles2::CopyTexImage2D(target, level, internalformat, x, y, width, height, border)
+    }
+    unsafe fn CopyTexSubImage2D(
+        &mut self,
+        target: GLenum,
+        level: GLint,
+        xoffset: GLint,
+        yoffset: GLint,
+        x: GLint,
+        y: GLint,
+        width: GLsizei,
+        height: GLsizei,
+    ) {
+        gles2::CopyTexSubImage2D(target, level, xoffset, yoffset, x, y, width, height)
+    }
+
+    // Framebuffers (core in OpenGL ES 2.0, no OES suffix)
+    unsafe fn GenFramebuffersOES(&mut self, n: GLsizei, framebuffers: *mut GLuint) {
+        gles2::GenFramebuffers(n, framebuffers)
+    }
+    unsafe fn GenRenderbuffersOES(&mut self, n: GLsizei, renderbuffers: *mut GLuint) {
+        gles2::GenRenderbuffers(n, renderbuffers)
+    }
+    unsafe fn BindFramebufferOES(&mut self, target: GLenum, framebuffer: GLuint) {
+        gles2::BindFramebuffer(target, framebuffer)
+    }
+    unsafe fn BindRenderbufferOES(&mut self, target: GLenum, renderbuffer: GLuint) {
+        gles2::BindRenderbuffer(target, renderbuffer)
+    }
+    unsafe fn RenderbufferStorageOES(
+        &mut self,
+        target: GLenum,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    ) {
+        gles2::RenderbufferStorage(target, internalformat, width, height)
+    }
+    unsafe fn FramebufferRenderbufferOES(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        renderbuffertarget: GLenum,
+        renderbuffer: GLuint,
+    ) {
+        gles2::FramebufferRenderbuffer(target, attachment, renderbuffertarget, renderbuffer)
+    }
+    unsafe fn FramebufferTexture2DOES(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        textarget: GLenum,
+        texture: GLuint,
+        level: i32,
+    ) {
+        gles2::FramebufferTexture2D(target, attachment, textarget, texture, level)
+    }
+    unsafe fn GetRenderbufferParameterivOES(
+        &mut self,
+        target: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    ) {
+        gles2::GetRenderbufferParameteriv(target, pname, params)
+    }
+    unsafe fn GetFramebufferAttachmentParameterivOES(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    ) {
+        gles2::GetFramebufferAttachmentParameteriv(target, attachment, pname, params)
+    }
+    unsafe fn CheckFramebufferStatusOES(&mut self, target: GLenum) -> GLenum {
+        gles2::CheckFramebufferStatus(target)
+    }
+    unsafe fn DeleteFramebuffersOES(&mut self, n: GLsizei, framebuffers: *const GLuint) {
+        gles2::DeleteFramebuffers(n, framebuffers)
+    }
+    unsafe fn DeleteRenderbuffersOES(&mut self, n: GLsizei, renderbuffers: *const GLuint) {
+        gles2::DeleteRenderbuffers(n, renderbuffers)
+    }
+    unsafe fn GenerateMipmapOES(&mut self, target: GLenum) {
+        gles2::GenerateMipmap(target)
+    }
+
+    // APPLE_framebuffer_multisample
+    unsafe fn RenderbufferStorageMultisampleAPPLE(
+        &mut self,
+        target: GLenum,
+        samples: GLsizei,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    ) {
+        gles2::RenderbufferStorageMultisampleAPPLE(target, samples, internalformat, width, height)
+    }
+    unsafe fn ResolveMultisampleFramebufferAPPLE(&mut self) {
+        gles2::ResolveMultisampleFramebufferAPPLE()
+    }
+
+    // Blending
+    unsafe fn BlendColor(
+        &mut self,
+        red: GLclampf,
+        green: GLclampf,
+        blue: GLclampf,
+        alpha: GLclampf,
+    ) {
+        gles2::BlendColor(red, green, blue, alpha)
+    }
+    unsafe fn BlendEquation(&mut self, mode: GLenum) {
+        gles2::BlendEquation(mode)
+    }
+    unsafe fn BlendEquationSeparate(&mut self, mode_rgb: GLenum, mode_alpha: GLenum) {
+        gles2::BlendEquationSeparate(mode_rgb, mode_alpha)
+    }
+    unsafe fn BlendFuncSeparate(
+        &mut self,
+        sfactor_rgb: GLenum,
+        dfactor_rgb: GLenum,
+        sfactor_alpha: GLenum,
+        dfactor_alpha: GLenum,
+    ) {
+        gles2::BlendFuncSeparate(sfactor_rgb, dfactor_rgb, sfactor_alpha, dfactor_alpha)
+    }
+
+    // Shaders
+    unsafe fn CreateShader(&mut self, type_: GLenum) -> GLuint {
+        gles2::CreateShader(type_)
+    }
+    unsafe fn ShaderSource(
+        &mut self,
+        shader: GLuint,
+        count: GLsizei,
+        string: *const *const GLchar,
+        length: *const GLint,
+    ) {
+        gles2::ShaderSource(shader, count, string, length)
+    }
+    unsafe fn CompileShader(&mut self, shader: GLuint) {
+        gles2::CompileShader(shader)
+    }
+    unsafe fn GetShaderiv(&mut self, shader: GLuint, pname: GLenum, params: *mut GLint) {
+        gles2::GetShaderiv(shader, pname, params)
+    }
+    unsafe fn GetShaderInfoLog(
+        &mut self,
+        shader: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        info_log: *mut GLchar,
+    ) {
+        gles2::GetShaderInfoLog(shader, buf_size, length, info_log)
+    }
+    unsafe fn IsShader(&mut self, shader: GLuint) -> GLboolean {
+        gles2::IsShader(shader)
+    }
+    unsafe fn DeleteShader(&mut self, shader: GLuint) {
+        gles2::DeleteShader(shader)
+    }
+
+    // Programs
+    unsafe fn CreateProgram(&mut self) -> GLuint {
+        gles2::CreateProgram()
+    }
+    unsafe fn AttachShader(&mut self, program: GLuint, shader: GLuint) {
+        gles2::AttachShader(program, shader)
+    }
+    unsafe fn DetachShader(&mut self, program: GLuint, shader: GLuint) {
+        gles2::DetachShader(program, shader)
+    }
+    unsafe fn BindAttribLocation(&mut self, program: GLuint, index: GLuint, name: *const GLchar) {
+        gles2::BindAttribLocation(program, index, name)
+    }
+    unsafe fn LinkProgram(&mut self, program: GLuint) {
+        gles2::LinkProgram(program)
+    }
+    unsafe fn ValidateProgram(&mut self, program: GLuint) {
+        gles2::ValidateProgram(program)
+    }
+    unsafe fn UseProgram(&mut self, program: GLuint) {
+        gles2::UseProgram(program)
+    }
+    unsafe fn GetProgramiv(&mut self, program: GLuint, pname: GLenum, params: *mut GLint) {
+        gles2::GetProgramiv(program, pname, params)
+    }
+    unsafe fn GetProgramInfoLog(
+        &mut self,
+        program: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        info_log: *mut GLchar,
+    ) {
+        gles2::GetProgramInfoLog(program, buf_size, length, info_log)
+    }
+    unsafe fn IsProgram(&mut self, program: GLuint) -> GLboolean {
+        gles2::IsProgram(program)
+    }
+    unsafe fn DeleteProgram(&mut self, program: GLuint) {
+        gles2::DeleteProgram(program)
+    }
+    unsafe fn GetAttribLocation(&mut self, program: GLuint, name: *const GLchar) -> GLint {
+        gles2::GetAttribLocation(program, name)
+    }
+    unsafe fn GetUniformLocation(&mut self, program: GLuint, name: *const GLchar) -> GLint {
+        gles2::GetUniformLocation(program, name)
+    }
+    unsafe fn GetActiveAttrib(
+        &mut self,
+        program: GLuint,
+        index: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar,
+    ) {
+        gles2::GetActiveAttrib(program, index, buf_size, length, size, type_, name)
+    }
+    unsafe fn GetActiveUniform(
+        &mut self,
+        program: GLuint,
+        index: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar,
+    ) {
+        gles2::GetActiveUniform(program, index, buf_size, length, size, type_, name)
+    }
+
+    // Uniforms
+    unsafe fn Uniform1f(&mut self, location: GLint, v0: GLfloat) {
+        gles2::Uniform1f(location, v0)
+    }
+    unsafe fn Uniform2f(&mut self, location: GLint, v0: GLfloat, v1: GLfloat) {
+        gles2::Uniform2f(location, v0, v1)
+    }
+    unsafe fn Uniform3f(&mut self, location: GLint, v0: GLfloat, v1: GLfloat, v2: GLfloat) {
+        gles2::Uniform3f(location, v0, v1, v2)
+    }
+    unsafe fn Uniform4f(
+        &mut self,
+        location: GLint,
+        v0: GLfloat,
+        v1: GLfloat,
+        v2: GLfloat,
+        v3: GLfloat,
+    ) {
+        gles2::Uniform4f(location, v0, v1, v2, v3)
+    }
+    unsafe fn Uniform1i(&mut self, location: GLint, v0: GLint) {
+        gles2::Uniform1i(location, v0)
+    }
+    unsafe fn Uniform2i(&mut self, location: GLint, v0: GLint, v1: GLint) {
+        gles2::Uniform2i(location, v0, v1)
+    }
+    unsafe fn Uniform3i(&mut self, location: GLint, v0: GLint, v1: GLint, v2: GLint) {
+        gles2::Uniform3i(location, v0, v1, v2)
+    }
+    unsafe fn Uniform4i(&mut self, location: GLint, v0: GLint, v1: GLint, v2: GLint, v3: GLint) {
+        gles2::Uniform4i(location, v0, v1, v2, v3)
+    }
+    unsafe fn Uniform1fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        gles2::Uniform1fv(location, count, value)
+    }
+    unsafe fn Uniform2fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        gles2::Uniform2fv(location, count, value)
+    }
+    unsafe fn Uniform3fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        gles2::Uniform3fv(location, count, value)
+    }
+    unsafe fn Uniform4fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        gles2::Uniform4fv(location, count, value)
+    }
+    unsafe fn Uniform1iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        gles2::Uniform1iv(location, count, value)
+    }
+    unsafe fn Uniform2iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        gles2::Uniform2iv(location, count, value)
+    }
+    unsafe fn Uniform3iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        gles2::Uniform3iv(location, count, value)
+    }
+    unsafe fn Uniform4iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        gles2::Uniform4iv(location, count, value)
+    }
+    unsafe fn UniformMatrix2fv(
+        &mut self,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    ) {
+        gles2::UniformMatrix2fv(location, count, transpose, value)
+    }
+    unsafe fn UniformMatrix3fv(
+        &mut self,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    ) {
+        gles2::UniformMatrix3fv(location, count, transpose, value)
+    }
+    unsafe fn UniformMatrix4fv(
+        &mut self,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    ) {
+        gles2::UniformMatrix4fv(location, count, transpose, value)
+    }
+
+    // Vertex attributes
+    unsafe fn EnableVertexAttribArray(&mut self, index: GLuint) {
+        gles2::EnableVertexAttribArray(index)
+    }
+    unsafe fn DisableVertexAttribArray(&mut self, index: GLuint) {
+        gles2::DisableVertexAttribArray(index)
+    }
+    unsafe fn VertexAttribPointer(
+        &mut self,
+        index: GLuint,
+        size: GLint,
+        type_: GLenum,
+        normalized: GLboolean,
+        stride: GLsizei,
+        pointer: *const GLvoid,
+    ) {
+        gles2::VertexAttribPointer(index, size, type_, normalized, stride, pointer)
+    }
+    unsafe fn VertexAttrib1f(&mut self, index: GLuint, x: GLfloat) {
+        gles2::VertexAttrib1f(index, x)
+    }
+    unsafe fn VertexAttrib2f(&mut self, index: GLuint, x: GLfloat, y: GLfloat) {
+        gles2::VertexAttrib2f(index, x, y)
+    }
+    unsafe fn VertexAttrib3f(&mut self, index: GLuint, x: GLfloat, y: GLfloat, z: GLfloat) {
+        gles2::VertexAttrib3f(index, x, y, z)
+    }
+    unsafe fn VertexAttrib4f(
+        &mut self,
+        index: GLuint,
+        x: GLfloat,
+        y: GLfloat,
+        z: GLfloat,
+        w: GLfloat,
+    ) {
+        gles2::VertexAttrib4f(index, x, y, z, w)
+    }
+    unsafe fn VertexAttrib1fv(&mut self, index: GLuint, v: *const GLfloat) {
+        gles2::VertexAttrib1fv(index, v)
+    }
+    unsafe fn VertexAttrib2fv(&mut self, index: GLuint, v: *const GLfloat) {
+        gles2::VertexAttrib2fv(index, v)
+    }
+    unsafe fn VertexAttrib3fv(&mut self, index: GLuint, v: *const GLfloat) {
+        gles2::VertexAttrib3fv(index, v)
+    }
+    unsafe fn VertexAttrib4fv(&mut self, index: GLuint, v: *const GLfloat) {
+        gles2::VertexAttrib4fv(index, v)
+    }
+    unsafe fn GetVertexAttribiv(&mut self, index: GLuint, pname: GLenum, params: *mut GLint) {
+        gles2::GetVertexAttribiv(index, pname, params)
+    }
+    unsafe fn GetVertexAttribfv(&mut self, index: GLuint, pname: GLenum, params: *mut GLfloat) {
+        gles2::GetVertexAttribfv(index, pname, params)
+    }
+    unsafe fn GetVertexAttribPointerv(
+        &mut self,
+        index: GLuint,
+        pname: GLenum,
+        pointer: *mut *const GLvoid,
+    ) {
+        // The third argument to glGetVertexAttribPointerv must be a mutable
+        // pointer, but gl_generator generates the wrong signature by mistake,
+        // see https://github.com/brendanzab/gl-rs/issues/541
+        gles2::GetVertexAttribPointerv(index, pname, pointer as *mut _ as *const _)
+    }
+}
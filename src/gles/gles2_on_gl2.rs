@@ -0,0 +1,881 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Implementation of OpenGL ES 2.0 on top of OpenGL 2.1 compatible profile.
+//!
+//! OpenGL ES 2.0 is, to a first approximation, a subset of OpenGL 2.1, so most
+//! functions are passed through directly. The notable differences are:
+//!
+//! - GLSL ES 1.00 shaders have to be translated to GLSL 1.20: the version
+//!   directive is replaced and precision qualifiers are stripped. See
+//!   [translate_shader_source].
+//! - Point sprites are always enabled in OpenGL ES 2.0, and the vertex shader
+//!   always controls the point size.
+//! - Framebuffer objects are core in OpenGL ES 2.0, but we need to use
+//!   `EXT_framebuffer_object` in OpenGL 2.1.
+//!
+//! Useful resources:
+//! - [OpenGL ES 2.0 specification](https://registry.khronos.org/OpenGL/specs/es/2.0/es_full_spec_2.0.pdf),
+//!   Appendix F lists differences from OpenGL 2.0.
+//! - [GLSL ES 1.00 specification](https://registry.khronos.org/OpenGL/specs/es/2.0/GLSL_ES_Specification_1.00.pdf)
+
+use super::gl21compat_raw as gl21;
+use super::gl21compat_raw::types::*;
+use super::gles2_raw as gles2; // constants only
+use super::util::{
+    finish_read_pixels_async_gl21, resolve_multisample_framebuffer_gl21,
+    start_read_pixels_async_gl21, try_decode_pvrtc,
+};
+use super::GLES;
+use crate::window::{GLContext, GLVersion, Window};
+use std::ffi::CStr;
+
+/// List of capabilities shared by OpenGL ES 2.0 and OpenGL 2.1.
+pub const CAPABILITIES: &[GLenum] = &[
+    gl21::BLEND,
+    gl21::CULL_FACE,
+    gl21::DEPTH_TEST,
+    gl21::DITHER,
+    gl21::POLYGON_OFFSET_FILL,
+    gl21::SAMPLE_ALPHA_TO_COVERAGE,
+    gl21::SAMPLE_COVERAGE,
+    gl21::SCISSOR_TEST,
+    gl21::STENCIL_TEST,
+];
+
+/// Translate GLSL ES 1.00 shader source code to GLSL 1.20.
+///
+/// GLSL 1.20 is almost a superset of GLSL ES 1.00, except that it has no
+/// precision qualifiers. Default precision statements are removed, and the
+/// qualifiers on declarations are defined away by the preprocessor.
+pub fn translate_shader_source(source: &str) -> String {
+    let mut translated = String::from(
+        "#version 120\n\
+         #define lowp\n\
+         #define mediump\n\
+         #define highp\n\
+         #line 0\n",
+    );
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        // These lines are commented out rather than removed, so that line
+        // numbers in error messages still match the original source.
+        // TODO: handle precision statements that share a line with other code
+        if trimmed.starts_with("#version") || trimmed.starts_with("precision ") {
+            translated.push_str("//");
+        }
+        translated.push_str(line);
+        translated.push('\n');
+    }
+    translated
+}
+
+pub struct GLES2OnGL2 {
+    gl_ctx: GLContext,
+    /// Whether the OpenGL ES 2.0 point sprite behavior has been enabled for
+    /// this context.
+    point_sprites_enabled: bool,
+}
+impl GLES2OnGL2 {
+    /// OpenGL ES 2.0 has no switch for point sprites, they're always on.
+    /// This can't be done when the context is created, because it isn't
+    /// current then.
+    unsafe fn prepare_for_draw(&mut self) {
+        if !self.point_sprites_enabled {
+            gl21::Enable(gl21::POINT_SPRITE);
+            gl21::Enable(gl21::VERTEX_PROGRAM_POINT_SIZE);
+            self.point_sprites_enabled = true;
+        }
+    }
+}
+impl GLES for GLES2OnGL2 {
+    fn description() -> &'static str {
+        "OpenGL ES 2.0 via touchHLE GLES2-on-GL2 layer"
+    }
+
+    fn new(window: &mut Window, share_with_current: bool) -> Result<Self, String> {
+        Ok(Self {
+            gl_ctx: window.create_gl_context(GLVersion::GL21Compat, share_with_current)?,
+            point_sprites_enabled: false,
+        })
+    }
+
+    fn try_make_current(&self, window: &Window) -> Result<(), String> {
+        unsafe { window.make_gl_context_current(&self.gl_ctx)? };
+        gl21::load_with(|s| window.gl_get_proc_address(s));
+        Ok(())
+    }
+
+    unsafe fn driver_description(&self) -> String {
+        let version = CStr::from_ptr(gl21::GetString(gl21::VERSION) as *const _);
+        let vendor = CStr::from_ptr(gl21::GetString(gl21::VENDOR) as *const _);
+        let renderer = CStr::from_ptr(gl21::GetString(gl21::RENDERER) as *const _);
+        // OpenGL's version string is just a number, so let's contextualize it.
+        format!(
+            "OpenGL {} / {} / {}",
+            version.to_string_lossy(),
+            vendor.to_string_lossy(),
+            renderer.to_string_lossy()
+        )
+    }
+
+    unsafe fn start_read_pixels_async(
+        &mut self,
+        buffer: GLuint,
+        width: GLsizei,
+        height: GLsizei,
+    ) -> Option<GLuint> {
+        Some(start_read_pixels_async_gl21(buffer, width, height))
+    }
+    unsafe fn finish_read_pixels_async(
+        &mut self,
+        buffer: GLuint,
+        width: GLsizei,
+        height: GLsizei,
+        pixels: &mut Vec<u8>,
+    ) {
+        finish_read_pixels_async_gl21(buffer, width, height, pixels)
+    }
+
+    // Generic state manipulation
+    unsafe fn GetError(&mut self) -> GLenum {
+        gl21::GetError()
+    }
+    unsafe fn Enable(&mut self, cap: GLenum) {
+        assert!(CAPABILITIES.contains(&cap));
+        gl21::Enable(cap);
+    }
+    unsafe fn IsEnabled(&mut self, cap: GLenum) -> GLboolean {
+        assert!(CAPABILITIES.contains(&cap));
+        gl21::IsEnabled(cap)
+    }
+    unsafe fn Disable(&mut self, cap: GLenum) {
+        assert!(CAPABILITIES.contains(&cap));
+        gl21::Disable(cap);
+    }
+    unsafe fn GetBooleanv(&mut self, pname: GLenum, params: *mut GLboolean) {
+        gl21::GetBooleanv(pname, params)
+    }
+    unsafe fn GetFloatv(&mut self, pname: GLenum, params: *mut GLfloat) {
+        gl21::GetFloatv(pname, params)
+    }
+    unsafe fn GetIntegerv(&mut self, pname: GLenum, params: *mut GLint) {
+        // OpenGL ES 2.0 counts uniforms and varyings in vectors, but OpenGL 2.1
+        // counts them in components.
+        let components_pname = match pname {
+            gles2::MAX_VERTEX_UNIFORM_VECTORS => gl21::MAX_VERTEX_UNIFORM_COMPONENTS,
+            gles2::MAX_FRAGMENT_UNIFORM_VECTORS => gl21::MAX_FRAGMENT_UNIFORM_COMPONENTS,
+            gles2::MAX_VARYING_VECTORS => gl21::MAX_VARYING_FLOATS,
+            gles2::SHADER_COMPILER => {
+                params.write(gl21::TRUE.into());
+                return;
+            }
+            gles2::NUM_SHADER_BINARY_FORMATS => {
+                params.write(0);
+                return;
+            }
+            _ => {
+                gl21::GetIntegerv(pname, params);
+                return;
+            }
+        };
+        let mut components = 0;
+        gl21::GetIntegerv(components_pname, &mut components);
+        params.write(components / 4);
+    }
+    unsafe fn Hint(&mut self, target: GLenum, mode: GLenum) {
+        gl21::Hint(target, mode)
+    }
+    unsafe fn Flush(&mut self) {
+        gl21::Flush()
+    }
+    unsafe fn GetString(&mut self, name: GLenum) -> *const GLubyte {
+        gl21::GetString(name)
+    }
+
+    // Other state manipulation
+    unsafe fn BlendFunc(&mut self, sfactor: GLenum, dfactor: GLenum) {
+        gl21::BlendFunc(sfactor, dfactor)
+    }
+    unsafe fn ColorMask(
+        &mut self,
+        red: GLboolean,
+        green: GLboolean,
+        blue: GLboolean,
+        alpha: GLboolean,
+    ) {
+        gl21::ColorMask(red, green, blue, alpha)
+    }
+    unsafe fn CullFace(&mut self, mode: GLenum) {
+        gl21::CullFace(mode)
+    }
+    unsafe fn DepthFunc(&mut self, func: GLenum) {
+        gl21::DepthFunc(func)
+    }
+    unsafe fn DepthMask(&mut self, flag: GLboolean) {
+        gl21::DepthMask(flag)
+    }
+    unsafe fn DepthRangef(&mut self, near: GLclampf, far: GLclampf) {
+        gl21::DepthRange(near.into(), far.into())
+    }
+    unsafe fn FrontFace(&mut self, mode: GLenum) {
+        gl21::FrontFace(mode)
+    }
+    unsafe fn PolygonOffset(&mut self, factor: GLfloat, units: GLfloat) {
+        gl21::PolygonOffset(factor, units)
+    }
+    unsafe fn Scissor(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        gl21::Scissor(x, y, width, height)
+    }
+    unsafe fn Viewport(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei) {
+        gl21::Viewport(x, y, width, height)
+    }
+    unsafe fn LineWidth(&mut self, val: GLfloat) {
+        gl21::LineWidth(val)
+    }
+
+    // Buffers
+    unsafe fn GenBuffers(&mut self, n: GLsizei, buffers: *mut GLuint) {
+        gl21::GenBuffers(n, buffers)
+    }
+    unsafe fn DeleteBuffers(&mut self, n: GLsizei, buffers: *const GLuint) {
+        gl21::DeleteBuffers(n, buffers)
+    }
+    unsafe fn BindBuffer(&mut self, target: GLenum, buffer: GLuint) {
+        gl21::BindBuffer(target, buffer)
+    }
+    unsafe fn BufferData(
+        &mut self,
+        target: GLenum,
+        size: GLsizeiptr,
+        data: *const GLvoid,
+        usage: GLenum,
+    ) {
+        gl21::BufferData(target, size, data, usage)
+    }
+    unsafe fn BufferSubData(
+        &mut self,
+        target: GLenum,
+        offset: GLintptr,
+        size: GLsizeiptr,
+        data: *const GLvoid,
+    ) {
+        gl21::BufferSubData(target, offset, size, data)
+    }
+
+    // Drawing
+    unsafe fn DrawArrays(&mut self, mode: GLenum, first: GLint, count: GLsizei) {
+        self.prepare_for_draw();
+        gl21::DrawArrays(mode, first, count)
+    }
+    unsafe fn DrawElements(
+        &mut self,
+        mode: GLenum,
+        count: GLsizei,
+        type_: GLenum,
+        indices: *const GLvoid,
+    ) {
+        assert!(type_ == gl21::UNSIGNED_BYTE || type_ == gl21::UNSIGNED_SHORT);
+        self.prepare_for_draw();
+        gl21::DrawElements(mode, count, type_, indices)
+    }
+
+    // Clearing
+    unsafe fn Clear(&mut self, mask: GLbitfield) {
+        gl21::Clear(mask)
+    }
+    unsafe fn ClearColor(
+        &mut self,
+        red: GLclampf,
+        green: GLclampf,
+        blue: GLclampf,
+        alpha: GLclampf,
+    ) {
+        gl21::ClearColor(red, green, blue, alpha)
+    }
+    unsafe fn ClearDepthf(&mut self, depth: GLclampf) {
+        gl21::ClearDepth(depth.into())
+    }
+    unsafe fn ClearStencil(&mut self, s: GLint) {
+        gl21::ClearStencil(s)
+    }
+
+    // Textures
+    unsafe fn PixelStorei(&mut self, pname: GLenum, param: GLint) {
+        gl21::PixelStorei(pname, param)
+    }
+    unsafe fn ReadPixels(
+        &mut self,
+        x: GLint,
+        y: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        format: GLenum,
+        type_: GLenum,
+        pixels: *mut GLvoid,
+    ) {
+        gl21::ReadPixels(x, y, width, height, format, type_, pixels)
+    }
+    unsafe fn GenTextures(&mut self, n: GLsizei, textures: *mut GLuint) {
+        gl21::GenTextures(n, textures)
+    }
+    unsafe fn DeleteTextures(&mut self, n: GLsizei, textures: *const GLuint) {
+        gl21::DeleteTextures(n, textures)
+    }
+    unsafe fn ActiveTexture(&mut self, texture: GLenum) {
+        gl21::ActiveTexture(texture)
+    }
+    unsafe fn BindTexture(&mut self, target: GLenum, texture: GLuint) {
+        gl21::BindTexture(target, texture)
+    }
+    unsafe fn TexParameteri(&mut self, target: GLenum, pname: GLenum, param: GLint) {
+        gl21::TexParameteri(target, pname, param)
+    }
+    unsafe fn TexParameterf(&mut self, target: GLenum, pname: GLenum, param: GLfloat) {
+        gl21::TexParameterf(target, pname, param)
+    }
+    unsafe fn TexParameteriv(&mut self, target: GLenum, pname: GLenum, params: *const GLint) {
+        gl21::TexParameteriv(target, pname, params)
+    }
+    unsafe fn TexParameterfv(&mut self, target: GLenum, pname: GLenum, params: *const GLfloat) {
+        gl21::TexParameterfv(target, pname, params)
+    }
+    unsafe fn TexImage2D(
+        &mut self,
+        target: GLenum,
+        level: GLint,
+        internalformat: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        border: GLint,
+        format: GLenum,
+        type_: GLenum,
+        pixels: *const GLvoid,
+    ) {
+        assert!(
+            target == gl21::TEXTURE_2D
+                || (gl21::TEXTURE_CUBE_MAP_POSITIVE_X..=gl21::TEXTURE_CUBE_MAP_NEGATIVE_Z)
+                    .contains(&target)
+        );
+        assert!(level >= 0);
+        assert!(
+            internalformat as GLenum == gl21::ALPHA
+                || internalformat as GLenum == gl21::RGB
+                || internalformat as GLenum == gl21::RGBA
+                || internalformat as GLenum == gl21::LUMINANCE
+                || internalformat as GLenum == gl21::LUMINANCE_ALPHA
+        );
+        assert!(border == 0);
+        assert!(
+            format == gl21::ALPHA
+                || format == gl21::RGB
+                || format == gl21::RGBA
+                || format == gl21::LUMINANCE
+                || format == gl21::LUMINANCE_ALPHA
+                || format == gl21::BGRA
+        );
+        assert!(
+            type_ == gl21::UNSIGNED_BYTE
+                || type_ == gl21::UNSIGNED_SHORT_5_6_5
+                || type_ == gl21::UNSIGNED_SHORT_4_4_4_4
+                || type_ == gl21::UNSIGNED_SHORT_5_5_5_1
+        );
+        gl21::TexImage2D(
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            border,
+            format,
+            type_,
+            pixels,
+        )
+    }
+    unsafe fn TexSubImage2D(
+        &mut self,
+        target: GLenum,
+        level: GLint,
+        xoffset: GLint,
+        yoffset: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        format: GLenum,
+        type_: GLenum,
+        pixels: *const GLvoid,
+    ) {
+        gl21::TexSubImage2D(
+            target, level, xoffset, yoffset, width, height, format, type_, pixels,
+        )
+    }
+    unsafe fn CompressedTexImage2D(
+        &mut self,
+        target: GLenum,
+        level: GLint,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+        border: GLint,
+        image_size: GLsizei,
+        data: *const GLvoid,
+    ) {
+        let data = unsafe { std::slice::from_raw_parts(data.cast::<u8>(), image_size as usize) };
+        if try_decode_pvrtc(
+            self,
+            target,
+            level,
+            internalformat,
+            width,
+            height,
+            border,
+            data,
+        ) {
+            log_dbg!("Decoded PVRTC");
+        } else {
+            unimplemented!("CompressedTexImage2D internalformat: {:#x}", internalformat);
+        }
+    }
+    unsafe fn CopyTexImage2D(
+        &mut self,
+        target: GLenum,
+        level: GLint,
+        internalformat: GLenum,
+        x: GLint,
+        y: GLint,
+        width: GLsizei,
+        height: GLsizei,
+        border: GLint,
+    ) {
+        gl21::CopyTexImage2D(target, level, internalformat, x, y, width, height, border)
+    }
+    unsafe fn CopyTexSubImage2D(
+        &mut self,
+        target: GLenum,
+        level: GLint,
+        xoffset: GLint,
+        yoffset: GLint,
+        x: GLint,
+        y: GLint,
+        width: GLsizei,
+        height: GLsizei,
+    ) {
+        gl21::CopyTexSubImage2D(target, level, xoffset, yoffset, x, y, width, height)
+    }
+
+    // Framebuffers (core in OpenGL ES 2.0, EXT_framebuffer_object in OpenGL
+    // 2.1)
+    unsafe fn GenFramebuffersOES(&mut self, n: GLsizei, framebuffers: *mut GLuint) {
+        gl21::GenFramebuffersEXT(n, framebuffers)
+    }
+    unsafe fn GenRenderbuffersOES(&mut self, n: GLsizei, renderbuffers: *mut GLuint) {
+        gl21::GenRenderbuffersEXT(n, renderbuffers)
+    }
+    unsafe fn BindFramebufferOES(&mut self, target: GLenum, framebuffer: GLuint) {
+        gl21::BindFramebufferEXT(target, framebuffer)
+    }
+    unsafe fn BindRenderbufferOES(&mut self, target: GLenum, renderbuffer: GLuint) {
+        gl21::BindRenderbufferEXT(target, renderbuffer)
+    }
+    unsafe fn RenderbufferStorageOES(
+        &mut self,
+        target: GLenum,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    ) {
+        gl21::RenderbufferStorageEXT(target, internalformat, width, height)
+    }
+    unsafe fn FramebufferRenderbufferOES(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        renderbuffertarget: GLenum,
+        renderbuffer: GLuint,
+    ) {
+        gl21::FramebufferRenderbufferEXT(target, attachment, renderbuffertarget, renderbuffer)
+    }
+    unsafe fn FramebufferTexture2DOES(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        textarget: GLenum,
+        texture: GLuint,
+        level: i32,
+    ) {
+        gl21::FramebufferTexture2DEXT(target, attachment, textarget, texture, level)
+    }
+    unsafe fn GetRenderbufferParameterivOES(
+        &mut self,
+        target: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    ) {
+        gl21::GetRenderbufferParameterivEXT(target, pname, params)
+    }
+    unsafe fn GetFramebufferAttachmentParameterivOES(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    ) {
+        gl21::GetFramebufferAttachmentParameterivEXT(target, attachment, pname, params)
+    }
+    unsafe fn CheckFramebufferStatusOES(&mut self, target: GLenum) -> GLenum {
+        gl21::CheckFramebufferStatusEXT(target)
+    }
+    unsafe fn DeleteFramebuffersOES(&mut self, n: GLsizei, framebuffers: *const GLuint) {
+        gl21::DeleteFramebuffersEXT(n, framebuffers)
+    }
+    unsafe fn DeleteRenderbuffersOES(&mut self, n: GLsizei, renderbuffers: *const GLuint) {
+        gl21::DeleteRenderbuffersEXT(n, renderbuffers)
+    }
+    unsafe fn GenerateMipmapOES(&mut self, target: GLenum) {
+        gl21::GenerateMipmapEXT(target)
+    }
+
+    // APPLE_framebuffer_multisample -> EXT_framebuffer_multisample and
+    // EXT_framebuffer_blit
+    unsafe fn RenderbufferStorageMultisampleAPPLE(
+        &mut self,
+        target: GLenum,
+        samples: GLsizei,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    ) {
+        gl21::RenderbufferStorageMultisampleEXT(target, samples, internalformat, width, height)
+    }
+    unsafe fn ResolveMultisampleFramebufferAPPLE(&mut self) {
+        resolve_multisample_framebuffer_gl21()
+    }
+
+    // Blending
+    unsafe fn BlendColor(
+        &mut self,
+        red: GLclampf,
+        green: GLclampf,
+        blue: GLclampf,
+        alpha: GLclampf,
+    ) {
+        gl21::BlendColor(red, green, blue, alpha)
+    }
+    unsafe fn BlendEquation(&mut self, mode: GLenum) {
+        gl21::BlendEquation(mode)
+    }
+    unsafe fn BlendEquationSeparate(&mut self, mode_rgb: GLenum, mode_alpha: GLenum) {
+        gl21::BlendEquationSeparate(mode_rgb, mode_alpha)
+    }
+    unsafe fn BlendFuncSeparate(
+        &mut self,
+        sfactor_rgb: GLenum,
+        dfactor_rgb: GLenum,
+        sfactor_alpha: GLenum,
+        dfactor_alpha: GLenum,
+    ) {
+        gl21::BlendFuncSeparate(sfactor_rgb, dfactor_rgb, sfactor_alpha, dfactor_alpha)
+    }
+
+    // Shaders
+    unsafe fn CreateShader(&mut self, type_: GLenum) -> GLuint {
+        gl21::CreateShader(type_)
+    }
+    unsafe fn ShaderSource(
+        &mut self,
+        shader: GLuint,
+        count: GLsizei,
+        string: *const *const GLchar,
+        length: *const GLint,
+    ) {
+        let Ok(count) = usize::try_from(count) else {
+            // Let the driver report GL_INVALID_VALUE.
+            return gl21::ShaderSource(shader, count, string, length);
+        };
+        let mut source = Vec::<u8>::new();
+        for i in 0..count {
+            let string = *string.add(i);
+            let length = if length.is_null() { -1 } else { *length.add(i) };
+            if length < 0 {
+                source.extend_from_slice(CStr::from_ptr(string).to_bytes());
+            } else {
+                source
+                    .extend_from_slice(std::slice::from_raw_parts(string.cast(), length as usize));
+            }
+        }
+        let source = translate_shader_source(&String::from_utf8_lossy(&source));
+        log_dbg!("Translated shader {} source:\n{}", shader, source);
+        let source_ptr: *const GLchar = source.as_ptr().cast();
+        let source_len: GLint = source.len().try_into().unwrap();
+        gl21::ShaderSource(shader, 1, &source_ptr, &source_len)
+    }
+    unsafe fn CompileShader(&mut self, shader: GLuint) {
+        gl21::CompileShader(shader)
+    }
+    unsafe fn GetShaderiv(&mut self, shader: GLuint, pname: GLenum, params: *mut GLint) {
+        gl21::GetShaderiv(shader, pname, params)
+    }
+    unsafe fn GetShaderInfoLog(
+        &mut self,
+        shader: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        info_log: *mut GLchar,
+    ) {
+        gl21::GetShaderInfoLog(shader, buf_size, length, info_log)
+    }
+    unsafe fn IsShader(&mut self, shader: GLuint) -> GLboolean {
+        gl21::IsShader(shader)
+    }
+    unsafe fn DeleteShader(&mut self, shader: GLuint) {
+        gl21::DeleteShader(shader)
+    }
+
+    // Programs
+    unsafe fn CreateProgram(&mut self) -> GLuint {
+        gl21::CreateProgram()
+    }
+    unsafe fn AttachShader(&mut self, program: GLuint, shader: GLuint) {
+        gl21::AttachShader(program, shader)
+    }
+    unsafe fn DetachShader(&mut self, program: GLuint, shader: GLuint) {
+        gl21::DetachShader(program, shader)
+    }
+    unsafe fn BindAttribLocation(&mut self, program: GLuint, index: GLuint, name: *const GLchar) {
+        gl21::BindAttribLocation(program, index, name)
+    }
+    unsafe fn LinkProgram(&mut self, program: GLuint) {
+        gl21::LinkProgram(program)
+    }
+    unsafe fn ValidateProgram(&mut self, program: GLuint) {
+        gl21::ValidateProgram(program)
+    }
+    unsafe fn UseProgram(&mut self, program: GLuint) {
+        gl21::UseProgram(program)
+    }
+    unsafe fn GetProgramiv(&mut self, program: GLuint, pname: GLenum, params: *mut GLint) {
+        gl21::GetProgramiv(program, pname, params)
+    }
+    unsafe fn GetProgramInfoLog(
+        &mut self,
+        program: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        info_log: *mut GLchar,
+    ) {
+        gl21::GetProgramInfoLog(program, buf_size, length, info_log)
+    }
+    unsafe fn IsProgram(&mut self, program: GLuint) -> GLboolean {
+        gl21::IsProgram(program)
+    }
+    unsafe fn DeleteProgram(&mut self, program: GLuint) {
+        gl21::DeleteProgram(program)
+    }
+    unsafe fn GetAttribLocation(&mut self, program: GLuint, name: *const GLchar) -> GLint {
+        gl21::GetAttribLocation(program, name)
+    }
+    unsafe fn GetUniformLocation(&mut self, program: GLuint, name: *const GLchar) -> GLint {
+        gl21::GetUniformLocation(program, name)
+    }
+    unsafe fn GetActiveAttrib(
+        &mut self,
+        program: GLuint,
+        index: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar,
+    ) {
+        gl21::GetActiveAttrib(program, index, buf_size, length, size, type_, name)
+    }
+    unsafe fn GetActiveUniform(
+        &mut self,
+        program: GLuint,
+        index: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar,
+    ) {
+        gl21::GetActiveUniform(program, index, buf_size, length, size, type_, name)
+    }
+
+    // Uniforms
+    unsafe fn Uniform1f(&mut self, location: GLint, v0: GLfloat) {
+        gl21::Uniform1f(location, v0)
+    }
+    unsafe fn Uniform2f(&mut self, location: GLint, v0: GLfloat, v1: GLfloat) {
+        gl21::Uniform2f(location, v0, v1)
+    }
+    unsafe fn Uniform3f(&mut self, location: GLint, v0: GLfloat, v1: GLfloat, v2: GLfloat) {
+        gl21::Uniform3f(location, v0, v1, v2)
+    }
+    unsafe fn Uniform4f(
+        &mut self,
+        location: GLint,
+        v0: GLfloat,
+        v1: GLfloat,
+        v2: GLfloat,
+        v3: GLfloat,
+    ) {
+        gl21::Uniform4f(location, v0, v1, v2, v3)
+    }
+    unsafe fn Uniform1i(&mut self, location: GLint, v0: GLint) {
+        gl21::Uniform1i(location, v0)
+    }
+    unsafe fn Uniform2i(&mut self, location: GLint, v0: GLint, v1: GLint) {
+        gl21::Uniform2i(location, v0, v1)
+    }
+    unsafe fn Uniform3i(&mut self, location: GLint, v0: GLint, v1: GLint, v2: GLint) {
+        gl21::Uniform3i(location, v0, v1, v2)
+    }
+    unsafe fn Uniform4i(&mut self, location: GLint, v0: GLint, v1: GLint, v2: GLint, v3: GLint) {
+        gl21::Uniform4i(location, v0, v1, v2, v3)
+    }
+    unsafe fn Uniform1fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        gl21::Uniform1fv(location, count, value)
+    }
+    unsafe fn Uniform2fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        gl21::Uniform2fv(location, count, value)
+    }
+    unsafe fn Uniform3fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        gl21::Uniform3fv(location, count, value)
+    }
+    unsafe fn Uniform4fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        gl21::Uniform4fv(location, count, value)
+    }
+    unsafe fn Uniform1iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        gl21::Uniform1iv(location, count, value)
+    }
+    unsafe fn Uniform2iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        gl21::Uniform2iv(location, count, value)
+    }
+    unsafe fn Uniform3iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        gl21::Uniform3iv(location, count, value)
+    }
+    unsafe fn Uniform4iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        gl21::Uniform4iv(location, count, value)
+    }
+    unsafe fn UniformMatrix2fv(
+        &mut self,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    ) {
+        gl21::UniformMatrix2fv(location, count, transpose, value)
+    }
+    unsafe fn UniformMatrix3fv(
+        &mut self,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    ) {
+        gl21::UniformMatrix3fv(location, count, transpose, value)
+    }
+    unsafe fn UniformMatrix4fv(
+        &mut self,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    ) {
+        gl21::UniformMatrix4fv(location, count, transpose, value)
+    }
+
+    // Vertex attributes
+    unsafe fn EnableVertexAttribArray(&mut self, index: GLuint) {
+        gl21::EnableVertexAttribArray(index)
+    }
+    unsafe fn DisableVertexAttribArray(&mut self, index: GLuint) {
+        gl21::DisableVertexAttribArray(index)
+    }
+    unsafe fn VertexAttribPointer(
+        &mut self,
+        index: GLuint,
+        size: GLint,
+        type_: GLenum,
+        normalized: GLboolean,
+        stride: GLsizei,
+        pointer: *const GLvoid,
+    ) {
+        gl21::VertexAttribPointer(index, size, type_, normalized, stride, pointer)
+    }
+    unsafe fn VertexAttrib1f(&mut self, index: GLuint, x: GLfloat) {
+        gl21::VertexAttrib1f(index, x)
+    }
+    unsafe fn VertexAttrib2f(&mut self, index: GLuint, x: GLfloat, y: GLfloat) {
+        gl21::VertexAttrib2f(index, x, y)
+    }
+    unsafe fn VertexAttrib3f(&mut self, index: GLuint, x: GLfloat, y: GLfloat, z: GLfloat) {
+        gl21::VertexAttrib3f(index, x, y, z)
+    }
+    unsafe fn VertexAttrib4f(
+        &mut self,
+        index: GLuint,
+        x: GLfloat,
+        y: GLfloat,
+        z: GLfloat,
+        w: GLfloat,
+    ) {
+        gl21::VertexAttrib4f(index, x, y, z, w)
+    }
+    unsafe fn VertexAttrib1fv(&mut self, index: GLuint, v: *const GLfloat) {
+        gl21::VertexAttrib1fv(index, v)
+    }
+    unsafe fn VertexAttrib2fv(&mut self, index: GLuint, v: *const GLfloat) {
+        gl21::VertexAttrib2fv(index, v)
+    }
+    unsafe fn VertexAttrib3fv(&mut self, index: GLuint, v: *const GLfloat) {
+        gl21::VertexAttrib3fv(index, v)
+    }
+    unsafe fn VertexAttrib4fv(&mut self, index: GLuint, v: *const GLfloat) {
+        gl21::VertexAttrib4fv(index, v)
+    }
+    unsafe fn GetVertexAttribiv(&mut self, index: GLuint, pname: GLenum, params: *mut GLint) {
+        gl21::GetVertexAttribiv(index, pname, params)
+    }
+    unsafe fn GetVertexAttribfv(&mut self, index: GLuint, pname: GLenum, params: *mut GLfloat) {
+        gl21::GetVertexAttribfv(index, pname, params)
+    }
+    unsafe fn GetVertexAttribPointerv(
+        &mut self,
+        index: GLuint,
+        pname: GLenum,
+        pointer: *mut *const GLvoid,
+    ) {
+        // The third argument to glGetVertexAttribPointerv must be a mutable
+        // pointer, but gl_generator generates the wrong signature by mistake,
+        // see https://github.com/brendanzab/gl-rs/issues/541
+        gl21::GetVertexAttribPointerv(index, pname, pointer as *mut _ as *const _)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::translate_shader_source;
+
+    #[test]
+    fn test_translate_shader_source() {
+        let source = "#version 100\n\
+                      precision mediump float;\n\
+                      varying lowp vec4 color;\n\
+                      void main() { gl_FragColor = color; }\n";
+        assert_eq!(
+            translate_shader_source(source),
+            "#version 120\n\
+             #define lowp\n\
+             #define mediump\n\
+             #define highp\n\
+             #line 0\n\
+             //#version 100\n\
+             //precision mediump float;\n\
+             varying lowp vec4 color;\n\
+             void main() { gl_FragColor = color; }\n"
+        );
+    }
+}
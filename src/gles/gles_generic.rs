@@ -3,21 +3,38 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-//! Generic OpenGL ES 1.1 interface.
+//! Generic OpenGL ES 1.1 and 2.0 interface.
 //!
 //! Unfortunately this does not provide the types and constants, so the correct
 //! usage is to import `GLES` and `types` from this module, but get the
-//! constants from [super::gles11_raw].
+//! constants from [super::gles11_raw] (or [super::gles2_raw] for constants
+//! that only exist in OpenGL ES 2.0).
 
 use super::gles11_raw::types::*;
 
+/// Used by the default implementations of the [GLES] methods that are specific
+/// to one version of OpenGL ES. The functions exposed to the guest check the
+/// version before calling these methods, so reaching this is a touchHLE bug.
+fn unsupported(name: &str) -> ! {
+    panic!("gl{}() is not available in this OpenGL ES version", name)
+}
+
 /// Trait representing an OpenGL ES implementation and context.
 ///
+/// This covers both OpenGL ES 1.1 and 2.0. Functions that only exist in one
+/// version have a default implementation that panics, so an implementation
+/// only needs to provide the functions of the version it implements.
+///
+/// The `OES_framebuffer_object` functions are part of the core of OpenGL ES
+/// 2.0, and OpenGL ES 2.0 implementations should provide the core functions
+/// through these methods.
+///
 /// # Safety
 /// It is the caller's responsibility to make the context active before using
 /// any of the `unsafe` methods of this trait.
 #[allow(clippy::upper_case_acronyms)]
 #[allow(clippy::too_many_arguments)] // not our fault :(
+#[allow(unused_variables)] // default implementations
 pub trait GLES {
     /// Get a human-friendly description of this implementation.
     fn description() -> &'static str
@@ -26,39 +43,94 @@ pub trait GLES {
 
     /// Construct a new context. This might fail if the host OS doesn't have a
     /// compatible driver, for example.
+    ///
+    /// If `share_with_current` is [true], the new context will share objects
+    /// with the current context, which must use the same implementation.
     #[allow(clippy::new_ret_no_self)]
-    fn new(window: &mut crate::window::Window) -> Result<Self, String>
+    fn new(window: &mut crate::window::Window, share_with_current: bool) -> Result<Self, String>
     where
         Self: Sized;
 
     /// Make this context (and any underlying context) the active OpenGL
     /// context.
-    fn make_current(&self, window: &crate::window::Window);
+    fn try_make_current(&self, window: &crate::window::Window) -> Result<(), String>;
+
+    /// Like [Self::try_make_current], but for when failure would be a bug.
+    fn make_current(&self, window: &crate::window::Window) {
+        self.try_make_current(window).unwrap()
+    }
 
     /// Get some string describing the underlying driver. For OpenGL this is
     /// `GL_VENDOR`, `GL_RENDERER` and `GL_VERSION`.
     unsafe fn driver_description(&self) -> String;
 
+    /// Start reading the pixels of the current framebuffer into a pixel
+    /// buffer object, like `glReadPixels(0, 0, width, height, GL_RGBA,
+    /// GL_UNSIGNED_BYTE, …)` but without waiting for rendering to finish.
+    /// The pixels can be retrieved later with
+    /// [GLES::finish_read_pixels_async].
+    ///
+    /// `buffer` is a buffer object previously returned by this method, which
+    /// will be reused, or 0 to create a new one. It can be deleted with
+    /// [GLES::DeleteBuffers].
+    ///
+    /// Pixel buffer objects aren't part of OpenGL ES 1.1 or 2.0, so this is
+    /// only for touchHLE's own use. Returns [None] if the implementation
+    /// doesn't support them, in which case [GLES::ReadPixels] must be used.
+    unsafe fn start_read_pixels_async(
+        &mut self,
+        buffer: GLuint,
+        width: GLsizei,
+        height: GLsizei,
+    ) -> Option<GLuint> {
+        None
+    }
+    /// Get the pixels read by [GLES::start_read_pixels_async], waiting for the
+    /// read to finish if necessary. The [Vec]'s contents are replaced.
+    unsafe fn finish_read_pixels_async(
+        &mut self,
+        buffer: GLuint,
+        width: GLsizei,
+        height: GLsizei,
+        pixels: &mut Vec<u8>,
+    ) {
+        unsupported("finish_read_pixels_async")
+    }
+
     // Generic state manipulation
     unsafe fn GetError(&mut self) -> GLenum;
     unsafe fn Enable(&mut self, cap: GLenum);
     unsafe fn IsEnabled(&mut self, cap: GLenum) -> GLboolean;
     unsafe fn Disable(&mut self, cap: GLenum);
-    unsafe fn ClientActiveTexture(&mut self, texture: GLenum);
-    unsafe fn EnableClientState(&mut self, array: GLenum);
-    unsafe fn DisableClientState(&mut self, array: GLenum);
+    unsafe fn ClientActiveTexture(&mut self, texture: GLenum) {
+        unsupported("ClientActiveTexture")
+    }
+    unsafe fn EnableClientState(&mut self, array: GLenum) {
+        unsupported("EnableClientState")
+    }
+    unsafe fn DisableClientState(&mut self, array: GLenum) {
+        unsupported("DisableClientState")
+    }
     unsafe fn GetBooleanv(&mut self, pname: GLenum, params: *mut GLboolean);
     unsafe fn GetFloatv(&mut self, pname: GLenum, params: *mut GLfloat);
     unsafe fn GetIntegerv(&mut self, pname: GLenum, params: *mut GLint);
-    unsafe fn GetTexEnviv(&mut self, target: GLenum, pname: GLenum, params: *mut GLint);
-    unsafe fn GetPointerv(&mut self, pname: GLenum, params: *mut *const GLvoid);
+    unsafe fn GetTexEnviv(&mut self, target: GLenum, pname: GLenum, params: *mut GLint) {
+        unsupported("GetTexEnviv")
+    }
+    unsafe fn GetPointerv(&mut self, pname: GLenum, params: *mut *const GLvoid) {
+        unsupported("GetPointerv")
+    }
     unsafe fn Hint(&mut self, target: GLenum, mode: GLenum);
     unsafe fn Flush(&mut self);
     unsafe fn GetString(&mut self, name: GLenum) -> *const GLubyte;
 
     // Other state manipulation
-    unsafe fn AlphaFunc(&mut self, func: GLenum, ref_: GLclampf);
-    unsafe fn AlphaFuncx(&mut self, func: GLenum, ref_: GLclampx);
+    unsafe fn AlphaFunc(&mut self, func: GLenum, ref_: GLclampf) {
+        unsupported("AlphaFunc")
+    }
+    unsafe fn AlphaFuncx(&mut self, func: GLenum, ref_: GLclampx) {
+        unsupported("AlphaFuncx")
+    }
     unsafe fn BlendFunc(&mut self, sfactor: GLenum, dfactor: GLenum);
     unsafe fn ColorMask(
         &mut self,
@@ -71,39 +143,87 @@ pub trait GLES {
     unsafe fn DepthFunc(&mut self, func: GLenum);
     unsafe fn DepthMask(&mut self, flag: GLboolean);
     unsafe fn DepthRangef(&mut self, near: GLclampf, far: GLclampf);
-    unsafe fn DepthRangex(&mut self, near: GLclampx, far: GLclampx);
+    unsafe fn DepthRangex(&mut self, near: GLclampx, far: GLclampx) {
+        unsupported("DepthRangex")
+    }
     unsafe fn FrontFace(&mut self, mode: GLenum);
     unsafe fn PolygonOffset(&mut self, factor: GLfloat, units: GLfloat);
-    unsafe fn PolygonOffsetx(&mut self, factor: GLfixed, units: GLfixed);
-    unsafe fn ShadeModel(&mut self, mode: GLenum);
+    unsafe fn PolygonOffsetx(&mut self, factor: GLfixed, units: GLfixed) {
+        unsupported("PolygonOffsetx")
+    }
+    unsafe fn ShadeModel(&mut self, mode: GLenum) {
+        unsupported("ShadeModel")
+    }
     unsafe fn Scissor(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei);
     unsafe fn Viewport(&mut self, x: GLint, y: GLint, width: GLsizei, height: GLsizei);
     unsafe fn LineWidth(&mut self, val: GLfloat);
-    unsafe fn LineWidthx(&mut self, val: GLfixed);
+    unsafe fn LineWidthx(&mut self, val: GLfixed) {
+        unsupported("LineWidthx")
+    }
 
     // Points
-    unsafe fn PointSize(&mut self, size: GLfloat);
-    unsafe fn PointSizex(&mut self, size: GLfixed);
-    unsafe fn PointParameterf(&mut self, pname: GLenum, param: GLfloat);
-    unsafe fn PointParameterx(&mut self, pname: GLenum, param: GLfixed);
-    unsafe fn PointParameterfv(&mut self, pname: GLenum, params: *const GLfloat);
-    unsafe fn PointParameterxv(&mut self, pname: GLenum, params: *const GLfixed);
+    unsafe fn PointSize(&mut self, size: GLfloat) {
+        unsupported("PointSize")
+    }
+    unsafe fn PointSizex(&mut self, size: GLfixed) {
+        unsupported("PointSizex")
+    }
+    unsafe fn PointParameterf(&mut self, pname: GLenum, param: GLfloat) {
+        unsupported("PointParameterf")
+    }
+    unsafe fn PointParameterx(&mut self, pname: GLenum, param: GLfixed) {
+        unsupported("PointParameterx")
+    }
+    unsafe fn PointParameterfv(&mut self, pname: GLenum, params: *const GLfloat) {
+        unsupported("PointParameterfv")
+    }
+    unsafe fn PointParameterxv(&mut self, pname: GLenum, params: *const GLfixed) {
+        unsupported("PointParameterxv")
+    }
 
     // Lighting and materials
-    unsafe fn Fogf(&mut self, pname: GLenum, param: GLfloat);
-    unsafe fn Fogx(&mut self, pname: GLenum, param: GLfixed);
-    unsafe fn Fogfv(&mut self, pname: GLenum, params: *const GLfloat);
-    unsafe fn Fogxv(&mut self, pname: GLenum, params: *const GLfixed);
-    unsafe fn Lightf(&mut self, light: GLenum, pname: GLenum, param: GLfloat);
-    unsafe fn Lightx(&mut self, light: GLenum, pname: GLenum, param: GLfixed);
-    unsafe fn Lightfv(&mut self, light: GLenum, pname: GLenum, params: *const GLfloat);
-    unsafe fn Lightxv(&mut self, light: GLenum, pname: GLenum, params: *const GLfixed);
-    unsafe fn LightModelf(&mut self, pname: GLenum, param: GLfloat);
-    unsafe fn LightModelfv(&mut self, pname: GLenum, params: *const GLfloat);
-    unsafe fn Materialf(&mut self, face: GLenum, pname: GLenum, param: GLfloat);
-    unsafe fn Materialx(&mut self, face: GLenum, pname: GLenum, param: GLfixed);
-    unsafe fn Materialfv(&mut self, face: GLenum, pname: GLenum, params: *const GLfloat);
-    unsafe fn Materialxv(&mut self, face: GLenum, pname: GLenum, params: *const GLfixed);
+    unsafe fn Fogf(&mut self, pname: GLenum, param: GLfloat) {
+        unsupported("Fogf")
+    }
+    unsafe fn Fogx(&mut self, pname: GLenum, param: GLfixed) {
+        unsupported("Fogx")
+    }
+    unsafe fn Fogfv(&mut self, pname: GLenum, params: *const GLfloat) {
+        unsupported("Fogfv")
+    }
+    unsafe fn Fogxv(&mut self, pname: GLenum, params: *const GLfixed) {
+        unsupported("Fogxv")
+    }
+    unsafe fn Lightf(&mut self, light: GLenum, pname: GLenum, param: GLfloat) {
+        unsupported("Lightf")
+    }
+    unsafe fn Lightx(&mut self, light: GLenum, pname: GLenum, param: GLfixed) {
+        unsupported("Lightx")
+    }
+    unsafe fn Lightfv(&mut self, light: GLenum, pname: GLenum, params: *const GLfloat) {
+        unsupported("Lightfv")
+    }
+    unsafe fn Lightxv(&mut self, light: GLenum, pname: GLenum, params: *const GLfixed) {
+        unsupported("Lightxv")
+    }
+    unsafe fn LightModelf(&mut self, pname: GLenum, param: GLfloat) {
+        unsupported("LightModelf")
+    }
+    unsafe fn LightModelfv(&mut self, pname: GLenum, params: *const GLfloat) {
+        unsupported("LightModelfv")
+    }
+    unsafe fn Materialf(&mut self, face: GLenum, pname: GLenum, param: GLfloat) {
+        unsupported("Materialf")
+    }
+    unsafe fn Materialx(&mut self, face: GLenum, pname: GLenum, param: GLfixed) {
+        unsupported("Materialx")
+    }
+    unsafe fn Materialfv(&mut self, face: GLenum, pname: GLenum, params: *const GLfloat) {
+        unsupported("Materialfv")
+    }
+    unsafe fn Materialxv(&mut self, face: GLenum, pname: GLenum, params: *const GLfixed) {
+        unsupported("Materialxv")
+    }
 
     // Buffers
     unsafe fn GenBuffers(&mut self, n: GLsizei, buffers: *mut GLuint);
@@ -125,11 +245,21 @@ pub trait GLES {
     );
 
     // Non-pointers
-    unsafe fn Color4f(&mut self, red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat);
-    unsafe fn Color4x(&mut self, red: GLfixed, green: GLfixed, blue: GLfixed, alpha: GLfixed);
-    unsafe fn Color4ub(&mut self, red: GLubyte, green: GLubyte, blue: GLubyte, alpha: GLubyte);
-    unsafe fn Normal3f(&mut self, nx: GLfloat, ny: GLfloat, nz: GLfloat);
-    unsafe fn Normal3x(&mut self, nx: GLfixed, ny: GLfixed, nz: GLfixed);
+    unsafe fn Color4f(&mut self, red: GLfloat, green: GLfloat, blue: GLfloat, alpha: GLfloat) {
+        unsupported("Color4f")
+    }
+    unsafe fn Color4x(&mut self, red: GLfixed, green: GLfixed, blue: GLfixed, alpha: GLfixed) {
+        unsupported("Color4x")
+    }
+    unsafe fn Color4ub(&mut self, red: GLubyte, green: GLubyte, blue: GLubyte, alpha: GLubyte) {
+        unsupported("Color4ub")
+    }
+    unsafe fn Normal3f(&mut self, nx: GLfloat, ny: GLfloat, nz: GLfloat) {
+        unsupported("Normal3f")
+    }
+    unsafe fn Normal3x(&mut self, nx: GLfixed, ny: GLfixed, nz: GLfixed) {
+        unsupported("Normal3x")
+    }
 
     // Pointers
     unsafe fn ColorPointer(
@@ -138,22 +268,30 @@ pub trait GLES {
         type_: GLenum,
         stride: GLsizei,
         pointer: *const GLvoid,
-    );
-    unsafe fn NormalPointer(&mut self, type_: GLenum, stride: GLsizei, pointer: *const GLvoid);
+    ) {
+        unsupported("ColorPointer")
+    }
+    unsafe fn NormalPointer(&mut self, type_: GLenum, stride: GLsizei, pointer: *const GLvoid) {
+        unsupported("NormalPointer")
+    }
     unsafe fn TexCoordPointer(
         &mut self,
         size: GLint,
         type_: GLenum,
         stride: GLsizei,
         pointer: *const GLvoid,
-    );
+    ) {
+        unsupported("TexCoordPointer")
+    }
     unsafe fn VertexPointer(
         &mut self,
         size: GLint,
         type_: GLenum,
         stride: GLsizei,
         pointer: *const GLvoid,
-    );
+    ) {
+        unsupported("VertexPointer")
+    }
 
     // Drawing
     unsafe fn DrawArrays(&mut self, mode: GLenum, first: GLint, count: GLsizei);
@@ -180,9 +318,13 @@ pub trait GLES {
         green: GLclampx,
         blue: GLclampx,
         alpha: GLclampx,
-    );
+    ) {
+        unsupported("ClearColorx")
+    }
     unsafe fn ClearDepthf(&mut self, depth: GLclampf);
-    unsafe fn ClearDepthx(&mut self, depth: GLclampx);
+    unsafe fn ClearDepthx(&mut self, depth: GLclampx) {
+        unsupported("ClearDepthx")
+    }
     unsafe fn ClearStencil(&mut self, s: GLint);
 
     // Textures
@@ -203,10 +345,14 @@ pub trait GLES {
     unsafe fn BindTexture(&mut self, target: GLenum, texture: GLuint);
     unsafe fn TexParameteri(&mut self, target: GLenum, pname: GLenum, param: GLint);
     unsafe fn TexParameterf(&mut self, target: GLenum, pname: GLenum, param: GLfloat);
-    unsafe fn TexParameterx(&mut self, target: GLenum, pname: GLenum, param: GLfixed);
+    unsafe fn TexParameterx(&mut self, target: GLenum, pname: GLenum, param: GLfixed) {
+        unsupported("TexParameterx")
+    }
     unsafe fn TexParameteriv(&mut self, target: GLenum, pname: GLenum, params: *const GLint);
     unsafe fn TexParameterfv(&mut self, target: GLenum, pname: GLenum, params: *const GLfloat);
-    unsafe fn TexParameterxv(&mut self, target: GLenum, pname: GLenum, params: *const GLfixed);
+    unsafe fn TexParameterxv(&mut self, target: GLenum, pname: GLenum, params: *const GLfixed) {
+        unsupported("TexParameterxv")
+    }
     unsafe fn TexImage2D(
         &mut self,
         target: GLenum,
@@ -264,22 +410,50 @@ pub trait GLES {
         width: GLsizei,
         height: GLsizei,
     );
-    unsafe fn TexEnvf(&mut self, target: GLenum, pname: GLenum, param: GLfloat);
-    unsafe fn TexEnvx(&mut self, target: GLenum, pname: GLenum, param: GLfixed);
-    unsafe fn TexEnvi(&mut self, target: GLenum, pname: GLenum, param: GLint);
-    unsafe fn TexEnvfv(&mut self, target: GLenum, pname: GLenum, params: *const GLfloat);
-    unsafe fn TexEnvxv(&mut self, target: GLenum, pname: GLenum, params: *const GLfixed);
-    unsafe fn TexEnviv(&mut self, target: GLenum, pname: GLenum, params: *const GLint);
+    unsafe fn TexEnvf(&mut self, target: GLenum, pname: GLenum, param: GLfloat) {
+        unsupported("TexEnvf")
+    }
+    unsafe fn TexEnvx(&mut self, target: GLenum, pname: GLenum, param: GLfixed) {
+        unsupported("TexEnvx")
+    }
+    unsafe fn TexEnvi(&mut self, target: GLenum, pname: GLenum, param: GLint) {
+        unsupported("TexEnvi")
+    }
+    unsafe fn TexEnvfv(&mut self, target: GLenum, pname: GLenum, params: *const GLfloat) {
+        unsupported("TexEnvfv")
+    }
+    unsafe fn TexEnvxv(&mut self, target: GLenum, pname: GLenum, params: *const GLfixed) {
+        unsupported("TexEnvxv")
+    }
+    unsafe fn TexEnviv(&mut self, target: GLenum, pname: GLenum, params: *const GLint) {
+        unsupported("TexEnviv")
+    }
 
     // Matrix stack operations
-    unsafe fn MatrixMode(&mut self, mode: GLenum);
-    unsafe fn LoadIdentity(&mut self);
-    unsafe fn LoadMatrixf(&mut self, m: *const GLfloat);
-    unsafe fn LoadMatrixx(&mut self, m: *const GLfixed);
-    unsafe fn MultMatrixf(&mut self, m: *const GLfloat);
-    unsafe fn MultMatrixx(&mut self, m: *const GLfixed);
-    unsafe fn PushMatrix(&mut self);
-    unsafe fn PopMatrix(&mut self);
+    unsafe fn MatrixMode(&mut self, mode: GLenum) {
+        unsupported("MatrixMode")
+    }
+    unsafe fn LoadIdentity(&mut self) {
+        unsupported("LoadIdentity")
+    }
+    unsafe fn LoadMatrixf(&mut self, m: *const GLfloat) {
+        unsupported("LoadMatrixf")
+    }
+    unsafe fn LoadMatrixx(&mut self, m: *const GLfixed) {
+        unsupported("LoadMatrixx")
+    }
+    unsafe fn MultMatrixf(&mut self, m: *const GLfloat) {
+        unsupported("MultMatrixf")
+    }
+    unsafe fn MultMatrixx(&mut self, m: *const GLfixed) {
+        unsupported("MultMatrixx")
+    }
+    unsafe fn PushMatrix(&mut self) {
+        unsupported("PushMatrix")
+    }
+    unsafe fn PopMatrix(&mut self) {
+        unsupported("PopMatrix")
+    }
     unsafe fn Orthof(
         &mut self,
         left: GLfloat,
@@ -288,7 +462,9 @@ pub trait GLES {
         top: GLfloat,
         near: GLfloat,
         far: GLfloat,
-    );
+    ) {
+        unsupported("Orthof")
+    }
     unsafe fn Orthox(
         &mut self,
         left: GLfixed,
@@ -297,7 +473,9 @@ pub trait GLES {
         top: GLfixed,
         near: GLfixed,
         far: GLfixed,
-    );
+    ) {
+        unsupported("Orthox")
+    }
     unsafe fn Frustumf(
         &mut self,
         left: GLfloat,
@@ -306,7 +484,9 @@ pub trait GLES {
         top: GLfloat,
         near: GLfloat,
         far: GLfloat,
-    );
+    ) {
+        unsupported("Frustumf")
+    }
     unsafe fn Frustumx(
         &mut self,
         left: GLfixed,
@@ -315,15 +495,30 @@ pub trait GLES {
         top: GLfixed,
         near: GLfixed,
         far: GLfixed,
-    );
-    unsafe fn Rotatef(&mut self, angle: GLfloat, x: GLfloat, y: GLfloat, z: GLfloat);
-    unsafe fn Rotatex(&mut self, angle: GLfixed, x: GLfixed, y: GLfixed, z: GLfixed);
-    unsafe fn Scalef(&mut self, x: GLfloat, y: GLfloat, z: GLfloat);
-    unsafe fn Scalex(&mut self, x: GLfixed, y: GLfixed, z: GLfixed);
-    unsafe fn Translatef(&mut self, x: GLfloat, y: GLfloat, z: GLfloat);
-    unsafe fn Translatex(&mut self, x: GLfixed, y: GLfixed, z: GLfixed);
+    ) {
+        unsupported("Frustumx")
+    }
+    unsafe fn Rotatef(&mut self, angle: GLfloat, x: GLfloat, y: GLfloat, z: GLfloat) {
+        unsupported("Rotatef")
+    }
+    unsafe fn Rotatex(&mut self, angle: GLfixed, x: GLfixed, y: GLfixed, z: GLfixed) {
+        unsupported("Rotatex")
+    }
+    unsafe fn Scalef(&mut self, x: GLfloat, y: GLfloat, z: GLfloat) {
+        unsupported("Scalef")
+    }
+    unsafe fn Scalex(&mut self, x: GLfixed, y: GLfixed, z: GLfixed) {
+        unsupported("Scalex")
+    }
+    unsafe fn Translatef(&mut self, x: GLfloat, y: GLfloat, z: GLfloat) {
+        unsupported("Translatef")
+    }
+    unsafe fn Translatex(&mut self, x: GLfixed, y: GLfixed, z: GLfixed) {
+        unsupported("Translatex")
+    }
 
-    // OES_framebuffer_object (incomplete)
+    // OES_framebuffer_object (incomplete), or the core equivalents in OpenGL ES
+    // 2.0
     unsafe fn GenFramebuffersOES(&mut self, n: GLsizei, framebuffers: *mut GLuint);
     unsafe fn GenRenderbuffersOES(&mut self, n: GLsizei, renderbuffers: *mut GLuint);
     unsafe fn BindFramebufferOES(&mut self, target: GLenum, framebuffer: GLuint);
@@ -356,8 +551,306 @@ pub trait GLES {
         pname: GLenum,
         params: *mut GLint,
     );
+    unsafe fn GetFramebufferAttachmentParameterivOES(
+        &mut self,
+        target: GLenum,
+        attachment: GLenum,
+        pname: GLenum,
+        params: *mut GLint,
+    );
     unsafe fn CheckFramebufferStatusOES(&mut self, target: GLenum) -> GLenum;
     unsafe fn DeleteFramebuffersOES(&mut self, n: GLsizei, framebuffers: *const GLuint);
     unsafe fn DeleteRenderbuffersOES(&mut self, n: GLsizei, renderbuffers: *const GLuint);
     unsafe fn GenerateMipmapOES(&mut self, target: GLenum);
+
+    // APPLE_framebuffer_multisample
+    unsafe fn RenderbufferStorageMultisampleAPPLE(
+        &mut self,
+        target: GLenum,
+        samples: GLsizei,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+    );
+    unsafe fn ResolveMultisampleFramebufferAPPLE(&mut self);
+
+    // OpenGL ES 2.0: blending
+    unsafe fn BlendColor(
+        &mut self,
+        red: GLclampf,
+        green: GLclampf,
+        blue: GLclampf,
+        alpha: GLclampf,
+    ) {
+        unsupported("BlendColor")
+    }
+    unsafe fn BlendEquation(&mut self, mode: GLenum) {
+        unsupported("BlendEquation")
+    }
+    unsafe fn BlendEquationSeparate(&mut self, mode_rgb: GLenum, mode_alpha: GLenum) {
+        unsupported("BlendEquationSeparate")
+    }
+    unsafe fn BlendFuncSeparate(
+        &mut self,
+        sfactor_rgb: GLenum,
+        dfactor_rgb: GLenum,
+        sfactor_alpha: GLenum,
+        dfactor_alpha: GLenum,
+    ) {
+        unsupported("BlendFuncSeparate")
+    }
+
+    // OpenGL ES 2.0: shaders
+    unsafe fn CreateShader(&mut self, type_: GLenum) -> GLuint {
+        unsupported("CreateShader")
+    }
+    unsafe fn ShaderSource(
+        &mut self,
+        shader: GLuint,
+        count: GLsizei,
+        string: *const *const GLchar,
+        length: *const GLint,
+    ) {
+        unsupported("ShaderSource")
+    }
+    unsafe fn CompileShader(&mut self, shader: GLuint) {
+        unsupported("CompileShader")
+    }
+    unsafe fn GetShaderiv(&mut self, shader: GLuint, pname: GLenum, params: *mut GLint) {
+        unsupported("GetShaderiv")
+    }
+    unsafe fn GetShaderInfoLog(
+        &mut self,
+        shader: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        info_log: *mut GLchar,
+    ) {
+        unsupported("GetShaderInfoLog")
+    }
+    unsafe fn IsShader(&mut self, shader: GLuint) -> GLboolean {
+        unsupported("IsShader")
+    }
+    unsafe fn DeleteShader(&mut self, shader: GLuint) {
+        unsupported("DeleteShader")
+    }
+
+    // OpenGL ES 2.0: programs
+    unsafe fn CreateProgram(&mut self) -> GLuint {
+        unsupported("CreateProgram")
+    }
+    unsafe fn AttachShader(&mut self, program: GLuint, shader: GLuint) {
+        unsupported("AttachShader")
+    }
+    unsafe fn DetachShader(&mut self, program: GLuint, shader: GLuint) {
+        unsupported("DetachShader")
+    }
+    unsafe fn BindAttribLocation(&mut self, program: GLuint, index: GLuint, name: *const GLchar) {
+        unsupported("BindAttribLocation")
+    }
+    unsafe fn LinkProgram(&mut self, program: GLuint) {
+        unsupported("LinkProgram")
+    }
+    unsafe fn ValidateProgram(&mut self, program: GLuint) {
+        unsupported("ValidateProgram")
+    }
+    unsafe fn UseProgram(&mut self, program: GLuint) {
+        unsupported("UseProgram")
+    }
+    unsafe fn GetProgramiv(&mut self, program: GLuint, pname: GLenum, params: *mut GLint) {
+        unsupported("GetProgramiv")
+    }
+    unsafe fn GetProgramInfoLog(
+        &mut self,
+        program: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        info_log: *mut GLchar,
+    ) {
+        unsupported("GetProgramInfoLog")
+    }
+    unsafe fn IsProgram(&mut self, program: GLuint) -> GLboolean {
+        unsupported("IsProgram")
+    }
+    unsafe fn DeleteProgram(&mut self, program: GLuint) {
+        unsupported("DeleteProgram")
+    }
+    unsafe fn GetAttribLocation(&mut self, program: GLuint, name: *const GLchar) -> GLint {
+        unsupported("GetAttribLocation")
+    }
+    unsafe fn GetUniformLocation(&mut self, program: GLuint, name: *const GLchar) -> GLint {
+        unsupported("GetUniformLocation")
+    }
+    unsafe fn GetActiveAttrib(
+        &mut self,
+        program: GLuint,
+        index: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar,
+    ) {
+        unsupported("GetActiveAttrib")
+    }
+    unsafe fn GetActiveUniform(
+        &mut self,
+        program: GLuint,
+        index: GLuint,
+        buf_size: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar,
+    ) {
+        unsupported("GetActiveUniform")
+    }
+
+    // OpenGL ES 2.0: uniforms
+    unsafe fn Uniform1f(&mut self, location: GLint, v0: GLfloat) {
+        unsupported("Uniform1f")
+    }
+    unsafe fn Uniform2f(&mut self, location: GLint, v0: GLfloat, v1: GLfloat) {
+        unsupported("Uniform2f")
+    }
+    unsafe fn Uniform3f(&mut self, location: GLint, v0: GLfloat, v1: GLfloat, v2: GLfloat) {
+        unsupported("Uniform3f")
+    }
+    unsafe fn Uniform4f(
+        &mut self,
+        location: GLint,
+        v0: GLfloat,
+        v1: GLfloat,
+        v2: GLfloat,
+        v3: GLfloat,
+    ) {
+        unsupported("Uniform4f")
+    }
+    unsafe fn Uniform1i(&mut self, location: GLint, v0: GLint) {
+        unsupported("Uniform1i")
+    }
+    unsafe fn Uniform2i(&mut self, location: GLint, v0: GLint, v1: GLint) {
+        unsupported("Uniform2i")
+    }
+    unsafe fn Uniform3i(&mut self, location: GLint, v0: GLint, v1: GLint, v2: GLint) {
+        unsupported("Uniform3i")
+    }
+    unsafe fn Uniform4i(&mut self, location: GLint, v0: GLint, v1: GLint, v2: GLint, v3: GLint) {
+        unsupported("Uniform4i")
+    }
+    unsafe fn Uniform1fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        unsupported("Uniform1fv")
+    }
+    unsafe fn Uniform2fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        unsupported("Uniform2fv")
+    }
+    unsafe fn Uniform3fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        unsupported("Uniform3fv")
+    }
+    unsafe fn Uniform4fv(&mut self, location: GLint, count: GLsizei, value: *const GLfloat) {
+        unsupported("Uniform4fv")
+    }
+    unsafe fn Uniform1iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        unsupported("Uniform1iv")
+    }
+    unsafe fn Uniform2iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        unsupported("Uniform2iv")
+    }
+    unsafe fn Uniform3iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        unsupported("Uniform3iv")
+    }
+    unsafe fn Uniform4iv(&mut self, location: GLint, count: GLsizei, value: *const GLint) {
+        unsupported("Uniform4iv")
+    }
+    unsafe fn UniformMatrix2fv(
+        &mut self,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    ) {
+        unsupported("UniformMatrix2fv")
+    }
+    unsafe fn UniformMatrix3fv(
+        &mut self,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    ) {
+        unsupported("UniformMatrix3fv")
+    }
+    unsafe fn UniformMatrix4fv(
+        &mut self,
+        location: GLint,
+        count: GLsizei,
+        transpose: GLboolean,
+        value: *const GLfloat,
+    ) {
+        unsupported("UniformMatrix4fv")
+    }
+
+    // OpenGL ES 2.0: vertex attributes
+    unsafe fn EnableVertexAttribArray(&mut self, index: GLuint) {
+        unsupported("EnableVertexAttribArray")
+    }
+    unsafe fn DisableVertexAttribArray(&mut self, index: GLuint) {
+        unsupported("DisableVertexAttribArray")
+    }
+    unsafe fn VertexAttribPointer(
+        &mut self,
+        index: GLuint,
+        size: GLint,
+        type_: GLenum,
+        normalized: GLboolean,
+        stride: GLsizei,
+        pointer: *const GLvoid,
+    ) {
+        unsupported("VertexAttribPointer")
+    }
+    unsafe fn VertexAttrib1f(&mut self, index: GLuint, x: GLfloat) {
+        unsupported("VertexAttrib1f")
+    }
+    unsafe fn VertexAttrib2f(&mut self, index: GLuint, x: GLfloat, y: GLfloat) {
+        unsupported("VertexAttrib2f")
+    }
+    unsafe fn VertexAttrib3f(&mut self, index: GLuint, x: GLfloat, y: GLfloat, z: GLfloat) {
+        unsupported("VertexAttrib3f")
+    }
+    unsafe fn VertexAttrib4f(
+        &mut self,
+        index: GLuint,
+        x: GLfloat,
+        y: GLfloat,
+        z: GLfloat,
+        w: GLfloat,
+    ) {
+        unsupported("VertexAttrib4f")
+    }
+    unsafe fn VertexAttrib1fv(&mut self, index: GLuint, v: *const GLfloat) {
+        unsupported("VertexAttrib1fv")
+    }
+    unsafe fn VertexAttrib2fv(&mut self, index: GLuint, v: *const GLfloat) {
+        unsupported("VertexAttrib2fv")
+    }
+    unsafe fn VertexAttrib3fv(&mut self, index: GLuint, v: *const GLfloat) {
+        unsupported("VertexAttrib3fv")
+    }
+    unsafe fn VertexAttrib4fv(&mut self, index: GLuint, v: *const GLfloat) {
+        unsupported("VertexAttrib4fv")
+    }
+    unsafe fn GetVertexAttribiv(&mut self, index: GLuint, pname: GLenum, params: *mut GLint) {
+        unsupported("GetVertexAttribiv")
+    }
+    unsafe fn GetVertexAttribfv(&mut self, index: GLuint, pname: GLenum, params: *mut GLfloat) {
+        unsupported("GetVertexAttribfv")
+    }
+    unsafe fn GetVertexAttribPointerv(
+        &mut self,
+        index: GLuint,
+        pname: GLenum,
+        pointer: *mut *const GLvoid,
+    ) {
+        unsupported("GetVertexAttribPointerv")
+    }
 }
@@ -7,7 +7,10 @@
 //! implementation.
 
 use super::gles11_raw as gles11; // constants and types only
+use super::gles11_raw::types::{GLchar, GLint, GLuint, GLvoid};
+use super::gles2_raw as gles2; // constants only
 use super::GLES;
+use crate::image::pixel_format;
 use crate::matrix::Matrix;
 use std::time::{Duration, Instant};
 
@@ -23,7 +26,9 @@ impl FpsCounter {
         }
     }
 
-    pub fn count_frame(&mut self, label: std::fmt::Arguments<'_>) {
+    /// Count a frame and print the framerate if a second has passed since it
+    /// was last printed. Returns [true] if it was printed.
+    pub fn count_frame(&mut self, label: std::fmt::Arguments<'_>) -> bool {
         self.frames += 1;
         let now = Instant::now();
         let duration = now - self.time;
@@ -34,21 +39,54 @@ impl FpsCounter {
                 label,
                 std::mem::take(&mut self.frames) as f32 / duration.as_secs_f32()
             );
+            true
+        } else {
+            false
         }
     }
 }
 
+/// Read back a region of the default framebuffer as RGBA pixels, with rows in
+/// top-to-bottom order (the opposite of OpenGL's), replacing the contents of
+/// `pixels`. This is used for screen recording and screenshots.
+///
+/// The provided context must be current, and the default framebuffer bound.
+pub unsafe fn read_back_frame(
+    gles: &mut dyn GLES,
+    viewport: (u32, u32, u32, u32),
+    pixels: &mut Vec<u8>,
+) {
+    let (x, y, width, height) = viewport;
+    let row_size = width as usize * 4;
+    pixels.clear();
+    pixels.resize(row_size * height as usize, 0);
+    if pixels.is_empty() {
+        return;
+    }
+    // The row size is a multiple of 4, so GL_PACK_ALIGNMENT doesn't matter.
+    gles.ReadPixels(
+        x as _,
+        y as _,
+        width as _,
+        height as _,
+        gles11::RGBA,
+        gles11::UNSIGNED_BYTE,
+        pixels.as_mut_ptr() as *mut _,
+    );
+    pixel_format::flip_rows(pixels, row_size);
+}
+
 /// Present the the latest frame (e.g. the app's splash screen or rendering
 /// output), provided as a texture bound to `GL_TEXTURE_2D`, by drawing it on
 /// the window. It may be rotated, scaled and/or letterboxed as necessary. The
-/// virtual cursor is also drawn if it should be currently visible.
+/// virtual cursors are also drawn if they should be currently visible.
 ///
 /// The provided context must be current.
 pub unsafe fn present_frame(
     gles: &mut dyn GLES,
     viewport: (u32, u32, u32, u32),
     rotation_matrix: Matrix<2>,
-    virtual_cursor_visible_at: Option<(f32, f32, bool)>,
+    virtual_cursors_visible_at: &[(f32, f32, bool)],
 ) {
     // While this is a generic utility, it is closely tied to
     // crate::frameworks::opengles::eagl::present_renderbuffer, which handles
@@ -83,8 +121,8 @@ pub unsafe fn present_frame(
     // clean this up so we don't need to worry about it in e.g. Core Animation
     gles.LoadIdentity();
 
-    // Display virtual cursor
-    if let Some((x, y, pressed)) = virtual_cursor_visible_at {
+    // Display virtual cursors
+    for &(x, y, pressed) in virtual_cursors_visible_at {
         let (vx, vy, vw, vh) = viewport;
         let x = x - vx as f32;
         let y = y - vy as f32;
@@ -107,3 +145,216 @@ pub unsafe fn present_frame(
         gles.DrawArrays(gles11::TRIANGLES, 0, 6);
     }
 }
+
+/// Like [present_frame], but blends the texture over the existing contents of
+/// the window with some `opacity`, rather than replacing them. This is used to
+/// fade out the splash screen. The virtual cursors are not drawn.
+///
+/// The provided context must be current, and it must be the internal context
+/// (which has the OpenGL ES 1.1 fixed-function pipeline).
+pub unsafe fn present_overlay(
+    gles: &mut dyn GLES,
+    viewport: (u32, u32, u32, u32),
+    rotation_matrix: Matrix<2>,
+    opacity: f32,
+) {
+    gles.Viewport(
+        viewport.0 as _,
+        viewport.1 as _,
+        viewport.2 as _,
+        viewport.3 as _,
+    );
+    gles.BindBuffer(gles11::ARRAY_BUFFER, 0);
+    let vertices: [f32; 12] = [
+        -1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0,
+    ];
+    gles.EnableClientState(gles11::VERTEX_ARRAY);
+    gles.VertexPointer(2, gles11::FLOAT, 0, vertices.as_ptr() as *const GLvoid);
+    let tex_coords: [f32; 12] = [0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+    gles.EnableClientState(gles11::TEXTURE_COORD_ARRAY);
+    gles.TexCoordPointer(2, gles11::FLOAT, 0, tex_coords.as_ptr() as *const GLvoid);
+    let matrix = Matrix::<4>::from(&rotation_matrix);
+    gles.MatrixMode(gles11::TEXTURE);
+    gles.LoadMatrixf(matrix.columns().as_ptr() as *const _);
+    gles.Enable(gles11::TEXTURE_2D);
+    // The texture has premultiplied alpha, and the default texture environment
+    // (GL_MODULATE) multiplies it by the current color.
+    gles.Enable(gles11::BLEND);
+    gles.BlendFunc(gles11::ONE, gles11::ONE_MINUS_SRC_ALPHA);
+    gles.Color4f(opacity, opacity, opacity, opacity);
+    gles.DrawArrays(gles11::TRIANGLES, 0, 6);
+    // clean this up so we don't need to worry about it in e.g. Core Animation
+    gles.Color4f(1.0, 1.0, 1.0, 1.0);
+    gles.Disable(gles11::BLEND);
+    gles.LoadIdentity();
+}
+
+const PRESENT_VERTEX_SHADER: &str = "
+attribute vec2 a_position;
+attribute vec2 a_tex_coord;
+uniform mat4 u_tex_matrix;
+varying vec2 v_tex_coord;
+void main() {
+    gl_Position = vec4(a_position, 0.0, 1.0);
+    v_tex_coord = (u_tex_matrix * vec4(a_tex_coord, 0.0, 1.0)).xy;
+}
+";
+const PRESENT_FRAGMENT_SHADER: &str = "
+precision mediump float;
+uniform sampler2D u_texture;
+uniform bool u_use_texture;
+uniform vec4 u_color;
+varying vec2 v_tex_coord;
+void main() {
+    gl_FragColor = u_use_texture ? texture2D(u_texture, v_tex_coord) : u_color;
+}
+";
+/// Attribute location of `a_position` in [create_present_program_gles2].
+const PRESENT_POSITION_ATTRIB: GLuint = 0;
+/// Attribute location of `a_tex_coord` in [create_present_program_gles2].
+const PRESENT_TEX_COORD_ATTRIB: GLuint = 1;
+/// The vertex attributes used by [present_frame_gles2], for the benefit of
+/// code that needs to back up and restore them.
+pub const PRESENT_ATTRIBS_GLES2: [GLuint; 2] = [PRESENT_POSITION_ATTRIB, PRESENT_TEX_COORD_ATTRIB];
+
+/// Create the shader program used by [present_frame_gles2]. OpenGL ES 2.0 has
+/// no fixed-function pipeline, so this is needed to draw anything.
+///
+/// The provided context must be current.
+pub unsafe fn create_present_program_gles2(gles: &mut dyn GLES) -> GLuint {
+    let program = gles.CreateProgram();
+    for (type_, source) in [
+        (gles2::VERTEX_SHADER, PRESENT_VERTEX_SHADER),
+        (gles2::FRAGMENT_SHADER, PRESENT_FRAGMENT_SHADER),
+    ] {
+        let shader = gles.CreateShader(type_);
+        let source_ptr: *const GLchar = source.as_ptr().cast();
+        let source_len: GLint = source.len().try_into().unwrap();
+        gles.ShaderSource(shader, 1, &source_ptr, &source_len);
+        gles.CompileShader(shader);
+        let mut status = 0;
+        gles.GetShaderiv(shader, gles2::COMPILE_STATUS, &mut status);
+        assert!(status == gles2::TRUE.into());
+        gles.AttachShader(program, shader);
+        // Flagged for deletion once the program is deleted.
+        gles.DeleteShader(shader);
+    }
+    gles.BindAttribLocation(
+        program,
+        PRESENT_POSITION_ATTRIB,
+        b"a_position\0".as_ptr().cast(),
+    );
+    gles.BindAttribLocation(
+        program,
+        PRESENT_TEX_COORD_ATTRIB,
+        b"a_tex_coord\0".as_ptr().cast(),
+    );
+    gles.LinkProgram(program);
+    let mut status = 0;
+    gles.GetProgramiv(program, gles2::LINK_STATUS, &mut status);
+    assert!(status == gles2::TRUE.into());
+    program
+}
+
+/// Like [present_frame], but for OpenGL ES 2.0 contexts, using a program
+/// created with [create_present_program_gles2].
+///
+/// The provided context must be current.
+pub unsafe fn present_frame_gles2(
+    gles: &mut dyn GLES,
+    program: GLuint,
+    viewport: (u32, u32, u32, u32),
+    rotation_matrix: Matrix<2>,
+    virtual_cursors_visible_at: &[(f32, f32, bool)],
+) {
+    // As with present_frame(), state backup is handled by
+    // crate::frameworks::opengles::eagl::present_renderbuffer, so these need to
+    // be updated in tandem.
+
+    gles.UseProgram(program);
+    let tex_matrix_loc = gles.GetUniformLocation(program, b"u_tex_matrix\0".as_ptr().cast());
+    let texture_loc = gles.GetUniformLocation(program, b"u_texture\0".as_ptr().cast());
+    let use_texture_loc = gles.GetUniformLocation(program, b"u_use_texture\0".as_ptr().cast());
+    let color_loc = gles.GetUniformLocation(program, b"u_color\0".as_ptr().cast());
+
+    // Draw the quad
+    gles.Viewport(
+        viewport.0 as _,
+        viewport.1 as _,
+        viewport.2 as _,
+        viewport.3 as _,
+    );
+    gles.ClearColor(0.0, 0.0, 0.0, 1.0);
+    gles.Clear(gles2::COLOR_BUFFER_BIT | gles2::DEPTH_BUFFER_BIT | gles2::STENCIL_BUFFER_BIT);
+    gles.BindBuffer(gles2::ARRAY_BUFFER, 0);
+    let vertices: [f32; 12] = [
+        -1.0, -1.0, -1.0, 1.0, 1.0, -1.0, 1.0, -1.0, -1.0, 1.0, 1.0, 1.0,
+    ];
+    gles.EnableVertexAttribArray(PRESENT_POSITION_ATTRIB);
+    gles.VertexAttribPointer(
+        PRESENT_POSITION_ATTRIB,
+        2,
+        gles2::FLOAT,
+        gles2::FALSE,
+        0,
+        vertices.as_ptr() as *const GLvoid,
+    );
+    let tex_coords: [f32; 12] = [0.0, 0.0, 0.0, 1.0, 1.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 1.0];
+    gles.EnableVertexAttribArray(PRESENT_TEX_COORD_ATTRIB);
+    gles.VertexAttribPointer(
+        PRESENT_TEX_COORD_ATTRIB,
+        2,
+        gles2::FLOAT,
+        gles2::FALSE,
+        0,
+        tex_coords.as_ptr() as *const GLvoid,
+    );
+    let matrix = Matrix::<4>::from(&rotation_matrix);
+    gles.UniformMatrix4fv(
+        tex_matrix_loc,
+        1,
+        gles2::FALSE,
+        matrix.columns().as_ptr() as *const _,
+    );
+    gles.Uniform1i(texture_loc, 0);
+    gles.Uniform1i(use_texture_loc, 1);
+    gles.DrawArrays(gles2::TRIANGLES, 0, 6);
+
+    // Display virtual cursors
+    for &(x, y, pressed) in virtual_cursors_visible_at {
+        let (vx, vy, vw, vh) = viewport;
+        let x = x - vx as f32;
+        let y = y - vy as f32;
+
+        gles.DisableVertexAttribArray(PRESENT_TEX_COORD_ATTRIB);
+        gles.Uniform1i(use_texture_loc, 0);
+
+        gles.Enable(gles2::BLEND);
+        gles.BlendFunc(gles2::ONE, gles2::ONE_MINUS_SRC_ALPHA);
+        // Premultiplied alpha, like in present_frame()
+        gles.Uniform4f(
+            color_loc,
+            0.0,
+            0.0,
+            0.0,
+            if pressed { 2.0 / 3.0 } else { 1.0 / 3.0 },
+        );
+
+        let radius = 10.0;
+
+        let mut vertices = vertices;
+        for i in (0..vertices.len()).step_by(2) {
+            vertices[i] = (vertices[i] * radius + x) / (vw as f32 / 2.0) - 1.0;
+            vertices[i + 1] = 1.0 - (vertices[i + 1] * radius + y) / (vh as f32 / 2.0);
+        }
+        gles.VertexAttribPointer(
+            PRESENT_POSITION_ATTRIB,
+            2,
+            gles2::FLOAT,
+            gles2::FALSE,
+            0,
+            vertices.as_ptr() as *const GLvoid,
+        );
+        gles.DrawArrays(gles2::TRIANGLES, 0, 6);
+    }
+}
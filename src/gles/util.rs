@@ -6,7 +6,7 @@
 //! Shared utilities.
 
 use super::gles11_raw as gles11; // constants only
-use super::gles11_raw::types::{GLenum, GLfixed, GLfloat, GLint, GLsizei};
+use super::gles11_raw::types::{GLenum, GLfixed, GLfloat, GLint, GLsizei, GLuint};
 use super::GLES;
 
 /// Convert a fixed-point scalar to a floating-point scalar.
@@ -250,3 +250,134 @@ impl PalettedTextureFormat {
         }
     }
 }
+
+/// Implementation of [GLES::start_read_pixels_async] for the OpenGL 2.1
+/// backends, which have pixel buffer objects in core.
+///
+/// The OpenGL 2.1 context must be current.
+pub unsafe fn start_read_pixels_async_gl21(
+    mut buffer: GLuint,
+    width: GLsizei,
+    height: GLsizei,
+) -> GLuint {
+    use super::gl21compat_raw as gl21;
+
+    if buffer == 0 {
+        gl21::GenBuffers(1, &mut buffer);
+    }
+    let mut old_buffer = 0;
+    gl21::GetIntegerv(gl21::PIXEL_PACK_BUFFER_BINDING, &mut old_buffer);
+    gl21::BindBuffer(gl21::PIXEL_PACK_BUFFER, buffer);
+    let size = (width as usize) * (height as usize) * 4;
+    // Respecifying the storage avoids waiting for any earlier read into this
+    // buffer that is still in progress.
+    gl21::BufferData(
+        gl21::PIXEL_PACK_BUFFER,
+        size.try_into().unwrap(),
+        std::ptr::null(),
+        gl21::STREAM_READ,
+    );
+    // With a pixel pack buffer bound, the pointer is an offset into it.
+    gl21::ReadPixels(
+        0,
+        0,
+        width,
+        height,
+        gl21::RGBA,
+        gl21::UNSIGNED_BYTE,
+        std::ptr::null_mut(),
+    );
+    gl21::BindBuffer(gl21::PIXEL_PACK_BUFFER, old_buffer as _);
+    buffer
+}
+
+/// Implementation of [GLES::finish_read_pixels_async] for the OpenGL 2.1
+/// backends.
+///
+/// The OpenGL 2.1 context must be current.
+pub unsafe fn finish_read_pixels_async_gl21(
+    buffer: GLuint,
+    width: GLsizei,
+    height: GLsizei,
+    pixels: &mut Vec<u8>,
+) {
+    use super::gl21compat_raw as gl21;
+
+    let size = (width as usize) * (height as usize) * 4;
+    pixels.clear();
+    let mut old_buffer = 0;
+    gl21::GetIntegerv(gl21::PIXEL_PACK_BUFFER_BINDING, &mut old_buffer);
+    gl21::BindBuffer(gl21::PIXEL_PACK_BUFFER, buffer);
+    let mapped = gl21::MapBuffer(gl21::PIXEL_PACK_BUFFER, gl21::READ_ONLY);
+    if mapped.is_null() {
+        log!("Warning: Couldn't map pixel buffer object {}", buffer);
+        pixels.resize(size, 0);
+    } else {
+        pixels.extend_from_slice(std::slice::from_raw_parts(mapped as *const u8, size));
+        gl21::UnmapBuffer(gl21::PIXEL_PACK_BUFFER);
+    }
+    gl21::BindBuffer(gl21::PIXEL_PACK_BUFFER, old_buffer as _);
+}
+
+/// Implementation of `glResolveMultisampleFramebufferAPPLE` for the OpenGL 2.1
+/// backends. Unlike `glBlitFramebufferEXT`, the APPLE function has no
+/// rectangle parameters: the whole color buffer of the read framebuffer is
+/// resolved to the same position in the draw framebuffer.
+///
+/// The OpenGL 2.1 context must be current.
+pub unsafe fn resolve_multisample_framebuffer_gl21() {
+    use super::gl21compat_raw as gl21;
+
+    let mut object_type = 0;
+    gl21::GetFramebufferAttachmentParameterivEXT(
+        gl21::READ_FRAMEBUFFER_EXT,
+        gl21::COLOR_ATTACHMENT0_EXT,
+        gl21::FRAMEBUFFER_ATTACHMENT_OBJECT_TYPE_EXT,
+        &mut object_type,
+    );
+    if object_type as GLenum != gl21::RENDERBUFFER_EXT {
+        // Multisampled textures don't exist in OpenGL ES, so there's nothing
+        // to resolve in this case.
+        log!(
+            "Warning: glResolveMultisampleFramebufferAPPLE() with read framebuffer color attachment type {:#x}, ignoring",
+            object_type
+        );
+        return;
+    }
+    let mut renderbuffer = 0;
+    gl21::GetFramebufferAttachmentParameterivEXT(
+        gl21::READ_FRAMEBUFFER_EXT,
+        gl21::COLOR_ATTACHMENT0_EXT,
+        gl21::FRAMEBUFFER_ATTACHMENT_OBJECT_NAME_EXT,
+        &mut renderbuffer,
+    );
+
+    let mut old_renderbuffer = 0;
+    gl21::GetIntegerv(gl21::RENDERBUFFER_BINDING_EXT, &mut old_renderbuffer);
+    gl21::BindRenderbufferEXT(gl21::RENDERBUFFER_EXT, renderbuffer as _);
+    let (mut width, mut height) = (0, 0);
+    gl21::GetRenderbufferParameterivEXT(
+        gl21::RENDERBUFFER_EXT,
+        gl21::RENDERBUFFER_WIDTH_EXT,
+        &mut width,
+    );
+    gl21::GetRenderbufferParameterivEXT(
+        gl21::RENDERBUFFER_EXT,
+        gl21::RENDERBUFFER_HEIGHT_EXT,
+        &mut height,
+    );
+    gl21::BindRenderbufferEXT(gl21::RENDERBUFFER_EXT, old_renderbuffer as _);
+
+    gl21::BlitFramebufferEXT(
+        0,
+        0,
+        width,
+        height,
+        0,
+        0,
+        width,
+        height,
+        gl21::COLOR_BUFFER_BIT,
+        gl21::NEAREST,
+    );
+}
@@ -3,14 +3,25 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-//! Image decoding. Currently only supports PNG files (treated as 8-bit sRGB).
+//! Image decoding. Currently supports PNG and JPEG files (treated as 8-bit
+//! sRGB), and PVR texture files (see [pvr]).
 //!
 //! Implemented as a wrapper around the C library stb_image, since it supports
 //! "CgBI" PNG files (an Apple proprietary extension used in iPhone OS apps).
+//! Its JPEG decoder handles progressive JPEGs and Adobe CMYK/YCCK JPEGs, and
+//! any channel layout is converted to RGBA. EXIF orientation is not applied
+//! when decoding, because UIImage exposes it separately (see [exif]).
 //!
 //! This module also exposes decompression for Imagination Technologies' PVRTC
 //! format, implementing as a wrapper around their decoder from the PowerVR
 //! SDK.
+//!
+//! Encoding is only needed for screenshots, see [png_writer].
+
+pub mod exif;
+pub mod pixel_format;
+pub mod png_writer;
+pub mod pvr;
 
 use std::ffi::{c_int, c_uchar, CStr};
 
@@ -29,6 +40,10 @@ enum PixelStore {
 
 impl Image {
     pub fn from_bytes(bytes: &[u8]) -> Result<Image, String> {
+        if pvr::is_pvr(bytes) {
+            return Self::from_pvr_bytes(bytes);
+        }
+
         let len: c_int = bytes.len().try_into().unwrap();
 
         let mut x: c_int = 0;
@@ -68,14 +83,7 @@ impl Image {
         {
             let len = width as usize * height as usize * 4;
             let pixels = unsafe { std::slice::from_raw_parts_mut(pixels, len) };
-            let mut i = 0;
-            while i < pixels.len() {
-                let a = pixels[i + 3] as f32 / 255.0;
-                pixels[i] = (pixels[i] as f32 * a) as u8;
-                pixels[i + 1] = (pixels[i + 1] as f32 * a) as u8;
-                pixels[i + 2] = (pixels[i + 2] as f32 * a) as u8;
-                i += 4;
-            }
+            pixel_format::premultiply(pixels);
         }
 
         Ok(Image {
@@ -84,6 +92,22 @@ impl Image {
         })
     }
 
+    fn from_pvr_bytes(bytes: &[u8]) -> Result<Image, String> {
+        let texture = pvr::PvrTexture::parse(bytes)?;
+        // Only the full-size image is needed, the mipmaps are discarded.
+        let mut pixels = texture.decode_level(0);
+        if texture.flipped {
+            pixel_format::flip_rows(&mut pixels, texture.width as usize * 4);
+        }
+        if !texture.premultiplied {
+            pixel_format::premultiply(&mut pixels);
+        }
+        Ok(Image {
+            pixels: PixelStore::Vec(pixels),
+            dimensions: (texture.width, texture.height),
+        })
+    }
+
     /// TODO: This shouldn't really exist, it's a workaround for `CGImage`
     /// relying on this type and should be removed once it can be refactored.
     pub fn from_pixel_vec(pixels: Vec<u8>, dimensions: (u32, u32)) -> Image {
@@ -98,6 +122,42 @@ impl Image {
         self.dimensions
     }
 
+    /// Create a copy of the image with an EXIF orientation applied, so that it
+    /// is the right way up.
+    pub fn apply_exif_orientation(&self, orientation: exif::ExifOrientation) -> Image {
+        use exif::ExifOrientation as O;
+
+        let (width, height) = self.dimensions;
+        let new_dimensions = if orientation.swaps_dimensions() {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        let (w, h) = (width as usize, height as usize);
+        let src = self.pixels();
+        let mut pixels = Vec::with_capacity(src.len());
+        for y in 0..new_dimensions.1 as usize {
+            for x in 0..new_dimensions.0 as usize {
+                let (src_x, src_y) = match orientation {
+                    O::Normal => (x, y),
+                    O::FlipHorizontal => (w - 1 - x, y),
+                    O::Rotate180 => (w - 1 - x, h - 1 - y),
+                    O::FlipVertical => (x, h - 1 - y),
+                    O::Transpose => (y, x),
+                    O::Rotate90Clockwise => (y, h - 1 - x),
+                    O::Transverse => (w - 1 - y, h - 1 - x),
+                    O::Rotate90CounterClockwise => (w - 1 - y, x),
+                };
+                let i = (src_y * w + src_x) * 4;
+                pixels.extend_from_slice(&src[i..i + 4]);
+            }
+        }
+        Image {
+            pixels: PixelStore::Vec(pixels),
+            dimensions: new_dimensions,
+        }
+    }
+
     /// Get image data as bytes (8 bits per channel sRGB RGBA with premultiplied
     /// alpha). Rows are in top-to-bottom order.
     pub fn pixels(&self) -> &[u8] {
@@ -0,0 +1,164 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Minimal EXIF metadata parsing. Only the orientation tag is supported.
+//!
+//! Resources:
+//! - [EXIF 2.3 specification](https://www.cipa.jp/std/documents/e/DC-008-2012_E.pdf)
+//! - [TIFF 6.0 specification](https://www.itu.int/itudoc/itu-t/com16/tiff-fx/docs/tiff6.pdf)
+
+/// EXIF orientation tag values. The names describe what must be done to the
+/// stored pixel data in order to display it the right way up.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExifOrientation {
+    Normal = 1,
+    FlipHorizontal = 2,
+    Rotate180 = 3,
+    FlipVertical = 4,
+    Transpose = 5,
+    Rotate90Clockwise = 6,
+    Transverse = 7,
+    Rotate90CounterClockwise = 8,
+}
+
+impl ExifOrientation {
+    fn from_tag_value(value: u16) -> Option<Self> {
+        match value {
+            1 => Some(Self::Normal),
+            2 => Some(Self::FlipHorizontal),
+            3 => Some(Self::Rotate180),
+            4 => Some(Self::FlipVertical),
+            5 => Some(Self::Transpose),
+            6 => Some(Self::Rotate90Clockwise),
+            7 => Some(Self::Transverse),
+            8 => Some(Self::Rotate90CounterClockwise),
+            _ => None,
+        }
+    }
+
+    /// Whether the width and height are swapped when displayed.
+    pub fn swaps_dimensions(self) -> bool {
+        matches!(
+            self,
+            Self::Transpose
+                | Self::Rotate90Clockwise
+                | Self::Transverse
+                | Self::Rotate90CounterClockwise
+        )
+    }
+}
+
+/// Find the EXIF orientation of a JPEG file, if it has one. Returns [None] for
+/// other file types or if the metadata is missing or malformed.
+pub fn jpeg_orientation(bytes: &[u8]) -> Option<ExifOrientation> {
+    // Start of image marker
+    if bytes.get(..2)? != [0xFF, 0xD8] {
+        return None;
+    }
+    let mut rest = &bytes[2..];
+    loop {
+        let &[0xFF, marker, len_hi, len_lo, ..] = rest else {
+            return None;
+        };
+        // Start of scan marker: the metadata should have come before this.
+        if marker == 0xDA {
+            return None;
+        }
+        let len = u16::from_be_bytes([len_hi, len_lo]) as usize;
+        let segment = rest.get(4..2 + len)?;
+        // APP1 marker
+        if marker == 0xE1 {
+            if let Some(tiff) = segment.strip_prefix(b"Exif\0\0") {
+                return tiff_orientation(tiff);
+            }
+        }
+        rest = &rest[2 + len..];
+    }
+}
+
+fn tiff_orientation(tiff: &[u8]) -> Option<ExifOrientation> {
+    let big_endian = match tiff.get(..2)? {
+        b"II" => false,
+        b"MM" => true,
+        _ => return None,
+    };
+    let read_u16 = |offset: usize| -> Option<u16> {
+        let bytes = tiff.get(offset..offset + 2)?.try_into().unwrap();
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+    let read_u32 = |offset: usize| -> Option<u32> {
+        let bytes = tiff.get(offset..offset + 4)?.try_into().unwrap();
+        Some(if big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        })
+    };
+
+    if read_u16(2)? != 42 {
+        return None;
+    }
+    let ifd0 = read_u32(4)? as usize;
+    let entry_count = read_u16(ifd0)? as usize;
+    for i in 0..entry_count {
+        let entry = ifd0 + 2 + i * 12;
+        const ORIENTATION_TAG: u16 = 0x0112;
+        const SHORT_TYPE: u16 = 3;
+        if read_u16(entry)? == ORIENTATION_TAG && read_u16(entry + 2)? == SHORT_TYPE {
+            // Values that fit in 4 bytes are stored inline.
+            return ExifOrientation::from_tag_value(read_u16(entry + 8)?);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jpeg_orientation() {
+        #[rustfmt::skip]
+        let big_endian = [
+            0xFF, 0xD8, // SOI
+            0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, // APP0 (skipped)
+            0xFF, 0xE1, 0x00, 0x22, // APP1
+            b'E', b'x', b'i', b'f', 0, 0,
+            b'M', b'M', 0x00, 42, 0x00, 0x00, 0x00, 0x08, // TIFF header
+            0x00, 0x01, // IFD0 entry count
+            0x01, 0x12, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01, 0x00, 0x06, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, // next IFD
+            0xFF, 0xDA, // SOS
+        ];
+        assert_eq!(
+            jpeg_orientation(&big_endian),
+            Some(ExifOrientation::Rotate90Clockwise)
+        );
+
+        #[rustfmt::skip]
+        let little_endian = [
+            0xFF, 0xD8, // SOI
+            0xFF, 0xE1, 0x00, 0x22, // APP1
+            b'E', b'x', b'i', b'f', 0, 0,
+            b'I', b'I', 42, 0x00, 0x08, 0x00, 0x00, 0x00, // TIFF header
+            0x01, 0x00, // IFD0 entry count
+            0x12, 0x01, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, // next IFD
+            0xFF, 0xDA, // SOS
+        ];
+        assert_eq!(
+            jpeg_orientation(&little_endian),
+            Some(ExifOrientation::Rotate180)
+        );
+
+        let no_exif = [0xFF, 0xD8, 0xFF, 0xDA];
+        assert_eq!(jpeg_orientation(&no_exif), None);
+        assert_eq!(jpeg_orientation(b"\x89PNG"), None);
+    }
+}
@@ -0,0 +1,444 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Conversions between pixel formats, and other simple operations on pixel
+//! data.
+//!
+//! All formats here have 8 bits per channel unless otherwise stated. Packed
+//! 16-bit formats (RGB565 etc) are stored in host byte order, like OpenGL ES's
+//! `GL_UNSIGNED_SHORT_5_6_5` and friends.
+//!
+//! These are used for large images, so the 32-bit pixel operations have
+//! hand-written SIMD paths for x86-64 (SSE2) and AArch64 (NEON), see [simd].
+//! Those handle as many whole vectors as possible and the scalar loops handle
+//! the rest, and any other architectures. The scalar loops are still written
+//! to be easy for the compiler to vectorize: each works on fixed-size chunks,
+//! avoids branches where possible, and manipulates 32-bit pixels as whole
+//! words where possible. Please check the generated code if you change them.
+
+/// Swap the red and blue channels of 32-bit pixels, i.e. convert RGBA8 to
+/// BGRA8 or vice-versa.
+pub fn swap_red_and_blue(pixels: &mut [u8]) {
+    assert!(pixels.len() % 4 == 0);
+    let pixels = simd::swap_red_and_blue(pixels);
+    for pixel in pixels.chunks_exact_mut(4) {
+        let p = u32::from_le_bytes(pixel.try_into().unwrap());
+        let p = (p & 0xFF00FF00) | ((p & 0xFF) << 16) | ((p >> 16) & 0xFF);
+        pixel.copy_from_slice(&p.to_le_bytes());
+    }
+}
+
+/// Reverse the order of the bytes of 32-bit pixels, e.g. to convert BGRA8
+/// (little-endian ARGB) to ARGB8.
+pub fn swap_byte_order_32(pixels: &mut [u8]) {
+    assert!(pixels.len() % 4 == 0);
+    let pixels = simd::swap_byte_order_32(pixels);
+    for pixel in pixels.chunks_exact_mut(4) {
+        let p = u32::from_le_bytes(pixel.try_into().unwrap());
+        pixel.copy_from_slice(&p.to_be_bytes());
+    }
+}
+
+/// Rotate the channels of 32-bit pixels so alpha moves from first to last,
+/// i.e. convert ARGB8 to RGBA8.
+pub fn alpha_first_to_last(pixels: &mut [u8]) {
+    assert!(pixels.len() % 4 == 0);
+    let pixels = simd::alpha_first_to_last(pixels);
+    for pixel in pixels.chunks_exact_mut(4) {
+        let p = u32::from_le_bytes(pixel.try_into().unwrap());
+        pixel.copy_from_slice(&p.rotate_right(8).to_le_bytes());
+    }
+}
+
+/// Set the alpha channel of RGBA8 pixels to fully opaque, e.g. to convert RGBX8
+/// to RGBA8.
+pub fn set_opaque(pixels: &mut [u8]) {
+    assert!(pixels.len() % 4 == 0);
+    let pixels = simd::set_opaque(pixels);
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel[3] = 0xFF;
+    }
+}
+
+/// Multiply the color channels of RGBA8 pixels by alpha.
+pub fn premultiply(pixels: &mut [u8]) {
+    assert!(pixels.len() % 4 == 0);
+    let pixels = simd::premultiply(pixels);
+    for pixel in pixels.chunks_exact_mut(4) {
+        let a = pixel[3] as u16;
+        let mul = |c: u8| ((c as u16 * a) / 255) as u8;
+        pixel[0] = mul(pixel[0]);
+        pixel[1] = mul(pixel[1]);
+        pixel[2] = mul(pixel[2]);
+    }
+}
+
+/// Convert RGB8 pixels to RGBA8 pixels, with opaque alpha.
+pub fn rgb8_to_rgba8(src: &[u8], dst: &mut Vec<u8>) {
+    assert!(src.len() % 3 == 0);
+    dst.reserve((src.len() / 3) * 4);
+    for rgb in src.chunks_exact(3) {
+        dst.extend_from_slice(&[rgb[0], rgb[1], rgb[2], 0xFF]);
+    }
+}
+
+/// Convert RGBA8 pixels to RGB8 pixels by discarding the alpha channel.
+pub fn rgba8_to_rgb8(src: &[u8], dst: &mut Vec<u8>) {
+    assert!(src.len() % 4 == 0);
+    dst.reserve((src.len() / 4) * 3);
+    for rgba in src.chunks_exact(4) {
+        dst.extend_from_slice(&rgba[..3]);
+    }
+}
+
+/// Expand an `n`-bit channel value to 8 bits, replicating the high bits into
+/// the low bits so that e.g. the maximum value maps to 255.
+fn expand_bits<const N: u32>(value: u16) -> u8 {
+    let value = value as u32;
+    ((value << (8 - N)) | (value >> (2 * N).saturating_sub(8))) as u8
+}
+
+/// Convert RGB565 pixels to RGBA8 pixels, with opaque alpha.
+pub fn rgb565_to_rgba8(src: &[u8], dst: &mut Vec<u8>) {
+    assert!(src.len() % 2 == 0);
+    dst.reserve((src.len() / 2) * 4);
+    for pixel in src.chunks_exact(2) {
+        let p = u16::from_ne_bytes(pixel.try_into().unwrap());
+        dst.extend_from_slice(&[
+            expand_bits::<5>(p >> 11),
+            expand_bits::<6>((p >> 5) & 0x3F),
+            expand_bits::<5>(p & 0x1F),
+            0xFF,
+        ]);
+    }
+}
+
+/// Convert RGBA8 pixels to RGB565 pixels, discarding alpha.
+pub fn rgba8_to_rgb565(src: &[u8], dst: &mut Vec<u8>) {
+    assert!(src.len() % 4 == 0);
+    dst.reserve(src.len() / 2);
+    for rgba in src.chunks_exact(4) {
+        let (r, g, b) = (rgba[0] as u16, rgba[1] as u16, rgba[2] as u16);
+        let p = ((r >> 3) << 11) | ((g >> 2) << 5) | (b >> 3);
+        dst.extend_from_slice(&p.to_ne_bytes());
+    }
+}
+
+/// Convert RGBA4444 pixels to RGBA8 pixels.
+pub fn rgba4444_to_rgba8(src: &[u8], dst: &mut Vec<u8>) {
+    assert!(src.len() % 2 == 0);
+    dst.reserve((src.len() / 2) * 4);
+    for pixel in src.chunks_exact(2) {
+        let p = u16::from_ne_bytes(pixel.try_into().unwrap());
+        dst.extend_from_slice(&[
+            expand_bits::<4>(p >> 12),
+            expand_bits::<4>((p >> 8) & 0xF),
+            expand_bits::<4>((p >> 4) & 0xF),
+            expand_bits::<4>(p & 0xF),
+        ]);
+    }
+}
+
+/// Convert RGBA5551 pixels to RGBA8 pixels.
+pub fn rgba5551_to_rgba8(src: &[u8], dst: &mut Vec<u8>) {
+    assert!(src.len() % 2 == 0);
+    dst.reserve((src.len() / 2) * 4);
+    for pixel in src.chunks_exact(2) {
+        let p = u16::from_ne_bytes(pixel.try_into().unwrap());
+        dst.extend_from_slice(&[
+            expand_bits::<5>(p >> 11),
+            expand_bits::<5>((p >> 6) & 0x1F),
+            expand_bits::<5>((p >> 1) & 0x1F),
+            (p & 1) as u8 * 0xFF,
+        ]);
+    }
+}
+
+/// Reverse the order of the rows of an image, e.g. to convert between
+/// OpenGL's bottom-to-top row order and the top-to-bottom order most other
+/// things use.
+pub fn flip_rows(pixels: &mut [u8], bytes_per_row: usize) {
+    assert!(bytes_per_row != 0 && pixels.len() % bytes_per_row == 0);
+    let height = pixels.len() / bytes_per_row;
+    let (top, bottom) = pixels.split_at_mut((height / 2) * bytes_per_row);
+    // If there's an odd number of rows, the middle one stays where it is.
+    let bottom = &mut bottom[(height % 2) * bytes_per_row..];
+    for (top_row, bottom_row) in top
+        .chunks_exact_mut(bytes_per_row)
+        .zip(bottom.chunks_exact_mut(bytes_per_row).rev())
+    {
+        top_row.swap_with_slice(bottom_row);
+    }
+}
+
+/// SIMD versions of the 32-bit pixel operations. Each function processes as
+/// many whole vectors at the start of the slice as it can, and returns the rest
+/// for the scalar code to process. The results are identical to the scalar
+/// code's.
+///
+/// The vector loads and stores treat pixels as little-endian 32-bit words, like
+/// the scalar code does.
+#[cfg(all(target_arch = "x86_64", target_endian = "little"))]
+mod simd {
+    // SSE2 is always available on x86-64.
+    use std::arch::x86_64::*;
+
+    /// Apply an operation to each 16-byte chunk.
+    fn for_each_vector(pixels: &mut [u8], f: impl Fn(__m128i) -> __m128i) -> &mut [u8] {
+        let mut chunks = pixels.chunks_exact_mut(16);
+        for chunk in &mut chunks {
+            let ptr: *mut __m128i = chunk.as_mut_ptr().cast();
+            unsafe { _mm_storeu_si128(ptr, f(_mm_loadu_si128(ptr))) };
+        }
+        chunks.into_remainder()
+    }
+
+    pub fn swap_red_and_blue(pixels: &mut [u8]) -> &mut [u8] {
+        for_each_vector(pixels, |p| unsafe {
+            let ga = _mm_and_si128(p, _mm_set1_epi32(0xFF00FF00u32 as i32));
+            let rb = _mm_andnot_si128(_mm_set1_epi32(0xFF00FF00u32 as i32), p);
+            let br = _mm_or_si128(_mm_slli_epi32(rb, 16), _mm_srli_epi32(rb, 16));
+            _mm_or_si128(ga, br)
+        })
+    }
+
+    pub fn swap_byte_order_32(pixels: &mut [u8]) -> &mut [u8] {
+        for_each_vector(pixels, |p| unsafe {
+            let mask = _mm_set1_epi32(0x00FF00FF);
+            // Swap the bytes within each 16-bit half, then swap the halves.
+            let p = _mm_or_si128(
+                _mm_slli_epi32(_mm_and_si128(p, mask), 8),
+                _mm_and_si128(_mm_srli_epi32(p, 8), mask),
+            );
+            _mm_or_si128(_mm_slli_epi32(p, 16), _mm_srli_epi32(p, 16))
+        })
+    }
+
+    pub fn alpha_first_to_last(pixels: &mut [u8]) -> &mut [u8] {
+        for_each_vector(pixels, |p| unsafe {
+            _mm_or_si128(_mm_srli_epi32(p, 8), _mm_slli_epi32(p, 24))
+        })
+    }
+
+    pub fn set_opaque(pixels: &mut [u8]) -> &mut [u8] {
+        for_each_vector(pixels, |p| unsafe {
+            _mm_or_si128(p, _mm_set1_epi32(0xFF000000u32 as i32))
+        })
+    }
+
+    pub fn premultiply(pixels: &mut [u8]) -> &mut [u8] {
+        for_each_vector(pixels, |p| unsafe {
+            let zero = _mm_setzero_si128();
+            // Two pixels per vector, with 16 bits per channel.
+            let premultiply_two = |p: __m128i| {
+                let alpha = _mm_shufflehi_epi16(_mm_shufflelo_epi16(p, 0xFF), 0xFF);
+                let x = _mm_mullo_epi16(p, alpha);
+                // This is exactly x / 255 for x <= 255 * 255.
+                let x = _mm_add_epi16(x, _mm_set1_epi16(1));
+                _mm_srli_epi16(_mm_add_epi16(x, _mm_srli_epi16(x, 8)), 8)
+            };
+            let low = premultiply_two(_mm_unpacklo_epi8(p, zero));
+            let high = premultiply_two(_mm_unpackhi_epi8(p, zero));
+            let premultiplied = _mm_packus_epi16(low, high);
+            // Keep the original alpha.
+            let alpha_mask = _mm_set1_epi32(0xFF000000u32 as i32);
+            _mm_or_si128(
+                _mm_andnot_si128(alpha_mask, premultiplied),
+                _mm_and_si128(alpha_mask, p),
+            )
+        })
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", target_endian = "little"))]
+mod simd {
+    // NEON is always available on AArch64.
+    use std::arch::aarch64::*;
+
+    /// Apply an operation to each 16-byte chunk, as four 32-bit pixels.
+    fn for_each_vector(pixels: &mut [u8], f: impl Fn(uint32x4_t) -> uint32x4_t) -> &mut [u8] {
+        let mut chunks = pixels.chunks_exact_mut(16);
+        for chunk in &mut chunks {
+            let ptr = chunk.as_mut_ptr();
+            unsafe {
+                let p = vreinterpretq_u32_u8(vld1q_u8(ptr));
+                vst1q_u8(ptr, vreinterpretq_u8_u32(f(p)));
+            }
+        }
+        chunks.into_remainder()
+    }
+
+    /// Apply an operation to each 64-byte chunk, as sixteen pixels split into
+    /// one vector for each channel.
+    fn for_each_deinterleaved(
+        pixels: &mut [u8],
+        f: impl Fn(uint8x16x4_t) -> uint8x16x4_t,
+    ) -> &mut [u8] {
+        let mut chunks = pixels.chunks_exact_mut(64);
+        for chunk in &mut chunks {
+            let ptr = chunk.as_mut_ptr();
+            unsafe { vst4q_u8(ptr, f(vld4q_u8(ptr))) };
+        }
+        chunks.into_remainder()
+    }
+
+    pub fn swap_red_and_blue(pixels: &mut [u8]) -> &mut [u8] {
+        for_each_deinterleaved(pixels, |uint8x16x4_t(r, g, b, a)| uint8x16x4_t(b, g, r, a))
+    }
+
+    pub fn swap_byte_order_32(pixels: &mut [u8]) -> &mut [u8] {
+        for_each_vector(pixels, |p| unsafe {
+            vreinterpretq_u32_u8(vrev32q_u8(vreinterpretq_u8_u32(p)))
+        })
+    }
+
+    pub fn alpha_first_to_last(pixels: &mut [u8]) -> &mut [u8] {
+        for_each_vector(pixels, |p| unsafe {
+            vorrq_u32(vshrq_n_u32::<8>(p), vshlq_n_u32::<24>(p))
+        })
+    }
+
+    pub fn set_opaque(pixels: &mut [u8]) -> &mut [u8] {
+        for_each_vector(pixels, |p| unsafe { vorrq_u32(p, vdupq_n_u32(0xFF000000)) })
+    }
+
+    pub fn premultiply(pixels: &mut [u8]) -> &mut [u8] {
+        for_each_deinterleaved(pixels, |uint8x16x4_t(r, g, b, a)| unsafe {
+            let mul = |c: uint8x16_t| {
+                // This is exactly x / 255 for x <= 255 * 255.
+                let div = |x: uint16x8_t| {
+                    let x = vaddq_u16(x, vdupq_n_u16(1));
+                    vmovn_u16(vshrq_n_u16::<8>(vaddq_u16(x, vshrq_n_u16::<8>(x))))
+                };
+                let low = div(vmull_u8(vget_low_u8(c), vget_low_u8(a)));
+                let high = div(vmull_high_u8(c, a));
+                vcombine_u8(low, high)
+            };
+            uint8x16x4_t(mul(r), mul(g), mul(b), a)
+        })
+    }
+}
+
+/// Fallback for other architectures: everything is left to the scalar code.
+#[cfg(not(any(
+    all(target_arch = "x86_64", target_endian = "little"),
+    all(target_arch = "aarch64", target_endian = "little")
+)))]
+mod simd {
+    pub fn swap_red_and_blue(pixels: &mut [u8]) -> &mut [u8] {
+        pixels
+    }
+    pub fn swap_byte_order_32(pixels: &mut [u8]) -> &mut [u8] {
+        pixels
+    }
+    pub fn alpha_first_to_last(pixels: &mut [u8]) -> &mut [u8] {
+        pixels
+    }
+    pub fn set_opaque(pixels: &mut [u8]) -> &mut [u8] {
+        pixels
+    }
+    pub fn premultiply(pixels: &mut [u8]) -> &mut [u8] {
+        pixels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swizzles() {
+        let mut pixels = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        swap_red_and_blue(&mut pixels);
+        assert_eq!(pixels, [3, 2, 1, 4, 7, 6, 5, 8]);
+
+        let mut pixels = vec![4, 1, 2, 3];
+        alpha_first_to_last(&mut pixels);
+        assert_eq!(pixels, [1, 2, 3, 4]);
+
+        let mut pixels = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        swap_byte_order_32(&mut pixels);
+        assert_eq!(pixels, [4, 3, 2, 1, 8, 7, 6, 5]);
+    }
+
+    #[test]
+    fn test_premultiply() {
+        let mut pixels = vec![255, 128, 0, 255, 255, 128, 0, 128, 255, 255, 255, 0];
+        premultiply(&mut pixels);
+        assert_eq!(pixels, [255, 128, 0, 255, 128, 64, 0, 128, 0, 0, 0, 0]);
+    }
+
+    /// Check that the SIMD paths give the same results as the scalar code, for
+    /// a length that isn't a multiple of any vector size.
+    #[test]
+    fn test_simd_matches_scalar() {
+        let pixels: Vec<u8> = (0..(4 * 37)).map(|i| (i * 97 + 13) as u8).collect();
+        let scalar = |f: fn(u32) -> u32| -> Vec<u8> {
+            pixels
+                .chunks_exact(4)
+                .flat_map(|p| f(u32::from_le_bytes(p.try_into().unwrap())).to_le_bytes())
+                .collect()
+        };
+        let check = |f: fn(&mut [u8]), expected: Vec<u8>| {
+            let mut actual = pixels.clone();
+            f(&mut actual);
+            assert_eq!(actual, expected);
+        };
+
+        check(
+            swap_red_and_blue,
+            scalar(|p| (p & 0xFF00FF00) | ((p & 0xFF) << 16) | ((p >> 16) & 0xFF)),
+        );
+        check(swap_byte_order_32, scalar(u32::swap_bytes));
+        check(alpha_first_to_last, scalar(|p| p.rotate_right(8)));
+        check(set_opaque, scalar(|p| p | 0xFF000000));
+        check(
+            premultiply,
+            scalar(|p| {
+                let [r, g, b, a] = p.to_le_bytes().map(u32::from);
+                let mul = |c: u32| (c * a / 255) as u8;
+                u32::from_le_bytes([mul(r), mul(g), mul(b), a as u8])
+            }),
+        );
+    }
+
+    #[test]
+    fn test_packed_formats() {
+        let rgba8 = [255, 0, 255, 255, 0, 255, 0, 255];
+
+        let mut rgb565 = Vec::new();
+        rgba8_to_rgb565(&rgba8, &mut rgb565);
+        let mut round_trip = Vec::new();
+        rgb565_to_rgba8(&rgb565, &mut round_trip);
+        assert_eq!(round_trip, rgba8);
+
+        let rgba4444: Vec<u8> = [0xF0FF_u16, 0x0F0F]
+            .iter()
+            .flat_map(|p| p.to_ne_bytes())
+            .collect();
+        let mut rgba8_from_4444 = Vec::new();
+        rgba4444_to_rgba8(&rgba4444, &mut rgba8_from_4444);
+        assert_eq!(rgba8_from_4444, rgba8);
+
+        let rgba5551: Vec<u8> = [0xF83F_u16, 0x07C1]
+            .iter()
+            .flat_map(|p| p.to_ne_bytes())
+            .collect();
+        let mut rgba8_from_5551 = Vec::new();
+        rgba5551_to_rgba8(&rgba5551, &mut rgba8_from_5551);
+        assert_eq!(rgba8_from_5551, rgba8);
+    }
+
+    #[test]
+    fn test_flip_rows() {
+        let mut pixels = vec![1, 1, 2, 2, 3, 3];
+        flip_rows(&mut pixels, 2);
+        assert_eq!(pixels, [3, 3, 2, 2, 1, 1]);
+
+        let mut pixels = vec![1, 1, 2, 2];
+        flip_rows(&mut pixels, 2);
+        assert_eq!(pixels, [2, 2, 1, 1]);
+    }
+}
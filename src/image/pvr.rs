@@ -0,0 +1,410 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Parsing of Imagination Technologies' PVR texture container format.
+//!
+//! iPhone OS doesn't support this format itself, but many apps bundle `.pvr`
+//! files, usually loading them with a copy of the `PVRTexture` class from
+//! Apple's sample code. Most of these are version 2 files, but version 3 files
+//! are supported too.
+//!
+//! This module is only used when the file is loaded as an image. Apps that
+//! upload `.pvr` files as textures parse them with their own code and pass the
+//! data to `glCompressedTexImage2D` or `glTexImage2D`, which already handle
+//! PVRTC, so touchHLE doesn't need a texture loader of its own.
+//!
+//! Resources:
+//! - [PVR file format specification](https://docs.imgtec.com/specifications/pvr-file-format-specification/html/topics/pvr-intro.html)
+//!   (version 3)
+//! - The header definitions in the PowerVR SDK's `PVRTTexture.h` (version 2)
+
+use super::{decode_pvrtc, pixel_format};
+
+/// Pixel formats supported by touchHLE. These are the formats that OpenGL ES
+/// 1.1 on iPhone OS can use, plus a few trivial ones.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PvrFormat {
+    Pvrtc2Bpp,
+    Pvrtc4Bpp,
+    Rgba8888,
+    Bgra8888,
+    Rgb888,
+    Rgb565,
+    Rgba4444,
+    Rgba5551,
+    L8,
+    La88,
+    A8,
+}
+
+impl PvrFormat {
+    /// Size of a mipmap level in bytes. This saturates rather than
+    /// overflowing, so that a bogus header is reported as truncated data.
+    fn level_size(self, width: u32, height: u32) -> usize {
+        let (width, height) = (width as usize, height as usize);
+        let size = |width: usize, height: usize, bits_per_pixel: usize| {
+            width
+                .saturating_mul(height)
+                .saturating_mul(bits_per_pixel)
+                .saturating_add(7)
+                / 8
+        };
+        match self {
+            // This formula is from the IMG_texture_compression_pvrtc extension
+            // spec.
+            Self::Pvrtc2Bpp => size(width.max(16), height.max(8), 2),
+            Self::Pvrtc4Bpp => size(width.max(8), height.max(8), 4),
+            Self::Rgba8888 | Self::Bgra8888 => size(width, height, 32),
+            Self::Rgb888 => size(width, height, 24),
+            Self::Rgb565 | Self::Rgba4444 | Self::Rgba5551 | Self::La88 => size(width, height, 16),
+            Self::L8 | Self::A8 => size(width, height, 8),
+        }
+    }
+}
+
+/// A parsed PVR file. The pixel data is borrowed from the file.
+pub struct PvrTexture<'a> {
+    pub format: PvrFormat,
+    pub width: u32,
+    pub height: u32,
+    /// Whether the rows are in bottom-to-top order (OpenGL style).
+    pub flipped: bool,
+    /// Whether the color channels are already premultiplied by alpha. Only
+    /// version 3 files can say this.
+    pub premultiplied: bool,
+    /// The pixel data for each level of the mipmap chain, starting with the
+    /// full-size image.
+    pub levels: Vec<&'a [u8]>,
+}
+
+const V2_HEADER_SIZE: usize = 52;
+const V2_TAG: &[u8; 4] = b"PVR!";
+const V2_FLAG_MIPMAPS: u32 = 1 << 8;
+const V2_FLAG_VERTICAL_FLIP: u32 = 1 << 16;
+
+const V3_HEADER_SIZE: usize = 52;
+const V3_VERSION: u32 = 0x03525650; // "PVR\x03" in little-endian
+const V3_FLAG_PREMULTIPLIED: u32 = 1 << 1;
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Check whether a file looks like a PVR file.
+pub fn is_pvr(bytes: &[u8]) -> bool {
+    (bytes.len() >= V2_HEADER_SIZE && &bytes[44..48] == V2_TAG)
+        || (bytes.len() >= V3_HEADER_SIZE && read_u32(bytes, 0) == V3_VERSION)
+}
+
+impl PvrTexture<'_> {
+    /// Parse a PVR file. Use [is_pvr] first to check if it is one.
+    pub fn parse(bytes: &[u8]) -> Result<PvrTexture, String> {
+        assert!(is_pvr(bytes));
+
+        let (format, width, height, flipped, premultiplied, level_count, data_offset) =
+            if read_u32(bytes, 0) == V3_VERSION {
+                let flags = read_u32(bytes, 4);
+                let pixel_format = u64::from_le_bytes(bytes[8..16].try_into().unwrap());
+                let format = match pixel_format {
+                    0 | 1 => PvrFormat::Pvrtc2Bpp,
+                    2 | 3 => PvrFormat::Pvrtc4Bpp,
+                    // Uncompressed formats have the channel names in the low
+                    // four bytes and bit counts in the high four bytes.
+                    _ => match pixel_format.to_le_bytes() {
+                        [b'r', b'g', b'b', b'a', 8, 8, 8, 8] => PvrFormat::Rgba8888,
+                        [b'b', b'g', b'r', b'a', 8, 8, 8, 8] => PvrFormat::Bgra8888,
+                        [b'r', b'g', b'b', 0, 8, 8, 8, 0] => PvrFormat::Rgb888,
+                        [b'r', b'g', b'b', 0, 5, 6, 5, 0] => PvrFormat::Rgb565,
+                        [b'r', b'g', b'b', b'a', 4, 4, 4, 4] => PvrFormat::Rgba4444,
+                        [b'r', b'g', b'b', b'a', 5, 5, 5, 1] => PvrFormat::Rgba5551,
+                        [b'l', 0, 0, 0, 8, 0, 0, 0] => PvrFormat::L8,
+                        [b'l', b'a', 0, 0, 8, 8, 0, 0] => PvrFormat::La88,
+                        [b'a', 0, 0, 0, 8, 0, 0, 0] => PvrFormat::A8,
+                        _ => return Err(format!("Unsupported pixel format {:#x}", pixel_format)),
+                    },
+                };
+                let height = read_u32(bytes, 24);
+                let width = read_u32(bytes, 28);
+                let depth = read_u32(bytes, 32);
+                let surface_count = read_u32(bytes, 36);
+                let face_count = read_u32(bytes, 40);
+                if depth != 1 || surface_count != 1 || face_count != 1 {
+                    return Err("3D, array and cube map textures are not supported".to_string());
+                }
+                let level_count = read_u32(bytes, 44);
+                let metadata_size = read_u32(bytes, 48) as usize;
+                // Version 3 files have no flipping flag: the orientation is in
+                // the metadata, and the default is top-to-bottom.
+                (
+                    format,
+                    width,
+                    height,
+                    false,
+                    flags & V3_FLAG_PREMULTIPLIED != 0,
+                    level_count,
+                    V3_HEADER_SIZE + metadata_size,
+                )
+            } else {
+                let header_size = read_u32(bytes, 0) as usize;
+                let height = read_u32(bytes, 4);
+                let width = read_u32(bytes, 8);
+                let mipmap_count = read_u32(bytes, 12);
+                let flags = read_u32(bytes, 16);
+                let format = match flags & 0xFF {
+                    0x10 => PvrFormat::Rgba4444,
+                    0x11 => PvrFormat::Rgba5551,
+                    0x12 => PvrFormat::Rgba8888,
+                    0x13 => PvrFormat::Rgb565,
+                    0x15 => PvrFormat::Rgb888,
+                    0x16 => PvrFormat::L8,
+                    0x17 => PvrFormat::La88,
+                    0x18 => PvrFormat::Pvrtc2Bpp,
+                    0x19 => PvrFormat::Pvrtc4Bpp,
+                    0x1A => PvrFormat::Bgra8888,
+                    0x1B => PvrFormat::A8,
+                    other => return Err(format!("Unsupported pixel format {:#x}", other)),
+                };
+                let level_count = if flags & V2_FLAG_MIPMAPS != 0 {
+                    mipmap_count + 1
+                } else {
+                    1
+                };
+                (
+                    format,
+                    width,
+                    height,
+                    flags & V2_FLAG_VERTICAL_FLIP != 0,
+                    false,
+                    level_count,
+                    header_size,
+                )
+            };
+
+        if width == 0 || height == 0 || level_count == 0 {
+            return Err("Invalid dimensions or mipmap count".to_string());
+        }
+
+        let mut levels = Vec::new();
+        let mut data = bytes
+            .get(data_offset..)
+            .ok_or_else(|| "Header is truncated".to_string())?;
+        let (mut level_width, mut level_height) = (width, height);
+        for level in 0..level_count {
+            let size = format.level_size(level_width, level_height);
+            if size > data.len() {
+                return Err(format!("Data for mipmap level {} is truncated", level));
+            }
+            let (level_data, rest) = data.split_at(size);
+            levels.push(level_data);
+            data = rest;
+            level_width = (level_width / 2).max(1);
+            level_height = (level_height / 2).max(1);
+        }
+
+        log_dbg!(
+            "Parsed PVR file: {}x{} {:?}, {} mipmap levels, premultiplied: {}",
+            width,
+            height,
+            format,
+            levels.len(),
+            premultiplied
+        );
+
+        Ok(PvrTexture {
+            format,
+            width,
+            height,
+            flipped,
+            premultiplied,
+            levels,
+        })
+    }
+
+    /// Decode a mipmap level to RGBA8. The row order and alpha type are not
+    /// changed (see [Self::flipped] and [Self::premultiplied]).
+    pub fn decode_level(&self, level: usize) -> Vec<u8> {
+        let width = (self.width >> level).max(1);
+        let height = (self.height >> level).max(1);
+        let src = self.levels[level];
+        let mut rgba8 = Vec::new();
+        match self.format {
+            PvrFormat::Pvrtc2Bpp | PvrFormat::Pvrtc4Bpp => {
+                let is_2bit = self.format == PvrFormat::Pvrtc2Bpp;
+                let words = decode_pvrtc(src, is_2bit, width, height);
+                rgba8.reserve(words.len() * 4);
+                for word in words {
+                    rgba8.extend_from_slice(&word.to_ne_bytes());
+                }
+            }
+            PvrFormat::Rgba8888 => rgba8.extend_from_slice(src),
+            PvrFormat::Bgra8888 => {
+                rgba8.extend_from_slice(src);
+                pixel_format::swap_red_and_blue(&mut rgba8);
+            }
+            PvrFormat::Rgb888 => pixel_format::rgb8_to_rgba8(src, &mut rgba8),
+            PvrFormat::Rgb565 => pixel_format::rgb565_to_rgba8(src, &mut rgba8),
+            PvrFormat::Rgba4444 => pixel_format::rgba4444_to_rgba8(src, &mut rgba8),
+            PvrFormat::Rgba5551 => pixel_format::rgba5551_to_rgba8(src, &mut rgba8),
+            PvrFormat::L8 => {
+                for &l in src {
+                    rgba8.extend_from_slice(&[l, l, l, 0xFF]);
+                }
+            }
+            PvrFormat::La88 => {
+                for la in src.chunks_exact(2) {
+                    rgba8.extend_from_slice(&[la[0], la[0], la[0], la[1]]);
+                }
+            }
+            PvrFormat::A8 => {
+                for &a in src {
+                    rgba8.extend_from_slice(&[0, 0, 0, a]);
+                }
+            }
+        }
+        rgba8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v2_header(width: u32, height: u32, mipmap_count: u32, flags: u32) -> Vec<u8> {
+        let mut header = Vec::new();
+        for value in [V2_HEADER_SIZE as u32, height, width, mipmap_count, flags] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        // Data size, bits per pixel and channel masks, which aren't used.
+        header.resize(44, 0);
+        header.extend_from_slice(V2_TAG);
+        header.extend_from_slice(&1u32.to_le_bytes()); // surface count
+        header
+    }
+
+    fn v3_header(width: u32, height: u32, pixel_format: [u8; 8]) -> Vec<u8> {
+        let mut header = Vec::new();
+        header.extend_from_slice(&V3_VERSION.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes()); // flags
+        header.extend_from_slice(&pixel_format);
+        // Color space and channel type, which aren't used.
+        header.resize(24, 0);
+        // Height, width, depth, surfaces, faces, mipmap levels and metadata
+        // size.
+        for value in [height, width, 1, 1, 1, 1, 0] {
+            header.extend_from_slice(&value.to_le_bytes());
+        }
+        header
+    }
+
+    fn parse_err(bytes: &[u8]) -> String {
+        match PvrTexture::parse(bytes) {
+            Ok(_) => panic!("Parsing should have failed"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn test_v2_mipmaps() {
+        let mut file = v2_header(2, 2, 1, 0x12 | V2_FLAG_MIPMAPS | V2_FLAG_VERTICAL_FLIP);
+        let level_0: Vec<u8> = (0..16).collect();
+        let level_1 = [16, 17, 18, 19];
+        file.extend_from_slice(&level_0);
+        file.extend_from_slice(&level_1);
+        assert!(is_pvr(&file));
+
+        let texture = PvrTexture::parse(&file).unwrap();
+        assert_eq!(texture.format, PvrFormat::Rgba8888);
+        assert_eq!((texture.width, texture.height), (2, 2));
+        assert!(texture.flipped);
+        assert_eq!(texture.levels, [&level_0[..], &level_1[..]]);
+        assert_eq!(texture.decode_level(0), level_0);
+        assert_eq!(texture.decode_level(1), level_1);
+    }
+
+    #[test]
+    fn test_v3() {
+        let mut file = v3_header(1, 1, [b'b', b'g', b'r', b'a', 8, 8, 8, 8]);
+        file.extend_from_slice(&[1, 2, 3, 4]);
+        assert!(is_pvr(&file));
+
+        let texture = PvrTexture::parse(&file).unwrap();
+        assert_eq!(texture.format, PvrFormat::Bgra8888);
+        assert!(!texture.flipped);
+        assert!(!texture.premultiplied);
+        assert_eq!(texture.decode_level(0), [3, 2, 1, 4]);
+
+        let mut file = v3_header(2, 1, [b'l', b'a', 0, 0, 8, 8, 0, 0]);
+        file.extend_from_slice(&[10, 20, 30, 40]);
+        let texture = PvrTexture::parse(&file).unwrap();
+        assert_eq!(texture.format, PvrFormat::La88);
+        assert_eq!(texture.decode_level(0), [10, 10, 10, 20, 30, 30, 30, 40]);
+    }
+
+    #[test]
+    fn test_v3_premultiplied() {
+        let mut file = v3_header(1, 1, [b'r', b'g', b'b', b'a', 8, 8, 8, 8]);
+        file[4..8].copy_from_slice(&V3_FLAG_PREMULTIPLIED.to_le_bytes());
+        file.extend_from_slice(&[64, 32, 16, 128]);
+        let texture = PvrTexture::parse(&file).unwrap();
+        assert!(texture.premultiplied);
+        // The data is passed through as-is, it's up to the caller not to
+        // premultiply it again.
+        assert_eq!(texture.decode_level(0), [64, 32, 16, 128]);
+    }
+
+    #[test]
+    fn test_not_pvr() {
+        assert!(!is_pvr(&[]));
+        assert!(!is_pvr(b"PVR!"));
+        assert!(!is_pvr(&[0; 64]));
+        // Too short to hold a whole header.
+        assert!(!is_pvr(&v2_header(1, 1, 0, 0x12)[..48]));
+    }
+
+    #[test]
+    fn test_truncated() {
+        // Missing the second mipmap level.
+        let mut file = v2_header(2, 2, 1, 0x12 | V2_FLAG_MIPMAPS);
+        file.extend_from_slice(&[0; 16]);
+        assert_eq!(parse_err(&file), "Data for mipmap level 1 is truncated");
+
+        // Missing all the data.
+        let file = v3_header(4, 4, [b'r', b'g', b'b', b'a', 8, 8, 8, 8]);
+        assert_eq!(parse_err(&file), "Data for mipmap level 0 is truncated");
+
+        // A header size that points past the end of the file.
+        let mut file = v2_header(1, 1, 0, 0x12);
+        file[0..4].copy_from_slice(&1000u32.to_le_bytes());
+        assert_eq!(parse_err(&file), "Header is truncated");
+
+        // Metadata that runs past the end of the file.
+        let mut file = v3_header(1, 1, [b'a', 0, 0, 0, 8, 0, 0, 0]);
+        file[48..52].copy_from_slice(&1000u32.to_le_bytes());
+        assert_eq!(parse_err(&file), "Header is truncated");
+    }
+
+    #[test]
+    fn test_bad_headers() {
+        let file = v2_header(1, 1, 0, 0x14);
+        assert_eq!(parse_err(&file), "Unsupported pixel format 0x14");
+
+        let file = v3_header(1, 1, [b'r', b'g', 0, 0, 8, 8, 0, 0]);
+        assert!(parse_err(&file).starts_with("Unsupported pixel format"));
+
+        let file = v2_header(0, 1, 0, 0x12);
+        assert_eq!(parse_err(&file), "Invalid dimensions or mipmap count");
+
+        let mut file = v3_header(1, 1, [b'a', 0, 0, 0, 8, 0, 0, 0]);
+        file[32..36].copy_from_slice(&2u32.to_le_bytes()); // depth
+        assert_eq!(
+            parse_err(&file),
+            "3D, array and cube map textures are not supported"
+        );
+
+        // Huge dimensions must not overflow.
+        let file = v2_header(u32::MAX, u32::MAX, 0, 0x12);
+        assert_eq!(parse_err(&file), "Data for mipmap level 0 is truncated");
+    }
+}
@@ -5,5 +5,6 @@
  */
 #define STB_IMAGE_IMPLEMENTATION
 #define STB_ONLY_PNG
+#define STB_ONLY_JPEG
 #define STB_NO_STDIO
 #include "../../../vendor/stb/stb_image.h"
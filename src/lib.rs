@@ -46,9 +46,12 @@ mod licenses;
 mod mach_o;
 mod matrix;
 mod mem;
+mod network_rules;
 mod objc;
 mod options;
 mod paths;
+mod pdf;
+mod random;
 mod stack;
 mod window;
 
@@ -57,7 +60,9 @@ mod window;
 // probably shouldn't be, but they need a new home (TODO).
 // Unlike its siblings, this module should be considered private and only used
 // via re-exports.
-use environment::{Environment, MutexId, MutexType, ThreadId, PTHREAD_MUTEX_DEFAULT};
+use environment::{
+    Environment, MutexId, MutexType, ThreadId, ThreadPriority, PTHREAD_MUTEX_DEFAULT,
+};
 
 use std::path::PathBuf;
 
@@ -114,12 +119,39 @@ Special options:
 
     --info
         Print basic information about the app bundle without running the app.
+
+    --validate-input-recording=...
+        Check that the specified file is a valid input recording (see
+        --record-input=) and print basic information about it.
 ";
 
+fn validate_input_recording(path: &str) -> Result<(), String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("Couldn't read {path}: {e}"))?;
+    let recording = window::input_recording::parse(&text)
+        .map_err(|e| format!("Invalid input recording {path}: {e}"))?;
+    let header = &recording.header;
+    echo!("Input recording {} is valid.", path);
+    echo!("- App: {} version {}", header.app_id, header.app_version);
+    echo!("- Recorded with: touchHLE {}", header.emulator_version);
+    echo!("- Device: {}", header.device);
+    echo!(
+        "- Events: {}, lasting {} ticks",
+        recording.events.len(),
+        recording.events.back().map_or(0, |&(tick, _)| tick)
+    );
+    Ok(())
+}
+
 pub fn main<T: Iterator<Item = String>>(mut args: T) -> Result<(), String> {
     echo!("touchHLE {} — https://touchhle.org/", VERSION);
     echo!();
 
+    if let Ok(spec) = std::env::var("TOUCHHLE_LOG") {
+        if let Err(e) = log::apply_level_spec(&spec) {
+            echo!("Warning: Invalid TOUCHHLE_LOG environment variable: {}", e);
+        }
+    }
+
     {
         let base_path = paths::user_data_base_path().to_str().unwrap();
         if !base_path.is_empty() {
@@ -144,6 +176,8 @@ pub fn main<T: Iterator<Item = String>>(mut args: T) -> Result<(), String> {
             return Ok(());
         } else if arg == "--info" {
             just_info = true;
+        } else if let Some(path) = arg.strip_prefix("--validate-input-recording=") {
+            return validate_input_recording(path);
         // Parse an option but discard the value, to test whether it's valid.
         // We don't want to apply it immediately, because then options loaded
         // from a file would take precedence over options from the command line.
@@ -290,6 +324,12 @@ pub fn main<T: Iterator<Item = String>>(mut args: T) -> Result<(), String> {
         assert!(parse_result == Ok(true));
     }
 
+    if let Some(ref spec) = options.log_levels {
+        log::apply_level_spec(spec).unwrap(); // validated by parse_argument
+    }
+
+    log::start_session_log(app_id, bundle.bundle_version(), VERSION);
+
     let mut env = Environment::new(bundle, fs, options, env_for_salvage)?;
     env.run();
     Ok(())
@@ -17,13 +17,19 @@ pub mod cxxabi;
 pub mod dirent;
 pub mod dlfcn;
 pub mod errno;
+pub mod ftw;
+pub mod glob;
 pub mod ifaddrs;
 pub mod keymgr;
+pub mod mach_port;
+pub mod mach_semaphore;
+pub mod mach_task;
 pub mod mach_thread_info;
 pub mod mach_time;
 pub mod math;
 pub mod mmap;
 pub mod net;
+pub mod os_byte_order;
 pub mod posix_io;
 pub mod pthread;
 pub mod sched;
@@ -44,9 +50,11 @@ pub mod wchar;
 pub struct State {
     dirent: dirent::State,
     keymgr: keymgr::State,
+    mach_port: mach_port::State,
     posix_io: posix_io::State,
     pthread: pthread::State,
     pub semaphore: semaphore::State,
+    stdio: stdio::State,
     stdlib: stdlib::State,
     string: string::State,
     time: time::State,
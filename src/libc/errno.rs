@@ -7,14 +7,23 @@
 
 use crate::dyld::FunctionExports;
 use crate::export_c_func;
+use crate::libc::posix_io::{write_to_host_stdio, STDERR_FILENO};
 use crate::mem::{ConstPtr, MutPtr};
 use crate::Environment;
-use std::io::Write;
 
 pub const EPERM: i32 = 1;
+pub const ENOENT: i32 = 2;
+pub const ESRCH: i32 = 3;
+pub const EIO: i32 = 5;
+pub const EBADF: i32 = 9;
+pub const ECHILD: i32 = 10;
 pub const EDEADLK: i32 = 11;
 pub const EBUSY: i32 = 16;
+pub const EEXIST: i32 = 17;
 pub const EINVAL: i32 = 22;
+pub const ENOSPC: i32 = 28;
+pub const EPIPE: i32 = 32;
+pub const EAGAIN: i32 = 35;
 
 #[derive(Default)]
 pub struct State {
@@ -28,7 +37,7 @@ impl State {
     ) -> MutPtr<i32> {
         *self.errnos.entry(thread).or_insert_with(|| {
             log!(
-                "TODO: errno accessed on thread {} (will always be 0)",
+                "TODO: errno accessed on thread {} (most functions don't set it)",
                 thread
             );
             mem.alloc_and_write(0i32)
@@ -36,6 +45,18 @@ impl State {
     }
 }
 
+/// Get `errno` for the current thread.
+pub fn get_errno(env: &mut Environment) -> i32 {
+    let ptr = __error(env);
+    env.mem.read(ptr)
+}
+
+/// Set `errno` for the current thread.
+pub fn set_errno(env: &mut Environment, errno: i32) {
+    let ptr = __error(env);
+    env.mem.write(ptr, errno);
+}
+
 fn __error(env: &mut Environment) -> MutPtr<i32> {
     env.libc_state
         .errno
@@ -54,7 +75,7 @@ fn perror(env: &mut Environment, s: ConstPtr<u8>) {
     } else {
         errno_msg.to_string()
     };
-    let _ = std::io::stderr().write_all(msg.as_bytes());
+    let _ = write_to_host_stdio(env.window.as_mut(), STDERR_FILENO, msg.as_bytes());
 }
 
 pub const FUNCTIONS: FunctionExports = &[export_c_func!(__error()), export_c_func!(perror(_))];
@@ -0,0 +1,175 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `ftw.h`
+
+use super::errno::{set_errno, ENOENT};
+use super::posix_io::stat::stat_for_path;
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::fs::GuestPath;
+use crate::mem::{ConstPtr, SafeRead};
+use crate::Environment;
+
+const FTW_F: i32 = 0;
+const FTW_D: i32 = 1;
+const FTW_DP: i32 = 3;
+
+const FTW_PHYS: i32 = 0x1;
+const FTW_MOUNT: i32 = 0x2;
+const FTW_DEPTH: i32 = 0x4;
+
+#[allow(clippy::upper_case_acronyms)]
+#[repr(C, packed)]
+struct FTW {
+    base: i32,
+    level: i32,
+}
+unsafe impl SafeRead for FTW {}
+
+fn ftw(
+    env: &mut Environment,
+    path: ConstPtr<u8>,
+    func: GuestFunction, // int (*)(const char *, const struct stat *, int)
+    depth: i32,
+) -> i32 {
+    let result = walk(env, path, func, None);
+    log_dbg!("ftw({:?}, {:?}, {}) => {}", path, func, depth, result);
+    result
+}
+
+fn nftw(
+    env: &mut Environment,
+    path: ConstPtr<u8>,
+    func: GuestFunction, // int (*)(const char *, const struct stat *, int, struct FTW *)
+    depth: i32,
+    flags: i32,
+) -> i32 {
+    // There are no symlinks or mount points in the guest filesystem, so
+    // FTW_PHYS and FTW_MOUNT make no difference.
+    let unsupported_flags = flags & !(FTW_PHYS | FTW_MOUNT | FTW_DEPTH);
+    if unsupported_flags != 0 {
+        log!("TODO: nftw() ignoring flags {:#x}", unsupported_flags);
+    }
+    let result = walk(env, path, func, Some(flags));
+    log_dbg!(
+        "nftw({:?}, {:?}, {}, {:#x}) => {}",
+        path,
+        func,
+        depth,
+        flags,
+        result
+    );
+    result
+}
+
+/// Shared implementation of [ftw] and [nftw]. `nftw_flags` is [None] for
+/// [ftw], whose callback has one argument fewer.
+fn walk(
+    env: &mut Environment,
+    path: ConstPtr<u8>,
+    func: GuestFunction,
+    nftw_flags: Option<i32>,
+) -> i32 {
+    let root = env.mem.cstr_at_utf8(path).unwrap().to_owned();
+    if !env.fs.exists(GuestPath::new(&root)) {
+        set_errno(env, ENOENT);
+        return -1;
+    }
+    visit(env, &root, 0, func, nftw_flags)
+}
+
+/// Visit a file or directory, and everything in it. Stops and returns the
+/// callback's result if it's non-zero.
+fn visit(
+    env: &mut Environment,
+    path: &str,
+    level: i32,
+    func: GuestFunction,
+    nftw_flags: Option<i32>,
+) -> i32 {
+    let post_order = nftw_flags.map_or(false, |flags| flags & FTW_DEPTH != 0);
+    if !env.fs.is_dir(GuestPath::new(path)) {
+        return call_callback(env, path, level, FTW_F, func, nftw_flags);
+    }
+
+    if !post_order {
+        let result = call_callback(env, path, level, FTW_D, func, nftw_flags);
+        if result != 0 {
+            return result;
+        }
+    }
+    let mut children: Vec<String> = env
+        .fs
+        .enumerate(GuestPath::new(path))
+        .unwrap()
+        .map(|name| name.to_string())
+        .collect();
+    children.sort();
+    for child in children {
+        let child_path = if path.ends_with('/') {
+            format!("{}{}", path, child)
+        } else {
+            format!("{}/{}", path, child)
+        };
+        let result = visit(env, &child_path, level + 1, func, nftw_flags);
+        if result != 0 {
+            return result;
+        }
+    }
+    if post_order {
+        call_callback(env, path, level, FTW_DP, func, nftw_flags)
+    } else {
+        0
+    }
+}
+
+fn call_callback(
+    env: &mut Environment,
+    path: &str,
+    level: i32,
+    typeflag: i32,
+    func: GuestFunction,
+    nftw_flags: Option<i32>,
+) -> i32 {
+    let stat = stat_for_path(&env.fs, GuestPath::new(path)).unwrap();
+    let path_ptr = env.mem.alloc_and_write_cstr(path.as_bytes());
+    let stat_ptr = env.mem.alloc_and_write(stat);
+    let result: i32 = if nftw_flags.is_some() {
+        // Offset of the file name within the path.
+        let base = path
+            .trim_end_matches('/')
+            .rfind('/')
+            .map_or(0, |slash_idx| slash_idx + 1);
+        let ftw_ptr = env.mem.alloc_and_write(FTW {
+            base: base.try_into().unwrap(),
+            level,
+        });
+        let result = func.call_from_host(
+            env,
+            (
+                path_ptr.cast_const(),
+                stat_ptr.cast_const(),
+                typeflag,
+                ftw_ptr,
+            ),
+        );
+        env.mem.free(ftw_ptr.cast());
+        result
+    } else {
+        func.call_from_host(
+            env,
+            (path_ptr.cast_const(), stat_ptr.cast_const(), typeflag),
+        )
+    };
+    env.mem.free(path_ptr.cast());
+    env.mem.free(stat_ptr.cast());
+    result
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(ftw(_, _, _)),
+    export_c_func!(nftw(_, _, _, _)),
+];
@@ -0,0 +1,333 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `glob.h`
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::fs::{Fs, GuestPath};
+use crate::mem::{guest_size_of, ConstPtr, ConstVoidPtr, GuestUSize, MutPtr, Ptr, SafeRead};
+use crate::Environment;
+
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+struct glob_t {
+    gl_pathc: GuestUSize,
+    gl_matchc: i32,
+    gl_offs: GuestUSize,
+    gl_flags: i32,
+    gl_pathv: MutPtr<MutPtr<u8>>,
+    gl_errfunc: ConstVoidPtr,
+    /// `gl_closedir`, `gl_readdir`, `gl_opendir`, `gl_lstat` and `gl_stat`,
+    /// for `GLOB_ALTDIRFUNC`.
+    _gl_alt_dir_funcs: [ConstVoidPtr; 5],
+}
+unsafe impl SafeRead for glob_t {}
+
+const GLOB_APPEND: i32 = 0x1;
+const GLOB_DOOFFS: i32 = 0x2;
+const GLOB_ERR: i32 = 0x4;
+const GLOB_MARK: i32 = 0x8;
+const GLOB_NOCHECK: i32 = 0x10;
+const GLOB_NOSORT: i32 = 0x20;
+const GLOB_MAGCHAR: i32 = 0x100;
+const GLOB_NOMAGIC: i32 = 0x200;
+const GLOB_NOESCAPE: i32 = 0x2000;
+
+const GLOB_NOMATCH: i32 = -3;
+
+/// Check if a pattern contains any wildcards.
+fn has_magic(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// Match a file name against a shell wildcard pattern (`*`, `?` and `[...]`).
+/// Like in a shell, wildcards don't match a `.` at the start of the name.
+fn matches_pattern(pattern: &str, name: &str, escape: bool) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let explicit_dot = pattern.first() == Some(&'.')
+        || (escape && pattern.first() == Some(&'\\') && pattern.get(1) == Some(&'.'));
+    if name.first() == Some(&'.') && !explicit_dot {
+        return false;
+    }
+    match_here(&pattern, &name, escape)
+}
+
+fn match_here(pattern: &[char], name: &[char], escape: bool) -> bool {
+    let Some((&p, rest)) = pattern.split_first() else {
+        return name.is_empty();
+    };
+    match p {
+        '*' => (0..=name.len()).any(|skip| match_here(rest, &name[skip..], escape)),
+        '?' => !name.is_empty() && match_here(rest, &name[1..], escape),
+        '[' => {
+            let Some(&c) = name.first() else {
+                return false;
+            };
+            match match_bracket(rest, c) {
+                Some((matched, after)) => matched && match_here(after, &name[1..], escape),
+                // Without a closing bracket, the `[` is an ordinary character.
+                None => c == '[' && match_here(rest, &name[1..], escape),
+            }
+        }
+        '\\' if escape && !rest.is_empty() => {
+            name.first() == Some(&rest[0]) && match_here(&rest[1..], &name[1..], escape)
+        }
+        c => name.first() == Some(&c) && match_here(rest, &name[1..], escape),
+    }
+}
+
+/// Match a character against a bracket expression, given the part of the
+/// pattern after the `[`. Returns whether it matched and the rest of the
+/// pattern, or [None] if the expression isn't terminated.
+fn match_bracket(pattern: &[char], c: char) -> Option<(bool, &[char])> {
+    let (negate, mut i) = match pattern.first() {
+        Some('!') | Some('^') => (true, 1),
+        _ => (false, 0),
+    };
+    let start_idx = i;
+    let mut matched = false;
+    loop {
+        let &start = pattern.get(i)?;
+        // A `]` right at the start is part of the set.
+        if start == ']' && i != start_idx {
+            return Some((matched != negate, &pattern[i + 1..]));
+        }
+        if pattern.get(i + 1) == Some(&'-')
+            && matches!(pattern.get(i + 2), Some(&end) if end != ']')
+        {
+            matched |= (start..=pattern[i + 2]).contains(&c);
+            i += 3;
+        } else {
+            matched |= start == c;
+            i += 1;
+        }
+    }
+}
+
+fn unescape(component: &str) -> String {
+    let mut result = String::new();
+    let mut chars = component.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            if let Some(escaped) = chars.next() {
+                result.push(escaped);
+                continue;
+            }
+        }
+        result.push(c);
+    }
+    result
+}
+
+fn join(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_string()
+    } else if prefix.ends_with('/') {
+        format!("{}{}", prefix, name)
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+/// Find the paths in the guest filesystem matching a pattern, in the order
+/// they're found.
+fn expand(fs: &Fs, pattern: &str, escape: bool, mark: bool) -> Vec<String> {
+    let components: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+    let mut candidates = vec![if pattern.starts_with('/') {
+        "/".to_string()
+    } else {
+        String::new()
+    }];
+    if components.is_empty() {
+        candidates.retain(|path| !path.is_empty());
+    }
+
+    for (i, &component) in components.iter().enumerate() {
+        let is_last = i == components.len() - 1;
+        let mut next = Vec::new();
+        for prefix in candidates {
+            if !has_magic(component) {
+                let name = if escape {
+                    unescape(component)
+                } else {
+                    component.to_string()
+                };
+                next.push(join(&prefix, &name));
+                continue;
+            }
+            let dir = if prefix.is_empty() { "." } else { &prefix };
+            let Ok(names) = fs.enumerate(GuestPath::new(dir)) else {
+                continue;
+            };
+            let mut names: Vec<&str> = names
+                .filter(|name| matches_pattern(component, name, escape))
+                .collect();
+            names.sort();
+            next.extend(names.into_iter().map(|name| join(&prefix, name)));
+        }
+        // Only directories can have more components after them.
+        candidates = next
+            .into_iter()
+            .filter(|path| {
+                let path = GuestPath::new(path);
+                if is_last {
+                    fs.exists(path)
+                } else {
+                    fs.is_dir(path)
+                }
+            })
+            .collect();
+    }
+
+    // A trailing slash means only directories match.
+    if pattern.ends_with('/') && !components.is_empty() {
+        candidates.retain(|path| fs.is_dir(GuestPath::new(path)));
+    }
+    for path in candidates.iter_mut() {
+        let wants_slash = pattern.ends_with('/') || (mark && fs.is_dir(GuestPath::new(path)));
+        if wants_slash && !path.ends_with('/') {
+            path.push('/');
+        }
+    }
+    candidates
+}
+
+fn glob(
+    env: &mut Environment,
+    pattern: ConstPtr<u8>,
+    flags: i32,
+    // Never called, because every directory in the guest filesystem can be
+    // read.
+    errfunc: ConstVoidPtr,
+    pglob: MutPtr<glob_t>,
+) -> i32 {
+    let pattern_str = env.mem.cstr_at_utf8(pattern).unwrap().to_owned();
+    let unsupported_flags = flags
+        & !(GLOB_APPEND
+            | GLOB_DOOFFS
+            | GLOB_ERR
+            | GLOB_MARK
+            | GLOB_NOCHECK
+            | GLOB_NOSORT
+            | GLOB_NOMAGIC
+            | GLOB_NOESCAPE);
+    if unsupported_flags != 0 {
+        log!(
+            "TODO: glob({:?}) ignoring flags {:#x}",
+            pattern_str,
+            unsupported_flags
+        );
+    }
+
+    let magic = has_magic(&pattern_str);
+    let mut matches = expand(
+        &env.fs,
+        &pattern_str,
+        flags & GLOB_NOESCAPE == 0,
+        flags & GLOB_MARK != 0,
+    );
+    if flags & GLOB_NOSORT == 0 {
+        matches.sort();
+    }
+    let match_count = matches.len();
+    if matches.is_empty() && (flags & GLOB_NOCHECK != 0 || (flags & GLOB_NOMAGIC != 0 && !magic)) {
+        matches.push(pattern_str.clone());
+    }
+
+    let mut data = env.mem.read(pglob);
+    if flags & GLOB_APPEND == 0 {
+        data.gl_pathc = 0;
+        data.gl_pathv = Ptr::null();
+    }
+    if flags & GLOB_DOOFFS == 0 {
+        data.gl_offs = 0;
+    }
+    let offs = data.gl_offs;
+    let old_count = data.gl_pathc;
+    let old_pathv = data.gl_pathv;
+    let new_count = old_count + GuestUSize::try_from(matches.len()).unwrap();
+
+    // The list has the reserved slots, the old and new paths, and a NULL.
+    let new_pathv: MutPtr<MutPtr<u8>> = env
+        .mem
+        .alloc((offs + new_count + 1) * guest_size_of::<MutPtr<u8>>())
+        .cast();
+    for i in 0..offs {
+        env.mem.write(new_pathv + i, Ptr::null());
+    }
+    for i in 0..old_count {
+        let path = env.mem.read(old_pathv + offs + i);
+        env.mem.write(new_pathv + offs + i, path);
+    }
+    for (i, path) in matches.iter().enumerate() {
+        let path = env.mem.alloc_and_write_cstr(path.as_bytes());
+        let i: GuestUSize = i.try_into().unwrap();
+        env.mem.write(new_pathv + offs + old_count + i, path);
+    }
+    env.mem.write(new_pathv + offs + new_count, Ptr::null());
+    if !old_pathv.is_null() {
+        env.mem.free(old_pathv.cast());
+    }
+
+    data.gl_pathc = new_count;
+    data.gl_matchc = match_count.try_into().unwrap();
+    data.gl_flags = if magic { flags | GLOB_MAGCHAR } else { flags };
+    data.gl_pathv = new_pathv;
+    data.gl_errfunc = errfunc;
+    env.mem.write(pglob, data);
+
+    let result = if matches.is_empty() { GLOB_NOMATCH } else { 0 };
+    log_dbg!(
+        "glob({:?}, {:#x}, {:?}, {:?}) => {} ({:?})",
+        pattern_str,
+        flags,
+        errfunc,
+        pglob,
+        result,
+        matches
+    );
+    result
+}
+
+fn globfree(env: &mut Environment, pglob: MutPtr<glob_t>) {
+    let mut data = env.mem.read(pglob);
+    let pathv = data.gl_pathv;
+    if !pathv.is_null() {
+        for i in 0..data.gl_pathc {
+            let path = env.mem.read(pathv + data.gl_offs + i);
+            env.mem.free(path.cast());
+        }
+        env.mem.free(pathv.cast());
+    }
+    data.gl_pathc = 0;
+    data.gl_pathv = Ptr::null();
+    env.mem.write(pglob, data);
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(glob(_, _, _, _)),
+    export_c_func!(globfree(_)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns() {
+        assert!(matches_pattern("*.dat", "level1.dat", true));
+        assert!(!matches_pattern("*.dat", "level1.png", true));
+        assert!(!matches_pattern("*", ".hidden", true));
+        assert!(matches_pattern(".*", ".hidden", true));
+        assert!(matches_pattern("level?.dat", "level7.dat", true));
+        assert!(matches_pattern("level[0-9].dat", "level3.dat", true));
+        assert!(!matches_pattern("level[!0-9].dat", "level3.dat", true));
+        assert!(matches_pattern("[]a]", "]", true));
+        assert!(matches_pattern("a\\*", "a*", true));
+        assert!(!matches_pattern("a\\*", "ab", true));
+        assert!(matches_pattern("a[b", "a[b", true));
+    }
+}
@@ -0,0 +1,316 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `mach/mach_port.h`
+//!
+//! There is no message passing, so ports are just names in a table, with
+//! user reference counts for each right. Some ports stand for a kernel object,
+//! e.g. a semaphore (see [super::mach_semaphore]).
+//!
+//! Kernel objects are reference-counted separately from port rights: the port
+//! holds one reference, and each thread blocked on the object holds another.
+//! This means an app can deallocate the last right to a semaphore's port while
+//! another thread is still waiting on it, without the semaphore going away
+//! under that thread.
+
+#![allow(non_camel_case_types)]
+
+use super::mach_semaphore::destroy_semaphore;
+use super::semaphore::sem_t;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::environment::ThreadId;
+use crate::mem::MutPtr;
+use crate::Environment;
+use std::collections::HashMap;
+
+type kern_return_t = i32;
+const KERN_SUCCESS: kern_return_t = 0;
+const KERN_INVALID_VALUE: kern_return_t = 18;
+const KERN_INVALID_NAME: kern_return_t = 15;
+const KERN_INVALID_RIGHT: kern_return_t = 17;
+const KERN_UREFS_OVERFLOW: kern_return_t = 19;
+
+type mach_port_t = u32;
+type task_t = mach_port_t;
+pub type mach_port_name_t = u32;
+type mach_port_right_t = u32;
+type mach_port_delta_t = i32;
+
+const MACH_PORT_NULL: mach_port_name_t = 0;
+
+const MACH_PORT_UREFS_MAX: u32 = 0xffff;
+
+const MACH_PORT_RIGHT_SEND: mach_port_right_t = 0;
+const MACH_PORT_RIGHT_RECEIVE: mach_port_right_t = 1;
+const MACH_PORT_RIGHT_SEND_ONCE: mach_port_right_t = 2;
+const MACH_PORT_RIGHT_PORT_SET: mach_port_right_t = 3;
+const MACH_PORT_RIGHT_DEAD_NAME: mach_port_right_t = 4;
+
+/// What a port stands for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MachObject {
+    /// A port with no kernel object, allocated by the app.
+    None,
+    /// A semaphore created by `semaphore_create()`.
+    Semaphore(MutPtr<sem_t>),
+}
+
+#[derive(Debug)]
+struct MachPort {
+    object: MachObject,
+    receive: bool,
+    port_set: bool,
+    send_refs: u32,
+    send_once_refs: u32,
+    dead_name_refs: u32,
+}
+impl MachPort {
+    fn has_rights(&self) -> bool {
+        self.receive
+            || self.port_set
+            || self.send_refs != 0
+            || self.send_once_refs != 0
+            || self.dead_name_refs != 0
+    }
+}
+
+#[derive(Default)]
+pub struct State {
+    ports: HashMap<mach_port_name_t, MachPort>,
+    next_name: mach_port_name_t,
+    /// Reference counts of kernel objects (never [MachObject::None]).
+    object_refs: HashMap<MachObject, u32>,
+    /// Objects that blocked threads hold a reference to.
+    blocked_threads: HashMap<ThreadId, MachObject>,
+}
+impl State {
+    fn get(env: &mut Environment) -> &mut Self {
+        &mut env.libc_state.mach_port
+    }
+
+    fn new_name(&mut self) -> mach_port_name_t {
+        // Real port names look similar: an index in the upper bits and a
+        // generation number in the lower bits.
+        if self.next_name == MACH_PORT_NULL {
+            self.next_name = 0x1003;
+        }
+        let name = self.next_name;
+        self.next_name += 0x100;
+        name
+    }
+}
+
+/// Allocate a port standing for `object`, with a single send right, like the
+/// ones the kernel returns from e.g. `semaphore_create()`.
+pub fn allocate_object_port(env: &mut Environment, object: MachObject) -> mach_port_name_t {
+    let state = State::get(env);
+    let name = state.new_name();
+    state.object_refs.insert(object, 1);
+    state.ports.insert(
+        name,
+        MachPort {
+            object,
+            receive: false,
+            port_set: false,
+            send_refs: 1,
+            send_once_refs: 0,
+            dead_name_refs: 0,
+        },
+    );
+    name
+}
+
+/// Look up the object a port name stands for, if the name is valid.
+pub fn port_object(env: &mut Environment, name: mach_port_name_t) -> Option<MachObject> {
+    State::get(env).ports.get(&name).map(|port| port.object)
+}
+
+/// Remove a port and all rights to it, when its object is being destroyed
+/// explicitly (e.g. by `semaphore_destroy()`) regardless of references.
+pub fn remove_port(env: &mut Environment, name: mach_port_name_t) {
+    let state = State::get(env);
+    let port = state.ports.remove(&name).unwrap();
+    state.object_refs.remove(&port.object);
+    state
+        .blocked_threads
+        .retain(|_, &mut object| object != port.object);
+}
+
+/// Take a reference to the object of the current thread, which is about to
+/// block on it. The reference is released by [handle_thread_unblocked].
+pub fn retain_object_for_blocked_thread(env: &mut Environment, object: MachObject) {
+    let thread = env.current_thread;
+    let state = State::get(env);
+    *state.object_refs.get_mut(&object).unwrap() += 1;
+    let old = state.blocked_threads.insert(thread, object);
+    assert!(old.is_none());
+}
+
+/// Called by the scheduler when a thread stops being blocked, for whatever
+/// reason.
+pub fn handle_thread_unblocked(env: &mut Environment, thread: ThreadId) {
+    let state = State::get(env);
+    if state.blocked_threads.is_empty() {
+        return;
+    }
+    if let Some(object) = state.blocked_threads.remove(&thread) {
+        release_object(env, object);
+    }
+}
+
+fn release_object(env: &mut Environment, object: MachObject) {
+    let refs = State::get(env).object_refs.get_mut(&object).unwrap();
+    *refs -= 1;
+    if *refs != 0 {
+        return;
+    }
+    State::get(env).object_refs.remove(&object);
+    log_dbg!("Object {:?} has no more references", object);
+    match object {
+        MachObject::None => unreachable!(),
+        MachObject::Semaphore(sem) => destroy_semaphore(env, sem),
+    }
+}
+
+/// Called when the last right to a port has gone away.
+fn destroy_port(env: &mut Environment, name: mach_port_name_t) {
+    let port = State::get(env).ports.remove(&name).unwrap();
+    log_dbg!("Port {:#x} ({:?}) has no more rights", name, port.object);
+    if port.object != MachObject::None {
+        release_object(env, port.object);
+    }
+}
+
+fn mach_port_allocate(
+    env: &mut Environment,
+    _task: task_t,
+    right: mach_port_right_t,
+    name: MutPtr<mach_port_name_t>,
+) -> kern_return_t {
+    let (receive, port_set, dead_name_refs) = match right {
+        MACH_PORT_RIGHT_RECEIVE => (true, false, 0),
+        MACH_PORT_RIGHT_PORT_SET => (false, true, 0),
+        MACH_PORT_RIGHT_DEAD_NAME => (false, false, 1),
+        _ => return KERN_INVALID_VALUE,
+    };
+    let state = State::get(env);
+    let new_name = state.new_name();
+    state.ports.insert(
+        new_name,
+        MachPort {
+            object: MachObject::None,
+            receive,
+            port_set,
+            send_refs: 0,
+            send_once_refs: 0,
+            dead_name_refs,
+        },
+    );
+    log_dbg!("mach_port_allocate(right: {}) => {:#x}", right, new_name);
+    env.mem.write(name, new_name);
+    KERN_SUCCESS
+}
+
+fn mach_port_deallocate(
+    env: &mut Environment,
+    _task: task_t,
+    name: mach_port_name_t,
+) -> kern_return_t {
+    if name == MACH_PORT_NULL {
+        return KERN_SUCCESS;
+    }
+    let Some(port) = State::get(env).ports.get_mut(&name) else {
+        log!(
+            "Warning: mach_port_deallocate() with invalid name {:#x}",
+            name
+        );
+        return KERN_INVALID_NAME;
+    };
+    // This releases one user reference for a send, send-once or dead-name
+    // right, whichever the name has.
+    if port.send_refs != 0 {
+        port.send_refs -= 1;
+    } else if port.send_once_refs != 0 {
+        port.send_once_refs -= 1;
+    } else if port.dead_name_refs != 0 {
+        port.dead_name_refs -= 1;
+    } else {
+        return KERN_INVALID_RIGHT;
+    }
+    if !port.has_rights() {
+        destroy_port(env, name);
+    }
+    KERN_SUCCESS
+}
+
+fn mach_port_mod_refs(
+    env: &mut Environment,
+    _task: task_t,
+    name: mach_port_name_t,
+    right: mach_port_right_t,
+    delta: mach_port_delta_t,
+) -> kern_return_t {
+    if right > MACH_PORT_RIGHT_DEAD_NAME {
+        return KERN_INVALID_VALUE;
+    }
+    if name == MACH_PORT_NULL {
+        return KERN_INVALID_NAME;
+    }
+    let Some(port) = State::get(env).ports.get_mut(&name) else {
+        return KERN_INVALID_NAME;
+    };
+    // Receive and port set rights have at most one reference, so they can
+    // only be removed.
+    fn modify_flag(flag: &mut bool, delta: mach_port_delta_t) -> kern_return_t {
+        match (*flag, delta) {
+            (_, 0) => KERN_SUCCESS,
+            (true, -1) => {
+                *flag = false;
+                KERN_SUCCESS
+            }
+            (false, _) => KERN_INVALID_RIGHT,
+            (true, _) => KERN_INVALID_VALUE,
+        }
+    }
+    fn modify_refs(refs: &mut u32, delta: mach_port_delta_t) -> kern_return_t {
+        if *refs == 0 && delta != 0 {
+            return KERN_INVALID_RIGHT;
+        }
+        let new_refs = i64::from(*refs) + i64::from(delta);
+        if new_refs < 0 {
+            KERN_INVALID_VALUE
+        } else if new_refs > MACH_PORT_UREFS_MAX.into() {
+            KERN_UREFS_OVERFLOW
+        } else {
+            *refs = new_refs as u32;
+            KERN_SUCCESS
+        }
+    }
+    let result = match right {
+        MACH_PORT_RIGHT_SEND => modify_refs(&mut port.send_refs, delta),
+        MACH_PORT_RIGHT_RECEIVE => modify_flag(&mut port.receive, delta),
+        MACH_PORT_RIGHT_SEND_ONCE => modify_refs(&mut port.send_once_refs, delta),
+        MACH_PORT_RIGHT_PORT_SET => modify_flag(&mut port.port_set, delta),
+        MACH_PORT_RIGHT_DEAD_NAME => modify_refs(&mut port.dead_name_refs, delta),
+        _ => unreachable!(),
+    };
+    log_dbg!(
+        "mach_port_mod_refs({:#x}, right: {}, delta: {}) => {}",
+        name,
+        right,
+        delta,
+        result
+    );
+    if result == KERN_SUCCESS && !port.has_rights() {
+        destroy_port(env, name);
+    }
+    result
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(mach_port_allocate(_, _, _)),
+    export_c_func!(mach_port_deallocate(_, _)),
+    export_c_func!(mach_port_mod_refs(_, _, _, _)),
+];
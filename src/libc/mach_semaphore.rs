@@ -0,0 +1,200 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `mach/semaphore.h`
+//!
+//! These are implemented on top of the same host semaphores as `semaphore.h`.
+//! Each semaphore has a port (see [super::mach_port]), so it is also destroyed
+//! if the app deallocates the port's send right, once no thread is waiting on
+//! it any more.
+
+#![allow(non_camel_case_types)]
+
+use super::mach_port::{
+    allocate_object_port, port_object, remove_port, retain_object_for_blocked_thread, MachObject,
+};
+use super::semaphore::{create_unnamed_semaphore, destroy_unnamed_semaphore, sem_t};
+use crate::abi::GuestArg;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::mem::MutPtr;
+use crate::Environment;
+use std::time::Duration;
+
+type kern_return_t = i32;
+const KERN_SUCCESS: kern_return_t = 0;
+const KERN_INVALID_ARGUMENT: kern_return_t = 4;
+const KERN_INVALID_VALUE: kern_return_t = 18;
+const KERN_TERMINATED: kern_return_t = 37;
+const KERN_OPERATION_TIMED_OUT: kern_return_t = 49;
+
+type mach_port_t = u32;
+type task_t = mach_port_t;
+type semaphore_t = mach_port_t;
+
+type sync_policy_t = i32;
+
+type clock_res_t = i32;
+
+#[derive(Copy, Clone, Debug)]
+#[repr(C, packed)]
+struct mach_timespec_t {
+    tv_sec: u32,
+    tv_nsec: clock_res_t,
+}
+impl GuestArg for mach_timespec_t {
+    const REG_COUNT: usize = 2;
+
+    fn from_regs(regs: &[u32]) -> Self {
+        mach_timespec_t {
+            tv_sec: GuestArg::from_regs(&regs[0..1]),
+            tv_nsec: GuestArg::from_regs(&regs[1..2]),
+        }
+    }
+    fn to_regs(self, regs: &mut [u32]) {
+        self.tv_sec.to_regs(&mut regs[0..1]);
+        self.tv_nsec.to_regs(&mut regs[1..2]);
+    }
+}
+
+/// Look up the host semaphore for a port name, if it is one.
+fn sem_for_port(env: &mut Environment, semaphore: semaphore_t) -> Option<MutPtr<sem_t>> {
+    match port_object(env, semaphore) {
+        Some(MachObject::Semaphore(sem)) => Some(sem),
+        _ => None,
+    }
+}
+
+/// If the current thread is now blocked on a semaphore, keep the semaphore
+/// alive until it wakes up, even if the port goes away.
+fn retain_if_blocked(env: &mut Environment, sem: MutPtr<sem_t>) {
+    let blocked = env.libc_state.semaphore.open_semaphores[&sem]
+        .borrow()
+        .waiting
+        .contains(&env.current_thread);
+    if blocked {
+        retain_object_for_blocked_thread(env, MachObject::Semaphore(sem));
+    }
+}
+
+/// Destroy a semaphore's host semaphore. Any threads waiting on it are woken
+/// up, and their wait returns `KERN_TERMINATED`.
+pub fn destroy_semaphore(env: &mut Environment, sem: MutPtr<sem_t>) {
+    env.sem_interrupt_waiters(sem, KERN_TERMINATED as u32);
+    destroy_unnamed_semaphore(env, sem);
+}
+
+fn semaphore_create(
+    env: &mut Environment,
+    _task: task_t,
+    semaphore: MutPtr<semaphore_t>,
+    _policy: sync_policy_t,
+    value: i32,
+) -> kern_return_t {
+    if value < 0 {
+        return KERN_INVALID_ARGUMENT;
+    }
+    let sem = create_unnamed_semaphore(env, value);
+    let port = allocate_object_port(env, MachObject::Semaphore(sem));
+    env.mem.write(semaphore, port);
+    KERN_SUCCESS
+}
+
+fn semaphore_destroy(
+    env: &mut Environment,
+    _task: task_t,
+    semaphore: semaphore_t,
+) -> kern_return_t {
+    let Some(sem) = sem_for_port(env, semaphore) else {
+        return KERN_INVALID_ARGUMENT;
+    };
+    remove_port(env, semaphore);
+    destroy_semaphore(env, sem);
+    KERN_SUCCESS
+}
+
+fn semaphore_signal(env: &mut Environment, semaphore: semaphore_t) -> kern_return_t {
+    let Some(sem) = sem_for_port(env, semaphore) else {
+        return KERN_INVALID_ARGUMENT;
+    };
+    env.sem_increment(sem);
+    KERN_SUCCESS
+}
+
+fn semaphore_signal_all(env: &mut Environment, semaphore: semaphore_t) -> kern_return_t {
+    let Some(sem) = sem_for_port(env, semaphore) else {
+        return KERN_INVALID_ARGUMENT;
+    };
+    // Wake every waiting thread. Unlike semaphore_signal(), this doesn't
+    // increment the count if there are no waiters.
+    let host_sem_rc = &env.libc_state.semaphore.open_semaphores[&sem];
+    let mut host_sem = (*host_sem_rc).borrow_mut();
+    if host_sem.value < 0 {
+        host_sem.value = 0;
+    }
+    KERN_SUCCESS
+}
+
+fn semaphore_wait(env: &mut Environment, semaphore: semaphore_t) -> kern_return_t {
+    let Some(sem) = sem_for_port(env, semaphore) else {
+        return KERN_INVALID_ARGUMENT;
+    };
+    env.sem_decrement(sem, true);
+    retain_if_blocked(env, sem);
+    KERN_SUCCESS
+}
+
+fn semaphore_timedwait(
+    env: &mut Environment,
+    semaphore: semaphore_t,
+    wait_time: mach_timespec_t,
+) -> kern_return_t {
+    let Some(sem) = sem_for_port(env, semaphore) else {
+        return KERN_INVALID_ARGUMENT;
+    };
+    let mach_timespec_t { tv_sec, tv_nsec } = wait_time;
+    if !(0..1_000_000_000).contains(&tv_nsec) {
+        return KERN_INVALID_VALUE;
+    }
+    if env.sem_decrement(sem, false) {
+        return KERN_SUCCESS;
+    }
+    let timeout = Duration::new(tv_sec.into(), tv_nsec as u32);
+    if timeout.is_zero() {
+        return KERN_OPERATION_TIMED_OUT;
+    }
+    // If the timeout expires first, the scheduler replaces this return value.
+    env.sem_decrement_with_timeout(sem, timeout, KERN_OPERATION_TIMED_OUT as u32);
+    retain_if_blocked(env, sem);
+    KERN_SUCCESS
+}
+
+fn semaphore_wait_signal(
+    env: &mut Environment,
+    wait_semaphore: semaphore_t,
+    signal_semaphore: semaphore_t,
+) -> kern_return_t {
+    let (Some(wait_sem), Some(signal_sem)) = (
+        sem_for_port(env, wait_semaphore),
+        sem_for_port(env, signal_semaphore),
+    ) else {
+        return KERN_INVALID_ARGUMENT;
+    };
+    // The signal happens first, so that a thread waiting on it can run while
+    // this one waits.
+    env.sem_increment(signal_sem);
+    env.sem_decrement(wait_sem, true);
+    retain_if_blocked(env, wait_sem);
+    KERN_SUCCESS
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(semaphore_create(_, _, _, _)),
+    export_c_func!(semaphore_destroy(_, _)),
+    export_c_func!(semaphore_signal(_)),
+    export_c_func!(semaphore_signal_all(_)),
+    export_c_func!(semaphore_wait(_)),
+    export_c_func!(semaphore_timedwait(_, _)),
+    export_c_func!(semaphore_wait_signal(_, _)),
+];
@@ -0,0 +1,175 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `mach/task.h` and `mach/task_info.h`
+//!
+//! There is only one task, the app itself.
+
+#![allow(non_camel_case_types)]
+
+use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
+use crate::mem::{guest_size_of, ConstVoidPtr, Mem, MutPtr, SafeRead};
+use crate::Environment;
+
+type kern_return_t = i32;
+const KERN_SUCCESS: kern_return_t = 0;
+const KERN_INVALID_ARGUMENT: kern_return_t = 4;
+
+type mach_port_t = u32;
+type task_t = mach_port_t;
+type task_name_t = mach_port_t;
+
+type natural_t = u32;
+type integer_t = i32;
+type policy_t = i32;
+const POLICY_TIMESHARE: policy_t = 1;
+
+type task_flavor_t = natural_t;
+type task_info_t = MutPtr<integer_t>;
+type mach_msg_type_number_t = natural_t;
+
+/// The name the app's own task port has. This is an arbitrary value that's
+/// unlikely to collide with ports allocated by [super::mach_port].
+const MACH_TASK_SELF: task_t = 0x103;
+
+/// `TASK_BASIC_INFO` is an alias of this on 32-bit platforms.
+const TASK_BASIC_INFO_32: task_flavor_t = 4;
+const TASK_BASIC_INFO_64: task_flavor_t = 5;
+
+#[repr(C, packed)]
+struct time_value_t {
+    seconds: integer_t,
+    microseconds: integer_t,
+}
+unsafe impl SafeRead for time_value_t {}
+
+#[repr(C, packed)]
+struct task_basic_info_32 {
+    suspend_count: integer_t,
+    virtual_size: natural_t,
+    resident_size: natural_t,
+    user_time: time_value_t,
+    system_time: time_value_t,
+    policy: policy_t,
+}
+unsafe impl SafeRead for task_basic_info_32 {}
+
+#[repr(C, packed)]
+struct task_basic_info_64 {
+    suspend_count: integer_t,
+    virtual_size: u64,
+    resident_size: u64,
+    user_time: time_value_t,
+    system_time: time_value_t,
+    policy: policy_t,
+}
+unsafe impl SafeRead for task_basic_info_64 {}
+
+pub const CONSTANTS: ConstantExports = &[(
+    "_mach_task_self_",
+    HostConstant::Custom(|mem: &mut Mem| -> ConstVoidPtr {
+        mem.alloc_and_write(MACH_TASK_SELF).cast().cast_const()
+    }),
+)];
+
+/// This is usually accessed via the `mach_task_self()` macro, which reads
+/// `mach_task_self_` instead.
+fn mach_task_self(_env: &mut Environment) -> task_t {
+    MACH_TASK_SELF
+}
+
+fn task_info(
+    env: &mut Environment,
+    target_task: task_name_t,
+    flavor: task_flavor_t,
+    task_info_out: task_info_t,
+    task_info_out_count: MutPtr<mach_msg_type_number_t>,
+) -> kern_return_t {
+    if target_task != MACH_TASK_SELF {
+        log!(
+            "Warning: task_info() called for unknown task {:#x}",
+            target_task
+        );
+        return KERN_INVALID_ARGUMENT;
+    }
+
+    // There's no virtual memory or paging, so everything that's in use is
+    // resident. The address space taken by the null segment is the only
+    // thing that would count towards the virtual size but not the resident
+    // size on a real device.
+    let resident_size = env.mem.used_bytes();
+    let virtual_size = resident_size + env.mem.null_segment_size();
+    // TODO: Track CPU time for real? This is wall-clock time.
+    let uptime = env.startup_time.elapsed();
+    let user_time = time_value_t {
+        seconds: uptime.as_secs() as integer_t,
+        microseconds: uptime.subsec_micros() as integer_t,
+    };
+    let system_time = time_value_t {
+        seconds: 0,
+        microseconds: 0,
+    };
+
+    let out_size_available = env.mem.read(task_info_out_count);
+    let out_size = match flavor {
+        TASK_BASIC_INFO_32 => {
+            let out_size = guest_size_of::<task_basic_info_32>() / guest_size_of::<integer_t>();
+            if out_size_available < out_size {
+                return KERN_INVALID_ARGUMENT;
+            }
+            env.mem.write(
+                task_info_out.cast(),
+                task_basic_info_32 {
+                    suspend_count: 0,
+                    virtual_size,
+                    resident_size,
+                    user_time,
+                    system_time,
+                    policy: POLICY_TIMESHARE,
+                },
+            );
+            out_size
+        }
+        TASK_BASIC_INFO_64 => {
+            let out_size = guest_size_of::<task_basic_info_64>() / guest_size_of::<integer_t>();
+            if out_size_available < out_size {
+                return KERN_INVALID_ARGUMENT;
+            }
+            env.mem.write(
+                task_info_out.cast(),
+                task_basic_info_64 {
+                    suspend_count: 0,
+                    virtual_size: virtual_size.into(),
+                    resident_size: resident_size.into(),
+                    user_time,
+                    system_time,
+                    policy: POLICY_TIMESHARE,
+                },
+            );
+            out_size
+        }
+        _ => {
+            log!("TODO: task_info() flavor {}, returning error", flavor);
+            return KERN_INVALID_ARGUMENT;
+        }
+    };
+    env.mem.write(task_info_out_count, out_size);
+
+    log_dbg!(
+        "task_info({:#x}, {}, {:?}, {:?}) => resident size {:#x}",
+        target_task,
+        flavor,
+        task_info_out,
+        task_info_out_count,
+        resident_size
+    );
+
+    KERN_SUCCESS
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(mach_task_self()),
+    export_c_func!(task_info(_, _, _, _)),
+];
@@ -11,10 +11,11 @@
 
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::mem::{guest_size_of, MutPtr, SafeRead};
-use crate::Environment;
+use crate::{Environment, ThreadPriority};
 
 type kern_return_t = i32;
 const KERN_SUCCESS: kern_return_t = 0;
+const KERN_INVALID_ARGUMENT: kern_return_t = 4;
 
 type mach_port_t = u32;
 
@@ -134,21 +135,99 @@ type thread_t = mach_port_t;
 type thread_policy_flavor_t = natural_t;
 type thread_policy_t = MutPtr<integer_t>;
 
-// This is actually from the thread policy file.
+// These are actually from the thread policy file.
+const THREAD_STANDARD_POLICY: thread_policy_flavor_t = 1;
+const THREAD_TIME_CONSTRAINT_POLICY: thread_policy_flavor_t = 2;
+const THREAD_PRECEDENCE_POLICY: thread_policy_flavor_t = 3;
+
+#[repr(C, packed)]
+struct thread_time_constraint_policy {
+    period: u32,
+    computation: u32,
+    constraint: u32,
+    preemptible: boolean_t,
+}
+unsafe impl SafeRead for thread_time_constraint_policy {}
+
+#[repr(C, packed)]
+struct thread_precedence_policy {
+    importance: integer_t,
+}
+unsafe impl SafeRead for thread_precedence_policy {}
+
+/// Thread ports are just thread IDs (see also `pthread_mach_thread_np`).
 fn thread_policy_set(
-    _env: &mut Environment,
+    env: &mut Environment,
     thread: thread_t,
     flavor: thread_policy_flavor_t,
     policy_info: thread_policy_t,
     count: mach_msg_type_number_t,
 ) -> kern_return_t {
-    log!(
-        "TODO: thread_policy_set({}, {}, {:?}, {}) (ignored)",
+    let thread_id = thread as usize;
+    if thread_id >= env.threads.len() {
+        return KERN_INVALID_ARGUMENT;
+    }
+
+    let count_expected = match flavor {
+        THREAD_STANDARD_POLICY => 0,
+        THREAD_TIME_CONSTRAINT_POLICY => {
+            guest_size_of::<thread_time_constraint_policy>() / guest_size_of::<integer_t>()
+        }
+        THREAD_PRECEDENCE_POLICY => {
+            guest_size_of::<thread_precedence_policy>() / guest_size_of::<integer_t>()
+        }
+        _ => {
+            log!(
+                "TODO: thread_policy_set({}, {}, {:?}, {}) (unsupported flavor, returning error)",
+                thread,
+                flavor,
+                policy_info,
+                count
+            );
+            return KERN_INVALID_ARGUMENT;
+        }
+    };
+    if count < count_expected {
+        return KERN_INVALID_ARGUMENT;
+    }
+
+    let priority = match flavor {
+        THREAD_STANDARD_POLICY => ThreadPriority::DEFAULT,
+        THREAD_TIME_CONSTRAINT_POLICY => {
+            // The timing parameters are in "absolute time units", which we
+            // can't meaningfully honor, so they're only logged.
+            let thread_time_constraint_policy {
+                period,
+                computation,
+                constraint,
+                ..
+            } = env.mem.read(policy_info.cast());
+            log_dbg!(
+                "Thread {} has time constraint: period {}, computation {}, constraint {}",
+                thread_id,
+                period,
+                computation,
+                constraint
+            );
+            ThreadPriority::TimeConstraint
+        }
+        THREAD_PRECEDENCE_POLICY => {
+            // Importance is relative to the default priority. The range of
+            // POSIX priorities is 32 wide, so treat that as the full range.
+            let thread_precedence_policy { importance } = env.mem.read(policy_info.cast());
+            ThreadPriority::Normal((0.5 + importance as f64 / 32.0).clamp(0.0, 1.0))
+        }
+        _ => unreachable!(),
+    };
+    log_dbg!(
+        "thread_policy_set({}, {}, {:?}, {}): priority is now {:?}",
         thread,
         flavor,
         policy_info,
-        count
+        count,
+        priority
     );
+    env.threads[thread_id].priority = priority;
     KERN_SUCCESS
 }
 
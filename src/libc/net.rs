@@ -6,3 +6,4 @@
 
 // `if` is a reserved word
 pub mod if_;
+pub mod inet;
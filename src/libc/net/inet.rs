@@ -0,0 +1,33 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `arpa/inet.h` byte order conversion functions.
+//!
+//! These are normally macros, but they can still end up being called as
+//! functions, e.g. if code takes their address. Network byte order is
+//! big-endian and the guest is little-endian.
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::Environment;
+
+fn htonl(_env: &mut Environment, hostlong: u32) -> u32 {
+    hostlong.to_be()
+}
+fn htons(_env: &mut Environment, hostshort: u16) -> u16 {
+    hostshort.to_be()
+}
+fn ntohl(_env: &mut Environment, netlong: u32) -> u32 {
+    u32::from_be(netlong)
+}
+fn ntohs(_env: &mut Environment, netshort: u16) -> u16 {
+    u16::from_be(netshort)
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(htonl(_)),
+    export_c_func!(htons(_)),
+    export_c_func!(ntohl(_)),
+    export_c_func!(ntohs(_)),
+];
@@ -0,0 +1,92 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `libkern/OSByteOrder.h`
+//!
+//! The header implements all of these as macros or inline functions, but the
+//! function forms are provided in case an app calls them dynamically. The
+//! guest is always little-endian.
+
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::Environment;
+
+fn _OSSwapInt16(_env: &mut Environment, data: u16) -> u16 {
+    data.swap_bytes()
+}
+fn _OSSwapInt32(_env: &mut Environment, data: u32) -> u32 {
+    data.swap_bytes()
+}
+fn _OSSwapInt64(_env: &mut Environment, data: u64) -> u64 {
+    data.swap_bytes()
+}
+
+fn OSSwapInt16(_env: &mut Environment, data: u16) -> u16 {
+    data.swap_bytes()
+}
+fn OSSwapInt32(_env: &mut Environment, data: u32) -> u32 {
+    data.swap_bytes()
+}
+fn OSSwapInt64(_env: &mut Environment, data: u64) -> u64 {
+    data.swap_bytes()
+}
+
+fn OSSwapBigToHostInt16(_env: &mut Environment, data: u16) -> u16 {
+    u16::from_be(data)
+}
+fn OSSwapBigToHostInt32(_env: &mut Environment, data: u32) -> u32 {
+    u32::from_be(data)
+}
+fn OSSwapBigToHostInt64(_env: &mut Environment, data: u64) -> u64 {
+    u64::from_be(data)
+}
+fn OSSwapHostToBigInt16(_env: &mut Environment, data: u16) -> u16 {
+    data.to_be()
+}
+fn OSSwapHostToBigInt32(_env: &mut Environment, data: u32) -> u32 {
+    data.to_be()
+}
+fn OSSwapHostToBigInt64(_env: &mut Environment, data: u64) -> u64 {
+    data.to_be()
+}
+
+fn OSSwapLittleToHostInt16(_env: &mut Environment, data: u16) -> u16 {
+    u16::from_le(data)
+}
+fn OSSwapLittleToHostInt32(_env: &mut Environment, data: u32) -> u32 {
+    u32::from_le(data)
+}
+fn OSSwapLittleToHostInt64(_env: &mut Environment, data: u64) -> u64 {
+    u64::from_le(data)
+}
+fn OSSwapHostToLittleInt16(_env: &mut Environment, data: u16) -> u16 {
+    data.to_le()
+}
+fn OSSwapHostToLittleInt32(_env: &mut Environment, data: u32) -> u32 {
+    data.to_le()
+}
+fn OSSwapHostToLittleInt64(_env: &mut Environment, data: u64) -> u64 {
+    data.to_le()
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(_OSSwapInt16(_)),
+    export_c_func!(_OSSwapInt32(_)),
+    export_c_func!(_OSSwapInt64(_)),
+    export_c_func!(OSSwapInt16(_)),
+    export_c_func!(OSSwapInt32(_)),
+    export_c_func!(OSSwapInt64(_)),
+    export_c_func!(OSSwapBigToHostInt16(_)),
+    export_c_func!(OSSwapBigToHostInt32(_)),
+    export_c_func!(OSSwapBigToHostInt64(_)),
+    export_c_func!(OSSwapHostToBigInt16(_)),
+    export_c_func!(OSSwapHostToBigInt32(_)),
+    export_c_func!(OSSwapHostToBigInt64(_)),
+    export_c_func!(OSSwapLittleToHostInt16(_)),
+    export_c_func!(OSSwapLittleToHostInt32(_)),
+    export_c_func!(OSSwapLittleToHostInt64(_)),
+    export_c_func!(OSSwapHostToLittleInt16(_)),
+    export_c_func!(OSSwapHostToLittleInt32(_)),
+    export_c_func!(OSSwapHostToLittleInt64(_)),
+];
@@ -7,29 +7,65 @@
 
 pub mod stat;
 
+use super::errno::{set_errno, EAGAIN, EBADF, EEXIST, EINVAL, EIO, ENOSPC, EPIPE};
 use crate::abi::DotDotDot;
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::fs::{GuestFile, GuestOpenOptions, GuestPath};
-use crate::mem::{ConstPtr, ConstVoidPtr, GuestISize, GuestUSize, MutPtr, MutVoidPtr, Ptr};
+use crate::mem::{
+    ConstPtr, ConstVoidPtr, GuestISize, GuestUSize, MutPtr, MutVoidPtr, Ptr, SafeRead,
+};
 use crate::Environment;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+use std::time::Duration;
 
 #[derive(Default)]
 pub struct State {
     /// File descriptors _other than stdin, stdout, and stderr_
     files: Vec<Option<PosixFileHostObject>>,
+    /// Files that stdin, stdout and stderr have been redirected to with
+    /// [dup2]. When there's none, they're forwarded to the host's.
+    std_streams: [Option<PosixFileHostObject>; 3],
 }
 impl State {
+    fn slot_for_fd(&mut self, fd: FileDescriptor) -> Option<&mut Option<PosixFileHostObject>> {
+        if fd < 0 {
+            None
+        } else if fd < NORMAL_FILENO_BASE {
+            Some(&mut self.std_streams[fd as usize])
+        } else {
+            self.files.get_mut(fd_to_file_idx(fd))
+        }
+    }
+
     fn file_for_fd(&mut self, fd: FileDescriptor) -> Option<&mut PosixFileHostObject> {
-        self.files
-            .get_mut(fd_to_file_idx(fd))
+        self.slot_for_fd(fd)
             .and_then(|file_or_none| file_or_none.as_mut())
     }
+
+    /// Put a file in the lowest free file descriptor.
+    fn add_file(&mut self, host_object: PosixFileHostObject) -> FileDescriptor {
+        let idx = if let Some(free_idx) = self.files.iter().position(|f| f.is_none()) {
+            self.files[free_idx] = Some(host_object);
+            free_idx
+        } else {
+            let idx = self.files.len();
+            self.files.push(Some(host_object));
+            idx
+        };
+        file_idx_to_fd(idx)
+    }
 }
 
+/// How often a thread blocked reading an empty pipe checks it again.
+const PIPE_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
 struct PosixFileHostObject {
     file: GuestFile,
     reached_eof: bool,
+    /// Access mode and file status flags, see `F_GETFL` in [fcntl].
+    status_flags: OpenFlag,
+    /// `FD_CLOEXEC`. There's no `exec()`, so this is only remembered.
+    close_on_exec: bool,
 }
 
 // TODO: stdin/stdout/stderr handling somehow
@@ -66,6 +102,9 @@ pub const O_CREAT: OpenFlag = 0x200;
 pub const O_TRUNC: OpenFlag = 0x400;
 pub const O_EXCL: OpenFlag = 0x800;
 
+/// Flags that can be changed with `F_SETFL` (see [fcntl]).
+const SETTABLE_STATUS_FLAGS: OpenFlag = O_NONBLOCK | O_APPEND;
+
 pub type FLockFlag = i32;
 pub const LOCK_SH: FLockFlag = 1;
 #[allow(dead_code)]
@@ -95,16 +134,15 @@ pub fn open_direct(env: &mut Environment, path: ConstPtr<u8>, flags: i32) -> Fil
                 | O_EXCL)
             == 0
     );
-    // TODO: exclusive mode not implemented yet
-    assert!(flags & O_EXCL == 0);
-
     if path.is_null() {
         log_dbg!("open({:?}, {:#x}) => -1", path, flags);
         return -1; // TODO: set errno to EFAULT
     }
 
     // TODO: respect the mode (in the variadic arguments) when creating a file
-    // Note: NONBLOCK flag is ignored, assumption is all file I/O is fast
+    // Note: NONBLOCK flag is only remembered for F_GETFL, assumption is all
+    // file I/O is fast. O_APPEND is handled by write(), so that it can be
+    // changed with F_SETFL.
     let mut options = GuestOpenOptions::new();
     match flags & O_ACCMODE {
         O_RDONLY => options.read(),
@@ -112,9 +150,6 @@ pub fn open_direct(env: &mut Environment, path: ConstPtr<u8>, flags: i32) -> Fil
         O_RDWR => options.read().write(),
         _ => panic!(),
     };
-    if (flags & O_APPEND) != 0 {
-        options.append();
-    }
     if (flags & O_CREAT) != 0 {
         options.create();
     }
@@ -138,31 +173,33 @@ pub fn open_direct(env: &mut Environment, path: ConstPtr<u8>, flags: i32) -> Fil
     if flags & O_NOFOLLOW != 0 {
         log!("Ignoring O_NOFOLLOW when opening {:?}", path_string);
     }
-    let res = match env
-        .fs
-        .open_with_options(GuestPath::new(&path_string), options)
-    {
+    // There's no /dev in the guest filesystem, but apps may read random
+    // bytes from these devices.
+    // O_EXCL without O_CREAT is undefined, and ignored like on macOS.
+    if flags & (O_CREAT | O_EXCL) == O_CREAT | O_EXCL {
+        let path = GuestPath::new(&path_string);
+        if env.fs.is_file(path) || env.fs.is_dir(path) {
+            log_dbg!("open({:?}, {:#x}) => -1, file exists", path, flags);
+            set_errno(env, EEXIST);
+            return -1;
+        }
+    }
+
+    let file = if matches!(path_string.as_str(), "/dev/random" | "/dev/urandom") {
+        Ok(GuestFile::random_device(env.rng.fork()))
+    } else {
+        env.fs
+            .open_with_options(GuestPath::new(&path_string), options)
+    };
+    let res = match file {
         Ok(file) => {
             let host_object = PosixFileHostObject {
                 file,
                 reached_eof: false,
+                status_flags: flags & (O_ACCMODE | SETTABLE_STATUS_FLAGS),
+                close_on_exec: false,
             };
-
-            let idx = if let Some(free_idx) = env
-                .libc_state
-                .posix_io
-                .files
-                .iter()
-                .position(|f| f.is_none())
-            {
-                env.libc_state.posix_io.files[free_idx] = Some(host_object);
-                free_idx
-            } else {
-                let idx = env.libc_state.posix_io.files.len();
-                env.libc_state.posix_io.files.push(Some(host_object));
-                idx
-            };
-            file_idx_to_fd(idx)
+            env.libc_state.posix_io.add_file(host_object)
         }
         Err(()) => {
             // TODO: set errno
@@ -189,17 +226,27 @@ pub fn read(
     buffer: MutVoidPtr,
     size: GuestUSize,
 ) -> GuestISize {
-    // TODO: error handling for unknown fd?
-    let file = env.libc_state.posix_io.file_for_fd(fd).unwrap();
+    let result = loop {
+        // TODO: error handling for unknown fd?
+        let file = env.libc_state.posix_io.file_for_fd(fd).unwrap();
+        let buffer_slice = env.mem.bytes_at_mut(buffer.cast(), size);
+        match file.file.read(buffer_slice) {
+            // Only an empty pipe can block. Other threads might write to it.
+            Err(e) if e.kind() == ErrorKind::WouldBlock && file.status_flags & O_NONBLOCK == 0 => {
+                env.sleep(PIPE_POLL_INTERVAL, /* tail_call: */ false);
+            }
+            result => break result,
+        }
+    };
 
-    let buffer_slice = env.mem.bytes_at_mut(buffer.cast(), size);
-    match file.file.read(buffer_slice) {
+    match result {
         Ok(bytes_read) => {
             if bytes_read == 0 && size != 0 {
                 // need to set EOF
+                let file = env.libc_state.posix_io.file_for_fd(fd).unwrap();
                 file.reached_eof = true;
             }
-            if bytes_read < buffer_slice.len() {
+            if bytes_read < size as usize {
                 log!(
                     "Warning: read({:?}, {:?}, {:#x}) read only {:#x} bytes",
                     fd,
@@ -218,6 +265,16 @@ pub fn read(
             }
             bytes_read.try_into().unwrap()
         }
+        Err(e) if e.kind() == ErrorKind::WouldBlock => {
+            log_dbg!(
+                "read({:?}, {:?}, {:#x}) => -1, would block",
+                fd,
+                buffer,
+                size
+            );
+            set_errno(env, EAGAIN);
+            -1
+        }
         Err(e) => {
             // TODO: set errno
             log!(
@@ -242,16 +299,57 @@ pub(super) fn eof(env: &mut Environment, fd: FileDescriptor) -> i32 {
     }
 }
 
+/// Write the app's output to the host's standard output or error stream. It is
+/// also shown in the console overlay if there's a window (see
+/// [crate::window::Window::console_write]).
+pub fn write_to_host_stdio(
+    window: Option<&mut crate::window::Window>,
+    fd: FileDescriptor,
+    bytes: &[u8],
+) -> std::io::Result<()> {
+    match fd {
+        STDOUT_FILENO => std::io::stdout().write_all(bytes)?,
+        STDERR_FILENO => std::io::stderr().write_all(bytes)?,
+        _ => unreachable!(),
+    }
+    if let Some(window) = window {
+        window.console_write(&String::from_utf8_lossy(bytes));
+    }
+    Ok(())
+}
+
 pub fn write(
     env: &mut Environment,
     fd: FileDescriptor,
     buffer: ConstVoidPtr,
     size: GuestUSize,
 ) -> GuestISize {
+    let buffer_slice = env.mem.bytes_at(buffer.cast(), size);
+
+    // The standard output and error streams are forwarded to the host's,
+    // unless they've been redirected.
+    let redirected = env.libc_state.posix_io.file_for_fd(fd).is_some();
+    if !redirected && (fd == STDOUT_FILENO || fd == STDERR_FILENO) {
+        return match write_to_host_stdio(env.window.as_mut(), fd, buffer_slice) {
+            Ok(()) => size.try_into().unwrap(),
+            Err(_) => {
+                set_errno(env, EIO);
+                -1
+            }
+        };
+    }
+
     // TODO: error handling for unknown fd?
     let file = env.libc_state.posix_io.file_for_fd(fd).unwrap();
 
-    let buffer_slice = env.mem.bytes_at(buffer.cast(), size);
+    if file.status_flags & O_APPEND != 0 {
+        if let Err(e) = file.file.seek(SeekFrom::End(0)) {
+            log!("Warning: write({:?}) couldn't seek to end: {:?}", fd, e);
+            set_errno(env, EIO);
+            return -1;
+        }
+    }
+
     match file.file.write(buffer_slice) {
         Ok(bytes_written) => {
             if bytes_written < buffer_slice.len() {
@@ -273,8 +371,18 @@ pub fn write(
             }
             bytes_written.try_into().unwrap()
         }
+        // TODO: SIGPIPE
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => {
+            log_dbg!(
+                "write({:?}, {:?}, {:#x}) => -1, broken pipe",
+                fd,
+                buffer,
+                size
+            );
+            set_errno(env, EPIPE);
+            -1
+        }
         Err(e) => {
-            // TODO: set errno
             log!(
                 "Warning: write({:?}, {:?}, {:#x}) encountered error {:?}, returning -1",
                 fd,
@@ -282,6 +390,11 @@ pub fn write(
                 size,
                 e,
             );
+            let errno = match e.kind() {
+                ErrorKind::StorageFull => ENOSPC,
+                _ => EIO,
+            };
+            set_errno(env, errno);
             -1
         }
     }
@@ -313,8 +426,10 @@ pub fn lseek(env: &mut Environment, fd: FileDescriptor, offset: off_t, whence: i
 
             new_offset.try_into().unwrap()
         }
-        // TODO: set errno
-        Err(_) => -1,
+        Err(_) => {
+            set_errno(env, EINVAL);
+            -1
+        }
     };
     log_dbg!("lseek({:?}, {:#x}, {}) => {}", fd, offset, whence, res);
     res
@@ -322,11 +437,21 @@ pub fn lseek(env: &mut Environment, fd: FileDescriptor, offset: off_t, whence: i
 
 pub fn close(env: &mut Environment, fd: FileDescriptor) -> i32 {
     // TODO: error handling for unknown fd?
-    if fd < 0 || matches!(fd, STDOUT_FILENO | STDERR_FILENO) {
+    if fd < 0 {
+        return 0;
+    }
+    // Closing a standard stream that isn't redirected does nothing. Closing one
+    // that is redirected goes back to forwarding it to the host's.
+    if fd < NORMAL_FILENO_BASE && env.libc_state.posix_io.file_for_fd(fd).is_none() {
         return 0;
     }
 
-    match env.libc_state.posix_io.files[fd_to_file_idx(fd)].take() {
+    match env
+        .libc_state
+        .posix_io
+        .slot_for_fd(fd)
+        .and_then(|slot| slot.take())
+    {
         Some(file) => {
             // The actual closing of the file happens implicitly when `file`
             // falls out of scope. The return value is about whether flushing
@@ -351,6 +476,103 @@ pub fn close(env: &mut Environment, fd: FileDescriptor) -> i32 {
     }
 }
 
+fn pipe(env: &mut Environment, fildes: MutPtr<FileDescriptor>) -> i32 {
+    let (read_end, write_end) = GuestFile::pipe();
+    let state = &mut env.libc_state.posix_io;
+    let read_fd = state.add_file(PosixFileHostObject {
+        file: read_end,
+        reached_eof: false,
+        status_flags: O_RDONLY,
+        close_on_exec: false,
+    });
+    let write_fd = state.add_file(PosixFileHostObject {
+        file: write_end,
+        reached_eof: false,
+        status_flags: O_WRONLY,
+        close_on_exec: false,
+    });
+    env.mem.write(fildes, read_fd);
+    env.mem.write(fildes + 1, write_fd);
+    log_dbg!("pipe({:?}) => 0, fds {} and {}", fildes, read_fd, write_fd);
+    0
+}
+
+/// Helper for `popen()`: open the read end of a pipe that `contents` has
+/// already been written to, and whose write end is closed.
+pub(super) fn open_pipe_with_contents(env: &mut Environment, contents: &[u8]) -> FileDescriptor {
+    let (read_end, mut write_end) = GuestFile::pipe();
+    write_end.write_all(contents).unwrap();
+    env.libc_state.posix_io.add_file(PosixFileHostObject {
+        file: read_end,
+        reached_eof: false,
+        status_flags: O_RDONLY,
+        close_on_exec: false,
+    })
+}
+
+/// Make a new file descriptor for the same file as `fd`, for [dup] and [dup2].
+/// Unlike on a real system, the file status flags aren't shared afterwards.
+fn duplicate_file(env: &mut Environment, fd: FileDescriptor) -> Option<PosixFileHostObject> {
+    let Some(file) = env.libc_state.posix_io.file_for_fd(fd) else {
+        set_errno(env, EBADF);
+        return None;
+    };
+    match file.file.try_clone() {
+        Ok(cloned) => Some(PosixFileHostObject {
+            file: cloned,
+            reached_eof: file.reached_eof,
+            status_flags: file.status_flags,
+            close_on_exec: false,
+        }),
+        Err(e) => {
+            log!("TODO: Can't duplicate fd {:?} ({:?}): {}", fd, file.file, e);
+            set_errno(env, EINVAL);
+            None
+        }
+    }
+}
+
+fn dup(env: &mut Environment, fd: FileDescriptor) -> FileDescriptor {
+    let result = match duplicate_file(env, fd) {
+        Some(host_object) => env.libc_state.posix_io.add_file(host_object),
+        None => -1,
+    };
+    log_dbg!("dup({:?}) => {:?}", fd, result);
+    result
+}
+
+fn dup2(env: &mut Environment, fd: FileDescriptor, fd2: FileDescriptor) -> FileDescriptor {
+    if fd2 < 0 {
+        set_errno(env, EBADF);
+        return -1;
+    }
+    if fd == fd2 {
+        return if env.libc_state.posix_io.file_for_fd(fd).is_some() {
+            fd2
+        } else {
+            set_errno(env, EBADF);
+            -1
+        };
+    }
+    let Some(host_object) = duplicate_file(env, fd) else {
+        log_dbg!("dup2({:?}, {:?}) => -1", fd, fd2);
+        return -1;
+    };
+    if env.libc_state.posix_io.file_for_fd(fd2).is_some() {
+        close(env, fd2);
+    }
+    let state = &mut env.libc_state.posix_io;
+    if fd2 >= NORMAL_FILENO_BASE {
+        let idx = fd_to_file_idx(fd2);
+        if idx >= state.files.len() {
+            state.files.resize_with(idx + 1, || None);
+        }
+    }
+    *state.slot_for_fd(fd2).unwrap() = Some(host_object);
+    log_dbg!("dup2({:?}, {:?}) => {:?}", fd, fd2, fd2);
+    fd2
+}
+
 pub fn getcwd(env: &mut Environment, buf_ptr: MutPtr<u8>, buf_size: GuestUSize) -> MutPtr<u8> {
     let working_directory = env.fs.working_directory();
     if !env.fs.is_dir(working_directory) {
@@ -426,7 +648,204 @@ fn flock(_env: &mut Environment, fd: FileDescriptor, operation: FLockFlag) -> i3
     0
 }
 
-fn ftruncate(env: &mut Environment, fd: FileDescriptor, len: off_t) -> i32 {
+/// Like [read], but at an offset, without using or changing the file position.
+fn pread(
+    env: &mut Environment,
+    fd: FileDescriptor,
+    buffer: MutVoidPtr,
+    size: GuestUSize,
+    offset: off_t,
+) -> GuestISize {
+    let Ok(offset) = u64::try_from(offset) else {
+        set_errno(env, EINVAL);
+        return -1;
+    };
+    let Some(file) = env.libc_state.posix_io.file_for_fd(fd) else {
+        set_errno(env, EBADF);
+        return -1;
+    };
+    let buffer_slice = env.mem.bytes_at_mut(buffer.cast(), size);
+    let result = with_position(&mut file.file, offset, |file| {
+        // Unlike read(), this loops until the buffer is full or the end of the
+        // file is reached, since a short read here is easier to handle.
+        let mut total = 0;
+        while total < buffer_slice.len() {
+            match file.read(&mut buffer_slice[total..])? {
+                0 => break,
+                n => total += n,
+            }
+        }
+        Ok(total)
+    });
+    log_dbg!(
+        "pread({:?}, {:?}, {:#x}, {:#x}) => {:?}",
+        fd,
+        buffer,
+        size,
+        offset,
+        result
+    );
+    match result {
+        Ok(bytes_read) => bytes_read.try_into().unwrap(),
+        Err(_) => -1, // TODO: set errno
+    }
+}
+
+/// Like [write], but at an offset, without using or changing the file
+/// position.
+fn pwrite(
+    env: &mut Environment,
+    fd: FileDescriptor,
+    buffer: ConstVoidPtr,
+    size: GuestUSize,
+    offset: off_t,
+) -> GuestISize {
+    let Ok(offset) = u64::try_from(offset) else {
+        set_errno(env, EINVAL);
+        return -1;
+    };
+    let Some(file) = env.libc_state.posix_io.file_for_fd(fd) else {
+        set_errno(env, EBADF);
+        return -1;
+    };
+    let buffer_slice = env.mem.bytes_at(buffer.cast(), size);
+    let result = with_position(&mut file.file, offset, |file| {
+        file.write_all(buffer_slice)?;
+        Ok(buffer_slice.len())
+    });
+    log_dbg!(
+        "pwrite({:?}, {:?}, {:#x}, {:#x}) => {:?}",
+        fd,
+        buffer,
+        size,
+        offset,
+        result
+    );
+    match result {
+        Ok(bytes_written) => bytes_written.try_into().unwrap(),
+        Err(_) => -1, // TODO: set errno
+    }
+}
+
+/// Do something with the file position temporarily moved to `offset`.
+fn with_position<T>(
+    file: &mut GuestFile,
+    offset: u64,
+    f: impl FnOnce(&mut GuestFile) -> std::io::Result<T>,
+) -> std::io::Result<T> {
+    let old_position = file.stream_position()?;
+    file.seek(SeekFrom::Start(offset))?;
+    let result = f(file);
+    file.seek(SeekFrom::Start(old_position))?;
+    result
+}
+
+pub const F_GETFD: i32 = 1;
+pub const F_SETFD: i32 = 2;
+pub const F_GETFL: i32 = 3;
+pub const F_SETFL: i32 = 4;
+pub const F_GETLK: i32 = 7;
+pub const F_SETLK: i32 = 8;
+pub const F_SETLKW: i32 = 9;
+pub const F_NOCACHE: i32 = 48;
+pub const F_FULLFSYNC: i32 = 51;
+
+pub const FD_CLOEXEC: i32 = 1;
+
+pub const F_RDLCK: i16 = 1;
+pub const F_UNLCK: i16 = 2;
+pub const F_WRLCK: i16 = 3;
+
+/// `struct flock`, used for advisory record locks.
+#[allow(non_camel_case_types)]
+#[derive(Copy, Clone, Debug)]
+#[repr(C, packed)]
+struct struct_flock {
+    l_start: off_t,
+    l_len: off_t,
+    l_pid: i32,
+    l_type: i16,
+    l_whence: i16,
+}
+unsafe impl SafeRead for struct_flock {}
+
+fn fcntl(env: &mut Environment, fd: FileDescriptor, cmd: i32, args: DotDotDot) -> i32 {
+    let Some(file) = env.libc_state.posix_io.file_for_fd(fd) else {
+        log!(
+            "Warning: fcntl({:?}, {}) with invalid fd, returning -1",
+            fd,
+            cmd
+        );
+        set_errno(env, EBADF);
+        return -1;
+    };
+    let result = match cmd {
+        F_GETFD => {
+            if file.close_on_exec {
+                FD_CLOEXEC
+            } else {
+                0
+            }
+        }
+        F_SETFD => {
+            let value: i32 = args.start().next(env);
+            let file = env.libc_state.posix_io.file_for_fd(fd).unwrap();
+            file.close_on_exec = value & FD_CLOEXEC != 0;
+            0
+        }
+        F_GETFL => file.status_flags,
+        F_SETFL => {
+            let value: i32 = args.start().next(env);
+            let file = env.libc_state.posix_io.file_for_fd(fd).unwrap();
+            // The access mode and creation flags can't be changed.
+            file.status_flags =
+                (file.status_flags & !SETTABLE_STATUS_FLAGS) | (value & SETTABLE_STATUS_FLAGS);
+            0
+        }
+        F_GETLK | F_SETLK | F_SETLKW => {
+            let access_mode = file.status_flags & O_ACCMODE;
+            let lock_ptr: MutPtr<struct_flock> = args.start().next(env);
+            let mut lock = env.mem.read(lock_ptr);
+            // Record locks belong to a process, and a process's own locks never
+            // conflict with each other. The app is the only process, so there
+            // are never any conflicts, and locks don't need to be tracked.
+            match lock.l_type {
+                F_RDLCK | F_WRLCK if cmd == F_GETLK => {
+                    lock.l_type = F_UNLCK;
+                    env.mem.write(lock_ptr, lock);
+                    0
+                }
+                F_RDLCK if access_mode == O_WRONLY => {
+                    set_errno(env, EBADF);
+                    -1
+                }
+                F_WRLCK if access_mode == O_RDONLY => {
+                    set_errno(env, EBADF);
+                    -1
+                }
+                F_RDLCK | F_WRLCK | F_UNLCK => 0,
+                _ => {
+                    set_errno(env, EINVAL);
+                    -1
+                }
+            }
+        }
+        F_NOCACHE => 0,
+        F_FULLFSYNC => match file.file.sync_all() {
+            Ok(()) => 0,
+            Err(_) => -1, // TODO: set errno
+        },
+        _ => {
+            log!("TODO: fcntl({:?}, {}), returning -1", fd, cmd);
+            set_errno(env, EINVAL);
+            -1
+        }
+    };
+    log_dbg!("fcntl({:?}, {}, ...) => {}", fd, cmd, result);
+    result
+}
+
+pub fn ftruncate(env: &mut Environment, fd: FileDescriptor, len: off_t) -> i32 {
     let file = env.libc_state.posix_io.file_for_fd(fd).unwrap();
     match file.file.set_len(len as u64) {
         Ok(()) => 0,
@@ -434,6 +853,51 @@ fn ftruncate(env: &mut Environment, fd: FileDescriptor, len: off_t) -> i32 {
     }
 }
 
+/// Shared implementation of [fsync] and [fdatasync]. Apps use these to make
+/// sure saved data survives a crash, and touchHLE could be killed at any time,
+/// so the host file really is synchronized.
+fn sync_file(env: &mut Environment, fd: FileDescriptor, data_only: bool) -> i32 {
+    let function_name = if data_only { "fdatasync" } else { "fsync" };
+    // Standard streams that aren't redirected are forwarded to the host's,
+    // which touchHLE doesn't buffer.
+    if (0..NORMAL_FILENO_BASE).contains(&fd) && env.libc_state.posix_io.file_for_fd(fd).is_none() {
+        return 0;
+    }
+    let Some(file) = env.libc_state.posix_io.file_for_fd(fd) else {
+        set_errno(env, EBADF);
+        return -1;
+    };
+    let result = if data_only {
+        file.file.sync_data()
+    } else {
+        file.file.sync_all()
+    };
+    match result {
+        Ok(()) => {
+            log_dbg!("{}({:?}) => 0", function_name, fd);
+            0
+        }
+        Err(e) => {
+            log!(
+                "Warning: {}({:?}) failed ({}), returning -1",
+                function_name,
+                fd,
+                e
+            );
+            set_errno(env, EIO);
+            -1
+        }
+    }
+}
+
+pub fn fsync(env: &mut Environment, fd: FileDescriptor) -> i32 {
+    sync_file(env, fd, /* data_only: */ false)
+}
+
+fn fdatasync(env: &mut Environment, fd: FileDescriptor) -> i32 {
+    sync_file(env, fd, /* data_only: */ true)
+}
+
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(open(_, _, _)),
     export_c_func!(read(_, _, _)),
@@ -444,4 +908,12 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(chdir(_)),
     export_c_func!(flock(_, _)),
     export_c_func!(ftruncate(_, _)),
+    export_c_func!(fsync(_)),
+    export_c_func!(fdatasync(_)),
+    export_c_func!(pread(_, _, _, _)),
+    export_c_func!(pwrite(_, _, _, _)),
+    export_c_func!(fcntl(_, _, _)),
+    export_c_func!(pipe(_)),
+    export_c_func!(dup(_)),
+    export_c_func!(dup2(_, _)),
 ];
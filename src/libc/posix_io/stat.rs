@@ -7,14 +7,64 @@
 
 use super::{off_t, FileDescriptor};
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::fs::GuestPath;
-use crate::mem::{ConstPtr, MutVoidPtr};
+use crate::fs::{Fs, GuestPath};
+use crate::mem::{ConstPtr, MutVoidPtr, SafeRead};
 use crate::Environment;
 use std::io::{Seek, SeekFrom};
 
 #[allow(non_camel_case_types)]
 pub type mode_t = u16;
 
+pub const S_IFDIR: mode_t = 0o040000;
+pub const S_IFREG: mode_t = 0o100000;
+
+/// `struct stat`, in the layout used with 64-bit inode numbers.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Default)]
+#[repr(C, packed)]
+pub struct stat {
+    pub st_dev: i32,
+    pub st_mode: mode_t,
+    pub st_nlink: u16,
+    pub st_ino: u64,
+    pub st_uid: u32,
+    pub st_gid: u32,
+    pub st_rdev: i32,
+    pub st_atimespec: [i32; 2],
+    pub st_mtimespec: [i32; 2],
+    pub st_ctimespec: [i32; 2],
+    pub st_birthtimespec: [i32; 2],
+    pub st_size: off_t,
+    pub st_blocks: i64,
+    pub st_blksize: i32,
+    pub st_flags: u32,
+    pub st_gen: u32,
+    pub st_lspare: i32,
+    pub st_qspare: [i64; 2],
+}
+unsafe impl SafeRead for stat {}
+
+/// Get what `stat()` would report for a path, as far as the guest filesystem
+/// knows it: the type and size. Returns [None] if the path doesn't exist.
+pub fn stat_for_path(fs: &Fs, path: &GuestPath) -> Option<stat> {
+    let (mode, size) = if fs.is_dir(path) {
+        (S_IFDIR | 0o755, 0)
+    } else if fs.is_file(path) {
+        let mut file = fs.open(path).ok()?;
+        (S_IFREG | 0o644, file.seek(SeekFrom::End(0)).ok()?)
+    } else {
+        return None;
+    };
+    Some(stat {
+        st_mode: mode,
+        st_nlink: 1,
+        st_size: size.try_into().unwrap(),
+        st_blocks: ((size + 511) / 512).try_into().unwrap(),
+        st_blksize: 4096,
+        ..Default::default()
+    })
+}
+
 fn mkdir(env: &mut Environment, path: ConstPtr<u8>, mode: mode_t) -> i32 {
     // TODO: respect the mode
     match env
@@ -5,7 +5,7 @@
  */
 //! Thread-specific data keys.
 
-use crate::abi::GuestFunction;
+use crate::abi::{CallFromHost, GuestFunction};
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::mem::{ConstVoidPtr, MutPtr, MutVoidPtr, Ptr};
 use crate::{Environment, ThreadId};
@@ -25,6 +25,9 @@ fn get_state(env: &mut Environment) -> &mut State {
 
 type pthread_key_t = u32;
 
+/// How many times destructors are called again if they set new values.
+const PTHREAD_DESTRUCTOR_ITERATIONS: usize = 4;
+
 fn pthread_key_create(
     env: &mut Environment,
     key_ptr: MutPtr<pthread_key_t>,
@@ -58,6 +61,42 @@ fn pthread_setspecific(env: &mut Environment, key: pthread_key_t, value: ConstVo
     0 // success
 }
 
+/// Call the destructors for the current thread's non-null values, as POSIX
+/// requires when a thread exits. This runs guest code, so the thread must still
+/// exist.
+pub fn run_destructors(env: &mut Environment) {
+    let current_thread = env.current_thread;
+    for _ in 0..PTHREAD_DESTRUCTOR_ITERATIONS {
+        // Values are set to null before calling the destructor, which may set
+        // new ones.
+        let to_destroy: Vec<(GuestFunction, MutVoidPtr)> = get_state(env)
+            .keys
+            .iter_mut()
+            .filter_map(|(values, destructor)| {
+                let value = values.remove(&current_thread)?;
+                (!value.is_null() && !destructor.to_ptr().is_null()).then_some((*destructor, value))
+            })
+            .collect();
+        if to_destroy.is_empty() {
+            return;
+        }
+        for (destructor, value) in to_destroy {
+            log_dbg!(
+                "Calling thread-specific data destructor {:?}({:?}) for thread {}",
+                destructor,
+                value,
+                current_thread
+            );
+            () = destructor.call_from_host(env, (value,));
+        }
+    }
+    log!(
+        "Warning: Thread {} still has thread-specific data after {} rounds of destructors",
+        current_thread,
+        PTHREAD_DESTRUCTOR_ITERATIONS
+    );
+}
+
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(pthread_key_create(_, _)),
     export_c_func!(pthread_getspecific(_)),
@@ -7,9 +7,9 @@
 
 use crate::abi::GuestFunction;
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::libc::errno::{EDEADLK, EINVAL};
-use crate::mem::{ConstPtr, MutPtr, MutVoidPtr, SafeRead};
-use crate::{Environment, ThreadId};
+use crate::libc::errno::{EDEADLK, EINVAL, ESRCH};
+use crate::mem::{ConstPtr, GuestUSize, Mem, MutPtr, MutVoidPtr, SafeRead};
+use crate::{Environment, ThreadId, ThreadPriority};
 use std::collections::HashMap;
 
 #[derive(Default)]
@@ -31,16 +31,55 @@ pub struct pthread_attr_t {
     /// Magic number (must be [MAGIC_ATTR])
     magic: u32,
     detachstate: i32,
-    _unused: [u32; 8],
+    stacksize: GuestUSize,
+    sched_priority: i32,
+    _unused: [u32; 6],
 }
 unsafe impl SafeRead for pthread_attr_t {}
 
 const DEFAULT_ATTR: pthread_attr_t = pthread_attr_t {
     magic: MAGIC_ATTR,
     detachstate: PTHREAD_CREATE_JOINABLE,
-    _unused: [0; 8],
+    stacksize: Mem::SECONDARY_THREAD_STACK_SIZE,
+    sched_priority: SCHED_PRIORITY_DEFAULT,
+    _unused: [0; 6],
 };
 
+#[derive(Copy, Clone, Debug)]
+#[repr(C, packed)]
+pub struct sched_param {
+    sched_priority: i32,
+    _opaque: [u8; 4],
+}
+unsafe impl SafeRead for sched_param {}
+
+/// Scheduling policies. The policy is ignored, only the priority matters.
+type SchedPolicy = i32;
+const SCHED_OTHER: SchedPolicy = 1;
+
+/// Range of priorities for all policies (matches Apple's).
+pub const SCHED_PRIORITY_MIN: i32 = 15;
+pub const SCHED_PRIORITY_MAX: i32 = 47;
+const SCHED_PRIORITY_DEFAULT: i32 = 31;
+
+/// Convert a POSIX thread priority to the scheduler's representation.
+fn priority_from_sched(sched_priority: i32) -> ThreadPriority {
+    let range = (SCHED_PRIORITY_MAX - SCHED_PRIORITY_MIN) as f64;
+    let priority = (sched_priority - SCHED_PRIORITY_MIN) as f64 / range;
+    ThreadPriority::Normal(priority.clamp(0.0, 1.0))
+}
+/// Convert the scheduler's representation of a priority to a POSIX thread
+/// priority.
+fn priority_to_sched(priority: ThreadPriority) -> i32 {
+    match priority {
+        ThreadPriority::Normal(priority) => {
+            let range = (SCHED_PRIORITY_MAX - SCHED_PRIORITY_MIN) as f64;
+            SCHED_PRIORITY_MIN + (priority * range).round() as i32
+        }
+        ThreadPriority::TimeConstraint => SCHED_PRIORITY_MAX,
+    }
+}
+
 /// Apple's implementation is a 4-byte magic number followed by a massive
 /// (>4KiB) opaque region. We will store the actual data on the host instead.
 #[repr(C, packed)]
@@ -55,7 +94,7 @@ pub type pthread_t = MutPtr<OpaqueThread>;
 struct ThreadHostObject {
     thread_id: ThreadId,
     joined_by: Option<ThreadId>,
-    _attr: pthread_attr_t,
+    attr: pthread_attr_t,
 }
 
 /// Arbitrarily-chosen magic number for `pthread_attr_t` (not Apple's).
@@ -68,6 +107,11 @@ type DetachState = i32;
 const PTHREAD_CREATE_JOINABLE: DetachState = 1;
 pub const PTHREAD_CREATE_DETACHED: DetachState = 2;
 
+/// Minimum stack size for a thread (matches Apple's `<limits.h>`).
+pub const PTHREAD_STACK_MIN: GuestUSize = 16 * 1024;
+/// Stack sizes must be a multiple of the page size.
+pub const PAGE_SIZE: GuestUSize = 4 * 1024;
+
 pub fn pthread_attr_init(env: &mut Environment, attr: MutPtr<pthread_attr_t>) -> i32 {
     env.mem.write(attr, DEFAULT_ATTR);
     0 // success
@@ -84,6 +128,66 @@ pub fn pthread_attr_setdetachstate(
     env.mem.write(attr, attr_copy);
     0 // success
 }
+pub fn pthread_attr_setstacksize(
+    env: &mut Environment,
+    attr: MutPtr<pthread_attr_t>,
+    stacksize: GuestUSize,
+) -> i32 {
+    check_magic!(env, attr, MAGIC_ATTR);
+    if stacksize < PTHREAD_STACK_MIN || stacksize % PAGE_SIZE != 0 {
+        log!(
+            "Warning: pthread_attr_setstacksize({:?}, {:#x}) rejected, returning EINVAL",
+            attr,
+            stacksize
+        );
+        return EINVAL;
+    }
+    let mut attr_copy = env.mem.read(attr);
+    attr_copy.stacksize = stacksize;
+    env.mem.write(attr, attr_copy);
+    0 // success
+}
+fn pthread_attr_getstacksize(
+    env: &mut Environment,
+    attr: ConstPtr<pthread_attr_t>,
+    stacksize: MutPtr<GuestUSize>,
+) -> i32 {
+    check_magic!(env, attr, MAGIC_ATTR);
+    let attr = env.mem.read(attr);
+    env.mem.write(stacksize, attr.stacksize);
+    0 // success
+}
+fn pthread_attr_setschedparam(
+    env: &mut Environment,
+    attr: MutPtr<pthread_attr_t>,
+    param: ConstPtr<sched_param>,
+) -> i32 {
+    check_magic!(env, attr, MAGIC_ATTR);
+    let sched_priority = env.mem.read(param).sched_priority;
+    if !(SCHED_PRIORITY_MIN..=SCHED_PRIORITY_MAX).contains(&sched_priority) {
+        return EINVAL;
+    }
+    let mut attr_copy = env.mem.read(attr);
+    attr_copy.sched_priority = sched_priority;
+    env.mem.write(attr, attr_copy);
+    0 // success
+}
+fn pthread_attr_getschedparam(
+    env: &mut Environment,
+    attr: ConstPtr<pthread_attr_t>,
+    param: MutPtr<sched_param>,
+) -> i32 {
+    check_magic!(env, attr, MAGIC_ATTR);
+    let attr = env.mem.read(attr);
+    env.mem.write(
+        param,
+        sched_param {
+            sched_priority: attr.sched_priority,
+            _opaque: [0; 4],
+        },
+    );
+    0 // success
+}
 fn pthread_attr_destroy(env: &mut Environment, attr: MutPtr<pthread_attr_t>) -> i32 {
     check_magic!(env, attr, MAGIC_ATTR);
     env.mem.write(
@@ -91,6 +195,8 @@ fn pthread_attr_destroy(env: &mut Environment, attr: MutPtr<pthread_attr_t>) ->
         pthread_attr_t {
             magic: 0,
             detachstate: 0,
+            stacksize: 0,
+            sched_priority: 0,
             _unused: Default::default(),
         },
     );
@@ -111,7 +217,10 @@ pub fn pthread_create(
         DEFAULT_ATTR
     };
 
-    let thread_id = env.new_thread(start_routine, user_data);
+    let thread_id = env.new_thread(start_routine, user_data, attr.stacksize);
+    if attr.sched_priority != SCHED_PRIORITY_DEFAULT {
+        env.threads[thread_id].priority = priority_from_sched(attr.sched_priority);
+    }
 
     let opaque = env.mem.alloc_and_write(OpaqueThread {
         magic: MAGIC_THREAD,
@@ -124,7 +233,7 @@ pub fn pthread_create(
         ThreadHostObject {
             thread_id,
             joined_by: None,
-            _attr: attr,
+            attr,
         },
     );
 
@@ -151,7 +260,7 @@ fn pthread_self(env: &mut Environment) -> pthread_t {
             ThreadHostObject {
                 thread_id: 0,
                 joined_by: None,
-                _attr: DEFAULT_ATTR,
+                attr: DEFAULT_ATTR,
             },
         );
         log_dbg!(
@@ -206,10 +315,15 @@ fn pthread_join(env: &mut Environment, thread: pthread_t, retval: MutPtr<MutVoid
 
     // Deattached threads cannot be joined with.
     let host_obj_joinee = State::get(env).threads.get_mut(&thread).unwrap();
-    if host_obj_joinee._attr.detachstate == PTHREAD_CREATE_DETACHED {
+    if host_obj_joinee.attr.detachstate == PTHREAD_CREATE_DETACHED {
         log_dbg!("Thread attempted join with deattached thread, returning EINVAL!");
         return EINVAL;
     }
+    // Only one thread can join with a given thread.
+    if host_obj_joinee.joined_by.is_some() {
+        log_dbg!("Thread attempted join with already-joined thread, returning EINVAL!");
+        return EINVAL;
+    }
 
     host_obj_joinee.joined_by = Some(current_thread);
     // The executor will write the return value (void*) to *retval after the
@@ -217,11 +331,128 @@ fn pthread_join(env: &mut Environment, thread: pthread_t, retval: MutPtr<MutVoid
     env.join_with_thread(joinee_thread, retval);
     0
 }
+/// Darwin's version of this function can only name the current thread.
+fn pthread_setname_np(env: &mut Environment, name: ConstPtr<u8>) -> i32 {
+    let name = String::from_utf8_lossy(env.mem.cstr_at(name)).into_owned();
+    log_dbg!(
+        "pthread_setname_np({:?}) on thread {}",
+        name,
+        env.current_thread
+    );
+    env.threads[env.current_thread].name = Some(name);
+    0 // success
+}
+fn pthread_getname_np(
+    env: &mut Environment,
+    thread: pthread_t,
+    name: MutPtr<u8>,
+    len: GuestUSize,
+) -> i32 {
+    let Some(host_obj) = State::get(env).threads.get(&thread) else {
+        log!(
+            "Warning: pthread_getname_np({:?}) on unknown thread, returning ESRCH",
+            thread
+        );
+        return ESRCH;
+    };
+    let thread_id = host_obj.thread_id;
+    let mut bytes = env.threads[thread_id]
+        .name
+        .as_deref()
+        .unwrap_or("")
+        .as_bytes()
+        .to_vec();
+    if len == 0 {
+        return 0;
+    }
+    // Truncate to leave room for the null terminator.
+    bytes.truncate(len as usize - 1);
+    bytes.push(b'\0');
+    env.mem
+        .bytes_at_mut(name, bytes.len() as GuestUSize)
+        .copy_from_slice(&bytes);
+    0 // success
+}
+
+fn pthread_detach(env: &mut Environment, thread: pthread_t) -> i32 {
+    let Some(host_obj) = State::get(env).threads.get_mut(&thread) else {
+        log!(
+            "Warning: pthread_detach({:?}) on unknown thread, returning ESRCH",
+            thread
+        );
+        return ESRCH;
+    };
+    // Detaching a thread that is already detached, or that another thread is
+    // waiting for, isn't allowed.
+    if host_obj.attr.detachstate == PTHREAD_CREATE_DETACHED || host_obj.joined_by.is_some() {
+        log_dbg!("pthread_detach({:?}) => EINVAL", thread);
+        return EINVAL;
+    }
+    host_obj.attr.detachstate = PTHREAD_CREATE_DETACHED;
+    log_dbg!("pthread_detach({:?}) => 0 (success)", thread);
+    0 // success
+}
+
+fn pthread_exit(env: &mut Environment, value_ptr: MutVoidPtr) {
+    log_dbg!("pthread_exit({:?})", value_ptr);
+    env.exit_current_thread(value_ptr);
+}
+
 fn pthread_setcanceltype(_env: &mut Environment, _type: i32, _oldtype: MutPtr<i32>) -> i32 {
     // TODO
     0
 }
 
+fn pthread_setschedparam(
+    env: &mut Environment,
+    thread: pthread_t,
+    policy: SchedPolicy,
+    param: ConstPtr<sched_param>,
+) -> i32 {
+    let Some(host_object) = State::get(env).threads.get(&thread) else {
+        return ESRCH;
+    };
+    let thread_id = host_object.thread_id;
+    let sched_priority = env.mem.read(param).sched_priority;
+    if !(SCHED_PRIORITY_MIN..=SCHED_PRIORITY_MAX).contains(&sched_priority) {
+        return EINVAL;
+    }
+    log_dbg!(
+        "pthread_setschedparam({:?}, {}, {}): thread {} priority set",
+        thread,
+        policy,
+        sched_priority,
+        thread_id
+    );
+    env.threads[thread_id].priority = priority_from_sched(sched_priority);
+    0 // success
+}
+fn pthread_getschedparam(
+    env: &mut Environment,
+    thread: pthread_t,
+    policy: MutPtr<SchedPolicy>,
+    param: MutPtr<sched_param>,
+) -> i32 {
+    let Some(host_object) = State::get(env).threads.get(&thread) else {
+        return ESRCH;
+    };
+    let thread_id = host_object.thread_id;
+    let sched_priority = priority_to_sched(env.threads[thread_id].priority);
+    if !policy.is_null() {
+        env.mem.write(policy, SCHED_OTHER);
+    }
+    if !param.is_null() {
+        env.mem.write(
+            param,
+            sched_param {
+                sched_priority,
+                _opaque: [0; 4],
+            },
+        );
+    }
+    0 // success
+}
+
 type mach_port_t = u32;
 
 /// Undocumented Darwin function that returns a `mach_port_t`, which in practice
@@ -234,10 +465,20 @@ fn pthread_mach_thread_np(env: &mut Environment, thread: pthread_t) -> mach_port
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(pthread_attr_init(_)),
     export_c_func!(pthread_attr_setdetachstate(_, _)),
+    export_c_func!(pthread_attr_setstacksize(_, _)),
+    export_c_func!(pthread_attr_getstacksize(_, _)),
+    export_c_func!(pthread_attr_setschedparam(_, _)),
+    export_c_func!(pthread_attr_getschedparam(_, _)),
     export_c_func!(pthread_attr_destroy(_)),
     export_c_func!(pthread_create(_, _, _, _)),
     export_c_func!(pthread_self()),
     export_c_func!(pthread_join(_, _)),
+    export_c_func!(pthread_detach(_)),
+    export_c_func!(pthread_exit(_)),
+    export_c_func!(pthread_setname_np(_)),
+    export_c_func!(pthread_getname_np(_, _, _)),
     export_c_func!(pthread_setcanceltype(_, _)),
+    export_c_func!(pthread_setschedparam(_, _, _)),
+    export_c_func!(pthread_getschedparam(_, _, _)),
     export_c_func!(pthread_mach_thread_np(_)),
 ];
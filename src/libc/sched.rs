@@ -5,6 +5,7 @@
  */
 //! `sched.h`.
 
+use super::pthread::thread::{SCHED_PRIORITY_MAX, SCHED_PRIORITY_MIN};
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::Environment;
 
@@ -16,4 +17,16 @@ fn sched_yield(env: &mut Environment) -> i32 {
     0 // success
 }
 
-pub const FUNCTIONS: FunctionExports = &[export_c_func!(sched_yield())];
+fn sched_get_priority_min(_env: &mut Environment, _policy: i32) -> i32 {
+    SCHED_PRIORITY_MIN
+}
+
+fn sched_get_priority_max(_env: &mut Environment, _policy: i32) -> i32 {
+    SCHED_PRIORITY_MAX
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(sched_yield()),
+    export_c_func!(sched_get_priority_min(_)),
+    export_c_func!(sched_get_priority_max(_)),
+];
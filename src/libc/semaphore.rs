@@ -83,6 +83,28 @@ fn sem_open(
     sem
 }
 
+/// Create an unnamed semaphore with no corresponding `sem_t` visible to the
+/// guest. This is used to implement other semaphore APIs (e.g. Mach's).
+pub fn create_unnamed_semaphore(env: &mut Environment, value: i32) -> MutPtr<sem_t> {
+    let sem = env.mem.alloc_and_write(0);
+    let host_sem_rc = Rc::new(RefCell::new(SemaphoreHostObject {
+        value,
+        waiting: HashSet::new(),
+        guest_sem: Some(sem),
+    }));
+    State::get_mut(env).open_semaphores.insert(sem, host_sem_rc);
+    sem
+}
+
+/// Destroy a semaphore created with [create_unnamed_semaphore].
+pub fn destroy_unnamed_semaphore(env: &mut Environment, sem: MutPtr<sem_t>) {
+    let host_sem_rc = State::get_mut(env).open_semaphores.remove(&sem).unwrap();
+    // Any waiting threads must have been woken up by the caller, see
+    // [Environment::sem_interrupt_waiters].
+    assert!((*host_sem_rc).borrow().waiting.is_empty());
+    env.mem.free(sem.cast());
+}
+
 fn sem_post(env: &mut Environment, sem: MutPtr<sem_t>) -> i32 {
     env.sem_increment(sem);
     0 // success
@@ -5,6 +5,7 @@
  */
 //! `stdio.h`
 
+use super::errno::{set_errno, ECHILD, EINVAL, ENOENT};
 use super::posix_io::{
     self, off_t, O_APPEND, O_CREAT, O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY, STDERR_FILENO,
     STDIN_FILENO, STDOUT_FILENO,
@@ -14,7 +15,7 @@ use crate::fs::GuestPath;
 use crate::libc::string::strlen;
 use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, Mem, MutPtr, MutVoidPtr, Ptr, SafeRead};
 use crate::Environment;
-use std::io::Write;
+use std::collections::HashSet;
 
 // Standard C functions
 
@@ -22,6 +23,12 @@ pub mod printf;
 
 const EOF: i32 = -1;
 
+#[derive(Default)]
+pub struct State {
+    /// Streams opened by [popen], which must be closed by [pclose].
+    popen_streams: HashSet<MutPtr<FILE>>,
+}
+
 #[allow(clippy::upper_case_acronyms)]
 /// C `FILE` struct. This is an opaque type in C, so the definition here is our
 /// own.
@@ -167,33 +174,14 @@ fn fwrite(
 
     let total_size = item_size.checked_mul(n_items).unwrap();
 
-    // TODO: Refactor, use traits instead of this hack
-    match fd {
-        STDOUT_FILENO => {
-            let buffer_slice = env.mem.bytes_at(buffer.cast(), total_size);
-            match std::io::stdout().write(buffer_slice) {
-                Ok(bytes_written) => (bytes_written / (item_size as usize)) as GuestUSize,
-                Err(_err) => 0,
-            }
-        }
-        STDERR_FILENO => {
-            let buffer_slice = env.mem.bytes_at(buffer.cast(), total_size);
-            match std::io::stderr().write(buffer_slice) {
-                Ok(bytes_written) => (bytes_written / (item_size as usize)) as GuestUSize,
-                Err(_err) => 0,
-            }
-        }
-        _ => {
-            // The comment about the item_size/n_items split in fread() applies
-            // here too.
-            match posix_io::write(env, fd, buffer, total_size) {
-                // TODO: ferror() support.
-                -1 => 0,
-                bytes_written => {
-                    let bytes_written: GuestUSize = bytes_written.try_into().unwrap();
-                    bytes_written / item_size
-                }
-            }
+    // The comment about the item_size/n_items split in fread() applies here
+    // too. write() takes care of stdout and stderr, including redirections.
+    match posix_io::write(env, fd, buffer, total_size) {
+        // TODO: ferror() support.
+        -1 => 0,
+        bytes_written => {
+            let bytes_written: GuestUSize = bytes_written.try_into().unwrap();
+            bytes_written / item_size
         }
     }
 }
@@ -261,15 +249,15 @@ fn feof(env: &mut Environment, file_ptr: MutPtr<FILE>) -> i32 {
 }
 
 fn puts(env: &mut Environment, s: ConstPtr<u8>) -> i32 {
-    let _ = std::io::stdout().write_all(env.mem.cstr_at(s));
-    let _ = std::io::stdout().write_all(b"\n");
+    let _ = posix_io::write_to_host_stdio(env.window.as_mut(), STDOUT_FILENO, env.mem.cstr_at(s));
+    let _ = posix_io::write_to_host_stdio(env.window.as_mut(), STDOUT_FILENO, b"\n");
     // TODO: I/O error handling
     // TODO: is this the return value iPhone OS uses?
     0
 }
 
-fn putchar(_env: &mut Environment, c: u8) -> i32 {
-    let _ = std::io::stdout().write(std::slice::from_ref(&c));
+fn putchar(env: &mut Environment, c: u8) -> i32 {
+    let _ = posix_io::write_to_host_stdio(env.window.as_mut(), STDOUT_FILENO, &[c]);
     0
 }
 
@@ -311,6 +299,85 @@ fn fileno(env: &mut Environment, file_ptr: MutPtr<FILE>) -> posix_io::FileDescri
     fd
 }
 
+/// Host implementations of shell commands for [popen], since there's no shell
+/// to run them. Each gets the command's arguments and returns its output.
+/// Users can provide the output of other commands with `--popen-output=`.
+const POPEN_COMMANDS: &[(&str, fn(&[&str]) -> Vec<u8>)] = &[
+    ("echo", |args| format!("{}\n", args.join(" ")).into_bytes()),
+    ("true", |_| Vec::new()),
+];
+
+fn popen(env: &mut Environment, command: ConstPtr<u8>, mode: ConstPtr<u8>) -> MutPtr<FILE> {
+    let (Ok(command_str), Ok(mode_str)) = (
+        env.mem.cstr_at_utf8(command).map(str::to_owned),
+        env.mem.cstr_at_utf8(mode).map(str::to_owned),
+    ) else {
+        log!("Warning: popen() with a non-UTF-8 command or mode, returning NULL");
+        set_errno(env, EINVAL);
+        return Ptr::null();
+    };
+    if mode_str != "r" {
+        log!(
+            "TODO: popen({:?}, {:?}), only reading is supported, returning NULL",
+            command_str,
+            mode_str
+        );
+        set_errno(env, EINVAL);
+        return Ptr::null();
+    }
+
+    let words: Vec<&str> = command_str.split_ascii_whitespace().collect();
+    let program = words.first().copied().unwrap_or("");
+    let output = if let Some(path) = env.options.popen_outputs.get(program) {
+        match std::fs::read(path) {
+            Ok(output) => output,
+            Err(e) => {
+                log!(
+                    "Warning: popen({:?}, {:?}): couldn't read output file {:?}: {}, returning NULL",
+                    command_str,
+                    mode_str,
+                    path,
+                    e
+                );
+                set_errno(env, ENOENT);
+                return Ptr::null();
+            }
+        }
+    } else if let Some(&(_, handler)) = POPEN_COMMANDS.iter().find(|&&(name, _)| name == program) {
+        handler(&words[1..])
+    } else {
+        log!(
+            "Warning: popen({:?}, {:?}) with unsupported command, returning NULL",
+            command_str,
+            mode_str
+        );
+        set_errno(env, ENOENT);
+        return Ptr::null();
+    };
+
+    let fd = posix_io::open_pipe_with_contents(env, &output);
+    let file_ptr = env.mem.alloc_and_write(FILE { fd });
+    env.libc_state.stdio.popen_streams.insert(file_ptr);
+    log_dbg!(
+        "popen({:?}, {:?}) => {:?} ({} bytes of output)",
+        command_str,
+        mode_str,
+        file_ptr,
+        output.len()
+    );
+    file_ptr
+}
+
+fn pclose(env: &mut Environment, stream: MutPtr<FILE>) -> i32 {
+    if !env.libc_state.stdio.popen_streams.remove(&stream) {
+        set_errno(env, ECHILD);
+        return -1;
+    }
+    fclose(env, stream);
+    // The commands always succeed, so the exit status is 0.
+    0
+}
+
 pub const CONSTANTS: ConstantExports = &[
     (
         "___stdinp",
@@ -355,4 +422,6 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(setbuf(_, _)),
     // POSIX-specific functions
     export_c_func!(fileno(_)),
+    export_c_func!(popen(_, _)),
+    export_c_func!(pclose(_)),
 ];
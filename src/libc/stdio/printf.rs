@@ -9,7 +9,7 @@ use crate::abi::{DotDotDot, VaList};
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::frameworks::foundation::{ns_string, unichar};
 use crate::libc::clocale::{setlocale, LC_CTYPE};
-use crate::libc::posix_io::{STDERR_FILENO, STDOUT_FILENO};
+use crate::libc::posix_io::{write_to_host_stdio, STDERR_FILENO, STDOUT_FILENO};
 use crate::libc::stdio::FILE;
 use crate::libc::stdlib::atoi_inner;
 use crate::libc::wchar::wchar_t;
@@ -17,7 +17,6 @@ use crate::mem::{ConstPtr, GuestUSize, Mem, MutPtr, MutVoidPtr, Ptr};
 use crate::objc::{id, msg, nil};
 use crate::Environment;
 use std::collections::HashSet;
-use std::io::Write;
 
 const INTEGER_SPECIFIERS: [u8; 6] = [b'd', b'i', b'o', b'u', b'x', b'X'];
 const FLOAT_SPECIFIERS: [u8; 3] = [b'f', b'e', b'g'];
@@ -358,7 +357,7 @@ fn vprintf(env: &mut Environment, format: ConstPtr<u8>, arg: VaList) -> i32 {
 
     let res = printf_inner::<false, _>(env, |mem, idx| mem.read(format + idx), arg);
     // TODO: I/O error handling
-    let _ = std::io::stdout().write_all(&res);
+    let _ = write_to_host_stdio(env.window.as_mut(), STDOUT_FILENO, &res);
     res.len().try_into().unwrap()
 }
 
@@ -486,7 +485,7 @@ fn printf(env: &mut Environment, format: ConstPtr<u8>, args: DotDotDot) -> i32 {
 
     let res = printf_inner::<false, _>(env, |mem, idx| mem.read(format + idx), args.start());
     // TODO: I/O error handling
-    let _ = std::io::stdout().write_all(&res);
+    let _ = write_to_host_stdio(env.window.as_mut(), STDOUT_FILENO, &res);
     res.len().try_into().unwrap()
 }
 
@@ -646,8 +645,9 @@ fn fprintf(
     let res = printf_inner::<false, _>(env, |mem, idx| mem.read(format + idx), args.start());
     // TODO: I/O error handling
     match env.mem.read(stream).fd {
-        STDOUT_FILENO => _ = std::io::stdout().write_all(&res),
-        STDERR_FILENO => _ = std::io::stderr().write_all(&res),
+        fd @ (STDOUT_FILENO | STDERR_FILENO) => {
+            _ = write_to_host_stdio(env.window.as_mut(), fd, &res);
+        }
         _ => unimplemented!(),
     }
     res.len().try_into().unwrap()
@@ -12,6 +12,7 @@ use crate::libc::clocale::{setlocale, LC_CTYPE};
 use crate::libc::string::strlen;
 use crate::libc::wchar::wchar_t;
 use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, MutPtr, MutVoidPtr, Ptr};
+use crate::random::xorshift32;
 use crate::Environment;
 use std::collections::HashMap;
 use std::str::FromStr;
@@ -22,7 +23,6 @@ pub mod qsort;
 pub struct State {
     rand: u32,
     random: u32,
-    arc4random: u32,
     env: HashMap<Vec<u8>, MutPtr<u8>>,
 }
 
@@ -30,20 +30,38 @@ pub struct State {
 // an allocation for any of these, so presumably iPhone OS does too.
 // (touchHLE's allocator will round up allocations to at least 16 bytes.)
 
+/// With `--track-allocations`, remember the caller of an allocation function
+/// so that heap usage can be attributed to it. See
+/// [crate::Environment::memory_usage_report].
+fn track_allocation(env: &mut Environment, ptr: MutVoidPtr) {
+    if env.options.track_allocations {
+        let site = env.cpu.regs()[crate::cpu::Cpu::LR];
+        env.mem.set_allocation_site(ptr, site);
+    }
+}
+
 fn malloc(env: &mut Environment, size: GuestUSize) -> MutVoidPtr {
-    env.mem.alloc(size)
+    let ptr = env.mem.alloc(size);
+    track_allocation(env, ptr);
+    ptr
 }
 
 fn calloc(env: &mut Environment, count: GuestUSize, size: GuestUSize) -> MutVoidPtr {
     let total = size.checked_mul(count).unwrap();
-    env.mem.alloc(total)
+    let ptr = env.mem.alloc(total);
+    track_allocation(env, ptr);
+    ptr
 }
 
 fn realloc(env: &mut Environment, ptr: MutVoidPtr, size: GuestUSize) -> MutVoidPtr {
     if ptr.is_null() {
         return malloc(env, size);
     }
-    env.mem.realloc(ptr, size)
+    let new_ptr = env.mem.realloc(ptr, size);
+    if new_ptr != ptr {
+        track_allocation(env, new_ptr);
+    }
+    new_ptr
 }
 
 fn free(env: &mut Environment, ptr: MutVoidPtr) {
@@ -101,19 +119,6 @@ fn strtod(env: &mut Environment, nptr: ConstPtr<u8>, endptr: MutPtr<MutPtr<u8>>)
     res
 }
 
-fn prng(state: u32) -> u32 {
-    // The state must not be zero for this algorithm to work. This also makes
-    // the default seed be 1, which matches the C standard.
-    let mut state: u32 = state.max(1);
-    // https://en.wikipedia.org/wiki/Xorshift#Example_implementation
-    // xorshift32 is not a good random number generator, but it is cute one!
-    // It's not like anyone expects the C stdlib `rand()` to be good.
-    state ^= state << 13;
-    state ^= state >> 17;
-    state ^= state << 5;
-    state
-}
-
 const RAND_MAX: i32 = i32::MAX;
 const ULONG_MAX: u32 = u32::MAX;
 
@@ -121,7 +126,7 @@ fn srand(env: &mut Environment, seed: u32) {
     env.libc_state.stdlib.rand = seed;
 }
 fn rand(env: &mut Environment) -> i32 {
-    env.libc_state.stdlib.rand = prng(env.libc_state.stdlib.rand);
+    env.libc_state.stdlib.rand = xorshift32(env.libc_state.stdlib.rand);
     (env.libc_state.stdlib.rand as i32) & RAND_MAX
 }
 
@@ -131,13 +136,31 @@ fn srandom(env: &mut Environment, seed: u32) {
     env.libc_state.stdlib.random = seed;
 }
 fn random(env: &mut Environment) -> i32 {
-    env.libc_state.stdlib.random = prng(env.libc_state.stdlib.random);
+    env.libc_state.stdlib.random = xorshift32(env.libc_state.stdlib.random);
     (env.libc_state.stdlib.random as i32) & RAND_MAX
 }
 
+// arc4random() has no seed, so unlike the above, it uses the environment's
+// random number generator (see crate::random).
 fn arc4random(env: &mut Environment) -> u32 {
-    env.libc_state.stdlib.arc4random = prng(env.libc_state.stdlib.arc4random);
-    env.libc_state.stdlib.arc4random
+    env.rng.next_u32()
+}
+fn arc4random_uniform(env: &mut Environment, upper_bound: u32) -> u32 {
+    if upper_bound < 2 {
+        return 0;
+    }
+    // Reject values that would make the result biased, like the real one.
+    let min = upper_bound.wrapping_neg() % upper_bound;
+    loop {
+        let value = env.rng.next_u32();
+        if value >= min {
+            return value % upper_bound;
+        }
+    }
+}
+fn arc4random_buf(env: &mut Environment, buf: MutVoidPtr, size: GuestUSize) {
+    let bytes = env.mem.bytes_at_mut(buf.cast(), size);
+    env.rng.fill_bytes(bytes);
 }
 
 fn getenv(env: &mut Environment, name: ConstPtr<u8>) -> MutPtr<u8> {
@@ -183,8 +206,11 @@ fn setenv(env: &mut Environment, name: ConstPtr<u8>, value: ConstPtr<u8>, overwr
     0 // success
 }
 
-fn exit(_env: &mut Environment, exit_code: i32) {
+fn exit(env: &mut Environment, exit_code: i32) {
     echo!("App called exit(), exiting.");
+    if let Some(report) = crate::objc::retain_audit_report(env) {
+        echo!("{}", report.trim_end());
+    }
     std::process::exit(exit_code);
 }
 
@@ -337,6 +363,8 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(srandom(_)),
     export_c_func!(random()),
     export_c_func!(arc4random()),
+    export_c_func!(arc4random_uniform(_)),
+    export_c_func!(arc4random_buf(_, _)),
     export_c_func!(getenv(_)),
     export_c_func!(setenv(_, _, _)),
     export_c_func!(exit(_)),
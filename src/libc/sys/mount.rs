@@ -0,0 +1,89 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `sys/mount.h`
+//!
+//! The disk space reported here is what `--disk-space=` says, minus whatever
+//! the app has written since (see [crate::fs::DiskQuota]).
+
+use crate::dyld::FunctionExports;
+use crate::environment::Environment;
+use crate::export_c_func;
+use crate::fs::GuestPath;
+use crate::libc::errno::{set_errno, ENOENT};
+use crate::mem::{ConstPtr, MutPtr, SafeRead};
+
+/// Block size of the simulated file system.
+pub const BLOCK_SIZE: u32 = 4096;
+
+/// `struct statfs`, in the layout used with 64-bit inode numbers.
+#[allow(non_camel_case_types)]
+#[repr(C, packed)]
+struct statfs {
+    f_bsize: u32,
+    f_iosize: i32,
+    f_blocks: u64,
+    f_bfree: u64,
+    f_bavail: u64,
+    f_files: u64,
+    f_ffree: u64,
+    f_fsid: [i32; 2],
+    f_owner: u32,
+    f_type: u32,
+    f_flags: u32,
+    f_fssubtype: u32,
+    f_fstypename: [u8; 16],
+    f_mntonname: [u8; 1024],
+    f_mntfromname: [u8; 1024],
+    f_reserved: [u32; 8],
+}
+unsafe impl SafeRead for statfs {}
+
+/// Copy a string into a fixed-size, null-terminated C string field.
+fn c_string_field<const N: usize>(string: &str) -> [u8; N] {
+    let mut field = [0; N];
+    field[..string.len()].copy_from_slice(string.as_bytes());
+    field
+}
+
+fn statfs(env: &mut Environment, path: ConstPtr<u8>, buf: MutPtr<statfs>) -> i32 {
+    let path_str = env.mem.cstr_at_utf8(path).unwrap();
+    if !env.fs.exists(GuestPath::new(path_str)) {
+        log!("Warning: statfs({:?}) for non-existent path", path_str);
+        set_errno(env, ENOENT);
+        return -1;
+    }
+
+    let free = env.fs.free_space();
+    let (_, total) = env.options.disk_space;
+    let free_blocks = free / u64::from(BLOCK_SIZE);
+    let total_blocks = total / u64::from(BLOCK_SIZE);
+    // The app's files are on the user data partition of an iPhone OS device.
+    env.mem.write(
+        buf,
+        statfs {
+            f_bsize: BLOCK_SIZE,
+            f_iosize: BLOCK_SIZE.try_into().unwrap(),
+            f_blocks: total_blocks,
+            f_bfree: free_blocks,
+            f_bavail: free_blocks,
+            f_files: total_blocks,
+            f_ffree: free_blocks,
+            f_fsid: [0; 2],
+            f_owner: 0,
+            f_type: 0x11, // HFS+
+            f_flags: 0,
+            f_fssubtype: 0,
+            f_fstypename: c_string_field("hfs"),
+            f_mntonname: c_string_field("/private/var"),
+            f_mntfromname: c_string_field("/dev/disk0s2"),
+            f_reserved: [0; 8],
+        },
+    );
+    log_dbg!("statfs({:?}, {:?}) => 0", path, buf);
+    0 // success
+}
+
+pub const FUNCTIONS: FunctionExports = &[export_c_func!(statfs(_, _))];
@@ -132,23 +132,23 @@ const fn calc_month_to_day(leap_year: bool) -> [i32; 12] {
     }
     table
 }
-pub fn timestamp_to_calendar_date(timestamp: time_t) -> tm {
-    let seconds_since_unix_epoch: i32 = timestamp;
-
-    // The easy bit: seconds, minutes, hours and days don't vary in length in
-    // UNIX time.
-
-    let days_since_unix_epoch = seconds_since_unix_epoch.div_euclid(DAY_SECONDS);
-    let second_in_day = seconds_since_unix_epoch.rem_euclid(DAY_SECONDS);
-
-    const MINUTE_SECONDS: i32 = 60;
-    const HOUR_SECONDS: i32 = MINUTE_SECONDS * 60;
-    const DAY_SECONDS: i32 = HOUR_SECONDS * 24;
-    let tm_sec = second_in_day % MINUTE_SECONDS;
-    let tm_min = (second_in_day % HOUR_SECONDS) / MINUTE_SECONDS;
-    let tm_hour = second_in_day / HOUR_SECONDS;
+/// A date in the (proleptic) Gregorian calendar. See [days_to_calendar_date].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CalendarDate {
+    pub year: i32,
+    /// Month of the year (**from 0**)
+    pub month: i32,
+    /// Day of the month (**from 0**)
+    pub day: i32,
+    /// Day of the week (where Sunday is the first day, 0)
+    pub weekday: i32,
+    /// Day of the year (from 0)
+    pub day_of_year: i32,
+}
 
-    // The hard bit: months and hence years vary in length.
+/// Convert a number of days relative to 1970-01-01 to a calendar date.
+pub fn days_to_calendar_date(days_since_unix_epoch: i32) -> CalendarDate {
+    // Months and hence years vary in length.
 
     // UNIX time starts on 1970-01-01. The pattern of leap and non-leap years
     // in the Gregorian calendar resets when the year is a multiple of 400, e.g.
@@ -175,15 +175,71 @@ pub fn timestamp_to_calendar_date(timestamp: time_t) -> tm {
     // 0 = Sunday, 1970-01-01 was a Thursday
     let day_of_the_week = (4 + days_since_unix_epoch).rem_euclid(7);
 
+    CalendarDate {
+        year,
+        month: month_in_year,
+        day: day_in_month,
+        weekday: day_of_the_week,
+        day_of_year: day_in_year,
+    }
+}
+
+/// Inverse of [days_to_calendar_date]. Like `mktime()`, a month or day outside
+/// the usual range is normalized, e.g. month 12 is January of the next year,
+/// and day -1 is the last day of the previous month.
+pub fn calendar_date_to_days(year: i32, month: i32, day: i32) -> i32 {
+    let year = year + month.div_euclid(12);
+    let month = month.rem_euclid(12);
+
+    let cycles_since_y2k = (year - 2000).div_euclid(CYCLE_YEARS);
+    let year_in_cycle = (year - 2000).rem_euclid(CYCLE_YEARS);
+    let month_to_day = if is_leap_year(year_in_cycle) {
+        &MONTH_TO_DAY_LEAP
+    } else {
+        &MONTH_TO_DAY_NONLEAP
+    };
+
+    10957
+        + cycles_since_y2k * CYCLE_DAYS
+        + YEAR_TO_DAY[year_in_cycle as usize]
+        + month_to_day[month as usize]
+        + day
+}
+
+pub fn timestamp_to_calendar_date(timestamp: time_t) -> tm {
+    let seconds_since_unix_epoch: i32 = timestamp;
+
+    // The easy bit: seconds, minutes, hours and days don't vary in length in
+    // UNIX time.
+
+    let days_since_unix_epoch = seconds_since_unix_epoch.div_euclid(DAY_SECONDS);
+    let second_in_day = seconds_since_unix_epoch.rem_euclid(DAY_SECONDS);
+
+    const MINUTE_SECONDS: i32 = 60;
+    const HOUR_SECONDS: i32 = MINUTE_SECONDS * 60;
+    const DAY_SECONDS: i32 = HOUR_SECONDS * 24;
+    let tm_sec = second_in_day % MINUTE_SECONDS;
+    let tm_min = (second_in_day % HOUR_SECONDS) / MINUTE_SECONDS;
+    let tm_hour = second_in_day / HOUR_SECONDS;
+
+    // The hard bit is separate.
+    let CalendarDate {
+        year,
+        month,
+        day,
+        weekday,
+        day_of_year,
+    } = days_to_calendar_date(days_since_unix_epoch);
+
     tm {
         tm_sec,
         tm_min,
         tm_hour,
-        tm_mday: day_in_month + 1,
-        tm_mon: month_in_year,
+        tm_mday: day + 1,
+        tm_mon: month,
         tm_year: year - 1900,
-        tm_wday: day_of_the_week,
-        tm_yday: day_in_year,
+        tm_wday: weekday,
+        tm_yday: day_of_year,
         // This function always returns UTC
         tm_isdst: 0,
         tm_gmtoff: 0,
@@ -244,6 +300,19 @@ fn test_timestamp_to_calendar_date() {
     do_test("Fri, 2005-05-27T19:45:47", 1117223147);
     do_test("Sat, 1955-03-26T20:47:45", -466053135);
 }
+#[cfg(test)]
+#[test]
+fn test_calendar_date_to_days() {
+    for days in [-200000, -1, 0, 1, 10957, 11016, 11017, 20000, 200000] {
+        let CalendarDate {
+            year, month, day, ..
+        } = days_to_calendar_date(days);
+        assert_eq!(calendar_date_to_days(year, month, day), days);
+    }
+    // Normalization of out-of-range values
+    assert_eq!(calendar_date_to_days(1999, 12, 0), 10957); // 2000-01-01
+    assert_eq!(calendar_date_to_days(2000, 2, -1), 11016); // 2000-02-29
+}
 
 fn gmtime_r(env: &mut Environment, timestamp: ConstPtr<time_t>, res: MutPtr<tm>) -> MutPtr<tm> {
     let timestamp = env.mem.read(timestamp);
@@ -3,7 +3,19 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-//! Logging and terminal output macros.
+//! Logging and terminal output macros, and log files.
+//!
+//! Log messages are sorted into categories ([LogCategory]) based on the module
+//! they come from, and each category has a level ([LogLevel]) that determines
+//! which messages are printed. The levels can be set with the `TOUCHHLE_LOG`
+//! environment variable, the `--log-level=` option, or the `monitor log`
+//! command in GDB (see [crate::gdb]), using the syntax of [apply_level_spec].
+
+use std::fs::File;
+use std::io::Write;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
 
 /// Accessing log output on Android is more difficult than on other platforms;
 /// logcat requires a separate device. As an alternative, let's write to a file
@@ -27,23 +39,58 @@ pub fn get_log_file() -> &'static std::fs::File {
     unsafe { LOG_FILE.as_ref().unwrap() }
 }
 
-/// Prints a log message unconditionally. Use this for errors or warnings.
+/// Prints a log message, unless logging is turned off for the category of the
+/// module where it is used. Use this for errors or warnings.
 ///
 /// The message is prefixed with the module path, so it is clear where it comes
 /// from.
 macro_rules! log {
     ($($arg:tt)+) => {
-        echo!("{}: {}", module_path!(), format_args!($($arg)+));
+        {
+            const CATEGORY: $crate::log::LogCategory =
+                $crate::log::LogCategory::for_module(module_path!());
+            if $crate::log::is_enabled(CATEGORY, $crate::log::LogLevel::Warn) {
+                echo!("{}: {}", module_path!(), format_args!($($arg)+));
+            }
+        }
     }
 }
 
 /// Like [log], but prints the message only if debugging is enabled for the
-/// module where it is used. This can be used for verbose things only needed
-/// when debugging.
+/// module where it is used, either by the level of its category or by
+/// [ENABLED_MODULES]. This can be used for verbose things only needed when
+/// debugging.
 macro_rules! log_dbg {
     ($($arg:tt)+) => {
-        if $crate::log::ENABLED_MODULES.contains(&module_path!()) {
-            log!($($arg)*);
+        {
+            const CATEGORY: $crate::log::LogCategory =
+                $crate::log::LogCategory::for_module(module_path!());
+            if $crate::log::is_enabled(CATEGORY, $crate::log::LogLevel::Debug)
+                || $crate::log::ENABLED_MODULES.contains(&module_path!())
+            {
+                echo!("{}: {}", module_path!(), format_args!($($arg)+));
+            }
+        }
+    }
+}
+
+/// Like [log], but for warnings that could be printed very often. At most
+/// [RATE_LIMIT_MESSAGES] messages from the same place in the code are printed
+/// within [RATE_LIMIT_WINDOW], and the next message after that says how many
+/// were suppressed.
+macro_rules! log_rate_limited {
+    ($($arg:tt)+) => {
+        {
+            static LIMITER: $crate::log::RateLimiter = $crate::log::RateLimiter::new();
+            match LIMITER.check() {
+                Some(0) => log!($($arg)+),
+                Some(suppressed) => log!(
+                    "{} ({} similar messages were suppressed)",
+                    format_args!($($arg)+),
+                    suppressed
+                ),
+                None => (),
+            }
         }
     }
 }
@@ -55,9 +102,9 @@ macro_rules! log_dbg {
 macro_rules! echo {
     ($($arg:tt)+) => {
         {
+            let formatted_str = format!($($arg)+);
             #[cfg(target_os = "android")]
             {
-                let formatted_str = format!($($arg)+);
                 sdl2::log::log(&formatted_str);
                 use std::io::Write;
                 let mut log_file = $crate::log::get_log_file();
@@ -65,7 +112,8 @@ macro_rules! echo {
                 let _ = log_file.write_all(b"\n");
             }
             #[cfg(not(target_os = "android"))]
-            eprintln!($($arg)+);
+            eprintln!("{}", formatted_str);
+            $crate::log::write_to_session_log(&formatted_str);
         }
     };
     () => {
@@ -78,10 +126,446 @@ macro_rules! echo {
             }
             #[cfg(not(target_os = "android"))]
             eprintln!("");
+            $crate::log::write_to_session_log("");
         }
     }
 }
 
 /// Put modules to enable [log_dbg] for here, e.g. "touchHLE::mem" to see when
-/// memory is allocated and freed.
+/// memory is allocated and freed. This is more fine-grained than setting the
+/// level of a whole [LogCategory].
 pub const ENABLED_MODULES: &[&str] = &[];
+
+/// Broad area of touchHLE that a log message comes from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LogCategory {
+    /// Anything not covered by another category: CPU, memory, dynamic linking,
+    /// threads, etc.
+    Core,
+    /// OpenGL ES, both the framework and touchHLE's implementations of it.
+    Gles,
+    /// Objective-C runtime.
+    Objc,
+    Audio,
+    /// Filesystem and file I/O.
+    Fs,
+    /// Networking.
+    Net,
+    /// Windowing, input, UIKit, Core Animation and Core Graphics.
+    Ui,
+    /// Frameworks not covered by another category.
+    Frameworks,
+    /// C standard library and POSIX functions not covered by another category.
+    Libc,
+}
+
+/// Module path prefixes for each category. More specific prefixes must come
+/// before less specific ones.
+const CATEGORY_PREFIXES: &[(&str, LogCategory)] = &[
+    ("touchHLE::gles", LogCategory::Gles),
+    ("touchHLE::frameworks::opengles", LogCategory::Gles),
+    ("touchHLE::objc", LogCategory::Objc),
+    ("touchHLE::audio", LogCategory::Audio),
+    ("touchHLE::frameworks::audio_toolbox", LogCategory::Audio),
+    ("touchHLE::frameworks::av_audio", LogCategory::Audio),
+    ("touchHLE::frameworks::core_audio_types", LogCategory::Audio),
+    ("touchHLE::frameworks::media_player", LogCategory::Audio),
+    ("touchHLE::frameworks::openal", LogCategory::Audio),
+    ("touchHLE::fs", LogCategory::Fs),
+    ("touchHLE::libc::dirent", LogCategory::Fs),
+    ("touchHLE::libc::posix_io", LogCategory::Fs),
+    ("touchHLE::libc::stdio", LogCategory::Fs),
+    (
+        "touchHLE::frameworks::foundation::ns_file_handle",
+        LogCategory::Fs,
+    ),
+    (
+        "touchHLE::frameworks::foundation::ns_file_manager",
+        LogCategory::Fs,
+    ),
+    ("touchHLE::libc::ifaddrs", LogCategory::Net),
+    ("touchHLE::libc::net", LogCategory::Net),
+    ("touchHLE::frameworks::dnssd", LogCategory::Net),
+    ("touchHLE::frameworks::core_telephony", LogCategory::Net),
+    (
+        "touchHLE::frameworks::system_configuration",
+        LogCategory::Net,
+    ),
+    ("touchHLE::frameworks::foundation::ns_url", LogCategory::Net),
+    (
+        "touchHLE::frameworks::foundation::ns_url_request",
+        LogCategory::Net,
+    ),
+    ("touchHLE::window", LogCategory::Ui),
+    ("touchHLE::frameworks::core_animation", LogCategory::Ui),
+    ("touchHLE::frameworks::core_graphics", LogCategory::Ui),
+    ("touchHLE::frameworks::uikit", LogCategory::Ui),
+    ("touchHLE::frameworks", LogCategory::Frameworks),
+    ("touchHLE::libc", LogCategory::Libc),
+];
+
+/// Whether `module_path` is `prefix` or one of its submodules.
+const fn module_has_prefix(module_path: &[u8], prefix: &[u8]) -> bool {
+    if module_path.len() < prefix.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < prefix.len() {
+        if module_path[i] != prefix[i] {
+            return false;
+        }
+        i += 1;
+    }
+    module_path.len() == prefix.len() || module_path[prefix.len()] == b':'
+}
+
+impl LogCategory {
+    pub const ALL: &'static [Self] = &[
+        Self::Core,
+        Self::Gles,
+        Self::Objc,
+        Self::Audio,
+        Self::Fs,
+        Self::Net,
+        Self::Ui,
+        Self::Frameworks,
+        Self::Libc,
+    ];
+
+    /// Get the category for a module path (from `module_path!()`). This is a
+    /// `const fn` so that the logging macros don't have to do this at runtime.
+    pub const fn for_module(module_path: &str) -> Self {
+        let mut i = 0;
+        while i < CATEGORY_PREFIXES.len() {
+            let (prefix, category) = CATEGORY_PREFIXES[i];
+            if module_has_prefix(module_path.as_bytes(), prefix.as_bytes()) {
+                return category;
+            }
+            i += 1;
+        }
+        Self::Core
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Core => "core",
+            Self::Gles => "gles",
+            Self::Objc => "objc",
+            Self::Audio => "audio",
+            Self::Fs => "fs",
+            Self::Net => "net",
+            Self::Ui => "ui",
+            Self::Frameworks => "frameworks",
+            Self::Libc => "libc",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|c| c.name() == name)
+    }
+}
+
+/// Which messages are printed for a [LogCategory].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    /// Nothing is printed.
+    Off = 0,
+    /// Messages from [log] are printed. This is the default.
+    Warn = 1,
+    /// Messages from both [log] and [log_dbg] are printed.
+    Debug = 2,
+}
+
+impl LogLevel {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Warn => "warn",
+            Self::Debug => "debug",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Off, Self::Warn, Self::Debug]
+            .into_iter()
+            .find(|l| l.name() == name)
+    }
+}
+
+/// Current level of each category, indexed by [LogCategory] discriminant.
+static LEVELS: [AtomicU8; LogCategory::ALL.len()] = [
+    AtomicU8::new(LogLevel::Warn as u8),
+    AtomicU8::new(LogLevel::Warn as u8),
+    AtomicU8::new(LogLevel::Warn as u8),
+    AtomicU8::new(LogLevel::Warn as u8),
+    AtomicU8::new(LogLevel::Warn as u8),
+    AtomicU8::new(LogLevel::Warn as u8),
+    AtomicU8::new(LogLevel::Warn as u8),
+    AtomicU8::new(LogLevel::Warn as u8),
+    AtomicU8::new(LogLevel::Warn as u8),
+];
+
+/// Only for internal use by the logging macros.
+pub fn is_enabled(category: LogCategory, level: LogLevel) -> bool {
+    LEVELS[category as usize].load(Ordering::Relaxed) >= level as u8
+}
+
+pub fn set_level(category: LogCategory, level: LogLevel) {
+    LEVELS[category as usize].store(level as u8, Ordering::Relaxed);
+}
+
+pub fn get_level(category: LogCategory) -> LogLevel {
+    match LEVELS[category as usize].load(Ordering::Relaxed) {
+        0 => LogLevel::Off,
+        1 => LogLevel::Warn,
+        _ => LogLevel::Debug,
+    }
+}
+
+/// Parse a log level specification: a comma-separated list of either
+/// `category=level`, which sets the level of one category, or just `level`,
+/// which sets the level of all categories. Later items take precedence, e.g.
+/// `off,gles=debug` turns off all logging except for OpenGL ES, which gets
+/// debug logging.
+///
+/// This is separate from [apply_level_spec] so options can be validated
+/// without applying them.
+pub fn parse_level_spec(spec: &str) -> Result<Vec<(Option<LogCategory>, LogLevel)>, String> {
+    spec.split(',')
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            let (category, level) = match item.split_once('=') {
+                Some((category, level)) => {
+                    let category = LogCategory::from_name(category)
+                        .ok_or_else(|| format!("Unknown log category {:?}", category))?;
+                    (Some(category), level)
+                }
+                None => (None, item),
+            };
+            let level = LogLevel::from_name(level)
+                .ok_or_else(|| format!("Unknown log level {:?}", level))?;
+            Ok((category, level))
+        })
+        .collect()
+}
+
+/// Parse and apply a log level specification (see [parse_level_spec]).
+pub fn apply_level_spec(spec: &str) -> Result<(), String> {
+    for (category, level) in parse_level_spec(spec)? {
+        match category {
+            Some(category) => set_level(category, level),
+            None => {
+                for &category in LogCategory::ALL {
+                    set_level(category, level);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Describe the current levels in the syntax of [apply_level_spec].
+pub fn describe_levels() -> String {
+    LogCategory::ALL
+        .iter()
+        .map(|&category| format!("{}={}", category.name(), get_level(category).name()))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// See [log_rate_limited].
+pub const RATE_LIMIT_MESSAGES: u32 = 5;
+/// See [log_rate_limited].
+pub const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(10);
+
+/// Only for internal use by [log_rate_limited].
+pub struct RateLimiter(Mutex<RateLimiterState>);
+
+struct RateLimiterState {
+    window_start: Option<Instant>,
+    count: u32,
+    suppressed: u32,
+}
+
+impl RateLimiter {
+    #[allow(clippy::new_without_default)]
+    pub const fn new() -> Self {
+        RateLimiter(Mutex::new(RateLimiterState {
+            window_start: None,
+            count: 0,
+            suppressed: 0,
+        }))
+    }
+
+    /// Returns [None] if a message should be suppressed, or otherwise the
+    /// number of messages suppressed since the last one that wasn't.
+    pub fn check(&self) -> Option<u32> {
+        let mut state = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        if state
+            .window_start
+            .map_or(true, |start| now.duration_since(start) >= RATE_LIMIT_WINDOW)
+        {
+            state.window_start = Some(now);
+            state.count = 0;
+        }
+        if state.count >= RATE_LIMIT_MESSAGES {
+            state.suppressed += 1;
+            return None;
+        }
+        state.count += 1;
+        Some(std::mem::take(&mut state.suppressed))
+    }
+}
+
+/// How many session log files to keep in [crate::paths::LOGS_DIR]. The oldest
+/// ones are deleted when a new session starts.
+const MAX_SESSION_LOGS: usize = 20;
+
+struct SessionLog {
+    file: File,
+    started_at: Instant,
+}
+
+/// The log file for the current session, if any. See [start_session_log].
+static SESSION_LOG: Mutex<Option<SessionLog>> = Mutex::new(None);
+
+/// Start writing all further output from [echo] (and therefore [log] and
+/// [log_dbg]) to a new file in [crate::paths::LOGS_DIR], so that users have
+/// something to attach when reporting a problem with an app. Each line in the
+/// file is prefixed with the time in seconds since the session started.
+///
+/// The file begins with a header identifying the app and touchHLE version,
+/// and, if touchHLE panics, the reason and a stack trace end up at the end of
+/// it (see [crate::Environment::run]).
+pub fn start_session_log(app_id: &str, app_version: &str, emulator_version: &str) {
+    let dir = crate::paths::user_data_base_path().join(crate::paths::LOGS_DIR);
+    if let Err(e) = std::fs::create_dir_all(&dir) {
+        log!("Warning: Couldn't create {}: {}", dir.display(), e);
+        return;
+    }
+    rotate_session_logs(&dir);
+
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let date = crate::libc::time::timestamp_to_calendar_date(timestamp as _);
+    let date = format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        date.tm_year + 1900,
+        date.tm_mon + 1,
+        date.tm_mday,
+        date.tm_hour,
+        date.tm_min,
+        date.tm_sec
+    );
+    // The name starts with the date so that sorting by name sorts by age.
+    // Characters that aren't allowed in file names on some OSes are avoided.
+    let file_name: String = format!("{}-{}.log", &date[..19], app_id)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let path = dir.join(file_name);
+
+    let mut file = match File::create(&path) {
+        Ok(file) => file,
+        Err(e) => {
+            log!("Warning: Couldn't create {}: {}", path.display(), e);
+            return;
+        }
+    };
+    let _ = writeln!(file, "touchHLE session log");
+    let _ = writeln!(file, "Date: {}", date);
+    let _ = writeln!(file, "App: {} version {}", app_id, app_version);
+    let _ = writeln!(file, "touchHLE version: {}", emulator_version);
+    let _ = writeln!(file, "Host OS: {}", std::env::consts::OS);
+    let _ = writeln!(file);
+
+    *lock_session_log() = Some(SessionLog {
+        file,
+        started_at: Instant::now(),
+    });
+    log!("Writing log for this session to {}", path.display());
+}
+
+/// Delete the oldest session log files so that there's room for a new one.
+fn rotate_session_logs(dir: &std::path::Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut logs: Vec<_> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map_or(false, |ext| ext == "log"))
+        .collect();
+    if logs.len() < MAX_SESSION_LOGS {
+        return;
+    }
+    logs.sort();
+    for path in &logs[..=(logs.len() - MAX_SESSION_LOGS)] {
+        if let Err(e) = std::fs::remove_file(path) {
+            log!("Warning: Couldn't delete {}: {}", path.display(), e);
+        }
+    }
+}
+
+fn lock_session_log() -> std::sync::MutexGuard<'static, Option<SessionLog>> {
+    // This might be used while panicking, so a poisoned mutex is fine.
+    SESSION_LOG.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Only for internal use by the logging macros.
+pub fn write_to_session_log(line: &str) {
+    if let Some(log) = lock_session_log().as_mut() {
+        let _ = writeln!(
+            log.file,
+            "[{:9.3}] {}",
+            log.started_at.elapsed().as_secs_f64(),
+            line
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn category_for_module() {
+        assert_eq!(LogCategory::for_module("touchHLE::gles"), LogCategory::Gles);
+        assert_eq!(
+            LogCategory::for_module("touchHLE::frameworks::opengles::eagl"),
+            LogCategory::Gles
+        );
+        assert_eq!(
+            LogCategory::for_module("touchHLE::frameworks::foundation::ns_url"),
+            LogCategory::Net
+        );
+        assert_eq!(
+            LogCategory::for_module("touchHLE::frameworks::foundation::ns_string"),
+            LogCategory::Frameworks
+        );
+        // Prefixes only match whole module names.
+        assert_eq!(LogCategory::for_module("touchHLE::fsx"), LogCategory::Core);
+        assert_eq!(LogCategory::for_module("touchHLE::mem"), LogCategory::Core);
+    }
+
+    #[test]
+    fn level_spec() {
+        assert_eq!(
+            parse_level_spec("off,gles=debug"),
+            Ok(vec![
+                (None, LogLevel::Off),
+                (Some(LogCategory::Gles), LogLevel::Debug)
+            ])
+        );
+        assert!(parse_level_spec("foo=debug").is_err());
+        assert!(parse_level_spec("gles=loud").is_err());
+    }
+}
@@ -15,6 +15,7 @@
 //! * [Memory Usage Performance Guidelines](https://developer.apple.com/library/archive/documentation/Performance/Conceptual/ManagingMemory/ManagingMemory.html)
 
 use crate::libc::wchar::wchar_t;
+use std::collections::HashMap;
 
 mod allocator;
 
@@ -234,6 +235,10 @@ pub struct Mem {
     null_segment_size: VAddr,
 
     allocator: allocator::Allocator,
+
+    /// The guest code address each live allocation was made from, if
+    /// allocation tracking is enabled (see [Self::set_allocation_site]).
+    allocation_sites: HashMap<VAddr, VAddr>,
 }
 
 impl Drop for Mem {
@@ -271,6 +276,7 @@ impl Mem {
             bytes,
             null_segment_size: 0,
             allocator,
+            allocation_sites: HashMap::new(),
         }
     }
 
@@ -284,7 +290,9 @@ impl Mem {
             bytes: _,
             null_segment_size: _,
             ref mut allocator,
+            ref mut allocation_sites,
         } = mem;
+        allocation_sites.clear();
         let used_chunks = allocator.reset_and_drain_used_chunks();
         for allocator::Chunk { base, size } in used_chunks {
             mem.bytes_mut()[base as usize..][..size.get() as usize].fill(0);
@@ -500,6 +508,9 @@ impl Mem {
 
     /// Free an allocation made with one of the `alloc` methods on this type.
     pub fn free(&mut self, ptr: MutVoidPtr) {
+        if !self.allocation_sites.is_empty() {
+            self.allocation_sites.remove(&ptr.to_bits());
+        }
         let size = self.allocator.free(ptr.to_bits());
         self.bytes_at_mut(ptr.cast(), size).fill(0);
         log_dbg!("Freed {:?} ({:#x} bytes)", ptr, size);
@@ -561,4 +572,38 @@ impl Mem {
     pub fn reserve(&mut self, base: VAddr, size: GuestUSize) {
         self.allocator.reserve(allocator::Chunk::new(base, size));
     }
+
+    /// Get the number of bytes of address space that are in use (allocated,
+    /// reserved, or part of a loaded binary), excluding the null segment. This
+    /// is an approximation of the memory usage of the app.
+    pub fn used_bytes(&self) -> GuestUSize {
+        self.allocator.used_bytes() - self.null_segment_size
+    }
+
+    /// Record which guest code an allocation was made from, identified by
+    /// a return address. This is only done when `--track-allocations` is used.
+    /// The record is forgotten when the allocation is freed.
+    pub fn set_allocation_site(&mut self, ptr: MutVoidPtr, site: VAddr) {
+        self.allocation_sites.insert(ptr.to_bits(), site);
+    }
+
+    /// Tally the live allocations that have a recorded site (see
+    /// [Self::set_allocation_site]) by that site. Each entry is the site, the
+    /// number of allocations, and their total size in bytes. The entries are
+    /// sorted with the largest total size first.
+    pub fn heap_usage_by_site(&self) -> Vec<(VAddr, usize, GuestUSize)> {
+        let mut tallies: HashMap<VAddr, (usize, GuestUSize)> = HashMap::new();
+        for (&ptr, &site) in &self.allocation_sites {
+            let size = self.allocator.find_allocated_size(ptr);
+            let (count, total) = tallies.entry(site).or_default();
+            *count += 1;
+            *total += size;
+        }
+        let mut tallies: Vec<_> = tallies
+            .into_iter()
+            .map(|(site, (count, total))| (site, count, total))
+            .collect();
+        tallies.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        tallies
+    }
 }
@@ -255,6 +255,8 @@ use collections::{ChunkMap, SizeBucketedChunkMap};
 pub struct Allocator {
     used_chunks: ChunkMap,
     unused_chunks: SizeBucketedChunkMap,
+    /// Total size of `used_chunks`, for memory usage statistics.
+    used_bytes: GuestUSize,
 }
 
 impl Allocator {
@@ -272,6 +274,7 @@ impl Allocator {
         Allocator {
             used_chunks,
             unused_chunks,
+            used_bytes: Mem::MAIN_THREAD_STACK_SIZE,
         }
     }
 
@@ -297,6 +300,7 @@ impl Allocator {
             self.unused_chunks.insert(after);
         }
         self.used_chunks.insert(chunk);
+        self.used_bytes += chunk.size.get();
     }
 
     pub fn alloc(&mut self, size: GuestUSize) -> VAddr {
@@ -314,12 +318,13 @@ impl Allocator {
             );
         };
         self.used_chunks.insert(alloc);
+        self.used_bytes += alloc.size.get();
 
         alloc.base
     }
 
     /// This is used for realloc
-    pub fn find_allocated_size(&mut self, base: VAddr) -> GuestUSize {
+    pub fn find_allocated_size(&self, base: VAddr) -> GuestUSize {
         let Some(size) = self.used_chunks.get_size_with_base(base) else {
             panic!("Can't find {:#x}, unknown allocation!", base);
         };
@@ -333,6 +338,7 @@ impl Allocator {
             log!("Can't free {:#x}, unknown allocation!", base);
             return 0;
         };
+        self.used_bytes -= freed.size.get();
 
         if let Some(adjacent) = self
             .unused_chunks
@@ -351,9 +357,43 @@ impl Allocator {
         freed.size.get()
     }
 
+    /// Total size of all allocations and reservations, in bytes.
+    pub fn used_bytes(&self) -> GuestUSize {
+        self.used_bytes
+    }
+
     pub(super) fn reset_and_drain_used_chunks(&mut self) -> impl Iterator<Item = Chunk> {
         let chunks = std::mem::take(&mut self.used_chunks);
         *self = Allocator::new();
         chunks.drain()
     }
 }
+
+#[cfg(test)]
+mod allocator_tests {
+    use super::{Allocator, Chunk, Mem, MIN_CHUNK_SIZE};
+    #[test]
+    fn used_bytes() {
+        let mut allocator = Allocator::new();
+        let initial = allocator.used_bytes();
+        assert_eq!(initial, Mem::MAIN_THREAD_STACK_SIZE);
+
+        allocator.reserve(Chunk::new(0, 0x1000));
+        assert_eq!(allocator.used_bytes(), initial + 0x1000);
+
+        let a = allocator.alloc(1);
+        let b = allocator.alloc(MIN_CHUNK_SIZE * 3);
+        assert_eq!(
+            allocator.used_bytes(),
+            initial + 0x1000 + MIN_CHUNK_SIZE * 4
+        );
+
+        let _ = allocator.free(a);
+        assert_eq!(
+            allocator.used_bytes(),
+            initial + 0x1000 + MIN_CHUNK_SIZE * 3
+        );
+        let _ = allocator.free(b);
+        assert_eq!(allocator.used_bytes(), initial + 0x1000);
+    }
+}
@@ -0,0 +1,236 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Rules for intercepting an app's network requests (`--network-rules=`).
+//!
+//! Many old games won't work without a server that no longer exists. A rules
+//! file lets the user redirect requests for a host to another one, answer them
+//! with a file from disk, or block them, without patching the app.
+//!
+//! The file has one rule per line, and `#` starts a comment:
+//!
+//! ```text
+//! # ACTION   PATTERN                  ARGUMENT
+//! redirect   scores.example.com       revived-scores.example.org
+//! serve      api.example.com/motd     motd.json
+//! block      *.ads.example.net
+//! ```
+//!
+//! A pattern is a host name, optionally starting with `*.` to also match
+//! subdomains, and optionally followed by a path prefix. Files to serve are
+//! relative to the directory containing the rules file. The first rule that
+//! matches a URL wins.
+//!
+//! A served file can have extra response header fields, one `Name: value` per
+//! line, in a file with `.headers` appended to its name (e.g.
+//! `motd.json.headers`). This is how a served response sets cookies.
+
+use std::path::{Path, PathBuf};
+
+/// Redirects can't be followed forever, in case the rules form a loop.
+const MAX_REDIRECTS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq)]
+enum RuleAction {
+    Redirect(String),
+    Serve(PathBuf),
+    Block,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    /// Lowercase host name, without the `*.`.
+    host: String,
+    include_subdomains: bool,
+    path_prefix: String,
+    action: RuleAction,
+}
+impl Rule {
+    fn matches(&self, host: &str, path: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+        let host_matches = host == self.host
+            || (self.include_subdomains
+                && host
+                    .strip_suffix(&self.host)
+                    .is_some_and(|subdomain| subdomain.ends_with('.')));
+        host_matches && path.starts_with(&self.path_prefix)
+    }
+}
+
+/// What should happen to a request after applying the rules.
+#[derive(Debug, PartialEq)]
+pub enum Resolution {
+    /// No rule says what to do, so the request goes to the network as usual.
+    Unhandled,
+    /// Respond with the contents of a host file.
+    Serve(PathBuf),
+    Block,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NetworkRules {
+    rules: Vec<Rule>,
+}
+impl NetworkRules {
+    pub fn from_file(path: &Path) -> Result<NetworkRules, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read network rules file {:?}: {}", path, e))?;
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        Self::parse(&text, base_dir)
+            .map_err(|e| format!("Error in network rules file {:?}: {}", path, e))
+    }
+
+    fn parse(text: &str, base_dir: &Path) -> Result<NetworkRules, String> {
+        let mut rules = Vec::new();
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.split_once('#').map_or(line, |(line, _comment)| line);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let (action, pattern, argument) = match words[..] {
+                [] => continue,
+                [action, pattern] => (action, pattern, None),
+                [action, pattern, argument] => (action, pattern, Some(argument)),
+                _ => return Err(format!("Line {}: Too many words", line_number + 1)),
+            };
+            let action = match (action, argument) {
+                ("redirect", Some(host)) => RuleAction::Redirect(host.to_string()),
+                ("serve", Some(file)) => RuleAction::Serve(base_dir.join(file)),
+                ("block", None) => RuleAction::Block,
+                ("redirect" | "serve", None) => {
+                    return Err(format!("Line {}: Missing argument", line_number + 1))
+                }
+                ("block", Some(_)) => {
+                    return Err(format!("Line {}: Unexpected argument", line_number + 1))
+                }
+                _ => {
+                    return Err(format!(
+                        "Line {}: Unknown action {:?}",
+                        line_number + 1,
+                        action
+                    ))
+                }
+            };
+            let (host, path_prefix) = match pattern.find('/') {
+                Some(slash) => pattern.split_at(slash),
+                None => (pattern, ""),
+            };
+            let (host, include_subdomains) = match host.strip_prefix("*.") {
+                Some(host) => (host, true),
+                None => (host, false),
+            };
+            if host.is_empty() {
+                return Err(format!("Line {}: Missing host name", line_number + 1));
+            }
+            rules.push(Rule {
+                host: host.to_ascii_lowercase(),
+                include_subdomains,
+                path_prefix: path_prefix.to_string(),
+                action,
+            });
+        }
+        Ok(NetworkRules { rules })
+    }
+
+    /// Apply the rules to an absolute URL, following any redirects. Returns the
+    /// final URL and what to do with it.
+    pub fn resolve(&self, url: &str) -> (String, Resolution) {
+        let mut url = url.to_string();
+        for _ in 0..=MAX_REDIRECTS {
+            let Some((host_range, path)) = split_url(&url) else {
+                return (url, Resolution::Unhandled);
+            };
+            let host = &url[host_range.clone()];
+            let Some(rule) = self.rules.iter().find(|rule| rule.matches(host, path)) else {
+                return (url, Resolution::Unhandled);
+            };
+            match &rule.action {
+                RuleAction::Redirect(new_host) => {
+                    log_dbg!("Redirecting {:?} to host {:?}", url, new_host);
+                    url.replace_range(host_range, new_host);
+                }
+                RuleAction::Serve(file) => return (url, Resolution::Serve(file.clone())),
+                RuleAction::Block => return (url, Resolution::Block),
+            }
+        }
+        log!("Warning: Too many redirects for {:?}, blocking it", url);
+        (url, Resolution::Block)
+    }
+}
+
+/// Find the host name and path in an absolute URL.
+fn split_url(url: &str) -> Option<(std::ops::Range<usize>, &str)> {
+    let authority_start = url.find("://")? + 3;
+    let rest = &url[authority_start..];
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let authority = &rest[..authority_end];
+    // Skip any user name and password, and leave out the port.
+    let host_start = authority.rfind('@').map_or(0, |at| at + 1);
+    let host_end = authority[host_start..]
+        .find(':')
+        .map_or(authority.len(), |colon| host_start + colon);
+    let path = &rest[authority_end..];
+    let path = &path[..path.find(['?', '#']).unwrap_or(path.len())];
+    Some((
+        (authority_start + host_start)..(authority_start + host_end),
+        path,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve() {
+        let rules = NetworkRules::parse(
+            "# comment\n\
+             redirect old.example.com new.example.org\n\
+             serve new.example.org/motd motd.json # inline comment\n\
+             \n\
+             block *.ads.example.net\n",
+            Path::new("rules"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            rules.resolve("http://old.example.com:8080/motd?lang=en"),
+            (
+                "http://new.example.org:8080/motd?lang=en".to_string(),
+                Resolution::Serve(PathBuf::from("rules/motd.json"))
+            )
+        );
+        assert_eq!(
+            rules.resolve("http://old.example.com/scores"),
+            (
+                "http://new.example.org/scores".to_string(),
+                Resolution::Unhandled
+            )
+        );
+        assert_eq!(
+            rules.resolve("https://cdn.ads.example.net/banner.png").1,
+            Resolution::Block
+        );
+        assert_eq!(
+            rules.resolve("https://notads.example.net/").1,
+            Resolution::Unhandled
+        );
+    }
+
+    #[test]
+    fn redirect_loop() {
+        let rules = NetworkRules::parse(
+            "redirect a.example.com b.example.com\nredirect b.example.com a.example.com\n",
+            Path::new(""),
+        )
+        .unwrap();
+        assert_eq!(rules.resolve("http://a.example.com/").1, Resolution::Block);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(NetworkRules::parse("serve example.com\n", Path::new("")).is_err());
+        assert!(NetworkRules::parse("block example.com x\n", Path::new("")).is_err());
+        assert!(NetworkRules::parse("allow example.com\n", Path::new("")).is_err());
+    }
+}
@@ -20,17 +20,20 @@
 
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::MutexId;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 mod classes;
+mod heap_snapshot;
 mod messages;
 mod methods;
 mod objects;
 mod properties;
+mod retain_audit;
 mod selectors;
 mod synchronization;
 
 pub use classes::{objc_classes, Class, ClassExports, ClassTemplate};
+pub use heap_snapshot::HeapSnapshot;
 pub use messages::{
     autorelease, msg, msg_class, msg_send, msg_send_super2, msg_super, objc_super, release, retain,
 };
@@ -38,6 +41,9 @@ pub use methods::{HostIMP, IMP};
 pub use objects::{
     id, impl_HostObject_with_superclass, nil, AnyHostObject, HostObject, TrivialHostObject,
 };
+pub use retain_audit::{
+    record_retain_release, release_autoreleased, retain_audit_report, RetainReleaseOperation,
+};
 pub use selectors::{selector, SEL};
 
 use classes::{ClassHostObject, FakeClass, UnimplementedClass, CLASS_LISTS};
@@ -78,6 +84,14 @@ pub struct ObjC {
     /// Type information isn't part of the `objc_msgSend` ABI, so an alternative
     /// channel is needed.
     message_type_info: Option<(std::any::TypeId, &'static str)>,
+
+    /// Methods that the main thread checker (`--main-thread-checker`) has
+    /// already warned about.
+    main_thread_checker_warnings: HashSet<(Class, SEL)>,
+
+    /// State of the retain/release audit (`--audit-retain-release`), if it is
+    /// enabled.
+    retain_audit: Option<retain_audit::RetainAudit>,
 }
 
 impl ObjC {
@@ -88,6 +102,8 @@ impl ObjC {
             classes: HashMap::new(),
             sync_mutexes: HashMap::new(),
             message_type_info: None,
+            main_thread_checker_warnings: HashSet::new(),
+            retain_audit: None,
         }
     }
 }
@@ -7,23 +7,28 @@
 //! very long and frequently-updated list.
 
 use crate::frameworks::{
-    av_audio, core_animation, core_foundation, core_graphics, foundation, media_player, opengles,
-    store_kit, uikit,
+    av_audio, core_animation, core_foundation, core_graphics, core_telephony, foundation,
+    media_player, opengles, store_kit, system_configuration, uikit,
 };
 
 /// All the lists of classes that the runtime should search through.
 pub const CLASS_LISTS: &[super::ClassExports] = &[
     crate::app_picker::CLASSES, // Not a framework! Special internal classes.
+    core_animation::ca_display_link::CLASSES,
     core_animation::ca_eagl_layer::CLASSES,
     core_animation::ca_layer::CLASSES,
     core_graphics::cg_data_provider::CLASSES,
     core_graphics::cg_color_space::CLASSES,
     core_graphics::cg_context::CLASSES,
     core_graphics::cg_image::CLASSES,
+    core_graphics::cg_pdf_document::CLASSES,
+    core_graphics::cg_pdf_page::CLASSES,
     core_foundation::cf_run_loop_timer::CLASSES, // Special internal classes.
+    core_telephony::CLASSES,
     foundation::ns_array::CLASSES,
     foundation::ns_autorelease_pool::CLASSES,
     foundation::ns_bundle::CLASSES,
+    foundation::ns_calendar::CLASSES,
     foundation::ns_character_set::CLASSES,
     foundation::ns_coder::CLASSES,
     foundation::ns_data::CLASSES,
@@ -32,8 +37,10 @@ pub const CLASS_LISTS: &[super::ClassExports] = &[
     foundation::ns_dictionary::CLASSES,
     foundation::ns_enumerator::CLASSES,
     foundation::ns_error::CLASSES,
+    foundation::ns_exception::CLASSES,
     foundation::ns_file_handle::CLASSES,
     foundation::ns_file_manager::CLASSES,
+    foundation::ns_http_cookie::CLASSES,
     foundation::ns_keyed_unarchiver::CLASSES,
     foundation::ns_locale::CLASSES,
     foundation::ns_lock::CLASSES,
@@ -46,8 +53,12 @@ pub const CLASS_LISTS: &[super::ClassExports] = &[
     foundation::ns_set::CLASSES,
     foundation::ns_string::CLASSES,
     foundation::ns_thread::CLASSES,
+    foundation::ns_time_zone::CLASSES,
     foundation::ns_timer::CLASSES,
     foundation::ns_url::CLASSES,
+    foundation::ns_url_cache::CLASSES,
+    foundation::ns_url_connection::CLASSES,
+    foundation::ns_url_request::CLASSES,
     foundation::ns_user_defaults::CLASSES,
     foundation::ns_value::CLASSES,
     av_audio::av_audio_player::CLASSES,
@@ -56,6 +67,7 @@ pub const CLASS_LISTS: &[super::ClassExports] = &[
     media_player::media_query::CLASSES,
     opengles::eagl::CLASSES,
     store_kit::sk_product::CLASSES,
+    system_configuration::CLASSES,
     uikit::ui_accelerometer::CLASSES,
     uikit::ui_activity_indicator_view::CLASSES,
     uikit::ui_application::CLASSES,
@@ -66,6 +78,7 @@ pub const CLASS_LISTS: &[super::ClassExports] = &[
     uikit::ui_image::CLASSES,
     uikit::ui_image_picker_controller::CLASSES,
     uikit::ui_nib::CLASSES,
+    uikit::ui_pasteboard::CLASSES,
     uikit::ui_responder::CLASSES,
     uikit::ui_screen::CLASSES,
     uikit::ui_touch::CLASSES,
@@ -76,6 +89,7 @@ pub const CLASS_LISTS: &[super::ClassExports] = &[
     uikit::ui_view::ui_control::ui_text_field::CLASSES,
     uikit::ui_view::ui_image_view::CLASSES,
     uikit::ui_view::ui_label::CLASSES,
+    uikit::ui_view::ui_web_view::CLASSES,
     uikit::ui_view::ui_window::CLASSES,
     uikit::ui_view_controller::CLASSES,
 ];
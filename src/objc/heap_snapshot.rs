@@ -0,0 +1,220 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Heap snapshots: dumps of the graph of live Objective-C objects, for hunting
+//! leaks and retain cycles (see `--heap-snapshot-key=` in `OPTIONS_HELP.txt`).
+//!
+//! Each node is a live reference-counted object, with its class and retain
+//! count. The edges come from two places:
+//!
+//! - Host objects that report the objects they have retained (see
+//!   [super::HostObject::strong_references]). This is how references held by
+//!   collections and touchHLE's own classes are found.
+//! - The object's instance variables in guest memory. These are scanned
+//!   conservatively: any word that happens to be the address of a live object
+//!   is treated as a reference, and there's no way to tell strong and weak
+//!   references apart.
+//!
+//! A cycle of host references almost always means touchHLE has a retain/release
+//! bug. Cycles involving instance variables might be the app's own (possibly
+//! intentional) doing.
+
+use super::{id, AnyHostObject, ClassHostObject, ObjC};
+use crate::mem::{guest_size_of, ConstPtr, GuestUSize, Mem};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum ReferenceKind {
+    Host,
+    Ivar,
+}
+impl ReferenceKind {
+    fn name(self) -> &'static str {
+        match self {
+            ReferenceKind::Host => "host",
+            ReferenceKind::Ivar => "ivar",
+        }
+    }
+}
+
+struct Node {
+    object: id,
+    class: String,
+    retain_count: u32,
+    references: Vec<(id, ReferenceKind)>,
+}
+
+pub struct HeapSnapshot {
+    nodes: Vec<Node>,
+}
+
+impl ObjC {
+    /// Take a snapshot of the graph of live reference-counted objects.
+    pub fn heap_snapshot(&self, mem: &Mem) -> HeapSnapshot {
+        let is_live = |object: id| {
+            self.objects
+                .get(&object)
+                .is_some_and(|entry| entry.refcount.is_some())
+        };
+
+        let mut nodes = Vec::new();
+        for (&object, entry) in &self.objects {
+            let Some(retain_count) = entry.refcount else {
+                continue;
+            };
+            let isa = Self::read_isa(object, mem);
+
+            let mut references = Vec::new();
+
+            let mut host_references = Vec::new();
+            let mut host_object: &(dyn AnyHostObject + 'static) = &*entry.host_object;
+            loop {
+                host_object.strong_references(&mut host_references);
+                let Some(next) = host_object.as_superclass() else {
+                    break;
+                };
+                host_object = next;
+            }
+            for reference in host_references {
+                if is_live(reference) && !references.contains(&(reference, ReferenceKind::Host)) {
+                    references.push((reference, ReferenceKind::Host));
+                }
+            }
+
+            let instance_size = self
+                .get_host_object(isa)
+                .and_then(|class| class.as_any().downcast_ref::<ClassHostObject>())
+                .map_or(0, |class| class.instance_size);
+            let words: ConstPtr<GuestUSize> = object.cast().cast_const();
+            let word_count = instance_size / guest_size_of::<GuestUSize>();
+            // The first word is the isa, which is a class, not a live object.
+            for i in 1..word_count {
+                let reference: id = crate::mem::Ptr::from_bits(mem.read(words + i));
+                if reference != object
+                    && is_live(reference)
+                    && !references.iter().any(|&(other, _)| other == reference)
+                {
+                    references.push((reference, ReferenceKind::Ivar));
+                }
+            }
+
+            nodes.push(Node {
+                object,
+                class: self.get_class_name(isa).to_string(),
+                retain_count: retain_count.get(),
+                references,
+            });
+        }
+        nodes.sort_by_key(|node| node.object.to_bits());
+        HeapSnapshot { nodes }
+    }
+}
+
+/// Escape a string for use inside quotes in JSON or GraphViz, which have
+/// compatible rules for the characters that can be in a class name.
+fn escape(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+    for c in string.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_control() => write!(escaped, "\\u{:04x}", c as u32).unwrap(),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl HeapSnapshot {
+    pub fn object_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Produce a JSON document with an array of objects, each of which has its
+    /// address, class, retain count and references.
+    pub fn to_json(&self) -> String {
+        let mut json = String::from("{\"objects\":[\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            write!(
+                json,
+                "{{\"address\":\"{:#x}\",\"class\":\"{}\",\"retain_count\":{},\"references\":[",
+                node.object.to_bits(),
+                escape(&node.class),
+                node.retain_count
+            )
+            .unwrap();
+            for (j, &(reference, kind)) in node.references.iter().enumerate() {
+                write!(
+                    json,
+                    "{}{{\"address\":\"{:#x}\",\"kind\":\"{}\"}}",
+                    if j == 0 { "" } else { "," },
+                    reference.to_bits(),
+                    kind.name()
+                )
+                .unwrap();
+            }
+            let separator = if i + 1 == self.nodes.len() { "" } else { "," };
+            writeln!(json, "]}}{}", separator).unwrap();
+        }
+        json.push_str("]}\n");
+        json
+    }
+
+    /// Produce a GraphViz graph. Host references are drawn as solid lines and
+    /// instance variable references as dashed lines.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph heap {\n    node [shape=box];\n");
+        for node in &self.nodes {
+            writeln!(
+                dot,
+                "    \"{:#x}\" [label=\"{} {:#x}\\nretain count: {}\"];",
+                node.object.to_bits(),
+                escape(&node.class),
+                node.object.to_bits(),
+                node.retain_count
+            )
+            .unwrap();
+            for &(reference, kind) in &node.references {
+                writeln!(
+                    dot,
+                    "    \"{:#x}\" -> \"{:#x}\"{};",
+                    node.object.to_bits(),
+                    reference.to_bits(),
+                    if kind == ReferenceKind::Ivar {
+                        " [style=dashed]"
+                    } else {
+                        ""
+                    }
+                )
+                .unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Save the snapshot in `dir` as both a JSON file and a GraphViz file.
+    /// Returns the path of the JSON file. The GraphViz file has the same name,
+    /// but with the `.dot` extension.
+    pub fn write_files(&self, dir: &Path) -> Result<PathBuf, String> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Couldn't create {}: {}", dir.display(), e))?;
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let json_path = dir.join(format!("touchHLE-{}.json", timestamp));
+        let dot_path = json_path.with_extension("dot");
+        for (path, contents) in [(&json_path, self.to_json()), (&dot_path, self.to_dot())] {
+            std::fs::write(path, contents)
+                .map_err(|e| format!("Couldn't write {}: {}", path.display(), e))?;
+        }
+        Ok(json_path)
+    }
+}
@@ -33,6 +33,13 @@ use std::any::TypeId;
 fn objc_msgSend_inner(env: &mut Environment, receiver: id, selector: SEL, super2: Option<Class>) {
     let message_type_info = env.objc.message_type_info.take();
 
+    if let Some(audit) = env.objc.retain_audit.as_mut() {
+        audit.current_host_message = audit
+            .next_host_site
+            .take()
+            .map(|location| (receiver, location));
+    }
+
     if receiver == nil {
         // https://developer.apple.com/library/archive/documentation/Cocoa/Conceptual/ObjectiveC/Chapters/ocObjectsClasses.html#//apple_ref/doc/uid/TP30001163-CH11-SW7
         log_dbg!("[nil {}]", selector.as_str(&env.mem));
@@ -76,6 +83,8 @@ fn objc_msgSend_inner(env: &mut Environment, receiver: id, selector: SEL, super2
         let host_object = env.objc.get_host_object(class).unwrap();
 
         if let Some(&super::ClassHostObject {
+            ref name,
+            is_metaclass,
             superclass,
             ref methods,
             ..
@@ -112,6 +121,22 @@ Type mismatch when sending message {} to {:?}!
                                 );
                             }
                         }
+                        // Only messages sent by the app are interesting to
+                        // the checker. Those sent by the host have type info.
+                        if message_type_info.is_none()
+                            && env.options.main_thread_checker
+                            && env.current_thread != 0
+                            && is_main_thread_only_class(name)
+                        {
+                            let class_name = name.clone();
+                            main_thread_checker_warning(
+                                env,
+                                receiver,
+                                class,
+                                (&class_name, is_metaclass),
+                                selector,
+                            );
+                        }
                         host_imp.call_from_guest(env)
                     }
                     // We can't create a new stack frame, because that would
@@ -157,6 +182,46 @@ Type mismatch when sending message {} to {:?}!
     }
 }
 
+/// Classes that UIKit and Core Animation document as being safe to use from
+/// any thread.
+const THREAD_SAFE_CLASSES: &[&str] = &["UIColor", "UIFont", "UIImage"];
+
+/// Whether the host implementation of a class should only be used on the main
+/// thread, for the purposes of the `--main-thread-checker` option.
+fn is_main_thread_only_class(class_name: &str) -> bool {
+    (class_name.starts_with("UI") || class_name.starts_with("CA"))
+        && !THREAD_SAFE_CLASSES.contains(&class_name)
+}
+
+/// Report a UIKit or Core Animation method being called from a secondary
+/// thread. Only the first call to each method is reported, because apps that
+/// do this often do it every frame.
+#[cold]
+fn main_thread_checker_warning(
+    env: &mut Environment,
+    receiver: id,
+    class: Class,
+    (class_name, is_metaclass): (&str, bool),
+    selector: SEL,
+) {
+    if !env
+        .objc
+        .main_thread_checker_warnings
+        .insert((class, selector))
+    {
+        return;
+    }
+    log!(
+        "Warning: Main thread checker: {}[{} {}] called on thread {} (receiver: {:?}). UIKit and Core Animation should only be used from the main thread. Further calls to this method will not be reported.",
+        if is_metaclass { "+" } else { "-" },
+        class_name,
+        selector.as_str(&env.mem),
+        env.current_thread,
+        receiver,
+    );
+    env.stack_trace();
+}
+
 /// Standard variant of `objc_msgSend`. See [objc_msgSend_inner].
 #[allow(non_snake_case)]
 pub(super) fn objc_msgSend(env: &mut Environment, receiver: id, selector: SEL) {
@@ -239,6 +304,7 @@ pub trait MsgSendSignature: 'static {
 /// Wrapper around [objc_msgSend] which, together with [msg], makes it easy to
 /// send messages in host code. Warning: all types are inferred from the
 /// call-site and they may not be checked, so be very sure you get them correct!
+#[track_caller]
 pub fn msg_send<R, P>(env: &mut Environment, args: P) -> R
 where
     fn(&mut Environment, id, SEL): CallFromHost<R, P>,
@@ -248,6 +314,9 @@ where
 {
     // Provide type info for dynamic type checking.
     env.objc.message_type_info = Some(<(R, P) as MsgSendSignature>::type_info());
+    if let Some(audit) = env.objc.retain_audit.as_mut() {
+        audit.next_host_site = Some(std::panic::Location::caller());
+    }
     if R::SIZE_IN_MEM.is_some() {
         (objc_msgSend_stret as fn(&mut Environment, MutVoidPtr, id, SEL)).call_from_host(env, args)
     } else {
@@ -263,6 +332,7 @@ pub trait MsgSendSuperSignature: 'static {
 
 /// [msg_send] but for super-calls (calls [objc_msgSendSuper2]). You probably
 /// want to use [msg_super] rather than calling this directly.
+#[track_caller]
 pub fn msg_send_super2<R, P>(env: &mut Environment, args: P) -> R
 where
     fn(&mut Environment, ConstPtr<objc_super>, SEL): CallFromHost<R, P>,
@@ -272,6 +342,9 @@ where
 {
     // Provide type info for dynamic type checking.
     env.objc.message_type_info = Some(<(R, P) as MsgSendSuperSignature>::WithoutSuper::type_info());
+    if let Some(audit) = env.objc.retain_audit.as_mut() {
+        audit.next_host_site = Some(std::panic::Location::caller());
+    }
     if R::SIZE_IN_MEM.is_some() {
         todo!() // no stret yet
     } else {
@@ -403,6 +476,7 @@ macro_rules! msg_class {
 pub use crate::msg_class; // #[macro_export] is weird...
 
 /// Shorthand for `let _: id = msg![env; object retain];`
+#[track_caller]
 pub fn retain(env: &mut Environment, object: id) -> id {
     if object == nil {
         // fast path
@@ -412,6 +486,7 @@ pub fn retain(env: &mut Environment, object: id) -> id {
 }
 
 /// Shorthand for `() = msg![env; object release];`
+#[track_caller]
 pub fn release(env: &mut Environment, object: id) {
     if object == nil {
         // fast path
@@ -421,6 +496,7 @@ pub fn release(env: &mut Environment, object: id) {
 }
 
 /// Shorthand for `let _: id = msg![env; object autorelease];`
+#[track_caller]
 pub fn autorelease(env: &mut Environment, object: id) -> id {
     if object == nil {
         // fast path
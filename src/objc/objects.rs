@@ -23,6 +23,7 @@
 use super::{Class, ClassHostObject};
 use crate::mem::{guest_size_of, GuestUSize, Mem, MutPtr, Ptr, SafeRead};
 use std::any::Any;
+use std::collections::HashMap;
 use std::num::NonZeroU32;
 
 /// Memory layout of a minimal Objective-C object. See [id].
@@ -60,8 +61,8 @@ pub const nil: id = Ptr::null();
 /// If the `refcount` is `None`, that means this object has a static duration
 /// and should not be reference-counted, e.g. it is a class.
 pub(super) struct HostObjectEntry {
-    host_object: Box<dyn AnyHostObject>,
-    refcount: Option<NonZeroU32>,
+    pub(super) host_object: Box<dyn AnyHostObject>,
+    pub(super) refcount: Option<NonZeroU32>,
 }
 
 /// Type for host objects.
@@ -85,6 +86,14 @@ pub trait HostObject: Any + 'static {
     fn as_superclass_mut<'a>(&'a mut self) -> Option<&'a mut (dyn AnyHostObject + 'static)> {
         None
     }
+    /// Add the objects this host object holds strong references to (i.e. has
+    /// retained) to `references`. This is only used for heap snapshots (see
+    /// [super::heap_snapshot]), so implementing it is optional, but it's
+    /// useful for types that can end up in retain cycles, like collections.
+    ///
+    /// The superclass's host object is visited separately, so only this
+    /// type's own fields need to be covered.
+    fn strong_references(&self, _references: &mut Vec<id>) {}
 }
 
 /// Convenience macro for implementing [HostObject] where the host object type
@@ -174,6 +183,9 @@ impl super::ObjC {
                 refcount,
             },
         );
+        if let (Some(audit), Some(_)) = (self.retain_audit.as_mut(), refcount) {
+            audit.object_allocated(ptr);
+        }
         ptr
     }
 
@@ -232,6 +244,24 @@ impl super::ObjC {
         );
     }
 
+    /// Count the live reference-counted objects of each class, for memory
+    /// usage reports. Static-lifetime objects, like classes, aren't counted.
+    /// The most numerous classes come first.
+    pub fn count_live_objects_by_class(&self, mem: &Mem) -> Vec<(String, usize)> {
+        let mut counts: HashMap<Class, usize> = HashMap::new();
+        for (&object, entry) in &self.objects {
+            if entry.refcount.is_some() {
+                *counts.entry(Self::read_isa(object, mem)).or_default() += 1;
+            }
+        }
+        let mut counts: Vec<(String, usize)> = counts
+            .into_iter()
+            .map(|(class, count)| (self.get_class_name(class).to_string(), count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
+    }
+
     /// Get a reference to a host object, if the object exists.
     pub(super) fn get_host_object(&self, object: id) -> Option<&dyn AnyHostObject> {
         self.objects.get(&object).map(|entry| &*entry.host_object)
@@ -304,6 +334,9 @@ impl super::ObjC {
     #[must_use]
     pub fn decrement_refcount(&mut self, object: id) -> bool {
         let Some(entry) = self.objects.get_mut(&object) else {
+            if let Some(audit) = self.retain_audit.as_ref() {
+                audit.report_dead_object(object);
+            }
             panic!(
                 "No entry found for object {:?}, it may have already been deallocated",
                 object
@@ -328,6 +361,14 @@ impl super::ObjC {
     /// Deallocate an object. Do not call this directly unless you're
     /// implementing `dealloc` and are sure you don't need to do a super-call.
     pub fn dealloc_object(&mut self, object: id, mem: &mut Mem) {
+        if self.retain_audit.is_some() {
+            let class_name = self.get_class_name(Self::read_isa(object, mem)).to_string();
+            self.retain_audit
+                .as_mut()
+                .unwrap()
+                .object_deallocated(object, class_name);
+        }
+
         let HostObjectEntry {
             host_object,
             refcount,
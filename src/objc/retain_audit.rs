@@ -0,0 +1,244 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Retain/release auditing (`--audit-retain-release`).
+//!
+//! When enabled, every `retain`, `release` and `autorelease` handled by
+//! `NSObject` is recorded per object, along with where it came from. For
+//! messages sent by host code, that's the Rust source location of the [msg]
+//! (or [retain], [release] or [autorelease]) call. For messages sent by the
+//! app, it's the guest return address. Allocation is an implicit `+1` that
+//! isn't recorded.
+//!
+//! This is meant for catching bugs in touchHLE's own classes, which are easy to
+//! get wrong since they're written by hand, but it can also help with finding
+//! the app's leaks. Two kinds of problem are reported:
+//!
+//! - When the app exits, or a memory usage report is made (see
+//!   [crate::Environment::memory_usage_report]), the objects that are still
+//!   alive are summarized by class, along with the call sites that have a net
+//!   imbalance of retains and releases for them. A host call site that retains
+//!   many objects of a class without a matching release is likely to be a leak.
+//! - When an object that has already been deallocated is released again, the
+//!   history of the old object is printed before touchHLE panics, which shows
+//!   where the extra release came from.
+//!
+//! Retains and releases of objects whose class overrides these methods without
+//! calling `super` aren't seen.
+//!
+//! [msg]: super::msg
+//! [retain]: super::retain
+//! [release]: super::release
+//! [autorelease]: super::autorelease
+
+use super::{id, ObjC};
+use crate::Environment;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::panic::Location;
+
+/// Where a retain, release or autorelease came from.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Site {
+    Host(&'static Location<'static>),
+    /// Return address in the app's code.
+    Guest(u32),
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum RetainReleaseOperation {
+    Retain,
+    Release,
+    Autorelease,
+    /// The release that balances an earlier autorelease, when the pool is
+    /// drained. This is not counted again, the autorelease already was.
+    PoolRelease,
+}
+impl RetainReleaseOperation {
+    fn name(self) -> &'static str {
+        match self {
+            RetainReleaseOperation::Retain => "retain",
+            RetainReleaseOperation::Release => "release",
+            RetainReleaseOperation::Autorelease => "autorelease",
+            RetainReleaseOperation::PoolRelease => "autorelease pool drain",
+        }
+    }
+}
+
+/// Number of times each operation happened at each site, for one object.
+type History = HashMap<(Site, RetainReleaseOperation), u32>;
+
+#[derive(Default)]
+pub struct RetainAudit {
+    live: HashMap<id, History>,
+    /// Histories of deallocated objects, with their class names. These are
+    /// kept until the address is reused, so that over-releases can be
+    /// explained.
+    dead: HashMap<id, (String, History)>,
+    /// Set by [super::msg_send] for the message it is about to send.
+    pub(super) next_host_site: Option<&'static Location<'static>>,
+    /// The receiver and host call site of the message currently being sent,
+    /// if it was sent by host code.
+    pub(super) current_host_message: Option<(id, &'static Location<'static>)>,
+    /// Object currently being released by an autorelease pool drain.
+    pool_release: Option<id>,
+}
+
+impl ObjC {
+    pub fn enable_retain_audit(&mut self) {
+        self.retain_audit = Some(Default::default());
+    }
+}
+
+impl RetainAudit {
+    pub(super) fn object_allocated(&mut self, object: id) {
+        self.dead.remove(&object);
+        self.live.insert(object, History::new());
+    }
+
+    pub(super) fn object_deallocated(&mut self, object: id, class_name: String) {
+        let history = self.live.remove(&object).unwrap_or_default();
+        self.dead.insert(object, (class_name, history));
+    }
+
+    /// Print what is known about an object that no longer exists.
+    pub(super) fn report_dead_object(&self, object: id) {
+        let Some((class_name, history)) = self.dead.get(&object) else {
+            echo!(
+                "Retain/release audit: nothing is known about {:?}, it wasn't allocated since auditing started.",
+                object
+            );
+            return;
+        };
+        echo!(
+            "Retain/release audit: history of {:?} ({}) before it was deallocated, after the implicit +1 of allocation:",
+            object,
+            class_name
+        );
+        let mut entries: Vec<_> = history.iter().collect();
+        entries.sort_by_key(|&(_, &count)| std::cmp::Reverse(count));
+        for (&(site, operation), count) in entries {
+            echo!(
+                "  {} x {} at {}",
+                count,
+                operation.name(),
+                describe_site(site, |addr| format!("{:#x}", addr))
+            );
+        }
+    }
+}
+
+/// Record a `retain`, `release` or `autorelease` of an object, if auditing is
+/// enabled. This must be called from within the host implementation of the
+/// method, so that the guest return address is still in the LR.
+pub fn record_retain_release(env: &mut Environment, object: id, operation: RetainReleaseOperation) {
+    let Some(audit) = env.objc.retain_audit.as_mut() else {
+        return;
+    };
+    let operation =
+        if operation == RetainReleaseOperation::Release && audit.pool_release == Some(object) {
+            audit.pool_release = None;
+            RetainReleaseOperation::PoolRelease
+        } else {
+            operation
+        };
+    let site = match audit.current_host_message.take() {
+        Some((receiver, location)) if receiver == object => Site::Host(location),
+        _ => Site::Guest(env.cpu.regs()[crate::cpu::Cpu::LR]),
+    };
+    // Objects allocated before auditing started (e.g. classes) aren't tracked.
+    if let Some(history) = audit.live.get_mut(&object) {
+        *history.entry((site, operation)).or_default() += 1;
+    }
+}
+
+/// Release an object from an autorelease pool that is being drained. See
+/// [RetainReleaseOperation::PoolRelease].
+pub fn release_autoreleased(env: &mut Environment, object: id) {
+    if let Some(audit) = env.objc.retain_audit.as_mut() {
+        audit.pool_release = Some(object);
+    }
+    super::release(env, object);
+}
+
+fn describe_site(site: Site, symbolicate: impl Fn(u32) -> String) -> String {
+    match site {
+        Site::Host(location) => format!("{} (host)", location),
+        Site::Guest(addr) => symbolicate(addr),
+    }
+}
+
+/// Summarize the live objects by class, with the call sites that have a net
+/// imbalance of retains and releases. Returns [None] if auditing isn't enabled.
+pub fn retain_audit_report(env: &Environment) -> Option<String> {
+    let audit = env.objc.retain_audit.as_ref()?;
+
+    struct ClassSummary {
+        objects: usize,
+        sites: HashMap<Site, [u32; 3]>,
+    }
+    let mut classes: HashMap<&str, ClassSummary> = HashMap::new();
+    for (&object, history) in &audit.live {
+        let class = ObjC::read_isa(object, &env.mem);
+        let summary = classes
+            .entry(env.objc.get_class_name(class))
+            .or_insert_with(|| ClassSummary {
+                objects: 0,
+                sites: HashMap::new(),
+            });
+        summary.objects += 1;
+        for (&(site, operation), &count) in history {
+            let counts = summary.sites.entry(site).or_default();
+            match operation {
+                RetainReleaseOperation::Retain => counts[0] += count,
+                RetainReleaseOperation::Release => counts[1] += count,
+                RetainReleaseOperation::Autorelease => counts[2] += count,
+                RetainReleaseOperation::PoolRelease => (),
+            }
+        }
+    }
+    let mut classes: Vec<_> = classes.into_iter().collect();
+    classes.sort_by(|a, b| b.1.objects.cmp(&a.1.objects).then(a.0.cmp(b.0)));
+
+    let mut report = String::new();
+    writeln!(
+        report,
+        "Retain/release audit: {} live objects of {} classes",
+        audit.live.len(),
+        classes.len()
+    )
+    .unwrap();
+    for (class, summary) in classes {
+        writeln!(
+            report,
+            "  {}: {} objects (+1 each from allocation)",
+            class, summary.objects
+        )
+        .unwrap();
+        let mut sites: Vec<_> = summary
+            .sites
+            .into_iter()
+            .map(|(site, counts @ [retains, releases, autoreleases])| {
+                let net = i64::from(retains) - i64::from(releases) - i64::from(autoreleases);
+                (site, counts, net)
+            })
+            .filter(|&(_, _, net)| net != 0)
+            .collect();
+        sites.sort_by_key(|&(_, _, net)| std::cmp::Reverse(net.abs()));
+        for (site, [retains, releases, autoreleases], net) in sites {
+            writeln!(
+                report,
+                "    {:+} at {} ({} retains, {} releases, {} autoreleases)",
+                net,
+                describe_site(site, |addr| env.symbolicate(addr)),
+                retains,
+                releases,
+                autoreleases
+            )
+            .unwrap();
+        }
+    }
+    Some(report)
+}
@@ -6,11 +6,13 @@
 //! Parsing and management of user-configurable options, e.g. for input methods.
 
 use crate::gles::GLESImplementation;
+use crate::network_rules::NetworkRules;
 use crate::window::DeviceOrientation;
 use std::collections::HashMap;
 use std::io::{BufRead, BufReader, Read};
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
 
 pub const OPTIONS_HELP: &str =
     include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/OPTIONS_HELP.txt"));
@@ -29,12 +31,54 @@ pub enum Button {
     Y,
     LeftShoulder,
 }
+impl Button {
+    /// Parse a button name, which is the same as the variant name.
+    pub fn from_name(name: &str) -> Option<Button> {
+        match name {
+            "DPadLeft" => Some(Button::DPadLeft),
+            "DPadUp" => Some(Button::DPadUp),
+            "DPadRight" => Some(Button::DPadRight),
+            "DPadDown" => Some(Button::DPadDown),
+            "Start" => Some(Button::Start),
+            "A" => Some(Button::A),
+            "B" => Some(Button::B),
+            "X" => Some(Button::X),
+            "Y" => Some(Button::Y),
+            "LeftShoulder" => Some(Button::LeftShoulder),
+            _ => None,
+        }
+    }
+}
+
+/// Camera hardware reported to the app, for the `--camera=` option.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct CameraFeatures {
+    pub rear: bool,
+    pub front: bool,
+    /// Only applies to the rear camera.
+    pub flash: bool,
+    pub video: bool,
+}
+
+/// Network connection reported to the app, for the `--connection-type=`
+/// option.
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub enum ConnectionType {
+    #[default]
+    None,
+    WiFi,
+    /// Cellular data.
+    WWAN,
+}
 
 /// Struct containing all user-configurable options.
 pub struct Options {
     pub fullscreen: bool,
     pub initial_orientation: DeviceOrientation,
     pub scale_hack: NonZeroU32,
+    /// Scale hack factors that override `scale_hack` for `CAEAGLLayer`s with a
+    /// particular size in points.
+    pub layer_scale_hacks: Vec<((u32, u32), NonZeroU32)>,
     pub deadzone: f32,
     pub x_tilt_range: f32,
     pub y_tilt_range: f32,
@@ -42,13 +86,52 @@ pub struct Options {
     pub y_tilt_offset: f32,
     pub button_to_touch: HashMap<Button, (f32, f32)>,
     pub stabilize_virtual_cursor: Option<(f32, f32)>,
+    /// Region of the screen for each player's virtual cursor, as fractions of
+    /// the screen: x, y, width and height.
+    pub controller_regions: Vec<(f32, f32, f32, f32)>,
+    pub controller_rumble: f32,
+    pub controller_led_colors: Vec<(u8, u8, u8)>,
     pub gles1_implementation: Option<GLESImplementation>,
+    pub gles2_implementation: Option<GLESImplementation>,
+    pub gles_error_checking: bool,
+    pub main_thread_checker: bool,
+    pub track_allocations: bool,
+    pub audit_retain_release: bool,
     pub direct_memory_access: bool,
     pub gdb_listen_addrs: Option<Vec<SocketAddr>>,
     pub preferred_languages: Option<Vec<String>>,
+    pub preferred_region: Option<String>,
     pub headless: bool,
     pub print_fps: bool,
+    pub home_button_exits: bool,
+    pub other_audio_playing: bool,
+    pub camera: CameraFeatures,
+    /// Initial battery level and whether it's charging, if not full.
+    pub battery: Option<(f32, bool)>,
+    pub battery_drain: f32,
+    /// Free and total disk space in bytes.
+    pub disk_space: (u64, u64),
+    pub network_rules: NetworkRules,
+    pub connection_type: ConnectionType,
+    /// Files with the output of commands run with `popen()`, keyed by command
+    /// name.
+    pub popen_outputs: HashMap<String, PathBuf>,
+    pub record_screen: Option<PathBuf>,
+    pub screenshot_key: String,
+    pub screenshot_dir: Option<PathBuf>,
+    pub console_key: String,
+    pub memory_report_key: String,
+    pub heap_snapshot_key: String,
+    pub record_input: Option<PathBuf>,
+    pub replay_input: Option<PathBuf>,
     pub fps_limit: Option<f64>,
+    /// Whether `--fps-limit=` was used, in which case `--refresh-rate=`
+    /// doesn't change the framerate limit.
+    fps_limit_set: bool,
+    pub vsync: bool,
+    pub refresh_rate: f64,
+    pub random_seed: Option<u64>,
+    pub log_levels: Option<String>,
 }
 
 impl Default for Options {
@@ -57,6 +140,7 @@ impl Default for Options {
             fullscreen: false,
             initial_orientation: DeviceOrientation::Portrait,
             scale_hack: NonZeroU32::new(1).unwrap(),
+            layer_scale_hacks: Vec::new(),
             deadzone: 0.1,
             x_tilt_range: 60.0,
             y_tilt_range: 60.0,
@@ -64,13 +148,45 @@ impl Default for Options {
             y_tilt_offset: 0.0,
             button_to_touch: HashMap::new(),
             stabilize_virtual_cursor: None,
+            controller_regions: Vec::new(),
+            controller_rumble: 0.5,
+            controller_led_colors: Vec::new(),
             gles1_implementation: None,
+            gles2_implementation: None,
+            gles_error_checking: false,
+            main_thread_checker: false,
+            track_allocations: false,
+            audit_retain_release: false,
             direct_memory_access: true,
             gdb_listen_addrs: None,
             preferred_languages: None,
+            preferred_region: None,
             headless: false,
             print_fps: false,
+            home_button_exits: false,
+            other_audio_playing: false,
+            camera: CameraFeatures::default(),
+            battery: None,
+            battery_drain: 0.0,
+            // Half of a 16GB device.
+            disk_space: (8 << 30, 16 << 30),
+            network_rules: NetworkRules::default(),
+            connection_type: ConnectionType::None,
+            popen_outputs: HashMap::new(),
+            record_screen: None,
+            screenshot_key: "F9".to_string(),
+            screenshot_dir: None,
+            console_key: "F7".to_string(),
+            memory_report_key: "F8".to_string(),
+            heap_snapshot_key: "F6".to_string(),
+            record_input: None,
+            replay_input: None,
             fps_limit: Some(60.0), // Original iPhone is 60Hz and uses v-sync
+            fps_limit_set: false,
+            vsync: false,
+            refresh_rate: 60.0,
+            random_seed: None,
+            log_levels: None,
         }
     }
 }
@@ -100,6 +216,21 @@ impl Options {
             self.scale_hack = value
                 .parse()
                 .map_err(|_| "Invalid scale hack factor".to_string())?;
+        } else if let Some(value) = arg.strip_prefix("--layer-scale-hack=") {
+            let parse = || -> Option<((u32, u32), NonZeroU32)> {
+                let (size, factor) = value.split_once(':')?;
+                let (width, height) = size.split_once('x')?;
+                Some((
+                    (width.parse().ok()?, height.parse().ok()?),
+                    factor.parse().ok()?,
+                ))
+            };
+            let (size, factor) =
+                parse().ok_or_else(|| "Invalid value for --layer-scale-hack=".to_string())?;
+            // A later use of the option for the same size takes precedence.
+            self.layer_scale_hacks
+                .retain(|&(other_size, _)| other_size != size);
+            self.layer_scale_hacks.push((size, factor));
         } else if let Some(value) = arg.strip_prefix("--deadzone=") {
             self.deadzone = parse_degrees(value, "deadzone")?;
         } else if let Some(value) = arg.strip_prefix("--x-tilt-range=") {
@@ -117,19 +248,8 @@ impl Options {
             let (x, y) = coords
                 .split_once(',')
                 .ok_or_else(|| "--button-to-touch= requires three values".to_string())?;
-            let button = match button {
-                "DPadLeft" => Ok(Button::DPadLeft),
-                "DPadUp" => Ok(Button::DPadUp),
-                "DPadRight" => Ok(Button::DPadRight),
-                "DPadDown" => Ok(Button::DPadDown),
-                "Start" => Ok(Button::Start),
-                "A" => Ok(Button::A),
-                "B" => Ok(Button::B),
-                "X" => Ok(Button::X),
-                "Y" => Ok(Button::Y),
-                "LeftShoulder" => Ok(Button::LeftShoulder),
-                _ => Err("Invalid button for --button-to-touch=".to_string()),
-            }?;
+            let button = Button::from_name(button)
+                .ok_or_else(|| "Invalid button for --button-to-touch=".to_string())?;
             let x: f32 = x
                 .parse()
                 .map_err(|_| "Invalid X co-ordinate for --button-to-touch=".to_string())?;
@@ -156,11 +276,59 @@ impl Options {
                     "Invalid sticky radius for --stabilize-virtual-cursor=".to_string()
                 })?;
             self.stabilize_virtual_cursor = Some((smoothing_strength, sticky_radius));
+        } else if let Some(value) = arg.strip_prefix("--controller-region=") {
+            let values: Vec<f32> = value
+                .split(',')
+                .map(|value| value.parse().ok().filter(|&v| (0.0..=1.0).contains(&v)))
+                .collect::<Option<_>>()
+                .ok_or_else(|| "Invalid value for --controller-region=".to_string())?;
+            let &[x, y, width, height] = &values[..] else {
+                return Err("--controller-region= requires four values".to_string());
+            };
+            if x + width > 1.0 || y + height > 1.0 || width == 0.0 || height == 0.0 {
+                return Err("--controller-region= must be within the screen".to_string());
+            }
+            self.controller_regions.push((x, y, width, height));
+        } else if let Some(value) = arg.strip_prefix("--controller-rumble=") {
+            self.controller_rumble = value
+                .parse()
+                .ok()
+                .filter(|v| (0.0..=1.0).contains(v))
+                .ok_or_else(|| "Invalid value for --controller-rumble=".to_string())?;
+        } else if let Some(value) = arg.strip_prefix("--controller-led-colors=") {
+            self.controller_led_colors = value
+                .split(',')
+                .map(|color| {
+                    if color.len() != 6 {
+                        return None;
+                    }
+                    let color = u32::from_str_radix(color, 16).ok()?;
+                    Some(((color >> 16) as u8, (color >> 8) as u8, color as u8))
+                })
+                .collect::<Option<_>>()
+                .ok_or_else(|| "Invalid color for --controller-led-colors=".to_string())?;
         } else if let Some(value) = arg.strip_prefix("--gles1=") {
             self.gles1_implementation = Some(
                 GLESImplementation::from_short_name(value)
-                    .map_err(|_| "Unrecognized --gles1= value".to_string())?,
+                    .ok()
+                    .filter(|implementation| !implementation.is_gles2())
+                    .ok_or_else(|| "Unrecognized --gles1= value".to_string())?,
+            );
+        } else if let Some(value) = arg.strip_prefix("--gles2=") {
+            self.gles2_implementation = Some(
+                GLESImplementation::from_short_name(value)
+                    .ok()
+                    .filter(|implementation| implementation.is_gles2())
+                    .ok_or_else(|| "Unrecognized --gles2= value".to_string())?,
             );
+        } else if arg == "--gles-error-checking" {
+            self.gles_error_checking = true;
+        } else if arg == "--main-thread-checker" {
+            self.main_thread_checker = true;
+        } else if arg == "--track-allocations" {
+            self.track_allocations = true;
+        } else if arg == "--audit-retain-release" {
+            self.audit_retain_release = true;
         } else if arg == "--disable-direct-memory-access" {
             self.direct_memory_access = false;
         } else if let Some(address) = arg.strip_prefix("--gdb=") {
@@ -171,10 +339,109 @@ impl Options {
             self.gdb_listen_addrs = Some(addrs);
         } else if let Some(value) = arg.strip_prefix("--preferred-languages=") {
             self.preferred_languages = Some(value.split(',').map(ToOwned::to_owned).collect());
+        } else if let Some(value) = arg.strip_prefix("--preferred-region=") {
+            if value.is_empty() || !value.chars().all(|c| c.is_ascii_alphanumeric()) {
+                return Err("Invalid region code".to_string());
+            }
+            self.preferred_region = Some(value.to_string());
         } else if arg == "--headless" {
             self.headless = true;
         } else if arg == "--print-fps" {
             self.print_fps = true;
+        } else if arg == "--home-button-exits" {
+            self.home_button_exits = true;
+        } else if arg == "--other-audio-playing" {
+            self.other_audio_playing = true;
+        } else if let Some(value) = arg.strip_prefix("--camera=") {
+            let mut camera = CameraFeatures::default();
+            for feature in value.split(',') {
+                match feature {
+                    "none" => (),
+                    "rear" => camera.rear = true,
+                    "front" => camera.front = true,
+                    "flash" => camera.flash = true,
+                    "video" => camera.video = true,
+                    _ => return Err(format!("Unrecognized --camera= value: {}", feature)),
+                }
+            }
+            if (camera.flash || camera.video) && !(camera.rear || camera.front) {
+                return Err("--camera= features require a rear or front camera".to_string());
+            }
+            self.camera = camera;
+        } else if let Some(value) = arg.strip_prefix("--battery=") {
+            let (level, charging) = match value.split_once(',') {
+                Some((level, "charging")) => (level, true),
+                Some(_) => return Err("Unrecognized --battery= state".to_string()),
+                None => (value, false),
+            };
+            let level: f32 = level
+                .parse()
+                .ok()
+                .filter(|level| (0.0..=1.0).contains(level))
+                .ok_or_else(|| "Invalid --battery= level".to_string())?;
+            self.battery = Some((level, charging));
+        } else if let Some(value) = arg.strip_prefix("--battery-drain=") {
+            self.battery_drain = value
+                .parse()
+                .ok()
+                .filter(|&rate: &f32| rate >= 0.0)
+                .ok_or_else(|| "Invalid value for --battery-drain=".to_string())?;
+        } else if let Some(value) = arg.strip_prefix("--disk-space=") {
+            let parse_megabytes = |value: &str| -> Result<u64, String> {
+                value
+                    .parse::<u64>()
+                    .ok()
+                    .and_then(|megabytes| megabytes.checked_mul(1 << 20))
+                    .ok_or_else(|| "Invalid value for --disk-space=".to_string())
+            };
+            let (free, total) = match value.split_once(',') {
+                Some((free, total)) => (parse_megabytes(free)?, parse_megabytes(total)?),
+                // Keep the default total unless the free space wouldn't fit.
+                None => {
+                    let free = parse_megabytes(value)?;
+                    (free, free.max(self.disk_space.1))
+                }
+            };
+            if free > total {
+                return Err("--disk-space= free space is larger than the total".to_string());
+            }
+            self.disk_space = (free, total);
+        } else if let Some(value) = arg.strip_prefix("--network-rules=") {
+            self.network_rules = NetworkRules::from_file(Path::new(value))?;
+        } else if let Some(value) = arg.strip_prefix("--connection-type=") {
+            self.connection_type = match value {
+                "none" => ConnectionType::None,
+                "wifi" => ConnectionType::WiFi,
+                "wwan" => ConnectionType::WWAN,
+                _ => return Err(format!("Unrecognized --connection-type= value: {}", value)),
+            };
+        } else if let Some(value) = arg.strip_prefix("--popen-output=") {
+            let (command, file) = value
+                .split_once(',')
+                .filter(|(command, file)| !command.is_empty() && !file.is_empty())
+                .ok_or_else(|| "Invalid value for --popen-output=".to_string())?;
+            self.popen_outputs
+                .insert(command.to_string(), PathBuf::from(file));
+        } else if let Some(value) = arg.strip_prefix("--record-screen=") {
+            self.record_screen = Some(PathBuf::from(value));
+        } else if let Some(value) = arg.strip_prefix("--screenshot-key=") {
+            // This is checked when the window is created.
+            self.screenshot_key = value.to_string();
+        } else if let Some(value) = arg.strip_prefix("--screenshot-dir=") {
+            self.screenshot_dir = Some(PathBuf::from(value));
+        } else if let Some(value) = arg.strip_prefix("--console-key=") {
+            // This is checked when the window is created.
+            self.console_key = value.to_string();
+        } else if let Some(value) = arg.strip_prefix("--memory-report-key=") {
+            // This is checked when the window is created.
+            self.memory_report_key = value.to_string();
+        } else if let Some(value) = arg.strip_prefix("--heap-snapshot-key=") {
+            // This is checked when the window is created.
+            self.heap_snapshot_key = value.to_string();
+        } else if let Some(value) = arg.strip_prefix("--record-input=") {
+            self.record_input = Some(PathBuf::from(value));
+        } else if let Some(value) = arg.strip_prefix("--replay-input=") {
+            self.replay_input = Some(PathBuf::from(value));
         } else if let Some(value) = arg.strip_prefix("--fps-limit=") {
             if value == "off" {
                 self.fps_limit = None;
@@ -186,11 +453,49 @@ impl Options {
                     .ok_or_else(|| "Invalid value for --fps-limit=".to_string())?;
                 self.fps_limit = Some(limit);
             }
+            self.fps_limit_set = true;
+        } else if arg == "--vsync" {
+            self.vsync = true;
+        } else if let Some(value) = arg.strip_prefix("--refresh-rate=") {
+            let rate: f64 = value
+                .parse()
+                .ok()
+                .filter(|&v: &f64| v.is_finite() && v > 0.0)
+                .ok_or_else(|| "Invalid value for --refresh-rate=".to_string())?;
+            self.refresh_rate = rate;
+            // The framerate limit simulates v-sync, so it should match, unless
+            // the user chose a different limit.
+            if !self.fps_limit_set {
+                self.fps_limit = Some(rate);
+            }
+        } else if let Some(value) = arg.strip_prefix("--random-seed=") {
+            let seed: u64 = value
+                .parse()
+                .map_err(|_| "Invalid value for --random-seed=".to_string())?;
+            self.random_seed = Some(seed);
+        } else if let Some(value) = arg.strip_prefix("--log-level=") {
+            crate::log::parse_level_spec(value)
+                .map_err(|e| format!("Invalid value for --log-level=: {}", e))?;
+            // Later options take precedence, so they can simply be appended.
+            self.log_levels = Some(match self.log_levels.take() {
+                Some(old) => format!("{},{}", old, value),
+                None => value.to_string(),
+            });
         } else {
             return Ok(false);
         };
         Ok(true)
     }
+
+    /// Get the scale hack factor for a `CAEAGLLayer` with a particular size in
+    /// points, if `--layer-scale-hack=` overrides the usual one. The size can
+    /// be given in either orientation.
+    pub fn layer_scale_hack(&self, (width, height): (u32, u32)) -> Option<NonZeroU32> {
+        self.layer_scale_hacks
+            .iter()
+            .find(|&&(size, _)| size == (width, height) || size == (height, width))
+            .map(|&(_, factor)| factor)
+    }
 }
 
 /// Try to get app-specific options from a file.
@@ -198,7 +503,12 @@ impl Options {
 /// Returns [Ok] if there is no error when reading the file, otherwise [Err].
 /// The [Ok] value is a [Some] with the options if they could be found, or
 /// [None] if no options were found for this app.
+/// Get the options for an app from an options file. Options on a line with the
+/// app ID `*` apply to all apps and come before any app-specific options, so
+/// that the latter take precedence.
 pub fn get_options_from_file<F: Read>(file: F, app_id: &str) -> Result<Option<String>, String> {
+    let mut global_options = None;
+    let mut app_options = None;
     let file = BufReader::new(file);
     for (line_no, line) in BufRead::lines(file).enumerate() {
         // Line numbering usually starts from 1
@@ -222,16 +532,23 @@ pub fn get_options_from_file<F: Read>(file: F, app_id: &str) -> Result<Option<St
         let (line_app_id, line_options) = line.split_once(':').ok_or_else(|| format!("Line {} is not a comment and is missing a colon (:) to separate the app ID from the options", line_no))?;
         let line_app_id = line_app_id.trim();
 
-        if line_app_id != app_id {
-            continue;
-        }
-
         let line_options = line_options.trim();
-        if line_options.is_empty() {
-            return Ok(None);
-        } else {
-            return Ok(Some(line_options.to_string()));
+        if line_app_id == "*" {
+            global_options.get_or_insert_with(|| line_options.to_string());
+        } else if line_app_id == app_id {
+            app_options = Some(line_options.to_string());
+            break;
         }
     }
-    Ok(None)
+    let options = [global_options.as_deref(), app_options.as_deref()]
+        .into_iter()
+        .flatten()
+        .filter(|options| !options.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ");
+    if options.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(options))
+    }
 }
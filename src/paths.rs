@@ -15,7 +15,8 @@
 //!   [USER_OPTIONS_FILE]. These are ordinary files and are found in
 //!   [user_data_base_path].
 //! * Files that touchHLE will create and modify, and the user may modify if
-//!   they want to: [SANDBOX_DIR]. These are ordinary files and are found in
+//!   they want to: [SANDBOX_DIR], [SCREENSHOTS_DIR], [LOGS_DIR],
+//!   [HEAP_SNAPSHOTS_DIR]. These are ordinary files and are found in
 //!   [user_data_base_path].
 //!
 //! See also [crate::fs], which provides a virtual filesystem for the guest app
@@ -80,6 +81,17 @@ pub const USER_OPTIONS_FILE: &str = "touchHLE_options.txt";
 /// the `Documents` directory.
 pub const SANDBOX_DIR: &str = "touchHLE_sandbox";
 
+/// Name of the directory where screenshots are saved by default.
+pub const SCREENSHOTS_DIR: &str = "touchHLE_screenshots";
+
+/// Name of the directory where heap snapshots are saved. See
+/// [crate::objc::HeapSnapshot].
+pub const HEAP_SNAPSHOTS_DIR: &str = "touchHLE_heap_snapshots";
+
+/// Name of the directory where a log file is written for each session. See
+/// [crate::log::start_session_log].
+pub const LOGS_DIR: &str = "touchHLE_logs";
+
 /// Get a platform-specific base path needed for accessing touchHLE's
 /// user-modifiable files. This is empty on platforms other than Android.
 pub fn user_data_base_path() -> &'static Path {
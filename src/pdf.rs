@@ -0,0 +1,894 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! PDF document parsing and rasterization, for `CGPDFDocument`.
+//!
+//! This is a small original implementation that only aims to display the
+//! vector graphics and images in the simple PDF files some apps bundle (e.g.
+//! manuals or level art). Text, shadings, patterns and encryption aren't
+//! supported, and clipping is approximated with rectangles. The page content
+//! interpreter and rasterizer are in [render].
+//!
+//! Objects are found by scanning the file for `obj` keywords rather than by
+//! reading the cross-reference table, which makes it tolerant of the slightly
+//! broken files that are common in the wild.
+
+pub mod render;
+
+use std::collections::HashMap;
+
+pub type Dict = HashMap<String, Object>;
+
+#[derive(Clone, Debug)]
+pub enum Object {
+    Null,
+    Bool(bool),
+    Number(f32),
+    String(Vec<u8>),
+    Name(String),
+    Array(Vec<Object>),
+    Dict(Dict),
+    /// Dictionary and raw (not yet decoded) data.
+    Stream(Dict, Vec<u8>),
+    /// Indirect reference. The generation number is ignored.
+    Reference(u32),
+    /// Only found in content streams.
+    Operator(String),
+}
+
+static NULL: Object = Object::Null;
+
+impl Object {
+    pub fn as_number(&self) -> Option<f32> {
+        match *self {
+            Object::Number(number) => Some(number),
+            _ => None,
+        }
+    }
+    pub fn as_name(&self) -> Option<&str> {
+        match self {
+            Object::Name(name) => Some(name),
+            _ => None,
+        }
+    }
+    pub fn as_array(&self) -> Option<&[Object]> {
+        match self {
+            Object::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+    /// Also returns the dictionary of a stream.
+    pub fn as_dict(&self) -> Option<&Dict> {
+        match self {
+            Object::Dict(dict) | Object::Stream(dict, _) => Some(dict),
+            _ => None,
+        }
+    }
+}
+
+/// The page boxes, see `CGPDFBox`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PageBox {
+    Media,
+    Crop,
+    Bleed,
+    Trim,
+    Art,
+}
+
+pub struct Document {
+    version: (i32, i32),
+    encrypted: bool,
+    objects: HashMap<u32, Object>,
+    /// Page dictionaries, with inherited attributes already filled in.
+    pages: Vec<Dict>,
+}
+
+impl Document {
+    pub fn parse(data: &[u8]) -> Result<Document, String> {
+        if !data.starts_with(b"%PDF-") {
+            return Err("Not a PDF file".to_string());
+        }
+        let version = {
+            let mut parser = Parser::new(&data[5..]);
+            let version = parser.read_regular();
+            let version = std::str::from_utf8(version).unwrap_or("");
+            let (major, minor) = version.split_once('.').unwrap_or((version, "0"));
+            (major.parse().unwrap_or(1), minor.parse().unwrap_or(0))
+        };
+
+        let mut objects = HashMap::new();
+        let mut pos = 0;
+        while let Some(offset) = find(data, pos, b"obj") {
+            pos = offset + 3;
+            if matches!(data.get(pos), Some(&b) if is_regular(b)) {
+                continue;
+            }
+            let Some(number) = object_number_before(data, offset) else {
+                continue;
+            };
+            let mut parser = Parser {
+                data,
+                pos: offset + 3,
+            };
+            let Some(object) = parser.parse_object() else {
+                continue;
+            };
+            let object = match object {
+                Object::Dict(dict) if parser.skip_keyword(b"stream") => {
+                    let stream_data = parser.read_stream_data(&dict);
+                    Object::Stream(dict, stream_data)
+                }
+                other => other,
+            };
+            // Skipping over the object means binary stream data is never
+            // mistaken for an object header. Later definitions of the same
+            // object (incremental updates) replace earlier ones.
+            pos = parser.pos;
+            objects.insert(number, object);
+        }
+
+        // Since PDF 1.5, objects can also be stored inside object streams.
+        let object_streams: Vec<(Dict, Vec<u8>)> = objects
+            .values()
+            .filter_map(|object| match object {
+                Object::Stream(dict, data) if name_is(dict.get("Type"), "ObjStm") => {
+                    Some((dict.clone(), data.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        for (dict, raw) in object_streams {
+            let Some(decoded) = decode_stream(&dict, &raw) else {
+                continue;
+            };
+            let count = dict.get("N").and_then(Object::as_number).unwrap_or(0.0) as usize;
+            let first = dict.get("First").and_then(Object::as_number).unwrap_or(0.0) as usize;
+            let mut header = Parser::new(&decoded);
+            let mut entries = Vec::new();
+            for _ in 0..count {
+                let (Some(Object::Number(number)), Some(Object::Number(offset))) =
+                    (header.parse_object(), header.parse_object())
+                else {
+                    break;
+                };
+                entries.push((number as u32, offset as usize));
+            }
+            for (number, offset) in entries {
+                let mut parser = Parser {
+                    data: &decoded,
+                    pos: first + offset,
+                };
+                if let Some(object) = parser.parse_object() {
+                    objects.entry(number).or_insert(object);
+                }
+            }
+        }
+
+        let trailer = rfind(data, b"trailer").and_then(|offset| {
+            Parser {
+                data,
+                pos: offset + 7,
+            }
+            .parse_object()
+        });
+        // Since PDF 1.5, the trailer can be a cross-reference stream instead.
+        let trailer = trailer.or_else(|| {
+            objects
+                .values()
+                .find(|object| {
+                    matches!(object, Object::Stream(dict, _) if name_is(dict.get("Type"), "XRef"))
+                })
+                .cloned()
+        });
+        let encrypted = trailer
+            .as_ref()
+            .and_then(Object::as_dict)
+            .is_some_and(|trailer| trailer.contains_key("Encrypt"));
+        let root = trailer
+            .as_ref()
+            .and_then(Object::as_dict)
+            .and_then(|trailer| trailer.get("Root").cloned())
+            .or_else(|| {
+                objects.iter().find_map(|(&number, object)| {
+                    name_is(object.as_dict()?.get("Type"), "Catalog")
+                        .then_some(Object::Reference(number))
+                })
+            })
+            .ok_or_else(|| "Couldn't find document catalog".to_string())?;
+
+        let mut document = Document {
+            version,
+            encrypted,
+            objects,
+            pages: Vec::new(),
+        };
+        let mut pages = Vec::new();
+        if let Some(page_tree) = document.get_dict_entry(&root, "Pages") {
+            document.collect_pages(page_tree, &Dict::new(), &mut pages, 0);
+        }
+        document.pages = pages;
+        Ok(document)
+    }
+
+    fn collect_pages(&self, node: &Object, inherited: &Dict, pages: &mut Vec<Dict>, depth: u32) {
+        // Guard against reference cycles.
+        if depth > 64 {
+            return;
+        }
+        let Some(dict) = self.resolve(node).as_dict() else {
+            return;
+        };
+        let mut inherited = inherited.clone();
+        for key in ["Resources", "MediaBox", "CropBox", "Rotate"] {
+            if let Some(value) = dict.get(key) {
+                inherited.insert(key.to_string(), value.clone());
+            }
+        }
+        if name_is(dict.get("Type"), "Pages") || dict.contains_key("Kids") {
+            for kid in self
+                .get(dict, "Kids")
+                .and_then(Object::as_array)
+                .unwrap_or(&[])
+            {
+                self.collect_pages(kid, &inherited, pages, depth + 1);
+            }
+        } else {
+            let mut page = dict.clone();
+            for (key, value) in inherited {
+                page.entry(key).or_insert(value);
+            }
+            pages.push(page);
+        }
+    }
+
+    /// Follow indirect references.
+    fn resolve<'a>(&'a self, object: &'a Object) -> &'a Object {
+        let mut object = object;
+        // Guard against reference cycles.
+        for _ in 0..32 {
+            match *object {
+                Object::Reference(number) => object = self.objects.get(&number).unwrap_or(&NULL),
+                _ => return object,
+            }
+        }
+        &NULL
+    }
+
+    /// Look up a key in a dictionary and follow indirect references.
+    fn get<'a>(&'a self, dict: &'a Dict, key: &str) -> Option<&'a Object> {
+        dict.get(key).map(|object| self.resolve(object))
+    }
+
+    fn get_dict_entry<'a>(&'a self, dict: &'a Object, key: &str) -> Option<&'a Object> {
+        self.get(self.resolve(dict).as_dict()?, key)
+    }
+
+    fn get_rect(&self, dict: &Dict, key: &str) -> Option<[f32; 4]> {
+        let array = self.get(dict, key)?.as_array()?;
+        let [x0, y0, x1, y1] = array else {
+            return None;
+        };
+        let [x0, y0, x1, y1] = [x0, y0, x1, y1].map(|n| self.resolve(n).as_number());
+        let (x0, y0, x1, y1) = (x0?, y0?, x1?, y1?);
+        Some([x0.min(x1), y0.min(y1), x0.max(x1), y0.max(y1)])
+    }
+
+    pub fn version(&self) -> (i32, i32) {
+        self.version
+    }
+
+    /// Encrypted documents can't be read by this implementation.
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Get a page box as `[min_x, min_y, max_x, max_y]`. Boxes default to and
+    /// are clipped to the boxes that contain them, like in Core Graphics.
+    pub fn page_box(&self, page_index: usize, page_box: PageBox) -> [f32; 4] {
+        let page = &self.pages[page_index];
+        // US Letter is the default media box in the PDF specification.
+        let media_box = self
+            .get_rect(page, "MediaBox")
+            .unwrap_or([0.0, 0.0, 612.0, 792.0]);
+        let intersect = |a: [f32; 4], b: [f32; 4]| {
+            [
+                a[0].max(b[0]),
+                a[1].max(b[1]),
+                a[2].min(b[2]),
+                a[3].min(b[3]),
+            ]
+        };
+        let crop_box = self
+            .get_rect(page, "CropBox")
+            .map_or(media_box, |crop_box| intersect(crop_box, media_box));
+        let key = match page_box {
+            PageBox::Media => return media_box,
+            PageBox::Crop => return crop_box,
+            PageBox::Bleed => "BleedBox",
+            PageBox::Trim => "TrimBox",
+            PageBox::Art => "ArtBox",
+        };
+        self.get_rect(page, key)
+            .map_or(crop_box, |rect| intersect(rect, crop_box))
+    }
+
+    /// Get the clockwise rotation of a page in degrees, which is always a
+    /// multiple of 90.
+    pub fn page_rotation(&self, page_index: usize) -> i32 {
+        let rotation = self
+            .get(&self.pages[page_index], "Rotate")
+            .and_then(Object::as_number)
+            .unwrap_or(0.0) as i32;
+        (rotation - rotation % 90).rem_euclid(360)
+    }
+
+    /// Rasterize the `area` (`[min_x, min_y, max_x, max_y]` in PDF user space)
+    /// of a page to RGBA8 pixels with premultiplied alpha, in top-to-bottom row
+    /// order. The page rotation is not applied.
+    pub fn render_page(
+        &self,
+        page_index: usize,
+        area: [f32; 4],
+        width: u32,
+        height: u32,
+    ) -> Vec<u8> {
+        let page = &self.pages[page_index];
+        let mut content = Vec::new();
+        match self.get(page, "Contents") {
+            Some(Object::Stream(dict, raw)) => {
+                content = decode_stream(dict, raw).unwrap_or_default();
+            }
+            Some(Object::Array(parts)) => {
+                for part in parts {
+                    if let Object::Stream(dict, raw) = self.resolve(part) {
+                        content.extend_from_slice(&decode_stream(dict, raw).unwrap_or_default());
+                        // Parts may split an operator anywhere but inside a
+                        // token, so they must be separated by whitespace.
+                        content.push(b'\n');
+                    }
+                }
+            }
+            _ => (),
+        }
+        let resources = self.get(page, "Resources").and_then(Object::as_dict);
+
+        // Map the area to the pixel grid, whose y axis points down.
+        let [x0, y0, x1, y1] = area;
+        let scale_x = width as f32 / (x1 - x0);
+        let scale_y = height as f32 / (y1 - y0);
+        let ctm = render::Matrix([scale_x, 0.0, 0.0, -scale_y, -x0 * scale_x, y1 * scale_y]);
+
+        render::render(self, &content, resources, ctm, (width, height))
+    }
+}
+
+fn name_is(object: Option<&Object>, name: &str) -> bool {
+    object.and_then(Object::as_name) == Some(name)
+}
+
+fn find(data: &[u8], from: usize, needle: &[u8]) -> Option<usize> {
+    data.get(from..)?
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .map(|offset| from + offset)
+}
+
+fn rfind(data: &[u8], needle: &[u8]) -> Option<usize> {
+    data.windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+/// Given the offset of an `obj` keyword, parse the `<number> <generation>`
+/// before it and return the object number.
+fn object_number_before(data: &[u8], obj_offset: usize) -> Option<u32> {
+    fn skip_back(data: &[u8], mut pos: usize, predicate: fn(u8) -> bool) -> usize {
+        while pos > 0 && predicate(data[pos - 1]) {
+            pos -= 1;
+        }
+        pos
+    }
+    let is_digit = |b: u8| b.is_ascii_digit();
+
+    let generation_end = skip_back(data, obj_offset, is_whitespace);
+    let generation_start = skip_back(data, generation_end, is_digit);
+    let number_end = skip_back(data, generation_start, is_whitespace);
+    let number_start = skip_back(data, number_end, is_digit);
+    if generation_end == obj_offset
+        || generation_start == generation_end
+        || number_end == generation_start
+        || number_start == number_end
+        || (number_start > 0 && is_regular(data[number_start - 1]))
+    {
+        return None;
+    }
+    std::str::from_utf8(&data[number_start..number_end])
+        .ok()?
+        .parse()
+        .ok()
+}
+
+/// Apply a stream's filters. DCT (JPEG) data is returned still encoded, since
+/// that is handled by the image decoder. Returns [None] for unsupported
+/// filters.
+fn decode_stream(dict: &Dict, raw: &[u8]) -> Option<Vec<u8>> {
+    let filters: Vec<&Object> = match dict.get("Filter").or_else(|| dict.get("F")) {
+        None => Vec::new(),
+        Some(Object::Array(filters)) => filters.iter().collect(),
+        Some(filter) => vec![filter],
+    };
+    let params: Vec<Option<&Dict>> = match dict.get("DecodeParms").or_else(|| dict.get("DP")) {
+        Some(Object::Array(params)) => params.iter().map(Object::as_dict).collect(),
+        Some(params) => vec![params.as_dict()],
+        None => Vec::new(),
+    };
+
+    let mut data = raw.to_vec();
+    for (i, filter) in filters.into_iter().enumerate() {
+        let params = params.get(i).copied().flatten();
+        data = match filter.as_name()? {
+            "FlateDecode" | "Fl" => {
+                // Some encoders write bad checksums, so fall back to ignoring
+                // the zlib wrapper.
+                let inflated = miniz_oxide::inflate::decompress_to_vec_zlib(&data)
+                    .or_else(|_| {
+                        miniz_oxide::inflate::decompress_to_vec(data.get(2..).unwrap_or(&[]))
+                    })
+                    .ok()?;
+                apply_predictor(inflated, params)?
+            }
+            "ASCIIHexDecode" | "AHx" => decode_ascii_hex(&data),
+            "ASCII85Decode" | "A85" => decode_ascii_85(&data),
+            "DCTDecode" | "DCT" => return Some(data),
+            other => {
+                log_dbg!("Unsupported PDF stream filter {:?}", other);
+                return None;
+            }
+        };
+    }
+    Some(data)
+}
+
+/// Undo the PNG predictors that can be used with Flate compression.
+fn apply_predictor(data: Vec<u8>, params: Option<&Dict>) -> Option<Vec<u8>> {
+    let param = |key: &str, default: f32| {
+        params
+            .and_then(|params| params.get(key))
+            .and_then(Object::as_number)
+            .unwrap_or(default) as usize
+    };
+    let predictor = param("Predictor", 1.0);
+    if predictor < 10 {
+        // 1 is no prediction. 2 (TIFF) is very rare and not supported.
+        return (predictor == 1).then_some(data);
+    }
+    let bytes_per_pixel = (param("Colors", 1.0) * param("BitsPerComponent", 8.0)).div_ceil(8);
+    let row_len =
+        (param("Columns", 1.0) * param("Colors", 1.0) * param("BitsPerComponent", 8.0)).div_ceil(8);
+
+    let mut output: Vec<u8> = Vec::with_capacity(data.len());
+    let mut previous_row = vec![0u8; row_len];
+    for row in data.chunks(row_len + 1) {
+        let (&filter_type, row) = row.split_first()?;
+        let mut current_row = row.to_vec();
+        current_row.resize(row_len, 0);
+        for i in 0..row_len {
+            let left = if i >= bytes_per_pixel {
+                current_row[i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let up = previous_row[i];
+            let up_left = if i >= bytes_per_pixel {
+                previous_row[i - bytes_per_pixel]
+            } else {
+                0
+            };
+            let prediction = match filter_type {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => {
+                    let p = left as i16 + up as i16 - up_left as i16;
+                    let (pa, pb, pc) = (
+                        (p - left as i16).abs(),
+                        (p - up as i16).abs(),
+                        (p - up_left as i16).abs(),
+                    );
+                    if pa <= pb && pa <= pc {
+                        left
+                    } else if pb <= pc {
+                        up
+                    } else {
+                        up_left
+                    }
+                }
+                _ => return None,
+            };
+            current_row[i] = current_row[i].wrapping_add(prediction);
+        }
+        output.extend_from_slice(&current_row);
+        previous_row = current_row;
+    }
+    Some(output)
+}
+
+fn decode_ascii_hex(data: &[u8]) -> Vec<u8> {
+    let digits: Vec<u8> = data
+        .iter()
+        .take_while(|&&b| b != b'>')
+        .filter_map(|&b| (b as char).to_digit(16).map(|digit| digit as u8))
+        .collect();
+    digits
+        .chunks(2)
+        .map(|pair| (pair[0] << 4) | pair.get(1).copied().unwrap_or(0))
+        .collect()
+}
+
+fn decode_ascii_85(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::new();
+    let mut group = Vec::with_capacity(5);
+    for &b in data {
+        match b {
+            b'~' => break,
+            b'z' if group.is_empty() => output.extend_from_slice(&[0; 4]),
+            b'!'..=b'u' => {
+                group.push(b - b'!');
+                if group.len() == 5 {
+                    let value = group.iter().fold(0u32, |acc, &digit| {
+                        acc.wrapping_mul(85).wrapping_add(digit as u32)
+                    });
+                    output.extend_from_slice(&value.to_be_bytes());
+                    group.clear();
+                }
+            }
+            _ => (),
+        }
+    }
+    // A final partial group is padded with the highest digit.
+    if group.len() > 1 {
+        let len = group.len();
+        group.resize(5, 84);
+        let value = group.iter().fold(0u32, |acc, &digit| {
+            acc.wrapping_mul(85).wrapping_add(digit as u32)
+        });
+        output.extend_from_slice(&value.to_be_bytes()[..len - 1]);
+    }
+    output
+}
+
+fn is_whitespace(b: u8) -> bool {
+    matches!(b, b'\0' | b'\t' | b'\n' | b'\x0c' | b'\r' | b' ')
+}
+
+fn is_delimiter(b: u8) -> bool {
+    matches!(
+        b,
+        b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%'
+    )
+}
+
+fn is_regular(b: u8) -> bool {
+    !is_whitespace(b) && !is_delimiter(b)
+}
+
+/// Tokenizer and object parser, used for both the file structure and content
+/// streams.
+struct Parser<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Parser { data, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.data.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b) = self.peek() {
+            if is_whitespace(b) {
+                self.pos += 1;
+            } else if b == b'%' {
+                while !matches!(self.peek(), None | Some(b'\r' | b'\n')) {
+                    self.pos += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn read_regular(&mut self) -> &'a [u8] {
+        let start = self.pos;
+        while matches!(self.peek(), Some(b) if is_regular(b)) {
+            self.pos += 1;
+        }
+        &self.data[start..self.pos]
+    }
+
+    /// Consume a keyword if it's next.
+    fn skip_keyword(&mut self, keyword: &[u8]) -> bool {
+        let old_pos = self.pos;
+        self.skip_whitespace();
+        if self.read_regular() == keyword {
+            true
+        } else {
+            self.pos = old_pos;
+            false
+        }
+    }
+
+    /// Read the data of a stream, just after the `stream` keyword.
+    fn read_stream_data(&mut self, dict: &Dict) -> Vec<u8> {
+        if self.data[self.pos..].starts_with(b"\r\n") {
+            self.pos += 2;
+        } else if matches!(self.peek(), Some(b'\r' | b'\n')) {
+            self.pos += 1;
+        }
+        let start = self.pos;
+
+        // The length is often an indirect reference, which may not have been
+        // parsed yet, and is sometimes wrong, so searching for the end is the
+        // fallback.
+        if let Some(length) = dict.get("Length").and_then(Object::as_number) {
+            let end = start + length as usize;
+            if end <= self.data.len() {
+                self.pos = end;
+                if self.skip_keyword(b"endstream") {
+                    return self.data[start..end].to_vec();
+                }
+            }
+        }
+        let Some(end) = find(self.data, start, b"endstream") else {
+            self.pos = self.data.len();
+            return self.data[start..].to_vec();
+        };
+        self.pos = end + 9;
+        let mut data = &self.data[start..end];
+        if data.ends_with(b"\r\n") {
+            data = &data[..data.len() - 2];
+        } else if data.ends_with(b"\n") || data.ends_with(b"\r") {
+            data = &data[..data.len() - 1];
+        }
+        data.to_vec()
+    }
+
+    /// Parse the next object. Returns [None] at the end of the data or if the
+    /// data is malformed.
+    fn parse_object(&mut self) -> Option<Object> {
+        self.skip_whitespace();
+        match self.peek()? {
+            b'/' => {
+                self.pos += 1;
+                Some(Object::Name(decode_name(self.read_regular())))
+            }
+            b'(' => {
+                self.pos += 1;
+                Some(Object::String(self.parse_literal_string()))
+            }
+            b'<' if self.data.get(self.pos + 1) == Some(&b'<') => {
+                self.pos += 2;
+                let mut dict = Dict::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.data[self.pos..].starts_with(b">>") {
+                        self.pos += 2;
+                        break;
+                    }
+                    let key = self.parse_object()?;
+                    let value = self.parse_object()?;
+                    if let Object::Name(key) = key {
+                        dict.insert(key, value);
+                    }
+                }
+                Some(Object::Dict(dict))
+            }
+            b'<' => {
+                self.pos += 1;
+                let start = self.pos;
+                while !matches!(self.peek(), None | Some(b'>')) {
+                    self.pos += 1;
+                }
+                let string = decode_ascii_hex(&self.data[start..self.pos]);
+                if self.peek().is_some() {
+                    self.pos += 1;
+                }
+                Some(Object::String(string))
+            }
+            b'[' => {
+                self.pos += 1;
+                let mut array = Vec::new();
+                loop {
+                    self.skip_whitespace();
+                    if self.peek()? == b']' {
+                        self.pos += 1;
+                        break;
+                    }
+                    array.push(self.parse_object()?);
+                }
+                Some(Object::Array(array))
+            }
+            b'0'..=b'9' | b'+' | b'-' | b'.' => {
+                let token = self.read_regular();
+                if token.iter().all(u8::is_ascii_digit) {
+                    if let Some(reference) = self.parse_reference_rest(token) {
+                        return Some(reference);
+                    }
+                }
+                let number = std::str::from_utf8(token).ok()?.parse().unwrap_or(0.0);
+                Some(Object::Number(number))
+            }
+            b if is_delimiter(b) => {
+                // Stray delimiter, e.g. the braces of a PostScript function.
+                self.pos += 1;
+                Some(Object::Null)
+            }
+            _ => Some(match self.read_regular() {
+                b"true" => Object::Bool(true),
+                b"false" => Object::Bool(false),
+                b"null" => Object::Null,
+                operator => Object::Operator(String::from_utf8_lossy(operator).into_owned()),
+            }),
+        }
+    }
+
+    /// Having read an integer, check if it's the start of an `N G R` indirect
+    /// reference, and consume the rest of it if so.
+    fn parse_reference_rest(&mut self, number: &[u8]) -> Option<Object> {
+        let old_pos = self.pos;
+        self.skip_whitespace();
+        let generation = self.read_regular();
+        self.skip_whitespace();
+        if !generation.is_empty()
+            && generation.iter().all(u8::is_ascii_digit)
+            && self.read_regular() == b"R"
+        {
+            Some(Object::Reference(
+                std::str::from_utf8(number).ok()?.parse().ok()?,
+            ))
+        } else {
+            self.pos = old_pos;
+            None
+        }
+    }
+
+    fn parse_literal_string(&mut self) -> Vec<u8> {
+        let mut string = Vec::new();
+        let mut depth = 0;
+        while let Some(b) = self.peek() {
+            self.pos += 1;
+            match b {
+                b'(' => {
+                    depth += 1;
+                    string.push(b);
+                }
+                b')' if depth == 0 => break,
+                b')' => {
+                    depth -= 1;
+                    string.push(b);
+                }
+                b'\\' => {
+                    let Some(escaped) = self.peek() else {
+                        break;
+                    };
+                    self.pos += 1;
+                    match escaped {
+                        b'n' => string.push(b'\n'),
+                        b'r' => string.push(b'\r'),
+                        b't' => string.push(b'\t'),
+                        b'b' => string.push(b'\x08'),
+                        b'f' => string.push(b'\x0c'),
+                        b'0'..=b'7' => {
+                            let mut value = (escaped - b'0') as u32;
+                            for _ in 0..2 {
+                                match self.peek() {
+                                    Some(digit @ b'0'..=b'7') => {
+                                        value = value * 8 + (digit - b'0') as u32;
+                                        self.pos += 1;
+                                    }
+                                    _ => break,
+                                }
+                            }
+                            string.push(value as u8);
+                        }
+                        // Line continuation
+                        b'\r' => {
+                            if self.peek() == Some(b'\n') {
+                                self.pos += 1;
+                            }
+                        }
+                        b'\n' => (),
+                        other => string.push(other),
+                    }
+                }
+                _ => string.push(b),
+            }
+        }
+        string
+    }
+}
+
+/// Names can contain `#xx` hex escapes.
+fn decode_name(raw: &[u8]) -> String {
+    let mut name = Vec::with_capacity(raw.len());
+    let mut i = 0;
+    while i < raw.len() {
+        if raw[i] == b'#' {
+            if let Some(value) = raw
+                .get(i + 1..i + 3)
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                name.push(value);
+                i += 3;
+                continue;
+            }
+        }
+        name.push(raw[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&name).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A single 200×100 page with a red rectangle in the bottom-left corner.
+    const TEST_PDF: &[u8] = b"%PDF-1.4
+1 0 obj << /Type /Catalog /Pages 2 0 R >> endobj
+2 0 obj << /Type /Pages /Kids [3 0 R] /Count 1 /MediaBox [0 0 200 100] >> endobj
+3 0 obj << /Type /Page /Parent 2 0 R /Contents 4 0 R >> endobj
+4 0 obj << /Length 5 0 R >>
+stream
+q 1 0 0 rg 0 0 100 50 re f Q
+endstream
+endobj
+5 0 obj 29 endobj
+trailer << /Root 1 0 R /Size 6 >>
+%%EOF
+";
+
+    #[test]
+    fn parse_and_render() {
+        let document = Document::parse(TEST_PDF).unwrap();
+        assert_eq!(document.version(), (1, 4));
+        assert_eq!(document.page_count(), 1);
+        assert_eq!(
+            document.page_box(0, PageBox::Crop),
+            [0.0, 0.0, 200.0, 100.0]
+        );
+        assert_eq!(document.page_rotation(0), 0);
+
+        let pixels = document.render_page(0, [0.0, 0.0, 200.0, 100.0], 20, 10);
+        let pixel = |x: usize, y: usize| &pixels[(y * 20 + x) * 4..][..4];
+        // Bottom-left is filled, top-right is transparent.
+        assert_eq!(pixel(2, 8), [255, 0, 0, 255]);
+        assert_eq!(pixel(15, 2), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn parse_objects() {
+        let mut parser = Parser::new(b"[1 2 R /A#20B (a\\(b\\)\\101) <4142> -.5 true]");
+        let Some(Object::Array(array)) = parser.parse_object() else {
+            panic!();
+        };
+        assert!(matches!(array[0], Object::Reference(1)));
+        assert_eq!(array[1].as_name(), Some("A B"));
+        assert!(matches!(array[2], Object::String(ref s) if s == b"a(b)A"));
+        assert!(matches!(array[3], Object::String(ref s) if s == b"AB"));
+        assert_eq!(array[4].as_number(), Some(-0.5));
+        assert!(matches!(array[5], Object::Bool(true)));
+    }
+}
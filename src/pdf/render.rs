@@ -0,0 +1,905 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Interpretation of page content streams and rasterization.
+//!
+//! Paths are flattened to polygons and filled with an anti-aliased scanline
+//! rasterizer. Strokes are approximated by filling a rectangle for each line
+//! segment, which is good enough for the thin lines in typical documents.
+
+use super::{decode_stream, name_is, Dict, Document, Object, Parser};
+use crate::image::Image;
+
+/// Affine transform in PDF's `[a b c d e f]` form.
+#[derive(Copy, Clone, Debug)]
+pub(super) struct Matrix(pub [f32; 6]);
+
+impl Matrix {
+    fn from_object(object: &Object) -> Option<Matrix> {
+        let array = object.as_array()?;
+        let values: Vec<f32> = array.iter().filter_map(Object::as_number).collect();
+        Some(Matrix(values.try_into().ok()?))
+    }
+
+    /// Returns a transform that applies `self` first, then `other`.
+    fn then(self, other: Matrix) -> Matrix {
+        let [a, b, c, d, e, f] = self.0;
+        let [a2, b2, c2, d2, e2, f2] = other.0;
+        Matrix([
+            a * a2 + b * c2,
+            a * b2 + b * d2,
+            c * a2 + d * c2,
+            c * b2 + d * d2,
+            e * a2 + f * c2 + e2,
+            e * b2 + f * d2 + f2,
+        ])
+    }
+
+    fn apply(self, (x, y): (f32, f32)) -> (f32, f32) {
+        let [a, b, c, d, e, f] = self.0;
+        (a * x + c * y + e, b * x + d * y + f)
+    }
+
+    fn invert(self) -> Option<Matrix> {
+        let [a, b, c, d, e, f] = self.0;
+        let det = a * d - b * c;
+        if det == 0.0 {
+            return None;
+        }
+        let (ia, ib, ic, id) = (d / det, -b / det, -c / det, a / det);
+        Some(Matrix([
+            ia,
+            ib,
+            ic,
+            id,
+            -(e * ia + f * ic),
+            -(e * ib + f * id),
+        ]))
+    }
+
+    /// Average scale factor, used for line widths.
+    fn scale_factor(self) -> f32 {
+        let [a, b, c, d, _, _] = self.0;
+        (a * d - b * c).abs().sqrt()
+    }
+}
+
+/// Device-space bounding box of `[min_x, min_y, max_x, max_y]` transformed by
+/// `matrix`.
+fn transform_rect(matrix: Matrix, [x0, y0, x1, y1]: [f32; 4]) -> [f32; 4] {
+    let corners = [(x0, y0), (x1, y0), (x0, y1), (x1, y1)].map(|point| matrix.apply(point));
+    bounding_box(&corners)
+}
+
+fn bounding_box(points: &[(f32, f32)]) -> [f32; 4] {
+    points.iter().fold(
+        [
+            f32::INFINITY,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::NEG_INFINITY,
+        ],
+        |[x0, y0, x1, y1], &(x, y)| [x0.min(x), y0.min(y), x1.max(x), y1.max(y)],
+    )
+}
+
+fn intersect([a0, a1, a2, a3]: [f32; 4], [b0, b1, b2, b3]: [f32; 4]) -> [f32; 4] {
+    [a0.max(b0), a1.max(b1), a2.min(b2), a3.min(b3)]
+}
+
+#[derive(Clone, Debug)]
+enum ColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+    Indexed(Box<ColorSpace>, Vec<u8>),
+    /// Separation and DeviceN spaces. Their tint transforms aren't supported,
+    /// so they are approximated as shades of gray, which is usually right for
+    /// black ink at least.
+    Ink(usize),
+    /// Not supported, nothing is painted.
+    Pattern,
+}
+
+impl ColorSpace {
+    fn from_object(
+        doc: &Document,
+        object: &Object,
+        resources: Option<&Dict>,
+        depth: u32,
+    ) -> ColorSpace {
+        match doc.resolve(object) {
+            Object::Name(name) => match name.as_str() {
+                "DeviceGray" | "CalGray" | "G" => ColorSpace::Gray,
+                // Lab is approximated by RGB.
+                "DeviceRGB" | "CalRGB" | "RGB" | "Lab" => ColorSpace::Rgb,
+                "DeviceCMYK" | "CMYK" => ColorSpace::Cmyk,
+                "Pattern" => ColorSpace::Pattern,
+                _ => {
+                    // Named color space in the resource dictionary.
+                    let defined = resources
+                        .and_then(|resources| doc.get(resources, "ColorSpace"))
+                        .and_then(Object::as_dict)
+                        .and_then(|spaces| spaces.get(name.as_str()));
+                    match defined {
+                        Some(defined) if depth < 8 => {
+                            ColorSpace::from_object(doc, defined, resources, depth + 1)
+                        }
+                        _ => {
+                            log_dbg!("Unknown PDF color space {:?}, using gray", name);
+                            ColorSpace::Gray
+                        }
+                    }
+                }
+            },
+            Object::Array(array) if depth < 8 => {
+                let family = array.first().and_then(Object::as_name).unwrap_or("");
+                match family {
+                    "ICCBased" => {
+                        let components = array
+                            .get(1)
+                            .and_then(|profile| doc.get_dict_entry(profile, "N"))
+                            .and_then(Object::as_number)
+                            .unwrap_or(3.0);
+                        match components as u32 {
+                            1 => ColorSpace::Gray,
+                            4 => ColorSpace::Cmyk,
+                            _ => ColorSpace::Rgb,
+                        }
+                    }
+                    "Indexed" | "I" => {
+                        let base = array.get(1).map_or(ColorSpace::Rgb, |base| {
+                            ColorSpace::from_object(doc, base, resources, depth + 1)
+                        });
+                        let table = match array.get(3).map(|table| doc.resolve(table)) {
+                            Some(Object::String(table)) => table.clone(),
+                            Some(Object::Stream(dict, raw)) => {
+                                decode_stream(dict, raw).unwrap_or_default()
+                            }
+                            _ => Vec::new(),
+                        };
+                        ColorSpace::Indexed(Box::new(base), table)
+                    }
+                    "Separation" => ColorSpace::Ink(1),
+                    "DeviceN" => ColorSpace::Ink(
+                        array
+                            .get(1)
+                            .map(|names| doc.resolve(names))
+                            .and_then(Object::as_array)
+                            .map_or(1, |names| names.len()),
+                    ),
+                    "Pattern" => ColorSpace::Pattern,
+                    _ => ColorSpace::from_object(
+                        doc,
+                        &Object::Name(family.to_string()),
+                        resources,
+                        depth + 1,
+                    ),
+                }
+            }
+            _ => ColorSpace::Gray,
+        }
+    }
+
+    fn component_count(&self) -> usize {
+        match *self {
+            ColorSpace::Gray | ColorSpace::Indexed(..) => 1,
+            ColorSpace::Rgb => 3,
+            ColorSpace::Cmyk => 4,
+            ColorSpace::Ink(count) => count,
+            ColorSpace::Pattern => 0,
+        }
+    }
+
+    /// The initial color after switching to this color space.
+    fn initial_color(&self) -> Option<(f32, f32, f32)> {
+        match self {
+            ColorSpace::Cmyk => self.to_rgb(&[0.0, 0.0, 0.0, 1.0]),
+            ColorSpace::Ink(count) => self.to_rgb(&vec![1.0; *count]),
+            _ => self.to_rgb(&[0.0; 4]),
+        }
+    }
+
+    /// Convert a color to RGB. Components for indexed spaces are not
+    /// normalized.
+    fn to_rgb(&self, components: &[f32]) -> Option<(f32, f32, f32)> {
+        let get = |i: usize| components.get(i).copied().unwrap_or(0.0).clamp(0.0, 1.0);
+        Some(match self {
+            ColorSpace::Gray => (get(0), get(0), get(0)),
+            ColorSpace::Rgb => (get(0), get(1), get(2)),
+            ColorSpace::Cmyk => {
+                let k = 1.0 - get(3);
+                ((1.0 - get(0)) * k, (1.0 - get(1)) * k, (1.0 - get(2)) * k)
+            }
+            ColorSpace::Indexed(base, table) => {
+                let size = base.component_count();
+                let index = components.first().copied().unwrap_or(0.0).max(0.0) as usize;
+                let entry = table.get(index * size..(index + 1) * size)?;
+                let entry: Vec<f32> = entry.iter().map(|&b| b as f32 / 255.0).collect();
+                return base.to_rgb(&entry);
+            }
+            ColorSpace::Ink(count) => {
+                let tint = (0..*count).map(get).fold(0.0, f32::max);
+                (1.0 - tint, 1.0 - tint, 1.0 - tint)
+            }
+            ColorSpace::Pattern => return None,
+        })
+    }
+}
+
+#[derive(Clone, Debug)]
+struct GraphicsState {
+    ctm: Matrix,
+    /// Device-space clip rectangle.
+    clip: [f32; 4],
+    fill_space: ColorSpace,
+    /// [None] if the color can't be painted.
+    fill_color: Option<(f32, f32, f32)>,
+    stroke_space: ColorSpace,
+    stroke_color: Option<(f32, f32, f32)>,
+    fill_alpha: f32,
+    stroke_alpha: f32,
+    line_width: f32,
+}
+
+struct Subpath {
+    /// Device-space points.
+    points: Vec<(f32, f32)>,
+    closed: bool,
+}
+
+/// RGBA with premultiplied alpha.
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Canvas {
+    /// Paint a color with straight alpha over a pixel.
+    fn blend(&mut self, x: usize, y: usize, (r, g, b, a): (f32, f32, f32, f32), coverage: f32) {
+        let a = a * coverage;
+        if a <= 0.0 {
+            return;
+        }
+        let pixel = &mut self.pixels[y * self.width + x];
+        *pixel = [
+            r * a + pixel[0] * (1.0 - a),
+            g * a + pixel[1] * (1.0 - a),
+            b * a + pixel[2] * (1.0 - a),
+            a + pixel[3] * (1.0 - a),
+        ];
+    }
+
+    /// Range of pixel indices covered by `[min, max)` on an axis.
+    fn pixel_range(min: f32, max: f32, size: usize) -> std::ops::Range<usize> {
+        let start = min.floor().clamp(0.0, size as f32) as usize;
+        let end = max.ceil().clamp(0.0, size as f32) as usize;
+        start..end.max(start)
+    }
+
+    /// Fill polygons, using the non-zero winding rule or the even-odd rule.
+    fn fill(
+        &mut self,
+        polygons: &[Vec<(f32, f32)>],
+        even_odd: bool,
+        color: (f32, f32, f32, f32),
+        clip: [f32; 4],
+    ) {
+        // Number of samples per pixel vertically. Horizontal coverage is
+        // calculated exactly.
+        const SUBSAMPLES: usize = 4;
+
+        let mut edges = Vec::new();
+        for polygon in polygons {
+            for (i, &(x0, y0)) in polygon.iter().enumerate() {
+                let (x1, y1) = polygon[(i + 1) % polygon.len()];
+                if y0 < y1 {
+                    edges.push((x0, y0, x1, y1, 1));
+                } else if y1 < y0 {
+                    edges.push((x1, y1, x0, y0, -1));
+                }
+            }
+        }
+        let points: Vec<(f32, f32)> = polygons.iter().flatten().copied().collect();
+        let [min_x, min_y, max_x, max_y] = intersect(bounding_box(&points), clip);
+        let columns = Self::pixel_range(min_x, max_x, self.width);
+        let rows = Self::pixel_range(min_y, max_y, self.height);
+
+        let mut coverage = vec![0.0f32; self.width];
+        let mut crossings: Vec<(f32, i32)> = Vec::new();
+        for row in rows {
+            coverage[columns.clone()].fill(0.0);
+            for sample in 0..SUBSAMPLES {
+                let y = row as f32 + (sample as f32 + 0.5) / SUBSAMPLES as f32;
+                if y < clip[1] || y >= clip[3] {
+                    continue;
+                }
+                crossings.clear();
+                for &(x0, y0, x1, y1, direction) in &edges {
+                    if y >= y0 && y < y1 {
+                        crossings.push((x0 + (y - y0) * (x1 - x0) / (y1 - y0), direction));
+                    }
+                }
+                crossings.sort_by(|a, b| a.0.total_cmp(&b.0));
+                let mut winding = 0;
+                for pair in crossings.windows(2) {
+                    winding += pair[0].1;
+                    let inside = if even_odd {
+                        winding % 2 != 0
+                    } else {
+                        winding != 0
+                    };
+                    if inside {
+                        add_span(
+                            &mut coverage,
+                            pair[0].0.max(min_x),
+                            pair[1].0.min(max_x),
+                            1.0 / SUBSAMPLES as f32,
+                        );
+                    }
+                }
+            }
+            for x in columns.clone() {
+                if coverage[x] > 0.0 {
+                    self.blend(x, row, color, coverage[x].min(1.0));
+                }
+            }
+        }
+    }
+
+    fn into_rgba8(self) -> Vec<u8> {
+        self.pixels
+            .into_iter()
+            .flat_map(|pixel| pixel.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+            .collect()
+    }
+}
+
+/// Add horizontal coverage for the span `[x0, x1)` of a row.
+fn add_span(coverage: &mut [f32], x0: f32, x1: f32, weight: f32) {
+    let x0 = x0.max(0.0);
+    let x1 = x1.min(coverage.len() as f32);
+    if x1 <= x0 {
+        return;
+    }
+    let (i0, i1) = (x0 as usize, x1 as usize);
+    if i0 == i1 {
+        coverage[i0] += (x1 - x0) * weight;
+        return;
+    }
+    coverage[i0] += (i0 as f32 + 1.0 - x0) * weight;
+    for pixel in &mut coverage[i0 + 1..i1] {
+        *pixel += weight;
+    }
+    if i1 < coverage.len() {
+        coverage[i1] += (x1 - i1 as f32) * weight;
+    }
+}
+
+/// Decoded image with straight alpha.
+struct DecodedImage {
+    width: usize,
+    height: usize,
+    pixels: Vec<(f32, f32, f32, f32)>,
+}
+
+struct Renderer<'a> {
+    doc: &'a Document,
+    canvas: Canvas,
+    state: GraphicsState,
+    saved_states: Vec<GraphicsState>,
+    path: Vec<Subpath>,
+    /// Set by `W` and `W*`, applied by the next painting operator.
+    pending_clip: bool,
+    /// Nesting level of form XObjects.
+    depth: u32,
+}
+
+pub(super) fn render(
+    doc: &Document,
+    content: &[u8],
+    resources: Option<&Dict>,
+    ctm: Matrix,
+    (width, height): (u32, u32),
+) -> Vec<u8> {
+    let (width, height) = (width as usize, height as usize);
+    let mut renderer = Renderer {
+        doc,
+        canvas: Canvas {
+            width,
+            height,
+            pixels: vec![[0.0; 4]; width * height],
+        },
+        state: GraphicsState {
+            ctm,
+            clip: [0.0, 0.0, width as f32, height as f32],
+            fill_space: ColorSpace::Gray,
+            fill_color: Some((0.0, 0.0, 0.0)),
+            stroke_space: ColorSpace::Gray,
+            stroke_color: Some((0.0, 0.0, 0.0)),
+            fill_alpha: 1.0,
+            stroke_alpha: 1.0,
+            line_width: 1.0,
+        },
+        saved_states: Vec::new(),
+        path: Vec::new(),
+        pending_clip: false,
+        depth: 0,
+    };
+    renderer.run(content, resources);
+    renderer.canvas.into_rgba8()
+}
+
+impl<'a> Renderer<'a> {
+    fn run(&mut self, content: &[u8], resources: Option<&'a Dict>) {
+        let mut parser = Parser::new(content);
+        let mut operands = Vec::new();
+        while let Some(object) = parser.parse_object() {
+            let Object::Operator(operator) = object else {
+                operands.push(object);
+                continue;
+            };
+            if operator == "BI" {
+                skip_inline_image(&mut parser);
+            } else {
+                self.operator(&operator, &operands, resources);
+            }
+            operands.clear();
+        }
+    }
+
+    fn current_point(&self) -> (f32, f32) {
+        self.path
+            .last()
+            .and_then(|subpath| subpath.points.last().copied())
+            .unwrap_or((0.0, 0.0))
+    }
+
+    fn line_to(&mut self, point: (f32, f32)) {
+        if let Some(subpath) = self.path.last_mut() {
+            subpath.points.push(point);
+        } else {
+            self.path.push(Subpath {
+                points: vec![point],
+                closed: false,
+            });
+        }
+    }
+
+    /// Flatten a cubic Bézier curve from the current point.
+    fn curve_to(&mut self, c1: (f32, f32), c2: (f32, f32), end: (f32, f32)) {
+        let start = self.current_point();
+        let distance = |(x0, y0): (f32, f32), (x1, y1): (f32, f32)| (x1 - x0).hypot(y1 - y0);
+        let length = distance(start, c1) + distance(c1, c2) + distance(c2, end);
+        let steps = (length / 4.0).ceil().clamp(1.0, 64.0) as usize;
+        for step in 1..=steps {
+            let t = step as f32 / steps as f32;
+            let u = 1.0 - t;
+            let (w0, w1, w2, w3) = (u * u * u, 3.0 * u * u * t, 3.0 * u * t * t, t * t * t);
+            self.line_to((
+                w0 * start.0 + w1 * c1.0 + w2 * c2.0 + w3 * end.0,
+                w0 * start.1 + w1 * c1.1 + w2 * c2.1 + w3 * end.1,
+            ));
+        }
+    }
+
+    fn paint(&mut self, fill: Option<bool>, stroke: bool) {
+        if let Some(even_odd) = fill {
+            if let Some((r, g, b)) = self.state.fill_color {
+                let polygons: Vec<Vec<(f32, f32)>> = self
+                    .path
+                    .iter()
+                    .filter(|subpath| subpath.points.len() > 2)
+                    .map(|subpath| subpath.points.clone())
+                    .collect();
+                let color = (r, g, b, self.state.fill_alpha);
+                self.canvas
+                    .fill(&polygons, even_odd, color, self.state.clip);
+            }
+        }
+        if stroke {
+            if let Some((r, g, b)) = self.state.stroke_color {
+                // Zero-width lines are the thinnest line that can be drawn.
+                let width = (self.state.line_width * self.state.ctm.scale_factor()).max(1.0);
+                let polygons = stroke_polygons(&self.path, width / 2.0);
+                let color = (r, g, b, self.state.stroke_alpha);
+                self.canvas.fill(&polygons, false, color, self.state.clip);
+            }
+        }
+        if self.pending_clip {
+            let points: Vec<(f32, f32)> = self
+                .path
+                .iter()
+                .flat_map(|subpath| subpath.points.iter().copied())
+                .collect();
+            self.state.clip = intersect(self.state.clip, bounding_box(&points));
+            self.pending_clip = false;
+        }
+        self.path.clear();
+    }
+
+    fn operator(&mut self, operator: &str, operands: &[Object], resources: Option<&'a Dict>) {
+        let numbers: Vec<f32> = operands.iter().filter_map(Object::as_number).collect();
+        let number = |i: usize| numbers.get(i).copied().unwrap_or(0.0);
+        let point = |i: usize| self.state.ctm.apply((number(i), number(i + 1)));
+        let doc = self.doc;
+
+        match operator {
+            "q" => self.saved_states.push(self.state.clone()),
+            "Q" => {
+                if let Some(state) = self.saved_states.pop() {
+                    self.state = state;
+                }
+            }
+            "cm" => {
+                if let Ok(matrix) = numbers.clone().try_into() {
+                    self.state.ctm = Matrix(matrix).then(self.state.ctm);
+                }
+            }
+            "w" => self.state.line_width = number(0),
+            "gs" => {
+                let Some(parameters) = operands
+                    .first()
+                    .and_then(Object::as_name)
+                    .zip(resources.and_then(|resources| doc.get(resources, "ExtGState")))
+                    .and_then(|(name, states)| doc.get(states.as_dict()?, name))
+                    .and_then(Object::as_dict)
+                else {
+                    return;
+                };
+                let get = |key: &str| doc.get(parameters, key).and_then(Object::as_number);
+                if let Some(line_width) = get("LW") {
+                    self.state.line_width = line_width;
+                }
+                if let Some(alpha) = get("CA") {
+                    self.state.stroke_alpha = alpha;
+                }
+                if let Some(alpha) = get("ca") {
+                    self.state.fill_alpha = alpha;
+                }
+            }
+
+            // Colors
+            "g" | "rg" | "k" | "G" | "RG" | "K" => {
+                let space = match operator {
+                    "g" | "G" => ColorSpace::Gray,
+                    "rg" | "RG" => ColorSpace::Rgb,
+                    _ => ColorSpace::Cmyk,
+                };
+                let color = space.to_rgb(&numbers);
+                if operator.chars().all(char::is_lowercase) {
+                    (self.state.fill_space, self.state.fill_color) = (space, color);
+                } else {
+                    (self.state.stroke_space, self.state.stroke_color) = (space, color);
+                }
+            }
+            "cs" | "CS" => {
+                let space = operands.first().map_or(ColorSpace::Gray, |space| {
+                    ColorSpace::from_object(doc, space, resources, 0)
+                });
+                let color = space.initial_color();
+                if operator == "cs" {
+                    (self.state.fill_space, self.state.fill_color) = (space, color);
+                } else {
+                    (self.state.stroke_space, self.state.stroke_color) = (space, color);
+                }
+            }
+            "sc" | "scn" => {
+                let space = &self.state.fill_space;
+                self.state.fill_color =
+                    space.to_rgb(&numbers[..space.component_count().min(numbers.len())]);
+            }
+            "SC" | "SCN" => {
+                let space = &self.state.stroke_space;
+                self.state.stroke_color =
+                    space.to_rgb(&numbers[..space.component_count().min(numbers.len())]);
+            }
+
+            // Path construction
+            "m" => {
+                let point = point(0);
+                self.path.push(Subpath {
+                    points: vec![point],
+                    closed: false,
+                });
+            }
+            "l" => {
+                let point = point(0);
+                self.line_to(point);
+            }
+            "c" => {
+                let (c1, c2, end) = (point(0), point(2), point(4));
+                self.curve_to(c1, c2, end);
+            }
+            "v" => {
+                let (c2, end) = (point(0), point(2));
+                let c1 = self.current_point();
+                self.curve_to(c1, c2, end);
+            }
+            "y" => {
+                let (c1, end) = (point(0), point(2));
+                self.curve_to(c1, end, end);
+            }
+            "h" => {
+                if let Some(subpath) = self.path.last_mut() {
+                    subpath.closed = true;
+                }
+            }
+            "re" => {
+                let (x, y, w, h) = (number(0), number(1), number(2), number(3));
+                let corners = [(x, y), (x + w, y), (x + w, y + h), (x, y + h)];
+                self.path.push(Subpath {
+                    points: corners.map(|corner| self.state.ctm.apply(corner)).to_vec(),
+                    closed: true,
+                });
+            }
+
+            // Path painting
+            "f" | "F" => self.paint(Some(false), false),
+            "f*" => self.paint(Some(true), false),
+            "S" => self.paint(None, true),
+            "s" => {
+                self.operator("h", &[], resources);
+                self.paint(None, true);
+            }
+            "B" => self.paint(Some(false), true),
+            "B*" => self.paint(Some(true), true),
+            "b" | "b*" => {
+                self.operator("h", &[], resources);
+                self.paint(Some(operator == "b*"), true);
+            }
+            "n" => self.paint(None, false),
+            "W" | "W*" => self.pending_clip = true,
+
+            "Do" => {
+                let Some(Object::Stream(dict, raw)) = operands
+                    .first()
+                    .and_then(Object::as_name)
+                    .zip(resources.and_then(|resources| doc.get(resources, "XObject")))
+                    .and_then(|(name, xobjects)| doc.get(xobjects.as_dict()?, name))
+                else {
+                    return;
+                };
+                match doc.get(dict, "Subtype").and_then(Object::as_name) {
+                    Some("Image") => self.draw_image(dict, raw, resources),
+                    Some("Form") => self.draw_form(dict, raw, resources),
+                    _ => (),
+                }
+            }
+
+            // Text, shadings, marked content, etc aren't supported.
+            _ => log_dbg!("Ignoring PDF operator {:?}", operator),
+        }
+    }
+
+    fn draw_form(&mut self, dict: &'a Dict, raw: &[u8], resources: Option<&'a Dict>) {
+        // Guard against recursion.
+        if self.depth >= 16 {
+            return;
+        }
+        let Some(content) = decode_stream(dict, raw) else {
+            return;
+        };
+        let doc = self.doc;
+
+        let saved_state = self.state.clone();
+        let saved_path = std::mem::take(&mut self.path);
+        if let Some(matrix) = doc.get(dict, "Matrix").and_then(Matrix::from_object) {
+            self.state.ctm = matrix.then(self.state.ctm);
+        }
+        if let Some(bbox) = doc.get_rect(dict, "BBox") {
+            self.state.clip = intersect(self.state.clip, transform_rect(self.state.ctm, bbox));
+        }
+        let form_resources = doc.get(dict, "Resources").and_then(Object::as_dict);
+
+        self.depth += 1;
+        self.run(&content, form_resources.or(resources));
+        self.depth -= 1;
+
+        self.state = saved_state;
+        self.path = saved_path;
+    }
+
+    fn draw_image(&mut self, dict: &Dict, raw: &[u8], resources: Option<&Dict>) {
+        let Some(image) = self.decode_image(dict, raw, resources) else {
+            return;
+        };
+        let Some(inverse) = self.state.ctm.invert() else {
+            return;
+        };
+        // The image fills the unit square in user space.
+        let [x0, y0, x1, y1] = intersect(
+            transform_rect(self.state.ctm, [0.0, 0.0, 1.0, 1.0]),
+            self.state.clip,
+        );
+        for y in Canvas::pixel_range(y0, y1, self.canvas.height) {
+            for x in Canvas::pixel_range(x0, x1, self.canvas.width) {
+                let (u, v) = inverse.apply((x as f32 + 0.5, y as f32 + 0.5));
+                if !((0.0..1.0).contains(&u) && (0.0..1.0).contains(&v)) {
+                    continue;
+                }
+                // The first row of the image is at the top (v = 1).
+                let column = ((u * image.width as f32) as usize).min(image.width - 1);
+                let row = ((1.0 - v) * image.height as f32) as usize;
+                let row = row.min(image.height - 1);
+                let (r, g, b, a) = image.pixels[row * image.width + column];
+                let color = (r, g, b, a * self.state.fill_alpha);
+                self.canvas.blend(x, y, color, 1.0);
+            }
+        }
+    }
+
+    fn decode_image(
+        &self,
+        dict: &Dict,
+        raw: &[u8],
+        resources: Option<&Dict>,
+    ) -> Option<DecodedImage> {
+        let doc = self.doc;
+        let get_number = |key: &str| doc.get(dict, key).and_then(Object::as_number);
+        let data = decode_stream(dict, raw)?;
+
+        let filters = match doc.get(dict, "Filter") {
+            Some(Object::Array(filters)) => filters.last(),
+            filter => filter,
+        };
+        let mut image = if name_is(filters, "DCTDecode") || name_is(filters, "DCT") {
+            let jpeg = Image::from_bytes(&data).ok()?;
+            let (width, height) = jpeg.dimensions();
+            DecodedImage {
+                width: width as usize,
+                height: height as usize,
+                pixels: jpeg
+                    .pixels()
+                    .chunks(4)
+                    .map(|pixel| {
+                        let [r, g, b, _] = [0, 1, 2, 3].map(|i| pixel[i] as f32 / 255.0);
+                        (r, g, b, 1.0)
+                    })
+                    .collect(),
+            }
+        } else {
+            let width = get_number("Width")? as usize;
+            let height = get_number("Height")? as usize;
+            let bits = get_number("BitsPerComponent").unwrap_or(1.0) as usize;
+            // Avoid unreasonably large allocations for corrupt files.
+            if width == 0
+                || height == 0
+                || width * height > 4096 * 4096
+                || ![1, 2, 4, 8].contains(&bits)
+            {
+                return None;
+            }
+            let is_mask = matches!(doc.get(dict, "ImageMask"), Some(Object::Bool(true)));
+            let space = if is_mask {
+                ColorSpace::Gray
+            } else {
+                ColorSpace::from_object(doc, doc.get(dict, "ColorSpace")?, resources, 0)
+            };
+            let components = space.component_count().max(1);
+            let max_value = ((1 << bits) - 1) as f32;
+            let row_len = (width * components * bits).div_ceil(8);
+            let sample = |row: usize, index: usize| -> f32 {
+                let bit = index * bits;
+                let byte = data.get(row * row_len + bit / 8).copied().unwrap_or(0);
+                let value = (byte >> (8 - bits - bit % 8)) & (max_value as u8);
+                value as f32
+            };
+            // A Decode array of [1 0] inverts the samples.
+            let inverted = doc
+                .get(dict, "Decode")
+                .and_then(Object::as_array)
+                .and_then(|decode| decode.first())
+                .and_then(Object::as_number)
+                == Some(1.0);
+
+            let mut pixels = Vec::with_capacity(width * height);
+            for row in 0..height {
+                for column in 0..width {
+                    let samples: Vec<f32> = (0..components)
+                        .map(|i| {
+                            let value = sample(row, column * components + i);
+                            let value = if inverted { max_value - value } else { value };
+                            if let ColorSpace::Indexed(..) = space {
+                                value
+                            } else {
+                                value / max_value
+                            }
+                        })
+                        .collect();
+                    pixels.push(if is_mask {
+                        // Stencil masks paint the fill color where the sample
+                        // is 0.
+                        let (r, g, b) = self.state.fill_color?;
+                        (r, g, b, 1.0 - samples[0])
+                    } else {
+                        let (r, g, b) = space.to_rgb(&samples).unwrap_or((0.0, 0.0, 0.0));
+                        (r, g, b, 1.0)
+                    });
+                }
+            }
+            DecodedImage {
+                width,
+                height,
+                pixels,
+            }
+        };
+
+        // Soft masks provide an alpha channel.
+        if let Some(Object::Stream(mask_dict, mask_raw)) = doc.get(dict, "SMask") {
+            if let Some(mask) = self.decode_image(mask_dict, mask_raw, resources) {
+                for row in 0..image.height {
+                    for column in 0..image.width {
+                        let mask_row = row * mask.height / image.height;
+                        let mask_column = column * mask.width / image.width;
+                        let (alpha, _, _, _) = mask.pixels[mask_row * mask.width + mask_column];
+                        image.pixels[row * image.width + column].3 *= alpha;
+                    }
+                }
+            }
+        }
+
+        (image.width > 0 && image.height > 0).then_some(image)
+    }
+}
+
+/// Approximate the outline of a stroked path with a rectangle per line
+/// segment. Segments are extended by half the line width at each end, which
+/// covers the gaps at joins.
+fn stroke_polygons(path: &[Subpath], half_width: f32) -> Vec<Vec<(f32, f32)>> {
+    let mut polygons = Vec::new();
+    for subpath in path {
+        let mut points = subpath.points.clone();
+        if subpath.closed {
+            points.push(points[0]);
+        }
+        for segment in points.windows(2) {
+            let ((x0, y0), (x1, y1)) = (segment[0], segment[1]);
+            let length = (x1 - x0).hypot(y1 - y0);
+            if length == 0.0 {
+                continue;
+            }
+            let (ux, uy) = (
+                (x1 - x0) / length * half_width,
+                (y1 - y0) / length * half_width,
+            );
+            // All the rectangles have the same winding direction, so the
+            // non-zero rule gives their union.
+            let (nx, ny) = (-uy, ux);
+            let (x0, y0, x1, y1) = (x0 - ux, y0 - uy, x1 + ux, y1 + uy);
+            polygons.push(vec![
+                (x0 + nx, y0 + ny),
+                (x1 + nx, y1 + ny),
+                (x1 - nx, y1 - ny),
+                (x0 - nx, y0 - ny),
+            ]);
+        }
+    }
+    polygons
+}
+
+/// Skip over an inline image (`BI ... ID <data> EI`), which isn't supported.
+fn skip_inline_image(parser: &mut Parser) {
+    while let Some(object) = parser.parse_object() {
+        if matches!(object, Object::Operator(ref operator) if operator == "ID") {
+            break;
+        }
+    }
+    // The data is binary, so look for "EI" surrounded by whitespace.
+    let data = parser.data;
+    let mut pos = parser.pos;
+    while pos + 2 <= data.len() {
+        if &data[pos..pos + 2] == b"EI"
+            && data[pos - 1].is_ascii_whitespace()
+            && !matches!(data.get(pos + 2), Some(b) if !b.is_ascii_whitespace())
+        {
+            parser.pos = pos + 2;
+            return;
+        }
+        pos += 1;
+    }
+    parser.pos = data.len();
+}
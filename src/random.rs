@@ -0,0 +1,106 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Source of randomness for the guest.
+//!
+//! All randomness the app can observe (`arc4random()`, `/dev/urandom`, etc)
+//! comes from the [Rng] in the [crate::Environment], which is seeded once per
+//! run. The seed is logged at startup, and can be set with `--random-seed=`,
+//! so that a run (e.g. one replayed with `--replay-input=`) can be reproduced.
+//!
+//! `rand()` and `random()` are an exception: they must give the same sequence
+//! for the same seed set by the app, so they only use [xorshift32].
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::SystemTime;
+
+#[derive(Debug, Clone)]
+pub struct Rng {
+    seed: u64,
+    state: u64,
+}
+impl Rng {
+    /// Create a generator with the given seed, or one taken from the host's
+    /// entropy if there is none.
+    pub fn new(seed: Option<u64>) -> Rng {
+        let seed = seed.unwrap_or_else(host_entropy);
+        Rng { seed, state: seed }
+    }
+
+    /// The seed this generator was created with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        // SplitMix64: https://prng.di.unimi.it/splitmix64.c
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        (self.next_u64() >> 32) as u32
+    }
+
+    pub fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(8) {
+            let value = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&value[..chunk.len()]);
+        }
+    }
+
+    /// Create an independent generator whose output is determined by this
+    /// one, e.g. for an open `/dev/urandom` file.
+    pub fn fork(&mut self) -> Rng {
+        Rng::new(Some(self.next_u64()))
+    }
+}
+
+/// Get a seed that is different each run.
+fn host_entropy() -> u64 {
+    // The standard library seeds hash maps with the system's random number
+    // source, which saves on a dependency.
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(time) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u128(time.as_nanos());
+    }
+    hasher.finish()
+}
+
+/// A tiny generator with 32 bits of state, for `rand()` and `random()`.
+pub fn xorshift32(state: u32) -> u32 {
+    // The state must not be zero for this algorithm to work. This also makes
+    // the default seed be 1, which matches the C standard.
+    let mut state: u32 = state.max(1);
+    // https://en.wikipedia.org/wiki/Xorshift#Example_implementation
+    // xorshift32 is not a good random number generator, but it is cute one!
+    // It's not like anyone expects the C stdlib `rand()` to be good.
+    state ^= state << 13;
+    state ^= state >> 17;
+    state ^= state << 5;
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_is_reproducible() {
+        let mut a = Rng::new(Some(1234));
+        let mut b = Rng::new(Some(1234));
+        let mut a_bytes = [0u8; 13];
+        let mut b_bytes = [0u8; 13];
+        a.fill_bytes(&mut a_bytes);
+        b.fill_bytes(&mut b_bytes);
+        assert_eq!(a_bytes, b_bytes);
+        assert_eq!(a.fork().next_u32(), b.fork().next_u32());
+        assert_ne!(a.next_u64(), Rng::new(Some(1235)).next_u64());
+    }
+}
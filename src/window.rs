@@ -12,18 +12,30 @@
 //! window system interaction in general, because it is assumed only one window
 //! will be needed for the runtime of the app.
 
-use crate::gles::present::present_frame;
+mod console;
+pub mod input_recording;
+mod screen_recording;
+mod screenshot;
+
+use crate::gles::present::{present_frame, present_overlay};
 use crate::gles::{create_gles1_ctx, GLES};
 use crate::image::Image;
 use crate::matrix::Matrix;
 use crate::options::Options;
+use crate::paths;
+use console::Console;
+use input_recording::{InputPlayer, InputRecorder};
+use screen_recording::ScreenRecorder;
 use sdl2::mouse::MouseButton;
 use sdl2::pixels::PixelFormatEnum;
 use sdl2::surface::Surface;
+use sdl2::video::SwapInterval;
+use std::cell::Cell;
 use std::collections::{HashMap, VecDeque};
 use std::env;
 use std::f32::consts::FRAC_PI_2;
 use std::num::NonZeroU32;
+use std::path::PathBuf;
 use std::time::{Duration, Instant};
 
 #[derive(Copy, Clone, Eq, PartialEq)]
@@ -72,7 +84,9 @@ fn set_sdl2_orientation(orientation: DeviceOrientation) {
 pub enum FingerId {
     Mouse,
     Touch(i64),
-    VirtualCursor,
+    /// Virtual cursor of a player (see `--controller-region=`), numbered from
+    /// 0. When there are no regions, all controllers share cursor 0.
+    VirtualCursor(usize),
     ButtonToTouch(crate::options::Button),
 }
 pub type Coords = (f32, f32);
@@ -100,17 +114,60 @@ pub enum Event {
     /// User pressed F12, requesting that execution be paused and the debugger
     /// take over.
     EnterDebugger,
+    /// User pressed the memory report key (see `--memory-report-key=`).
+    MemoryReport,
+    /// User pressed the heap snapshot key (see `--heap-snapshot-key=`).
+    HeapSnapshot,
+    /// User pressed F10, simulating a press of the device's Home button.
+    HomeButton,
+    /// User pressed F11, simulating a press of the device's Sleep/Wake (lock)
+    /// button.
+    LockButton,
     TextInput(TextInputEvent),
 }
 
 pub enum GLVersion {
     /// OpenGL ES 1.1
     GLES11,
+    /// OpenGL ES 2.0
+    GLES20,
     /// OpenGL 2.1 compatibility profile
     GL21Compat,
 }
 
-pub struct GLContext(sdl2::video::GLContext);
+pub struct GLContext {
+    gl_ctx: sdl2::video::GLContext,
+    /// The swap interval applies to the current context, so it can only be set
+    /// once the context has been made current.
+    swap_interval_set: Cell<bool>,
+}
+
+struct Controller {
+    sdl_controller: sdl2::controller::GameController,
+    /// Player number, starting from 0. This is assigned when the controller is
+    /// connected, using the lowest number that isn't taken.
+    player: usize,
+    /// Buttons mapped with `--button-to-touch=` that are being held. SDL
+    /// doesn't send button releases for a controller that is disconnected, so
+    /// these are released then.
+    held_buttons: Vec<crate::options::Button>,
+}
+
+fn translate_button(button: sdl2::controller::Button) -> Option<crate::options::Button> {
+    match button {
+        sdl2::controller::Button::DPadLeft => Some(crate::options::Button::DPadLeft),
+        sdl2::controller::Button::DPadUp => Some(crate::options::Button::DPadUp),
+        sdl2::controller::Button::DPadRight => Some(crate::options::Button::DPadRight),
+        sdl2::controller::Button::DPadDown => Some(crate::options::Button::DPadDown),
+        sdl2::controller::Button::Start => Some(crate::options::Button::Start),
+        sdl2::controller::Button::A => Some(crate::options::Button::A),
+        sdl2::controller::Button::B => Some(crate::options::Button::B),
+        sdl2::controller::Button::X => Some(crate::options::Button::X),
+        sdl2::controller::Button::Y => Some(crate::options::Button::Y),
+        sdl2::controller::Button::LeftShoulder => Some(crate::options::Button::LeftShoulder),
+        _ => None,
+    }
+}
 
 fn surface_from_image(image: &Image) -> Surface {
     let src_pixels = image.pixels();
@@ -152,16 +209,50 @@ pub struct Window {
     /// [Self::rotatable_fullscreen] returns [true].
     fullscreen: bool,
     scale_hack: NonZeroU32,
+    /// Copy of `vsync` on [Options].
+    vsync: bool,
     internal_gl_ctx: Option<Box<dyn GLES>>,
+    /// The window title without any status (see [Self::set_title_status]).
+    base_title: String,
+    /// The app's launch image. This is displayed until the app presents its
+    /// first frame, and then faded out (see [Self::draw_splash_fade]).
     splash_image: Option<Image>,
+    /// Set when the first frame that isn't the splash screen is presented.
+    splash_fade_started: Option<Instant>,
+    /// Used to measure the boot time (see [Self::swap_window]).
+    created_at: Instant,
+    first_frame_presented: bool,
     device_orientation: DeviceOrientation,
     app_gl_ctx_no_longer_current: bool,
     controller_ctx: sdl2::GameControllerSubsystem,
-    controllers: Vec<sdl2::controller::GameController>,
+    controllers: Vec<Controller>,
+    /// Copy of `controller_rumble` on [Options].
+    controller_rumble: f32,
     _sensor_ctx: sdl2::SensorSubsystem,
     accelerometer: Option<sdl2::sensor::Sensor>,
-    virtual_cursor_last: Option<(f32, f32, bool, bool)>,
-    virtual_cursor_last_unsticky: Option<(f32, f32, Instant)>,
+    /// Last state of each virtual cursor (see [FingerId::VirtualCursor]).
+    virtual_cursor_last: HashMap<usize, (f32, f32, bool, bool)>,
+    virtual_cursor_last_unsticky: HashMap<usize, (f32, f32, Instant)>,
+    screen_recorder: Option<ScreenRecorder>,
+    /// [None] if the key name in [Options] isn't valid.
+    screenshot_key: Option<sdl2::keyboard::Keycode>,
+    screenshot_dir: PathBuf,
+    /// Set when the screenshot key is pressed, so that the next frame is saved.
+    screenshot_requested: bool,
+    /// [None] if the key name in [Options] isn't valid.
+    console_key: Option<sdl2::keyboard::Keycode>,
+    console: Console,
+    /// [None] if the key name in [Options] isn't valid.
+    memory_report_key: Option<sdl2::keyboard::Keycode>,
+    /// [None] if the key name in [Options] isn't valid.
+    heap_snapshot_key: Option<sdl2::keyboard::Keycode>,
+    /// Number of times events have been polled (see [Self::poll_for_events]).
+    /// This is the time unit used by input recordings.
+    input_tick: u64,
+    input_recorder: Option<InputRecorder>,
+    input_player: Option<InputPlayer>,
+    /// Events from [Self::input_player] that are due.
+    replayed_events: VecDeque<Event>,
 }
 impl Window {
     /// Returns [true] if touchHLE is running on a device where we should always
@@ -276,6 +367,35 @@ impl Window {
         #[cfg(target_os = "macos")]
         let max_height = window.size().1;
 
+        let screenshot_key = sdl2::keyboard::Keycode::from_name(&options.screenshot_key);
+        if screenshot_key.is_none() {
+            log!(
+                "Warning: {:?} is not a valid key name for --screenshot-key=, screenshots are disabled.",
+                options.screenshot_key
+            );
+        }
+        let console_key = sdl2::keyboard::Keycode::from_name(&options.console_key);
+        if console_key.is_none() {
+            log!(
+                "Warning: {:?} is not a valid key name for --console-key=, the console is disabled.",
+                options.console_key
+            );
+        }
+        let memory_report_key = sdl2::keyboard::Keycode::from_name(&options.memory_report_key);
+        if memory_report_key.is_none() {
+            log!(
+                "Warning: {:?} is not a valid key name for --memory-report-key=, memory reports are disabled.",
+                options.memory_report_key
+            );
+        }
+        let heap_snapshot_key = sdl2::keyboard::Keycode::from_name(&options.heap_snapshot_key);
+        if heap_snapshot_key.is_none() {
+            log!(
+                "Warning: {:?} is not a valid key name for --heap-snapshot-key=, heap snapshots are disabled.",
+                options.heap_snapshot_key
+            );
+        }
+
         let mut window = Window {
             _sdl_ctx: sdl_ctx,
             video_ctx,
@@ -291,23 +411,47 @@ impl Window {
             viewport_y_offset: 0,
             fullscreen,
             scale_hack,
+            vsync: options.vsync,
             internal_gl_ctx: None,
+            base_title: title.to_string(),
             splash_image: launch_image,
+            splash_fade_started: None,
+            created_at: Instant::now(),
+            first_frame_presented: false,
             device_orientation,
             app_gl_ctx_no_longer_current: false,
             controller_ctx,
             controllers: Vec::new(),
+            controller_rumble: options.controller_rumble,
             _sensor_ctx: sensor_ctx,
             accelerometer,
-            virtual_cursor_last: None,
-            virtual_cursor_last_unsticky: None,
+            virtual_cursor_last: HashMap::new(),
+            virtual_cursor_last_unsticky: HashMap::new(),
+            screen_recorder: options
+                .record_screen
+                .clone()
+                .map(|path| ScreenRecorder::new(path, options.refresh_rate)),
+            screenshot_key,
+            screenshot_dir: options
+                .screenshot_dir
+                .clone()
+                .unwrap_or_else(|| paths::user_data_base_path().join(paths::SCREENSHOTS_DIR)),
+            screenshot_requested: false,
+            console_key,
+            console: Console::default(),
+            memory_report_key,
+            heap_snapshot_key,
+            input_tick: 0,
+            input_recorder: None,
+            input_player: None,
+            replayed_events: VecDeque::new(),
         };
 
         // Set up OpenGL ES context used for splash screen and app UI rendering
         // (see src/frameworks/core_animation/composition.rs). OpenGL ES is used
         // because SDL2 won't let us use more than one graphics API in the same
         // window, and we also need OpenGL ES for the app's own rendering.
-        let gl_ctx = create_gles1_ctx(&mut window, options);
+        let (gl_ctx, _) = create_gles1_ctx(&mut window, options, None);
         gl_ctx.make_current(&window);
         log!("Driver info: {}", unsafe { gl_ctx.driver_description() });
         window.internal_gl_ctx = Some(gl_ctx);
@@ -334,6 +478,17 @@ impl Window {
         }
         self.last_polled = now;
 
+        self.input_tick += 1;
+        if let Some(player) = self.input_player.as_mut() {
+            while let Some(event) = player.pop_due(self.input_tick) {
+                self.replayed_events.push_back(event);
+            }
+            if player.is_finished() {
+                echo!("Input replay finished, live input is now enabled.");
+                self.input_player = None;
+            }
+        }
+
         fn transform_input_coords(
             window: &Window,
             (in_x, in_y): (f32, f32),
@@ -358,29 +513,18 @@ impl Window {
             let out_y = (y + 0.5) * out_h as f32;
             (out_x, out_y)
         }
-        fn translate_button(button: sdl2::controller::Button) -> Option<crate::options::Button> {
-            match button {
-                sdl2::controller::Button::DPadLeft => Some(crate::options::Button::DPadLeft),
-                sdl2::controller::Button::DPadUp => Some(crate::options::Button::DPadUp),
-                sdl2::controller::Button::DPadRight => Some(crate::options::Button::DPadRight),
-                sdl2::controller::Button::DPadDown => Some(crate::options::Button::DPadDown),
-                sdl2::controller::Button::Start => Some(crate::options::Button::Start),
-                sdl2::controller::Button::A => Some(crate::options::Button::A),
-                sdl2::controller::Button::B => Some(crate::options::Button::B),
-                sdl2::controller::Button::X => Some(crate::options::Button::X),
-                sdl2::controller::Button::Y => Some(crate::options::Button::Y),
-                sdl2::controller::Button::LeftShoulder => {
-                    Some(crate::options::Button::LeftShoulder)
-                }
-                _ => None,
-            }
-        }
         fn finger_absolute_coords(window: &Window, (x, y): (f32, f32)) -> (f32, f32) {
             let (screen_width, screen_height) = window.window.drawable_size();
             (screen_width as f32 * x, screen_height as f32 * y)
         }
 
-        let mut controller_updated = false;
+        // Virtual cursors that might have moved or been pressed or released.
+        let mut updated_cursors: Vec<usize> = Vec::new();
+        fn cursor_updated(updated_cursors: &mut Vec<usize>, cursor: Option<usize>) {
+            if let Some(cursor) = cursor.filter(|cursor| !updated_cursors.contains(cursor)) {
+                updated_cursors.push(cursor);
+            }
+        }
         // event_pump doesn't have a method to peek on events
         // so, we keep track of an unconsumed one from a previous loop iteration
         // FIXME: use peek_event() from even_subsystem
@@ -432,43 +576,67 @@ impl Window {
                     Event::TouchesUp(HashMap::from([(FingerId::Mouse, coords)]))
                 }
                 E::ControllerDeviceAdded { which, .. } => {
-                    self.controller_added(which);
+                    self.controller_added(which, options);
                     continue;
                 }
                 E::ControllerDeviceRemoved { which, .. } => {
-                    self.controller_removed(which);
-                    continue;
+                    let Some((cursor, held_buttons)) = self.controller_removed(which, options)
+                    else {
+                        continue;
+                    };
+                    // The cursor is released if nothing else is holding it.
+                    cursor_updated(&mut updated_cursors, Some(cursor));
+                    let released: HashMap<_, _> = held_buttons
+                        .into_iter()
+                        .filter_map(|button| {
+                            let &(x, y) = options.button_to_touch.get(&button)?;
+                            let coords = transform_input_coords(self, (x, y), true);
+                            Some((FingerId::ButtonToTouch(button), coords))
+                        })
+                        .collect();
+                    if released.is_empty() {
+                        continue;
+                    }
+                    Event::TouchesUp(released)
                 }
                 // Note that accelerometer simulation with analog sticks is
                 // handled with polling, rather than being event-based.
-                E::ControllerButtonUp { button, .. } | E::ControllerButtonDown { button, .. } => {
-                    controller_updated = true;
+                E::ControllerButtonUp { which, button, .. }
+                | E::ControllerButtonDown { which, button, .. } => {
+                    let is_down = matches!(event, E::ControllerButtonDown { .. });
+                    cursor_updated(
+                        &mut updated_cursors,
+                        self.cursor_for_controller(which, options),
+                    );
                     let Some(button) = translate_button(button) else {
                         continue;
                     };
+                    if let Some(controller) = self
+                        .controllers
+                        .iter_mut()
+                        .find(|controller| controller.sdl_controller.instance_id() == which)
+                    {
+                        controller.held_buttons.retain(|&held| held != button);
+                        if is_down {
+                            controller.held_buttons.push(button);
+                        }
+                    }
                     let Some(&(x, y)) = options.button_to_touch.get(&button) else {
                         continue;
                     };
-                    match event {
-                        E::ControllerButtonUp { .. } => {
-                            let coords = transform_input_coords(self, (x, y), true);
-                            Event::TouchesUp(HashMap::from([(
-                                FingerId::ButtonToTouch(button),
-                                coords,
-                            )]))
-                        }
-                        E::ControllerButtonDown { .. } => {
-                            let coords = transform_input_coords(self, (x, y), true);
-                            Event::TouchesDown(HashMap::from([(
-                                FingerId::ButtonToTouch(button),
-                                coords,
-                            )]))
-                        }
-                        _ => unreachable!(),
+                    let coords = transform_input_coords(self, (x, y), true);
+                    let map = HashMap::from([(FingerId::ButtonToTouch(button), coords)]);
+                    if is_down {
+                        Event::TouchesDown(map)
+                    } else {
+                        Event::TouchesUp(map)
                     }
                 }
-                E::ControllerAxisMotion { .. } => {
-                    controller_updated = true;
+                E::ControllerAxisMotion { which, .. } => {
+                    cursor_updated(
+                        &mut updated_cursors,
+                        self.cursor_for_controller(which, options),
+                    );
                     continue;
                 }
                 E::AppWillEnterBackground { .. } => {
@@ -574,6 +742,56 @@ impl Window {
                         _ => unreachable!(),
                     }
                 }
+                E::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } if Some(keycode) == self.screenshot_key => {
+                    // This isn't an event for the app, the next frame is
+                    // saved when it's presented (see Self::record_frame).
+                    echo!("Taking a screenshot of the next frame.");
+                    self.screenshot_requested = true;
+                    continue;
+                }
+                E::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } if Some(keycode) == self.console_key => {
+                    // This isn't an event for the app either.
+                    self.console.toggle();
+                    continue;
+                }
+                E::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } if Some(keycode) == self.memory_report_key => Event::MemoryReport,
+                E::KeyDown {
+                    keycode: Some(keycode),
+                    repeat: false,
+                    ..
+                } if Some(keycode) == self.heap_snapshot_key => Event::HeapSnapshot,
+                // While the console is open, it takes the scrolling input.
+                E::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::PageUp),
+                    ..
+                } if self.console.is_visible() => {
+                    self.console.scroll_by(console::SCROLL_PAGE_LINES);
+                    continue;
+                }
+                E::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::PageDown),
+                    ..
+                } if self.console.is_visible() => {
+                    self.console.scroll_by(-console::SCROLL_PAGE_LINES);
+                    continue;
+                }
+                E::MouseWheel { y, .. } if self.console.is_visible() => {
+                    self.console
+                        .scroll_by(y as isize * console::SCROLL_WHEEL_LINES);
+                    continue;
+                }
                 E::KeyDown {
                     keycode: Some(sdl2::keyboard::Keycode::F12),
                     ..
@@ -583,6 +801,16 @@ impl Window {
                     echo!("F12 pressed, EnterDebugger event queued.");
                     Event::EnterDebugger
                 }
+                E::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::F10),
+                    repeat: false,
+                    ..
+                } => Event::HomeButton,
+                E::KeyDown {
+                    keycode: Some(sdl2::keyboard::Keycode::F11),
+                    repeat: false,
+                    ..
+                } => Event::LockButton,
                 E::KeyDown {
                     keycode: Some(sdl2::keyboard::Keycode::Backspace),
                     ..
@@ -605,58 +833,165 @@ impl Window {
             })
         }
 
-        if controller_updated {
+        for cursor in updated_cursors {
             let (new_x, new_y, pressed, pressed_changed, moved) =
-                self.update_virtual_cursor(options);
+                self.update_virtual_cursor(options, cursor);
+            let coords = transform_input_coords(self, (new_x, new_y), false);
+            let map = HashMap::from([(FingerId::VirtualCursor(cursor), coords)]);
             self.event_queue
                 .push_back(match (pressed, pressed_changed, moved) {
-                    (true, true, _) => {
-                        let coords = transform_input_coords(self, (new_x, new_y), false);
-                        Event::TouchesDown(HashMap::from([(FingerId::VirtualCursor, coords)]))
-                    }
-                    (false, true, _) => {
-                        let coords = transform_input_coords(self, (new_x, new_y), false);
-                        Event::TouchesUp(HashMap::from([(FingerId::VirtualCursor, coords)]))
-                    }
-                    (true, _, true) => {
-                        let coords = transform_input_coords(self, (new_x, new_y), false);
-                        Event::TouchesMove(HashMap::from([(FingerId::VirtualCursor, coords)]))
-                    }
-                    _ => return,
+                    (true, true, _) => Event::TouchesDown(map),
+                    (false, true, _) => Event::TouchesUp(map),
+                    (true, _, true) => Event::TouchesMove(map),
+                    _ => continue,
                 });
         }
     }
 
+    /// Set up recording and/or replay of input according to the options. This
+    /// needs information about the app, so it's separate from [Self::new].
+    pub fn set_up_input_recording(
+        &mut self,
+        options: &Options,
+        header: input_recording::Header,
+    ) -> Result<(), String> {
+        if let Some(path) = &options.replay_input {
+            self.input_player = Some(InputPlayer::new(path, &header)?);
+        }
+        if let Some(path) = &options.record_input {
+            self.input_recorder = Some(InputRecorder::new(path, &header)?);
+        }
+        Ok(())
+    }
+
     /// Pop an event from the queue (in FIFO order, except for high priority
     /// events)
     pub fn pop_event(&mut self) -> Option<Event> {
-        self.high_priority_event
-            .take()
-            .or_else(|| self.event_queue.pop_front())
+        let event = self.high_priority_event.take().or_else(|| {
+            if let Some(event) = self.replayed_events.pop_front() {
+                return Some(event);
+            }
+            loop {
+                let event = self.event_queue.pop_front()?;
+                // Live input would interfere with the replay.
+                if self.input_player.is_none() || !input_recording::is_recordable(&event) {
+                    return Some(event);
+                }
+            }
+        })?;
+        if let Some(recorder) = self.input_recorder.as_mut() {
+            recorder.record(self.input_tick, &event);
+        }
+        Some(event)
     }
 
-    fn controller_added(&mut self, joystick_idx: u32) {
-        let Ok(controller) = self.controller_ctx.open(joystick_idx) else {
+    fn controller_added(&mut self, joystick_idx: u32, options: &Options) {
+        let Ok(mut sdl_controller) = self.controller_ctx.open(joystick_idx) else {
             log!("Warning: A new controller was connected, but it couldn't be accessed!");
             return;
         };
+        let player = (0..)
+            .find(|&player| {
+                !self
+                    .controllers
+                    .iter()
+                    .any(|controller| controller.player == player)
+            })
+            .unwrap();
+        if let Some(&(red, green, blue)) = options.controller_led_colors.get(player) {
+            if let Err(e) = sdl_controller.set_led(red, green, blue) {
+                log_dbg!("Couldn't set LED color of {}: {}", sdl_controller.name(), e);
+            }
+        }
         log!(
-            "New controller connected: {}. Left stick = device tilt. Right stick = touch input (press the stick or shoulder button to tap/hold).",
-            controller.name()
+            "New controller connected: {} (player {}). Left stick = device tilt. Right stick = touch input (press the stick or shoulder button to tap/hold).",
+            sdl_controller.name(),
+            player + 1
         );
-        self.controllers.push(controller);
+        self.controllers.push(Controller {
+            sdl_controller,
+            player,
+            held_buttons: Vec::new(),
+        });
     }
-    fn controller_removed(&mut self, instance_id: u32) {
-        let Some(idx) = self
+    /// Forget about a disconnected controller. Returns its virtual cursor and
+    /// the `--button-to-touch=` buttons it was holding.
+    fn controller_removed(
+        &mut self,
+        instance_id: u32,
+        options: &Options,
+    ) -> Option<(usize, Vec<crate::options::Button>)> {
+        let idx = self
             .controllers
             .iter()
-            .position(|controller| controller.instance_id() == instance_id)
-        else {
-            return;
-        };
+            .position(|controller| controller.sdl_controller.instance_id() == instance_id)?;
         let controller = self.controllers.remove(idx);
-        log!("Warning: Controller disconnected: {}", controller.name());
+        log!(
+            "Warning: Controller disconnected: {} (player {})",
+            controller.sdl_controller.name(),
+            controller.player + 1
+        );
+        Some((
+            Self::cursor_for_player(controller.player, options),
+            controller.held_buttons,
+        ))
+    }
+    /// Get the virtual cursor that a player controls. Without any regions, all
+    /// players share one cursor.
+    fn cursor_for_player(player: usize, options: &Options) -> usize {
+        if options.controller_regions.is_empty() {
+            0
+        } else {
+            player
+        }
+    }
+    fn cursor_for_controller(&self, instance_id: u32, options: &Options) -> Option<usize> {
+        self.controllers
+            .iter()
+            .find(|controller| controller.sdl_controller.instance_id() == instance_id)
+            .map(|controller| Self::cursor_for_player(controller.player, options))
+    }
+    /// Simulate the device's vibration motor by rumbling all connected
+    /// controllers. Returns [false] if there's nothing that can rumble.
+    pub fn vibrate(&mut self, duration: Duration) -> bool {
+        if self.controller_rumble == 0.0 {
+            return false;
+        }
+        let duration_ms = duration.as_millis().try_into().unwrap_or(u32::MAX);
+        // The iPhone's motor is a single one, so both of the controller's
+        // motors are used equally.
+        let strength = (self.controller_rumble * f32::from(u16::MAX)) as u16;
+        let mut rumbled = false;
+        for controller in &mut self.controllers {
+            let controller = &mut controller.sdl_controller;
+            match controller.set_rumble(strength, strength, duration_ms) {
+                Ok(()) => rumbled = true,
+                Err(e) => log_dbg!("Couldn't rumble {}: {}", controller.name(), e),
+            }
+        }
+        rumbled
+    }
+    /// Get the text on the host's clipboard, if there is any.
+    pub fn clipboard_text(&self) -> Option<String> {
+        let clipboard = self.video_ctx.clipboard();
+        if !clipboard.has_clipboard_text() {
+            return None;
+        }
+        match clipboard.clipboard_text() {
+            Ok(text) => Some(text),
+            Err(e) => {
+                log!("Warning: Couldn't read the clipboard: {}", e);
+                None
+            }
+        }
+    }
+    /// Replace the contents of the host's clipboard with some text.
+    pub fn set_clipboard_text(&self, text: &str) {
+        if let Err(e) = self.video_ctx.clipboard().set_clipboard_text(text) {
+            log!("Warning: Couldn't write to the clipboard: {}", e);
+        }
     }
+
     pub fn print_accelerometer_notice(&self) {
         log!("This app uses the accelerometer.");
         if !self.controllers.is_empty() {
@@ -694,7 +1029,7 @@ impl Window {
         }
 
         // Get left analog stick input. The range is [-1, 1] on each axis.
-        let (x, y, _) = self.get_controller_stick(options, true);
+        let (x, y, _) = self.get_controller_stick(options, true, None);
 
         // Correct for window rotation
         let [x, y] = self.rotation_matrix().transform([x, y]);
@@ -732,31 +1067,38 @@ impl Window {
         (x, y, z)
     }
 
-    /// For use when redrawing the screen: Get the cached on-screen position and
-    /// press state of the analog stick-controlled virtual cursor, if it is
+    /// For use when redrawing the screen: Get the cached on-screen positions
+    /// and press states of the analog stick-controlled virtual cursors that are
     /// visible.
-    pub fn virtual_cursor_visible_at(&self) -> Option<(f32, f32, bool)> {
-        let (x, y, pressed, visible) = self.virtual_cursor_last?;
-        if visible {
-            // When stickyness is in use, the visual cursor movement appears
-            // uncomfortably choppy. Showing the un-sticky position is a bit
-            // misleading but it *feels* better, and it is documented.
-            if let Some((x_unsticky, y_unsticky, _time)) = self.virtual_cursor_last_unsticky {
-                Some((x_unsticky, y_unsticky, pressed))
-            } else {
-                Some((x, y, pressed))
-            }
-        } else {
-            None
-        }
+    pub fn virtual_cursors_visible_at(&self) -> Vec<(f32, f32, bool)> {
+        self.virtual_cursor_last
+            .iter()
+            .filter(|&(_, &(_, _, _, visible))| visible)
+            .map(|(cursor, &(x, y, pressed, _))| {
+                // When stickyness is in use, the visual cursor movement appears
+                // uncomfortably choppy. Showing the un-sticky position is a bit
+                // misleading but it *feels* better, and it is documented.
+                if let Some(&(x_unsticky, y_unsticky, _time)) =
+                    self.virtual_cursor_last_unsticky.get(cursor)
+                {
+                    (x_unsticky, y_unsticky, pressed)
+                } else {
+                    (x, y, pressed)
+                }
+            })
+            .collect()
     }
 
-    /// Update the virtual cursor's position, click state and visibility, then
+    /// Update a virtual cursor's position, click state and visibility, then
     /// return the new position, pressed state, whether the press state changed
     /// and whether the cursor moved.
-    fn update_virtual_cursor(&mut self, options: &Options) -> (f32, f32, bool, bool, bool) {
+    fn update_virtual_cursor(
+        &mut self,
+        options: &Options,
+        cursor: usize,
+    ) -> (f32, f32, bool, bool, bool) {
         // Get right analog stick input. The range is [-1, 1] on each axis.
-        let (x, y, pressed) = self.get_controller_stick(options, false);
+        let (x, y, pressed) = self.get_controller_stick(options, false, Some(cursor));
 
         // The cursor is intended to only show up once you move the analog stick
         // out of its deadzone, or while the button is held.
@@ -767,6 +1109,11 @@ impl Window {
         // rectangular shape of the screen from that circle within the square.
         let (vx, vy, vw, vh) = self.viewport();
         let (vx, vy, vw, vh) = (vx as f32, vy as f32, vw as f32, vh as f32);
+        // A player's cursor is confined to their region, if they have one.
+        let (vx, vy, vw, vh) = match options.controller_regions.get(cursor) {
+            Some(&(rx, ry, rw, rh)) => (vx + rx * vw, vy + ry * vh, rw * vw, rh * vh),
+            None => (vx, vy, vw, vh),
+        };
 
         let (x, y) = {
             // Use Pythagoras's theorem to find the largest size the rectangle
@@ -784,8 +1131,11 @@ impl Window {
         let x = (x / 2.0 + 0.5) * vw + vx;
         let y = (y / 2.0 + 0.5) * vh + vy;
 
-        let (old_x, old_y, old_pressed, _old_visible) =
-            self.virtual_cursor_last.unwrap_or_default();
+        let (old_x, old_y, old_pressed, _old_visible) = self
+            .virtual_cursor_last
+            .get(&cursor)
+            .copied()
+            .unwrap_or_default();
 
         let (x, y) = if let Some((smoothing_strength, sticky_radius)) =
             options.stabilize_virtual_cursor
@@ -794,6 +1144,8 @@ impl Window {
 
             let (old_x_unsticky, old_y_unsticky, old_time) = self
                 .virtual_cursor_last_unsticky
+                .get(&cursor)
+                .copied()
                 .unwrap_or((0.0, 0.0, new_time));
 
             let delta_t = new_time.saturating_duration_since(old_time).as_secs_f32();
@@ -813,7 +1165,8 @@ impl Window {
             let new_x_unsticky = smooth(old_x_unsticky, x);
             let new_y_unsticky = smooth(old_y_unsticky, y);
 
-            self.virtual_cursor_last_unsticky = Some((new_x_unsticky, new_y_unsticky, new_time));
+            self.virtual_cursor_last_unsticky
+                .insert(cursor, (new_x_unsticky, new_y_unsticky, new_time));
 
             // Make the reported position "sticky" within a certain radius, i.e.
             // if the new position's distance from the old one is within the
@@ -828,7 +1181,8 @@ impl Window {
             (x, y)
         };
 
-        self.virtual_cursor_last = Some((x, y, pressed, visible));
+        self.virtual_cursor_last
+            .insert(cursor, (x, y, pressed, visible));
 
         (
             x,
@@ -841,8 +1195,14 @@ impl Window {
 
     /// Get the summed X and Y positions and button state of the left or right
     /// analog stick of the game controllers. Each axis value is in the range
-    /// [-1, 1].
-    fn get_controller_stick(&self, options: &Options, left: bool) -> (f32, f32, bool) {
+    /// [-1, 1]. If `cursor` is given, only the controllers for that virtual
+    /// cursor are included.
+    fn get_controller_stick(
+        &self,
+        options: &Options,
+        left: bool,
+        cursor: Option<usize>,
+    ) -> (f32, f32, bool) {
         fn convert_axis(axis: i16, deadzone: f32) -> f32 {
             assert!(deadzone >= 0.0);
             let axis = ((axis as f32) / (i16::MAX as f32)).clamp(-1.0, 1.0);
@@ -853,7 +1213,13 @@ impl Window {
         let (mut x, mut y) = (0.0, 0.0);
         let mut pressed = false;
         for controller in &self.controllers {
+            if cursor
+                .is_some_and(|cursor| Self::cursor_for_player(controller.player, options) != cursor)
+            {
+                continue;
+            }
             use sdl2::controller::{Axis, Button};
+            let controller = &controller.sdl_controller;
             let (x_axis, y_axis, button1, button2) = if left {
                 (
                     Axis::LeftX,
@@ -879,13 +1245,24 @@ impl Window {
         (x, y, pressed)
     }
 
-    pub fn create_gl_context(&self, version: GLVersion) -> Result<GLContext, String> {
+    /// Create a new OpenGL context. If `share_with_current` is [true], the new
+    /// context will share objects (textures etc) with the current context.
+    pub fn create_gl_context(
+        &self,
+        version: GLVersion,
+        share_with_current: bool,
+    ) -> Result<GLContext, String> {
         let attr = self.video_ctx.gl_attr();
+        attr.set_share_with_current_context(share_with_current);
         match version {
             GLVersion::GLES11 => {
                 attr.set_context_version(1, 1);
                 attr.set_context_profile(sdl2::video::GLProfile::GLES);
             }
+            GLVersion::GLES20 => {
+                attr.set_context_version(2, 0);
+                attr.set_context_profile(sdl2::video::GLProfile::GLES);
+            }
             GLVersion::GL21Compat => {
                 attr.set_context_version(2, 1);
                 attr.set_context_profile(sdl2::video::GLProfile::Compatibility);
@@ -894,7 +1271,10 @@ impl Window {
 
         let gl_ctx = self.window.gl_create_context()?;
 
-        Ok(GLContext(gl_ctx))
+        Ok(GLContext {
+            gl_ctx,
+            swap_interval_set: Cell::new(false),
+        })
     }
 
     pub fn gl_get_proc_address(&self, procname: &str) -> *const std::ffi::c_void {
@@ -903,8 +1283,27 @@ impl Window {
         self.video_ctx.gl_get_proc_address(procname) as *const _
     }
 
-    pub unsafe fn make_gl_context_current(&self, gl_ctx: &GLContext) {
-        self.window.gl_make_current(&gl_ctx.0).unwrap();
+    pub unsafe fn make_gl_context_current(&self, gl_ctx: &GLContext) -> Result<(), String> {
+        self.window.gl_make_current(&gl_ctx.gl_ctx)?;
+        if self.vsync && !gl_ctx.swap_interval_set.replace(true) {
+            if let Err(e) = self.video_ctx.gl_set_swap_interval(SwapInterval::VSync) {
+                log!("Warning: couldn't enable v-sync: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Make no OpenGL context current, e.g. when the app unsets its context.
+    pub fn make_no_gl_context_current(&self) {
+        // rust-sdl2 has no safe wrapper for this.
+        let result =
+            unsafe { sdl2_sys::SDL_GL_MakeCurrent(self.window.raw(), std::ptr::null_mut()) };
+        if result != 0 {
+            log!(
+                "Warning: couldn't unset current OpenGL context: {}",
+                sdl2::get_error()
+            );
+        }
     }
 
     /// Retrieve and reset the flag that indicates if the current OpenGL context
@@ -931,12 +1330,58 @@ impl Window {
         self.internal_gl_ctx.as_deref_mut().unwrap()
     }
 
+    /// Get the texture co-ordinate transform for drawing the splash image.
+    fn splash_matrix(&self) -> Matrix<2> {
+        // OpenGL ES expects bottom-to-top row order for image data, but our
+        // image data will be top-to-bottom. A reflection transform compensates.
+        // Landscape-shaped launch images are already the right way up for a
+        // landscape window, so they don't need to be rotated like a portrait
+        // image (or the app's framebuffer) would.
+        let (width, height) = self.splash_image.as_ref().unwrap().dimensions();
+        if width > height && self.device_orientation != DeviceOrientation::Portrait {
+            Matrix::y_flip()
+        } else {
+            self.rotation_matrix().multiply(&Matrix::y_flip())
+        }
+    }
+
+    /// Upload RGBA pixel data (e.g. the splash image) to a new texture and
+    /// bind it to `GL_TEXTURE_2D`. The internal context must be current.
+    unsafe fn upload_texture(gl_ctx: &mut dyn GLES, pixels: &[u8], dimensions: (u32, u32)) -> u32 {
+        use crate::gles::gles11_raw as gles11; // constants only
+
+        let mut texture = 0;
+        gl_ctx.GenTextures(1, &mut texture);
+        gl_ctx.BindTexture(gles11::TEXTURE_2D, texture);
+        let (width, height) = dimensions;
+        gl_ctx.TexImage2D(
+            gles11::TEXTURE_2D,
+            0,
+            gles11::RGBA as _,
+            width as _,
+            height as _,
+            0,
+            gles11::RGBA,
+            gles11::UNSIGNED_BYTE,
+            pixels.as_ptr() as *const _,
+        );
+        gl_ctx.TexParameteri(
+            gles11::TEXTURE_2D,
+            gles11::TEXTURE_MIN_FILTER,
+            gles11::LINEAR as _,
+        );
+        gl_ctx.TexParameteri(
+            gles11::TEXTURE_2D,
+            gles11::TEXTURE_MAG_FILTER,
+            gles11::LINEAR as _,
+        );
+        texture
+    }
+
     fn display_splash(&mut self) {
         assert!(self.splash_image.is_some());
 
-        // OpenGL ES expects bottom-to-top row order for image data, but our
-        // image data will be top-to-bottom. A reflection transform compensates.
-        let matrix = self.rotation_matrix().multiply(&Matrix::y_flip());
+        let matrix = self.splash_matrix();
         let (vx, vy, vw, vh) = self.viewport();
         let viewport = (vx, vy + self.viewport_y_offset(), vw, vh);
 
@@ -945,39 +1390,14 @@ impl Window {
         let image = self.splash_image.as_ref().unwrap();
         let gl_ctx = self.internal_gl_ctx.as_deref_mut().unwrap();
 
-        use crate::gles::gles11_raw as gles11; // constants only
-
         unsafe {
-            let mut texture = 0;
-            gl_ctx.GenTextures(1, &mut texture);
-            gl_ctx.BindTexture(gles11::TEXTURE_2D, texture);
-            let (width, height) = image.dimensions();
-            gl_ctx.TexImage2D(
-                gles11::TEXTURE_2D,
-                0,
-                gles11::RGBA as _,
-                width as _,
-                height as _,
-                0,
-                gles11::RGBA,
-                gles11::UNSIGNED_BYTE,
-                image.pixels().as_ptr() as *const _,
-            );
-            gl_ctx.TexParameteri(
-                gles11::TEXTURE_2D,
-                gles11::TEXTURE_MIN_FILTER,
-                gles11::LINEAR as _,
-            );
-            gl_ctx.TexParameteri(
-                gles11::TEXTURE_2D,
-                gles11::TEXTURE_MAG_FILTER,
-                gles11::LINEAR as _,
-            );
-
+            let texture = Self::upload_texture(gl_ctx, image.pixels(), image.dimensions());
             present_frame(
-                gl_ctx, viewport, matrix, /* virtual_cursor_visible_at: */ None,
+                gl_ctx,
+                viewport,
+                matrix,
+                /* virtual_cursors_visible_at: */ &[],
             );
-
             gl_ctx.DeleteTextures(1, &texture);
         };
 
@@ -987,9 +1407,148 @@ impl Window {
         // onto image so we can rotate later if necessary
     }
 
+    /// Fill the window with black, e.g. to simulate the screen being turned
+    /// off.
+    pub fn present_blank_frame(&mut self) {
+        self.make_internal_gl_ctx_current();
+        let gl_ctx = self.internal_gl_ctx.as_deref_mut().unwrap();
+
+        use crate::gles::gles11_raw as gles11; // constants only
+
+        unsafe {
+            gl_ctx.ClearColor(0.0, 0.0, 0.0, 1.0);
+            gl_ctx.Clear(gles11::COLOR_BUFFER_BIT);
+        }
+
+        self.window.gl_swap_window();
+    }
+
+    /// If the screen is being recorded or a screenshot was requested, read
+    /// back the frame that is about to be presented and add it to the
+    /// recording or save it. This should be called just before
+    /// [Self::swap_window], while the default framebuffer is bound.
+    ///
+    /// `gles` must be the current context, or [None] if the internal context
+    /// is current.
+    pub fn record_frame(&mut self, gles: Option<&mut dyn GLES>) {
+        if self.screen_recorder.is_none() && !self.screenshot_requested {
+            return;
+        }
+        let (vx, vy, vw, vh) = self.viewport();
+        let viewport = (vx, vy + self.viewport_y_offset(), vw, vh);
+        let gles = match gles {
+            Some(gles) => gles,
+            None => self.internal_gl_ctx.as_deref_mut().unwrap(),
+        };
+        if std::mem::take(&mut self.screenshot_requested) {
+            match unsafe { screenshot::take_screenshot(gles, viewport, &self.screenshot_dir) } {
+                Ok(path) => echo!("Saved screenshot to {}.", path.display()),
+                Err(e) => log!("Warning: {}, screenshot not saved.", e),
+            }
+        }
+        if let Some(recorder) = self.screen_recorder.as_mut() {
+            unsafe { recorder.capture(gles, viewport) }
+        }
+    }
+
+    /// Returns [true] if the splash screen is still visible, i.e. the app has
+    /// not presented a frame yet or the splash screen is still fading out.
+    /// While this is the case, all frames must be composited, so that
+    /// [Self::draw_splash_fade] can draw on top of them.
+    pub fn is_splash_visible(&self) -> bool {
+        self.splash_image.is_some()
+    }
+
+    /// If the splash screen is still visible, draw it on top of the frame that
+    /// is about to be presented, with an opacity that decreases over time after
+    /// the first such frame. This should be called just before
+    /// [Self::record_frame], while the default framebuffer is bound and the
+    /// internal context is current.
+    pub fn draw_splash_fade(&mut self) {
+        const SPLASH_FADE_DURATION: Duration = Duration::from_millis(300);
+
+        if self.splash_image.is_none() {
+            return;
+        }
+        let fade_started = *self.splash_fade_started.get_or_insert_with(Instant::now);
+        let progress = fade_started.elapsed().as_secs_f32() / SPLASH_FADE_DURATION.as_secs_f32();
+        if progress >= 1.0 {
+            log_dbg!("Splash screen fade finished");
+            self.splash_image = None;
+            return;
+        }
+
+        let matrix = self.splash_matrix();
+        let (vx, vy, vw, vh) = self.viewport();
+        let viewport = (vx, vy + self.viewport_y_offset(), vw, vh);
+
+        let image = self.splash_image.as_ref().unwrap();
+        let gl_ctx = self.internal_gl_ctx.as_deref_mut().unwrap();
+
+        unsafe {
+            let texture = Self::upload_texture(gl_ctx, image.pixels(), image.dimensions());
+            present_overlay(gl_ctx, viewport, matrix, 1.0 - progress);
+            gl_ctx.DeleteTextures(1, &texture);
+        }
+    }
+
+    /// Add output from the app to the console overlay (see [console]).
+    pub fn console_write(&mut self, text: &str) {
+        self.console.write(text);
+    }
+
+    /// Returns [true] if the console overlay is open. While this is the case,
+    /// all frames must be composited, so that [Self::draw_console] can draw on
+    /// top of them.
+    pub fn is_console_visible(&self) -> bool {
+        self.console.is_visible()
+    }
+
+    /// Open the console overlay if it isn't already open.
+    pub fn show_console(&mut self) {
+        if !self.console.is_visible() {
+            self.console.toggle();
+        }
+    }
+
+    /// If the console overlay is open, draw it on top of the frame that is
+    /// about to be presented. This should be called after [Self::record_frame],
+    /// so that the console doesn't end up in screenshots and recordings, while
+    /// the default framebuffer is bound and the internal context is current.
+    pub fn draw_console(&mut self) {
+        if !self.console.is_visible() {
+            return;
+        }
+
+        let (vx, vy, vw, vh) = self.viewport();
+        let viewport = (vx, vy + self.viewport_y_offset(), vw, vh);
+
+        let pixels = self.console.render((vw, vh));
+        let gl_ctx = self.internal_gl_ctx.as_deref_mut().unwrap();
+
+        unsafe {
+            let texture = Self::upload_texture(gl_ctx, pixels, (vw, vh));
+            // The viewport is already the right way up for the window, so the
+            // only transform needed is for the top-to-bottom row order.
+            present_overlay(gl_ctx, viewport, Matrix::y_flip(), 1.0);
+            gl_ctx.DeleteTextures(1, &texture);
+        }
+    }
+
     /// Swap front-buffer and back-buffer so the result of OpenGL rendering is
     /// presented.
-    pub fn swap_window(&self) {
+    ///
+    /// The time until the app's first frame is presented is logged as the
+    /// "boot time". If an app never gets this far, it has probably hung or
+    /// crashed during startup.
+    pub fn swap_window(&mut self) {
+        if !self.first_frame_presented {
+            self.first_frame_presented = true;
+            log!(
+                "First frame presented {:.2}s after launch (boot time).",
+                self.created_at.elapsed().as_secs_f32()
+            );
+        }
         self.window.gl_swap_window();
     }
 
@@ -1139,6 +1698,18 @@ impl Window {
             false => self.video_ctx.disable_screen_saver(),
         }
     }
+
+    /// Show some status text after the app's name in the window title, in
+    /// place of what the home screen or status bar would show on a device.
+    /// An empty string removes the status.
+    pub fn set_title_status(&mut self, status: &str) {
+        let title = if status.is_empty() {
+            self.base_title.clone()
+        } else {
+            format!("{} {}", self.base_title, status)
+        };
+        self.window.set_title(&title).unwrap();
+    }
 }
 
 pub fn open_url(url: &str) -> Result<(), String> {
@@ -0,0 +1,188 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! On-screen console overlay showing the app's own log output.
+//!
+//! Everything the app writes to its standard output and error streams, and
+//! everything it logs with `NSLog()`, is kept here as well as being written to
+//! the host's standard output. This means the logs can be seen on platforms
+//! where the terminal is hard to get to, like Android. The overlay is toggled
+//! with a hotkey and drawn on top of the app's frames, see
+//! [super::Window::draw_console].
+
+use crate::font::{Font, TextAlignment, WrapMode};
+use std::collections::VecDeque;
+
+/// Number of complete lines kept. Older lines are discarded.
+const MAX_LINES: usize = 1000;
+
+/// Number of lines of text that fit on the screen, which determines the font
+/// size.
+const LINES_PER_SCREEN: f32 = 36.0;
+
+/// Margin around the text, in pixels.
+const MARGIN: f32 = 4.0;
+
+/// Opacity of the black background behind the text.
+const BACKGROUND_OPACITY: f32 = 0.75;
+
+/// Number of lines scrolled by the Page Up and Page Down keys.
+pub const SCROLL_PAGE_LINES: isize = 10;
+/// Number of lines scrolled by each step of the mouse wheel.
+pub const SCROLL_WHEEL_LINES: isize = 3;
+
+#[derive(Default)]
+pub struct Console {
+    lines: VecDeque<String>,
+    /// Text written since the last newline.
+    partial_line: String,
+    /// How many lines the view is scrolled back from the most recent one.
+    scroll: usize,
+    visible: bool,
+    font: Option<Font>,
+    /// The last rendered RGBA pixels and their dimensions, which are reused
+    /// until the console changes.
+    rendered: Option<(Vec<u8>, (u32, u32))>,
+}
+
+impl Console {
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+        // Opening the console always shows the most recent output.
+        self.scroll = 0;
+        self.rendered = None;
+    }
+
+    /// Append some output. This may contain any number of lines, including
+    /// a partial line that will be completed by a later write.
+    pub fn write(&mut self, text: &str) {
+        let mut segments = text.split('\n');
+        self.partial_line.push_str(segments.next().unwrap());
+        for segment in segments {
+            let line = std::mem::replace(&mut self.partial_line, segment.to_string());
+            self.lines.push_back(line);
+            if self.lines.len() > MAX_LINES {
+                self.lines.pop_front();
+            }
+            if self.scroll > 0 {
+                // Keep the view still if scrolled back.
+                self.scroll += 1;
+            }
+        }
+        self.scroll = self.scroll.min(self.lines.len());
+        self.rendered = None;
+    }
+
+    /// Scroll back (positive) or forward (negative) by some number of lines.
+    pub fn scroll_by(&mut self, lines: isize) {
+        let max_scroll = self.lines.len();
+        self.scroll = self.scroll.saturating_add_signed(lines).min(max_scroll);
+        self.rendered = None;
+    }
+
+    /// Get the console's contents as RGBA pixels with premultiplied alpha, in
+    /// top-to-bottom row order. The most recent lines are at the bottom.
+    pub fn render(&mut self, dimensions: (u32, u32)) -> &[u8] {
+        if self
+            .rendered
+            .as_ref()
+            .is_some_and(|&(_, rendered_dimensions)| rendered_dimensions == dimensions)
+        {
+            return &self.rendered.as_ref().unwrap().0;
+        }
+
+        let (width, height) = dimensions;
+        let background = [0, 0, 0, (BACKGROUND_OPACITY * 255.0) as u8];
+        let mut pixels = background.repeat(width as usize * height as usize);
+
+        let font = self.font.get_or_insert_with(Font::sans_regular);
+        let font_size = (height as f32 / LINES_PER_SCREEN).round().max(8.0);
+        let wrap = Some((width as f32 - MARGIN * 2.0, WrapMode::Char));
+
+        let partial_line = Some(&self.partial_line).filter(|line| !line.is_empty());
+        let mut y = height as f32 - MARGIN;
+        for line in self
+            .lines
+            .iter()
+            .chain(partial_line)
+            .rev()
+            .skip(self.scroll)
+        {
+            let (_, line_height) = font.calculate_text_size(font_size, None, line, wrap);
+            y -= line_height;
+            if y < MARGIN {
+                break;
+            }
+            font.draw(
+                font_size,
+                None,
+                line,
+                (MARGIN, y),
+                wrap,
+                TextAlignment::Left,
+                |glyph| {
+                    let (origin_x, origin_y) = glyph.origin();
+                    let (glyph_width, glyph_height) = glyph.dimensions();
+                    for glyph_y in 0..glyph_height {
+                        for glyph_x in 0..glyph_width {
+                            let pixel_x = origin_x as i32 + glyph_x;
+                            let pixel_y = origin_y as i32 + glyph_y;
+                            if !(0..width as i32).contains(&pixel_x)
+                                || !(0..height as i32).contains(&pixel_y)
+                            {
+                                continue;
+                            }
+                            let coverage = glyph.pixel_at((glyph_x, glyph_y));
+                            let idx = (pixel_y as usize * width as usize + pixel_x as usize) * 4;
+                            // White text over the background.
+                            let pixel = &mut pixels[idx..idx + 4];
+                            for channel in &mut pixel[..3] {
+                                let value = f32::from(*channel) * (1.0 - coverage);
+                                *channel = (value + coverage * 255.0).round() as u8;
+                            }
+                            let alpha = f32::from(pixel[3]) * (1.0 - coverage);
+                            pixel[3] = (alpha + coverage * 255.0).round() as u8;
+                        }
+                    }
+                },
+            );
+        }
+
+        &self.rendered.insert((pixels, dimensions)).0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_splits_lines() {
+        let mut console = Console::default();
+        console.write("first line\nsecond ");
+        console.write("line\n\nthird");
+        assert_eq!(console.lines, ["first line", "second line", ""]);
+        assert_eq!(console.partial_line, "third");
+    }
+
+    #[test]
+    fn scroll_is_kept_when_scrolled_back() {
+        let mut console = Console::default();
+        console.write("a\nb\nc\n");
+        console.scroll_by(1);
+        console.write("d\n");
+        assert_eq!(console.scroll, 2);
+        console.scroll_by(-5);
+        assert_eq!(console.scroll, 0);
+        console.write("e\n");
+        assert_eq!(console.scroll, 0);
+        console.scroll_by(100);
+        assert_eq!(console.scroll, console.lines.len());
+    }
+}
@@ -0,0 +1,402 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Input recording and replay (see `--record-input=` and `--replay-input=` in
+//! `OPTIONS_HELP.txt`).
+//!
+//! The file format is documented in `dev-docs/input-recording.md`. If you
+//! change it, update that document and bump [FORMAT_VERSION].
+
+use super::{Event, FingerId, TextInputEvent};
+use crate::options::Button;
+use std::collections::{HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+const MAGIC: &str = "touchHLE-input-recording";
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Information about the circumstances a recording was made in. A recording
+/// can be replayed in different circumstances, but it might not work.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub app_id: String,
+    pub app_version: String,
+    pub emulator_version: String,
+    /// See [device_profile].
+    pub device: String,
+}
+
+pub struct Recording {
+    pub header: Header,
+    /// Events and the ticks (see [super::Window::poll_for_events]) they
+    /// happened on, in order.
+    pub events: VecDeque<(u64, Event)>,
+}
+
+/// Description of the emulated device that affects how input is interpreted.
+pub fn device_profile(initial_orientation: super::DeviceOrientation) -> String {
+    let orientation = match initial_orientation {
+        super::DeviceOrientation::Portrait => "portrait",
+        super::DeviceOrientation::LandscapeLeft => "landscape-left",
+        super::DeviceOrientation::LandscapeRight => "landscape-right",
+    };
+    format!("iPhone {}", orientation)
+}
+
+/// Whether an event is user input that belongs in a recording. Other events,
+/// e.g. the user quitting touchHLE, are never recorded or replaced by replay.
+pub fn is_recordable(event: &Event) -> bool {
+    match event {
+        Event::TouchesDown(_)
+        | Event::TouchesMove(_)
+        | Event::TouchesUp(_)
+        | Event::TextInput(_)
+        | Event::HomeButton
+        | Event::LockButton => true,
+        Event::Quit
+        | Event::AppWillResignActive
+        | Event::AppWillTerminate
+        | Event::EnterDebugger
+        | Event::MemoryReport
+        | Event::HeapSnapshot => false,
+    }
+}
+
+fn format_finger(finger: FingerId) -> String {
+    match finger {
+        FingerId::Mouse => "mouse".to_string(),
+        // Player 1's cursor has the same name as when there was only one.
+        FingerId::VirtualCursor(0) => "cursor".to_string(),
+        FingerId::VirtualCursor(player) => format!("cursor:{}", player),
+        FingerId::Touch(id) => format!("touch:{}", id),
+        FingerId::ButtonToTouch(button) => format!("button:{:?}", button),
+    }
+}
+
+fn parse_finger(word: &str) -> Result<FingerId, String> {
+    match word {
+        "mouse" => Ok(FingerId::Mouse),
+        "cursor" => Ok(FingerId::VirtualCursor(0)),
+        _ => {
+            if let Some(player) = word.strip_prefix("cursor:") {
+                player
+                    .parse()
+                    .map(FingerId::VirtualCursor)
+                    .map_err(|_| format!("Invalid cursor player {:?}", player))
+            } else if let Some(id) = word.strip_prefix("touch:") {
+                id.parse()
+                    .map(FingerId::Touch)
+                    .map_err(|_| format!("Invalid touch ID {:?}", id))
+            } else if let Some(name) = word.strip_prefix("button:") {
+                Button::from_name(name)
+                    .map(FingerId::ButtonToTouch)
+                    .ok_or_else(|| format!("Invalid button {:?}", name))
+            } else {
+                Err(format!("Invalid finger {:?}", word))
+            }
+        }
+    }
+}
+
+/// Format an event as a line of a recording (without the tick), or return
+/// [None] if it isn't recordable.
+fn format_event(event: &Event) -> Option<String> {
+    let (name, touches) = match event {
+        Event::TouchesDown(touches) => ("down", touches),
+        Event::TouchesMove(touches) => ("move", touches),
+        Event::TouchesUp(touches) => ("up", touches),
+        Event::TextInput(TextInputEvent::Text(text)) => {
+            let mut line = "text ".to_string();
+            for byte in text.bytes() {
+                write!(line, "{:02x}", byte).unwrap();
+            }
+            return Some(line);
+        }
+        Event::TextInput(TextInputEvent::Backspace) => return Some("backspace".to_string()),
+        Event::TextInput(TextInputEvent::Return) => return Some("return".to_string()),
+        Event::HomeButton => return Some("home".to_string()),
+        Event::LockButton => return Some("lock".to_string()),
+        _ => return None,
+    };
+    // Sort so that the output doesn't depend on HashMap iteration order.
+    let mut touches: Vec<(String, (f32, f32))> = touches
+        .iter()
+        .map(|(&finger, &coords)| (format_finger(finger), coords))
+        .collect();
+    touches.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut line = name.to_string();
+    for (finger, (x, y)) in touches {
+        write!(line, " {} {} {}", finger, x, y).unwrap();
+    }
+    Some(line)
+}
+
+fn parse_event(name: &str, args: &[&str]) -> Result<Event, String> {
+    let no_args = |event: Event| {
+        if args.is_empty() {
+            Ok(event)
+        } else {
+            Err(format!("Unexpected arguments for {:?}", name))
+        }
+    };
+    match name {
+        "down" | "move" | "up" => {
+            if args.is_empty() || args.len() % 3 != 0 {
+                return Err(format!("{:?} needs a finger, X and Y for each touch", name));
+            }
+            let mut touches = HashMap::new();
+            for touch in args.chunks_exact(3) {
+                let finger = parse_finger(touch[0])?;
+                let x: f32 = touch[1]
+                    .parse()
+                    .map_err(|_| format!("Invalid X coordinate {:?}", touch[1]))?;
+                let y: f32 = touch[2]
+                    .parse()
+                    .map_err(|_| format!("Invalid Y coordinate {:?}", touch[2]))?;
+                touches.insert(finger, (x, y));
+            }
+            Ok(match name {
+                "down" => Event::TouchesDown(touches),
+                "move" => Event::TouchesMove(touches),
+                _ => Event::TouchesUp(touches),
+            })
+        }
+        "text" => {
+            let &[hex] = args else {
+                return Err("\"text\" needs exactly one argument".to_string());
+            };
+            if hex.len() % 2 != 0 {
+                return Err(format!("Invalid hexadecimal text {:?}", hex));
+            }
+            let bytes = (0..hex.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(hex.get(i..i + 2).unwrap_or("?"), 16))
+                .collect::<Result<Vec<u8>, _>>()
+                .map_err(|_| format!("Invalid hexadecimal text {:?}", hex))?;
+            let text = String::from_utf8(bytes).map_err(|_| "Text is not UTF-8".to_string())?;
+            Ok(Event::TextInput(TextInputEvent::Text(text)))
+        }
+        "backspace" => no_args(Event::TextInput(TextInputEvent::Backspace)),
+        "return" => no_args(Event::TextInput(TextInputEvent::Return)),
+        "home" => no_args(Event::HomeButton),
+        "lock" => no_args(Event::LockButton),
+        _ => Err(format!("Unknown event {:?}", name)),
+    }
+}
+
+/// Parse a recording, checking that it is well-formed.
+pub fn parse(text: &str) -> Result<Recording, String> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+    let mut next_header_line = |key: &str| -> Result<String, String> {
+        let Some((line_number, line)) = lines.next() else {
+            return Err(format!("Missing {:?} line", key));
+        };
+        match line.split_once(' ') {
+            Some((found_key, value)) if found_key == key => Ok(value.trim().to_string()),
+            _ => Err(format!("Line {}: expected {:?} line", line_number, key)),
+        }
+    };
+
+    let version =
+        next_header_line(MAGIC).map_err(|_| "Not a touchHLE input recording".to_string())?;
+    if version != FORMAT_VERSION.to_string() {
+        return Err(format!(
+            "Unsupported input recording format version {} (expected {})",
+            version, FORMAT_VERSION
+        ));
+    }
+    let app = next_header_line("app")?;
+    let Some((app_id, app_version)) = app.split_once(' ') else {
+        return Err("The \"app\" line needs an ID and a version".to_string());
+    };
+    let header = Header {
+        app_id: app_id.to_string(),
+        app_version: app_version.to_string(),
+        emulator_version: next_header_line("emulator")?,
+        device: next_header_line("device")?,
+    };
+
+    let mut events = VecDeque::new();
+    let mut last_tick = 0;
+    for (line_number, line) in lines {
+        let words: Vec<&str> = line.split_ascii_whitespace().collect();
+        let (tick, name, args) = match &words[..] {
+            [tick, name, args @ ..] => (tick, name, args),
+            _ => {
+                return Err(format!(
+                    "Line {}: expected a tick and an event",
+                    line_number
+                ))
+            }
+        };
+        let tick: u64 = tick
+            .parse()
+            .map_err(|_| format!("Line {}: invalid tick {:?}", line_number, tick))?;
+        if tick < last_tick {
+            return Err(format!("Line {}: ticks must not decrease", line_number));
+        }
+        last_tick = tick;
+        let event = parse_event(name, args).map_err(|e| format!("Line {}: {}", line_number, e))?;
+        events.push_back((tick, event));
+    }
+
+    Ok(Recording { header, events })
+}
+
+pub struct InputRecorder {
+    /// This is deliberately unbuffered: touchHLE may exit at any time without
+    /// running destructors, and input events aren't very frequent.
+    file: File,
+}
+impl InputRecorder {
+    pub fn new(path: &Path, header: &Header) -> Result<InputRecorder, String> {
+        let mut file =
+            File::create(path).map_err(|e| format!("Couldn't create {}: {}", path.display(), e))?;
+        write!(
+            file,
+            "{} {}\napp {} {}\nemulator {}\ndevice {}\n",
+            MAGIC,
+            FORMAT_VERSION,
+            header.app_id,
+            header.app_version,
+            header.emulator_version,
+            header.device
+        )
+        .map_err(|e| format!("Couldn't write to {}: {}", path.display(), e))?;
+        echo!("Recording input to {}.", path.display());
+        Ok(InputRecorder { file })
+    }
+
+    pub fn record(&mut self, tick: u64, event: &Event) {
+        let Some(line) = format_event(event) else {
+            return;
+        };
+        if let Err(e) = writeln!(self.file, "{} {}", tick, line) {
+            log!("Warning: Couldn't write to input recording: {}", e);
+        }
+    }
+}
+
+pub struct InputPlayer {
+    events: VecDeque<(u64, Event)>,
+}
+impl InputPlayer {
+    /// Load a recording to be replayed. Warnings are logged if it was made in
+    /// different circumstances to `expected_header`.
+    pub fn new(path: &Path, expected_header: &Header) -> Result<InputPlayer, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+        let Recording { header, events } = parse(&text)
+            .map_err(|e| format!("Invalid input recording {}: {}", path.display(), e))?;
+
+        if header.app_id != expected_header.app_id {
+            log!(
+                "Warning: Input recording is for app {:?}, not {:?}.",
+                header.app_id,
+                expected_header.app_id
+            );
+        } else if header.app_version != expected_header.app_version {
+            log!(
+                "Warning: Input recording is for app version {:?}, not {:?}.",
+                header.app_version,
+                expected_header.app_version
+            );
+        }
+        if header.emulator_version != expected_header.emulator_version {
+            log!(
+                "Warning: Input recording was made with touchHLE {}, not {}. It might not replay correctly.",
+                header.emulator_version,
+                expected_header.emulator_version
+            );
+        }
+        if header.device != expected_header.device {
+            log!(
+                "Warning: Input recording was made with device {:?}, not {:?}.",
+                header.device,
+                expected_header.device
+            );
+        }
+
+        echo!(
+            "Replaying {} input events from {}. Live input is ignored until replay finishes.",
+            events.len(),
+            path.display()
+        );
+        Ok(InputPlayer { events })
+    }
+
+    /// Take the next event, if it is due at or before `tick`.
+    pub fn pop_due(&mut self, tick: u64) -> Option<Event> {
+        match self.events.front() {
+            Some(&(event_tick, _)) if event_tick <= tick => {
+                self.events.pop_front().map(|(_, event)| event)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let events = [
+            Event::TouchesDown(HashMap::from([
+                (FingerId::Touch(3), (10.5, 20.0)),
+                (FingerId::ButtonToTouch(Button::A), (1.0, 2.0)),
+                (FingerId::VirtualCursor(1), (3.0, 4.0)),
+            ])),
+            Event::TouchesUp(HashMap::from([(FingerId::Mouse, (0.0, 479.0))])),
+            Event::TextInput(TextInputEvent::Text("Hé llo".to_string())),
+            Event::TextInput(TextInputEvent::Backspace),
+            Event::HomeButton,
+        ];
+        let mut text = format!(
+            "{} {}\n# comment\napp com.example.app 1.0\nemulator v0.0.0\ndevice iPhone portrait\n",
+            MAGIC, FORMAT_VERSION
+        );
+        for (tick, event) in events.iter().enumerate() {
+            writeln!(text, "{} {}", tick * 2, format_event(event).unwrap()).unwrap();
+        }
+
+        let recording = parse(&text).unwrap();
+        assert_eq!(recording.header.app_id, "com.example.app");
+        assert_eq!(recording.header.device, "iPhone portrait");
+        assert_eq!(recording.events.len(), events.len());
+        for ((tick, parsed), (i, original)) in
+            recording.events.iter().zip(events.iter().enumerate())
+        {
+            assert_eq!(*tick, i as u64 * 2);
+            assert_eq!(format_event(parsed), format_event(original));
+        }
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        let header = format!(
+            "{} {}\napp a 1\nemulator v\ndevice iPhone portrait\n",
+            MAGIC, FORMAT_VERSION
+        );
+        assert!(parse("hello").is_err());
+        assert!(parse(&format!("{}5 down touch:1 1.0\n", header)).is_err());
+        assert!(parse(&format!("{}5 home\n3 lock\n", header)).is_err());
+        assert!(parse(&format!("{}5 text 4\n", header)).is_err());
+        assert!(parse(&format!("{}5 home\n5 lock\n", header)).is_ok());
+    }
+}
@@ -0,0 +1,98 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Screen recording (see `--record-screen=` in `OPTIONS_HELP.txt`).
+//!
+//! Frames are read back from the window's framebuffer just before they are
+//! presented, and written as raw RGBA video, which avoids needing a video
+//! encoder. This happens the same way for apps that present with OpenGL ES
+//! and for apps that only use UIKit (Core Animation composition), see
+//! [super::Window::record_frame].
+
+use crate::gles::present::read_back_frame;
+use crate::gles::GLES;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+pub struct ScreenRecorder {
+    path: PathBuf,
+    framerate: f64,
+    /// The file for the current segment and the frame size used in it.
+    /// Raw video can't change size, so a new segment (file) is started when
+    /// the frame size changes, e.g. because the device was rotated.
+    segment: Option<(File, (u32, u32))>,
+    segment_count: u32,
+    /// Set if creating a file failed, so that it isn't retried every frame.
+    stopped: bool,
+    /// Buffer reused between frames.
+    pixels: Vec<u8>,
+}
+
+impl ScreenRecorder {
+    pub fn new(path: PathBuf, framerate: f64) -> ScreenRecorder {
+        ScreenRecorder {
+            path,
+            framerate,
+            segment: None,
+            segment_count: 0,
+            stopped: false,
+            pixels: Vec::new(),
+        }
+    }
+
+    fn start_segment(&mut self, size: (u32, u32)) -> Result<(), String> {
+        self.segment_count += 1;
+        let path = if self.segment_count == 1 {
+            self.path.clone()
+        } else {
+            let mut file_name = self.path.file_stem().unwrap_or_default().to_owned();
+            file_name.push(format!("-{}", self.segment_count));
+            if let Some(extension) = self.path.extension() {
+                file_name.push(".");
+                file_name.push(extension);
+            }
+            self.path.with_file_name(file_name)
+        };
+        let file = File::create(&path)
+            .map_err(|e| format!("Couldn't create {}: {}", path.display(), e))?;
+        echo!(
+            "Recording screen to {}. To convert it to a video file, you can use: \
+             ffmpeg -f rawvideo -pixel_format rgba -video_size {}x{} -framerate {} -i {} out.mp4",
+            path.display(),
+            size.0,
+            size.1,
+            self.framerate,
+            path.display()
+        );
+        self.segment = Some((file, size));
+        Ok(())
+    }
+
+    /// Read back a region of the default framebuffer with the provided context,
+    /// which must be current, and append it to the recording.
+    pub unsafe fn capture(&mut self, gles: &mut dyn GLES, viewport: (u32, u32, u32, u32)) {
+        let (_, _, width, height) = viewport;
+        if self.stopped || width == 0 || height == 0 {
+            return;
+        }
+        if self.segment.as_ref().map(|&(_, size)| size) != Some((width, height)) {
+            if let Err(e) = self.start_segment((width, height)) {
+                log!("Warning: {}, screen recording stopped.", e);
+                self.segment = None;
+                self.stopped = true;
+                return;
+            }
+        }
+
+        read_back_frame(gles, viewport, &mut self.pixels);
+        // The file is deliberately unbuffered: touchHLE may exit at any time
+        // without running destructors.
+        let (file, _) = self.segment.as_mut().unwrap();
+        if let Err(e) = file.write_all(&self.pixels) {
+            log!("Warning: Couldn't write to screen recording: {}", e);
+        }
+    }
+}
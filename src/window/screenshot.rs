@@ -0,0 +1,39 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Screenshots (see `--screenshot-key=` in `OPTIONS_HELP.txt`).
+//!
+//! Like screen recording, this reads back the next frame just before it is
+//! presented, see [super::Window::record_frame].
+
+use crate::gles::present::read_back_frame;
+use crate::gles::GLES;
+use crate::image::png_writer::encode_rgba_png;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Read back a region of the default framebuffer with the provided context,
+/// which must be current, and save it as a PNG file in `dir`. Returns the path
+/// of the new file.
+pub unsafe fn take_screenshot(
+    gles: &mut dyn GLES,
+    viewport: (u32, u32, u32, u32),
+    dir: &Path,
+) -> Result<PathBuf, String> {
+    let (_, _, width, height) = viewport;
+    let mut pixels = Vec::new();
+    read_back_frame(gles, viewport, &mut pixels);
+    let png = encode_rgba_png(width, height, &pixels);
+
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Couldn't create {}: {}", dir.display(), e))?;
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = dir.join(format!("touchHLE-{}.png", timestamp));
+    std::fs::write(&path, png).map_err(|e| format!("Couldn't write {}: {}", path.display(), e))?;
+    Ok(path)
+}